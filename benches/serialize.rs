@@ -0,0 +1,169 @@
+//! Event serialization, conversion and deserialization throughput, compared
+//! with `tracing-serde` where it supports the same operation. `tracing-serde`
+//! cannot deserialize, so deserialization is compared with parsing the same JSON
+//! into a `serde_json::Value` instead.
+//!
+//! Run with `cargo bench`, adding `--features bumpalo` or `--features compact_str`
+//! to include (or measure the effect of) those features.
+
+use std::sync::Mutex;
+
+use criterion::{
+    black_box, criterion_group, criterion_main, measurement::WallTime, BenchmarkGroup, Criterion,
+    Throughput,
+};
+use tracing::info;
+use tracing_core::{
+    span::{Attributes, Id, Record},
+    Dispatch, Event, Metadata, Subscriber,
+};
+use tracing_serde_structured::{wire::StringTable, AsSerde, SerializeEvent};
+
+/// Runs a closure for every event, and ignores everything else.
+struct OnEvent<F>(F);
+
+impl<F: Fn(&Event<'_>) + Send + Sync + 'static> Subscriber for OnEvent<F> {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, _: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        (self.0)(event)
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+/// Emits the event every benchmark works on.
+fn emit() {
+    info!(
+        user = "ferris",
+        id = 42u64,
+        latency = 1.5,
+        request = ?("GET", 200),
+        "served {}",
+        "/index.html",
+    );
+}
+
+/// Benchmarks `f`, called with the event emitted by each iteration.
+fn bench_event<F>(group: &mut BenchmarkGroup<'_, WallTime>, name: &str, f: F)
+where
+    F: Fn(&Event<'_>) + Send + Sync + 'static,
+{
+    let dispatch = Dispatch::new(OnEvent(f));
+    tracing::dispatcher::with_default(&dispatch, || {
+        group.bench_function(name, |b| b.iter(emit));
+    });
+}
+
+/// Captures the emitted event, encoded with `encode`.
+fn capture(encode: fn(&Event<'_>) -> Vec<u8>) -> Vec<u8> {
+    let out = std::sync::Arc::new(Mutex::new(Vec::new()));
+    let sink = out.clone();
+    let dispatch = Dispatch::new(OnEvent(move |event: &Event<'_>| {
+        *sink.lock().unwrap() = encode(event);
+    }));
+    tracing::dispatcher::with_default(&dispatch, emit);
+    let bytes = std::mem::take(&mut *out.lock().unwrap());
+    bytes
+}
+
+fn serialize(c: &mut Criterion) {
+    let mut group = c.benchmark_group("serialize");
+
+    group.bench_function("dispatch only", |b| {
+        let dispatch = Dispatch::new(OnEvent(|event: &Event<'_>| {
+            black_box(event);
+        }));
+        tracing::dispatcher::with_default(&dispatch, || b.iter(emit));
+    });
+
+    let buf = Mutex::new(Vec::with_capacity(1024));
+    bench_event(&mut group, "json", move |event| {
+        let mut buf = buf.lock().unwrap();
+        buf.clear();
+        serde_json::to_writer(&mut *buf, &event.as_serde()).unwrap();
+        black_box(&*buf);
+    });
+
+    let buf = Mutex::new(Vec::with_capacity(1024));
+    bench_event(&mut group, "json (tracing-serde)", move |event| {
+        let mut buf = buf.lock().unwrap();
+        buf.clear();
+        serde_json::to_writer(&mut *buf, &tracing_serde::AsSerde::as_serde(event)).unwrap();
+        black_box(&*buf);
+    });
+
+    bench_event(&mut group, "postcard", |event| {
+        let mut buf = [0u8; 512];
+        black_box(postcard::to_slice(&event.as_serde(), &mut buf).unwrap());
+    });
+
+    let table = Mutex::new(StringTable::new());
+    bench_event(&mut group, "postcard (interned)", move |event| {
+        let mut table = table.lock().unwrap();
+        let mut buf = [0u8; 512];
+        let msg = table.event(event, |def| {
+            black_box(def);
+        });
+        black_box(postcard::to_slice(&msg, &mut buf).unwrap());
+    });
+
+    group.finish();
+}
+
+fn to_owned(c: &mut Criterion) {
+    let mut group = c.benchmark_group("to_owned");
+
+    bench_event(&mut group, "to_owned", |event| {
+        black_box(event.as_serde().to_owned());
+    });
+
+    #[cfg(feature = "bumpalo")]
+    {
+        let bump = Mutex::new(bumpalo::Bump::new());
+        bench_event(&mut group, "to_owned_in", move |event| {
+            let mut bump = bump.lock().unwrap();
+            bump.reset();
+            black_box(event.as_serde().to_owned_in(&bump));
+        });
+    }
+
+    group.finish();
+}
+
+fn deserialize(c: &mut Criterion) {
+    let mut group = c.benchmark_group("deserialize");
+
+    let json = capture(|event| serde_json::to_vec(&event.as_serde()).unwrap());
+    let json = String::from_utf8(json).unwrap();
+    group.throughput(Throughput::Bytes(json.len() as u64));
+    group.bench_function("json", |b| {
+        b.iter(|| black_box(serde_json::from_str::<SerializeEvent<'_>>(&json).unwrap()))
+    });
+    group.bench_function("json (serde_json::Value)", |b| {
+        b.iter(|| black_box(serde_json::from_str::<serde_json::Value>(&json).unwrap()))
+    });
+
+    let bytes = capture(|event| postcard::to_allocvec(&event.as_serde()).unwrap());
+    group.throughput(Throughput::Bytes(bytes.len() as u64));
+    group.bench_function("postcard", |b| {
+        b.iter(|| black_box(postcard::from_bytes::<SerializeEvent<'_>>(&bytes).unwrap()))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, serialize, to_owned, deserialize);
+criterion_main!(benches);
@@ -1,3 +1,45 @@
+use std::{env, fs, path::Path};
+
+/// The default capacity of the `no_std` field collections.
+const DEFAULT_MAX_FIELDS: usize = 32;
+
+/// The default size of each serialization scratch buffer, in bytes.
+const DEFAULT_SCRATCH_SIZE: usize = 512;
+
 fn main() {
     println!("cargo::rustc-check-cfg=cfg(tracing_unstable)");
+    println!("cargo::rerun-if-changed=build.rs");
+    println!("cargo::rerun-if-env-changed=TRACING_SERDE_STRUCTURED_MAX_FIELDS");
+    println!("cargo::rerun-if-env-changed=TRACING_SERDE_STRUCTURED_SCRATCH_SIZE");
+
+    // `FnvIndexMap` needs a power of two; the sorted map takes any capacity.
+    let sorted_map = env::var_os("CARGO_FEATURE_SORTED_MAP").is_some();
+    let max_fields = match env::var("TRACING_SERDE_STRUCTURED_MAX_FIELDS") {
+        Ok(value) => match value.trim().parse::<usize>() {
+            Ok(n) if sorted_map && n >= 1 => n,
+            Ok(n) if n >= 2 && n.is_power_of_two() => n,
+            _ if sorted_map => panic!(
+                "TRACING_SERDE_STRUCTURED_MAX_FIELDS must be at least 1 (got {value:?})"
+            ),
+            _ => panic!(
+                "TRACING_SERDE_STRUCTURED_MAX_FIELDS must be a power of two, and at least 2 (got {value:?})"
+            ),
+        },
+        Err(_) => DEFAULT_MAX_FIELDS,
+    };
+
+    let scratch_size = match env::var("TRACING_SERDE_STRUCTURED_SCRATCH_SIZE") {
+        Ok(value) => match value.trim().parse::<usize>() {
+            Ok(n) if n >= 1 => n,
+            _ => panic!(
+                "TRACING_SERDE_STRUCTURED_SCRATCH_SIZE must be a number of bytes, and at least 1 (got {value:?})"
+            ),
+        },
+        Err(_) => DEFAULT_SCRATCH_SIZE,
+    };
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let out = Path::new(&out_dir);
+    fs::write(out.join("max_fields"), max_fields.to_string()).unwrap();
+    fs::write(out.join("scratch_size"), scratch_size.to_string()).unwrap();
 }
@@ -0,0 +1,17 @@
+//! Arbitrary bytes read as a stream of COBS framed messages, the way a host
+//! reads what a device sends it, must only ever produce errors for the
+//! frames that are malformed.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tracing_serde_structured::{
+    host::{Decoder, Merger},
+    time::TickRate,
+};
+
+fuzz_target!(|data: &[u8]| {
+    let rate = TickRate::hz(32_768);
+    Decoder::new(rate).read(data).for_each(drop);
+    Decoder::mid_stream(rate).read(data).for_each(drop);
+    Merger::new(rate).read(data).for_each(drop);
+});
@@ -0,0 +1,18 @@
+//! Arbitrary bytes decoded as JSON messages must fail cleanly, and whatever
+//! does decode must encode again, unless it is of a kind this version does
+//! not know.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tracing_serde_structured::{wire::TracingWire, SerializeEvent};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(event) = serde_json::from_slice::<SerializeEvent<'_>>(data) {
+        serde_json::to_vec(&event).unwrap();
+    }
+    if let Ok(msg) = serde_json::from_slice::<TracingWire<'_>>(data) {
+        if !matches!(msg, TracingWire::Unknown) {
+            serde_json::to_vec(&msg).unwrap();
+        }
+    }
+});
@@ -0,0 +1,26 @@
+//! Arbitrary bytes decoded as postcard messages must fail cleanly, and
+//! whatever does decode must encode again, unless it is of a kind this
+//! version does not know.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tracing_serde_structured::{
+    wire::{delta::DeltaFrame, TracingWire},
+    SerializeEvent,
+};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(event) = postcard::from_bytes::<SerializeEvent<'_>>(data) {
+        postcard::to_allocvec(&event).unwrap();
+    }
+    if let Ok(msg) = postcard::from_bytes::<TracingWire<'_>>(data) {
+        if !matches!(msg, TracingWire::Unknown) {
+            postcard::to_allocvec(&msg).unwrap();
+        }
+    }
+    if let Ok(frame) = postcard::from_bytes::<DeltaFrame<'_>>(data) {
+        if !matches!(frame.msg, TracingWire::Unknown) {
+            postcard::to_allocvec(&frame).unwrap();
+        }
+    }
+});
@@ -0,0 +1,524 @@
+//! Statistics over decoded streams.
+//!
+//! A [`Stats`] is handed every message of a stream, with its timestamp and
+//! encoded size, and counts messages by level, target and callsite, how many
+//! bytes each callsite costs, and how many events arrive in each window of
+//! time. Its [`Summary`] lists callsites by the bytes they cost, most
+//! expensive first, to find those blowing the telemetry budget:
+//!
+//! ```rust
+//! use std::time::Duration;
+//! use tracing_serde_structured::{
+//!     analysis::Stats, builder::SerializeEventBuilder, wire::TracingWire,
+//! };
+//!
+//! let mut stats = Stats::new();
+//! for i in 0..10 {
+//!     let event = SerializeEventBuilder::new().name("poll").field("i", i as u64).build();
+//!     stats.record(Duration::from_millis(100 * i), &TracingWire::Event(event), 12);
+//! }
+//!
+//! let summary = stats.summary();
+//! assert_eq!(summary.bytes, 120);
+//! assert_eq!(summary.callsites[0].callsite.name, "poll");
+//! assert_eq!(summary.peak_events_per_window, 10);
+//! println!("{}", serde_json::to_string_pretty(&summary).unwrap());
+//! ```
+//!
+//! Messages that refer to definitions by id, rather than carrying their
+//! metadata, can only be counted in the totals, so feed a `Stats` with
+//! messages a [`host::Decoder`](crate::host::Decoder) has resolved.
+//!
+//! [`Latencies`] collects how long the spans of each callsite took, from a
+//! [`SpanTree`], into [`Histogram`]s, and reports their percentiles, to
+//! compare against the report of an earlier run:
+//!
+//! ```rust
+//! # use std::time::Duration;
+//! # use tracing_serde_structured::{
+//! #     builder::SerializeAttributesBuilder, span_tree::SpanTreeBuilder, wire::TracingWire,
+//! #     SerializeId,
+//! # };
+//! use tracing_serde_structured::analysis::Latencies;
+//!
+//! # let id = |id: u64| SerializeId { id: id.try_into().unwrap() };
+//! # let mut builder = SpanTreeBuilder::new();
+//! # for ms in 0..100 {
+//! #     let attributes = SerializeAttributesBuilder::new("query").build();
+//! #     builder.push(Duration::ZERO, &TracingWire::NewSpan { id: id(1), attributes });
+//! #     builder.push(Duration::from_millis(ms), &TracingWire::CloseSpan(id(1)));
+//! # }
+//! # let tree = builder.finish();
+//! let mut latencies = Latencies::new();
+//! latencies.add_tree(&tree);
+//!
+//! let report = latencies.report();
+//! let query = &report.callsites[0];
+//! assert_eq!(query.callsite.name, "query");
+//! assert_eq!(query.count, 100);
+//! assert!(query.p99 >= Duration::from_millis(97));
+//! ```
+
+use std::{collections::BTreeMap, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    span_tree::{SpanRef, SpanTree},
+    wire::TracingWire,
+    SerializeLevel, SerializeMetadata,
+};
+
+/// Where a callsite is, and what it is called.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Callsite {
+    pub target: String,
+    pub name: String,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+}
+
+impl Callsite {
+    fn of(metadata: &SerializeMetadata<'_>) -> Self {
+        Callsite {
+            target: metadata.target.as_str().into(),
+            name: metadata.name.as_str().into(),
+            file: metadata.file.as_ref().map(|f| f.as_str().into()),
+            line: metadata.line,
+        }
+    }
+}
+
+/// A number of messages, and their total encoded size.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Counts {
+    pub messages: u64,
+    pub bytes: u64,
+}
+
+impl Counts {
+    fn add(&mut self, bytes: usize) {
+        self.messages += 1;
+        self.bytes += bytes as u64;
+    }
+}
+
+/// The messages of one target.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TargetStats {
+    pub target: String,
+    #[serde(flatten)]
+    pub counts: Counts,
+}
+
+/// The events, or spans created, of one callsite.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CallsiteStats {
+    pub callsite: Callsite,
+    pub level: SerializeLevel,
+    #[serde(flatten)]
+    pub counts: Counts,
+}
+
+/// A snapshot of a [`Stats`], which serializes e.g. as a JSON report.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Summary {
+    /// Every message recorded.
+    pub messages: u64,
+    /// The encoded size of every message recorded.
+    pub bytes: u64,
+    /// The time from the first message recorded to the last.
+    pub duration: Duration,
+    /// The events and spans created at each level.
+    pub levels: BTreeMap<SerializeLevel, u64>,
+    /// Targets, with the most bytes first.
+    pub targets: Vec<TargetStats>,
+    /// Callsites, with the most bytes first.
+    pub callsites: Vec<CallsiteStats>,
+    /// The length of the windows events are counted in.
+    pub window: Duration,
+    /// The events in each window, from the one of the first message recorded
+    /// to the one of the last.
+    pub events_per_window: Vec<u64>,
+    /// The most events in any one window.
+    pub peak_events_per_window: u64,
+}
+
+/// Counts the messages of a stream. See the [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct Stats {
+    window: Duration,
+    total: Counts,
+    first: Option<Duration>,
+    last: Duration,
+    levels: BTreeMap<SerializeLevel, u64>,
+    targets: BTreeMap<String, Counts>,
+    callsites: BTreeMap<Callsite, (SerializeLevel, Counts)>,
+    /// Events per window, by the window's index since the start of the
+    /// stream.
+    windows: BTreeMap<u64, u64>,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Stats {
+    /// Statistics counting events per second.
+    pub fn new() -> Self {
+        Self {
+            window: Duration::from_secs(1),
+            total: Counts::default(),
+            first: None,
+            last: Duration::ZERO,
+            levels: BTreeMap::new(),
+            targets: BTreeMap::new(),
+            callsites: BTreeMap::new(),
+            windows: BTreeMap::new(),
+        }
+    }
+
+    /// Count events in windows of `window` instead of one second. Events
+    /// already counted are counted over.
+    ///
+    /// # Panics
+    ///
+    /// If `window` is zero.
+    pub fn set_window(&mut self, window: Duration) {
+        assert!(!window.is_zero(), "the window must not be empty");
+        self.window = window;
+        self.windows.clear();
+    }
+
+    /// Counts `msg`, which happened `since_start` after the stream began,
+    /// and was `bytes` long when encoded.
+    ///
+    /// Events and created spans are counted by level, target and callsite.
+    /// Other messages only count towards the totals.
+    pub fn record(&mut self, since_start: Duration, msg: &TracingWire<'_>, bytes: usize) {
+        self.total.add(bytes);
+        self.first = Some(self.first.map_or(since_start, |f| f.min(since_start)));
+        self.last = self.last.max(since_start);
+
+        let metadata = match msg {
+            TracingWire::Event(event) => {
+                let window = since_start.as_nanos() / self.window.as_nanos();
+                *self.windows.entry(window as u64).or_default() += 1;
+                &event.metadata
+            }
+            TracingWire::NewSpan { attributes, .. } => &attributes.metadata,
+            _ => return,
+        };
+        *self.levels.entry(metadata.level).or_default() += 1;
+        self.targets
+            .entry(metadata.target.as_str().into())
+            .or_default()
+            .add(bytes);
+        self.callsites
+            .entry(Callsite::of(metadata))
+            .or_insert((metadata.level, Counts::default()))
+            .1
+            .add(bytes);
+    }
+
+    /// Counts a message decoded by a [`host::Decoder`](crate::host::Decoder),
+    /// by the size of its self-contained encoding.
+    ///
+    /// That is larger than the frame it was decoded from, if the frame
+    /// referred to definitions; pass the frame's length to [`Self::record`]
+    /// instead where it is known.
+    #[cfg(feature = "host")]
+    pub fn record_message(&mut self, msg: &crate::host::Message) {
+        self.record(msg.since_start, &msg.msg, msg.msg.wire_size());
+    }
+
+    /// Forget everything counted so far.
+    pub fn clear(&mut self) {
+        *self = Self {
+            window: self.window,
+            ..Self::new()
+        };
+    }
+
+    /// The statistics so far.
+    pub fn summary(&self) -> Summary {
+        let mut targets: Vec<_> = self
+            .targets
+            .iter()
+            .map(|(target, counts)| TargetStats {
+                target: target.clone(),
+                counts: *counts,
+            })
+            .collect();
+        targets.sort_by_key(|t| core::cmp::Reverse(t.counts.bytes));
+
+        let mut callsites: Vec<_> = self
+            .callsites
+            .iter()
+            .map(|(callsite, (level, counts))| CallsiteStats {
+                callsite: callsite.clone(),
+                level: *level,
+                counts: *counts,
+            })
+            .collect();
+        callsites.sort_by_key(|c| core::cmp::Reverse(c.counts.bytes));
+
+        let events_per_window = match self.first {
+            Some(first) => {
+                let window = |t: Duration| (t.as_nanos() / self.window.as_nanos()) as u64;
+                (window(first)..=window(self.last))
+                    .map(|w| self.windows.get(&w).copied().unwrap_or(0))
+                    .collect()
+            }
+            None => Vec::new(),
+        };
+
+        Summary {
+            messages: self.total.messages,
+            bytes: self.total.bytes,
+            duration: self.first.map_or(Duration::ZERO, |f| self.last - f),
+            levels: self.levels.clone(),
+            targets,
+            callsites,
+            window: self.window,
+            peak_events_per_window: events_per_window.iter().copied().max().unwrap_or(0),
+            events_per_window,
+        }
+    }
+}
+
+/// Bits of precision of the buckets of a [`Histogram`].
+const PRECISION: u32 = 6;
+
+/// Counts durations in buckets whose width grows with the durations they
+/// hold, so that every duration is counted to within about 3%, however
+/// small or large, in little memory.
+///
+/// Durations are counted in nanoseconds: each exactly below 64ns, and in 32
+/// buckets per power of two above.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Histogram {
+    buckets: BTreeMap<u32, u64>,
+    count: u64,
+    sum: u128,
+    min: u64,
+    max: u64,
+}
+
+/// A bucket of a [`Histogram`], and the number of durations in it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bucket {
+    /// The shortest duration counted in this bucket.
+    pub low: Duration,
+    /// The longest duration counted in this bucket.
+    pub high: Duration,
+    pub count: u64,
+}
+
+fn nanos(duration: Duration) -> u64 {
+    u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX)
+}
+
+fn bucket_of(nanos: u64) -> u32 {
+    if nanos < 1 << PRECISION {
+        return nanos as u32;
+    }
+    let shift = 63 - nanos.leading_zeros() - (PRECISION - 1);
+    shift * (1 << (PRECISION - 1)) + (nanos >> shift) as u32
+}
+
+/// The shortest and longest durations in a bucket, in nanoseconds.
+fn bucket_range(bucket: u32) -> (u64, u64) {
+    if bucket < 1 << PRECISION {
+        return (u64::from(bucket), u64::from(bucket));
+    }
+    let half = 1 << (PRECISION - 1);
+    let shift = bucket / half - 1;
+    let mantissa = u64::from(bucket % half + half);
+    let low = mantissa << shift;
+    (low, low + ((1 << shift) - 1))
+}
+
+impl Histogram {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts `duration`.
+    pub fn record(&mut self, duration: Duration) {
+        let nanos = nanos(duration);
+        *self.buckets.entry(bucket_of(nanos)).or_default() += 1;
+        self.min = if self.count == 0 {
+            nanos
+        } else {
+            self.min.min(nanos)
+        };
+        self.max = self.max.max(nanos);
+        self.count += 1;
+        self.sum += u128::from(nanos);
+    }
+
+    /// Counts the durations counted by `other` as well.
+    pub fn merge(&mut self, other: &Histogram) {
+        if other.count == 0 {
+            return;
+        }
+        for (bucket, count) in &other.buckets {
+            *self.buckets.entry(*bucket).or_default() += count;
+        }
+        self.min = if self.count == 0 {
+            other.min
+        } else {
+            self.min.min(other.min)
+        };
+        self.max = self.max.max(other.max);
+        self.count += other.count;
+        self.sum += other.sum;
+    }
+
+    /// The number of durations counted.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// The shortest duration counted, exactly, or zero if there are none.
+    pub fn min(&self) -> Duration {
+        Duration::from_nanos(self.min)
+    }
+
+    /// The longest duration counted, exactly.
+    pub fn max(&self) -> Duration {
+        Duration::from_nanos(self.max)
+    }
+
+    /// The mean of the durations counted, exactly, or zero if there are none.
+    pub fn mean(&self) -> Duration {
+        match self.count {
+            0 => Duration::ZERO,
+            count => Duration::from_nanos((self.sum / u128::from(count)) as u64),
+        }
+    }
+
+    /// The duration that a `quantile` of the durations counted are at most,
+    /// e.g. `0.99` for the 99th percentile, to within the width of its
+    /// bucket. Zero if there are none.
+    pub fn quantile(&self, quantile: f64) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+        let rank = ((quantile.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, count) in &self.buckets {
+            seen += count;
+            if seen >= rank {
+                let (_, high) = bucket_range(*bucket);
+                return Duration::from_nanos(high.clamp(self.min, self.max));
+            }
+        }
+        self.max()
+    }
+
+    /// The buckets that durations were counted in, shortest first.
+    pub fn buckets(&self) -> impl Iterator<Item = Bucket> + '_ {
+        self.buckets.iter().map(|(bucket, count)| {
+            let (low, high) = bucket_range(*bucket);
+            Bucket {
+                low: Duration::from_nanos(low),
+                high: Duration::from_nanos(high),
+                count: *count,
+            }
+        })
+    }
+}
+
+/// The durations of the spans of one callsite.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CallsiteLatency {
+    pub callsite: Callsite,
+    pub level: SerializeLevel,
+    pub count: u64,
+    pub min: Duration,
+    pub mean: Duration,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+    /// The histogram's buckets, shortest first, leaving out empty ones.
+    pub buckets: Vec<Bucket>,
+}
+
+/// A snapshot of [`Latencies`], which serializes e.g. as a JSON report.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LatencyReport {
+    /// Callsites, with the longest 99th percentile first.
+    pub callsites: Vec<CallsiteLatency>,
+}
+
+/// Collects the durations of spans, from their creation to their closing,
+/// into a [`Histogram`] for each callsite. See the
+/// [module documentation](self).
+#[derive(Debug, Clone, Default)]
+pub struct Latencies {
+    callsites: BTreeMap<Callsite, (SerializeLevel, Histogram)>,
+}
+
+impl Latencies {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts a span of the callsite described by `metadata` that took
+    /// `duration`.
+    pub fn record(&mut self, metadata: &SerializeMetadata<'_>, duration: Duration) {
+        self.callsites
+            .entry(Callsite::of(metadata))
+            .or_insert_with(|| (metadata.level, Histogram::new()))
+            .1
+            .record(duration);
+    }
+
+    /// Counts `span`, if it was closed.
+    pub fn add_span(&mut self, span: SpanRef<'_>) {
+        if let Some(duration) = span.duration() {
+            self.record(span.metadata(), duration);
+        }
+    }
+
+    /// Counts every span of `tree` that was closed.
+    pub fn add_tree(&mut self, tree: &SpanTree) {
+        tree.spans().for_each(|span| self.add_span(span));
+    }
+
+    /// The histogram of the spans of `callsite`.
+    pub fn histogram(&self, callsite: &Callsite) -> Option<&Histogram> {
+        self.callsites.get(callsite).map(|(_, histogram)| histogram)
+    }
+
+    /// The durations of the spans of each callsite so far.
+    pub fn report(&self) -> LatencyReport {
+        let mut callsites: Vec<_> = self
+            .callsites
+            .iter()
+            .map(|(callsite, (level, histogram))| CallsiteLatency {
+                callsite: callsite.clone(),
+                level: *level,
+                count: histogram.count(),
+                min: histogram.min(),
+                mean: histogram.mean(),
+                p50: histogram.quantile(0.5),
+                p95: histogram.quantile(0.95),
+                p99: histogram.quantile(0.99),
+                max: histogram.max(),
+                buckets: histogram.buckets().collect(),
+            })
+            .collect();
+        callsites.sort_by_key(|c| core::cmp::Reverse(c.p99));
+        LatencyReport { callsites }
+    }
+}
@@ -0,0 +1,279 @@
+//! [`Arbitrary`] implementations for the owned forms of the message types.
+//!
+//! Every generated value is one a decoder could have produced: strings are
+//! owned copies, field sets and maps are their deserialized variants, events
+//! list the names of their fields in their metadata, and sync markers carry
+//! [`SyncMarker::MAGIC`]. Floating point values can be NaN, which does not
+//! compare equal to itself.
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+use crate::{
+    baggage::{BaggageEntry, SerializeBaggage},
+    wire::{
+        delta::{DeltaFrame, SourceFrame, SourceId, SyncMarker},
+        description::{Description, SchemaBlob},
+        handshake::Hello,
+        InternedFields, SerializeEventInterned, SerializeEventRef, SerializePanic, StringId,
+        TracingWire,
+    },
+    CowString, DebugRecord, RecordMap, SerializeAttributes, SerializeEvent, SerializeFieldSet,
+    SerializeMetadata, SerializeRecord, SerializeRecordFields, SerializeValue,
+};
+
+impl<'a> Arbitrary<'a> for CowString<'static> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(CowString::copied(<&str>::arbitrary(u)?))
+    }
+}
+
+impl<'a> Arbitrary<'a> for SerializeFieldSet<'static> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(SerializeFieldSet::De(u.arbitrary()?))
+    }
+}
+
+impl<'a> SerializeMetadata<'static> {
+    /// Metadata of a span or an event, with the given field names.
+    fn arbitrary_with(
+        u: &mut Unstructured<'a>,
+        is_span: bool,
+        fields: SerializeFieldSet<'static>,
+    ) -> Result<Self> {
+        Ok(SerializeMetadata {
+            name: u.arbitrary()?,
+            target: u.arbitrary()?,
+            level: u.arbitrary()?,
+            module_path: u.arbitrary()?,
+            file: u.arbitrary()?,
+            line: u.arbitrary()?,
+            fields,
+            is_span,
+            is_event: !is_span,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for SerializeMetadata<'static> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let is_span = u.arbitrary()?;
+        let fields = u.arbitrary()?;
+        Self::arbitrary_with(u, is_span, fields)
+    }
+}
+
+impl<'a> Arbitrary<'a> for SerializeAttributes<'static> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let fields = u.arbitrary()?;
+        Ok(SerializeAttributes {
+            metadata: SerializeMetadata::arbitrary_with(u, true, fields)?,
+            parent: u.arbitrary()?,
+            is_root: u.arbitrary()?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for DebugRecord<'static> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(DebugRecord::De(u.arbitrary()?))
+    }
+}
+
+impl<'a> Arbitrary<'a> for SerializeValue<'static> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(match u.int_in_range(0..=5)? {
+            0 => SerializeValue::Debug(u.arbitrary()?),
+            1 => SerializeValue::Str(u.arbitrary()?),
+            2 => SerializeValue::F64(u.arbitrary()?),
+            3 => SerializeValue::I64(u.arbitrary()?),
+            4 => SerializeValue::U64(u.arbitrary()?),
+            _ => SerializeValue::Bool(u.arbitrary()?),
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for SerializeRecordFields<'static> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(SerializeRecordFields::De(
+            u.arbitrary::<RecordMap<'static>>()?,
+        ))
+    }
+}
+
+impl<'a> Arbitrary<'a> for SerializeRecord<'static> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(SerializeRecord::De(u.arbitrary::<RecordMap<'static>>()?))
+    }
+}
+
+impl<'a> Arbitrary<'a> for SerializeEvent<'static> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let fields: RecordMap<'static> = u.arbitrary()?;
+        let names = SerializeFieldSet::De(fields.keys().cloned().collect());
+        Ok(SerializeEvent {
+            fields: SerializeRecordFields::De(fields),
+            metadata: SerializeMetadata::arbitrary_with(u, false, names)?,
+            parent: u.arbitrary()?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for SerializeEventRef<'static> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(SerializeEventRef {
+            fields: u.arbitrary()?,
+            metadata: u.arbitrary()?,
+            parent: u.arbitrary()?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for InternedFields<'static> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(InternedFields::De(u.arbitrary()?))
+    }
+}
+
+impl<'a> Arbitrary<'a> for SerializeEventInterned<'static> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(SerializeEventInterned {
+            fields: u.arbitrary()?,
+            metadata: u.arbitrary()?,
+            parent: u.arbitrary()?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for SerializePanic<'static> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(SerializePanic {
+            message: u.arbitrary()?,
+            file: u.arbitrary()?,
+            line: u.arbitrary()?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Hello<'static> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Hello {
+            version: u.arbitrary()?,
+            min_version: u.arbitrary()?,
+            uses: u.arbitrary()?,
+            offers: u.arbitrary()?,
+            tick_rate: u.arbitrary()?,
+            producer: u.arbitrary()?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for SchemaBlob<'static> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(SchemaBlob::Owned(u.arbitrary()?))
+    }
+}
+
+impl<'a> Arbitrary<'a> for Description<'static> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Description {
+            crate_version: u.arbitrary()?,
+            encoding: u.arbitrary()?,
+            schema: u.arbitrary()?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for BaggageEntry<'static> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(BaggageEntry {
+            key: u.arbitrary()?,
+            value: u.arbitrary()?,
+            metadata: u.arbitrary()?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for SerializeBaggage<'static> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let entries: Vec<BaggageEntry<'static>> = u.arbitrary()?;
+        Ok(entries.into_iter().collect())
+    }
+}
+
+impl<'a> Arbitrary<'a> for SyncMarker {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(SyncMarker {
+            magic: SyncMarker::MAGIC,
+            seq: u.arbitrary()?,
+            generation: u.arbitrary()?,
+            time: u.arbitrary()?,
+            span: u.arbitrary()?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for TracingWire<'static> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(match u.int_in_range(0..=19)? {
+            0 => TracingWire::DefineMetadata {
+                id: u.arbitrary()?,
+                metadata: u.arbitrary()?,
+            },
+            1 => TracingWire::NewSpan {
+                id: u.arbitrary()?,
+                attributes: u.arbitrary()?,
+            },
+            2 => TracingWire::NewSpanRef {
+                id: u.arbitrary()?,
+                attributes: u.arbitrary()?,
+            },
+            3 => TracingWire::Record {
+                span: u.arbitrary()?,
+                values: u.arbitrary()?,
+            },
+            4 => TracingWire::FollowsFrom {
+                span: u.arbitrary()?,
+                follows: u.arbitrary()?,
+            },
+            5 => TracingWire::Event(u.arbitrary()?),
+            6 => TracingWire::EventRef(u.arbitrary()?),
+            7 => TracingWire::Enter(u.arbitrary()?),
+            8 => TracingWire::Exit(u.arbitrary()?),
+            9 => TracingWire::CloseSpan(u.arbitrary()?),
+            10 => TracingWire::DefineString {
+                id: u.arbitrary::<StringId>()?,
+                value: u.arbitrary()?,
+            },
+            11 => TracingWire::EventInterned(u.arbitrary()?),
+            12 => TracingWire::Sync(u.arbitrary()?),
+            13 => TracingWire::Panic(u.arbitrary()?),
+            14 => TracingWire::TimeSync(u.arbitrary()?),
+            15 => TracingWire::PipelineStats(u.arbitrary()?),
+            16 => TracingWire::Hello(u.arbitrary()?),
+            17 => TracingWire::Dropped(u.arbitrary()?),
+            18 => TracingWire::Baggage {
+                span: u.arbitrary()?,
+                baggage: u.arbitrary()?,
+            },
+            _ => TracingWire::Description(u.arbitrary()?),
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for DeltaFrame<'static> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(DeltaFrame {
+            time: u.arbitrary()?,
+            msg: u.arbitrary()?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for SourceFrame<'static> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(SourceFrame {
+            source: u.arbitrary::<SourceId>()?,
+            frame: u.arbitrary()?,
+        })
+    }
+}
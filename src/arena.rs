@@ -0,0 +1,192 @@
+//! Conversions that copy borrowed data into a [`bumpalo::Bump`] arena.
+//!
+//! These mirror the `to_owned` conversions, but instead of producing
+//! `'static` values backed by individual heap allocations, every string is
+//! copied into the arena, and the result borrows from it. Strings from
+//! `'static` metadata (see [`CowString::Static`]) are not copied at all.
+//!
+//! The field maps are still ordinary `BTreeMap`s, so `SerializeRecordFields`
+//! and `SerializeRecord` make a few global allocations per conversion (one
+//! per map node, rather than one per string).
+
+use std::collections::BTreeMap;
+
+use bumpalo::Bump;
+use tracing_core::field::{Field, Visit};
+
+use crate::{
+    fmt, CowString, DebugRecord, Form, RecordMap, SerializeAttributes, SerializeEvent,
+    SerializeFieldSet, SerializeMetadata, SerializeRecord, SerializeRecordFields, SerializeValue,
+};
+
+impl<'a> CowString<'a> {
+    /// Copy the string into `bump`, unless it is [`CowString::Static`].
+    pub fn to_owned_in<'b>(&self, bump: &'b Bump) -> CowString<'b> {
+        match self {
+            CowString::Static(s) => CowString::Static(s),
+            _ => CowString::Borrowed(bump.alloc_str(self.as_str())),
+        }
+    }
+}
+
+impl<'a> SerializeFieldSet<'a> {
+    pub fn to_owned_in<'b>(&self, bump: &'b Bump) -> SerializeFieldSet<'b> {
+        match self {
+            SerializeFieldSet::Ser(sfs) => {
+                SerializeFieldSet::De(sfs.iter().map(|i| CowString::Static(i.name())).collect())
+            }
+            SerializeFieldSet::De(dfs) => {
+                SerializeFieldSet::De(dfs.iter().map(|s| s.to_owned_in(bump)).collect())
+            }
+        }
+    }
+}
+
+impl<'a> SerializeMetadata<'a> {
+    pub fn to_owned_in<'b>(&self, bump: &'b Bump) -> SerializeMetadata<'b> {
+        SerializeMetadata {
+            name: self.name.to_owned_in(bump),
+            target: self.target.to_owned_in(bump),
+            level: self.level,
+            module_path: self.module_path.as_ref().map(|s| s.to_owned_in(bump)),
+            file: self.file.as_ref().map(|s| s.to_owned_in(bump)),
+            line: self.line,
+            fields: self.fields.to_owned_in(bump),
+            is_span: self.is_span,
+            is_event: self.is_event,
+        }
+    }
+}
+
+impl<'a, F: Form> DebugRecord<'a, F> {
+    /// Format (or copy) the debug representation into `bump`.
+    pub fn to_owned_in<'b>(&self, bump: &'b Bump) -> DebugRecord<'b> {
+        match self {
+            DebugRecord::Ser(args) => DebugRecord::De(CowString::Borrowed(
+                bumpalo::format!(in bump, "{}", F::arguments(args)).into_bump_str(),
+            )),
+            DebugRecord::De(d) => DebugRecord::De(d.to_owned_in(bump)),
+        }
+    }
+}
+
+impl<'a> SerializeValue<'a> {
+    pub fn to_owned_in<'b>(&self, bump: &'b Bump) -> SerializeValue<'b> {
+        match self {
+            SerializeValue::Debug(dr) => SerializeValue::Debug(dr.to_owned_in(bump)),
+            SerializeValue::Str(s) => SerializeValue::Str(s.to_owned_in(bump)),
+            SerializeValue::F64(x) => SerializeValue::F64(*x),
+            SerializeValue::I64(x) => SerializeValue::I64(*x),
+            SerializeValue::U64(x) => SerializeValue::U64(*x),
+            SerializeValue::Bool(x) => SerializeValue::Bool(*x),
+            SerializeValue::Tree(t) => SerializeValue::Tree(t.to_owned()),
+            SerializeValue::Unknown => SerializeValue::Unknown,
+        }
+    }
+}
+
+impl<'a, F: Form> SerializeRecordFields<'a, F> {
+    pub fn to_owned_in<'b>(&self, bump: &'b Bump) -> SerializeRecordFields<'b> {
+        match self {
+            SerializeRecordFields::Ser(e) => {
+                let mut visit = BumpVisit::new(bump);
+                F::event(e).record(&mut visit);
+                SerializeRecordFields::De(visit.map)
+            }
+            SerializeRecordFields::De(map) => SerializeRecordFields::De(map_in(map, bump)),
+        }
+    }
+}
+
+impl<'a, F: Form> SerializeEvent<'a, F> {
+    pub fn to_owned_in<'b>(&self, bump: &'b Bump) -> SerializeEvent<'b> {
+        SerializeEvent {
+            fields: self.fields.to_owned_in(bump),
+            metadata: self.metadata.to_owned_in(bump),
+            parent: self.parent.clone(),
+        }
+    }
+}
+
+impl<'a> SerializeAttributes<'a> {
+    pub fn to_owned_in<'b>(&self, bump: &'b Bump) -> SerializeAttributes<'b> {
+        SerializeAttributes {
+            metadata: self.metadata.to_owned_in(bump),
+            parent: self.parent.clone(),
+            is_root: self.is_root,
+        }
+    }
+}
+
+impl<'a, F: Form> SerializeRecord<'a, F> {
+    pub fn to_owned_in<'b>(&self, bump: &'b Bump) -> SerializeRecord<'b> {
+        match self {
+            SerializeRecord::Ser(s) => {
+                let mut visit = BumpVisit::new(bump);
+                F::record(s).record(&mut visit);
+                SerializeRecord::De(visit.map)
+            }
+            SerializeRecord::De(map) => SerializeRecord::De(map_in(map, bump)),
+        }
+    }
+}
+
+fn map_in<'b>(map: &RecordMap<'_>, bump: &'b Bump) -> RecordMap<'b> {
+    map.iter()
+        .map(|(k, v)| (k.to_owned_in(bump), v.to_owned_in(bump)))
+        .collect()
+}
+
+struct BumpVisit<'b> {
+    bump: &'b Bump,
+    map: BTreeMap<CowString<'b>, SerializeValue<'b>>,
+}
+
+impl<'b> BumpVisit<'b> {
+    fn new(bump: &'b Bump) -> Self {
+        BumpVisit {
+            bump,
+            map: BTreeMap::new(),
+        }
+    }
+
+    fn insert(&mut self, field: &Field, value: SerializeValue<'b>) {
+        self.map.insert(CowString::Static(field.name()), value);
+    }
+}
+
+impl<'b> Visit for BumpVisit<'b> {
+    #[cfg(all(tracing_unstable, feature = "valuable"))]
+    fn record_value(&mut self, field: &Field, value: valuable_crate::Value<'_>) {
+        self.insert(field, SerializeValue::from_valuable(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field, SerializeValue::Bool(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        let s = bumpalo::format!(in self.bump, "{:?}", value).into_bump_str();
+        self.insert(
+            field,
+            SerializeValue::Debug(DebugRecord::De(CowString::Borrowed(s))),
+        );
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field, SerializeValue::U64(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field, SerializeValue::I64(value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.insert(field, SerializeValue::F64(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        let s = self.bump.alloc_str(value);
+        self.insert(field, SerializeValue::Str(CowString::Borrowed(s)));
+    }
+}
@@ -0,0 +1,210 @@
+//! Columnar export of events to [Apache Arrow] and [Parquet].
+//!
+//! [`EventBatchBuilder`] accumulates events into Arrow [`RecordBatch`]es with
+//! the following columns:
+//!
+//! | column      | type                               |
+//! |-------------|------------------------------------|
+//! | `timestamp` | `Timestamp(Nanosecond, "UTC")`     |
+//! | `level`     | `Utf8`                             |
+//! | `target`    | `Utf8`                             |
+//! | `message`   | `Utf8`, null if there is none      |
+//! | `fields`    | `Map<Utf8, Utf8>`, all other fields |
+//!
+//! Field values are stored as their display form, so that events with
+//! differently-typed values for the same field name still share a schema.
+//!
+//! [`ParquetEventWriter`] wraps a builder and writes the batches to a
+//! Parquet file as they fill up.
+//!
+//! [Apache Arrow]: https://arrow.apache.org
+//! [Parquet]: https://parquet.apache.org
+
+use std::{io::Write, sync::Arc, time::SystemTime};
+
+use arrow_array::{
+    builder::{ArrayBuilder, MapBuilder, StringBuilder, TimestampNanosecondBuilder},
+    Array, ArrayRef, RecordBatch,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use parquet::{arrow::ArrowWriter, errors::ParquetError, file::properties::WriterProperties};
+
+use crate::{time::unix_nanos, SerializeEvent, SerializeRecordFields, SerializeValue};
+
+const MESSAGE: &str = "message";
+
+/// Accumulates events into Arrow record batches.
+#[derive(Debug)]
+pub struct EventBatchBuilder {
+    schema: SchemaRef,
+    timestamp: TimestampNanosecondBuilder,
+    level: StringBuilder,
+    target: StringBuilder,
+    message: StringBuilder,
+    fields: MapBuilder<StringBuilder, StringBuilder>,
+}
+
+impl Default for EventBatchBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventBatchBuilder {
+    /// Create a new, empty builder.
+    pub fn new() -> Self {
+        let fields = MapBuilder::new(None, StringBuilder::new(), StringBuilder::new());
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(
+                "timestamp",
+                DataType::Timestamp(TimeUnit::Nanosecond, Some("UTC".into())),
+                false,
+            ),
+            Field::new("level", DataType::Utf8, false),
+            Field::new("target", DataType::Utf8, false),
+            Field::new(MESSAGE, DataType::Utf8, true),
+            Field::new("fields", fields.finish_cloned().data_type().clone(), false),
+        ]));
+
+        Self {
+            schema,
+            timestamp: TimestampNanosecondBuilder::new().with_timezone("UTC"),
+            level: StringBuilder::new(),
+            target: StringBuilder::new(),
+            message: StringBuilder::new(),
+            fields,
+        }
+    }
+
+    /// The schema of the batches produced by this builder.
+    pub fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    /// The number of events added since the last call to [`Self::finish`].
+    pub fn len(&self) -> usize {
+        self.timestamp.len()
+    }
+
+    /// Returns `true` if no events were added since the last call to [`Self::finish`].
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Add an event that occurred at `timestamp`.
+    pub fn push(&mut self, timestamp: SystemTime, event: &SerializeEvent<'_>) {
+        self.timestamp.append_value(unix_nanos(timestamp));
+        self.level.append_value(event.metadata.level.as_str());
+        self.target.append_value(event.metadata.target.as_str());
+
+        let mut message = None;
+        for_each_field(&event.fields, |name, value| {
+            let value = value.to_string();
+            if name == MESSAGE {
+                message = Some(value);
+            } else {
+                self.fields.keys().append_value(name);
+                self.fields.values().append_value(value);
+            }
+        });
+        self.message.append_option(message);
+        self.fields
+            .append(true)
+            .expect("keys and values are appended in pairs");
+    }
+
+    /// Build a record batch from the events added so far, and reset the builder.
+    pub fn finish(&mut self) -> RecordBatch {
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(self.timestamp.finish()),
+            Arc::new(self.level.finish()),
+            Arc::new(self.target.finish()),
+            Arc::new(self.message.finish()),
+            Arc::new(self.fields.finish()),
+        ];
+        RecordBatch::try_new(self.schema.clone(), columns)
+            .expect("columns always match the builder's schema")
+    }
+}
+
+/// Writes events to a Parquet file, one row group per batch.
+pub struct ParquetEventWriter<W: Write + Send> {
+    builder: EventBatchBuilder,
+    writer: ArrowWriter<W>,
+    batch_size: usize,
+}
+
+impl<W: Write + Send> core::fmt::Debug for ParquetEventWriter<W> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ParquetEventWriter")
+            .field("buffered", &self.builder.len())
+            .field("batch_size", &self.batch_size)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<W: Write + Send> ParquetEventWriter<W> {
+    /// The default number of events buffered before a batch is written.
+    pub const DEFAULT_BATCH_SIZE: usize = 8192;
+
+    /// Create a writer with the default batch size and writer properties.
+    pub fn new(writer: W) -> Result<Self, ParquetError> {
+        Self::with_options(writer, Self::DEFAULT_BATCH_SIZE, None)
+    }
+
+    /// Create a writer that writes a batch every `batch_size` events.
+    pub fn with_options(
+        writer: W,
+        batch_size: usize,
+        props: Option<WriterProperties>,
+    ) -> Result<Self, ParquetError> {
+        let builder = EventBatchBuilder::new();
+        let writer = ArrowWriter::try_new(writer, builder.schema(), props)?;
+        Ok(Self {
+            builder,
+            writer,
+            batch_size: batch_size.max(1),
+        })
+    }
+
+    /// Add an event that occurred at `timestamp`, writing a batch if one is full.
+    pub fn push(
+        &mut self,
+        timestamp: SystemTime,
+        event: &SerializeEvent<'_>,
+    ) -> Result<(), ParquetError> {
+        self.builder.push(timestamp, event);
+        if self.builder.len() >= self.batch_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Write any buffered events as a batch.
+    pub fn flush(&mut self) -> Result<(), ParquetError> {
+        if !self.builder.is_empty() {
+            self.writer.write(&self.builder.finish())?;
+        }
+        Ok(())
+    }
+
+    /// Write any buffered events, finish the file, and return the underlying writer.
+    pub fn into_inner(mut self) -> Result<W, ParquetError> {
+        self.flush()?;
+        self.writer.into_inner()
+    }
+}
+
+fn for_each_field(
+    fields: &SerializeRecordFields<'_>,
+    mut f: impl FnMut(&str, &SerializeValue<'_>),
+) {
+    match fields {
+        SerializeRecordFields::Ser(never) => match *never {},
+        SerializeRecordFields::De(map) => {
+            for (k, v) in map.iter() {
+                f(k.as_str(), v)
+            }
+        }
+    }
+}
@@ -0,0 +1,269 @@
+//! Tamper-evident streams, chaining every frame to the one before it.
+//!
+//! In an audit stream, each COBS frame carries the postcard encoded message
+//! followed by the hash of the previous frame, before COBS encoding. The
+//! first frame carries the hash of a seed both ends agree on, such as a
+//! nonce exchanged when the connection is set up. The hash is any
+//! [`Digest`], such as `sha2::Sha256`:
+//!
+//! ```rust
+//! use sha2::Sha256;
+//! use tracing_serde_structured::{
+//!     audit::{Chain, Error, Verifier},
+//!     SerializeLevel,
+//! };
+//!
+//! let mut chain = Chain::<Sha256>::new(b"session 42");
+//! let mut frames = Vec::new();
+//! for level in [SerializeLevel::Warn, SerializeLevel::Info, SerializeLevel::Error] {
+//!     let mut buf = [0u8; 64];
+//!     frames.push(chain.to_slice_cobs(&level, &mut buf).unwrap().to_vec());
+//! }
+//!
+//! // Someone deletes the second frame.
+//! frames.remove(1);
+//!
+//! let mut verifier = Verifier::<Sha256>::new(b"session 42");
+//! let mut first = frames[0].clone();
+//! let level: SerializeLevel = verifier.from_bytes_cobs(&mut first).unwrap();
+//! assert_eq!(level, SerializeLevel::Warn);
+//! let mut third = frames[1].clone();
+//! assert!(matches!(
+//!     verifier.from_bytes_cobs::<SerializeLevel>(&mut third),
+//!     Err(Error::Broken)
+//! ));
+//! ```
+//!
+//! A frame that was altered still embeds the right hash, but the frame after
+//! it does not, so losing, reordering or altering frames is reported at the
+//! next frame. The verifier then carries on from that frame, so each gap is
+//! reported once.
+//!
+//! Cutting frames off the end of a stream leaves nothing to compare, though.
+//! To detect that, store the [`Chain::head`] somewhere the stream's readers
+//! can trust every so often, such as a separate log, and compare it to the
+//! [`Verifier::head`] once the stream has been read up to that point.
+//!
+//! [`FramedWriter::write_chained`](crate::embedded_io::FramedWriter::write_chained)
+//! sends audit frames to an `embedded_io` sink, and with the `host` feature,
+//! [`Decoder::set_audit`](crate::host::Decoder::set_audit) verifies them while
+//! decoding.
+
+use core::fmt;
+
+use ::postcard::ser_flavors::{Cobs, Flavor, Slice};
+use digest::{Digest, Output};
+use serde::{Deserialize, Serialize};
+
+/// Errors returned when verifying a frame.
+#[derive(Debug)]
+pub enum Error {
+    /// The frame is not valid COBS, or too short to end with a hash.
+    Frame,
+    /// The frame does not embed the hash of the previous frame, so frames
+    /// before it were lost, reordered or altered, or the seeds differ.
+    Broken,
+    /// The frame's hash is right, but its message could not be decoded.
+    Decode(::postcard::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Frame => f.write_str("frame is too short or not valid COBS"),
+            Error::Broken => f.write_str("frame does not follow from the previous frame's hash"),
+            Error::Decode(e) => write!(f, "failed to decode message: {e}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Decode(e) => Some(e),
+            Error::Frame | Error::Broken => None,
+        }
+    }
+}
+
+/// The producer's end of a chain, encoding each message into a frame that
+/// embeds the hash of the previous one.
+pub struct Chain<D: Digest> {
+    head: Output<D>,
+}
+
+impl<D: Digest> fmt::Debug for Chain<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Chain").field("head", &self.head).finish()
+    }
+}
+
+impl<D: Digest> Chain<D> {
+    /// A chain whose first frame embeds the hash of `seed`.
+    pub fn new(seed: &[u8]) -> Self {
+        Self {
+            head: D::digest(seed),
+        }
+    }
+
+    /// The hash the next frame embeds: that of the last frame encoded, or of
+    /// the seed.
+    pub fn head(&self) -> &Output<D> {
+        &self.head
+    }
+
+    /// Encodes `value` into `buf` as one COBS frame, including its
+    /// terminating zero byte, as with [`postcard::to_slice_cobs`](::postcard::to_slice_cobs).
+    ///
+    /// The frame takes the size of the hash more than a plain one. The chain
+    /// only moves on if the frame is encoded, so a frame that does not fit
+    /// can be skipped, but every frame that is encoded must be sent.
+    pub fn to_slice_cobs<'b, T>(
+        &mut self,
+        value: &T,
+        buf: &'b mut [u8],
+    ) -> Result<&'b mut [u8], ::postcard::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let flavor = self.chained(Cobs::try_new(Slice::new(buf))?);
+        ::postcard::serialize_with_flavor(value, flavor)
+    }
+
+    /// A flavor chaining the frames encoded with `inner`.
+    pub(crate) fn chained<F: Flavor>(&mut self, inner: F) -> Chained<'_, D, F> {
+        Chained {
+            head: &mut self.head,
+            digest: D::new(),
+            inner,
+        }
+    }
+}
+
+/// A flavor hashing the message as it is encoded, and appending the previous
+/// frame's hash to it.
+pub(crate) struct Chained<'c, D: Digest, F> {
+    head: &'c mut Output<D>,
+    digest: D,
+    inner: F,
+}
+
+impl<D: Digest, F: Flavor> Flavor for Chained<'_, D, F> {
+    type Output = F::Output;
+
+    fn try_push(&mut self, data: u8) -> ::postcard::Result<()> {
+        Digest::update(&mut self.digest, [data]);
+        self.inner.try_push(data)
+    }
+
+    fn try_extend(&mut self, data: &[u8]) -> ::postcard::Result<()> {
+        Digest::update(&mut self.digest, data);
+        self.inner.try_extend(data)
+    }
+
+    fn finalize(self) -> ::postcard::Result<F::Output> {
+        let Chained {
+            head,
+            mut digest,
+            mut inner,
+        } = self;
+        inner.try_extend(head)?;
+        Digest::update(&mut digest, &*head);
+        let frame = inner.finalize()?;
+        *head = digest.finalize();
+        Ok(frame)
+    }
+}
+
+/// The consumer's end of a chain, checking that each frame embeds the hash of
+/// the previous one.
+pub struct Verifier<D: Digest> {
+    head: Output<D>,
+}
+
+impl<D: Digest> fmt::Debug for Verifier<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Verifier")
+            .field("head", &self.head)
+            .finish()
+    }
+}
+
+impl<D: Digest> Verifier<D> {
+    /// A verifier expecting the first frame to embed the hash of `seed`.
+    pub fn new(seed: &[u8]) -> Self {
+        Self {
+            head: D::digest(seed),
+        }
+    }
+
+    /// The hash the next frame should embed: that of the last frame
+    /// verified, or of the seed.
+    pub fn head(&self) -> &Output<D> {
+        &self.head
+    }
+
+    /// Checks a frame that has already been COBS decoded, and returns the
+    /// encoded message in it.
+    ///
+    /// The next frame is checked against this one, whether or not this one
+    /// embeds the expected hash.
+    pub fn verify<'f>(&mut self, frame: &'f [u8]) -> Result<&'f [u8], Error> {
+        match self.check(frame) {
+            Some((msg, true)) => Ok(msg),
+            Some((_, false)) => Err(Error::Broken),
+            None => Err(Error::Frame),
+        }
+    }
+
+    /// Returns the message in `frame`, and whether the frame embeds the
+    /// expected hash, or `None` if it is too short to embed one.
+    fn check<'f>(&mut self, frame: &'f [u8]) -> Option<(&'f [u8], bool)> {
+        let at = frame.len().checked_sub(self.head.len())?;
+        let (msg, hash) = frame.split_at(at);
+        let expected = core::mem::replace(&mut self.head, D::digest(frame));
+        Some((msg, hash == expected.as_slice()))
+    }
+
+    /// Decodes one COBS frame in place, without its terminating zero byte,
+    /// then checks it as with [`verify`](Self::verify).
+    pub fn open<'f>(&mut self, frame: &'f mut [u8]) -> Result<&'f [u8], Error> {
+        let len = cobs::decode_in_place(frame).map_err(|_| Error::Frame)?;
+        self.verify(&frame[..len])
+    }
+
+    /// Decodes one COBS frame in place, without its terminating zero byte,
+    /// checks it, and deserializes its message, as with
+    /// [`postcard::from_bytes_cobs`](::postcard::from_bytes_cobs).
+    pub fn from_bytes_cobs<'f, T>(&mut self, frame: &'f mut [u8]) -> Result<T, Error>
+    where
+        T: Deserialize<'f>,
+    {
+        ::postcard::from_bytes(self.open(frame)?).map_err(Error::Decode)
+    }
+}
+
+/// A [`Verifier`] with its hash erased, for the host decoder.
+#[cfg(feature = "host")]
+pub(crate) trait Verify: fmt::Debug + Send {
+    /// Returns the message in a COBS decoded frame, and whether the frame
+    /// embeds the expected hash.
+    fn check<'f>(&mut self, frame: &'f [u8]) -> Result<(&'f [u8], bool), Error>;
+
+    fn head(&self) -> &[u8];
+}
+
+#[cfg(feature = "host")]
+impl<D: Digest> Verify for Verifier<D>
+where
+    Output<D>: Send,
+{
+    fn check<'f>(&mut self, frame: &'f [u8]) -> Result<(&'f [u8], bool), Error> {
+        Verifier::check(self, frame).ok_or(Error::Frame)
+    }
+
+    fn head(&self) -> &[u8] {
+        &self.head
+    }
+}
@@ -0,0 +1,514 @@
+//! W3C baggage: key/value pairs set on a span and carried by the spans
+//! created within it.
+//!
+//! Baggage is metadata that concerns a whole request rather than one event,
+//! such as the tenant or the feature flags it runs with, and that travels
+//! with it from service to service in the `baggage` HTTP header. A
+//! [`SerializeBaggage`] holds such a set, parsed from and written back to
+//! that header, and is sent on the wire as a [`TracingWire::Baggage`] about
+//! the span it was set on.
+//!
+//! Like W3C baggage, a span's baggage is inherited: the spans created within
+//! it start out with the baggage of their parent, and entries set on a span
+//! replace those of the same key it inherited. A [`SpanBaggage`] keeps the
+//! baggage of every open span on the producer's side:
+//!
+//! ```rust
+//! # use tracing_core::{Event, Metadata, Subscriber};
+//! # use tracing_core::span::{Attributes, Id, Record};
+//! use tracing_serde_structured::baggage::{BaggageEntry, SpanBaggage};
+//!
+//! struct Forwarder {
+//!     baggage: SpanBaggage,
+//! }
+//!
+//! impl Forwarder {
+//!     /// The span the current thread is in, which the subscriber tracks in
+//!     /// `enter` and `exit`.
+//!     fn current(&self) -> Option<Id> {
+//!         // ...
+//!         # None
+//!     }
+//! }
+//!
+//! impl Subscriber for Forwarder {
+//!     fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+//!         let id = Id::from_u64(1);
+//!         self.baggage.new_span(&id, attrs, self.current().as_ref());
+//!         if attrs.is_root() {
+//!             // E.g. from the `baggage` header of the request being handled.
+//!             let _ = self.baggage.insert(&id, BaggageEntry::new("tenant", "acme"));
+//!             let msg = self.baggage.message(&id).unwrap();
+//!             println!("{}", serde_json::to_string(&msg).unwrap());
+//!         }
+//!         id
+//!     }
+//!
+//!     fn try_close(&self, id: Id) -> bool {
+//!         self.baggage.close(&id);
+//!         true
+//!     }
+//!
+//!     // ...
+//!     # fn enabled(&self, _: &Metadata<'_>) -> bool { true }
+//!     # fn record(&self, _: &Id, _: &Record<'_>) {}
+//!     # fn record_follows_from(&self, _: &Id, _: &Id) {}
+//!     # fn event(&self, _: &Event<'_>) {}
+//!     # fn enter(&self, _: &Id) {}
+//!     # fn exit(&self, _: &Id) {}
+//! }
+//! ```
+//!
+//! A [`TracingWire::Baggage`] carries all of the span's baggage, inherited
+//! entries included, so a consumer only needs the message of the nearest
+//! span that has one; a [`SpanStore`](crate::span_store::SpanStore) looks it
+//! up with [`baggage`](crate::span_store::SpanStore::baggage).
+//!
+//! [`TracingWire::Baggage`]: crate::wire::TracingWire::Baggage
+
+use core::fmt::{self, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{CowString, TracingVec};
+
+/// The most entries a baggage holds, the least the W3C specification
+/// requires a system to propagate. Without `alloc`, a baggage holds no more
+/// than [`MAX_FIELDS`](crate::MAX_FIELDS) entries either.
+pub const MAX_ENTRIES: usize = 64;
+
+/// The longest `baggage` header written, in bytes.
+pub const MAX_HEADER_LEN: usize = 8192;
+
+/// One entry of a [`SerializeBaggage`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "postcard-schema", derive(postcard_schema::Schema))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BaggageEntry<'a> {
+    #[serde(borrow)]
+    pub key: CowString<'a>,
+    #[serde(borrow)]
+    pub value: CowString<'a>,
+    /// The properties of the entry, the `;` separated text following its
+    /// value in the header, kept as it is.
+    #[serde(borrow)]
+    pub metadata: Option<CowString<'a>>,
+}
+
+impl<'a> BaggageEntry<'a> {
+    pub fn new(key: impl Into<CowString<'a>>, value: impl Into<CowString<'a>>) -> Self {
+        Self {
+            key: key.into(),
+            value: value.into(),
+            metadata: None,
+        }
+    }
+
+    /// Adds the properties `metadata`, such as `ttl=60`.
+    pub fn with_metadata(mut self, metadata: impl Into<CowString<'a>>) -> Self {
+        self.metadata = Some(metadata.into());
+        self
+    }
+
+    pub fn is_borrowed(&self) -> bool {
+        self.key.is_borrowed()
+            || self.value.is_borrowed()
+            || self.metadata.as_ref().is_some_and(CowString::is_borrowed)
+    }
+
+    /// Writes the entry as a member of a `baggage` header, percent-encoding
+    /// its value, or nothing if its key is not a valid one.
+    fn write_header(&self, w: &mut impl Write) -> fmt::Result {
+        if !is_token(&self.key) {
+            return Ok(());
+        }
+        w.write_str(&self.key)?;
+        w.write_char('=')?;
+        for byte in self.value.bytes() {
+            if is_baggage_octet(byte) {
+                w.write_char(byte as char)?;
+            } else {
+                write!(w, "%{byte:02X}")?;
+            }
+        }
+        if let Some(metadata) = &self.metadata {
+            write!(w, ";{}", metadata.as_str())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> BaggageEntry<'a> {
+    pub fn ensure_owned(&mut self) {
+        self.key.ensure_owned();
+        self.value.ensure_owned();
+        if let Some(metadata) = &mut self.metadata {
+            metadata.ensure_owned();
+        }
+    }
+
+    pub fn to_owned(&self) -> BaggageEntry<'static> {
+        BaggageEntry {
+            key: self.key.to_owned_name(),
+            value: self.value.to_owned(),
+            metadata: self.metadata.as_ref().map(CowString::to_owned),
+        }
+    }
+
+    /// Parses one member of a `baggage` header, `None` if it is malformed.
+    fn parse(member: &'a str) -> Option<Self> {
+        let (pair, metadata) = match member.split_once(';') {
+            Some((pair, metadata)) => (pair, Some(metadata.trim_matches(OWS))),
+            None => (member, None),
+        };
+        let (key, value) = pair.split_once('=')?;
+        let key = key.trim_matches(OWS);
+        let value = value.trim_matches(OWS);
+        if !is_token(key) || !value.bytes().all(|b| is_baggage_octet(b) || b == b'%') {
+            return None;
+        }
+        let value = if value.contains('%') {
+            CowString::copied(&percent_decode(value)?)
+        } else {
+            CowString::Borrowed(value)
+        };
+        Some(Self {
+            key: CowString::Borrowed(key),
+            value,
+            metadata: metadata
+                .filter(|metadata| !metadata.is_empty())
+                .map(CowString::Borrowed),
+        })
+    }
+}
+
+/// A set of [`BaggageEntry`]s, with at most one entry for each key, kept in
+/// the order they were added.
+///
+/// It displays as the value of a `baggage` header: entries whose key is not
+/// a valid W3C key, or that would make the header longer than
+/// [`MAX_HEADER_LEN`], are left out.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SerializeBaggage<'a> {
+    #[serde(borrow)]
+    entries: TracingVec<BaggageEntry<'a>>,
+}
+
+impl<'a> SerializeBaggage<'a> {
+    pub const fn new() -> Self {
+        Self {
+            entries: TracingVec::new(),
+        }
+    }
+
+    /// The value of the entry `key`, if there is one.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entry(key).map(|entry| entry.value.as_str())
+    }
+
+    pub fn entry(&self, key: &str) -> Option<&BaggageEntry<'a>> {
+        self.entries.iter().find(|entry| entry.key.as_str() == key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &BaggageEntry<'a>> + '_ {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Adds `entry`, replacing the entry with the same key if there is one.
+    /// Returns `entry` back if the baggage already holds [`MAX_ENTRIES`]
+    /// others.
+    pub fn insert(&mut self, entry: BaggageEntry<'a>) -> Result<(), BaggageEntry<'a>> {
+        if let Some(old) = self
+            .entries
+            .iter_mut()
+            .find(|old| old.key.as_str() == entry.key.as_str())
+        {
+            *old = entry;
+            return Ok(());
+        }
+        if self.entries.len() >= MAX_ENTRIES {
+            return Err(entry);
+        }
+        #[cfg(feature = "alloc")]
+        self.entries.push(entry);
+        #[cfg(not(feature = "alloc"))]
+        self.entries.push(entry)?;
+        Ok(())
+    }
+
+    /// Removes the entry `key`, returning it if there was one.
+    pub fn remove(&mut self, key: &str) -> Option<BaggageEntry<'a>> {
+        let index = self
+            .entries
+            .iter()
+            .position(|entry| entry.key.as_str() == key)?;
+        Some(self.entries.remove(index))
+    }
+
+    pub fn is_borrowed(&self) -> bool {
+        self.entries.iter().any(BaggageEntry::is_borrowed)
+    }
+
+    pub fn is_owned(&self) -> bool {
+        !self.is_borrowed()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> SerializeBaggage<'a> {
+    /// Parses the value of a `baggage` header.
+    ///
+    /// Like the W3C propagators, malformed members are skipped rather than
+    /// failing the whole header, as are the members past the first
+    /// [`MAX_ENTRIES`]. A key that appears more than once keeps its last
+    /// value.
+    pub fn parse(header: &'a str) -> Self {
+        let mut baggage = Self::new();
+        for entry in header
+            .split(',')
+            .filter(|member| !member.trim_matches(OWS).is_empty())
+            .filter_map(BaggageEntry::parse)
+        {
+            if baggage.insert(entry).is_err() {
+                break;
+            }
+        }
+        baggage
+    }
+
+    pub fn ensure_owned(&mut self) {
+        self.entries.iter_mut().for_each(BaggageEntry::ensure_owned);
+    }
+
+    pub fn to_owned(&self) -> SerializeBaggage<'static> {
+        SerializeBaggage {
+            entries: self.entries.iter().map(BaggageEntry::to_owned).collect(),
+        }
+    }
+}
+
+impl fmt::Display for SerializeBaggage<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut len = 0;
+        for entry in &self.entries {
+            let mut count = CountWrite(0);
+            entry.write_header(&mut count)?;
+            let separator = usize::from(len != 0);
+            if count.0 == 0 || len + separator + count.0 > MAX_HEADER_LEN {
+                continue;
+            }
+            if separator != 0 {
+                f.write_char(',')?;
+            }
+            entry.write_header(f)?;
+            len += separator + count.0;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> FromIterator<BaggageEntry<'a>> for SerializeBaggage<'a> {
+    /// Collects the entries, a later entry for a key replacing an earlier
+    /// one, and entries past the first [`MAX_ENTRIES`] keys left out.
+    fn from_iter<I: IntoIterator<Item = BaggageEntry<'a>>>(iter: I) -> Self {
+        let mut baggage = Self::new();
+        for entry in iter {
+            let _ = baggage.insert(entry);
+        }
+        baggage
+    }
+}
+
+#[cfg(feature = "postcard-schema")]
+impl<'a> postcard_schema::Schema for SerializeBaggage<'a> {
+    const SCHEMA: &'static postcard_schema::schema::NamedType =
+        &postcard_schema::schema::NamedType {
+            name: "SerializeBaggage",
+            ty: &postcard_schema::schema::DataModelType::Seq(BaggageEntry::SCHEMA),
+        };
+}
+
+/// Writes the keys of the entries.
+#[cfg(feature = "defmt")]
+impl<'a> defmt::Format for SerializeBaggage<'a> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "[");
+        for (i, entry) in self.entries.iter().enumerate() {
+            if i != 0 {
+                defmt::write!(f, ", ");
+            }
+            defmt::write!(f, "{}", entry.key);
+        }
+        defmt::write!(f, "]");
+    }
+}
+
+/// The whitespace allowed around keys, values and members.
+#[cfg(feature = "alloc")]
+const OWS: &[char] = &[' ', '\t'];
+
+/// Whether `s` is a valid key, an HTTP token.
+fn is_token(s: &str) -> bool {
+    !s.is_empty()
+        && s.bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+/// Whether `b` can appear in a value as it is, rather than percent-encoded.
+/// `%` can, but is encoded so that it is not read as an escape.
+fn is_baggage_octet(b: u8) -> bool {
+    matches!(b, 0x21 | 0x23..=0x24 | 0x26..=0x2B | 0x2D..=0x3A | 0x3C..=0x5B | 0x5D..=0x7E)
+}
+
+#[cfg(feature = "alloc")]
+fn percent_decode(s: &str) -> Option<alloc::string::String> {
+    let mut bytes = alloc::vec::Vec::with_capacity(s.len());
+    let mut rest = s.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        if b == b'%' {
+            let hex = core::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(b);
+            rest = tail;
+        }
+    }
+    alloc::string::String::from_utf8(bytes).ok()
+}
+
+/// Counts the bytes written, to measure a header member before writing it.
+struct CountWrite(usize);
+
+impl Write for CountWrite {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 += s.len();
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+pub use self::spans::SpanBaggage;
+
+#[cfg(feature = "std")]
+mod spans {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, PoisonError, RwLock, RwLockWriteGuard},
+    };
+
+    use tracing_core::span::{Attributes, Id};
+
+    use super::{BaggageEntry, SerializeBaggage};
+    use crate::{wire::TracingWire, AsSerde};
+
+    type Baggage = SerializeBaggage<'static>;
+
+    /// The baggage of every open span. See the [module
+    /// documentation](super).
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    #[derive(Debug, Default)]
+    pub struct SpanBaggage {
+        /// The baggage by span, for spans that have any.
+        spans: RwLock<HashMap<u64, Arc<Baggage>>>,
+    }
+
+    impl SpanBaggage {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Gives the new span `id` the baggage of its parent, which is
+        /// `current` unless the span has an explicit parent or is a root.
+        pub fn new_span(&self, id: &Id, attrs: &Attributes<'_>, current: Option<&Id>) {
+            let parent = if attrs.is_contextual() {
+                current
+            } else {
+                attrs.parent()
+            };
+            if let Some(baggage) = parent.and_then(|parent| self.get(parent)) {
+                self.write().insert(id.into_u64(), baggage);
+            }
+        }
+
+        /// Adds `entry` to the baggage of the span `id`, replacing the entry
+        /// with the same key it has, and so to that of the spans created
+        /// within it from then on. Returns `entry` back if the span already
+        /// has [`MAX_ENTRIES`](super::MAX_ENTRIES) others.
+        pub fn insert<'e>(&self, id: &Id, entry: BaggageEntry<'e>) -> Result<(), BaggageEntry<'e>> {
+            let mut spans = self.write();
+            let baggage = spans.entry(id.into_u64()).or_default();
+            Arc::make_mut(baggage)
+                .insert(entry.to_owned())
+                .map_err(|_| entry)
+        }
+
+        /// Adds every entry of `baggage`, such as one parsed from the
+        /// `baggage` header of an incoming request, to the baggage of the
+        /// span `id`.
+        pub fn extend(&self, id: &Id, baggage: &SerializeBaggage<'_>) {
+            let mut spans = self.write();
+            let own = Arc::make_mut(spans.entry(id.into_u64()).or_default());
+            for entry in baggage.iter() {
+                let _ = own.insert(entry.to_owned());
+            }
+        }
+
+        /// Removes the entry `key` from the baggage of the span `id`, and
+        /// from that of the spans created within it from then on.
+        pub fn remove(&self, id: &Id, key: &str) {
+            if let Some(baggage) = self.write().get_mut(&id.into_u64()) {
+                Arc::make_mut(baggage).remove(key);
+            }
+        }
+
+        /// Forgets the span `id`, once it is closed.
+        pub fn close(&self, id: &Id) {
+            self.write().remove(&id.into_u64());
+        }
+
+        /// The baggage of the span `id`, if it has any.
+        pub fn get(&self, id: &Id) -> Option<Arc<Baggage>> {
+            self.spans
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .get(&id.into_u64())
+                .cloned()
+        }
+
+        /// The message to send for the baggage of the span `id`, if it has
+        /// any, after setting it.
+        pub fn message(&self, id: &Id) -> Option<TracingWire<'static>> {
+            Some(TracingWire::Baggage {
+                span: id.as_serde(),
+                baggage: (*self.get(id)?).clone(),
+            })
+        }
+
+        /// The number of spans with baggage.
+        pub fn len(&self) -> usize {
+            self.spans
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+
+        fn write(&self) -> RwLockWriteGuard<'_, HashMap<u64, Arc<Baggage>>> {
+            self.spans.write().unwrap_or_else(PoisonError::into_inner)
+        }
+    }
+}
@@ -0,0 +1,417 @@
+//! Decodes the framed postcard stream of a device, and prints its messages.
+//!
+//! ```text
+//! tss-decode [OPTIONS] [PATH]
+//! ```
+//!
+//! Reads from `PATH`, or from stdin if it is missing or `-`. A serial port
+//! is read like any other file, once it is set up, e.g. with
+//! `stty -F /dev/ttyACM0 115200 raw`.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Write as _},
+    fs::File,
+    io::{self, BufRead, BufReader, IsTerminal, Write},
+    process::ExitCode,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use tracing_serde_structured::{
+    framing::LengthPrefixed,
+    host::{self, Decoder, Merger, Message, SourceMessage},
+    json_lines, pretty,
+    time::TickRate,
+    wire::{delta::SourceId, table::MetadataTable, TracingWire},
+    CowString, SerializeRecord, SerializeRecordFields, SerializeValue,
+};
+
+const USAGE: &str = "\
+Usage: tss-decode [OPTIONS] [PATH]
+
+Decodes a COBS framed postcard stream of delta encoded frames, read from PATH,
+or from stdin if PATH is missing or `-`.
+
+Options:
+  -f, --format FORMAT   Output format: pretty (default), json or logfmt
+  -c, --color WHEN      Color pretty output: auto (default), always or never
+  -r, --rate HZ         Tick rate of the device's clock (default 1000000)
+  -t, --table PATH      Metadata table (JSON) the device's ids refer to
+  -m, --mid-stream      Wait for a sync frame before decoding
+  -s, --sources         Read source frames, merging several sources by time
+  -l, --length-prefixed Read length prefixed frames rather than COBS frames
+  -h, --help            Print this help
+";
+
+#[derive(Clone, Copy)]
+enum Format {
+    Pretty,
+    Json,
+    Logfmt,
+}
+
+#[derive(Clone, Copy)]
+enum Color {
+    Auto,
+    Always,
+    Never,
+}
+
+struct Args {
+    format: Format,
+    color: Color,
+    rate: TickRate,
+    table: Option<String>,
+    mid_stream: bool,
+    sources: bool,
+    length_prefixed: bool,
+    path: Option<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Args>, String> {
+    let mut parsed = Args {
+        format: Format::Pretty,
+        color: Color::Auto,
+        rate: TickRate::MICROS,
+        table: None,
+        mid_stream: false,
+        sources: false,
+        length_prefixed: false,
+        path: None,
+    };
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("{name} needs a value"));
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-f" | "--format" => {
+                parsed.format = match value(&arg)?.as_str() {
+                    "pretty" => Format::Pretty,
+                    "json" => Format::Json,
+                    "logfmt" => Format::Logfmt,
+                    other => return Err(format!("unknown format `{other}`")),
+                }
+            }
+            "-c" | "--color" => {
+                parsed.color = match value(&arg)?.as_str() {
+                    "auto" => Color::Auto,
+                    "always" => Color::Always,
+                    "never" => Color::Never,
+                    other => return Err(format!("unknown color choice `{other}`")),
+                }
+            }
+            "-r" | "--rate" => {
+                let hz = value(&arg)?;
+                match hz.parse() {
+                    Ok(hz) if hz > 0 => parsed.rate = TickRate::hz(hz),
+                    _ => return Err(format!("invalid tick rate `{hz}`")),
+                }
+            }
+            "-t" | "--table" => parsed.table = Some(value(&arg)?),
+            "-m" | "--mid-stream" => parsed.mid_stream = true,
+            "-s" | "--sources" => parsed.sources = true,
+            "-l" | "--length-prefixed" => parsed.length_prefixed = true,
+            "-" => parsed.path = None,
+            flag if flag.starts_with('-') => return Err(format!("unknown option `{flag}`")),
+            path => parsed.path = Some(path.to_owned()),
+        }
+    }
+    if parsed.table.is_some() && (parsed.mid_stream || parsed.sources) {
+        return Err("--table cannot be combined with --mid-stream or --sources".into());
+    }
+    Ok(Some(parsed))
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            print!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprint!("error: {e}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        // Stop quietly when piped into e.g. `head`.
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: Args) -> io::Result<()> {
+    let input: Box<dyn BufRead> = match &args.path {
+        Some(path) => Box::new(BufReader::new(File::open(path)?)),
+        None => Box::new(io::stdin().lock()),
+    };
+    let ansi = match args.color {
+        Color::Auto => io::stdout().is_terminal(),
+        Color::Always => true,
+        Color::Never => false,
+    };
+    let mut out = Output::new(args.format, ansi, io::stdout().lock());
+
+    if args.sources {
+        let merger = Merger::new(args.rate);
+        let messages: Box<dyn Iterator<Item = Result<SourceMessage, host::Error>>> =
+            if args.length_prefixed {
+                Box::new(merger.read_framed(input, LengthPrefixed::new()))
+            } else {
+                Box::new(merger.read(input))
+            };
+        for msg in messages {
+            match msg {
+                Ok(msg) => out.write(Some(msg.source), &msg.message)?,
+                Err(e) => eprintln!("dropped a frame: {e}"),
+            }
+        }
+        return out.flush();
+    }
+
+    let decoder = match (&args.table, args.mid_stream) {
+        (Some(path), _) => {
+            let table: MetadataTable =
+                serde_json::from_reader(BufReader::new(File::open(path)?))
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            Decoder::with_table(args.rate, table)
+        }
+        (None, true) => Decoder::mid_stream(args.rate),
+        (None, false) => Decoder::new(args.rate),
+    };
+    let messages: Box<dyn Iterator<Item = Result<Message, host::Error>>> = if args.length_prefixed {
+        Box::new(decoder.read_framed(input, LengthPrefixed::new()))
+    } else {
+        Box::new(decoder.read(input))
+    };
+    for msg in messages {
+        match msg {
+            Ok(msg) => out.write(None, &msg)?,
+            Err(e) => eprintln!("dropped a frame: {e}"),
+        }
+    }
+    out.flush()
+}
+
+enum Output<W: Write> {
+    Json(json_lines::Writer<W>),
+    Logfmt(W),
+    /// A writer for each source, as each keeps track of its own spans, with
+    /// lines tagged with their source.
+    Pretty(BTreeMap<Option<SourceId>, pretty::Writer<Vec<u8>>>, bool, W),
+}
+
+impl<W: Write> Output<W> {
+    fn new(format: Format, ansi: bool, writer: W) -> Self {
+        match format {
+            Format::Json => Output::Json(json_lines::Writer::new(writer)),
+            Format::Logfmt => Output::Logfmt(writer),
+            Format::Pretty => Output::Pretty(BTreeMap::new(), ansi, writer),
+        }
+    }
+
+    fn write(&mut self, source: Option<SourceId>, msg: &Message) -> io::Result<()> {
+        match self {
+            Output::Json(writer) => writer.write(&serde_json::json!({
+                "source": source.map(|s| s.id),
+                "ticks": msg.ticks,
+                "since_start_ns": msg.since_start.as_nanos() as u64,
+                "wall_clock_ns": msg.wall_clock.map(unix_nanos),
+                "msg": msg.msg,
+            })),
+            Output::Logfmt(writer) => {
+                let mut line = String::new();
+                let _ = logfmt(&mut line, source, msg);
+                writeln!(writer, "{line}")
+            }
+            Output::Pretty(writers, ansi, writer) => {
+                let pretty = writers.entry(source).or_insert_with(|| {
+                    let mut pretty = pretty::Writer::new(Vec::new());
+                    pretty.set_ansi(*ansi);
+                    pretty
+                });
+                pretty.write_message(msg)?;
+                let line = std::mem::take(pretty.get_mut());
+                if line.is_empty() {
+                    return Ok(());
+                }
+                if let Some(source) = source {
+                    write!(writer, "[{}] ", source.id)?;
+                }
+                writer.write_all(&line)
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::Json(writer) => writer.flush(),
+            Output::Logfmt(writer) | Output::Pretty(_, _, writer) => writer.flush(),
+        }
+    }
+}
+
+fn unix_nanos(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_nanos() as u64
+}
+
+type Fields<'a> = Vec<(&'a str, &'a SerializeValue<'static>)>;
+
+/// Splits the `message` field off the others.
+fn split_message<'a>(
+    fields: impl Iterator<Item = (&'a CowString<'static>, &'a SerializeValue<'static>)>,
+) -> (Option<&'a SerializeValue<'static>>, Fields<'a>) {
+    let mut message = None;
+    let mut rest = Vec::new();
+    for (k, v) in fields {
+        match k.as_str() {
+            "message" => message = Some(v),
+            k => rest.push((k, v)),
+        }
+    }
+    (message, rest)
+}
+
+fn event_fields<'a>(
+    fields: &'a SerializeRecordFields<'static>,
+) -> (Option<&'a SerializeValue<'static>>, Fields<'a>) {
+    match fields {
+        SerializeRecordFields::De(map) => split_message(map.iter()),
+        SerializeRecordFields::Ser(never) => match *never {},
+    }
+}
+
+fn record_fields<'a>(values: &'a SerializeRecord<'static>) -> Fields<'a> {
+    match values {
+        SerializeRecord::De(map) => map.iter().map(|(k, v)| (k.as_str(), v)).collect(),
+        SerializeRecord::Ser(never) => match *never {},
+    }
+}
+
+/// Writes a message as [logfmt](https://brandur.org/logfmt), e.g.
+/// `ts=1.250000 kind=event level=INFO target=app::net msg=connected peer=10.0.0.1`.
+fn logfmt(line: &mut String, source: Option<SourceId>, msg: &Message) -> fmt::Result {
+    write!(line, "ts={:.6}", msg.since_start.as_secs_f64())?;
+    if let Some(wall) = msg.wall_clock {
+        let nanos = unix_nanos(wall);
+        write!(
+            line,
+            " wall={}.{:09}",
+            nanos / 1_000_000_000,
+            nanos % 1_000_000_000
+        )?;
+    }
+    if let Some(source) = source {
+        write!(line, " source={}", source.id)?;
+    }
+    let pair = |line: &mut String, k: &str, v: &dyn fmt::Display| -> fmt::Result {
+        let v = v.to_string();
+        if !v.is_empty()
+            && !v.contains(|c: char| c == ' ' || c == '=' || c == '"' || c.is_control())
+        {
+            return write!(line, " {k}={v}");
+        }
+        write!(line, " {k}=\"")?;
+        for c in v.chars() {
+            match c {
+                '"' | '\\' => write!(line, "\\{c}")?,
+                c if c.is_control() => write!(line, "{}", c.escape_default())?,
+                c => line.push(c),
+            }
+        }
+        line.push('"');
+        Ok(())
+    };
+    match &msg.msg {
+        TracingWire::Event(event) => {
+            let (message, rest) = event_fields(&event.fields);
+            pair(line, "kind", &"event")?;
+            pair(line, "level", &event.metadata.level)?;
+            pair(line, "target", &event.metadata.target.as_str())?;
+            if let Some(message) = message {
+                pair(line, "msg", message)?;
+            }
+            if let Some(parent) = &event.parent {
+                pair(line, "span", &parent.id)?;
+            }
+            for (k, v) in rest {
+                pair(line, k, v)?;
+            }
+            Ok(())
+        }
+        TracingWire::NewSpan { id, attributes } => {
+            let meta = &attributes.metadata;
+            pair(line, "kind", &"new_span")?;
+            pair(line, "span", &id.id)?;
+            pair(line, "name", &meta.name.as_str())?;
+            pair(line, "level", &meta.level)?;
+            pair(line, "target", &meta.target.as_str())?;
+            if let Some(parent) = &attributes.parent {
+                pair(line, "parent", &parent.id)?;
+            }
+            Ok(())
+        }
+        TracingWire::Record { span, values } => {
+            pair(line, "kind", &"record")?;
+            pair(line, "span", &span.id)?;
+            for (k, v) in record_fields(values) {
+                pair(line, k, v)?;
+            }
+            Ok(())
+        }
+        TracingWire::FollowsFrom { span, follows } => {
+            pair(line, "kind", &"follows_from")?;
+            pair(line, "span", &span.id)?;
+            pair(line, "follows", &follows.id)
+        }
+        TracingWire::Enter(id) | TracingWire::Exit(id) | TracingWire::CloseSpan(id) => {
+            let kind = match &msg.msg {
+                TracingWire::Enter(_) => "enter",
+                TracingWire::Exit(_) => "exit",
+                _ => "close_span",
+            };
+            pair(line, "kind", &kind)?;
+            pair(line, "span", &id.id)
+        }
+        TracingWire::Panic(panic) => {
+            pair(line, "kind", &"panic")?;
+            pair(line, "msg", &panic.message)?;
+            if let Some(file) = &panic.file {
+                pair(line, "file", &file.as_str())?;
+            }
+            if let Some(line_no) = panic.line {
+                pair(line, "line", &line_no)?;
+            }
+            Ok(())
+        }
+        TracingWire::PipelineStats(stats) => {
+            pair(line, "kind", &"pipeline_stats")?;
+            pair(line, "sent", &stats.sent)?;
+            pair(line, "dropped", &stats.dropped)?;
+            pair(line, "overflows", &stats.overflows)?;
+            pair(line, "errors", &stats.errors)
+        }
+        TracingWire::Dropped(dropped) => {
+            pair(line, "kind", &"dropped")?;
+            pair(line, "count", &dropped.count)?;
+            pair(line, "reason", &format_args!("{:?}", dropped.reason))?;
+            pair(line, "first_seq", &dropped.first_seq)?;
+            pair(line, "last_seq", &dropped.last_seq)
+        }
+        TracingWire::Baggage { span, baggage } => {
+            pair(line, "kind", &"baggage")?;
+            pair(line, "span", &span.id)?;
+            pair(line, "baggage", baggage)
+        }
+        other => {
+            pair(line, "kind", &"other")?;
+            pair(line, "msg", &format_args!("{other:?}"))
+        }
+    }
+}
@@ -0,0 +1,237 @@
+//! Events and span attributes built by hand, without a tracing callsite.
+//!
+//! Tests, bridges from other logging systems and tools replaying recorded
+//! traces need messages that no `tracing` macro produced. Instead of writing
+//! out every field of a [`SerializeEvent`] and its [`SerializeMetadata`], start
+//! from a builder, which fills in the rest:
+//!
+//! ```rust
+//! use tracing_serde_structured::{builder::SerializeEventBuilder, SerializeLevel};
+//!
+//! let event = SerializeEventBuilder::new()
+//!     .target("my_app::net")
+//!     .level(SerializeLevel::Warn)
+//!     .field("message", "retrying")
+//!     .field("attempt", 3u64)
+//!     .build();
+//!
+//! assert_eq!(event.metadata.target.as_str(), "my_app::net");
+//! assert!(event.metadata.is_event);
+//! ```
+//!
+//! Names and values borrow from wherever the builder is given `&str`s. Given
+//! `String`s instead, it builds `'static` messages that can be kept around;
+//! [`OwnedEventBuilder`] and [`OwnedAttributesBuilder`] name those builders.
+
+use alloc::vec::Vec;
+
+use crate::{
+    CowString, RecordMap, SerializeAttributes, SerializeEvent, SerializeFieldSet, SerializeId,
+    SerializeLevel, SerializeMetadata, SerializeRecordFields, SerializeValue,
+};
+
+/// Builds a [`SerializeEvent`].
+///
+/// Unless set, the event is named `"event"`, has an empty target, is at the
+/// `INFO` level, has no location, and has no explicit parent.
+#[derive(Debug, Clone)]
+pub struct SerializeEventBuilder<'a> {
+    metadata: MetadataBuilder<'a>,
+    fields: RecordMap<'a>,
+    parent: Option<SerializeId>,
+}
+
+/// A builder for events that own all of their strings.
+pub type OwnedEventBuilder = SerializeEventBuilder<'static>;
+
+impl<'a> Default for SerializeEventBuilder<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> SerializeEventBuilder<'a> {
+    pub fn new() -> Self {
+        Self {
+            metadata: MetadataBuilder::new("event"),
+            fields: RecordMap::new(),
+            parent: None,
+        }
+    }
+
+    pub fn name(mut self, name: impl Into<CowString<'a>>) -> Self {
+        self.metadata.name = name.into();
+        self
+    }
+
+    pub fn target(mut self, target: impl Into<CowString<'a>>) -> Self {
+        self.metadata.target = target.into();
+        self
+    }
+
+    pub fn level(mut self, level: SerializeLevel) -> Self {
+        self.metadata.level = level;
+        self
+    }
+
+    pub fn module_path(mut self, module_path: impl Into<CowString<'a>>) -> Self {
+        self.metadata.module_path = Some(module_path.into());
+        self
+    }
+
+    /// Sets the file and line the event claims to come from.
+    pub fn location(mut self, file: impl Into<CowString<'a>>, line: u32) -> Self {
+        self.metadata.file = Some(file.into());
+        self.metadata.line = Some(line);
+        self
+    }
+
+    /// Adds a field, or replaces the value of a field added before.
+    pub fn field(
+        mut self,
+        name: impl Into<CowString<'a>>,
+        value: impl Into<SerializeValue<'a>>,
+    ) -> Self {
+        let name = name.into();
+        self.metadata.field(name.clone());
+        self.fields.insert(name, value.into());
+        self
+    }
+
+    /// Sets the span the event is inside of.
+    pub fn parent(mut self, parent: SerializeId) -> Self {
+        self.parent = Some(parent);
+        self
+    }
+
+    pub fn build(self) -> SerializeEvent<'a> {
+        SerializeEvent {
+            fields: SerializeRecordFields::De(self.fields),
+            metadata: self.metadata.build(false),
+            parent: self.parent,
+        }
+    }
+}
+
+/// Builds a [`SerializeAttributes`] for a new span.
+///
+/// Unless set, the span has an empty target, is at the `INFO` level, has no
+/// location, and takes its parent from the context it is entered in.
+#[derive(Debug, Clone)]
+pub struct SerializeAttributesBuilder<'a> {
+    metadata: MetadataBuilder<'a>,
+    parent: Option<SerializeId>,
+    is_root: bool,
+}
+
+/// A builder for span attributes that own all of their strings.
+pub type OwnedAttributesBuilder = SerializeAttributesBuilder<'static>;
+
+impl<'a> SerializeAttributesBuilder<'a> {
+    /// A span named `name`.
+    pub fn new(name: impl Into<CowString<'a>>) -> Self {
+        Self {
+            metadata: MetadataBuilder::new(name),
+            parent: None,
+            is_root: false,
+        }
+    }
+
+    pub fn target(mut self, target: impl Into<CowString<'a>>) -> Self {
+        self.metadata.target = target.into();
+        self
+    }
+
+    pub fn level(mut self, level: SerializeLevel) -> Self {
+        self.metadata.level = level;
+        self
+    }
+
+    pub fn module_path(mut self, module_path: impl Into<CowString<'a>>) -> Self {
+        self.metadata.module_path = Some(module_path.into());
+        self
+    }
+
+    /// Sets the file and line the span claims to come from.
+    pub fn location(mut self, file: impl Into<CowString<'a>>, line: u32) -> Self {
+        self.metadata.file = Some(file.into());
+        self.metadata.line = Some(line);
+        self
+    }
+
+    /// Declares a field of the span. Span attributes carry the names of their
+    /// fields only; their values are sent in records.
+    pub fn field(mut self, name: impl Into<CowString<'a>>) -> Self {
+        self.metadata.field(name.into());
+        self
+    }
+
+    /// Sets the span's parent explicitly.
+    pub fn parent(mut self, parent: SerializeId) -> Self {
+        self.parent = Some(parent);
+        self.is_root = false;
+        self
+    }
+
+    /// Makes the span a root, without a parent even when entered inside
+    /// another span.
+    pub fn root(mut self) -> Self {
+        self.parent = None;
+        self.is_root = true;
+        self
+    }
+
+    pub fn build(self) -> SerializeAttributes<'a> {
+        SerializeAttributes {
+            metadata: self.metadata.build(true),
+            parent: self.parent,
+            is_root: self.is_root,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct MetadataBuilder<'a> {
+    name: CowString<'a>,
+    target: CowString<'a>,
+    level: SerializeLevel,
+    module_path: Option<CowString<'a>>,
+    file: Option<CowString<'a>>,
+    line: Option<u32>,
+    fields: Vec<CowString<'a>>,
+}
+
+impl<'a> MetadataBuilder<'a> {
+    fn new(name: impl Into<CowString<'a>>) -> Self {
+        Self {
+            name: name.into(),
+            target: CowString::Static(""),
+            level: SerializeLevel::Info,
+            module_path: None,
+            file: None,
+            line: None,
+            fields: Vec::new(),
+        }
+    }
+
+    /// Adds a field name, keeping the order they were first added in.
+    fn field(&mut self, name: CowString<'a>) {
+        if !self.fields.contains(&name) {
+            self.fields.push(name);
+        }
+    }
+
+    fn build(self, is_span: bool) -> SerializeMetadata<'a> {
+        SerializeMetadata {
+            name: self.name,
+            target: self.target,
+            level: self.level,
+            module_path: self.module_path,
+            file: self.file,
+            line: self.line,
+            fields: SerializeFieldSet::De(self.fields),
+            is_span,
+            is_event: !is_span,
+        }
+    }
+}
@@ -0,0 +1,99 @@
+//! A cache of owned metadata, converted once per callsite.
+//!
+//! Subscribers that keep events around (e.g. to send them to another thread)
+//! usually need owned metadata for every event. Since metadata is fixed per
+//! callsite, a [`MetadataCache`] converts it the first time a callsite is
+//! seen, and hands out a shared [`Arc`] from then on:
+//!
+//! ```rust
+//! # use tracing_core::{Event, Metadata, Subscriber};
+//! # use tracing_core::span::{Attributes, Id, Record};
+//! use std::sync::Arc;
+//! use tracing_serde_structured::{cache::MetadataCache, SerializeMetadata};
+//!
+//! struct Forwarder {
+//!     cache: MetadataCache,
+//! }
+//!
+//! impl Subscriber for Forwarder {
+//!     fn event(&self, event: &Event<'_>) {
+//!         let metadata: Arc<SerializeMetadata<'static>> = self.cache.get(event.metadata());
+//!         // ... send `metadata` along with the event's fields
+//!     }
+//!
+//!     // ...
+//!     # fn enabled(&self, _: &Metadata<'_>) -> bool { true }
+//!     # fn new_span(&self, _: &Attributes<'_>) -> Id { Id::from_u64(1) }
+//!     # fn record(&self, _: &Id, _: &Record<'_>) {}
+//!     # fn record_follows_from(&self, _: &Id, _: &Id) {}
+//!     # fn enter(&self, _: &Id) {}
+//!     # fn exit(&self, _: &Id) {}
+//! }
+//! ```
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, OnceLock, PoisonError, RwLock},
+};
+
+use tracing_core::{callsite::Identifier, Metadata};
+
+use crate::SerializeMetadata;
+
+/// Owned metadata, converted once for each callsite.
+#[derive(Debug, Default)]
+pub struct MetadataCache {
+    entries: RwLock<HashMap<Identifier, Arc<SerializeMetadata<'static>>>>,
+}
+
+impl MetadataCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A cache shared by the whole process.
+    pub fn global() -> &'static MetadataCache {
+        static GLOBAL: OnceLock<MetadataCache> = OnceLock::new();
+        GLOBAL.get_or_init(MetadataCache::new)
+    }
+
+    /// Returns the owned metadata of a callsite, converting it on first use.
+    pub fn get(&self, metadata: &'static Metadata<'static>) -> Arc<SerializeMetadata<'static>> {
+        let id = metadata.callsite();
+        if let Some(cached) = self
+            .entries
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&id)
+        {
+            return cached.clone();
+        }
+
+        self.entries
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(id)
+            .or_insert_with(|| Arc::new(SerializeMetadata::from_static(metadata).to_owned()))
+            .clone()
+    }
+
+    /// The number of callsites in the cache.
+    pub fn len(&self) -> usize {
+        self.entries
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove all entries.
+    pub fn clear(&self) {
+        self.entries
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear()
+    }
+}
@@ -0,0 +1,763 @@
+//! Canonical CBOR encoding helpers.
+//!
+//! [`to_cbor`] produces the "core deterministic encoding" described in
+//! [RFC 8949 §4.2.1]: integers and lengths use their shortest form, floats
+//! use the shortest form that preserves their value, no indefinite-length
+//! items are emitted, and the keys of every map are sorted by the bytewise
+//! lexicographic order of their encoding. Encoding the same message twice
+//! always produces the same bytes, which makes the output suitable for
+//! signing (e.g. with COSE).
+//!
+//! As with the [`msgpack`](crate::msgpack) helpers, structs can be written
+//! either as maps keyed by field name ([`Representation::Named`]) or as
+//! positional arrays ([`Representation::Compact`]). Maps that are maps in
+//! the data model, such as the fields of a record, keep their keys either
+//! way.
+//!
+//! [`from_cbor_slice`] accepts any well-formed CBOR, canonical or not, and
+//! structs in either representation.
+//!
+//! For streaming, a [`SequenceWriter`] writes values as a CBOR Sequence
+//! ([RFC 8742]), items simply following each other with no array around
+//! them, which ingestion pipelines accept as `application/cbor-seq`, and a
+//! [`SequenceReader`] reads them back one at a time:
+//!
+//! ```rust
+//! use tracing_serde_structured::{
+//!     cbor::{SequenceReader, SequenceWriter},
+//!     SerializeLevel,
+//! };
+//!
+//! let mut writer = SequenceWriter::new(Vec::new());
+//! writer.write(&SerializeLevel::Info).unwrap();
+//! writer.write(&SerializeLevel::Warn).unwrap();
+//! let bytes = writer.into_inner();
+//!
+//! let levels: Vec<SerializeLevel> = SequenceReader::new(&bytes[..])
+//!     .collect::<Result<_, _>>()
+//!     .unwrap();
+//! assert_eq!(levels, [SerializeLevel::Info, SerializeLevel::Warn]);
+//! ```
+//!
+//! [RFC 8949 §4.2.1]: https://www.rfc-editor.org/rfc/rfc8949#section-4.2.1
+//! [RFC 8742]: https://www.rfc-editor.org/rfc/rfc8742
+
+use core::{fmt, marker::PhantomData};
+use std::io;
+
+use ciborium::value::{CanonicalValue, Value};
+use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Errors returned by the CBOR helpers.
+#[derive(Debug)]
+pub enum Error {
+    /// The value could not be converted into CBOR.
+    Serialize(ciborium::value::Error),
+    /// The CBOR could not be written.
+    Encode(ciborium::ser::Error<std::io::Error>),
+    /// The input was not well-formed CBOR.
+    Decode(ciborium::de::Error<std::io::Error>),
+    /// The CBOR did not match the shape of the requested type.
+    Deserialize(ciborium::value::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Serialize(e) => write!(f, "failed to serialize value: {e}"),
+            Error::Encode(e) => write!(f, "failed to encode CBOR: {e}"),
+            Error::Decode(e) => write!(f, "failed to decode CBOR: {e}"),
+            Error::Deserialize(e) => write!(f, "failed to deserialize value: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// How structs are laid out in the encoded output.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Representation {
+    /// Structs are encoded as maps from field name to value.
+    #[default]
+    Named,
+    /// Structs are encoded as arrays of values, in declaration order.
+    Compact,
+}
+
+/// Serializes `value` using the canonical CBOR encoding, with the given
+/// struct representation.
+pub fn to_cbor<T>(value: &T, repr: Representation) -> Result<Vec<u8>, Error>
+where
+    T: Serialize + ?Sized,
+{
+    let value = match repr {
+        Representation::Named => Value::serialized(value),
+        Representation::Compact => Value::serialized(&Compact(value)),
+    };
+    let mut value = value.map_err(Error::Serialize)?;
+    canonicalize(&mut value);
+
+    let mut out = Vec::new();
+    ciborium::into_writer(&value, &mut out).map_err(Error::Encode)?;
+    Ok(out)
+}
+
+/// Deserializes a value from a CBOR byte slice.
+///
+/// Structs may be encoded with either [`Representation`].
+///
+/// CBOR is always decoded through an intermediate value, so strings in the
+/// result are owned rather than borrowed from `bytes`.
+pub fn from_cbor_slice<'a, T>(bytes: &[u8]) -> Result<T, Error>
+where
+    T: Deserialize<'a>,
+{
+    let value: Value = ciborium::from_reader(bytes).map_err(Error::Decode)?;
+    deserialized(&value)
+}
+
+fn deserialized<'a, T: Deserialize<'a>>(value: &Value) -> Result<T, Error> {
+    value
+        .deserialized::<Lenient<T>>()
+        .map(|Lenient(value)| value)
+        .map_err(Error::Deserialize)
+}
+
+/// The media type of a CBOR Sequence.
+pub const SEQUENCE_MEDIA_TYPE: &str = "application/cbor-seq";
+
+/// Writes values to an [`io::Write`] as a CBOR Sequence, each in the
+/// canonical encoding of [`to_cbor`].
+///
+/// Each value is encoded in full before anything is written, so a value
+/// that fails to serialize never leaves a partial item behind.
+#[derive(Debug)]
+pub struct SequenceWriter<W> {
+    inner: W,
+    repr: Representation,
+}
+
+impl<W: io::Write> SequenceWriter<W> {
+    /// Creates a writer that encodes structs with [`Representation::Named`].
+    pub fn new(inner: W) -> Self {
+        Self::with_representation(inner, Representation::Named)
+    }
+
+    pub fn with_representation(inner: W, repr: Representation) -> Self {
+        Self { inner, repr }
+    }
+
+    /// Serializes `value` and writes it as the next item.
+    pub fn write<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let item = to_cbor(value, self.repr)?;
+        self.inner
+            .write_all(&item)
+            .map_err(|e| Error::Encode(ciborium::ser::Error::Io(e)))
+    }
+
+    /// Flush the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Returns the underlying writer, without flushing it first.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+/// Reads the items of a CBOR Sequence from an [`io::BufRead`], as values of
+/// type `T`.
+///
+/// The sequence ends cleanly where the input does between two items. Input
+/// that ends within an item, or is not well-formed CBOR, yields an error,
+/// after which the iterator ends, since the start of the next item cannot be
+/// found. An item that does not match the shape of `T` yields an error too,
+/// but reading continues with the next one.
+#[derive(Debug)]
+pub struct SequenceReader<R, T> {
+    inner: R,
+    failed: bool,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<R: io::BufRead, T> SequenceReader<R, T> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            failed: false,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+/// Items are decoded through an intermediate value, as by
+/// [`from_cbor_slice`], so they never borrow from the input.
+impl<R: io::BufRead, T> Iterator for SequenceReader<R, T>
+where
+    T: Deserialize<'static>,
+{
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        match self.inner.fill_buf() {
+            Ok([]) => return None,
+            Ok(_) => {}
+            Err(e) => {
+                self.failed = true;
+                return Some(Err(Error::Decode(ciborium::de::Error::Io(e))));
+            }
+        }
+        match ciborium::from_reader::<Value, _>(&mut self.inner) {
+            Ok(value) => Some(deserialized(&value)),
+            Err(e) => {
+                self.failed = true;
+                Some(Err(Error::Decode(e)))
+            }
+        }
+    }
+}
+
+/// Sorts the keys of every map in `value`, recursively, into canonical order.
+fn canonicalize(value: &mut Value) {
+    match value {
+        Value::Array(items) => items.iter_mut().for_each(canonicalize),
+        Value::Tag(_, inner) => canonicalize(inner),
+        Value::Map(entries) => {
+            for (k, v) in entries.iter_mut() {
+                canonicalize(k);
+                canonicalize(v);
+            }
+            entries.sort_by_cached_key(|(k, _)| CanonicalValue::from(k.clone()));
+        }
+        _ => {}
+    }
+}
+
+/// Serializes the wrapped value with every struct in it, at any depth,
+/// turned into a tuple, and every struct variant into a tuple variant.
+struct Compact<'a, T: ?Sized>(&'a T);
+
+impl<T: Serialize + ?Sized> Serialize for Compact<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(CompactSerializer(serializer))
+    }
+}
+
+struct CompactSerializer<S>(S);
+
+macro_rules! forward_primitives {
+    ($($method:ident($ty:ty)),* $(,)?) => {
+        $(
+            fn $method(self, v: $ty) -> Result<S::Ok, S::Error> {
+                self.0.$method(v)
+            }
+        )*
+    };
+}
+
+impl<S: Serializer> Serializer for CompactSerializer<S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+    type SerializeSeq = CompactCompound<S::SerializeSeq>;
+    type SerializeTuple = CompactCompound<S::SerializeTuple>;
+    type SerializeTupleStruct = CompactCompound<S::SerializeTupleStruct>;
+    type SerializeTupleVariant = CompactCompound<S::SerializeTupleVariant>;
+    type SerializeMap = CompactCompound<S::SerializeMap>;
+    type SerializeStruct = CompactCompound<S::SerializeTuple>;
+    type SerializeStructVariant = CompactCompound<S::SerializeTupleVariant>;
+
+    forward_primitives! {
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_i128(i128),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_u128(u128),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_char(char),
+        serialize_str(&str),
+        serialize_bytes(&[u8]),
+        serialize_unit_struct(&'static str),
+    }
+
+    fn serialize_none(self) -> Result<S::Ok, S::Error> {
+        self.0.serialize_none()
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<S::Ok, S::Error> {
+        self.0.serialize_some(&Compact(value))
+    }
+
+    fn serialize_unit(self) -> Result<S::Ok, S::Error> {
+        self.0.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+    ) -> Result<S::Ok, S::Error> {
+        self.0.serialize_unit_variant(name, index, variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error> {
+        self.0.serialize_newtype_struct(name, &Compact(value))
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error> {
+        self.0
+            .serialize_newtype_variant(name, index, variant, &Compact(value))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, S::Error> {
+        self.0.serialize_seq(len).map(CompactCompound)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, S::Error> {
+        self.0.serialize_tuple(len).map(CompactCompound)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, S::Error> {
+        self.0
+            .serialize_tuple_struct(name, len)
+            .map(CompactCompound)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, S::Error> {
+        self.0
+            .serialize_tuple_variant(name, index, variant, len)
+            .map(CompactCompound)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, S::Error> {
+        self.0.serialize_map(len).map(CompactCompound)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, S::Error> {
+        self.0.serialize_tuple(len).map(CompactCompound)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, S::Error> {
+        self.0
+            .serialize_tuple_variant(name, index, variant, len)
+            .map(CompactCompound)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.0.is_human_readable()
+    }
+}
+
+struct CompactCompound<C>(C);
+
+impl<C: ser::SerializeSeq> ser::SerializeSeq for CompactCompound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), C::Error> {
+        self.0.serialize_element(&Compact(value))
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.0.end()
+    }
+}
+
+impl<C: ser::SerializeTuple> ser::SerializeTuple for CompactCompound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), C::Error> {
+        self.0.serialize_element(&Compact(value))
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.0.end()
+    }
+}
+
+impl<C: ser::SerializeTupleStruct> ser::SerializeTupleStruct for CompactCompound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), C::Error> {
+        self.0.serialize_field(&Compact(value))
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.0.end()
+    }
+}
+
+impl<C: ser::SerializeTupleVariant> ser::SerializeTupleVariant for CompactCompound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), C::Error> {
+        self.0.serialize_field(&Compact(value))
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.0.end()
+    }
+}
+
+impl<C: ser::SerializeMap> ser::SerializeMap for CompactCompound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), C::Error> {
+        self.0.serialize_key(&Compact(key))
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), C::Error> {
+        self.0.serialize_value(&Compact(value))
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.0.end()
+    }
+}
+
+impl<C: ser::SerializeTuple> ser::SerializeStruct for CompactCompound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<(), C::Error> {
+        self.0.serialize_element(&Compact(value))
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.0.end()
+    }
+}
+
+impl<C: ser::SerializeTupleVariant> ser::SerializeStructVariant for CompactCompound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<(), C::Error> {
+        self.0.serialize_field(&Compact(value))
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.0.end()
+    }
+}
+
+/// Deserializes through the wrapped deserializer, or the wrapped type from
+/// any deserializer, accepting every struct in the input, at any depth, as
+/// either a map or an array.
+///
+/// `ciborium` only accepts maps for structs, so the wrapper asks for any
+/// value instead, which derived implementations accept in either form, and
+/// wraps every nested deserializer the same way.
+struct Lenient<T>(T);
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Lenient<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(Lenient(deserializer)).map(Lenient)
+    }
+}
+
+macro_rules! forward_hints {
+    ($($method:ident($($arg:ident: $ty:ty),*)),* $(,)?) => {
+        $(
+            fn $method<V: de::Visitor<'de>>(
+                self,
+                $($arg: $ty,)*
+                visitor: V,
+            ) -> Result<V::Value, D::Error> {
+                self.0.$method($($arg,)* Lenient(visitor))
+            }
+        )*
+    };
+}
+
+impl<'de, D: Deserializer<'de>> Deserializer<'de> for Lenient<D> {
+    type Error = D::Error;
+
+    forward_hints! {
+        deserialize_any(),
+        deserialize_bool(),
+        deserialize_i8(),
+        deserialize_i16(),
+        deserialize_i32(),
+        deserialize_i64(),
+        deserialize_i128(),
+        deserialize_u8(),
+        deserialize_u16(),
+        deserialize_u32(),
+        deserialize_u64(),
+        deserialize_u128(),
+        deserialize_f32(),
+        deserialize_f64(),
+        deserialize_char(),
+        deserialize_str(),
+        deserialize_string(),
+        deserialize_bytes(),
+        deserialize_byte_buf(),
+        deserialize_option(),
+        deserialize_unit(),
+        deserialize_unit_struct(name: &'static str),
+        deserialize_newtype_struct(name: &'static str),
+        deserialize_seq(),
+        deserialize_tuple(len: usize),
+        deserialize_tuple_struct(name: &'static str, len: usize),
+        deserialize_map(),
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]),
+        deserialize_identifier(),
+        deserialize_ignored_any(),
+    }
+
+    fn deserialize_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, D::Error> {
+        self.0.deserialize_any(Lenient(visitor))
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.0.is_human_readable()
+    }
+}
+
+macro_rules! forward_visits {
+    ($($method:ident($ty:ty)),* $(,)?) => {
+        $(
+            fn $method<E: de::Error>(self, v: $ty) -> Result<V::Value, E> {
+                self.0.$method(v)
+            }
+        )*
+    };
+}
+
+impl<'de, V: de::Visitor<'de>> de::Visitor<'de> for Lenient<V> {
+    type Value = V::Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.expecting(f)
+    }
+
+    forward_visits! {
+        visit_bool(bool),
+        visit_i8(i8),
+        visit_i16(i16),
+        visit_i32(i32),
+        visit_i64(i64),
+        visit_i128(i128),
+        visit_u8(u8),
+        visit_u16(u16),
+        visit_u32(u32),
+        visit_u64(u64),
+        visit_u128(u128),
+        visit_f32(f32),
+        visit_f64(f64),
+        visit_char(char),
+        visit_str(&str),
+        visit_borrowed_str(&'de str),
+        visit_string(String),
+        visit_bytes(&[u8]),
+        visit_borrowed_bytes(&'de [u8]),
+        visit_byte_buf(Vec<u8>),
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<V::Value, E> {
+        self.0.visit_none()
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<V::Value, D::Error> {
+        self.0.visit_some(Lenient(deserializer))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<V::Value, E> {
+        self.0.visit_unit()
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<V::Value, D::Error> {
+        self.0.visit_newtype_struct(Lenient(deserializer))
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, seq: A) -> Result<V::Value, A::Error> {
+        self.0.visit_seq(Lenient(seq))
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<V::Value, A::Error> {
+        self.0.visit_map(Lenient(map))
+    }
+
+    fn visit_enum<A: de::EnumAccess<'de>>(self, data: A) -> Result<V::Value, A::Error> {
+        self.0.visit_enum(Lenient(data))
+    }
+}
+
+impl<'de, S: de::DeserializeSeed<'de>> de::DeserializeSeed<'de> for Lenient<S> {
+    type Value = S::Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<S::Value, D::Error> {
+        self.0.deserialize(Lenient(deserializer))
+    }
+}
+
+impl<'de, A: de::SeqAccess<'de>> de::SeqAccess<'de> for Lenient<A> {
+    type Error = A::Error;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, A::Error> {
+        self.0.next_element_seed(Lenient(seed))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.0.size_hint()
+    }
+}
+
+impl<'de, A: de::MapAccess<'de>> de::MapAccess<'de> for Lenient<A> {
+    type Error = A::Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, A::Error> {
+        self.0.next_key_seed(Lenient(seed))
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, A::Error> {
+        self.0.next_value_seed(Lenient(seed))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.0.size_hint()
+    }
+}
+
+impl<'de, A: de::EnumAccess<'de>> de::EnumAccess<'de> for Lenient<A> {
+    type Error = A::Error;
+    type Variant = Lenient<A::Variant>;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), A::Error> {
+        let (value, variant) = self.0.variant_seed(Lenient(seed))?;
+        Ok((value, Lenient(variant)))
+    }
+}
+
+impl<'de, A: de::VariantAccess<'de>> de::VariantAccess<'de> for Lenient<A> {
+    type Error = A::Error;
+
+    fn unit_variant(self) -> Result<(), A::Error> {
+        self.0.unit_variant()
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, A::Error> {
+        self.0.newtype_variant_seed(Lenient(seed))
+    }
+
+    fn tuple_variant<V: de::Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, A::Error> {
+        self.0.tuple_variant(len, Lenient(visitor))
+    }
+
+    fn struct_variant<V: de::Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, A::Error> {
+        // As for a struct, ask for the payload as any value.
+        self.0.newtype_variant_seed(AnyValue(Lenient(visitor)))
+    }
+}
+
+/// Deserializes a value of whatever shape the input has, with the wrapped
+/// visitor.
+struct AnyValue<V>(V);
+
+impl<'de, V: de::Visitor<'de>> de::DeserializeSeed<'de> for AnyValue<V> {
+    type Value = V::Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<V::Value, D::Error> {
+        deserializer.deserialize_any(self.0)
+    }
+}
@@ -0,0 +1,204 @@
+//! Detecting corrupt frames with a CRC.
+//!
+//! COBS framing lets a reader find the start of the next frame after losing
+//! bytes, but it does not notice bytes that arrive flipped, as they do on
+//! noisy serial links. Most such frames still decode, into the wrong message.
+//! Ending every frame with a CRC of its message, before COBS encoding, lets
+//! the reader tell them apart and drop them:
+//!
+//! ```rust
+//! use tracing_serde_structured::{
+//!     checksum::{Checksum, Error},
+//!     SerializeLevel,
+//! };
+//!
+//! let mut buf = [0u8; 16];
+//! let mut frame = Checksum::Crc32
+//!     .to_slice_cobs(&SerializeLevel::Warn, &mut buf)
+//!     .unwrap()
+//!     .to_vec();
+//! frame.pop();
+//!
+//! let level: SerializeLevel = Checksum::Crc32.from_bytes_cobs(&mut frame.clone()).unwrap();
+//! assert_eq!(level, SerializeLevel::Warn);
+//!
+//! // A bit flips on the wire.
+//! frame[1] ^= 0x04;
+//! assert!(matches!(
+//!     Checksum::Crc32.from_bytes_cobs::<SerializeLevel>(&mut frame),
+//!     Err(Error::Corrupt)
+//! ));
+//! ```
+//!
+//! [`Checksum::Crc32`] takes four bytes a frame, and [`Checksum::Crc16`] two,
+//! for links where every byte counts and frames are short. Both ends have to
+//! agree on which one is used; a producer that starts its stream with a
+//! [`Hello`](crate::wire::handshake::Hello) says so in its capabilities.
+//!
+//! A CRC only catches accidents: anyone can compute the right one for a
+//! frame they altered. See the [`crypto`](crate::crypto) module for that.
+//!
+//! [`FramedWriter::write_checked`](crate::embedded_io::FramedWriter::write_checked)
+//! sends checked frames to an `embedded_io` sink, and with the `host`
+//! feature, [`Decoder::set_checksum`](crate::host::Decoder::set_checksum)
+//! counts and skips corrupt frames while decoding.
+
+use core::fmt;
+
+use ::postcard::ser_flavors::{Cobs, Flavor, Slice};
+use crc::{Crc, Digest, CRC_16_IBM_SDLC, CRC_32_ISO_HDLC};
+use serde::{Deserialize, Serialize};
+
+static CRC16: Crc<u16> = Crc::<u16>::new(&CRC_16_IBM_SDLC);
+static CRC32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+/// Errors returned when checking a frame.
+#[derive(Debug)]
+pub enum Error {
+    /// The frame is not valid COBS, or too short to end with a CRC.
+    Frame,
+    /// The frame's CRC is wrong, so some of its bytes were corrupted.
+    Corrupt,
+    /// The frame's CRC is right, but its message could not be decoded.
+    Decode(::postcard::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Frame => f.write_str("frame is too short or not valid COBS"),
+            Error::Corrupt => f.write_str("frame's CRC is wrong"),
+            Error::Decode(e) => write!(f, "failed to decode message: {e}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Decode(e) => Some(e),
+            Error::Frame | Error::Corrupt => None,
+        }
+    }
+}
+
+/// The CRC that ends each frame, in little endian byte order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Checksum {
+    /// CRC-16/IBM-SDLC, as used by HDLC and X.25.
+    Crc16,
+    /// CRC-32/ISO-HDLC, as used by Ethernet and zlib.
+    Crc32,
+}
+
+impl Checksum {
+    /// The number of bytes the CRC takes.
+    pub const fn size(self) -> usize {
+        match self {
+            Checksum::Crc16 => 2,
+            Checksum::Crc32 => 4,
+        }
+    }
+
+    /// The CRC of `data`.
+    pub fn checksum(self, data: &[u8]) -> u32 {
+        match self {
+            Checksum::Crc16 => u32::from(CRC16.checksum(data)),
+            Checksum::Crc32 => CRC32.checksum(data),
+        }
+    }
+
+    /// Encodes `value` into `buf` as one COBS frame, including its
+    /// terminating zero byte, as with [`postcard::to_slice_cobs`](::postcard::to_slice_cobs).
+    ///
+    /// The frame takes the size of the CRC more than a plain one.
+    pub fn to_slice_cobs<'b, T>(
+        self,
+        value: &T,
+        buf: &'b mut [u8],
+    ) -> Result<&'b mut [u8], ::postcard::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let flavor = self.checked(Cobs::try_new(Slice::new(buf))?);
+        ::postcard::serialize_with_flavor(value, flavor)
+    }
+
+    /// A flavor ending the frames encoded with `inner` with this CRC.
+    pub(crate) fn checked<F: Flavor>(self, inner: F) -> Checked<F> {
+        let running = match self {
+            Checksum::Crc16 => Running::Crc16(CRC16.digest()),
+            Checksum::Crc32 => Running::Crc32(CRC32.digest()),
+        };
+        Checked { running, inner }
+    }
+
+    /// Checks the CRC of a frame that has already been COBS decoded, and
+    /// returns the encoded message in it.
+    pub fn check(self, frame: &[u8]) -> Result<&[u8], Error> {
+        let at = frame.len().checked_sub(self.size()).ok_or(Error::Frame)?;
+        let (msg, crc) = frame.split_at(at);
+        let mut bytes = [0; 4];
+        bytes[..crc.len()].copy_from_slice(crc);
+        if u32::from_le_bytes(bytes) != self.checksum(msg) {
+            return Err(Error::Corrupt);
+        }
+        Ok(msg)
+    }
+
+    /// Decodes one COBS frame in place, without its terminating zero byte,
+    /// then checks it as with [`check`](Self::check).
+    pub fn open(self, frame: &mut [u8]) -> Result<&[u8], Error> {
+        let len = cobs::decode_in_place(frame).map_err(|_| Error::Frame)?;
+        self.check(&frame[..len])
+    }
+
+    /// Decodes one COBS frame in place, without its terminating zero byte,
+    /// checks it, and deserializes its message, as with
+    /// [`postcard::from_bytes_cobs`](::postcard::from_bytes_cobs).
+    pub fn from_bytes_cobs<'f, T>(self, frame: &'f mut [u8]) -> Result<T, Error>
+    where
+        T: Deserialize<'f>,
+    {
+        ::postcard::from_bytes(self.open(frame)?).map_err(Error::Decode)
+    }
+}
+
+pub(crate) enum Running {
+    Crc16(Digest<'static, u16>),
+    Crc32(Digest<'static, u32>),
+}
+
+/// A flavor computing the CRC of the message as it is encoded, and appending
+/// it.
+pub(crate) struct Checked<F> {
+    running: Running,
+    inner: F,
+}
+
+impl<F: Flavor> Flavor for Checked<F> {
+    type Output = F::Output;
+
+    fn try_push(&mut self, data: u8) -> ::postcard::Result<()> {
+        self.try_extend(&[data])
+    }
+
+    fn try_extend(&mut self, data: &[u8]) -> ::postcard::Result<()> {
+        match &mut self.running {
+            Running::Crc16(digest) => digest.update(data),
+            Running::Crc32(digest) => digest.update(data),
+        }
+        self.inner.try_extend(data)
+    }
+
+    fn finalize(self) -> ::postcard::Result<F::Output> {
+        let Checked { running, mut inner } = self;
+        match running {
+            Running::Crc16(digest) => inner.try_extend(&digest.finalize().to_le_bytes())?,
+            Running::Crc32(digest) => inner.try_extend(&digest.finalize().to_le_bytes())?,
+        }
+        inner.finalize()
+    }
+}
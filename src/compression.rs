@@ -0,0 +1,228 @@
+//! Compressed batches of wire messages.
+//!
+//! Network sinks usually send messages in batches, and the field names, targets
+//! and span ids repeated across a batch compress well. A [`CompressedBatch`]
+//! holds a postcard encoded sequence of [`TracingWire`] messages, compressed with
+//! one of the algorithms enabled by the `deflate`, `lz4` and `zstd` features:
+//!
+//! ```rust
+//! # #[cfg(feature = "deflate")] {
+//! use tracing_serde_structured::{
+//!     compression::{CompressedBatch, Compression},
+//!     wire::TracingWire,
+//!     SerializeId,
+//! };
+//! # use core::num::NonZeroU64;
+//!
+//! let id = SerializeId { id: NonZeroU64::new(1).unwrap() };
+//! let messages: Vec<TracingWire<'_>> = vec![TracingWire::Enter(id.clone()), TracingWire::Exit(id)];
+//!
+//! let batch = CompressedBatch::new(Compression::Deflate, &messages).unwrap();
+//! let frame = postcard::to_allocvec(&batch).unwrap();
+//!
+//! // On the receiving side:
+//! let batch: CompressedBatch = postcard::from_bytes(&frame).unwrap();
+//! let raw = batch.decompress().unwrap();
+//! let decoded = CompressedBatch::messages(&raw).unwrap();
+//! assert_eq!(decoded.len(), 2);
+//! # }
+//! ```
+//!
+//! Each batch names its own algorithm, so a decoder can read batches from any
+//! encoder built with the same features. [`Compression::supported`] lists the
+//! algorithms available in this build, for peers to advertise to each other, and
+//! [`Compression::negotiate`] picks the best one both sides support.
+
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{wire::TracingWire, Form};
+
+/// Errors returned when compressing or decompressing a batch.
+#[derive(Debug)]
+pub enum Error {
+    /// The batch uses an algorithm that is not enabled in this build.
+    Unsupported(Compression),
+    /// The messages could not be encoded, or the decompressed batch could not be decoded.
+    Postcard(postcard::Error),
+    /// The batch could not be compressed.
+    Compress(String),
+    /// The batch could not be decompressed.
+    Decompress(String),
+    /// The batch decompressed to a different size than it declared.
+    LengthMismatch {
+        /// The size declared by the batch.
+        expected: usize,
+        /// The size of the decompressed data.
+        actual: usize,
+    },
+    /// The batch is larger than the limit.
+    TooLarge(usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Unsupported(c) => write!(f, "compression algorithm {c:?} is not enabled"),
+            Error::Postcard(e) => write!(f, "failed to encode or decode messages: {e}"),
+            Error::Compress(e) => write!(f, "failed to compress batch: {e}"),
+            Error::Decompress(e) => write!(f, "failed to decompress batch: {e}"),
+            Error::LengthMismatch { expected, actual } => write!(
+                f,
+                "batch decompressed to {actual} bytes, but declared {expected}"
+            ),
+            Error::TooLarge(len) => write!(f, "batch of {len} bytes is above the limit"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A compression algorithm.
+///
+/// Every algorithm can be named on the wire, whether or not it is enabled in
+/// this build. Positional formats such as postcard write it as its
+/// discriminant, so new algorithms are only added at the end.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Compression {
+    /// The payload is stored as-is.
+    #[default]
+    None = 0,
+    /// Raw DEFLATE, using `miniz_oxide`. Requires the `deflate` feature.
+    Deflate = 1,
+    /// LZ4 block format, using `lz4_flex`. Requires the `lz4` feature.
+    Lz4 = 2,
+    /// Zstandard, using `zstd`. Requires the `zstd` feature.
+    Zstd = 3,
+}
+
+impl Compression {
+    /// The algorithms enabled in this build, in order of preference.
+    pub fn supported() -> &'static [Compression] {
+        &[
+            #[cfg(feature = "zstd")]
+            Compression::Zstd,
+            #[cfg(feature = "lz4")]
+            Compression::Lz4,
+            #[cfg(feature = "deflate")]
+            Compression::Deflate,
+            Compression::None,
+        ]
+    }
+
+    /// Returns `true` if this algorithm is enabled in this build.
+    pub fn is_supported(&self) -> bool {
+        Self::supported().contains(self)
+    }
+
+    /// Pick the most preferred algorithm of this build that the peer also
+    /// supports, falling back to [`Compression::None`].
+    pub fn negotiate(peer: &[Compression]) -> Compression {
+        Self::supported()
+            .iter()
+            .copied()
+            .find(|c| peer.contains(c))
+            .unwrap_or(Compression::None)
+    }
+
+    /// Compress `data` with this algorithm.
+    pub fn compress(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        match self {
+            Compression::None => Ok(data.to_vec()),
+            #[cfg(feature = "deflate")]
+            Compression::Deflate => Ok(miniz_oxide::deflate::compress_to_vec(data, 6)),
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => Ok(lz4_flex::block::compress(data)),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => {
+                zstd::bulk::compress(data, 0).map_err(|e| Error::Compress(e.to_string()))
+            }
+            #[allow(unreachable_patterns)]
+            other => Err(Error::Unsupported(*other)),
+        }
+    }
+
+    /// Decompress `data`, which must decompress to exactly `len` bytes.
+    pub fn decompress(&self, data: &[u8], len: usize) -> Result<Vec<u8>, Error> {
+        let out = match self {
+            Compression::None => data.to_vec(),
+            #[cfg(feature = "deflate")]
+            Compression::Deflate => miniz_oxide::inflate::decompress_to_vec_with_limit(data, len)
+                .map_err(|e| Error::Decompress(e.to_string()))?,
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => lz4_flex::block::decompress(data, len)
+                .map_err(|e| Error::Decompress(e.to_string()))?,
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => {
+                zstd::bulk::decompress(data, len).map_err(|e| Error::Decompress(e.to_string()))?
+            }
+            #[allow(unreachable_patterns)]
+            other => return Err(Error::Unsupported(*other)),
+        };
+        if out.len() != len {
+            return Err(Error::LengthMismatch {
+                expected: len,
+                actual: out.len(),
+            });
+        }
+        Ok(out)
+    }
+}
+
+/// A batch of wire messages, encoded with postcard and then compressed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompressedBatch {
+    /// The algorithm used to compress `data`.
+    pub compression: Compression,
+    /// The size of the batch before compression.
+    pub len: u32,
+    /// The compressed messages.
+    pub data: Vec<u8>,
+}
+
+impl CompressedBatch {
+    /// The default limit on the decompressed size of a batch, 16 MiB.
+    pub const DEFAULT_LIMIT: usize = 16 * 1024 * 1024;
+
+    /// Encode and compress `messages`.
+    pub fn new<F: Form>(
+        compression: Compression,
+        messages: &[TracingWire<'_, F>],
+    ) -> Result<Self, Error> {
+        let raw = postcard::to_allocvec(messages).map_err(Error::Postcard)?;
+        Self::from_raw(compression, &raw)
+    }
+
+    /// Compress an already encoded batch.
+    pub fn from_raw(compression: Compression, raw: &[u8]) -> Result<Self, Error> {
+        let len = u32::try_from(raw.len()).map_err(|_| Error::TooLarge(raw.len()))?;
+        Ok(Self {
+            compression,
+            len,
+            data: compression.compress(raw)?,
+        })
+    }
+
+    /// Decompress the batch, refusing batches larger than [`Self::DEFAULT_LIMIT`].
+    pub fn decompress(&self) -> Result<Vec<u8>, Error> {
+        self.decompress_with_limit(Self::DEFAULT_LIMIT)
+    }
+
+    /// Decompress the batch, refusing batches that declare more than `limit` bytes.
+    ///
+    /// The declared size comes from the sender, so the limit bounds the memory a
+    /// malicious or corrupted batch can make the decoder allocate.
+    pub fn decompress_with_limit(&self, limit: usize) -> Result<Vec<u8>, Error> {
+        let len = self.len as usize;
+        if len > limit {
+            return Err(Error::TooLarge(len));
+        }
+        self.compression.decompress(&self.data, len)
+    }
+
+    /// Decode the messages of a decompressed batch, borrowing strings from `raw`.
+    pub fn messages(raw: &[u8]) -> Result<Vec<TracingWire<'_>>, Error> {
+        postcard::from_bytes(raw).map_err(Error::Postcard)
+    }
+}
@@ -0,0 +1,249 @@
+//! Authenticating frames with a message authentication code.
+//!
+//! A collector receiving telemetry over a network it does not trust can tell
+//! forged frames from genuine ones if every frame ends with a tag computed
+//! over it with a key only the producers and the collector know. A
+//! [`Signer`] appends the tag before COBS encoding, and a [`Verifier`]
+//! checks and strips it. The code is any [`Mac`], such as an [`Hmac`]:
+//!
+//! ```rust
+//! use sha2::Sha256;
+//! use tracing_serde_structured::{
+//!     crypto::{Error, Hmac, Signer, Verifier},
+//!     SerializeLevel,
+//! };
+//!
+//! let key = b"shared secret";
+//! let signer = Signer::<Hmac<Sha256>>::new(key).unwrap();
+//! let mut buf = [0u8; 64];
+//! let mut frame = signer.to_slice_cobs(&SerializeLevel::Warn, &mut buf).unwrap().to_vec();
+//! frame.pop();
+//!
+//! let verifier = Verifier::<Hmac<Sha256>>::new(key).unwrap();
+//! let level: SerializeLevel = verifier.from_bytes_cobs(&mut frame.clone()).unwrap();
+//! assert_eq!(level, SerializeLevel::Warn);
+//!
+//! let forger = Verifier::<Hmac<Sha256>>::new(b"guess").unwrap();
+//! assert!(matches!(
+//!     forger.from_bytes_cobs::<SerializeLevel>(&mut frame),
+//!     Err(Error::Forged)
+//! ));
+//! ```
+//!
+//! A batch of messages, such as one compressed with the
+//! [`compression`](crate::compression) module, is sent as one frame, and so
+//! authenticated with one tag.
+//!
+//! A tag only shows that a frame was sent by someone with the key, not that
+//! it was sent just once, or in that order. With the `audit` feature,
+//! [`Signer::to_slice_cobs_chained`] also chains every frame to the one
+//! before it (see the [`audit`](crate::audit) module), and the tag covers the
+//! chain's hash, so replayed, reordered and dropped frames are found too.
+//!
+//! [`FramedWriter::write_signed`](crate::embedded_io::FramedWriter::write_signed)
+//! sends signed frames to an `embedded_io` sink, and with the `host` feature,
+//! [`Decoder::set_authentication`](crate::host::Decoder::set_authentication)
+//! rejects forged frames while decoding.
+//!
+//! With `alloc`, the [`fields`] module encrypts the values of sensitive
+//! fields, rather than authenticating whole frames.
+
+use core::fmt;
+
+use ::postcard::ser_flavors::{Cobs, Flavor, Slice};
+use digest::{InvalidLength, KeyInit, Mac};
+use serde::{Deserialize, Serialize};
+
+pub use hmac::Hmac;
+
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub mod fields;
+
+/// Errors returned when verifying a frame.
+#[derive(Debug)]
+pub enum Error {
+    /// The frame is not valid COBS, or too short to end with a tag.
+    Frame,
+    /// The frame's tag is wrong, so it was altered, or sent by someone
+    /// without the key.
+    Forged,
+    /// The frame's tag is right, but its message could not be decoded.
+    Decode(::postcard::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Frame => f.write_str("frame is too short or not valid COBS"),
+            Error::Forged => f.write_str("frame's authentication tag is wrong"),
+            Error::Decode(e) => write!(f, "failed to decode message: {e}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Decode(e) => Some(e),
+            Error::Frame | Error::Forged => None,
+        }
+    }
+}
+
+/// Encodes messages into frames that end with a tag.
+#[derive(Clone)]
+pub struct Signer<M> {
+    mac: M,
+}
+
+impl<M> fmt::Debug for Signer<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Signer").finish_non_exhaustive()
+    }
+}
+
+impl<M: Mac + KeyInit + Clone> Signer<M> {
+    /// A signer using `key`, which may be of any length for an [`Hmac`].
+    pub fn new(key: &[u8]) -> Result<Self, InvalidLength> {
+        Ok(Self {
+            mac: <M as KeyInit>::new_from_slice(key)?,
+        })
+    }
+
+    /// Encodes `value` into `buf` as one COBS frame, including its
+    /// terminating zero byte, as with [`postcard::to_slice_cobs`](::postcard::to_slice_cobs).
+    ///
+    /// The frame takes the size of the tag more than a plain one.
+    pub fn to_slice_cobs<'b, T>(
+        &self,
+        value: &T,
+        buf: &'b mut [u8],
+    ) -> Result<&'b mut [u8], ::postcard::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let flavor = self.signed(Cobs::try_new(Slice::new(buf))?);
+        ::postcard::serialize_with_flavor(value, flavor)
+    }
+
+    /// Encodes `value` into `buf` as one frame of `chain`, as with
+    /// [`Chain::to_slice_cobs`](crate::audit::Chain::to_slice_cobs), with the
+    /// tag after the chain's hash.
+    #[cfg(feature = "audit")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "audit")))]
+    pub fn to_slice_cobs_chained<'b, T, D>(
+        &self,
+        value: &T,
+        chain: &mut crate::audit::Chain<D>,
+        buf: &'b mut [u8],
+    ) -> Result<&'b mut [u8], ::postcard::Error>
+    where
+        T: Serialize + ?Sized,
+        D: digest::Digest,
+    {
+        let flavor = chain.chained(self.signed(Cobs::try_new(Slice::new(buf))?));
+        ::postcard::serialize_with_flavor(value, flavor)
+    }
+
+    /// A flavor ending the frames encoded with `inner` with a tag.
+    pub(crate) fn signed<F: Flavor>(&self, inner: F) -> Signed<M, F> {
+        Signed {
+            mac: self.mac.clone(),
+            inner,
+        }
+    }
+}
+
+/// A flavor computing the tag of the message as it is encoded, and appending
+/// it.
+pub(crate) struct Signed<M, F> {
+    mac: M,
+    inner: F,
+}
+
+impl<M: Mac, F: Flavor> Flavor for Signed<M, F> {
+    type Output = F::Output;
+
+    fn try_push(&mut self, data: u8) -> ::postcard::Result<()> {
+        Mac::update(&mut self.mac, &[data]);
+        self.inner.try_push(data)
+    }
+
+    fn try_extend(&mut self, data: &[u8]) -> ::postcard::Result<()> {
+        Mac::update(&mut self.mac, data);
+        self.inner.try_extend(data)
+    }
+
+    fn finalize(self) -> ::postcard::Result<F::Output> {
+        let Signed { mac, mut inner } = self;
+        inner.try_extend(&mac.finalize().into_bytes())?;
+        inner.finalize()
+    }
+}
+
+/// Checks the tags at the end of frames.
+#[derive(Clone)]
+pub struct Verifier<M> {
+    mac: M,
+}
+
+impl<M> fmt::Debug for Verifier<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Verifier").finish_non_exhaustive()
+    }
+}
+
+impl<M: Mac + KeyInit + Clone> Verifier<M> {
+    /// A verifier expecting tags computed with `key`.
+    pub fn new(key: &[u8]) -> Result<Self, InvalidLength> {
+        Ok(Self {
+            mac: <M as KeyInit>::new_from_slice(key)?,
+        })
+    }
+
+    /// Checks the tag of a frame that has already been COBS decoded, in
+    /// constant time, and returns the encoded message in it.
+    pub fn verify<'f>(&self, frame: &'f [u8]) -> Result<&'f [u8], Error> {
+        let at = frame
+            .len()
+            .checked_sub(M::output_size())
+            .ok_or(Error::Frame)?;
+        let (msg, tag) = frame.split_at(at);
+        let mut mac = self.mac.clone();
+        Mac::update(&mut mac, msg);
+        mac.verify_slice(tag).map_err(|_| Error::Forged)?;
+        Ok(msg)
+    }
+
+    /// Decodes one COBS frame in place, without its terminating zero byte,
+    /// then checks it as with [`verify`](Self::verify).
+    pub fn open<'f>(&self, frame: &'f mut [u8]) -> Result<&'f [u8], Error> {
+        let len = cobs::decode_in_place(frame).map_err(|_| Error::Frame)?;
+        self.verify(&frame[..len])
+    }
+
+    /// Decodes one COBS frame in place, without its terminating zero byte,
+    /// checks it, and deserializes its message, as with
+    /// [`postcard::from_bytes_cobs`](::postcard::from_bytes_cobs).
+    pub fn from_bytes_cobs<'f, T>(&self, frame: &'f mut [u8]) -> Result<T, Error>
+    where
+        T: Deserialize<'f>,
+    {
+        ::postcard::from_bytes(self.open(frame)?).map_err(Error::Decode)
+    }
+}
+
+/// A [`Verifier`] with its code erased, for the host decoder.
+#[cfg(feature = "host")]
+pub(crate) trait Authenticate: fmt::Debug + Send {
+    fn verify<'f>(&self, frame: &'f [u8]) -> Result<&'f [u8], Error>;
+}
+
+#[cfg(feature = "host")]
+impl<M: Mac + KeyInit + Clone + Send> Authenticate for Verifier<M> {
+    fn verify<'f>(&self, frame: &'f [u8]) -> Result<&'f [u8], Error> {
+        Verifier::verify(self, frame)
+    }
+}
@@ -0,0 +1,327 @@
+//! Encrypting the values of sensitive fields.
+//!
+//! Traces often pass through systems, such as collectors, queues and log
+//! storage, that are not cleared for everything the traced program handles.
+//! A [`FieldEncryptor`] encrypts the values of the fields whose names match
+//! its patterns with an AEAD the application provides, before the message is
+//! serialized, and the [`FieldDecryptor`] at the other end restores them:
+//!
+//! ```rust
+//! use chacha20poly1305::{aead::{KeyInit, OsRng}, ChaCha20Poly1305};
+//! use tracing_serde_structured::{
+//!     builder::SerializeEventBuilder,
+//!     crypto::fields::{self, FieldDecryptor, FieldEncryptor},
+//!     SerializeRecordFields, SerializeValue,
+//! };
+//!
+//! let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+//! let mut encryptor = FieldEncryptor::new(ChaCha20Poly1305::new(&key), OsRng);
+//! encryptor.add("user.*");
+//!
+//! let mut event = SerializeEventBuilder::new()
+//!     .field("user.email", "ferris@example.com")
+//!     .field("status", 200u64)
+//!     .build();
+//! assert_eq!(encryptor.encrypt_fields(&mut event.fields).unwrap(), 1);
+//!
+//! let SerializeRecordFields::De(map) = &event.fields else { unreachable!() };
+//! let email = map.iter().find(|(k, _)| k.as_str() == "user.email").unwrap().1;
+//! assert!(fields::is_encrypted(email));
+//!
+//! let decryptor = FieldDecryptor::new(ChaCha20Poly1305::new(&key));
+//! assert_eq!(decryptor.decrypt_fields(&mut event.fields).unwrap(), 1);
+//! let SerializeRecordFields::De(map) = &event.fields else { unreachable!() };
+//! let email = map.iter().find(|(k, _)| k.as_str() == "user.email").unwrap().1;
+//! assert_eq!(*email, SerializeValue::from("ferris@example.com"));
+//! ```
+//!
+//! An encrypted value is a string: [`PREFIX`], then the nonce and the
+//! ciphertext of the postcard encoded value, in unpadded URL-safe base64. So
+//! it passes through every format and every consumer unchanged, and decrypts
+//! back to the same kind of value. The field's name is authenticated along
+//! with the value, so a ciphertext cannot be moved to another field.
+//!
+//! Field names are matched as with [`Query::target`](crate::query::Query::target),
+//! with `*` standing for any number of characters, and `?` for any one.
+
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+use aead::{
+    generic_array::typenum::Unsigned,
+    rand_core::{CryptoRng, RngCore},
+    AeadInPlace, Nonce,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+
+use crate::{
+    glob::Glob, wire::TracingWire, CowString, DebugRecord, Form, RecordMap, SerializeRecord,
+    SerializeRecordFields, SerializeValue,
+};
+
+/// The prefix of encrypted values.
+pub const PREFIX: &str = "enc:";
+
+/// Errors returned when encrypting or decrypting a value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The AEAD failed to encrypt the value, e.g. because it is too long.
+    Encrypt,
+    /// A value starts with [`PREFIX`], but is not a valid ciphertext.
+    Malformed,
+    /// The ciphertext does not decrypt with the key, or was altered.
+    Decrypt,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Error::Encrypt => "failed to encrypt value",
+            Error::Malformed => "encrypted value is malformed",
+            Error::Decrypt => "failed to decrypt value",
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// Whether `value` was encrypted by a [`FieldEncryptor`].
+pub fn is_encrypted(value: &SerializeValue<'_>) -> bool {
+    matches!(value, SerializeValue::Str(s) if s.starts_with(PREFIX))
+}
+
+/// Encrypts the values of the fields whose names match its patterns.
+pub struct FieldEncryptor<A, R> {
+    aead: A,
+    rng: R,
+    patterns: Vec<Glob>,
+}
+
+impl<A, R> fmt::Debug for FieldEncryptor<A, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FieldEncryptor")
+            .field("patterns", &self.patterns)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<A: AeadInPlace, R: CryptoRng + RngCore> FieldEncryptor<A, R> {
+    /// An encryptor using `aead`, with nonces drawn from `rng`, that matches
+    /// no fields yet.
+    pub fn new(aead: A, rng: R) -> Self {
+        Self {
+            aead,
+            rng,
+            patterns: Vec::new(),
+        }
+    }
+
+    /// Encrypts the fields whose names match `pattern` as well.
+    pub fn add(&mut self, pattern: &str) {
+        self.patterns.push(Glob::new(pattern));
+    }
+
+    /// Whether the values of fields named `field` are encrypted.
+    pub fn matches(&self, field: &str) -> bool {
+        self.patterns.iter().any(|glob| glob.matches(field))
+    }
+
+    /// Encrypts `value`, recorded in a field named `field`, whether or not
+    /// the field matches.
+    pub fn encrypt_value<'a>(
+        &mut self,
+        field: &str,
+        value: &SerializeValue<'_>,
+    ) -> Result<SerializeValue<'a>, Error> {
+        let mut nonce = Nonce::<A>::default();
+        self.rng.fill_bytes(&mut nonce);
+        let mut buf = ::postcard::to_extend(value, Vec::new()).map_err(|_| Error::Encrypt)?;
+        self.aead
+            .encrypt_in_place(&nonce, field.as_bytes(), &mut buf)
+            .map_err(|_| Error::Encrypt)?;
+
+        let mut encrypted = String::from(PREFIX);
+        URL_SAFE_NO_PAD.encode_string([&nonce[..], &buf[..]].concat(), &mut encrypted);
+        Ok(SerializeValue::Str(CowString::Owned(encrypted)))
+    }
+
+    /// Encrypts the values of the matching fields, copying the fields of a
+    /// live event first, and returns how many were encrypted.
+    ///
+    /// Values that are already encrypted are left alone. If encrypting a
+    /// value fails, the others may have been encrypted already, and the
+    /// fields must not be sent.
+    pub fn encrypt_fields<F: Form>(
+        &mut self,
+        fields: &mut SerializeRecordFields<'_, F>,
+    ) -> Result<usize, Error> {
+        fields.ensure_owned();
+        match fields {
+            SerializeRecordFields::De(map) => self.encrypt_map(map),
+            SerializeRecordFields::Ser(_) => unreachable!("fields were just copied"),
+        }
+    }
+
+    /// Encrypts the values of the matching fields recorded on a span, as
+    /// with [`encrypt_fields`](Self::encrypt_fields).
+    pub fn encrypt_record<F: Form>(
+        &mut self,
+        values: &mut SerializeRecord<'_, F>,
+    ) -> Result<usize, Error> {
+        values.ensure_owned();
+        match values {
+            SerializeRecord::De(map) => self.encrypt_map(map),
+            SerializeRecord::Ser(_) => unreachable!("values were just copied"),
+        }
+    }
+
+    /// Encrypts the values of the matching fields of an event, or of values
+    /// recorded on a span, as with [`encrypt_fields`](Self::encrypt_fields).
+    ///
+    /// Other messages carry no values, except for
+    /// [`TracingWire::EventInterned`], whose field names are not known
+    /// without the stream's definitions, and which is left alone.
+    pub fn encrypt_message<F: Form>(
+        &mut self,
+        msg: &mut TracingWire<'_, F>,
+    ) -> Result<usize, Error> {
+        match msg {
+            TracingWire::Event(event) => self.encrypt_fields(&mut event.fields),
+            TracingWire::EventRef(event) => self.encrypt_fields(&mut event.fields),
+            TracingWire::Record { values, .. } => self.encrypt_record(values),
+            _ => Ok(0),
+        }
+    }
+
+    fn encrypt_map(&mut self, map: &mut RecordMap<'_>) -> Result<usize, Error> {
+        let mut count = 0;
+        for (field, value) in map.iter_mut() {
+            if is_encrypted(value) || !self.matches(field.as_str()) {
+                continue;
+            }
+            *value = self.encrypt_value(field.as_str(), value)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+/// Decrypts the values encrypted by a [`FieldEncryptor`].
+pub struct FieldDecryptor<A> {
+    aead: A,
+}
+
+impl<A> fmt::Debug for FieldDecryptor<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FieldDecryptor").finish_non_exhaustive()
+    }
+}
+
+impl<A: AeadInPlace> FieldDecryptor<A> {
+    /// A decryptor using `aead`, with the same key as the encryptor.
+    pub fn new(aead: A) -> Self {
+        Self { aead }
+    }
+
+    /// Decrypts `value`, recorded in a field named `field`, or returns
+    /// `None` if it is not encrypted.
+    pub fn decrypt_value<'a>(
+        &self,
+        field: &str,
+        value: &SerializeValue<'_>,
+    ) -> Result<Option<SerializeValue<'a>>, Error> {
+        let encoded = match value {
+            SerializeValue::Str(s) => match s.strip_prefix(PREFIX) {
+                Some(encoded) => encoded,
+                None => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+        let mut buf = URL_SAFE_NO_PAD
+            .decode(encoded)
+            .map_err(|_| Error::Malformed)?;
+        if buf.len() < A::NonceSize::USIZE {
+            return Err(Error::Malformed);
+        }
+        let mut ciphertext = buf.split_off(A::NonceSize::USIZE);
+        self.aead
+            .decrypt_in_place(
+                Nonce::<A>::from_slice(&buf),
+                field.as_bytes(),
+                &mut ciphertext,
+            )
+            .map_err(|_| Error::Decrypt)?;
+        let value: SerializeValue<'_> =
+            ::postcard::from_bytes(&ciphertext).map_err(|_| Error::Malformed)?;
+        Ok(Some(copied(&value)))
+    }
+
+    /// Decrypts the encrypted values of `fields`, and returns how many were
+    /// decrypted.
+    ///
+    /// If decrypting a value fails, the others may have been decrypted
+    /// already.
+    pub fn decrypt_fields<F: Form>(
+        &self,
+        fields: &mut SerializeRecordFields<'_, F>,
+    ) -> Result<usize, Error> {
+        match fields {
+            SerializeRecordFields::De(map) => self.decrypt_map(map),
+            // A live event has not been encrypted.
+            SerializeRecordFields::Ser(_) => Ok(0),
+        }
+    }
+
+    /// Decrypts the encrypted values recorded on a span, as with
+    /// [`decrypt_fields`](Self::decrypt_fields).
+    pub fn decrypt_record<F: Form>(
+        &self,
+        values: &mut SerializeRecord<'_, F>,
+    ) -> Result<usize, Error> {
+        match values {
+            SerializeRecord::De(map) => self.decrypt_map(map),
+            SerializeRecord::Ser(_) => Ok(0),
+        }
+    }
+
+    /// Decrypts the encrypted values of an event, or of values recorded on a
+    /// span, as with [`decrypt_fields`](Self::decrypt_fields).
+    pub fn decrypt_message<F: Form>(&self, msg: &mut TracingWire<'_, F>) -> Result<usize, Error> {
+        match msg {
+            TracingWire::Event(event) => self.decrypt_fields(&mut event.fields),
+            TracingWire::EventRef(event) => self.decrypt_fields(&mut event.fields),
+            TracingWire::Record { values, .. } => self.decrypt_record(values),
+            _ => Ok(0),
+        }
+    }
+
+    fn decrypt_map(&self, map: &mut RecordMap<'_>) -> Result<usize, Error> {
+        let mut count = 0;
+        for (field, value) in map.iter_mut() {
+            if let Some(decrypted) = self.decrypt_value(field.as_str(), value)? {
+                *value = decrypted;
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+}
+
+/// Copies `value`, for any lifetime.
+fn copied<'a>(value: &SerializeValue<'_>) -> SerializeValue<'a> {
+    match value {
+        SerializeValue::Debug(DebugRecord::De(s)) => {
+            SerializeValue::Debug(DebugRecord::De(CowString::copied(s)))
+        }
+        SerializeValue::Debug(DebugRecord::Ser(never)) => match *never {},
+        SerializeValue::Str(s) => SerializeValue::Str(CowString::copied(s)),
+        SerializeValue::F64(x) => SerializeValue::F64(*x),
+        SerializeValue::I64(x) => SerializeValue::I64(*x),
+        SerializeValue::U64(x) => SerializeValue::U64(*x),
+        SerializeValue::Bool(x) => SerializeValue::Bool(*x),
+        #[cfg(feature = "alloc")]
+        SerializeValue::Tree(t) => SerializeValue::Tree(t.to_owned()),
+        SerializeValue::Unknown => SerializeValue::Unknown,
+    }
+}
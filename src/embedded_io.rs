@@ -0,0 +1,486 @@
+//! Writing COBS framed postcard messages to an [`embedded_io::Write`] sink.
+//!
+//! A [`FramedWriter`] encodes each message into its own buffer, frames it
+//! with [COBS], and writes it to any `embedded_io::Write` implementation,
+//! such as a HAL's UART or USB CDC class:
+//!
+//! ```rust
+//! use tracing_serde_structured::{embedded_io::FramedWriter, SerializeLevel};
+//!
+//! let mut out = [0u8; 16];
+//! let mut writer = FramedWriter::<_, 64>::new(&mut out[..]);
+//! writer.write(&SerializeLevel::Warn).unwrap();
+//! writer.write(&SerializeLevel::Info).unwrap();
+//!
+//! assert_eq!(out[..6], [0x02, 0x03, 0x00, 0x02, 0x02, 0x00]);
+//! ```
+//!
+//! Every frame ends with a zero byte, and contains no other zero bytes, so a
+//! reader that starts in the middle of a stream (or loses bytes) can skip to
+//! the next zero and carry on from there. Decode each frame with
+//! [`postcard::from_bytes_cobs`].
+//!
+//! Writers created [`with_framing`](FramedWriter::with_framing) a
+//! [`LengthPrefixed`](crate::framing::LengthPrefixed) framing start each
+//! frame with its length instead, which suits TCP connections and files
+//! better (see the [`framing`](crate::framing) module).
+//!
+//! With the `embedded-io-async` feature, [`AsyncFramedWriter`] does the same
+//! for [`embedded_io_async::Write`] sinks.
+//!
+//! Both writers count the messages they send and lose, so a device can
+//! report whether its trace data is getting through. With a stats interval
+//! set, [`FramedWriter::stats_due`] returns the counts every so often, to be
+//! sent like any other message:
+//!
+//! ```rust
+//! use tracing_serde_structured::{embedded_io::FramedWriter, wire::TracingWire};
+//!
+//! let mut out = [0u8; 64];
+//! let mut writer = FramedWriter::<_, 64>::new(&mut out[..]);
+//! writer.set_stats_interval(Some(100));
+//! # let msg: TracingWire<'_> = TracingWire::Exit(tracing_serde_structured::SerializeId { id: 1.try_into().unwrap() });
+//!
+//! writer.write(&msg).ok();
+//! if let Some(stats) = writer.stats_due() {
+//!     let stats: TracingWire<'_> = TracingWire::PipelineStats(stats);
+//!     writer.write(&stats).ok();
+//! }
+//! assert_eq!(writer.stats().sent, 1);
+//! ```
+//!
+//! [COBS]: https://en.wikipedia.org/wiki/Consistent_Overhead_Byte_Stuffing
+
+use core::fmt;
+
+use serde::Serialize;
+
+use crate::{
+    framing::{Cobs, Framing},
+    wire::SerializePipelineStats,
+};
+
+/// Errors returned when writing a frame.
+#[derive(Debug)]
+pub enum Error<E> {
+    /// The message could not be encoded, e.g. because its frame is longer
+    /// than the writer's buffer.
+    Encode(::postcard::Error),
+    /// Writing the frame to the sink failed.
+    Io(E),
+}
+
+impl<E: fmt::Debug> fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Encode(e) => write!(f, "failed to encode message: {e}"),
+            Error::Io(e) => write!(f, "failed to write frame: {e:?}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Debug> std::error::Error for Error<E> {}
+
+/// Writes messages as framed postcard to `W`, encoding each frame in a
+/// buffer of `N` bytes.
+///
+/// Frames are COBS framed, unless the writer is created
+/// [`with_framing`](Self::with_framing) some other [`Framing`]. A COBS frame
+/// takes one byte more than the encoded message, plus one byte for every 254
+/// bytes of it.
+pub struct FramedWriter<W, const N: usize, F = Cobs> {
+    writer: W,
+    buf: [u8; N],
+    framing: F,
+    stats: Stats,
+}
+
+impl<W, const N: usize, F> fmt::Debug for FramedWriter<W, N, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FramedWriter")
+            .field("buf_len", &N)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<W: ::embedded_io::Write, const N: usize> FramedWriter<W, N> {
+    pub fn new(writer: W) -> Self {
+        Self::with_framing(writer, Cobs)
+    }
+}
+
+impl<W: ::embedded_io::Write, const N: usize, F: Framing> FramedWriter<W, N, F> {
+    /// A writer framing messages with `framing`, such as
+    /// [`LengthPrefixed`](crate::framing::LengthPrefixed).
+    pub fn with_framing(writer: W, framing: F) -> Self {
+        Self {
+            writer,
+            buf: [0; N],
+            framing,
+            stats: Stats::default(),
+        }
+    }
+
+    /// Encodes `value` and writes it to the sink as one frame.
+    ///
+    /// Nothing is written if the message cannot be encoded.
+    pub fn write<T>(&mut self, value: &T) -> Result<(), Error<W::Error>>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.encode_with(|framing, buf| framing.to_slice(value, buf))
+    }
+
+    /// Encodes a frame with `encode`, which is given the writer's buffer and
+    /// returns the frame in it, framed as the writer frames messages
+    /// (including a COBS frame's terminating zero byte), then writes it to the
+    /// sink, as [`write`](Self::write) does.
+    ///
+    /// This is for frames the other methods do not cover, such as those
+    /// encoded by `Signer::to_slice_cobs_chained`, which are both chained and
+    /// signed.
+    pub fn write_with<E>(&mut self, encode: E) -> Result<(), Error<W::Error>>
+    where
+        E: FnOnce(&mut [u8]) -> Result<&mut [u8], ::postcard::Error>,
+    {
+        self.encode_with(|_, buf| encode(buf))
+    }
+
+    /// Encodes a frame with `encode`, which is given the writer's framing
+    /// and buffer, and writes it to the sink.
+    fn encode_with<E>(&mut self, encode: E) -> Result<(), Error<W::Error>>
+    where
+        E: for<'b> FnOnce(&F, &'b mut [u8]) -> Result<&'b mut [u8], ::postcard::Error>,
+    {
+        let result = match encode(&self.framing, &mut self.buf) {
+            Ok(frame) => self.writer.write_all(frame).map_err(Error::Io),
+            Err(e) => Err(Error::Encode(e)),
+        };
+        self.stats.count(&result);
+        result
+    }
+
+    /// Encodes `value` and writes it to the sink as one frame of an audit
+    /// stream, embedding the hash of the previous frame of `chain` (see the
+    /// [`audit`](crate::audit) module).
+    ///
+    /// Nothing is written if the message cannot be encoded. If writing the
+    /// frame fails, the chain has still moved on, and the reader will find it
+    /// broken at the next frame.
+    #[cfg(feature = "audit")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "audit")))]
+    pub fn write_chained<T, D>(
+        &mut self,
+        value: &T,
+        chain: &mut crate::audit::Chain<D>,
+    ) -> Result<(), Error<W::Error>>
+    where
+        T: Serialize + ?Sized,
+        D: digest::Digest,
+    {
+        self.encode_with(|framing, buf| {
+            ::postcard::serialize_with_flavor(value, chain.chained(framing.flavor(buf)?))
+        })
+    }
+
+    /// Encodes `value` and writes it to the sink as one frame ending with
+    /// `checksum` (see the [`checksum`](crate::checksum) module).
+    #[cfg(feature = "checksum")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "checksum")))]
+    pub fn write_checked<T>(
+        &mut self,
+        value: &T,
+        checksum: crate::checksum::Checksum,
+    ) -> Result<(), Error<W::Error>>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.encode_with(|framing, buf| {
+            ::postcard::serialize_with_flavor(value, checksum.checked(framing.flavor(buf)?))
+        })
+    }
+
+    /// Encodes `value` and writes it to the sink as one frame ending with a
+    /// tag computed by `signer` (see the [`crypto`](crate::crypto) module).
+    #[cfg(feature = "crypto")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "crypto")))]
+    pub fn write_signed<T, M>(
+        &mut self,
+        value: &T,
+        signer: &crate::crypto::Signer<M>,
+    ) -> Result<(), Error<W::Error>>
+    where
+        T: Serialize + ?Sized,
+        M: digest::Mac + digest::KeyInit + Clone,
+    {
+        self.encode_with(|framing, buf| {
+            ::postcard::serialize_with_flavor(value, signer.signed(framing.flavor(buf)?))
+        })
+    }
+
+    /// Flushes the sink.
+    pub fn flush(&mut self) -> Result<(), W::Error> {
+        self.writer.flush()
+    }
+
+    /// The counts of messages sent and lost so far.
+    pub fn stats(&self) -> SerializePipelineStats {
+        self.stats.counts
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats.counts = SerializePipelineStats::default();
+    }
+
+    /// Have [`stats_due`](Self::stats_due) return the counts after every
+    /// `interval` messages, or never with `None` (the default).
+    pub fn set_stats_interval(&mut self, interval: Option<u32>) {
+        self.stats.interval = interval;
+    }
+
+    /// Returns the counts if a stats interval is set, and that many messages
+    /// have been written since they were last returned.
+    pub fn stats_due(&mut self) -> Option<SerializePipelineStats> {
+        self.stats.due()
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Writes `msg` as a final frame, and flushes the sink.
+#[cfg(feature = "panic-flush")]
+impl<W: ::embedded_io::Write, const N: usize, F: Framing> crate::panic_flush::FinalFlush
+    for FramedWriter<W, N, F>
+{
+    fn final_flush(&mut self, msg: &crate::wire::TracingWire<'_, crate::Live>) {
+        let _ = self.write(msg);
+        let _ = self.flush();
+    }
+}
+
+/// Writes messages as framed postcard to the async sink `W`, encoding each
+/// frame in a buffer of `N` bytes.
+///
+/// See [`FramedWriter`] for the frame format.
+#[cfg(feature = "embedded-io-async")]
+#[cfg_attr(docsrs, doc(cfg(feature = "embedded-io-async")))]
+pub struct AsyncFramedWriter<W, const N: usize, F = Cobs> {
+    writer: W,
+    buf: [u8; N],
+    framing: F,
+    stats: Stats,
+}
+
+#[cfg(feature = "embedded-io-async")]
+impl<W, const N: usize, F> fmt::Debug for AsyncFramedWriter<W, N, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncFramedWriter")
+            .field("buf_len", &N)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "embedded-io-async")]
+impl<W: ::embedded_io_async::Write, const N: usize> AsyncFramedWriter<W, N> {
+    pub fn new(writer: W) -> Self {
+        Self::with_framing(writer, Cobs)
+    }
+}
+
+#[cfg(feature = "embedded-io-async")]
+impl<W: ::embedded_io_async::Write, const N: usize, F: Framing> AsyncFramedWriter<W, N, F> {
+    /// A writer framing messages with `framing`, such as
+    /// [`LengthPrefixed`](crate::framing::LengthPrefixed).
+    pub fn with_framing(writer: W, framing: F) -> Self {
+        Self {
+            writer,
+            buf: [0; N],
+            framing,
+            stats: Stats::default(),
+        }
+    }
+
+    /// Encodes `value` and writes it to the sink as one frame.
+    ///
+    /// Nothing is written if the message cannot be encoded. If the returned
+    /// future is dropped before it completes, part of the frame may have been
+    /// written, and the reader will fail to decode it together with the frame
+    /// that follows.
+    pub async fn write<T>(&mut self, value: &T) -> Result<(), Error<W::Error>>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.encode_with(|framing, buf| framing.to_slice(value, buf))
+            .await
+    }
+
+    /// Encodes a frame with `encode`, which is given the writer's buffer and
+    /// returns the frame in it, framed as the writer frames messages
+    /// (including a COBS frame's terminating zero byte), then writes it to the
+    /// sink, as [`write`](Self::write) does.
+    ///
+    /// This is for frames the other methods do not cover, such as those
+    /// encoded by `Signer::to_slice_cobs_chained`, which are both chained and
+    /// signed.
+    pub async fn write_with<E>(&mut self, encode: E) -> Result<(), Error<W::Error>>
+    where
+        E: FnOnce(&mut [u8]) -> Result<&mut [u8], ::postcard::Error>,
+    {
+        self.encode_with(|_, buf| encode(buf)).await
+    }
+
+    /// Encodes a frame with `encode`, which is given the writer's framing
+    /// and buffer, and writes it to the sink.
+    async fn encode_with<E>(&mut self, encode: E) -> Result<(), Error<W::Error>>
+    where
+        E: for<'b> FnOnce(&F, &'b mut [u8]) -> Result<&'b mut [u8], ::postcard::Error>,
+    {
+        let result = match encode(&self.framing, &mut self.buf) {
+            Ok(frame) => self.writer.write_all(frame).await.map_err(Error::Io),
+            Err(e) => Err(Error::Encode(e)),
+        };
+        self.stats.count(&result);
+        result
+    }
+
+    /// Encodes `value` and writes it to the sink as one frame of an audit
+    /// stream, embedding the hash of the previous frame of `chain` (see the
+    /// [`audit`](crate::audit) module).
+    ///
+    /// Nothing is written if the message cannot be encoded. If writing the
+    /// frame fails, the chain has still moved on, and the reader will find it
+    /// broken at the next frame.
+    #[cfg(feature = "audit")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "audit")))]
+    pub async fn write_chained<T, D>(
+        &mut self,
+        value: &T,
+        chain: &mut crate::audit::Chain<D>,
+    ) -> Result<(), Error<W::Error>>
+    where
+        T: Serialize + ?Sized,
+        D: digest::Digest,
+    {
+        self.encode_with(|framing, buf| {
+            ::postcard::serialize_with_flavor(value, chain.chained(framing.flavor(buf)?))
+        })
+        .await
+    }
+
+    /// Encodes `value` and writes it to the sink as one frame ending with
+    /// `checksum` (see the [`checksum`](crate::checksum) module).
+    #[cfg(feature = "checksum")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "checksum")))]
+    pub async fn write_checked<T>(
+        &mut self,
+        value: &T,
+        checksum: crate::checksum::Checksum,
+    ) -> Result<(), Error<W::Error>>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.encode_with(|framing, buf| {
+            ::postcard::serialize_with_flavor(value, checksum.checked(framing.flavor(buf)?))
+        })
+        .await
+    }
+
+    /// Encodes `value` and writes it to the sink as one frame ending with a
+    /// tag computed by `signer` (see the [`crypto`](crate::crypto) module).
+    #[cfg(feature = "crypto")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "crypto")))]
+    pub async fn write_signed<T, M>(
+        &mut self,
+        value: &T,
+        signer: &crate::crypto::Signer<M>,
+    ) -> Result<(), Error<W::Error>>
+    where
+        T: Serialize + ?Sized,
+        M: digest::Mac + digest::KeyInit + Clone,
+    {
+        self.encode_with(|framing, buf| {
+            ::postcard::serialize_with_flavor(value, signer.signed(framing.flavor(buf)?))
+        })
+        .await
+    }
+
+    /// Flushes the sink.
+    pub async fn flush(&mut self) -> Result<(), W::Error> {
+        self.writer.flush().await
+    }
+
+    /// The counts of messages sent and lost so far.
+    pub fn stats(&self) -> SerializePipelineStats {
+        self.stats.counts
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats.counts = SerializePipelineStats::default();
+    }
+
+    /// Have [`stats_due`](Self::stats_due) return the counts after every
+    /// `interval` messages, or never with `None` (the default).
+    pub fn set_stats_interval(&mut self, interval: Option<u32>) {
+        self.stats.interval = interval;
+    }
+
+    /// Returns the counts if a stats interval is set, and that many messages
+    /// have been written since they were last returned.
+    pub fn stats_due(&mut self) -> Option<SerializePipelineStats> {
+        self.stats.due()
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// The counts kept by a writer, and when to report them.
+#[derive(Debug, Default)]
+struct Stats {
+    counts: SerializePipelineStats,
+    interval: Option<u32>,
+    since_due: u32,
+}
+
+impl Stats {
+    fn count<E>(&mut self, result: &Result<(), Error<E>>) {
+        let count = match result {
+            Ok(()) => &mut self.counts.sent,
+            Err(Error::Io(_)) => &mut self.counts.dropped,
+            Err(Error::Encode(::postcard::Error::SerializeBufferFull)) => {
+                &mut self.counts.overflows
+            }
+            Err(Error::Encode(_)) => &mut self.counts.errors,
+        };
+        *count = count.saturating_add(1);
+        self.since_due = self.since_due.saturating_add(1);
+    }
+
+    fn due(&mut self) -> Option<SerializePipelineStats> {
+        if self.since_due < self.interval? {
+            return None;
+        }
+        self.since_due = 0;
+        Some(self.counts)
+    }
+}
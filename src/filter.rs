@@ -0,0 +1,231 @@
+//! Filtering by `RUST_LOG` style directives.
+//!
+//! [`Directives`] parse the same comma separated lists of directives as
+//! `tracing-subscriber`'s `EnvFilter`, such as `my_crate::module=debug,warn`,
+//! without depending on it. Each directive is either a level, which applies
+//! to every target, or a target and a level, which applies to that target and
+//! every target it is a prefix of. The directive with the longest matching
+//! target decides, and callsites no directive matches are disabled:
+//!
+//! ```rust
+//! use tracing_serde_structured::{filter::Directives, SerializeLevel};
+//!
+//! let filter: Directives = "warn,app=info,app::db=trace,app::net=off".parse().unwrap();
+//! assert!(filter.enabled_at("app::db::pool", SerializeLevel::Trace));
+//! assert!(!filter.enabled_at("app::ui", SerializeLevel::Debug));
+//! assert!(!filter.enabled_at("app::net", SerializeLevel::Error));
+//! assert!(filter.enabled_at("hyper", SerializeLevel::Warn));
+//! ```
+//!
+//! On the consumer side, [`Directives::enabled`] filters decoded metadata.
+//! On the producer side, a subscriber asks the directives which callsites it
+//! is interested in:
+//!
+//! ```rust
+//! # use tracing_core::{Event, Metadata, Subscriber, subscriber::Interest};
+//! # use tracing_core::span::{Attributes, Id, Record};
+//! use tracing_core::LevelFilter;
+//! use tracing_serde_structured::filter::Directives;
+//!
+//! struct Forwarder {
+//!     filter: Directives,
+//! }
+//!
+//! impl Subscriber for Forwarder {
+//!     fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+//!         self.filter.interest(metadata)
+//!     }
+//!
+//!     fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+//!         self.filter.enabled_at(metadata.target(), (*metadata.level()).into())
+//!     }
+//!
+//!     fn max_level_hint(&self) -> Option<LevelFilter> {
+//!         Some(self.filter.max_level())
+//!     }
+//!
+//!     // ...
+//!     # fn event(&self, _: &Event<'_>) {}
+//!     # fn new_span(&self, _: &Attributes<'_>) -> Id { Id::from_u64(1) }
+//!     # fn record(&self, _: &Id, _: &Record<'_>) {}
+//!     # fn record_follows_from(&self, _: &Id, _: &Id) {}
+//!     # fn enter(&self, _: &Id) {}
+//!     # fn exit(&self, _: &Id) {}
+//! }
+//! ```
+//!
+//! Directives that select spans or field values, such as `[request]=debug`,
+//! are not supported, and fail to parse.
+
+use alloc::{string::String, vec::Vec};
+use core::{fmt, str::FromStr};
+
+use tracing_core::{metadata::LevelFilter, subscriber::Interest, Metadata};
+
+use crate::{SerializeLevel, SerializeMetadata};
+
+/// A list of directives. See the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Directives {
+    /// The directives with targets, longest target first.
+    targets: Vec<Directive>,
+    /// The level of the directive without a target.
+    default: LevelFilter,
+}
+
+/// One directive, such as `app::db=trace`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Directive {
+    target: String,
+    level: LevelFilter,
+}
+
+/// The error returned when parsing invalid directives.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseDirectivesError {
+    directive: String,
+}
+
+impl fmt::Display for ParseDirectivesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid directive {:?}: expected a level, a target, or target=level",
+            self.directive
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseDirectivesError {}
+
+impl Default for Directives {
+    /// Directives that disable everything.
+    fn default() -> Self {
+        Self::new(LevelFilter::OFF)
+    }
+}
+
+impl Directives {
+    /// Directives enabling `default` and everything less verbose, for every
+    /// target.
+    pub fn new(default: LevelFilter) -> Self {
+        Self {
+            targets: Vec::new(),
+            default,
+        }
+    }
+
+    /// Adds a directive, enabling `level` and everything less verbose for
+    /// `target` and every target it is a prefix of. Replaces any directive
+    /// for the same target.
+    pub fn add(&mut self, target: &str, level: LevelFilter) {
+        match self.targets.iter_mut().find(|d| d.target == target) {
+            Some(directive) => directive.level = level,
+            None => {
+                let at = self
+                    .targets
+                    .partition_point(|d| d.target.len() >= target.len());
+                let directive = Directive {
+                    target: target.into(),
+                    level,
+                };
+                self.targets.insert(at, directive);
+            }
+        }
+    }
+
+    /// Parses directives, as with `str::parse`. An empty string disables
+    /// everything.
+    pub fn parse(directives: &str) -> Result<Self, ParseDirectivesError> {
+        let mut parsed = Self::default();
+        for directive in directives.split(',').map(str::trim) {
+            if directive.is_empty() {
+                continue;
+            }
+            let error = || ParseDirectivesError {
+                directive: directive.into(),
+            };
+            let (target, level) = match directive.split_once('=') {
+                Some((target, level)) => {
+                    let level = level.trim();
+                    if level.is_empty() {
+                        return Err(error());
+                    }
+                    (target.trim(), level.parse().map_err(|_| error())?)
+                }
+                None => match directive.parse::<LevelFilter>() {
+                    Ok(level) => {
+                        parsed.default = level;
+                        continue;
+                    }
+                    Err(_) => (directive, LevelFilter::TRACE),
+                },
+            };
+            // Brackets and braces select spans and fields in `EnvFilter`.
+            let invalid = |c: char| c.is_whitespace() || matches!(c, '[' | ']' | '{' | '}');
+            if target.is_empty() || target.contains(invalid) {
+                return Err(error());
+            }
+            parsed.add(target, level);
+        }
+        Ok(parsed)
+    }
+
+    /// The most verbose level enabled for `target`.
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.targets
+            .iter()
+            .find(|d| target.starts_with(d.target.as_str()))
+            .map_or(self.default, |d| d.level)
+    }
+
+    /// Whether a callsite in `target` at `level` is enabled.
+    pub fn enabled_at(&self, target: &str, level: SerializeLevel) -> bool {
+        level <= self.level_for(target)
+    }
+
+    /// Whether the callsite described by `metadata`, e.g. that of a decoded
+    /// event, is enabled.
+    pub fn enabled(&self, metadata: &SerializeMetadata<'_>) -> bool {
+        self.enabled_at(metadata.target.as_str(), metadata.level)
+    }
+
+    /// Whether a subscriber filtering by these directives is interested in
+    /// a callsite, for `Subscriber::register_callsite`.
+    pub fn interest(&self, metadata: &Metadata<'_>) -> Interest {
+        if self.enabled_at(metadata.target(), (*metadata.level()).into()) {
+            Interest::always()
+        } else {
+            Interest::never()
+        }
+    }
+
+    /// The most verbose level enabled for any target, for
+    /// `Subscriber::max_level_hint`.
+    pub fn max_level(&self) -> LevelFilter {
+        self.targets
+            .iter()
+            .map(|d| d.level)
+            .fold(self.default, LevelFilter::max)
+    }
+}
+
+impl FromStr for Directives {
+    type Err = ParseDirectivesError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+/// Formats the directives so that they parse back to the same, e.g.
+/// `app::db=trace,app=info,warn`.
+impl fmt::Display for Directives {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for directive in &self.targets {
+            write!(f, "{}={},", directive.target, directive.level)?;
+        }
+        write!(f, "{}", self.default)
+    }
+}
@@ -0,0 +1,292 @@
+//! Splitting a stream of bytes into frames, one message each.
+//!
+//! Two framings are provided, behind the [`Framing`] trait:
+//!
+//! * [`Cobs`] ends each frame with a zero byte, and escapes the zero bytes
+//!   in the message with [COBS]. A reader that starts in the middle of a
+//!   stream, or loses bytes, skips to the next zero and carries on from
+//!   there, which suits serial links. This is the default.
+//!
+//! * [`LengthPrefixed`] starts each frame with the length of the message, as
+//!   a varint. There is nothing to escape, so large messages take no more
+//!   space, and a reader does not have to look at every byte to find the end
+//!   of a frame, which suits TCP connections and files. A reader that loses
+//!   its place cannot find it again, though.
+//!
+//! Both ends have to use the same framing. The writers of the `embedded_io`
+//! module take it when they are created, and so do the iterators of the
+//! `host` module:
+//!
+//! ```rust
+//! use tracing_serde_structured::{
+//!     framing::{Framing, LengthPrefixed},
+//!     SerializeLevel,
+//! };
+//!
+//! let mut buf = [0u8; 16];
+//! let frame = LengthPrefixed::new().to_slice(&SerializeLevel::Warn, &mut buf).unwrap();
+//! assert_eq!(frame, [1, 3]);
+//!
+//! let level: SerializeLevel = LengthPrefixed::new().decode(frame).unwrap();
+//! assert_eq!(level, SerializeLevel::Warn);
+//! ```
+//!
+//! The frames of the [`audit`](crate::audit), [`crypto`](crate::crypto) and
+//! [`checksum`](crate::checksum) modules carry a trailer after the message;
+//! the framing goes around both.
+//!
+//! [COBS]: https://en.wikipedia.org/wiki/Consistent_Overhead_Byte_Stuffing
+
+use ::postcard::{
+    ser_flavors::{self, Flavor, Slice},
+    Error,
+};
+use serde::{Deserialize, Serialize};
+
+/// A way of framing messages, for both the producer and the consumer.
+pub trait Framing {
+    /// The flavor encoding a message into a frame.
+    type Flavor<'b>: Flavor<Output = &'b mut [u8]>;
+
+    /// A flavor encoding one message into a frame in `buf`.
+    fn flavor<'b>(&self, buf: &'b mut [u8]) -> Result<Self::Flavor<'b>, Error>;
+
+    /// Removes the framing from a frame, as split off a stream by
+    /// [`read_frame`](Self::read_frame), in place, and returns the encoded
+    /// message in it.
+    fn unframe<'f>(&self, frame: &'f mut [u8]) -> Result<&'f [u8], Error>;
+
+    /// Reads the next frame from `reader` into `buf`, replacing its
+    /// contents. Returns `false` at the end of the stream, dropping any
+    /// incomplete last frame.
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    fn read_frame<R>(&self, reader: &mut R, buf: &mut Vec<u8>) -> std::io::Result<bool>
+    where
+        R: std::io::BufRead + ?Sized;
+
+    /// Encodes `value` into `buf` as one frame, returning the frame.
+    fn to_slice<'b, T>(&self, value: &T, buf: &'b mut [u8]) -> Result<&'b mut [u8], Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        ::postcard::serialize_with_flavor(value, self.flavor(buf)?)
+    }
+
+    /// Removes the framing from `frame` in place, and deserializes the
+    /// message in it.
+    fn decode<'f, T>(&self, frame: &'f mut [u8]) -> Result<T, Error>
+    where
+        T: Deserialize<'f>,
+    {
+        ::postcard::from_bytes(self.unframe(frame)?)
+    }
+}
+
+/// Frames that end with a zero byte, with the message COBS encoded before
+/// it, as [`postcard::to_slice_cobs`](::postcard::to_slice_cobs) writes them.
+///
+/// A frame takes one byte more than the encoded message, plus one byte for
+/// every 254 bytes of it. Frames split off a stream by
+/// [`read_frame`](Framing::read_frame) do not include the zero byte, and
+/// empty ones are skipped.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Cobs;
+
+impl Framing for Cobs {
+    type Flavor<'b> = ser_flavors::Cobs<Slice<'b>>;
+
+    fn flavor<'b>(&self, buf: &'b mut [u8]) -> Result<Self::Flavor<'b>, Error> {
+        ser_flavors::Cobs::try_new(Slice::new(buf))
+    }
+
+    fn unframe<'f>(&self, frame: &'f mut [u8]) -> Result<&'f [u8], Error> {
+        let len = cobs::decode_in_place(frame).map_err(|_| Error::DeserializeBadEncoding)?;
+        Ok(&frame[..len])
+    }
+
+    #[cfg(feature = "std")]
+    fn read_frame<R>(&self, reader: &mut R, buf: &mut Vec<u8>) -> std::io::Result<bool>
+    where
+        R: std::io::BufRead + ?Sized,
+    {
+        loop {
+            buf.clear();
+            match reader.read_until(0, buf) {
+                Ok(0) => return Ok(false),
+                Ok(_) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+            if buf.pop() != Some(0) {
+                return Ok(false);
+            }
+            if !buf.is_empty() {
+                return Ok(true);
+            }
+        }
+    }
+}
+
+/// Frames that start with the length of the message, as a varint like
+/// postcard encodes a `u32`, followed by the message.
+///
+/// A frame takes one byte more than the encoded message, plus one byte for
+/// every 7 bits of its length past the first 7. Encoding reserves the
+/// longest prefix, five bytes, at the start of the buffer, so a message has
+/// to fit into the rest of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LengthPrefixed {
+    max_len: u32,
+}
+
+impl LengthPrefixed {
+    /// The longest message read, by default: 1 MiB.
+    pub const DEFAULT_MAX_LEN: u32 = 1 << 20;
+
+    /// The longest prefix, in bytes.
+    const MAX_PREFIX: usize = 5;
+
+    pub const fn new() -> Self {
+        Self {
+            max_len: Self::DEFAULT_MAX_LEN,
+        }
+    }
+
+    /// Reject frames whose prefix says they are longer than `max_len` bytes,
+    /// rather than allocating a buffer for them, as a stream that is not
+    /// length prefixed, or that is corrupted, may.
+    pub const fn with_max_len(max_len: u32) -> Self {
+        Self { max_len }
+    }
+
+    pub const fn max_len(&self) -> u32 {
+        self.max_len
+    }
+
+    /// Returns the length a frame's prefix says its message has, and the
+    /// size of the prefix, or `None` if the prefix is incomplete, or too
+    /// long for a `u32`.
+    fn prefix(frame: &[u8]) -> Option<(u32, usize)> {
+        let mut len = 0u32;
+        for (i, byte) in frame.iter().take(Self::MAX_PREFIX).enumerate() {
+            if i == Self::MAX_PREFIX - 1 && *byte > 0x0f {
+                return None;
+            }
+            len |= u32::from(byte & 0x7f) << (7 * i);
+            if byte & 0x80 == 0 {
+                return Some((len, i + 1));
+            }
+        }
+        None
+    }
+}
+
+impl Default for LengthPrefixed {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Framing for LengthPrefixed {
+    type Flavor<'b> = Prefixed<'b>;
+
+    fn flavor<'b>(&self, buf: &'b mut [u8]) -> Result<Self::Flavor<'b>, Error> {
+        if buf.len() < Self::MAX_PREFIX {
+            return Err(Error::SerializeBufferFull);
+        }
+        Ok(Prefixed {
+            buf,
+            pos: Self::MAX_PREFIX,
+        })
+    }
+
+    fn unframe<'f>(&self, frame: &'f mut [u8]) -> Result<&'f [u8], Error> {
+        let (len, at) = Self::prefix(frame).ok_or(Error::DeserializeBadVarint)?;
+        let msg = &frame[at..];
+        if msg.len() != len as usize {
+            return Err(Error::DeserializeBadEncoding);
+        }
+        Ok(msg)
+    }
+
+    #[cfg(feature = "std")]
+    fn read_frame<R>(&self, reader: &mut R, buf: &mut Vec<u8>) -> std::io::Result<bool>
+    where
+        R: std::io::BufRead + ?Sized,
+    {
+        use std::io::{Error as IoError, ErrorKind};
+
+        buf.clear();
+        let (len, at) = loop {
+            let mut byte = [0];
+            match reader.read_exact(&mut byte) {
+                Ok(()) => buf.push(byte[0]),
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(false),
+                Err(e) => return Err(e),
+            }
+            match Self::prefix(buf) {
+                Some(prefix) => break prefix,
+                None if buf.len() < Self::MAX_PREFIX => {}
+                None => return Err(IoError::new(ErrorKind::InvalidData, "bad length prefix")),
+            }
+        };
+        if len > self.max_len {
+            let msg = format!("frame of {len} bytes is longer than {}", self.max_len);
+            return Err(IoError::new(ErrorKind::InvalidData, msg));
+        }
+        buf.resize(at + len as usize, 0);
+        match reader.read_exact(&mut buf[at..]) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// The flavor of [`LengthPrefixed`], which encodes the message after room
+/// for the longest prefix, then moves it up behind the actual one.
+#[derive(Debug)]
+pub struct Prefixed<'b> {
+    buf: &'b mut [u8],
+    pos: usize,
+}
+
+impl<'b> Flavor for Prefixed<'b> {
+    type Output = &'b mut [u8];
+
+    fn try_push(&mut self, data: u8) -> ::postcard::Result<()> {
+        self.try_extend(&[data])
+    }
+
+    fn try_extend(&mut self, data: &[u8]) -> ::postcard::Result<()> {
+        let end = self.pos + data.len();
+        self.buf
+            .get_mut(self.pos..end)
+            .ok_or(Error::SerializeBufferFull)?
+            .copy_from_slice(data);
+        self.pos = end;
+        Ok(())
+    }
+
+    fn finalize(self) -> ::postcard::Result<&'b mut [u8]> {
+        let Prefixed { buf, pos } = self;
+        let start = LengthPrefixed::MAX_PREFIX;
+        let len = u32::try_from(pos - start).map_err(|_| Error::SerializeBufferFull)?;
+        let mut prefix = [0u8; LengthPrefixed::MAX_PREFIX];
+        let mut at = 0;
+        let mut rest = len;
+        loop {
+            prefix[at] = rest as u8 & 0x7f;
+            rest >>= 7;
+            at += 1;
+            if rest == 0 {
+                break;
+            }
+            prefix[at - 1] |= 0x80;
+        }
+        buf.copy_within(start..pos, at);
+        buf[..at].copy_from_slice(&prefix[..at]);
+        Ok(&mut buf[..at + len as usize])
+    }
+}
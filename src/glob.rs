@@ -0,0 +1,43 @@
+//! Matching names against patterns with wildcards.
+
+use alloc::vec::Vec;
+
+/// A pattern in which `*` stands for any number of characters, and `?` for
+/// any one.
+#[derive(Debug, Clone)]
+pub(crate) struct Glob(Vec<char>);
+
+impl Glob {
+    pub(crate) fn new(pattern: &str) -> Self {
+        Glob(pattern.chars().collect())
+    }
+
+    pub(crate) fn matches(&self, s: &str) -> bool {
+        let s: Vec<char> = s.chars().collect();
+        let (mut p, mut i) = (0, 0);
+        // Where the last `*` is in the pattern, and where in `s` it was
+        // tried to match up to.
+        let mut star = None;
+        while i < s.len() {
+            match self.0.get(p) {
+                Some('*') => {
+                    star = Some((p, i));
+                    p += 1;
+                }
+                Some(&c) if c == '?' || c == s[i] => {
+                    p += 1;
+                    i += 1;
+                }
+                _ => match star {
+                    Some((sp, si)) => {
+                        p = sp + 1;
+                        i = si + 1;
+                        star = Some((sp, si + 1));
+                    }
+                    None => return false,
+                },
+            }
+        }
+        self.0[p..].iter().all(|c| *c == '*')
+    }
+}
@@ -0,0 +1,437 @@
+//! Streaming messages to a collector service over gRPC, with [`tonic`].
+//!
+//! The service is defined by `proto/collector.proto`, shown in the [`proto`]
+//! module: a producer opens one client streaming `Collect` call to the
+//! collector, and sends an [`Envelope`](proto::Envelope) for every message,
+//! holding either a postcard encoded [`DeltaFrame`], for collectors built on
+//! the [`host`](crate::host) module, or an [`Event`](proto::Event) in plain
+//! protobuf, for collectors in any language.
+//!
+//! A [`CollectorSink`] bridges the producer, which may not block or await,
+//! and the call, which runs on the application's async runtime:
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//!
+//! use tracing_serde_structured::{
+//!     builder::SerializeEventBuilder,
+//!     grpc::{CollectorClient, CollectorSink},
+//! };
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let client = CollectorClient::connect("http://collector:50051").await?;
+//! let (mut sink, call) = CollectorSink::new(client, 1024);
+//! let call = tokio::spawn(call);
+//!
+//! let event = SerializeEventBuilder::new().field("attempt", 3u64).build();
+//! sink.send_event(Duration::from_secs(2), None, &event)?;
+//!
+//! // Dropping the sink ends the stream, and the collector answers.
+//! drop(sink);
+//! let summary = call.await??;
+//! println!("collector received {} messages", summary.received);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The client is what `tonic-build` generates for the service, so the crate
+//! builds without `protoc`.
+
+use core::fmt;
+use std::{
+    collections::HashMap,
+    future::Future,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tonic::{
+    body::Body,
+    client::{Grpc, GrpcService},
+    codegen::{
+        http, tokio_stream::wrappers::ReceiverStream, Body as HttpBody, Bytes, GrpcMethod, StdError,
+    },
+    transport::{Channel, Endpoint},
+    IntoStreamingRequest, Response, Status,
+};
+
+use crate::{
+    wire::{
+        delta::{DeltaFrame, SourceId},
+        SerializePipelineStats,
+    },
+    Form, SerializeEvent, SerializeLevel, SerializeRecordFields, SerializeValue,
+};
+
+/// The messages of the collector service, as `prost-build` generates them
+/// from `proto/collector.proto`:
+///
+#[doc = concat!("```protobuf\n", include_str!("../proto/collector.proto"), "```")]
+pub mod proto {
+    use std::collections::HashMap;
+
+    /// A message from one of the producers sharing a call.
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Envelope {
+        #[prost(uint32, tag = "1")]
+        pub source: u32,
+        #[prost(oneof = "envelope::Payload", tags = "2, 3")]
+        pub payload: Option<envelope::Payload>,
+    }
+
+    pub mod envelope {
+        #[derive(Clone, PartialEq, ::prost::Oneof)]
+        pub enum Payload {
+            /// A postcard encoded `DeltaFrame`.
+            #[prost(bytes, tag = "2")]
+            Frame(Vec<u8>),
+            #[prost(message, tag = "3")]
+            Event(super::Event),
+        }
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Level {
+        Unspecified = 0,
+        Trace = 1,
+        Debug = 2,
+        Info = 3,
+        Warn = 4,
+        Error = 5,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Event {
+        #[prost(uint64, tag = "1")]
+        pub since_start_nanos: u64,
+        #[prost(uint64, optional, tag = "2")]
+        pub wall_clock_nanos: Option<u64>,
+        #[prost(enumeration = "Level", tag = "3")]
+        pub level: i32,
+        #[prost(string, tag = "4")]
+        pub target: String,
+        #[prost(string, tag = "5")]
+        pub name: String,
+        #[prost(string, optional, tag = "6")]
+        pub module_path: Option<String>,
+        #[prost(string, optional, tag = "7")]
+        pub file: Option<String>,
+        #[prost(uint32, optional, tag = "8")]
+        pub line: Option<u32>,
+        #[prost(map = "string, message", tag = "9")]
+        pub fields: HashMap<String, Value>,
+        #[prost(uint64, optional, tag = "10")]
+        pub parent: Option<u64>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Value {
+        #[prost(oneof = "value::Kind", tags = "1, 2, 3, 4, 5, 6")]
+        pub kind: Option<value::Kind>,
+    }
+
+    pub mod value {
+        #[derive(Clone, PartialEq, ::prost::Oneof)]
+        pub enum Kind {
+            #[prost(string, tag = "1")]
+            Debug(String),
+            #[prost(string, tag = "2")]
+            Str(String),
+            #[prost(double, tag = "3")]
+            F64(f64),
+            #[prost(sint64, tag = "4")]
+            I64(i64),
+            #[prost(uint64, tag = "5")]
+            U64(u64),
+            #[prost(bool, tag = "6")]
+            Bool(bool),
+        }
+    }
+
+    #[derive(Clone, Copy, PartialEq, ::prost::Message)]
+    pub struct Summary {
+        #[prost(uint64, tag = "1")]
+        pub received: u64,
+    }
+}
+
+use proto::{envelope::Payload, value::Kind, Envelope, Summary};
+
+impl From<SerializeLevel> for proto::Level {
+    fn from(level: SerializeLevel) -> Self {
+        match level {
+            SerializeLevel::Trace => proto::Level::Trace,
+            SerializeLevel::Debug => proto::Level::Debug,
+            SerializeLevel::Info => proto::Level::Info,
+            SerializeLevel::Warn => proto::Level::Warn,
+            SerializeLevel::Error => proto::Level::Error,
+        }
+    }
+}
+
+impl From<&SerializeValue<'_>> for proto::Value {
+    /// Converts a value, leaving the kind of an
+    /// [`Unknown`](SerializeValue::Unknown) one unset.
+    fn from(value: &SerializeValue<'_>) -> Self {
+        let kind = match value {
+            SerializeValue::Debug(debug) => Some(Kind::Debug(debug.to_string())),
+            SerializeValue::Str(s) => Some(Kind::Str(s.as_str().to_string())),
+            SerializeValue::F64(x) => Some(Kind::F64(*x)),
+            SerializeValue::I64(x) => Some(Kind::I64(*x)),
+            SerializeValue::U64(x) => Some(Kind::U64(*x)),
+            SerializeValue::Bool(x) => Some(Kind::Bool(*x)),
+            // The protocol has no nested values, so trees are sent as text.
+            SerializeValue::Tree(tree) => Some(Kind::Debug(tree.to_string())),
+            SerializeValue::Unknown => None,
+        };
+        proto::Value { kind }
+    }
+}
+
+impl proto::Event {
+    /// Converts `event`, which happened `since_start` after the producer's
+    /// clock started, at `wall_clock` if the producer knows the time of day.
+    pub fn new<F: Form>(
+        since_start: Duration,
+        wall_clock: Option<SystemTime>,
+        event: &SerializeEvent<'_, F>,
+    ) -> Self {
+        let meta = &event.metadata;
+        let fields = match event.fields.to_owned() {
+            SerializeRecordFields::De(map) => map
+                .iter()
+                .map(|(name, value)| (name.as_str().to_string(), value.into()))
+                .collect(),
+            SerializeRecordFields::Ser(_) => HashMap::new(),
+        };
+        proto::Event {
+            since_start_nanos: nanos(since_start),
+            wall_clock_nanos: wall_clock
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(nanos),
+            level: proto::Level::from(meta.level).into(),
+            target: meta.target.as_str().to_string(),
+            name: meta.name.as_str().to_string(),
+            module_path: meta.module_path.as_ref().map(|m| m.as_str().to_string()),
+            file: meta.file.as_ref().map(|f| f.as_str().to_string()),
+            line: meta.line,
+            fields,
+            parent: event.parent.as_ref().map(|id| id.id.get()),
+        }
+    }
+}
+
+impl Envelope {
+    /// An envelope holding `frame`, postcard encoded, from `source`.
+    pub fn frame<F: Form>(
+        source: SourceId,
+        frame: &DeltaFrame<'_, F>,
+    ) -> Result<Self, ::postcard::Error> {
+        Ok(Envelope {
+            source: source.id.into(),
+            payload: Some(Payload::Frame(::postcard::to_allocvec(frame)?)),
+        })
+    }
+
+    /// An envelope holding `event` from `source`.
+    pub fn event(source: SourceId, event: proto::Event) -> Self {
+        Envelope {
+            source: source.id.into(),
+            payload: Some(Payload::Event(event)),
+        }
+    }
+}
+
+fn nanos(duration: Duration) -> u64 {
+    u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX)
+}
+
+/// A client of the collector service.
+#[derive(Debug, Clone)]
+pub struct CollectorClient<T = Channel> {
+    inner: Grpc<T>,
+}
+
+impl CollectorClient<Channel> {
+    /// Connects to the collector at `dst`, such as `http://collector:50051`.
+    pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+    where
+        D: TryInto<Endpoint>,
+        D::Error: Into<StdError>,
+    {
+        let channel = Endpoint::new(dst)?.connect().await?;
+        Ok(Self::new(channel))
+    }
+}
+
+impl<T> CollectorClient<T>
+where
+    T: GrpcService<Body>,
+    T::Error: Into<StdError>,
+    T::ResponseBody: HttpBody<Data = Bytes> + Send + 'static,
+    <T::ResponseBody as HttpBody>::Error: Into<StdError> + Send,
+{
+    /// A client sending its calls to `inner`, such as a [`Channel`] with
+    /// interceptors or timeouts of the application's choosing.
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner: Grpc::new(inner),
+        }
+    }
+
+    /// Streams the envelopes of `request` to the collector, and returns its
+    /// summary once the stream ends.
+    pub async fn collect(
+        &mut self,
+        request: impl IntoStreamingRequest<Message = Envelope>,
+    ) -> Result<Response<Summary>, Status> {
+        self.inner
+            .ready()
+            .await
+            .map_err(|e| Status::unknown(format!("Service was not ready: {}", e.into())))?;
+        let codec = tonic_prost::ProstCodec::default();
+        let path = http::uri::PathAndQuery::from_static(
+            "/tracing_serde_structured.collector.v1.Collector/Collect",
+        );
+        let mut request = request.into_streaming_request();
+        request.extensions_mut().insert(GrpcMethod::new(
+            "tracing_serde_structured.collector.v1.Collector",
+            "Collect",
+        ));
+        self.inner.client_streaming(request, path, codec).await
+    }
+}
+
+/// Errors returned by a [`CollectorSink`].
+#[derive(Debug)]
+pub enum Error {
+    /// The frame could not be encoded.
+    Encode(::postcard::Error),
+    /// The sink already holds as many envelopes as it may, so the envelope
+    /// was dropped.
+    Full,
+    /// The call has ended, e.g. because the collector could not be reached.
+    Closed,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Encode(e) => write!(f, "failed to encode message: {e}"),
+            Error::Full => f.write_str("sink is full, message dropped"),
+            Error::Closed => f.write_str("call to the collector has ended"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Encode(e) => Some(e),
+            Error::Full | Error::Closed => None,
+        }
+    }
+}
+
+/// Queues envelopes for a `Collect` call, without blocking or awaiting.
+///
+/// The envelopes are sent by the call returned with the sink, which the
+/// application spawns on its runtime. At most `capacity` of them wait for it;
+/// any more are dropped, and counted in the [`stats`](Self::stats).
+#[derive(Debug)]
+pub struct CollectorSink {
+    tx: mpsc::Sender<Envelope>,
+    source: SourceId,
+    stats: SerializePipelineStats,
+}
+
+impl CollectorSink {
+    /// A sink queueing up to `capacity` envelopes for `client`, and the call
+    /// sending them, which ends with the collector's summary once the sink is
+    /// dropped.
+    pub fn new<T>(
+        mut client: CollectorClient<T>,
+        capacity: usize,
+    ) -> (Self, impl Future<Output = Result<Summary, Status>>)
+    where
+        T: GrpcService<Body>,
+        T::Error: Into<StdError>,
+        T::ResponseBody: HttpBody<Data = Bytes> + Send + 'static,
+        <T::ResponseBody as HttpBody>::Error: Into<StdError> + Send,
+    {
+        let (tx, rx) = mpsc::channel(capacity.max(1));
+        let call = async move {
+            let response = client.collect(ReceiverStream::new(rx)).await?;
+            Ok(response.into_inner())
+        };
+        let sink = Self {
+            tx,
+            source: SourceId { id: 0 },
+            stats: SerializePipelineStats::default(),
+        };
+        (sink, call)
+    }
+
+    /// Tag envelopes as coming from `source`, rather than source 0.
+    pub fn with_source(mut self, source: SourceId) -> Self {
+        self.source = source;
+        self
+    }
+
+    /// Queues `frame`, postcard encoded.
+    pub fn send_frame<F: Form>(&mut self, frame: &DeltaFrame<'_, F>) -> Result<(), Error> {
+        match Envelope::frame(self.source, frame) {
+            Ok(envelope) => self.send(envelope),
+            Err(e) => {
+                count(&mut self.stats.errors);
+                Err(Error::Encode(e))
+            }
+        }
+    }
+
+    /// Queues `event`, which happened `since_start` after the producer's
+    /// clock started, at `wall_clock` if it knows the time of day, as a
+    /// protobuf [`Event`](proto::Event).
+    pub fn send_event<F: Form>(
+        &mut self,
+        since_start: Duration,
+        wall_clock: Option<SystemTime>,
+        event: &SerializeEvent<'_, F>,
+    ) -> Result<(), Error> {
+        let event = proto::Event::new(since_start, wall_clock, event);
+        self.send(Envelope::event(self.source, event))
+    }
+
+    /// Queues `envelope` as it is.
+    pub fn send(&mut self, envelope: Envelope) -> Result<(), Error> {
+        match self.tx.try_send(envelope) {
+            Ok(()) => {
+                count(&mut self.stats.sent);
+                Ok(())
+            }
+            Err(TrySendError::Full(_)) => {
+                count(&mut self.stats.dropped);
+                Err(Error::Full)
+            }
+            Err(TrySendError::Closed(_)) => {
+                count(&mut self.stats.dropped);
+                Err(Error::Closed)
+            }
+        }
+    }
+
+    /// The counts of envelopes queued and lost so far. Envelopes are counted
+    /// as sent once they are queued.
+    pub fn stats(&self) -> SerializePipelineStats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = SerializePipelineStats::default();
+    }
+}
+
+fn count(count: &mut u32) {
+    *count = count.saturating_add(1);
+}
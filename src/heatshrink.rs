@@ -0,0 +1,190 @@
+//! [Heatshrink] compression of single frames, for producers with very little RAM.
+//!
+//! Heatshrink is an LZSS variant designed for embedded targets. Compressing a
+//! frame needs no state beyond the input and output buffers, so it works on
+//! microcontrollers where deflate or zstd do not fit:
+//!
+//! ```rust
+//! use tracing_serde_structured::{
+//!     heatshrink::{self, Config},
+//!     postcard, SerializeLevel,
+//! };
+//!
+//! let mut scratch = [0u8; 64];
+//! let mut out = [0u8; 64];
+//! let level = SerializeLevel::Warn;
+//! let frame = heatshrink::serialize_into(&level, &mut scratch, &mut out, Config::DEFAULT).unwrap();
+//!
+//! let mut buf = [0u8; 64];
+//! let raw = heatshrink::decompress_into(frame, &mut buf).unwrap();
+//! assert_eq!(postcard::from_bytes::<SerializeLevel>(raw).unwrap(), level);
+//! ```
+//!
+//! Each compressed frame starts with one byte recording the [`Config`] it was
+//! compressed with, so the decoder does not need to be configured to match.
+//! With `alloc`, [`decompress`] decodes a frame of unknown size into a `Vec`.
+//!
+//! [Heatshrink]: https://github.com/atomicobject/heatshrink
+
+use core::fmt;
+
+use serde::Serialize;
+
+/// Errors returned when compressing or decompressing a frame.
+#[derive(Debug)]
+pub enum Error {
+    /// The message could not be encoded into the scratch buffer.
+    Postcard(::postcard::Error),
+    /// The output buffer is too small.
+    OutputFull,
+    /// The frame is empty, or its header does not describe a valid [`Config`].
+    InvalidHeader,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Postcard(e) => write!(f, "failed to encode message: {e}"),
+            Error::OutputFull => f.write_str("output buffer is too small"),
+            Error::InvalidHeader => f.write_str("invalid heatshrink frame header"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// The window and lookahead sizes used to compress a frame.
+///
+/// Larger windows find more repetition, at the cost of slower compression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Config {
+    window_sz2: u8,
+    lookahead_sz2: u8,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl Config {
+    /// A 256 byte window and 16 byte lookahead, suited to frames of a few hundred bytes.
+    pub const DEFAULT: Config = Config {
+        window_sz2: 8,
+        lookahead_sz2: 4,
+    };
+
+    /// A window of `2^window_sz2` bytes and a lookahead of `2^lookahead_sz2` bytes.
+    ///
+    /// `window_sz2` must be in `4..=15`, as in the reference implementation, and
+    /// `lookahead_sz2` in `3..=8` and smaller than `window_sz2`; otherwise this
+    /// returns `None`.
+    pub const fn new(window_sz2: u8, lookahead_sz2: u8) -> Option<Self> {
+        if window_sz2 < 4 || window_sz2 > 15 || lookahead_sz2 < 3 || lookahead_sz2 > 8 {
+            return None;
+        }
+        if lookahead_sz2 >= window_sz2 {
+            return None;
+        }
+        Some(Self {
+            window_sz2,
+            lookahead_sz2,
+        })
+    }
+
+    /// The base-2 logarithm of the window size.
+    pub fn window_sz2(&self) -> u8 {
+        self.window_sz2
+    }
+
+    /// The base-2 logarithm of the lookahead size.
+    pub fn lookahead_sz2(&self) -> u8 {
+        self.lookahead_sz2
+    }
+
+    fn header(&self) -> u8 {
+        self.window_sz2 << 4 | self.lookahead_sz2
+    }
+
+    fn from_header(header: u8) -> Result<Self, Error> {
+        Self::new(header >> 4, header & 0x0F).ok_or(Error::InvalidHeader)
+    }
+
+    fn to_heatshrink(self) -> ::heatshrink::Config {
+        ::heatshrink::Config::new(self.window_sz2, self.lookahead_sz2)
+            .expect("sizes are validated by `Config::new`")
+    }
+}
+
+/// Compresses `input` into `out`, returning the used part of `out`.
+///
+/// Fails with [`Error::OutputFull`] if `out` is too small. Incompressible
+/// input grows by one bit per byte, plus the one byte header.
+pub fn compress_into<'a>(
+    input: &[u8],
+    out: &'a mut [u8],
+    config: Config,
+) -> Result<&'a [u8], Error> {
+    let (header, body) = out.split_first_mut().ok_or(Error::OutputFull)?;
+    *header = config.header();
+    let len = ::heatshrink::encode(input, body, &config.to_heatshrink())
+        .map_err(|_| Error::OutputFull)?
+        .len();
+    Ok(&out[..len + 1])
+}
+
+/// Decompresses a frame produced by [`compress_into`] into `out`, returning
+/// the used part of `out`.
+///
+/// Fails with [`Error::OutputFull`] unless `out` is at least one byte longer
+/// than the decompressed frame.
+pub fn decompress_into<'a>(frame: &[u8], out: &'a mut [u8]) -> Result<&'a [u8], Error> {
+    let (&header, body) = frame.split_first().ok_or(Error::InvalidHeader)?;
+    let config = Config::from_header(header)?;
+    ::heatshrink::decode(body, out, &config.to_heatshrink()).map_err(|_| Error::OutputFull)
+}
+
+/// Encodes `value` with postcard into `scratch`, then compresses it into `out`.
+pub fn serialize_into<'a, T>(
+    value: &T,
+    scratch: &mut [u8],
+    out: &'a mut [u8],
+    config: Config,
+) -> Result<&'a [u8], Error>
+where
+    T: Serialize + ?Sized,
+{
+    let raw = ::postcard::to_slice(value, scratch).map_err(Error::Postcard)?;
+    compress_into(raw, out, config)
+}
+
+/// Decompresses a frame produced by [`compress_into`], of any size.
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub fn decompress(frame: &[u8]) -> Result<alloc::vec::Vec<u8>, Error> {
+    let header = *frame.first().ok_or(Error::InvalidHeader)?;
+    let config = Config::from_header(header)?;
+
+    // Every back-reference takes at least `1 + window + lookahead` bits, and
+    // expands to at most `2^lookahead` bytes, which bounds the output size.
+    // The decoder needs one spare byte beyond that.
+    let bits = (frame.len() - 1) * 8;
+    let refs = bits / (1 + config.window_sz2 as usize + config.lookahead_sz2 as usize);
+    let max = ((refs + 1) << config.lookahead_sz2) + 1;
+
+    let mut len = (frame.len() * 4).min(max);
+    loop {
+        let mut out = alloc::vec![0; len];
+        match decompress_into(frame, &mut out) {
+            Ok(raw) => {
+                let used = raw.len();
+                out.truncate(used);
+                return Ok(out);
+            }
+            Err(Error::OutputFull) if len < max => len = (len * 2).min(max),
+            Err(e) => return Err(e),
+        }
+    }
+}
@@ -0,0 +1,1218 @@
+//! Decoding the streams sent by embedded devices, on the host side.
+//!
+//! A device usually sends its trace data as compactly as it can: metadata and
+//! field names once, as definitions, then events that refer to them by id,
+//! with delta encoded ids and timestamps counted in ticks of some hardware
+//! timer. Each message is a postcard encoded [`DeltaFrame`], framed with COBS,
+//! for example by the `FramedWriter` of the `embedded_io` module:
+//!
+//! ```rust
+//! # use tracing_serde_structured::{wire::{delta::DeltaEncoder, TracingWire}, SerializeId};
+//! # fn send(_: &[u8]) {}
+//! # let now = 0;
+//! # let msg = TracingWire::Enter(SerializeId { id: 1.try_into().unwrap() });
+//! let mut encoder = DeltaEncoder::new();
+//! let mut buf = [0u8; 256];
+//! send(postcard::to_slice_cobs(&encoder.encode(now, msg), &mut buf).unwrap());
+//! ```
+//!
+//! A [`Decoder`] reverses all of this. It collects the definitions, turns
+//! every message that refers to them back into a self-contained one (such as
+//! a [`TracingWire::EventRef`] into a [`TracingWire::Event`]), and converts
+//! timestamps from ticks into time since the device started:
+//!
+//! ```rust,no_run
+//! use std::{fs::File, io::BufReader};
+//! use tracing_serde_structured::{host::Decoder, time::TickRate};
+//!
+//! let port = BufReader::new(File::open("/dev/ttyACM0").unwrap());
+//! for msg in Decoder::new(TickRate::hz(32_768)).read(port) {
+//!     match msg {
+//!         Ok(msg) => println!("{:?} {:?}", msg.since_start, msg.msg),
+//!         Err(e) => eprintln!("dropped a frame: {e}"),
+//!     }
+//! }
+//! ```
+//!
+//! A frame that fails to decode is reported and skipped, and decoding carries
+//! on with the next one. The delta encoding cannot recover from lost frames,
+//! though: once the device resets its encoder, call [`Decoder::reset`] to
+//! match. Devices that send sync frames every so often avoid this, and also
+//! let a [`Decoder::mid_stream`] start reading a stream that is already
+//! running.
+//!
+//! Bytes flipped on a noisy link mostly go unnoticed, though, and decode into
+//! the wrong message. Devices that end their frames with a CRC (see the
+//! [`checksum`](crate::checksum) module) avoid this: a decoder with
+//! [`Decoder::set_checksum`] counts and skips the frames that fail it.
+//!
+//! Devices that start their streams with a [`TracingWire::Hello`] (see the
+//! [`handshake`](crate::wire::handshake) module) say how fast their clock
+//! ticks, which the decoder uses from then on, and what their stream uses. A
+//! stream the decoder is not set up to read fails right away with
+//! [`Error::Handshake`], saying what is missing; there is no point reading
+//! on after that. The [`Description`] they may send after their hello is
+//! kept as well, see [`Decoder::description`].
+//!
+//! Devices that know the time of day send [`TracingWire::TimeSync`]s as
+//! well, which a [`WallClock`] fits the device's ticks to, so that every
+//! message also gets a [`Message::wall_clock`] timestamp.
+//!
+//! Devices with several producers, such as multi-core chips, send
+//! [`SourceFrame`]s instead, each tagged with the producer it came from. A
+//! [`Merger`] decodes each source separately, and interleaves their messages
+//! into one sequence ordered by timestamp.
+//!
+//! Streams that are decoded separately, such as the files of a fleet of
+//! devices or the live streams of several processes, are interleaved the same
+//! way with [`merge`].
+
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BTreeMap, BinaryHeap, VecDeque},
+    fmt, io,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    framing::{Cobs, Framing},
+    time::{TickRate, TimeSync},
+    wire::{
+        delta::{DeltaDecoder, DeltaFrame, SourceFrame, SourceId},
+        description::Description,
+        handshake::{self, Capabilities, Hello},
+        table::MetadataTable,
+        MetadataDictionary, TracingWire,
+    },
+    Form,
+};
+
+/// Errors returned while decoding a stream.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading the stream failed.
+    Io(io::Error),
+    /// A frame is not a valid COBS framed [`DeltaFrame`].
+    Postcard(::postcard::Error),
+    /// A frame does not follow from the previous one, so frames were lost or
+    /// the device reset its encoder, or a decoder started mid-stream has not
+    /// seen a sync frame yet.
+    Delta,
+    /// A message refers to metadata or a field name that has not been defined.
+    Undefined,
+    /// The device's [`Hello`] says its stream uses a version of the wire
+    /// format or capabilities the decoder cannot read.
+    Handshake(handshake::Error),
+    /// A frame of an audit stream is malformed, or does not follow from the
+    /// previous frame (see [`Decoder::set_audit`]).
+    #[cfg(feature = "audit")]
+    Audit(crate::audit::Error),
+    /// A frame's authentication tag is missing or wrong (see
+    /// [`Decoder::set_authentication`]).
+    #[cfg(feature = "crypto")]
+    Crypto(crate::crypto::Error),
+    /// A frame's CRC is missing or wrong (see [`Decoder::set_checksum`]).
+    #[cfg(feature = "checksum")]
+    Checksum(crate::checksum::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "failed to read stream: {e}"),
+            Error::Postcard(e) => write!(f, "failed to decode frame: {e}"),
+            Error::Delta => f.write_str("frame does not follow from the previous frame"),
+            Error::Undefined => f.write_str("message refers to an undefined definition"),
+            Error::Handshake(e) => write!(f, "cannot read stream: {e}"),
+            #[cfg(feature = "audit")]
+            Error::Audit(e) => write!(f, "failed to verify frame: {e}"),
+            #[cfg(feature = "crypto")]
+            Error::Crypto(e) => write!(f, "failed to authenticate frame: {e}"),
+            #[cfg(feature = "checksum")]
+            Error::Checksum(e) => write!(f, "failed to check frame: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Postcard(e) => Some(e),
+            Error::Handshake(e) => Some(e),
+            #[cfg(feature = "audit")]
+            Error::Audit(e) => Some(e),
+            #[cfg(feature = "crypto")]
+            Error::Crypto(e) => Some(e),
+            #[cfg(feature = "checksum")]
+            Error::Checksum(e) => Some(e),
+            Error::Delta | Error::Undefined => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/// A decoded message, and when it was produced.
+#[derive(Debug)]
+pub struct Message {
+    /// The device's timestamp, in ticks.
+    pub ticks: u64,
+    /// The device's timestamp, as time since its clock started.
+    pub since_start: Duration,
+    /// The device's timestamp, as a time of day, once the device has sent a
+    /// [`TimeSync`].
+    pub wall_clock: Option<SystemTime>,
+    /// The message, which never refers to definitions.
+    pub msg: TracingWire<'static>,
+}
+
+/// Maps a device's ticks to the time of day, from the [`TimeSync`]s it
+/// sends.
+///
+/// With one sync, ticks are converted at the device's nominal rate. With
+/// more, the rate is fitted to the most recent syncs, so timestamps stay
+/// accurate even if the device's clock runs a little fast or slow, and
+/// drifts with temperature. A sync with fewer ticks than the one before
+/// means the device restarted, and starts the mapping over.
+#[derive(Debug, Clone)]
+pub struct WallClock {
+    rate: TickRate,
+    window: usize,
+    syncs: VecDeque<TimeSync>,
+    fit: Option<Fit>,
+}
+
+/// A straight line through the syncs, anchored at the latest one.
+#[derive(Debug, Clone, Copy)]
+struct Fit {
+    ticks: u64,
+    wall_clock: u64,
+    offset: f64,
+    nanos_per_tick: f64,
+}
+
+impl WallClock {
+    /// The number of syncs the rate is fitted to, by default.
+    pub const DEFAULT_WINDOW: usize = 16;
+
+    /// A mapping for a device whose clock runs at `rate`.
+    pub fn new(rate: TickRate) -> Self {
+        Self {
+            rate,
+            window: Self::DEFAULT_WINDOW,
+            syncs: VecDeque::new(),
+            fit: None,
+        }
+    }
+
+    /// Fit the rate to the last `window` syncs, at least two.
+    ///
+    /// Longer windows average out more jitter in the syncs, shorter ones
+    /// follow changes in the drift more quickly.
+    pub fn set_window(&mut self, window: usize) {
+        self.window = window.max(2);
+        while self.syncs.len() > self.window {
+            self.syncs.pop_front();
+        }
+        self.refit();
+    }
+
+    /// Add a sync to the mapping.
+    pub fn observe(&mut self, sync: TimeSync) {
+        if self
+            .syncs
+            .back()
+            .is_some_and(|last| sync.device_ticks < last.device_ticks)
+        {
+            self.syncs.clear();
+        }
+        if self.syncs.len() == self.window {
+            self.syncs.pop_front();
+        }
+        self.syncs.push_back(sync);
+        self.refit();
+    }
+
+    /// Forget all syncs.
+    pub fn clear(&mut self) {
+        self.syncs.clear();
+        self.fit = None;
+    }
+
+    /// Converts a device timestamp into the time of day, if any syncs have
+    /// been observed.
+    pub fn to_wall(&self, ticks: u64) -> Option<SystemTime> {
+        let fit = self.fit?;
+        let delta = (i128::from(ticks) - i128::from(fit.ticks)) as f64;
+        // Syncs sent by a confused or malicious device can make the offset
+        // arbitrarily large.
+        let nanos = i128::from(fit.wall_clock)
+            .checked_add((delta * fit.nanos_per_tick + fit.offset).round() as i128)?;
+        let nanos = u64::try_from(nanos).ok()?;
+        UNIX_EPOCH.checked_add(Duration::from_nanos(nanos))
+    }
+
+    /// How far the device's clock is from its nominal rate, in parts per
+    /// million; positive if it runs fast.
+    ///
+    /// Returns `None` until there are at least two syncs.
+    pub fn drift_ppm(&self) -> Option<f64> {
+        if self.syncs.len() < 2 {
+            return None;
+        }
+        let fit = self.fit?;
+        Some((self.nominal() / fit.nanos_per_tick - 1.0) * 1e6)
+    }
+
+    fn nominal(&self) -> f64 {
+        f64::from(self.rate.seconds) * 1e9 / f64::from(self.rate.ticks)
+    }
+
+    /// Least squares, relative to the latest sync to keep the numbers small.
+    fn refit(&mut self) {
+        let Some(last) = self.syncs.back().copied() else {
+            self.fit = None;
+            return;
+        };
+        let n = self.syncs.len() as f64;
+        let points = || {
+            self.syncs.iter().map(move |s| {
+                (
+                    (i128::from(s.device_ticks) - i128::from(last.device_ticks)) as f64,
+                    (i128::from(s.wall_clock) - i128::from(last.wall_clock)) as f64,
+                )
+            })
+        };
+        let (mean_x, mean_y) =
+            points().fold((0.0, 0.0), |(x, y), (px, py)| (x + px / n, y + py / n));
+        let (sxy, sxx) = points().fold((0.0, 0.0), |(sxy, sxx), (x, y)| {
+            let dx = x - mean_x;
+            (sxy + dx * (y - mean_y), sxx + dx * dx)
+        });
+        let (nanos_per_tick, offset) = if sxx > 0.0 && sxy > 0.0 {
+            let slope = sxy / sxx;
+            (slope, mean_y - slope * mean_x)
+        } else {
+            (self.nominal(), 0.0)
+        };
+        self.fit = Some(Fit {
+            ticks: last.device_ticks,
+            wall_clock: last.wall_clock,
+            offset,
+            nanos_per_tick,
+        });
+    }
+}
+
+/// Decodes the frames of one device's stream.
+#[derive(Debug)]
+pub struct Decoder {
+    rate: TickRate,
+    delta: DeltaDecoder,
+    dictionary: MetadataDictionary,
+    table: MetadataTable,
+    clock: WallClock,
+    hello: Option<Hello<'static>>,
+    description: Option<Description<'static>>,
+    #[cfg(feature = "audit")]
+    audit: Option<Box<dyn crate::audit::Verify>>,
+    #[cfg(feature = "crypto")]
+    authentication: Option<Box<dyn crate::crypto::Authenticate>>,
+    #[cfg(feature = "checksum")]
+    checksum: Option<crate::checksum::Checksum>,
+    #[cfg(feature = "checksum")]
+    corrupt: u64,
+}
+
+impl Decoder {
+    /// A decoder for a device whose clock runs at `rate`.
+    pub fn new(rate: TickRate) -> Self {
+        Self {
+            rate,
+            delta: DeltaDecoder::new(),
+            dictionary: MetadataDictionary::new(),
+            table: MetadataTable::new([]),
+            clock: WallClock::new(rate),
+            hello: None,
+            description: None,
+            #[cfg(feature = "audit")]
+            audit: None,
+            #[cfg(feature = "crypto")]
+            authentication: None,
+            #[cfg(feature = "checksum")]
+            checksum: None,
+            #[cfg(feature = "checksum")]
+            corrupt: 0,
+        }
+    }
+
+    /// A decoder for a device that refers to its callsites by their ids in
+    /// `table` (see [`wire::table`](crate::wire::table)).
+    ///
+    /// The table's definitions are kept when the dictionary is cleared.
+    pub fn with_table(rate: TickRate, table: MetadataTable) -> Self {
+        Self {
+            dictionary: table.to_dictionary(),
+            table,
+            ..Self::new(rate)
+        }
+    }
+
+    /// A decoder for a stream that is already running, which skips frames
+    /// until the device sends a sync frame (see
+    /// [`DeltaEncoder::set_sync_interval`](crate::wire::delta::DeltaEncoder::set_sync_interval)).
+    ///
+    /// Events whose definitions were sent before the decoder started fail
+    /// with [`Error::Undefined`] until the device defines them again.
+    pub fn mid_stream(rate: TickRate) -> Self {
+        Self {
+            delta: DeltaDecoder::mid_stream(),
+            ..Self::new(rate)
+        }
+    }
+
+    /// The definitions received so far.
+    pub fn dictionary(&self) -> &MetadataDictionary {
+        &self.dictionary
+    }
+
+    /// The [`Hello`] the device started its stream with, if any.
+    pub fn hello(&self) -> Option<&Hello<'static>> {
+        self.hello.as_ref()
+    }
+
+    /// The [`Description`] of the device's stream it last sent, if any.
+    pub fn description(&self) -> Option<&Description<'static>> {
+        self.description.as_ref()
+    }
+
+    /// What the decoder can read, as set up: every message of the wire
+    /// format, but not compressed frames, callsites referred to by their ids
+    /// in a table only [`with_table`](Self::with_table), and frames with a
+    /// hash, a tag or a CRC only if it checks them.
+    pub fn capabilities(&self) -> Capabilities {
+        let mut capabilities = Capabilities::DELTA
+            | Capabilities::DEFINITIONS
+            | Capabilities::INTERNED
+            | Capabilities::SOURCES;
+        if !self.table.is_empty() {
+            capabilities |= Capabilities::METADATA_TABLE;
+        }
+        #[cfg(feature = "audit")]
+        if self.audit.is_some() {
+            capabilities |= Capabilities::AUDIT;
+        }
+        #[cfg(feature = "crypto")]
+        if self.authentication.is_some() {
+            capabilities |= Capabilities::AUTHENTICATION;
+        }
+        #[cfg(feature = "checksum")]
+        match self.checksum {
+            Some(crate::checksum::Checksum::Crc16) => capabilities |= Capabilities::CRC16,
+            Some(crate::checksum::Checksum::Crc32) => capabilities |= Capabilities::CRC32,
+            None => {}
+        }
+        capabilities
+    }
+
+    /// The mapping from the device's ticks to the time of day.
+    pub fn wall_clock(&self) -> &WallClock {
+        &self.clock
+    }
+
+    pub fn wall_clock_mut(&mut self) -> &mut WallClock {
+        &mut self.clock
+    }
+
+    /// Forget the previous frame, matching a reset of the device's
+    /// [`DeltaEncoder`](crate::wire::delta::DeltaEncoder).
+    ///
+    /// Definitions are kept; clear the dictionary too if the device restarted.
+    pub fn reset(&mut self) {
+        self.delta.reset();
+    }
+
+    /// Remove all definitions, e.g. when the device restarts.
+    pub fn clear_dictionary(&mut self) {
+        self.dictionary = self.table.to_dictionary();
+    }
+
+    /// Verify that every frame embeds the hash of the previous one, as written
+    /// by an [`audit::Chain`](crate::audit::Chain) seeded the same way.
+    ///
+    /// A frame that does not fails with [`audit::Error::Broken`](crate::audit::Error::Broken).
+    /// Its message is still decoded, so that the frames after it follow from
+    /// it, but not returned.
+    #[cfg(feature = "audit")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "audit")))]
+    pub fn set_audit<D>(&mut self, verifier: crate::audit::Verifier<D>)
+    where
+        D: digest::Digest + 'static,
+        digest::Output<D>: Send,
+    {
+        self.audit = Some(Box::new(verifier));
+    }
+
+    /// The hash the next frame of an audit stream should embed, to compare
+    /// with the one the producer recorded (see the [`audit`](crate::audit)
+    /// module), or `None` without [`set_audit`](Self::set_audit).
+    #[cfg(feature = "audit")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "audit")))]
+    pub fn audit_head(&self) -> Option<&[u8]> {
+        self.audit.as_deref().map(|verifier| verifier.head())
+    }
+
+    /// Reject frames whose tag does not match, as appended by a
+    /// [`crypto::Signer`](crate::crypto::Signer) with the same key, with
+    /// [`Error::Crypto`].
+    ///
+    /// Rejected frames are not decoded. With [`set_audit`](Self::set_audit)
+    /// as well, the tag is expected after the chain's hash, as appended by
+    /// [`Signer::to_slice_cobs_chained`](crate::crypto::Signer::to_slice_cobs_chained).
+    #[cfg(feature = "crypto")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "crypto")))]
+    pub fn set_authentication<M>(&mut self, verifier: crate::crypto::Verifier<M>)
+    where
+        M: digest::Mac + digest::KeyInit + Clone + Send + 'static,
+    {
+        self.authentication = Some(Box::new(verifier));
+    }
+
+    /// Expect every frame to end with `checksum`, as appended by
+    /// [`Checksum::to_slice_cobs`](crate::checksum::Checksum::to_slice_cobs).
+    ///
+    /// Frames whose CRC does not match fail with [`Error::Checksum`] and are
+    /// not decoded, and [`Messages`] skips them. Either way, they are counted
+    /// in [`corrupt_frames`](Self::corrupt_frames). The CRC is expected after
+    /// any hash or tag, and checked before them.
+    #[cfg(feature = "checksum")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "checksum")))]
+    pub fn set_checksum(&mut self, checksum: crate::checksum::Checksum) {
+        self.checksum = Some(checksum);
+    }
+
+    /// The number of frames that failed their CRC so far (see
+    /// [`set_checksum`](Self::set_checksum)).
+    #[cfg(feature = "checksum")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "checksum")))]
+    pub fn corrupt_frames(&self) -> u64 {
+        self.corrupt
+    }
+
+    /// Decodes one COBS encoded frame, without its terminating zero byte.
+    ///
+    /// The frame is decoded in place. Returns `None` for definitions, which
+    /// are stored in the dictionary instead, for time syncs, which go to the
+    /// [`wall_clock`](Decoder::wall_clock), for hellos and descriptions,
+    /// which the decoder keeps, for sync frames, and
+    /// for kinds of messages added in later versions of this crate.
+    pub fn decode_frame(&mut self, frame: &mut [u8]) -> Result<Option<Message>, Error> {
+        let frame = Cobs.unframe(frame).map_err(Error::Postcard)?;
+        self.decode_unframed(frame)
+    }
+
+    /// Decodes one frame whose framing has already been removed, e.g. with
+    /// [`Framing::unframe`], as [`decode_frame`](Self::decode_frame) does.
+    pub fn decode_unframed(&mut self, frame: &[u8]) -> Result<Option<Message>, Error> {
+        #[cfg(any(feature = "audit", feature = "crypto", feature = "checksum"))]
+        if self.has_trailers() {
+            return self.decode_trailed(frame);
+        }
+        let frame: DeltaFrame<'_> = ::postcard::from_bytes(frame).map_err(Error::Postcard)?;
+        self.decode(frame)
+    }
+
+    /// Whether frames end with a hash, a tag or a CRC to check and strip
+    /// before decoding.
+    #[cfg(any(feature = "audit", feature = "crypto", feature = "checksum"))]
+    fn has_trailers(&self) -> bool {
+        #[cfg(feature = "audit")]
+        if self.audit.is_some() {
+            return true;
+        }
+        #[cfg(feature = "crypto")]
+        if self.authentication.is_some() {
+            return true;
+        }
+        #[cfg(feature = "checksum")]
+        if self.checksum.is_some() {
+            return true;
+        }
+        false
+    }
+
+    /// Decodes a frame whose message is followed by a chain's hash, a tag,
+    /// and a CRC, in that order, or by some of them.
+    #[cfg(any(feature = "audit", feature = "crypto", feature = "checksum"))]
+    fn decode_trailed(&mut self, msg: &[u8]) -> Result<Option<Message>, Error> {
+        #[cfg(feature = "checksum")]
+        let msg = match self.checksum {
+            Some(checksum) => checksum.check(msg).map_err(|e| {
+                self.corrupt += 1;
+                Error::Checksum(e)
+            })?,
+            None => msg,
+        };
+        #[cfg(feature = "crypto")]
+        let msg = match &self.authentication {
+            Some(verifier) => verifier.verify(msg).map_err(Error::Crypto)?,
+            None => msg,
+        };
+        #[cfg(feature = "audit")]
+        let (msg, follows) = match &mut self.audit {
+            Some(verifier) => verifier.check(msg).map_err(Error::Audit)?,
+            None => (msg, true),
+        };
+        let frame: DeltaFrame<'_> = ::postcard::from_bytes(msg).map_err(Error::Postcard)?;
+        let msg = self.decode(frame)?;
+        #[cfg(feature = "audit")]
+        if !follows {
+            return Err(Error::Audit(crate::audit::Error::Broken));
+        }
+        Ok(msg)
+    }
+
+    /// Decodes a frame that has already been deserialized, or that was never
+    /// serialized, such as one just encoded in the same process.
+    pub fn decode<F: Form>(&mut self, frame: DeltaFrame<'_, F>) -> Result<Option<Message>, Error> {
+        let generation = self.delta.last_sync().map(|sync| sync.generation);
+        let (ticks, msg) = self.delta.decode(frame).ok_or(Error::Delta)?;
+        if let TracingWire::Sync(sync) = &msg {
+            // The device started its definitions over.
+            if generation.is_some_and(|g| g != sync.generation) {
+                self.clear_dictionary();
+            }
+            return Ok(None);
+        }
+        if self.dictionary.observe(&msg) {
+            return Ok(None);
+        }
+        if let TracingWire::TimeSync(sync) = msg {
+            self.clock.observe(sync);
+            return Ok(None);
+        }
+        if let TracingWire::Hello(hello) = &msg {
+            self.greet(hello)?;
+            return Ok(None);
+        }
+        if let TracingWire::Description(description) = &msg {
+            self.description = Some(description.to_owned());
+            return Ok(None);
+        }
+        if let TracingWire::Unknown = msg {
+            return Ok(None);
+        }
+
+        let msg = match msg {
+            TracingWire::EventRef(event) => self
+                .dictionary
+                .resolve_event(event)
+                .map(|event| TracingWire::Event(event.to_owned()))
+                .map_err(|_| Error::Undefined)?,
+            TracingWire::EventInterned(event) => self
+                .dictionary
+                .resolve_interned(event)
+                .map(|event| TracingWire::Event(event.to_owned()))
+                .map_err(|_| Error::Undefined)?,
+            TracingWire::NewSpanRef { id, attributes } => TracingWire::NewSpan {
+                id,
+                attributes: self
+                    .dictionary
+                    .resolve_attributes(&attributes)
+                    .ok_or(Error::Undefined)?,
+            },
+            msg => msg.to_owned(),
+        };
+        Ok(Some(Message {
+            ticks,
+            since_start: Duration::from_nanos(self.rate.to_nanos(ticks)),
+            wall_clock: self.clock.to_wall(ticks),
+            msg,
+        }))
+    }
+
+    /// Checks that the stream `hello` starts can be read, and takes the
+    /// device's tick rate from it.
+    fn greet(&mut self, hello: &Hello<'_>) -> Result<(), Error> {
+        hello
+            .negotiate(self.capabilities())
+            .map_err(Error::Handshake)?;
+        if let Some(rate) = hello.tick_rate.filter(|rate| *rate != self.rate) {
+            self.rate = rate;
+            self.clock = WallClock::new(rate);
+        }
+        self.hello = Some(hello.to_owned());
+        Ok(())
+    }
+
+    /// Decodes the COBS frames read from `reader`, as an iterator over
+    /// messages.
+    pub fn read<R: io::BufRead>(self, reader: R) -> Messages<R> {
+        self.read_framed(reader, Cobs)
+    }
+
+    /// Decodes the frames read from `reader`, framed with `framing`, such as
+    /// [`LengthPrefixed`](crate::framing::LengthPrefixed), as an iterator
+    /// over messages.
+    pub fn read_framed<R: io::BufRead, F: Framing>(self, reader: R, framing: F) -> Messages<R, F> {
+        Messages {
+            decoder: self,
+            reader,
+            framing,
+            buf: Vec::new(),
+        }
+    }
+}
+
+/// An iterator over the messages in a stream, returned by [`Decoder::read`].
+///
+/// Iteration ends when the reader does, dropping any incomplete last frame.
+/// Frames that fail their CRC are skipped, rather than returned as errors;
+/// see [`Decoder::corrupt_frames`] for how many.
+#[derive(Debug)]
+pub struct Messages<R, F = Cobs> {
+    decoder: Decoder,
+    reader: R,
+    framing: F,
+    buf: Vec<u8>,
+}
+
+impl<R, F> Messages<R, F> {
+    pub fn decoder(&self) -> &Decoder {
+        &self.decoder
+    }
+
+    pub fn decoder_mut(&mut self) -> &mut Decoder {
+        &mut self.decoder
+    }
+
+    pub fn into_inner(self) -> (Decoder, R) {
+        (self.decoder, self.reader)
+    }
+}
+
+impl<R: io::BufRead, F: Framing> Iterator for Messages<R, F> {
+    type Item = Result<Message, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Err(e) = read_frame(&self.framing, &mut self.reader, &mut self.buf)? {
+                return Some(Err(e));
+            }
+            let decoded = match self.framing.unframe(&mut self.buf) {
+                Ok(frame) => self.decoder.decode_unframed(frame),
+                Err(e) => Err(Error::Postcard(e)),
+            };
+            match decoded {
+                Ok(Some(msg)) => return Some(Ok(msg)),
+                Ok(None) => continue,
+                #[cfg(feature = "checksum")]
+                Err(Error::Checksum(_)) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// Reads the next frame into `buf`. Returns `None` at the end of the stream.
+fn read_frame<F: Framing>(
+    framing: &F,
+    reader: &mut impl io::BufRead,
+    buf: &mut Vec<u8>,
+) -> Option<Result<(), Error>> {
+    match framing.read_frame(reader, buf) {
+        Ok(true) => Some(Ok(())),
+        Ok(false) => None,
+        Err(e) => Some(Err(e.into())),
+    }
+}
+
+/// A decoded message, and the source it came from.
+#[derive(Debug)]
+pub struct SourceMessage {
+    pub source: SourceId,
+    pub message: Message,
+}
+
+/// Interleaves the messages of several sources into one sequence, ordered by
+/// timestamp.
+///
+/// Each source's frames arrive in order, but the sources can be arbitrarily
+/// far apart: one core's frames may be buffered for a while before they are
+/// sent. So [`Merger::pop`] only returns a message once every source has sent
+/// one at least as late, and a source that stops sending holds back the
+/// others until [`Merger::remove`] or [`Merger::drain`] is called.
+///
+/// All sources are assumed to count ticks of the same clock, as the cores of
+/// one chip do, so a [`TimeSync`] from any of them sets the time of day of
+/// the messages of all of them.
+#[derive(Debug)]
+pub struct Merger {
+    rate: TickRate,
+    clock: WallClock,
+    sources: BTreeMap<SourceId, Source>,
+    pending: BinaryHeap<Reverse<Pending>>,
+    seq: u64,
+}
+
+#[derive(Debug)]
+struct Source {
+    decoder: Decoder,
+    latest: Duration,
+}
+
+/// A message waiting in a [`Merger`], ordered by timestamp, then arrival.
+#[derive(Debug)]
+struct Pending {
+    seq: u64,
+    msg: SourceMessage,
+}
+
+impl Pending {
+    fn key(&self) -> (Duration, u64) {
+        (self.msg.message.since_start, self.seq)
+    }
+}
+
+impl PartialEq for Pending {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Pending {}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Pending {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+impl Merger {
+    /// A merger for sources whose clocks run at `rate`.
+    pub fn new(rate: TickRate) -> Self {
+        Self {
+            rate,
+            clock: WallClock::new(rate),
+            sources: BTreeMap::new(),
+            pending: BinaryHeap::new(),
+            seq: 0,
+        }
+    }
+
+    /// Wait for messages from `source`, even before it has sent any.
+    ///
+    /// Sources are otherwise only waited for once their first frame arrives.
+    pub fn add(&mut self, source: SourceId) {
+        let rate = self.rate;
+        self.sources.entry(source).or_insert_with(|| Source {
+            decoder: Decoder::new(rate),
+            latest: Duration::ZERO,
+        });
+    }
+
+    /// The mapping from the sources' ticks to the time of day.
+    pub fn wall_clock(&self) -> &WallClock {
+        &self.clock
+    }
+
+    pub fn wall_clock_mut(&mut self) -> &mut WallClock {
+        &mut self.clock
+    }
+
+    /// Returns the decoder of `source`, if it has been added or has sent anything.
+    pub fn decoder(&self, source: SourceId) -> Option<&Decoder> {
+        self.sources.get(&source).map(|s| &s.decoder)
+    }
+
+    /// Decodes one COBS encoded [`SourceFrame`], without its terminating zero
+    /// byte, and queues its message.
+    ///
+    /// Errors are those of [`Decoder::decode_frame`]; the frame is dropped.
+    pub fn decode_frame(&mut self, frame: &mut [u8]) -> Result<(), Error> {
+        let frame = Cobs.unframe(frame).map_err(Error::Postcard)?;
+        self.decode_unframed(frame)
+    }
+
+    /// Decodes one [`SourceFrame`] whose framing has already been removed,
+    /// and queues its message.
+    pub fn decode_unframed(&mut self, frame: &[u8]) -> Result<(), Error> {
+        let frame: SourceFrame<'_> = ::postcard::from_bytes(frame).map_err(Error::Postcard)?;
+        self.decode(frame)
+    }
+
+    /// Queues the message of a frame that has already been deserialized.
+    pub fn decode<F: Form>(&mut self, frame: SourceFrame<'_, F>) -> Result<(), Error> {
+        self.add(frame.source);
+        if let TracingWire::TimeSync(sync) = frame.frame.msg {
+            self.clock.observe(sync);
+        }
+        let source = self.sources.get_mut(&frame.source).expect("just added");
+        if let Some(message) = source.decoder.decode(frame.frame)? {
+            source.latest = source.latest.max(message.since_start);
+            self.seq += 1;
+            self.pending.push(Reverse(Pending {
+                seq: self.seq,
+                msg: SourceMessage {
+                    source: frame.source,
+                    message,
+                },
+            }));
+        }
+        Ok(())
+    }
+
+    /// Returns the earliest queued message, if no source can send an earlier
+    /// one anymore.
+    pub fn pop(&mut self) -> Option<SourceMessage> {
+        let watermark = self.sources.values().map(|s| s.latest).min()?;
+        if self.pending.peek()?.0.msg.message.since_start > watermark {
+            return None;
+        }
+        self.next_pending()
+    }
+
+    /// Pops the earliest queued message, with its time of day from the
+    /// latest syncs.
+    fn next_pending(&mut self) -> Option<SourceMessage> {
+        let Reverse(Pending { mut msg, .. }) = self.pending.pop()?;
+        msg.message.wall_clock = self.clock.to_wall(msg.message.ticks);
+        Some(msg)
+    }
+
+    /// Stop waiting for `source`, e.g. when it has gone quiet or its stream
+    /// has ended. Its queued messages are kept.
+    pub fn remove(&mut self, source: SourceId) -> Option<Decoder> {
+        self.sources.remove(&source).map(|s| s.decoder)
+    }
+
+    /// Returns all queued messages in order, e.g. once every stream has ended.
+    pub fn drain(&mut self) -> impl Iterator<Item = SourceMessage> + '_ {
+        core::iter::from_fn(|| self.next_pending())
+    }
+
+    /// Decodes the [`SourceFrame`]s read from `reader`, as an iterator over
+    /// the merged messages.
+    ///
+    /// Once the reader ends, the remaining queued messages are returned too.
+    pub fn read<R: io::BufRead>(self, reader: R) -> MergedMessages<R> {
+        self.read_framed(reader, Cobs)
+    }
+
+    /// Decodes the [`SourceFrame`]s read from `reader`, framed with
+    /// `framing`, as [`read`](Self::read) does.
+    pub fn read_framed<R: io::BufRead, F: Framing>(
+        self,
+        reader: R,
+        framing: F,
+    ) -> MergedMessages<R, F> {
+        MergedMessages {
+            merger: self,
+            reader,
+            framing,
+            buf: Vec::new(),
+            done: false,
+        }
+    }
+}
+
+/// An iterator over the merged messages of a stream of [`SourceFrame`]s,
+/// returned by [`Merger::read`].
+#[derive(Debug)]
+pub struct MergedMessages<R, F = Cobs> {
+    merger: Merger,
+    reader: R,
+    framing: F,
+    buf: Vec<u8>,
+    done: bool,
+}
+
+impl<R, F> MergedMessages<R, F> {
+    pub fn merger(&self) -> &Merger {
+        &self.merger
+    }
+
+    pub fn merger_mut(&mut self) -> &mut Merger {
+        &mut self.merger
+    }
+
+    pub fn into_inner(self) -> (Merger, R) {
+        (self.merger, self.reader)
+    }
+}
+
+impl<R: io::BufRead, F: Framing> Iterator for MergedMessages<R, F> {
+    type Item = Result<SourceMessage, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.done {
+                return self.merger.next_pending().map(Ok);
+            }
+            if let Some(msg) = self.merger.pop() {
+                return Some(Ok(msg));
+            }
+            match read_frame(&self.framing, &mut self.reader, &mut self.buf) {
+                None => self.done = true,
+                Some(Err(e)) => return Some(Err(e)),
+                Some(Ok(())) => {
+                    let decoded = match self.framing.unframe(&mut self.buf) {
+                        Ok(frame) => self.merger.decode_unframed(frame),
+                        Err(e) => Err(Error::Postcard(e)),
+                    };
+                    if let Err(e) = decoded {
+                        return Some(Err(e));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A message from one of the streams given to [`merge`], tagged with that
+/// stream.
+#[derive(Debug)]
+pub struct StreamMessage<S> {
+    pub stream: S,
+    pub message: Message,
+}
+
+/// An error from one of the streams given to [`merge`].
+#[derive(Debug)]
+pub struct StreamError<S> {
+    pub stream: S,
+    pub error: Error,
+}
+
+impl<S: fmt::Display> fmt::Display for StreamError<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.stream, self.error)
+    }
+}
+
+impl<S: fmt::Debug + fmt::Display> std::error::Error for StreamError<S> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Merges separately decoded streams, such as those of a fleet of devices or
+/// of several processes, into one sequence ordered by timestamp. Each stream
+/// is tagged with an `S`, e.g. the name of its file or device, which is
+/// returned with each of its messages.
+///
+/// ```rust,no_run
+/// use std::{fs::File, io::BufReader};
+/// use tracing_serde_structured::{host::{self, Decoder}, time::TickRate};
+///
+/// let streams = ["a.bin", "b.bin"].map(|path| {
+///     let file = BufReader::new(File::open(path).unwrap());
+///     (path, Decoder::new(TickRate::MICROS).read(file))
+/// });
+/// for msg in host::merge(streams).by_wall_clock() {
+///     match msg {
+///         Ok(msg) => println!("{} {:?}", msg.stream, msg.message.msg),
+///         Err(e) => eprintln!("{e}"),
+///     }
+/// }
+/// ```
+///
+/// Unlike a [`Merger`], which decodes the sources of one stream, the streams
+/// are decoded separately and each can be any iterator over messages, such
+/// as a [`Messages`] reading a file or a device.
+pub fn merge<S, I, T>(streams: I) -> Merged<S, T::IntoIter>
+where
+    I: IntoIterator<Item = (S, T)>,
+    T: IntoIterator<Item = Result<Message, Error>>,
+{
+    Merged {
+        streams: streams
+            .into_iter()
+            .map(|(tag, stream)| Stream {
+                tag,
+                iter: Some(stream.into_iter()),
+                latest: Duration::ZERO,
+            })
+            .collect(),
+        pending: BinaryHeap::new(),
+        seq: 0,
+        wall_clock: false,
+        window: Duration::ZERO,
+        capacity: Merged::<S, T::IntoIter>::DEFAULT_CAPACITY,
+    }
+}
+
+/// The iterator returned by [`merge`].
+///
+/// Each stream is read in turn, always the one furthest behind, and a message
+/// is returned once every stream that has not ended has read past it. Messages
+/// that are out of order within their stream, e.g. from a process with
+/// several threads, are put in order as long as they are at most
+/// [`window`](Merged::set_window) out of place, and at most
+/// [`capacity`](Merged::set_capacity) messages are buffered, however far
+/// apart the streams are.
+///
+/// Reading a live source blocks until it sends something, so a source that
+/// has gone quiet holds back the others.
+#[derive(Debug)]
+pub struct Merged<S, I> {
+    streams: Vec<Stream<S, I>>,
+    pending: BinaryHeap<Reverse<MergePending<S>>>,
+    seq: u64,
+    wall_clock: bool,
+    window: Duration,
+    capacity: usize,
+}
+
+#[derive(Debug)]
+struct Stream<S, I> {
+    tag: S,
+    /// `None` once the stream has ended.
+    iter: Option<I>,
+    latest: Duration,
+}
+
+/// A message waiting in a [`Merged`], ordered by timestamp, then arrival.
+#[derive(Debug)]
+struct MergePending<S> {
+    key: Duration,
+    seq: u64,
+    msg: StreamMessage<S>,
+}
+
+impl<S> PartialEq for MergePending<S> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.key, self.seq) == (other.key, other.seq)
+    }
+}
+
+impl<S> Eq for MergePending<S> {}
+
+impl<S> PartialOrd for MergePending<S> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<S> Ord for MergePending<S> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.key, self.seq).cmp(&(other.key, other.seq))
+    }
+}
+
+impl<S, I> Merged<S, I> {
+    /// The number of messages buffered at most, by default.
+    pub const DEFAULT_CAPACITY: usize = 4096;
+
+    /// Order messages by [`Message::wall_clock`] rather than
+    /// [`Message::since_start`], for streams whose clocks started at
+    /// different times, such as those of different devices.
+    ///
+    /// Messages from before a stream's first time sync have no time of day,
+    /// and are ordered as if they happened with the message before them from
+    /// the same stream, or at the very start.
+    pub fn by_wall_clock(mut self) -> Self {
+        self.wall_clock = true;
+        self
+    }
+
+    /// Put messages up to `window` out of order within their stream back in
+    /// order, at the cost of holding each message back until every stream
+    /// has read `window` past it.
+    pub fn set_window(&mut self, window: Duration) {
+        self.window = window;
+    }
+
+    /// Buffer at most `capacity` messages, at least one. Once that many are
+    /// buffered, the earliest is returned even if a stream could still send
+    /// an earlier one.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+    }
+
+    /// The number of messages buffered.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    fn key(&self, stream: usize, message: &Message) -> Duration {
+        if !self.wall_clock {
+            return message.since_start;
+        }
+        match message.wall_clock {
+            Some(wall_clock) => wall_clock.duration_since(UNIX_EPOCH).unwrap_or_default(),
+            None => self.streams[stream].latest,
+        }
+    }
+
+    /// Whether the earliest buffered message can be returned.
+    fn ready(&self) -> bool {
+        let Some(Reverse(first)) = self.pending.peek() else {
+            return false;
+        };
+        if self.pending.len() >= self.capacity {
+            return true;
+        }
+        match self.furthest_behind() {
+            Some(i) => first.key.saturating_add(self.window) <= self.streams[i].latest,
+            None => true,
+        }
+    }
+
+    /// The stream that has not ended and has read the least far.
+    fn furthest_behind(&self) -> Option<usize> {
+        (0..self.streams.len())
+            .filter(|&i| self.streams[i].iter.is_some())
+            .min_by_key(|&i| self.streams[i].latest)
+    }
+}
+
+impl<S: Clone, I: Iterator<Item = Result<Message, Error>>> Iterator for Merged<S, I> {
+    type Item = Result<StreamMessage<S>, StreamError<S>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.ready() {
+                let Reverse(MergePending { msg, .. }) = self.pending.pop()?;
+                return Some(Ok(msg));
+            }
+            let i = self.furthest_behind()?;
+            let stream = &mut self.streams[i];
+            let next = stream.iter.as_mut().and_then(Iterator::next);
+            match next {
+                None => stream.iter = None,
+                Some(Err(error)) => {
+                    return Some(Err(StreamError {
+                        stream: stream.tag.clone(),
+                        error,
+                    }))
+                }
+                Some(Ok(message)) => {
+                    let key = self.key(i, &message);
+                    let stream = &mut self.streams[i];
+                    stream.latest = stream.latest.max(key);
+                    self.seq += 1;
+                    self.pending.push(Reverse(MergePending {
+                        key,
+                        seq: self.seq,
+                        msg: StreamMessage {
+                            stream: stream.tag.clone(),
+                            message,
+                        },
+                    }));
+                }
+            }
+        }
+    }
+}
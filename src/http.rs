@@ -0,0 +1,489 @@
+//! An exporter posting batches of messages to an HTTP intake.
+//!
+//! An [`HttpExporter`] collects messages into batches, and sends each batch
+//! as the body of one `POST` request, gzip compressed, to a log intake or a
+//! collector of one's own:
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//!
+//! use tracing_serde_structured::{
+//!     http::{Encoding, HttpExporter},
+//!     SerializeLevel,
+//! };
+//!
+//! let mut exporter = HttpExporter::new("https://logs.example.com/v1/intake")
+//!     .with_header("Authorization", "Bearer 0123456789abcdef")
+//!     .with_encoding(Encoding::Ndjson)
+//!     .with_batch_size(500)
+//!     .with_linger(Duration::from_secs(5));
+//! # let msg = SerializeLevel::Info;
+//! if let Err(e) = exporter.send(&msg) {
+//!     eprintln!("intake unreachable: {e}");
+//! }
+//! // Before exiting:
+//! exporter.flush().ok();
+//! ```
+//!
+//! A batch is sent once it holds [`with_batch_size`](HttpExporter::with_batch_size)
+//! messages or [`with_batch_bytes`](HttpExporter::with_batch_bytes) bytes,
+//! once it has been open for longer than the
+//! [linger](HttpExporter::with_linger) when a message is sent, or when the
+//! exporter is [flushed](HttpExporter::flush). With
+//! [`Encoding::Ndjson`], the body holds one JSON document per line. With
+//! [`Encoding::Postcard`], it holds COBS framed postcard messages, one after
+//! the other, as a [`host::Decoder`](crate::host::Decoder) reads them.
+//!
+//! Requests block until the intake answers. A batch the intake could not be
+//! reached for, or that it answered with `408`, `429` or a `5xx` status,
+//! stays queued and is sent again once the backoff has passed, waiting
+//! longer after every failed attempt. Any other status rejects the batch for
+//! good, and its messages are dropped. The batches waiting to be sent are
+//! bounded by [`with_max_buffered`](HttpExporter::with_max_buffered): once
+//! they take more bytes than that, the oldest ones are dropped, and their
+//! messages counted in the [`stats`](HttpExporter::stats).
+//!
+//! `https://` URLs need one of `ureq`'s TLS features enabled, or an
+//! [agent](HttpExporter::with_agent) set up with a TLS provider.
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    io::{self, Write},
+    time::Duration,
+};
+
+use serde::Serialize;
+
+use crate::wire::SerializePipelineStats;
+
+/// Errors returned by an [`HttpExporter`].
+#[derive(Debug)]
+pub enum Error {
+    /// The message could not be encoded as postcard.
+    Postcard(::postcard::Error),
+    /// The message could not be encoded as JSON.
+    Json(serde_json::Error),
+    /// The batch could not be compressed.
+    Compress(io::Error),
+    /// The intake could not be reached. The batch stays queued.
+    Http(ureq::Error),
+    /// The intake answered with a status other than `2xx`. The batch stays
+    /// queued if the status is `408`, `429` or `5xx`, and is dropped
+    /// otherwise.
+    Status(u16),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Postcard(e) => write!(f, "failed to encode message as postcard: {e}"),
+            Error::Json(e) => write!(f, "failed to encode message as JSON: {e}"),
+            Error::Compress(e) => write!(f, "failed to compress batch: {e}"),
+            Error::Http(e) => write!(f, "failed to send batch: {e}"),
+            Error::Status(status) => write!(f, "intake answered with status {status}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Postcard(e) => Some(e),
+            Error::Json(e) => Some(e),
+            Error::Compress(e) => Some(e),
+            Error::Http(e) => Some(e),
+            Error::Status(_) => None,
+        }
+    }
+}
+
+impl Error {
+    /// Returns `true` if the batch stays queued, to be sent again.
+    pub fn is_retried(&self) -> bool {
+        match self {
+            Error::Http(_) => true,
+            Error::Status(status) => retried(*status),
+            _ => false,
+        }
+    }
+}
+
+/// How messages are encoded in the body of a batch.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    /// One JSON document per line, sent as `application/x-ndjson`.
+    #[default]
+    Ndjson,
+    /// COBS framed postcard messages, sent as `application/octet-stream`.
+    Postcard,
+}
+
+impl Encoding {
+    /// Appends `msg` to `body`, encoded with this encoding.
+    fn encode<T: Serialize + ?Sized>(&self, body: &mut Vec<u8>, msg: &T) -> Result<(), Error> {
+        match self {
+            Encoding::Ndjson => {
+                // Encoding into the body directly could leave half a line
+                // behind on error.
+                let line = serde_json::to_vec(msg).map_err(Error::Json)?;
+                body.extend_from_slice(&line);
+                body.push(b'\n');
+            }
+            Encoding::Postcard => {
+                let frame = ::postcard::to_allocvec_cobs(msg).map_err(Error::Postcard)?;
+                body.extend_from_slice(&frame);
+            }
+        }
+        Ok(())
+    }
+
+    fn content_type(&self) -> &'static str {
+        match self {
+            Encoding::Ndjson => "application/x-ndjson",
+            Encoding::Postcard => "application/octet-stream",
+        }
+    }
+}
+
+/// How the body of a batch is compressed, as named by its
+/// `Content-Encoding` header.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ContentEncoding {
+    /// The body is sent as-is.
+    Identity,
+    /// The body is gzip compressed, which nearly every intake accepts.
+    #[default]
+    Gzip,
+}
+
+impl ContentEncoding {
+    fn compress(&self, body: Vec<u8>) -> Result<Vec<u8>, Error> {
+        match self {
+            ContentEncoding::Identity => Ok(body),
+            ContentEncoding::Gzip => {
+                let mut gzip = flate2::write::GzEncoder::new(
+                    Vec::with_capacity(body.len() / 4),
+                    flate2::Compression::default(),
+                );
+                gzip.write_all(&body).map_err(Error::Compress)?;
+                gzip.finish().map_err(Error::Compress)
+            }
+        }
+    }
+}
+
+/// How long to wait before sending again, doubling after every failed
+/// attempt.
+#[derive(Debug)]
+struct Backoff {
+    min: Duration,
+    max: Duration,
+    delay: Duration,
+    retry_at: Option<Duration>,
+}
+
+impl Backoff {
+    fn is_due(&self, now: Duration) -> bool {
+        self.retry_at.is_none_or(|at| now >= at)
+    }
+
+    fn failed(&mut self, now: Duration) {
+        self.retry_at = Some(now + self.delay);
+        self.delay = (self.delay * 2).min(self.max);
+    }
+
+    fn reset(&mut self) {
+        self.delay = self.min;
+        self.retry_at = None;
+    }
+}
+
+/// A compressed batch, waiting to be sent.
+#[derive(Debug)]
+struct Batch {
+    body: Vec<u8>,
+    messages: u32,
+}
+
+/// Posts batches of messages to an HTTP endpoint, retrying those that fail.
+pub struct HttpExporter {
+    agent: ureq::Agent,
+    url: String,
+    headers: Vec<(String, String)>,
+    encoding: Encoding,
+    content_encoding: ContentEncoding,
+    /// The batch being filled, encoded but not compressed yet.
+    open: Vec<u8>,
+    open_messages: u32,
+    opened_at: Duration,
+    queue: VecDeque<Batch>,
+    queued_bytes: usize,
+    batch_size: u32,
+    batch_bytes: usize,
+    linger: Option<Duration>,
+    max_buffered: usize,
+    backoff: Backoff,
+    stats: SerializePipelineStats,
+}
+
+impl fmt::Debug for HttpExporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpExporter")
+            .field("url", &self.url)
+            .field("encoding", &self.encoding)
+            .field("content_encoding", &self.content_encoding)
+            .field("open", &self.open_messages)
+            .field("queued", &self.queue.len())
+            .field("stats", &self.stats)
+            .finish_non_exhaustive()
+    }
+}
+
+impl HttpExporter {
+    /// The number of messages in a batch, by default.
+    pub const DEFAULT_BATCH_SIZE: u32 = 512;
+
+    /// The bytes of encoded messages in a batch, by default: 1 MiB.
+    pub const DEFAULT_BATCH_BYTES: usize = 1 << 20;
+
+    /// The bytes the exporter holds on to, by default: 16 MiB.
+    pub const DEFAULT_MAX_BUFFERED: usize = 16 << 20;
+
+    /// An exporter posting gzip compressed NDJSON batches to `url`.
+    ///
+    /// It waits 500 ms before sending again after a failed attempt, and
+    /// twice as long after every further one, up to 30 s.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            agent: ureq::Agent::new_with_defaults(),
+            url: url.into(),
+            headers: Vec::new(),
+            encoding: Encoding::default(),
+            content_encoding: ContentEncoding::default(),
+            open: Vec::new(),
+            open_messages: 0,
+            opened_at: Duration::ZERO,
+            queue: VecDeque::new(),
+            queued_bytes: 0,
+            batch_size: Self::DEFAULT_BATCH_SIZE,
+            batch_bytes: Self::DEFAULT_BATCH_BYTES,
+            linger: None,
+            max_buffered: Self::DEFAULT_MAX_BUFFERED,
+            backoff: Backoff {
+                min: Duration::from_millis(500),
+                max: Duration::from_secs(30),
+                delay: Duration::from_millis(500),
+                retry_at: None,
+            },
+            stats: SerializePipelineStats::default(),
+        }
+    }
+
+    /// Send requests with `agent`, e.g. one with timeouts, a proxy or a TLS
+    /// provider set up.
+    pub fn with_agent(mut self, agent: ureq::Agent) -> Self {
+        self.agent = agent;
+        self
+    }
+
+    /// Add a header to every request, such as the intake's API key.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Set how messages are encoded.
+    pub fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Set how batches are compressed.
+    pub fn with_content_encoding(mut self, content_encoding: ContentEncoding) -> Self {
+        self.content_encoding = content_encoding;
+        self
+    }
+
+    /// Send a batch once it holds `batch_size` messages, rather than
+    /// [`DEFAULT_BATCH_SIZE`](Self::DEFAULT_BATCH_SIZE).
+    pub fn with_batch_size(mut self, batch_size: u32) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Send a batch once its messages take `batch_bytes` bytes, before
+    /// compression, rather than [`DEFAULT_BATCH_BYTES`](Self::DEFAULT_BATCH_BYTES).
+    /// A message larger than that is sent in a batch of its own.
+    pub fn with_batch_bytes(mut self, batch_bytes: usize) -> Self {
+        self.batch_bytes = batch_bytes.max(1);
+        self
+    }
+
+    /// Send a batch when a message is sent more than `linger` after the
+    /// batch's first one, however few messages it holds. By default, batches
+    /// wait until they are full, or the exporter is flushed.
+    pub fn with_linger(mut self, linger: Duration) -> Self {
+        self.linger = Some(linger);
+        self
+    }
+
+    /// Hold on to at most `max_buffered` bytes of batches waiting to be sent,
+    /// rather than [`DEFAULT_MAX_BUFFERED`](Self::DEFAULT_MAX_BUFFERED),
+    /// dropping the oldest batches beyond that. The newest batch is always
+    /// kept.
+    pub fn with_max_buffered(mut self, max_buffered: usize) -> Self {
+        self.max_buffered = max_buffered;
+        self
+    }
+
+    /// Wait `min` before sending again after a failed attempt, and twice as
+    /// long after every further one, up to `max`.
+    pub fn with_backoff(mut self, min: Duration, max: Duration) -> Self {
+        self.backoff.min = min;
+        self.backoff.max = max.max(min);
+        self.backoff.delay = min;
+        self
+    }
+
+    /// Encodes `msg` into the open batch, and if that fills the batch, or
+    /// its linger has passed, sends it and any batches queued before it.
+    ///
+    /// An error sending leaves the batch queued, to be sent again with the
+    /// next batch or flush, unless the intake rejected it for good.
+    pub fn send<T>(&mut self, msg: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let now = now();
+        let lingered = self.open_messages > 0
+            && self
+                .linger
+                .is_some_and(|linger| now.saturating_sub(self.opened_at) >= linger);
+        if let Err(e) = self.encoding.encode(&mut self.open, msg) {
+            count(&mut self.stats.errors, 1);
+            return Err(e);
+        }
+        if self.open_messages == 0 {
+            self.opened_at = now;
+        }
+        self.open_messages += 1;
+        if lingered || self.open_messages >= self.batch_size || self.open.len() >= self.batch_bytes
+        {
+            self.flush()
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Closes the open batch, if it holds any messages, and sends every
+    /// queued batch, oldest first, unless the backoff after a failed
+    /// attempt has not passed yet.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.close_batch()?;
+        if !self.backoff.is_due(now()) {
+            return Ok(());
+        }
+        while let Some(batch) = self.queue.front() {
+            let result = self.post(&batch.body);
+            if let Err(e) = &result {
+                if e.is_retried() {
+                    self.backoff.failed(now());
+                    return result;
+                }
+            }
+            let batch = self.queue.pop_front().expect("queue is not empty");
+            self.queued_bytes -= batch.body.len();
+            match result {
+                Ok(()) => {
+                    count(&mut self.stats.sent, batch.messages);
+                    self.backoff.reset();
+                }
+                Err(e) => {
+                    count(&mut self.stats.dropped, batch.messages);
+                    return Err(e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Compresses the open batch and queues it, dropping the oldest batches
+    /// if the queue takes more than `max_buffered` bytes.
+    fn close_batch(&mut self) -> Result<(), Error> {
+        if self.open_messages == 0 {
+            return Ok(());
+        }
+        let messages = core::mem::take(&mut self.open_messages);
+        let body = core::mem::take(&mut self.open);
+        let body = match self.content_encoding.compress(body) {
+            Ok(body) => body,
+            Err(e) => {
+                count(&mut self.stats.errors, messages);
+                return Err(e);
+            }
+        };
+        self.queued_bytes += body.len();
+        self.queue.push_back(Batch { body, messages });
+        while self.queued_bytes > self.max_buffered && self.queue.len() > 1 {
+            let batch = self.queue.pop_front().expect("queue is not empty");
+            self.queued_bytes -= batch.body.len();
+            count(&mut self.stats.dropped, batch.messages);
+        }
+        Ok(())
+    }
+
+    fn post(&self, body: &[u8]) -> Result<(), Error> {
+        let mut request = self
+            .agent
+            .post(&self.url)
+            .header("Content-Type", self.encoding.content_type());
+        if self.content_encoding == ContentEncoding::Gzip {
+            request = request.header("Content-Encoding", "gzip");
+        }
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        match request.send(body) {
+            Ok(response) if response.status().is_success() => Ok(()),
+            Ok(response) => Err(Error::Status(response.status().as_u16())),
+            // Agents treat statuses of 400 and above as errors by default.
+            Err(ureq::Error::StatusCode(status)) => Err(Error::Status(status)),
+            Err(e) => Err(Error::Http(e)),
+        }
+    }
+
+    /// The number of messages in the open batch.
+    pub fn pending(&self) -> u32 {
+        self.open_messages
+    }
+
+    /// The number of closed batches waiting to be sent.
+    pub fn queued(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// The counts of messages sent and lost so far.
+    pub fn stats(&self) -> SerializePipelineStats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = SerializePipelineStats::default();
+    }
+}
+
+/// Returns `true` if a request answered with `status` is worth sending
+/// again.
+fn retried(status: u16) -> bool {
+    matches!(status, 408 | 429 | 500..=599)
+}
+
+fn count(count: &mut u32, n: u32) {
+    *count = count.saturating_add(n);
+}
+
+fn now() -> Duration {
+    static START: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();
+    START.get_or_init(std::time::Instant::now).elapsed()
+}
@@ -0,0 +1,350 @@
+//! Adding selected fields of the spans an event is in to the event's own.
+//!
+//! Log backends that store each event as a flat record cannot join it with
+//! the spans it happened in, so the `request_id` recorded once on a request's
+//! span is missing from every event logged while handling it. A
+//! [`FieldInheritance`] keeps the values of the chosen fields for every span,
+//! each span inheriting those of its parent, and serializes events with them
+//! added:
+//!
+//! ```rust
+//! # use tracing_core::{Event, Metadata, Subscriber};
+//! # use tracing_core::span::{Attributes, Id, Record};
+//! use tracing_serde_structured::inherit::FieldInheritance;
+//!
+//! struct Forwarder {
+//!     inheritance: FieldInheritance,
+//! }
+//!
+//! impl Forwarder {
+//!     /// The span the current thread is in, which the subscriber tracks in
+//!     /// `enter` and `exit`.
+//!     fn current(&self) -> Option<Id> {
+//!         // ...
+//!         # None
+//!     }
+//! }
+//!
+//! impl Subscriber for Forwarder {
+//!     fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+//!         let id = Id::from_u64(1);
+//!         self.inheritance.new_span(&id, attrs, self.current().as_ref());
+//!         id
+//!     }
+//!
+//!     fn record(&self, id: &Id, values: &Record<'_>) {
+//!         self.inheritance.record(id, values);
+//!     }
+//!
+//!     fn event(&self, event: &Event<'_>) {
+//!         let event = self.inheritance.event(event, self.current().as_ref());
+//!         println!("{}", serde_json::to_string(&event).unwrap());
+//!     }
+//!
+//!     fn try_close(&self, id: Id) -> bool {
+//!         self.inheritance.close(&id);
+//!         true
+//!     }
+//!
+//!     // ...
+//!     # fn enabled(&self, _: &Metadata<'_>) -> bool { true }
+//!     # fn record_follows_from(&self, _: &Id, _: &Id) {}
+//!     # fn enter(&self, _: &Id) {}
+//!     # fn exit(&self, _: &Id) {}
+//! }
+//!
+//! let forwarder = Forwarder {
+//!     inheritance: FieldInheritance::new(["request_id", "tenant"]),
+//! };
+//! ```
+//!
+//! A span inherits the values its parent has when it is created, and its own
+//! values take precedence over them. Values recorded on a span later are
+//! inherited by the events and spans created within it from then on. An
+//! event's own fields take precedence over inherited ones.
+//!
+//! A correlation id, such as the id of the request a root span handles, is
+//! inherited differently: every span of the tree shares the value of the
+//! root, even if it is recorded on the root after they were created, and a
+//! value recorded on another span is not a correlation id. With
+//! [`FieldInheritance::correlation_id`], or
+//! [`from_root`](FieldInheritance::from_root) for several fields, every event
+//! within the tree carries it:
+//!
+//! ```rust
+//! use tracing_serde_structured::inherit::FieldInheritance;
+//!
+//! let inheritance = FieldInheritance::correlation_id("request_id");
+//! ```
+//!
+//! The event is serialized like a [`SerializeEvent`], without copying it,
+//! but the names of the inherited fields are not in its metadata, which is
+//! the callsite's. [`InheritingEvent::to_owned`] adds them.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, PoisonError, RwLock},
+};
+
+use serde::{
+    ser::{SerializeMap, SerializeStruct},
+    Serialize, Serializer,
+};
+use tracing_core::{
+    span::{Attributes, Id, Record},
+    Event,
+};
+
+use crate::{
+    serialize_option, skip_none, AsSerde, CowString, FieldCount, HashVisit, Live, SerdeMapVisitor,
+    SerializeEvent, SerializeFieldSet, SerializeRecordFields, SerializeValue,
+};
+
+type Fields = BTreeMap<CowString<'static>, SerializeValue<'static>>;
+
+/// The values of the inherited fields of every open span. See the
+/// [module documentation](self).
+#[derive(Debug, Default)]
+pub struct FieldInheritance {
+    names: Vec<String>,
+    from_root: bool,
+    /// The inherited values by span, for spans that have any. With
+    /// `from_root`, only roots have values.
+    spans: RwLock<HashMap<u64, Arc<Fields>>>,
+    /// With `from_root`, the root of every span that is not one.
+    roots: RwLock<HashMap<u64, u64>>,
+}
+
+impl FieldInheritance {
+    /// Inherit the fields named `names`.
+    pub fn new<I>(names: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        Self {
+            names: names.into_iter().map(Into::into).collect(),
+            from_root: false,
+            spans: RwLock::default(),
+            roots: RwLock::default(),
+        }
+    }
+
+    /// Inherit the field `name` from root spans, as a correlation id. See
+    /// [`from_root`](Self::from_root).
+    pub fn correlation_id(name: impl Into<String>) -> Self {
+        Self::new([name.into()]).from_root()
+    }
+
+    /// Takes the values of the fields from root spans only, and gives them
+    /// to every span in the root's tree, including the values recorded on
+    /// the root after the span was created. Values of the fields recorded on
+    /// other spans are ignored, though an event's own still take precedence.
+    pub fn from_root(mut self) -> Self {
+        self.from_root = true;
+        self
+    }
+
+    /// The names of the inherited fields.
+    pub fn names(&self) -> impl Iterator<Item = &str> + '_ {
+        self.names.iter().map(String::as_str)
+    }
+
+    /// Keeps the values of the new span `id`, inheriting those of its
+    /// parent, which is `current` unless the span has an explicit parent or
+    /// is a root.
+    pub fn new_span(&self, id: &Id, attrs: &Attributes<'_>, current: Option<&Id>) {
+        let parent = if attrs.is_contextual() {
+            current
+        } else {
+            attrs.parent()
+        };
+        if self.from_root {
+            if let Some(parent) = parent {
+                let mut roots = self.roots.write().unwrap_or_else(PoisonError::into_inner);
+                let root = roots.get(&parent.into_u64()).copied();
+                roots.insert(id.into_u64(), root.unwrap_or(parent.into_u64()));
+                return;
+            }
+        }
+        let mut fields = parent
+            .and_then(|parent| self.fields(parent))
+            .map(|fields| Fields::clone(&fields))
+            .unwrap_or_default();
+        let mut visit = HashVisit(BTreeMap::new());
+        attrs.record(&mut visit);
+        fields.extend(self.selected(visit.0));
+        if !fields.is_empty() {
+            self.write().insert(id.into_u64(), Arc::new(fields));
+        }
+    }
+
+    /// Updates the values of the span `id` with those recorded on it.
+    pub fn record(&self, id: &Id, values: &Record<'_>) {
+        if self.from_root && self.root(id).is_some() {
+            return;
+        }
+        let mut visit = HashVisit(BTreeMap::new());
+        values.record(&mut visit);
+        let mut selected = self.selected(visit.0).peekable();
+        if selected.peek().is_none() {
+            return;
+        }
+        let mut spans = self.write();
+        let fields = spans.entry(id.into_u64()).or_default();
+        Arc::make_mut(fields).extend(selected);
+    }
+
+    /// Forgets the span `id`, once it is closed.
+    pub fn close(&self, id: &Id) {
+        self.write().remove(&id.into_u64());
+        if self.from_root {
+            self.roots
+                .write()
+                .unwrap_or_else(PoisonError::into_inner)
+                .remove(&id.into_u64());
+        }
+    }
+
+    /// The inherited values of the span `id`, if it has any.
+    pub fn fields(&self, id: &Id) -> Option<Arc<Fields>> {
+        let id = self.root(id).unwrap_or(id.into_u64());
+        self.spans
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&id)
+            .cloned()
+    }
+
+    /// Returns `event` with the values of the span it is in, which is
+    /// `current` unless it has an explicit parent or is a root.
+    pub fn event<'a>(&self, event: &'a Event<'a>, current: Option<&Id>) -> InheritingEvent<'a> {
+        let parent = if event.is_contextual() {
+            current
+        } else {
+            event.parent()
+        };
+        InheritingEvent {
+            event,
+            inherited: parent.and_then(|parent| self.fields(parent)),
+        }
+    }
+
+    /// The number of spans with inherited values, or with `from_root`, of
+    /// roots with values.
+    pub fn len(&self) -> usize {
+        self.spans
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn selected(
+        &self,
+        fields: Fields,
+    ) -> impl Iterator<Item = (CowString<'static>, SerializeValue<'static>)> + '_ {
+        fields
+            .into_iter()
+            .filter(|(name, _)| self.names.iter().any(|n| n == name.as_str()))
+    }
+
+    /// With `from_root`, the root of the span `id`, unless it is one.
+    fn root(&self, id: &Id) -> Option<u64> {
+        if !self.from_root {
+            return None;
+        }
+        self.roots
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&id.into_u64())
+            .copied()
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, HashMap<u64, Arc<Fields>>> {
+        self.spans.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// An event with the values it inherits, which serializes like a
+/// [`SerializeEvent`] with them added to its fields.
+#[derive(Debug, Clone)]
+pub struct InheritingEvent<'a> {
+    event: &'a Event<'a>,
+    inherited: Option<Arc<Fields>>,
+}
+
+impl<'a> InheritingEvent<'a> {
+    /// The inherited values, without those the event has a field for.
+    pub fn inherited(&self) -> impl Iterator<Item = (&str, &SerializeValue<'static>)> + '_ {
+        let fields = self.event.metadata().fields();
+        self.inherited
+            .iter()
+            .flat_map(|inherited| inherited.iter())
+            .filter(move |(name, _)| fields.field(name.as_str()).is_none())
+            .map(|(name, value)| (name.as_str(), value))
+    }
+
+    /// Returns an owned copy of the event, with the inherited values added
+    /// to its fields, and their names to its metadata.
+    pub fn to_owned(&self) -> SerializeEvent<'static> {
+        let mut event = self.event.as_serde().to_owned();
+        let fields = match &mut event.fields {
+            SerializeRecordFields::De(fields) => fields,
+            SerializeRecordFields::Ser(never) => match *never {},
+        };
+        let mut names = Vec::new();
+        for (name, value) in self.inherited() {
+            fields.insert(CowString::copied(name), value.clone());
+            names.push(CowString::copied(name));
+        }
+        if let SerializeFieldSet::De(fieldset) = &mut event.metadata.fields {
+            fieldset.extend(names);
+        }
+        event
+    }
+}
+
+impl Serialize for InheritingEvent<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let event: SerializeEvent<'_, Live> = self.event.as_serde();
+        let skip = skip_none(&serializer);
+        let mut s = serializer.serialize_struct("SerializeEvent", 3)?;
+        s.serialize_field("fields", &InheritingFields(self))?;
+        s.serialize_field("metadata", &event.metadata)?;
+        serialize_option(&mut s, skip, "parent", &event.parent)?;
+        s.end()
+    }
+}
+
+/// The event's fields, followed by the inherited ones.
+struct InheritingFields<'a, 'e>(&'a InheritingEvent<'e>);
+
+impl Serialize for InheritingFields<'_, '_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let event = self.0.event;
+        let len = if serializer.is_human_readable() {
+            None
+        } else {
+            let mut count = FieldCount(0);
+            event.record(&mut count);
+            Some(count.0 + self.0.inherited().count())
+        };
+        let mut map = SerdeMapVisitor::new(serializer.serialize_map(len)?);
+        event.record(&mut map);
+        let mut map = map.take_serializer()?;
+        for (name, value) in self.0.inherited() {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
@@ -0,0 +1,90 @@
+//! A process-wide set of shared strings, for deduplicating owned data.
+//!
+//! A collector that keeps many owned events holds the same few field names
+//! and targets over and over. With the `interner` feature, `to_owned` looks
+//! these names up in [`Interner::global`] and stores them as shared
+//! [`CowString::Copied`] strings, so every event with a `"message"` field
+//! shares one copy of `"message"`:
+//!
+//! ```rust
+//! use tracing_serde_structured::CowString;
+//!
+//! let a = CowString::Borrowed("a rather long field name").to_owned_name();
+//! let b = CowString::Borrowed("a rather long field name").to_owned_name();
+//! assert_eq!(a.as_str().as_ptr(), b.as_str().as_ptr());
+//! ```
+//!
+//! The strings interned are the field names, and the name, target, module
+//! path and file of the metadata. Field values usually differ from event to
+//! event, so they are copied as usual. Strings from `'static` metadata are not copied at all
+//! (see [`CowString::Static`]), so they are not interned either.
+//!
+//! Interned strings live as long as the interner holds them; call
+//! [`Interner::purge`] now and then to drop the ones no event uses anymore.
+
+use std::{
+    collections::HashSet,
+    sync::{Arc, OnceLock, PoisonError, RwLock},
+};
+
+#[cfg(doc)]
+use crate::CowString;
+
+/// A set of shared strings.
+#[derive(Debug, Default)]
+pub struct Interner {
+    strings: RwLock<HashSet<Arc<str>>>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The interner used by `to_owned`.
+    pub fn global() -> &'static Interner {
+        static GLOBAL: OnceLock<Interner> = OnceLock::new();
+        GLOBAL.get_or_init(Interner::new)
+    }
+
+    /// Returns the shared copy of `s`, adding it if it is not interned yet.
+    pub fn intern(&self, s: &str) -> Arc<str> {
+        if let Some(shared) = self
+            .strings
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(s)
+        {
+            return shared.clone();
+        }
+
+        let mut strings = self.strings.write().unwrap_or_else(PoisonError::into_inner);
+        if let Some(shared) = strings.get(s) {
+            return shared.clone();
+        }
+        let shared: Arc<str> = s.into();
+        strings.insert(shared.clone());
+        shared
+    }
+
+    /// The number of interned strings.
+    pub fn len(&self) -> usize {
+        self.strings
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drop the strings that are not used outside of the interner, returning
+    /// how many were dropped.
+    pub fn purge(&self) -> usize {
+        let mut strings = self.strings.write().unwrap_or_else(PoisonError::into_inner);
+        let before = strings.len();
+        strings.retain(|s| Arc::strong_count(s) > 1);
+        before - strings.len()
+    }
+}
@@ -0,0 +1,64 @@
+//! Sending trace data through a Cortex-M ITM stimulus port.
+//!
+//! On chips with a Serial Wire Output pin, the Instrumentation Trace
+//! Macrocell forwards whatever is written to its stimulus ports to the
+//! debug probe, so traces can be captured with existing tools (such as
+//! `probe-rs` or OpenOCD's `itm` support) without a spare UART or USB.
+//!
+//! An [`ItmWriter`] is an [`embedded_io::Write`](::embedded_io::Write) sink,
+//! so a [`FramedWriter`](crate::embedded_io::FramedWriter) can frame messages
+//! onto a port:
+//!
+//! ```rust,ignore
+//! use tracing_serde_structured::{embedded_io::FramedWriter, itm::ItmWriter};
+//!
+//! let mut cp = cortex_m::Peripherals::take().unwrap();
+//! let mut writer = FramedWriter::<_, 256>::new(ItmWriter::new(&mut cp.ITM.stim[1]));
+//! writer.write(&msg)?;
+//! ```
+//!
+//! The port must already be enabled, along with the ITM and the SWO pin,
+//! which is usually done by the debugger. Port 0 is commonly used for text
+//! output, so a separate port keeps the two apart on the host. Writes from
+//! different interrupt priorities to the same port interleave their bytes,
+//! so share a writer through a critical section instead.
+
+use core::{convert::Infallible, fmt};
+
+use ::cortex_m::peripheral::itm::Stim;
+
+/// Writes to one ITM stimulus port, waiting whenever its FIFO is full.
+pub struct ItmWriter<'a> {
+    port: &'a mut Stim,
+}
+
+impl<'a> fmt::Debug for ItmWriter<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ItmWriter").finish_non_exhaustive()
+    }
+}
+
+impl<'a> ItmWriter<'a> {
+    pub fn new(port: &'a mut Stim) -> Self {
+        Self { port }
+    }
+
+    pub fn get_mut(&mut self) -> &mut Stim {
+        self.port
+    }
+}
+
+impl<'a> ::embedded_io::ErrorType for ItmWriter<'a> {
+    type Error = Infallible;
+}
+
+impl<'a> ::embedded_io::Write for ItmWriter<'a> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Infallible> {
+        ::cortex_m::itm::write_all(self.port, buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+}
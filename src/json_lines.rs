@@ -0,0 +1,150 @@
+//! Newline-delimited JSON ([JSON Lines]) output.
+//!
+//! [JSON Lines]: https://jsonlines.org
+//!
+//! ```rust
+//! use tracing_serde_structured::{json_lines::Writer, SerializeLevel};
+//!
+//! let mut writer = Writer::new(Vec::new());
+//! writer.write(&SerializeLevel::Info).unwrap();
+//! writer.write("multi\nline").unwrap();
+//!
+//! let out = String::from_utf8(writer.into_inner()).unwrap();
+//! assert_eq!(out, "\"INFO\"\n\"multi\\nline\"\n");
+//! ```
+//!
+//! Events, span attributes and wire messages can also be turned into JSON
+//! with a single call, when all that's wanted is to look at them:
+//!
+//! ```rust
+//! use tracing_serde_structured::{builder::SerializeEventBuilder, SerializeLevel};
+//!
+//! let event = SerializeEventBuilder::new()
+//!     .level(SerializeLevel::Warn)
+//!     .field("attempt", 3u64)
+//!     .build();
+//!
+//! let json = event.to_json_value().unwrap();
+//! assert_eq!(json["metadata"]["level"], "WARN");
+//! assert_eq!(json["fields"]["attempt"], serde_json::json!({ "U64": 3 }));
+//! println!("{}", event.to_json_string_pretty().unwrap());
+//! ```
+
+use std::io;
+
+use serde::Serialize;
+
+use crate::{wire::TracingWire, Form, SerializeAttributes, SerializeEvent};
+
+/// When a [`Writer`] flushes the underlying writer.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FlushPolicy {
+    /// Only flush when [`Writer::flush`] is called.
+    #[default]
+    Manual,
+    /// Flush after every line.
+    EveryLine,
+}
+
+/// Writes serializable values to an [`io::Write`], one JSON document per line.
+///
+/// Each value is serialized into an internal buffer before anything is
+/// written, so a value that fails to serialize never leaves a partial line
+/// behind. Strings are escaped by `serde_json`, so a line never contains a
+/// raw newline.
+#[derive(Debug)]
+pub struct Writer<W> {
+    inner: W,
+    policy: FlushPolicy,
+    buf: Vec<u8>,
+}
+
+impl<W: io::Write> Writer<W> {
+    /// Create a new writer that only flushes when asked to.
+    pub fn new(inner: W) -> Self {
+        Self::with_flush_policy(inner, FlushPolicy::default())
+    }
+
+    /// Create a new writer with the given flush policy.
+    pub fn with_flush_policy(inner: W, policy: FlushPolicy) -> Self {
+        Self {
+            inner,
+            policy,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Serialize `value` and write it as a single line.
+    pub fn write<T>(&mut self, value: &T) -> io::Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.buf.clear();
+        serde_json::to_writer(&mut self.buf, value)?;
+        self.buf.push(b'\n');
+        self.inner.write_all(&self.buf)?;
+
+        if self.policy == FlushPolicy::EveryLine {
+            self.inner.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Flush the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    /// Returns a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Returns the underlying writer.
+    ///
+    /// The writer is not flushed first.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<'a, F: Form> SerializeEvent<'a, F> {
+    /// Serialize the event into a [`serde_json::Value`].
+    pub fn to_json_value(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::to_value(self)
+    }
+
+    /// Serialize the event as pretty-printed JSON.
+    pub fn to_json_string_pretty(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+impl<'a> SerializeAttributes<'a> {
+    /// Serialize the attributes into a [`serde_json::Value`].
+    pub fn to_json_value(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::to_value(self)
+    }
+
+    /// Serialize the attributes as pretty-printed JSON.
+    pub fn to_json_string_pretty(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+impl<'a, F: Form> TracingWire<'a, F> {
+    /// Serialize the message into a [`serde_json::Value`].
+    pub fn to_json_value(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::to_value(self)
+    }
+
+    /// Serialize the message as pretty-printed JSON.
+    pub fn to_json_string_pretty(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
@@ -0,0 +1,329 @@
+//! A sink that publishes serialized messages to a Kafka topic.
+//!
+//! The sink wraps a [`kafka::producer::Producer`], which the application
+//! configures (brokers, acks, compression) and hands over:
+//!
+//! ```rust,no_run
+//! use kafka::producer::{Producer, RequiredAcks};
+//! use tracing_serde_structured::kafka::{Encoding, KafkaSink, PartitionKey};
+//!
+//! let producer = Producer::from_hosts(vec!["localhost:9092".to_owned()])
+//!     .with_required_acks(RequiredAcks::One)
+//!     .create()
+//!     .unwrap();
+//! let sink = KafkaSink::new(producer, "traces")
+//!     .with_encoding(Encoding::Json)
+//!     .with_key(PartitionKey::Field("trace_id".into()));
+//! ```
+//!
+//! Messages are buffered and sent in batches of up to
+//! [`KafkaSink::with_batch_size`] records. Sending a batch blocks until the
+//! brokers have acknowledged it, which bounds the number of records in flight.
+//! Records the brokers did not acknowledge stay queued for the next batch, up
+//! to [`KafkaSink::with_max_pending`] records: beyond that, the oldest record
+//! is dropped for each new one, and counted in the
+//! [`stats`](KafkaSink::stats).
+//!
+//! The sink picks each record's partition itself, so that it knows which
+//! records a failing partition rejected. It does so the way the producer's
+//! [`DefaultPartitioner`](kafka::producer::DefaultPartitioner) does: by a
+//! hash of the record's key, or taking the available partitions in turn for
+//! records without one, so it takes a producer using that partitioner.
+
+use core::{fmt, hash::Hasher};
+use std::collections::VecDeque;
+
+use kafka::producer::{DefaultHasher, Producer, Record};
+use serde::Serialize;
+
+use crate::{wire::SerializePipelineStats, SerializeEvent, SerializeRecordFields};
+
+/// Errors returned by a [`KafkaSink`].
+#[derive(Debug)]
+pub enum Error {
+    /// The message could not be encoded as postcard.
+    Postcard(postcard::Error),
+    /// The message could not be encoded as JSON.
+    Json(serde_json::Error),
+    /// The batch could not be delivered.
+    Kafka(kafka::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Postcard(e) => write!(f, "failed to encode message as postcard: {e}"),
+            Error::Json(e) => write!(f, "failed to encode message as JSON: {e}"),
+            Error::Kafka(e) => write!(f, "failed to deliver batch: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// The encoding used for record values.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    /// Compact binary encoding using `postcard`.
+    #[default]
+    Postcard,
+    /// JSON, for consumers that cannot decode postcard.
+    Json,
+}
+
+impl Encoding {
+    /// Encode `msg` using this encoding.
+    pub fn encode<T: Serialize + ?Sized>(&self, msg: &T) -> Result<Vec<u8>, Error> {
+        match self {
+            Encoding::Postcard => postcard::to_allocvec(msg).map_err(Error::Postcard),
+            Encoding::Json => serde_json::to_vec(msg).map_err(Error::Json),
+        }
+    }
+}
+
+/// How the record key (and so, with the default partitioner, the partition)
+/// is chosen for an event.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub enum PartitionKey {
+    /// Records have no key, and are spread across partitions.
+    #[default]
+    None,
+    /// The event's target, keeping each module's events in order.
+    Target,
+    /// The event's explicit parent span id, if any.
+    Parent,
+    /// The value of the named field, if present (e.g. a trace id).
+    Field(String),
+}
+
+impl PartitionKey {
+    /// Returns the key for `event`, or an empty key if there is none.
+    pub fn key_for(&self, event: &SerializeEvent<'_>) -> Vec<u8> {
+        match self {
+            PartitionKey::None => Vec::new(),
+            PartitionKey::Target => event.metadata.target.as_bytes().to_vec(),
+            PartitionKey::Parent => event
+                .parent
+                .as_ref()
+                .map(|p| p.id.get().to_be_bytes().to_vec())
+                .unwrap_or_default(),
+            PartitionKey::Field(name) => match &event.fields {
+                SerializeRecordFields::De(map) => map
+                    .iter()
+                    .find(|(k, _)| k.as_str() == name)
+                    .map(|(_, v)| v.to_string().into_bytes()),
+                SerializeRecordFields::Ser(never) => match *never {},
+            }
+            .unwrap_or_default(),
+        }
+    }
+}
+
+/// Publishes serialized messages to a Kafka topic.
+pub struct KafkaSink {
+    producer: Producer,
+    topic: String,
+    encoding: Encoding,
+    key: PartitionKey,
+    batch_size: usize,
+    max_pending: usize,
+    pending: VecDeque<(Vec<u8>, Vec<u8>)>,
+    next_partition: u32,
+    stats: SerializePipelineStats,
+}
+
+impl fmt::Debug for KafkaSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KafkaSink")
+            .field("topic", &self.topic)
+            .field("encoding", &self.encoding)
+            .field("key", &self.key)
+            .field("batch_size", &self.batch_size)
+            .field("max_pending", &self.max_pending)
+            .field("pending", &self.pending.len())
+            .field("stats", &self.stats)
+            .finish_non_exhaustive()
+    }
+}
+
+impl KafkaSink {
+    /// The default number of records sent per batch.
+    pub const DEFAULT_BATCH_SIZE: usize = 128;
+
+    /// The default number of records held while they cannot be delivered.
+    pub const DEFAULT_MAX_PENDING: usize = 4096;
+
+    /// Create a sink publishing postcard-encoded, unkeyed records to `topic`.
+    pub fn new(producer: Producer, topic: impl Into<String>) -> Self {
+        Self {
+            producer,
+            topic: topic.into(),
+            encoding: Encoding::default(),
+            key: PartitionKey::default(),
+            batch_size: Self::DEFAULT_BATCH_SIZE,
+            max_pending: Self::DEFAULT_MAX_PENDING,
+            pending: VecDeque::new(),
+            next_partition: 0,
+            stats: SerializePipelineStats::default(),
+        }
+    }
+
+    /// Set the encoding of record values.
+    pub fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Set how keys are chosen by [`Self::send_event`].
+    pub fn with_key(mut self, key: PartitionKey) -> Self {
+        self.key = key;
+        self
+    }
+
+    /// Set the maximum number of records buffered before a batch is sent.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Hold at most `max_pending` records that could not be delivered yet,
+    /// rather than [`DEFAULT_MAX_PENDING`](Self::DEFAULT_MAX_PENDING).
+    pub fn with_max_pending(mut self, max_pending: usize) -> Self {
+        self.max_pending = max_pending.max(1);
+        self
+    }
+
+    /// Queue an event, keyed according to the sink's [`PartitionKey`].
+    pub fn send_event(&mut self, event: &SerializeEvent<'_>) -> Result<(), Error> {
+        let key = self.key.key_for(event);
+        self.send_keyed(key, event)
+    }
+
+    /// Queue any serializable message with an explicit key (empty for none).
+    ///
+    /// If [`Self::with_max_pending`] records are queued already, the oldest
+    /// one is dropped to make room.
+    pub fn send_keyed<T: Serialize + ?Sized>(
+        &mut self,
+        key: Vec<u8>,
+        msg: &T,
+    ) -> Result<(), Error> {
+        let value = match self.encoding.encode(msg) {
+            Ok(value) => value,
+            Err(e) => {
+                count(&mut self.stats.errors);
+                return Err(e);
+            }
+        };
+        if self.pending.len() >= self.max_pending {
+            self.pending.pop_front();
+            count(&mut self.stats.dropped);
+        }
+        self.pending.push_back((key, value));
+        if self.pending.len() >= self.batch_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// The number of records waiting to be sent.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// The counts of records delivered and lost so far.
+    pub fn stats(&self) -> SerializePipelineStats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = SerializePipelineStats::default();
+    }
+
+    /// Send all queued records, waiting for them to be acknowledged.
+    ///
+    /// If a partition rejects its records, they stay queued and are retried
+    /// by the next flush, while the records other partitions acknowledged are
+    /// not sent again; the first rejection is returned. Queued records are
+    /// not sent when the sink is dropped.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+
+        let partitions = self.partitions();
+        let records: Vec<_> = self
+            .pending
+            .iter()
+            .zip(&partitions)
+            .map(|((k, v), &partition)| {
+                Record::from_key_value(&self.topic, k.as_slice(), v.as_slice())
+                    .with_partition(partition)
+            })
+            .collect();
+        let confirms = self.producer.send_all(&records).map_err(Error::Kafka)?;
+
+        let mut rejected = Vec::new();
+        let mut error = None;
+        for confirm in confirms {
+            for partition in confirm.partition_confirms {
+                if let Err(code) = partition.offset {
+                    rejected.push(partition.partition);
+                    error.get_or_insert_with(|| kafka::Error::TopicPartitionError {
+                        topic_name: confirm.topic.clone(),
+                        partition_id: partition.partition,
+                        error_code: code,
+                    });
+                }
+            }
+        }
+        // Records without a partition of their own may have gone anywhere.
+        let mut partitions = partitions.into_iter();
+        self.pending.retain(|_| {
+            let partition = partitions.next().unwrap_or(-1);
+            let acked = error.is_none() || (partition >= 0 && !rejected.contains(&partition));
+            if acked {
+                count(&mut self.stats.sent);
+            }
+            !acked
+        });
+        error.map_or(Ok(()), |e| Err(Error::Kafka(e)))
+    }
+
+    /// The partition of each queued record, or `-1` if the producer has no
+    /// metadata for the topic.
+    fn partitions(&mut self) -> Vec<i32> {
+        let topics = self.producer.client().topics();
+        let Some(partitions) = topics.partitions(&self.topic) else {
+            return vec![-1; self.pending.len()];
+        };
+        let all = partitions.len() as u32;
+        let available = partitions.available_ids();
+        let next = &mut self.next_partition;
+        self.pending
+            .iter()
+            .map(|(key, _)| {
+                if !key.is_empty() && all > 0 {
+                    let mut hasher = DefaultHasher::default();
+                    hasher.write(key);
+                    (hasher.finish() as u32 % all) as i32
+                } else if !available.is_empty() {
+                    let id = available[*next as usize % available.len()];
+                    *next = next.wrapping_add(1);
+                    id
+                } else {
+                    -1
+                }
+            })
+            .collect()
+    }
+
+    /// Returns a mutable reference to the underlying producer.
+    pub fn producer_mut(&mut self) -> &mut Producer {
+        &mut self.producer
+    }
+}
+
+fn count(count: &mut u32) {
+    *count = count.saturating_add(1);
+}
@@ -38,6 +38,14 @@
 //! `tracing-serde-structured` is still compatible with serialization and deserialization to/from
 //! JSON, though it does change the format of the JSON data, meaning it is not a 100% drop-in replacement.
 //!
+//! The `Deserialize` implementations only rely on `deserialize_any` for human-readable formats,
+//! which describe themselves: levels are accepted by name or by number, and
+//! [`SerializeValue::Tree`] values are read as the nested data they describe. Other formats are
+//! never asked to describe their data, so non-self-describing formats like [`postcard`] and
+//! `bincode` work as well as self-describing ones. Strings are
+//! borrowed from the input where the format allows it; otherwise (e.g. escaped JSON strings,
+//! or formats that only decode from a reader) they are copied, which requires the `alloc` feature.
+//!
 //! [`tracing-serde`]: https://docs.rs/tracing-serde
 //! [`postcard`]: https://docs.rs/postcard
 //!
@@ -112,6 +120,65 @@
 //! subscriber (`JsonSubscriber` in the above example) to record serialized
 //! trace data.
 //!
+//! Subscribers that need owned copies of callsite metadata for every event can use a
+//! [`cache::MetadataCache`] to convert it only once per callsite. Those writing to flat
+//! log backends can add selected fields of the spans an event is in, such as a request
+//! id, to the event's own with an [`inherit::FieldInheritance`], and a
+//! [`baggage::SpanBaggage`] propagates W3C baggage set on a span to the spans created
+//! within it.
+//!
+//! Tests, bridges and replay tools that need messages no callsite produced can make
+//! them with the builders in the [`builder`] module.
+//!
+//! To send everything a `Subscriber` sees over a single connection, the [`wire`] module
+//! provides one message type covering all notifications, and a way of sending each
+//! callsite's metadata only once instead of with every event.
+//!
+//! ## Allocation
+//!
+//! Serializing the borrowed forms of tracing data, as returned by `as_serde` for events,
+//! attributes, records and metadata (and the reference messages of the [`wire`] module),
+//! never allocates, with any combination of stable crate features: strings are borrowed
+//! from the callsite, and field values, including `Debug` ones, are written straight to
+//! the serializer. This makes it usable where there is no allocator at all, and the test
+//! suite checks it.
+//!
+//! Whether the whole operation stays off the heap also depends on the serializer.
+//! `postcard::to_slice` and `serde_json::to_writer` into a fixed buffer do, but
+//! serializers relying on the default [`Serializer::collect_str`] allocate a `String`
+//! for every `Debug` value. To avoid allocating an output buffer for every message,
+//! encode into a reusable [`scratch::SerializeScratch`] buffer. Converting to owned data
+//! with `to_owned` always allocates.
+//!
+//! ## Threads
+//!
+//! The borrowed forms of events and records returned by `as_serde` refer to the values
+//! being recorded, which can only be read on the thread recording them. Their types are
+//! in the [`Live`] [`Form`], e.g. `SerializeEvent<'a, Live>`, and cannot leave that
+//! thread. Deserialized values, and the copies made by `to_owned`, are in the default
+//! [`Detached`] form, and are [`Send`] and [`Sync`] like any other data, so they can be
+//! queued, handed to other threads, or shared between them behind an `Arc`, with no
+//! `unsafe` involved.
+//!
+//! A detached value converts into a live one with `into`, to be sent along with live
+//! ones, and values of either form compare equal if they hold the same data.
+//!
+//! ## Field Names
+//!
+//! Names in self-describing formats such as JSON follow the Rust names, and do not
+//! change between releases:
+//!
+//! * Struct fields are `snake_case`, e.g. `module_path`, `is_span` and `is_root`.
+//! * Enum variants, such as the messages of [`wire::TracingWire`] and the kinds of
+//!   [`SerializeValue`], are `PascalCase`, e.g. `{"Debug": "..."}`.
+//! * Levels are upper case, e.g. `"INFO"`.
+//!
+//! Consumers expecting `camelCase` fields can enable the `camel-case` feature, which
+//! writes `modulePath`, `isSpan` and so on instead. Deserializing accepts both spellings
+//! of every field whichever way the feature is set, so data written before switching
+//! keeps loading. Positional formats like postcard do not write field names, and are
+//! unaffected.
+//!
 //! ##  Crate Feature Flags
 //!
 //! The following crate feature flags are available:
@@ -125,6 +192,235 @@
 //!   tracing-serde = { version = "0.2", default-features = false }
 //!   ```
 //!
+//! * `alloc`: Use `Vec` and `BTreeMap` for deserialized field sets and field maps, and
+//!   provide `to_owned` conversions, on `no_std` targets with a global allocator.
+//!   Also implements `PartialEq` for events, records, values and wire messages, which
+//!   may borrow live `tracing` data that has to be copied to be compared. Metadata,
+//!   attributes and ids are comparable either way. Provides the [`filter`] module, for
+//!   filtering by `RUST_LOG` style directives, the [`span_tree`] module, for
+//!   reconstructing the tree of spans of a decoded stream, the [`span_store`] module, for
+//!   enriching the events of a stream with the fields of their spans in bounded memory,
+//!   the [`metrics`] module, for extracting metrics from events by the names of their
+//!   fields, and the [`tee`] module, for writing each event in several formats while
+//!   visiting its fields only once. Implied by `std`.
+//!
+//! * `cbor`: Provides the [`cbor`] module, with helpers for encoding to and decoding
+//!   from canonical (deterministic) CBOR using either named or compact struct layouts,
+//!   and for streaming CBOR Sequences. Implies `std`.
+//!
+//! * `msgpack`: Provides the [`msgpack`] module, with helpers for encoding to and decoding
+//!   from MessagePack using either named or compact struct layouts. Implies `std`.
+//!
+//! * `serde-json`: Provides the [`json_lines`] module, for writing newline-delimited
+//!   JSON, and `to_json_value` and `to_json_string_pretty` methods on events, span
+//!   attributes and wire messages. Implies `std`.
+//!
+//! * `structured-event`: Provides the [`structured`] module and the [`structured_event!`]
+//!   macro, for recording values that implement `Serialize` as event fields without
+//!   flattening them into a debug string. Implies `serde-json`.
+//!
+//! * `pretty`: Provides the [`pretty`] module, for printing decoded events and spans
+//!   as colored lines for a person to read. Implies `std`.
+//!
+//! * `analysis`: Provides the [`analysis`] module, for counting decoded messages by
+//!   level, target and callsite, to find those costing the most bytes, and for
+//!   histograms of how long the spans of each callsite took. Implies `std`.
+//!
+//! * `query`: Provides the [`query`] module, for filtering decoded events by level,
+//!   target, field values and the spans they are within. Implies `std`.
+//!
+//! * `redact`: Provides the [`redact`] module, for redacting field values by the names of
+//!   their fields, or when they look like email addresses, card numbers or other personal
+//!   data, as they are recorded or after. Implies `std`.
+//!
+//! * `arrow`: Provides the [`arrow`] module, for converting events into Arrow record batches
+//!   and writing them to Parquet files. Implies `std`.
+//!
+//! * `sqlite`: Provides the [`sqlite`] module, a sink storing events and spans in a SQLite
+//!   database. Implies `std`.
+//!
+//! * `postgres`: Provides the [`postgres`](mod@postgres) module, a sink storing events in
+//!   a PostgreSQL table, with their fields as JSONB, written in batches with `COPY`.
+//!   Implies `std`.
+//!
+//! * `kafka`: Provides the [`kafka`] module, a sink publishing postcard or JSON encoded
+//!   messages to a Kafka topic. Implies `std`.
+//!
+//! * `postcard`: Provides the [`postcard`](mod@postcard) module, with helpers for encoding
+//!   into fixed buffers and `heapless` vectors, and `wire_size` methods computing the
+//!   encoded size of messages, and the [`framing`] module, for COBS or length prefixed
+//!   frames. Does not require `std`.
+//!
+//! * `deflate`, `lz4`, `zstd`: Provide the [`compression`] module, for sending batches
+//!   of wire messages compressed with the named algorithm. Any combination may be
+//!   enabled, and each batch records the algorithm it uses. Imply `std`.
+//!
+//! * `heatshrink`: Provides the [`heatshrink`](mod@heatshrink) module, for compressing
+//!   single frames on microcontrollers with too little RAM for the algorithms above.
+//!   Implies `postcard`, and does not require `std` or `alloc`.
+//!
+//! * `audit`: Provides the [`audit`] module, for tamper-evident streams in which every
+//!   frame embeds a hash of the previous one, with any [`digest::Digest`]. Adds writing
+//!   such frames to the `embedded_io` module, and verifying them to the `host` module.
+//!   Implies `postcard`, and does not require `std` or `alloc`.
+//!
+//! * `checksum`: Provides the [`checksum`] module, for detecting frames corrupted on noisy
+//!   links with a CRC-16 or CRC-32 trailer. Adds writing such frames to the `embedded_io`
+//!   module, and counting and skipping corrupt ones to the `host` module. Implies
+//!   `postcard`, and does not require `std` or `alloc`.
+//!
+//! * `crypto`: Provides the [`crypto`] module, for authenticating frames with an HMAC, or
+//!   any other [`digest::Mac`], keyed by the application. Adds writing such frames to the
+//!   `embedded_io` module, and rejecting forged ones to the `host` module. With `alloc`,
+//!   also encrypts the values of sensitive fields with an AEAD. Implies `postcard`, and
+//!   does not require `std` or `alloc`.
+//!
+//! * `host`: Provides the [`host`] module, for decoding the compact, COBS framed streams
+//!   sent by embedded devices back into self-contained messages. Implies `std` and
+//!   `postcard`.
+//!
+//! * `nonblocking`: Provides the [`nonblocking`] module, for writing framed postcard
+//!   messages to non-blocking sockets, keeping a frame the socket would block on until
+//!   it is ready, so async runtimes can drive the sink without a thread of its own.
+//!   Implies `std` and `postcard`.
+//!
+//! * `rotate`: Provides the [`rotate`] module, a sink writing delta encoded streams to
+//!   files rotated by size or age, each of which starts with a hello and the definitions
+//!   written so far, so that it can be decoded on its own. Implies `std` and `postcard`.
+//!
+//! * `wasm`: Provides the [`wasm`] module, for converting events, span attributes and
+//!   wire messages into JavaScript objects with `serde-wasm-bindgen`, and a subscriber
+//!   logging events to the browser console as such objects. Implies `std`. The crate
+//!   builds for `wasm32-unknown-unknown` with this or any other feature not relying on
+//!   C libraries, such as `sqlite`, `kafka` or `zstd`.
+//!
+//! * `grpc`: Provides the [`grpc`] module, a `tonic` client streaming postcard frames or
+//!   protobuf events to a collector service, as defined by `proto/collector.proto`, and a
+//!   sink queueing them for it without blocking. Implies `std` and `postcard`. It does
+//!   not build for `wasm32-unknown-unknown`, as `tonic`'s transport needs sockets.
+//!
+//! * `otlp`: Provides the [`otlp`] module, converting OpenTelemetry log records and spans,
+//!   as exported over OTLP, into events and span attributes, so the tools built on this
+//!   crate can read them too. Implies `std`.
+//!
+//! * `http`: Provides the [`http`](mod@http) module, an exporter posting gzip compressed
+//!   batches of NDJSON or postcard messages to an HTTP intake with `ureq`, retrying failed
+//!   batches with a backoff, and dropping the oldest once they take too much memory.
+//!   Implies `std` and `postcard`.
+//!
+//! * `mqtt`: Provides the [`mqtt`] module, a sink publishing postcard or JSON encoded
+//!   messages to MQTT topics made from each event's service, level and target with
+//!   `rumqttc`, at the chosen QoS, queueing them while the broker is unreachable.
+//!   Implies `std`.
+//!
+//! * `nats`: Provides the [`nats`] module, a sink publishing postcard or JSON encoded
+//!   messages to NATS subjects made from each event's service, level and target, in
+//!   batches, optionally waiting for JetStream to acknowledge them. Implies `std`.
+//!
+//! * `websocket`: Provides the [`websocket`] module, a sink streaming messages over a
+//!   WebSocket connection to a live viewer, with a bounded queue and reconnecting, with
+//!   `tungstenite` on native targets and the browser's `WebSocket` on `wasm32`. Implies
+//!   `std` and `postcard`.
+//!
+//! * `cli`: Builds the `tss-decode` binary, which decodes such a stream from a file,
+//!   serial port or stdin and prints it as JSON lines, logfmt, or for a person to read.
+//!   Implies `host` and `serde-json`.
+//!
+//! * `embedded-io`: Provides the [`embedded_io`](mod@embedded_io) module, for writing
+//!   COBS framed postcard messages to [`embedded_io::Write`](::embedded_io::Write)
+//!   sinks such as UARTs. Implies `postcard`, and does not require `std` or `alloc`.
+//!
+//! * `embedded-io-async`: Adds an async writer for
+//!   [`embedded_io_async::Write`] sinks to the `embedded_io` module. Implies
+//!   `embedded-io`.
+//!
+//! * `itm`: Provides the [`itm`](mod@itm) module, an `embedded_io::Write` sink for
+//!   Cortex-M ITM stimulus ports, to capture traces over SWO with a debug probe.
+//!   Implies `embedded-io`, and does not require `std`.
+//!
+//! * `semihosting`: Provides the [`semihosting`](mod@semihosting) module, an
+//!   `embedded_io::Write` sink for the debugger's console, in builds with debug
+//!   assertions only. Implies `embedded-io`, and does not require `std`.
+//!
+//! * `panic-flush`: Provides the [`panic_flush`](mod@panic_flush) module, for sending
+//!   a sink's buffered messages and a final panic message from a panic or HardFault
+//!   handler. Uses `critical-section`, and does not require `std`.
+//!
+//! * `postcard-rpc`: Provides the [`postcard_rpc`](mod@postcard_rpc) module, with a
+//!   topic for publishing wire messages and endpoints for setting the level a device
+//!   reports through [postcard-rpc](https://docs.rs/postcard-rpc). Implies
+//!   `postcard-schema`, and does not require `std`.
+//!
+//! * `fugit`: Adds [`time::FugitSource`], for timestamping with the `fugit` instants
+//!   returned by HAL and RTIC monotonic timers. Does not require `std`.
+//!
+//! * `defmt`: Implements [`defmt::Format`] for levels, ids, values and
+//!   [`TracingWire`](wire::TracingWire) messages, so firmware that logs with `defmt`
+//!   can report on its tracing pipeline without `core::fmt`. Messages are written as
+//!   their kind, ids and metadata, without field values. Does not require `std`.
+//!
+//! * `bumpalo`: Adds `to_owned_in` conversions, which copy borrowed strings into a
+//!   [`bumpalo::Bump`] arena instead of allocating each one separately. Implies `std`.
+//!
+//! * `arbitrary`: Implements [`arbitrary::Arbitrary`] for the owned forms of events,
+//!   metadata, values and wire messages, for fuzzing decoders and anything else that
+//!   consumes them. Implies `std`.
+//!
+//! * `proptest`: Provides the [`proptest`](mod@proptest) module, with strategies generating
+//!   events, metadata and values for property tests. Implies `std`.
+//!
+//! * `test-util`: Provides the [`test_util`] module, with assertions that messages
+//!   survive a round trip through postcard and JSON unchanged. Implies `std`.
+//!
+//! * `compact_str`: Stores the [`CopiedString`]s made by `to_owned` and while
+//!   deserializing inline instead of allocating, when they are short enough. Implies
+//!   `std`.
+//!
+//! * `arc-str`: Shares the [`CopiedString`]s made by `to_owned` and while deserializing
+//!   by reference counting, so that cloning an owned event (e.g. to hand it to several
+//!   sinks) only increments reference counts instead of copying every string. Takes
+//!   precedence over `compact_str`. Implies `alloc`.
+//!
+//! * `interner`: Provides the [`interner`] module, and makes `to_owned` share field names,
+//!   targets and other metadata strings through a process-wide interner instead of
+//!   copying them for every message. Implies `std` and `arc-str`.
+//!
+//! * `skip-none`: Leave out `None` values (`module_path`, `file`, `line` and `parent`)
+//!   instead of writing them as `null`, when serializing events, attributes and
+//!   metadata to human-readable formats such as JSON. Binary formats keep their fixed
+//!   layout, and deserializing accepts both forms either way.
+//!
+//! * `camel-case`: Write struct fields with more than one word in `camelCase`
+//!   (`modulePath` rather than `module_path`) in human-readable formats. See
+//!   [Field Names](#field-names).
+//!
+//! * `strip-locations`: Leave `module_path`, `file` and `line` out of serialized
+//!   metadata entirely, in every format, for production firmware where they cost
+//!   bandwidth and give away the layout of the source. Deserializing sets them to
+//!   `None`. Both ends of a positional format like postcard must agree on this
+//!   feature, and tables for the [`wire::table`] module must be recorded without it,
+//!   as they identify callsites by location.
+//!
+//! * `sorted-map`: Provides the [`sorted_map`] module, and stores deserialized field maps
+//!   in a [`SortedMap`](sorted_map::SortedMap) instead of a `heapless::FnvIndexMap` when
+//!   `alloc` is disabled, which uses less memory per entry and allows any capacity.
+//!   Has no effect on the maps used with `alloc`. Without `alloc`, this changes the type
+//!   held by [`SerializeRecordFields::De`] and [`SerializeRecord::De`], so code that names
+//!   the map type, rather than only calling the methods both maps share, builds with
+//!   either this feature or without it, not both.
+//!
+//! Without `alloc`, deserialized field sets and field maps are stored in `heapless`
+//! collections holding up to [`MAX_FIELDS`] entries. Deserializing a message with more
+//! entries than that fails with an "invalid length" error, instead of silently dropping
+//! the extra entries. To size the collections for a project, set
+//! the `TRACING_SERDE_STRUCTURED_MAX_FIELDS` environment variable to a power of two
+//! (or any number, with `sorted-map`) when building, e.g. in `.cargo/config.toml`:
+//!
+//! ```toml
+//! [env]
+//! TRACING_SERDE_STRUCTURED_MAX_FIELDS = "8"
+//! ```
+//!
 //! ### Unstable Features
 //!
 //! These feature flags enable **unstable** features. The public API may break in 0.1.x
@@ -134,7 +430,9 @@
 //! The following unstable feature flags are currently available:
 //!
 //! * `valuable`: Enables [`Visit::record_value`] implementations, for
-//!   serializing values recorded using the [`valuable`] crate.
+//!   serializing values recorded using the [`valuable`] crate. Nested values
+//!   keep their structure as a [`SerializeValue::Tree`] when `alloc` is
+//!   enabled, and are recorded as `Debug` otherwise.
 //!
 //! #### Enabling Unstable Features
 //!
@@ -203,6 +501,9 @@
 // Support using tracing-serde without the standard library!
 #![cfg_attr(not(feature = "std"), no_std)]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
 use core::fmt;
 use core::fmt::Arguments;
 use core::hash::Hash;
@@ -210,17 +511,234 @@ use core::num::NonZeroU64;
 use core::ops::Deref;
 
 use serde::{
-    ser::{SerializeMap, SerializeSeq, Serializer},
+    de::{self, Deserializer},
+    ser::{SerializeMap, SerializeSeq, SerializeStruct, Serializer},
     Deserialize, Serialize,
 };
 
+#[cfg(feature = "alloc")]
+use alloc::string::String;
+
 use tracing_core::{
     event::Event,
     field::{Field, FieldSet, Visit},
-    metadata::{Level, Metadata},
+    metadata::{Level, LevelFilter, Metadata},
     span::{Attributes, Id, Record},
 };
 
+pub mod wire;
+
+pub mod time;
+
+pub mod baggage;
+
+#[cfg(any(feature = "std", target_has_atomic = "8"))]
+pub mod scratch;
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub mod cache;
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub mod inherit;
+
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub mod builder;
+
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub mod filter;
+
+#[cfg(any(
+    feature = "query",
+    feature = "redact",
+    all(feature = "crypto", feature = "alloc")
+))]
+mod glob;
+
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub mod span_tree;
+
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub mod span_store;
+
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub mod metrics;
+
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub mod tee;
+
+#[cfg(feature = "cbor")]
+#[cfg_attr(docsrs, doc(cfg(feature = "cbor")))]
+pub mod cbor;
+
+#[cfg(feature = "msgpack")]
+#[cfg_attr(docsrs, doc(cfg(feature = "msgpack")))]
+pub mod msgpack;
+
+#[cfg(feature = "serde-json")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde-json")))]
+pub mod json_lines;
+
+#[cfg(feature = "structured-event")]
+#[cfg_attr(docsrs, doc(cfg(feature = "structured-event")))]
+pub mod structured;
+
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub mod tree;
+
+#[cfg(feature = "pretty")]
+#[cfg_attr(docsrs, doc(cfg(feature = "pretty")))]
+pub mod pretty;
+
+#[cfg(feature = "analysis")]
+#[cfg_attr(docsrs, doc(cfg(feature = "analysis")))]
+pub mod analysis;
+
+#[cfg(feature = "query")]
+#[cfg_attr(docsrs, doc(cfg(feature = "query")))]
+pub mod query;
+
+#[cfg(feature = "redact")]
+#[cfg_attr(docsrs, doc(cfg(feature = "redact")))]
+pub mod redact;
+
+#[cfg(feature = "arrow")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arrow")))]
+pub mod arrow;
+
+#[cfg(feature = "sqlite")]
+#[cfg_attr(docsrs, doc(cfg(feature = "sqlite")))]
+pub mod sqlite;
+
+#[cfg(feature = "postgres")]
+#[cfg_attr(docsrs, doc(cfg(feature = "postgres")))]
+pub mod postgres;
+
+#[cfg(feature = "kafka")]
+#[cfg_attr(docsrs, doc(cfg(feature = "kafka")))]
+pub mod kafka;
+
+#[cfg(any(feature = "deflate", feature = "lz4", feature = "zstd"))]
+#[cfg_attr(
+    docsrs,
+    doc(cfg(any(feature = "deflate", feature = "lz4", feature = "zstd")))
+)]
+pub mod compression;
+
+#[cfg(feature = "interner")]
+#[cfg_attr(docsrs, doc(cfg(feature = "interner")))]
+pub mod interner;
+
+#[cfg(feature = "bumpalo")]
+mod arena;
+
+#[cfg(feature = "arbitrary")]
+mod arbitrary_impls;
+
+#[cfg(feature = "proptest")]
+#[cfg_attr(docsrs, doc(cfg(feature = "proptest")))]
+pub mod proptest;
+
+#[cfg(feature = "test-util")]
+#[cfg_attr(docsrs, doc(cfg(feature = "test-util")))]
+pub mod test_util;
+
+#[cfg(feature = "postcard")]
+#[cfg_attr(docsrs, doc(cfg(feature = "postcard")))]
+pub mod postcard;
+
+#[cfg(feature = "postcard")]
+#[cfg_attr(docsrs, doc(cfg(feature = "postcard")))]
+pub mod framing;
+
+#[cfg(feature = "heatshrink")]
+#[cfg_attr(docsrs, doc(cfg(feature = "heatshrink")))]
+pub mod heatshrink;
+
+#[cfg(feature = "audit")]
+#[cfg_attr(docsrs, doc(cfg(feature = "audit")))]
+pub mod audit;
+
+#[cfg(feature = "checksum")]
+#[cfg_attr(docsrs, doc(cfg(feature = "checksum")))]
+pub mod checksum;
+
+#[cfg(feature = "crypto")]
+#[cfg_attr(docsrs, doc(cfg(feature = "crypto")))]
+pub mod crypto;
+
+#[cfg(feature = "host")]
+#[cfg_attr(docsrs, doc(cfg(feature = "host")))]
+pub mod host;
+
+#[cfg(feature = "nonblocking")]
+#[cfg_attr(docsrs, doc(cfg(feature = "nonblocking")))]
+pub mod nonblocking;
+
+#[cfg(feature = "rotate")]
+#[cfg_attr(docsrs, doc(cfg(feature = "rotate")))]
+pub mod rotate;
+
+#[cfg(feature = "wasm")]
+#[cfg_attr(docsrs, doc(cfg(feature = "wasm")))]
+pub mod wasm;
+
+#[cfg(feature = "grpc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "grpc")))]
+pub mod grpc;
+
+#[cfg(feature = "otlp")]
+#[cfg_attr(docsrs, doc(cfg(feature = "otlp")))]
+pub mod otlp;
+
+#[cfg(feature = "http")]
+#[cfg_attr(docsrs, doc(cfg(feature = "http")))]
+pub mod http;
+
+#[cfg(feature = "mqtt")]
+#[cfg_attr(docsrs, doc(cfg(feature = "mqtt")))]
+pub mod mqtt;
+
+#[cfg(feature = "nats")]
+#[cfg_attr(docsrs, doc(cfg(feature = "nats")))]
+pub mod nats;
+
+#[cfg(feature = "websocket")]
+#[cfg_attr(docsrs, doc(cfg(feature = "websocket")))]
+pub mod websocket;
+
+#[cfg(feature = "embedded-io")]
+#[cfg_attr(docsrs, doc(cfg(feature = "embedded-io")))]
+pub mod embedded_io;
+
+#[cfg(feature = "postcard-rpc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "postcard-rpc")))]
+pub mod postcard_rpc;
+
+#[cfg(feature = "itm")]
+#[cfg_attr(docsrs, doc(cfg(feature = "itm")))]
+pub mod itm;
+
+#[cfg(all(feature = "semihosting", debug_assertions))]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "semihosting", debug_assertions))))]
+pub mod semihosting;
+
+#[cfg(feature = "panic-flush")]
+#[cfg_attr(docsrs, doc(cfg(feature = "panic-flush")))]
+pub mod panic_flush;
+
+#[cfg(feature = "sorted-map")]
+#[cfg_attr(docsrs, doc(cfg(feature = "sorted-map")))]
+pub mod sorted_map;
+
 #[cfg(feature = "postcard-schema")]
 impl<'a> postcard_schema::Schema for CowString<'a> {
     const SCHEMA: &'static postcard_schema::schema::NamedType =
@@ -230,12 +748,18 @@ impl<'a> postcard_schema::Schema for CowString<'a> {
         };
 }
 
-#[derive(Debug, Deserialize, Eq, PartialOrd, Ord)]
-#[serde(from = "&'a str")]
+#[derive(Debug, Clone, Eq)]
 pub enum CowString<'a> {
     Borrowed(&'a str),
-    #[cfg(feature = "std")]
+    /// A string known to live forever, such as the names and targets in
+    /// `'static` [`Metadata`]. Converting it with `to_owned` does not allocate.
+    Static(&'static str),
+    #[cfg(feature = "alloc")]
     Owned(String),
+    /// A string copied by `to_owned`, or while deserializing when the input
+    /// could not be borrowed.
+    #[cfg(feature = "alloc")]
+    Copied(CopiedString),
 }
 
 impl<'a> Deref for CowString<'a> {
@@ -246,20 +770,152 @@ impl<'a> Deref for CowString<'a> {
     }
 }
 
+#[cfg(feature = "defmt")]
+impl<'a> defmt::Format for CowString<'a> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "{=str}", self.as_str())
+    }
+}
+
 impl<'a> CowString<'a> {
     pub fn as_str(&'a self) -> &'a str {
         match self {
             CowString::Borrowed(b) => b,
-            #[cfg(feature = "std")]
+            CowString::Static(s) => s,
+            #[cfg(feature = "alloc")]
             CowString::Owned(o) => o.as_str(),
+            #[cfg(feature = "alloc")]
+            CowString::Copied(c) => c.as_str(),
         }
     }
+
+    /// Whether this is a [`CowString::Borrowed`] string, which cannot be kept
+    /// past the buffer or value it borrows from.
+    pub fn is_borrowed(&self) -> bool {
+        matches!(self, CowString::Borrowed(_))
+    }
+
+    /// Whether this string can be kept for as long as needed, either because
+    /// it is `'static` or because it owns its data.
+    pub fn is_owned(&self) -> bool {
+        !self.is_borrowed()
+    }
 }
 
-#[cfg(feature = "std")]
+#[cfg(feature = "alloc")]
 impl<'a> CowString<'a> {
     pub fn to_owned(&'a self) -> CowString<'static> {
-        CowString::Owned(self.as_str().to_string())
+        match self {
+            CowString::Static(s) => CowString::Static(s),
+            _ => CowString::copied(self.as_str()),
+        }
+    }
+
+    /// Like [`Self::to_owned`], for strings repeated across many messages,
+    /// such as field names and targets. With the `interner` feature, these
+    /// are shared through [`interner::Interner::global`].
+    pub fn to_owned_name(&'a self) -> CowString<'static> {
+        match self {
+            CowString::Static(s) => CowString::Static(s),
+            #[cfg(feature = "interner")]
+            _ => CowString::Copied(CopiedString(
+                interner::Interner::global().intern(self.as_str()),
+            )),
+            #[cfg(not(feature = "interner"))]
+            _ => CowString::copied(self.as_str()),
+        }
+    }
+
+    /// Copies the string in place if it is borrowed, leaving strings that
+    /// are already owned as they are.
+    pub fn ensure_owned(&mut self) {
+        if let CowString::Borrowed(s) = *self {
+            *self = CowString::copied(s);
+        }
+    }
+
+    /// Like [`Self::ensure_owned`], with the string copied as by
+    /// [`Self::to_owned_name`].
+    fn ensure_owned_name(&mut self) {
+        if let CowString::Borrowed(s) = *self {
+            *self = CowString::Borrowed(s).to_owned_name();
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl CowString<'static> {
+    fn copied(s: &str) -> Self {
+        CowString::Copied(CopiedString(s.into()))
+    }
+
+    #[cfg(not(any(feature = "compact_str", feature = "arc-str")))]
+    fn formatted(args: fmt::Arguments<'_>) -> Self {
+        CowString::Copied(CopiedString(alloc::fmt::format(args).into()))
+    }
+
+    #[cfg(all(feature = "compact_str", not(feature = "arc-str")))]
+    fn formatted(args: fmt::Arguments<'_>) -> Self {
+        CowString::Copied(CopiedString(compact_str::format_compact!("{}", args)))
+    }
+
+    #[cfg(feature = "arc-str")]
+    fn formatted(args: fmt::Arguments<'_>) -> Self {
+        CowString::Copied(CopiedString(alloc::fmt::format(args).into()))
+    }
+}
+
+/// The storage of a [`CowString::Copied`] string.
+///
+/// By default, this is a `Box<str>`. With the `compact_str` feature, strings
+/// of up to 24 bytes (on 64-bit targets) are stored inline instead, and with
+/// the `arc-str` feature, which takes precedence, they are shared by
+/// reference counting, so that cloning one does not copy the string. The
+/// storage is private, so that enabling these features does not change the
+/// variants of [`CowString`] or what they hold.
+#[cfg(feature = "alloc")]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CopiedString(CopiedRepr);
+
+#[cfg(all(
+    feature = "alloc",
+    not(any(feature = "compact_str", feature = "arc-str"))
+))]
+type CopiedRepr = alloc::boxed::Box<str>;
+
+#[cfg(all(feature = "compact_str", not(feature = "arc-str")))]
+type CopiedRepr = compact_str::CompactString;
+
+#[cfg(feature = "arc-str")]
+type CopiedRepr = alloc::sync::Arc<str>;
+
+#[cfg(feature = "alloc")]
+impl CopiedString {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Deref for CopiedString {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        self.as_str()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl fmt::Debug for CopiedString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl From<&str> for CopiedString {
+    fn from(other: &str) -> Self {
+        CopiedString(other.into())
     }
 }
 
@@ -278,18 +934,38 @@ impl<'a> hash32::Hash for CowString<'a> {
     }
 }
 
-impl<'a> PartialEq for CowString<'a> {
-    fn eq(&self, other: &Self) -> bool {
+impl<'a, 'b> PartialEq<CowString<'b>> for CowString<'a> {
+    fn eq(&self, other: &CowString<'b>) -> bool {
         self.as_str().eq(other.as_str())
     }
 }
 
+impl<'a> PartialOrd for CowString<'a> {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Strings are ordered by their contents, regardless of how they are stored.
+impl<'a> Ord for CowString<'a> {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
 impl<'a> From<&'a str> for CowString<'a> {
     fn from(other: &'a str) -> Self {
         Self::Borrowed(other)
     }
 }
 
+#[cfg(feature = "alloc")]
+impl<'a> From<String> for CowString<'a> {
+    fn from(other: String) -> Self {
+        Self::Owned(other)
+    }
+}
+
 impl<'a> Serialize for CowString<'a> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -299,19 +975,76 @@ impl<'a> Serialize for CowString<'a> {
     }
 }
 
-#[cfg(not(feature = "std"))]
-type TracingVec<T> = heapless::Vec<T, 32>;
+/// Borrows the string from the input when the format allows it, and falls
+/// back to an owned copy (with the `std` feature) when it does not, e.g. for
+/// escaped JSON strings or formats that decode from a reader.
+impl<'de: 'a, 'a> Deserialize<'de> for CowString<'a> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(CowStringVisitor)
+    }
+}
+
+struct CowStringVisitor;
 
-#[cfg(not(feature = "std"))]
-type TracingMap<K, V> = heapless::FnvIndexMap<K, V, 32>;
+impl<'de> de::Visitor<'de> for CowStringVisitor {
+    type Value = CowString<'de>;
 
-#[cfg(feature = "std")]
-type TracingVec<T> = std::vec::Vec<T>;
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a string")
+    }
 
-#[cfg(feature = "std")]
-type TracingMap<K, V> = std::collections::BTreeMap<K, V>;
+    fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(CowString::Borrowed(v))
+    }
 
-#[derive(Debug, Deserialize)]
+    #[cfg(feature = "alloc")]
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(CowString::copied(v))
+    }
+
+    #[cfg(feature = "alloc")]
+    fn visit_string<E>(self, v: String) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(CowString::Copied(CopiedString(v.into())))
+    }
+}
+
+/// The maximum number of fields that can be deserialized into a field set or
+/// a field map without the `alloc` feature.
+///
+/// This is 32 unless the `TRACING_SERDE_STRUCTURED_MAX_FIELDS` environment
+/// variable was set to another value when the crate was built. The value must
+/// be a power of two, unless the `sorted-map` feature is enabled.
+/// Deserializing more fields than this fails.
+pub const MAX_FIELDS: usize = include!(concat!(env!("OUT_DIR"), "/max_fields"));
+
+#[cfg(not(feature = "alloc"))]
+type TracingVec<T> = heapless::Vec<T, MAX_FIELDS>;
+
+#[cfg(all(not(feature = "alloc"), not(feature = "sorted-map")))]
+type TracingMap<K, V> = heapless::FnvIndexMap<K, V, MAX_FIELDS>;
+
+#[cfg(all(not(feature = "alloc"), feature = "sorted-map"))]
+type TracingMap<K, V> = sorted_map::SortedMap<K, V, MAX_FIELDS>;
+
+#[cfg(feature = "alloc")]
+type TracingVec<T> = alloc::vec::Vec<T>;
+
+#[cfg(feature = "alloc")]
+type TracingMap<K, V> = alloc::collections::BTreeMap<K, V>;
+
+#[derive(Debug, Clone, Deserialize)]
 #[serde(from = "TracingVec<CowString<'a>>")]
 pub enum SerializeFieldSet<'a> {
     Ser(&'a FieldSet),
@@ -337,6 +1070,27 @@ impl<'a> Serialize for SerializeFieldSet<'a> {
     }
 }
 
+/// Field sets are equal if they have the same names in the same order.
+impl<'a, 'b> PartialEq<SerializeFieldSet<'b>> for SerializeFieldSet<'a> {
+    fn eq(&self, other: &SerializeFieldSet<'b>) -> bool {
+        match (self, other) {
+            (SerializeFieldSet::Ser(a), SerializeFieldSet::Ser(b)) => {
+                a.iter().map(|f| f.name()).eq(b.iter().map(|f| f.name()))
+            }
+            (SerializeFieldSet::Ser(a), SerializeFieldSet::De(b))
+            | (SerializeFieldSet::De(b), SerializeFieldSet::Ser(a)) => {
+                a.iter().map(|f| f.name()).eq(b.iter().map(|f| f.as_str()))
+            }
+            (SerializeFieldSet::De(a), SerializeFieldSet::De(b)) => a
+                .iter()
+                .map(|f| f.as_str())
+                .eq(b.iter().map(|f| f.as_str())),
+        }
+    }
+}
+
+impl<'a> Eq for SerializeFieldSet<'a> {}
+
 impl<'a> From<TracingVec<CowString<'a>>> for SerializeFieldSet<'a> {
     fn from(other: TracingVec<CowString<'a>>) -> Self {
         SerializeFieldSet::De(other)
@@ -352,13 +1106,16 @@ impl<'a> postcard_schema::Schema for SerializeFieldSet<'a> {
         };
 }
 
+/// The level of a span or event.
+///
+/// Positional formats such as postcard write a level as its discriminant,
+/// which never changes, in a single byte. Only human-readable formats spell
+/// out its name.
 #[repr(usize)]
-#[cfg_attr(
-    feature = "postcard-schema",
-    derive(postcard_schema::Schema)
-)]
-#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "UPPERCASE")]
+#[cfg_attr(feature = "postcard-schema", derive(postcard_schema::Schema))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
 pub enum SerializeLevel {
     /// The "trace" level.
     ///
@@ -382,151 +1139,1028 @@ pub enum SerializeLevel {
     Error = 4,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[cfg_attr(
-    feature = "postcard-schema",
-    derive(postcard_schema::Schema)
-)]
-pub struct SerializeId {
-    pub id: NonZeroU64,
+const LEVEL_NAMES: &[&str] = &["TRACE", "DEBUG", "INFO", "WARN", "ERROR"];
+
+/// Levels are written as the variant of their discriminant, named as in
+/// `tracing` (e.g. `"INFO"`).
+impl Serialize for SerializeLevel {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_unit_variant("SerializeLevel", *self as u32, self.as_str())
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-#[cfg_attr(
-    feature = "postcard-schema",
-    derive(postcard_schema::Schema)
-)]
-pub struct SerializeMetadata<'a> {
-    #[serde(borrow)]
-    pub name: CowString<'a>,
-    pub target: CowString<'a>,
-    pub level: SerializeLevel,
-    pub module_path: Option<CowString<'a>>,
-    pub file: Option<CowString<'a>>,
-    pub line: Option<u32>,
-    pub fields: SerializeFieldSet<'a>,
-    pub is_span: bool,
-    pub is_event: bool,
+impl SerializeLevel {
+    /// Returns the name of the level, as it is serialized (e.g. `"INFO"`).
+    pub fn as_str(&self) -> &'static str {
+        LEVEL_NAMES[*self as usize]
+    }
 }
 
-/// Implements `serde::Serialize` to write `Event` data to a serializer.
-#[derive(Debug, Serialize, Deserialize)]
-#[cfg_attr(
-    feature = "postcard-schema",
-    derive(postcard_schema::Schema)
-)]
-pub struct SerializeEvent<'a> {
-    #[serde(borrow)]
-    pub fields: SerializeRecordFields<'a>,
-    pub metadata: SerializeMetadata<'a>,
-    pub parent: Option<SerializeId>,
+/// Formats the level by name, as `tracing` does (e.g. `INFO`).
+impl fmt::Display for SerializeLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
 }
 
-/// Implements `serde::Serialize` to write `Attributes` data to a serializer.
-#[derive(Debug, Serialize, Deserialize)]
-#[cfg_attr(
-    feature = "postcard-schema",
-    derive(postcard_schema::Schema)
-)]
+/// Levels compare like [`tracing::Level`](Level)s: more verbose levels are
+/// greater, so `TRACE` is the greatest and `ERROR` the least, and
+/// `level <= SerializeLevel::Info` holds for the levels at least as important
+/// as `INFO`.
+///
+/// A maximum level sent as an `Option<SerializeLevel>` compares the same way,
+/// with `None` (nothing enabled) less than every level.
+impl Ord for SerializeLevel {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        (*other as u8).cmp(&(*self as u8))
+    }
+}
+
+impl PartialOrd for SerializeLevel {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// A level is enabled by a filter if it is less than or equal to it, as with
+/// [`tracing::Level`](Level).
+impl PartialEq<LevelFilter> for SerializeLevel {
+    fn eq(&self, other: &LevelFilter) -> bool {
+        Level::from(*self) == *other
+    }
+}
+
+impl PartialOrd<LevelFilter> for SerializeLevel {
+    fn partial_cmp(&self, other: &LevelFilter) -> Option<core::cmp::Ordering> {
+        Level::from(*self).partial_cmp(other)
+    }
+}
+
+impl PartialEq<SerializeLevel> for LevelFilter {
+    fn eq(&self, other: &SerializeLevel) -> bool {
+        *self == Level::from(*other)
+    }
+}
+
+impl PartialOrd<SerializeLevel> for LevelFilter {
+    fn partial_cmp(&self, other: &SerializeLevel) -> Option<core::cmp::Ordering> {
+        self.partial_cmp(&Level::from(*other))
+    }
+}
+
+/// Parses a level name, ignoring case, e.g. `"info"` or `"WARN"`.
+impl core::str::FromStr for SerializeLevel {
+    type Err = ParseLevelError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        LEVEL_NAMES
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(s))
+            .map(|index| LEVEL_ORDER[index])
+            .ok_or(ParseLevelError { _priv: () })
+    }
+}
+
+const LEVEL_ORDER: [SerializeLevel; 5] = [
+    SerializeLevel::Trace,
+    SerializeLevel::Debug,
+    SerializeLevel::Info,
+    SerializeLevel::Warn,
+    SerializeLevel::Error,
+];
+
+/// The error returned when parsing a string that is not a level name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseLevelError {
+    _priv: (),
+}
+
+impl fmt::Display for ParseLevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("expected one of TRACE, DEBUG, INFO, WARN or ERROR (in any case)")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseLevelError {}
+
+/// In human-readable formats the level is accepted either by name (as it is
+/// serialized) or by its numeric discriminant.
+impl<'de> Deserialize<'de> for SerializeLevel {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(LevelVisitor)
+        } else {
+            deserializer.deserialize_enum("SerializeLevel", LEVEL_NAMES, LevelVisitor)
+        }
+    }
+}
+
+struct LevelVisitor;
+
+impl LevelVisitor {
+    fn from_index<E: de::Error>(v: u64) -> Result<SerializeLevel, E> {
+        match v {
+            0 => Ok(SerializeLevel::Trace),
+            1 => Ok(SerializeLevel::Debug),
+            2 => Ok(SerializeLevel::Info),
+            3 => Ok(SerializeLevel::Warn),
+            4 => Ok(SerializeLevel::Error),
+            _ => Err(E::invalid_value(
+                de::Unexpected::Unsigned(v),
+                &"a level between 0 and 4",
+            )),
+        }
+    }
+}
+
+impl<'de> de::Visitor<'de> for LevelVisitor {
+    type Value = SerializeLevel;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a level name or discriminant")
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        LevelVisitor::from_index(v)
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        match u64::try_from(v) {
+            Ok(v) => LevelVisitor::from_index(v),
+            Err(_) => Err(E::invalid_value(
+                de::Unexpected::Signed(v),
+                &"a level between 0 and 4",
+            )),
+        }
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        match v {
+            "TRACE" => Ok(SerializeLevel::Trace),
+            "DEBUG" => Ok(SerializeLevel::Debug),
+            "INFO" => Ok(SerializeLevel::Info),
+            "WARN" => Ok(SerializeLevel::Warn),
+            "ERROR" => Ok(SerializeLevel::Error),
+            _ => Err(E::unknown_variant(v, LEVEL_NAMES)),
+        }
+    }
+
+    fn visit_enum<A>(self, data: A) -> Result<Self::Value, A::Error>
+    where
+        A: de::EnumAccess<'de>,
+    {
+        let (LevelIdentifier(level), variant) = data.variant()?;
+        de::VariantAccess::unit_variant(variant)?;
+        Ok(level)
+    }
+}
+
+/// The variant tag of a `SerializeLevel`, by name or by index.
+struct LevelIdentifier(SerializeLevel);
+
+impl<'de> Deserialize<'de> for LevelIdentifier {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer
+            .deserialize_identifier(LevelVisitor)
+            .map(LevelIdentifier)
+    }
+}
+
+/// The variant tag of an enum that may gain variants in later versions of
+/// this crate, such as [`SerializeValue`].
+#[derive(Debug, Clone, Copy)]
+enum VariantTag {
+    /// The index of one of the variants this version knows about.
+    Known(usize),
+    /// An `Unknown` variant, written by [`serialize_unknown`].
+    Unknown,
+    /// A variant added in a later version.
+    Other,
+}
+
+/// Reads a [`VariantTag`] by name or by index, given the names of the known
+/// variants.
+struct VariantTagSeed(&'static [&'static str]);
+
+/// The index `Unknown` variants are written with, which no real variant will
+/// ever have, so that they read back as unknown in every version.
+const UNKNOWN_INDEX: u32 = u32::MAX;
+
+/// Writes the `Unknown` variant of the enum `name`.
+///
+/// It is written as a unit variant: whatever the variant it stands for held
+/// is gone.
+fn serialize_unknown<S: Serializer>(name: &'static str, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_unit_variant(name, UNKNOWN_INDEX, "Unknown")
+}
+
+/// Reads past the contents of a variant added in a later version.
+///
+/// Human-readable formats such as JSON say where the contents end, so they
+/// are skipped. Positional formats such as postcard do not, so the contents
+/// can only be left for whatever reads the input next, which is only done
+/// with `leave_unread`, for enums at the very end of their input.
+fn skip_variant<'de, A>(
+    variant: A,
+    human_readable: bool,
+    leave_unread: bool,
+) -> Result<(), A::Error>
+where
+    A: de::VariantAccess<'de>,
+{
+    if human_readable {
+        variant.newtype_variant::<de::IgnoredAny>().map(drop)
+    } else if leave_unread {
+        variant.unit_variant()
+    } else {
+        Err(de::Error::custom(
+            "cannot skip a variant from a later version in a format that is not self-describing",
+        ))
+    }
+}
+
+impl<'de> de::DeserializeSeed<'de> for VariantTagSeed {
+    type Value = VariantTag;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_identifier(self)
+    }
+}
+
+impl<'de> de::Visitor<'de> for VariantTagSeed {
+    type Value = VariantTag;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a variant name or index")
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(match usize::try_from(v) {
+            _ if v == u64::from(UNKNOWN_INDEX) => VariantTag::Unknown,
+            Ok(index) if index < self.0.len() => VariantTag::Known(index),
+            _ => VariantTag::Other,
+        })
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(match self.0.iter().position(|name| *name == v) {
+            Some(index) => VariantTag::Known(index),
+            None if v == "Unknown" => VariantTag::Unknown,
+            None => VariantTag::Other,
+        })
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        match core::str::from_utf8(v) {
+            Ok(v) => self.visit_str(v),
+            Err(_) => Ok(VariantTag::Other),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "postcard-schema", derive(postcard_schema::Schema))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct SerializeId {
+    pub id: NonZeroU64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[cfg_attr(
+    all(feature = "postcard-schema", not(feature = "strip-locations")),
+    derive(postcard_schema::Schema)
+)]
+pub struct SerializeMetadata<'a> {
+    #[serde(borrow)]
+    pub name: CowString<'a>,
+    pub target: CowString<'a>,
+    pub level: SerializeLevel,
+    #[serde(default)]
+    #[cfg_attr(feature = "strip-locations", serde(skip))]
+    #[cfg_attr(
+        feature = "camel-case",
+        serde(rename = "modulePath", alias = "module_path")
+    )]
+    #[cfg_attr(not(feature = "camel-case"), serde(alias = "modulePath"))]
+    pub module_path: Option<CowString<'a>>,
+    #[serde(default)]
+    #[cfg_attr(feature = "strip-locations", serde(skip))]
+    pub file: Option<CowString<'a>>,
+    #[serde(default)]
+    #[cfg_attr(feature = "strip-locations", serde(skip))]
+    pub line: Option<u32>,
+    pub fields: SerializeFieldSet<'a>,
+    #[cfg_attr(feature = "camel-case", serde(rename = "isSpan", alias = "is_span"))]
+    #[cfg_attr(not(feature = "camel-case"), serde(alias = "isSpan"))]
+    pub is_span: bool,
+    #[cfg_attr(feature = "camel-case", serde(rename = "isEvent", alias = "is_event"))]
+    #[cfg_attr(not(feature = "camel-case"), serde(alias = "isEvent"))]
+    pub is_event: bool,
+}
+
+impl<'a> Serialize for SerializeMetadata<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let skip = skip_none(&serializer);
+        let len = if cfg!(feature = "strip-locations") {
+            6
+        } else {
+            9
+        };
+        let mut s = serializer.serialize_struct("SerializeMetadata", len)?;
+        s.serialize_field("name", &self.name)?;
+        s.serialize_field("target", &self.target)?;
+        s.serialize_field("level", &self.level)?;
+        if !cfg!(feature = "strip-locations") {
+            serialize_option(&mut s, skip, names::MODULE_PATH, &self.module_path)?;
+            serialize_option(&mut s, skip, "file", &self.file)?;
+            serialize_option(&mut s, skip, "line", &self.line)?;
+        }
+        s.serialize_field("fields", &self.fields)?;
+        s.serialize_field(names::IS_SPAN, &self.is_span)?;
+        s.serialize_field(names::IS_EVENT, &self.is_event)?;
+        s.end()
+    }
+}
+
+/// The schema of metadata without locations, which the derive would not
+/// leave out.
+#[cfg(all(feature = "postcard-schema", feature = "strip-locations"))]
+impl<'a> postcard_schema::Schema for SerializeMetadata<'a> {
+    const SCHEMA: &'static postcard_schema::schema::NamedType = {
+        use postcard_schema::schema::{DataModelType, NamedType, NamedValue};
+        &NamedType {
+            name: "SerializeMetadata",
+            ty: &DataModelType::Struct(&[
+                &NamedValue {
+                    name: "name",
+                    ty: CowString::SCHEMA,
+                },
+                &NamedValue {
+                    name: "target",
+                    ty: CowString::SCHEMA,
+                },
+                &NamedValue {
+                    name: "level",
+                    ty: SerializeLevel::SCHEMA,
+                },
+                &NamedValue {
+                    name: "fields",
+                    ty: SerializeFieldSet::SCHEMA,
+                },
+                &NamedValue {
+                    name: names::IS_SPAN,
+                    ty: bool::SCHEMA,
+                },
+                &NamedValue {
+                    name: names::IS_EVENT,
+                    ty: bool::SCHEMA,
+                },
+            ]),
+        }
+    };
+}
+
+/// Implements `serde::Serialize` to write `Event` data to a serializer.
+#[derive(Debug, Clone, Deserialize)]
+#[cfg_attr(feature = "postcard-schema", derive(postcard_schema::Schema))]
+pub struct SerializeEvent<'a, F: Form = Detached> {
+    #[serde(borrow)]
+    pub fields: SerializeRecordFields<'a, F>,
+    pub metadata: SerializeMetadata<'a>,
+    #[serde(default)]
+    pub parent: Option<SerializeId>,
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, 'b, F: Form, G: Form> PartialEq<SerializeEvent<'b, G>> for SerializeEvent<'a, F> {
+    fn eq(&self, other: &SerializeEvent<'b, G>) -> bool {
+        self.fields == other.fields
+            && self.metadata == other.metadata
+            && self.parent == other.parent
+    }
+}
+
+impl<'a> From<SerializeEvent<'a>> for SerializeEvent<'a, Live> {
+    fn from(other: SerializeEvent<'a>) -> Self {
+        SerializeEvent {
+            fields: other.fields.into(),
+            metadata: other.metadata,
+            parent: other.parent,
+        }
+    }
+}
+
+impl<'a, F: Form> Serialize for SerializeEvent<'a, F> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let skip = skip_none(&serializer);
+        let mut s = serializer.serialize_struct("SerializeEvent", 3)?;
+        s.serialize_field("fields", &self.fields)?;
+        s.serialize_field("metadata", &self.metadata)?;
+        serialize_option(&mut s, skip, "parent", &self.parent)?;
+        s.end()
+    }
+}
+
+/// Implements `serde::Serialize` to write `Attributes` data to a serializer.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "postcard-schema", derive(postcard_schema::Schema))]
 pub struct SerializeAttributes<'a> {
     #[serde(borrow)]
     pub metadata: SerializeMetadata<'a>,
+    #[serde(default)]
     pub parent: Option<SerializeId>,
+    #[cfg_attr(feature = "camel-case", serde(rename = "isRoot", alias = "is_root"))]
+    #[cfg_attr(not(feature = "camel-case"), serde(alias = "isRoot"))]
     pub is_root: bool,
 }
 
-type RecordMap<'a> = TracingMap<CowString<'a>, SerializeValue<'a>>;
+impl<'a> Serialize for SerializeAttributes<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let skip = skip_none(&serializer);
+        let mut s = serializer.serialize_struct("SerializeAttributes", 3)?;
+        s.serialize_field("metadata", &self.metadata)?;
+        serialize_option(&mut s, skip, "parent", &self.parent)?;
+        s.serialize_field(names::IS_ROOT, &self.is_root)?;
+        s.end()
+    }
+}
+
+/// The names of fields with more than one word, in the casing chosen with
+/// the `camel-case` feature.
+pub(crate) mod names {
+    macro_rules! name {
+        ($const:ident, $snake:literal, $camel:literal) => {
+            pub(crate) const $const: &str = if cfg!(feature = "camel-case") {
+                $camel
+            } else {
+                $snake
+            };
+        };
+    }
+
+    name!(MODULE_PATH, "module_path", "modulePath");
+    name!(IS_SPAN, "is_span", "isSpan");
+    name!(IS_EVENT, "is_event", "isEvent");
+    name!(IS_ROOT, "is_root", "isRoot");
+}
+
+/// Whether `None` fields are left out, rather than written as `null`.
+///
+/// Only human-readable formats skip them: positional formats like postcard
+/// need every field to be present.
+fn skip_none<S: Serializer>(serializer: &S) -> bool {
+    cfg!(feature = "skip-none") && serializer.is_human_readable()
+}
+
+fn serialize_option<S, T>(
+    s: &mut S,
+    skip: bool,
+    key: &'static str,
+    value: &Option<T>,
+) -> Result<(), S::Error>
+where
+    S: SerializeStruct,
+    T: Serialize,
+{
+    if skip && value.is_none() {
+        s.skip_field(key)
+    } else {
+        s.serialize_field(key, value)
+    }
+}
+
+type RecordMap<'a> = TracingMap<CowString<'a>, SerializeValue<'a>>;
+
+/// Whether a value may refer to data that only lives for the duration of a
+/// `tracing` callback.
+///
+/// The fields of an [`Event`] or [`Record`], and the formatting arguments of
+/// a `Debug` value, can only be read on the thread that recorded them. Types
+/// that may refer to them, in their `Ser` variants, take a form: [`Live`] for
+/// values converted with `as_serde`, which can, and [`Detached`], the
+/// default, for values that are deserialized or converted with `to_owned`,
+/// which cannot, and so are [`Send`] and [`Sync`].
+pub trait Form: self::sealed::Sealed + fmt::Debug + Copy + Eq + Hash + Serialize + 'static {
+    /// What the `Ser` variant of a [`DebugRecord`] holds.
+    type Arguments<'a>: fmt::Debug + Copy;
+    /// What the `Ser` variant of a [`SerializeRecordFields`] holds.
+    type Event<'a>: fmt::Debug + Copy;
+    /// What the `Ser` variant of a [`SerializeRecord`] holds.
+    type Record<'a>: fmt::Debug + Copy;
+
+    fn arguments<'b, 'a: 'b>(args: &'b Self::Arguments<'a>) -> &'b Arguments<'a>;
+
+    fn event<'b, 'a: 'b>(event: &'b Self::Event<'a>) -> &'b Event<'a>;
+
+    fn record<'b, 'a: 'b>(record: &'b Self::Record<'a>) -> &'b Record<'a>;
+}
+
+/// The [`Form`] of values converted from `tracing` data with `as_serde`,
+/// which borrow it while it is being recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Live {}
+
+impl Form for Live {
+    type Arguments<'a> = &'a Arguments<'a>;
+    type Event<'a> = &'a Event<'a>;
+    type Record<'a> = &'a Record<'a>;
+
+    fn arguments<'b, 'a: 'b>(args: &'b Self::Arguments<'a>) -> &'b Arguments<'a> {
+        args
+    }
+
+    fn event<'b, 'a: 'b>(event: &'b Self::Event<'a>) -> &'b Event<'a> {
+        event
+    }
+
+    fn record<'b, 'a: 'b>(record: &'b Self::Record<'a>) -> &'b Record<'a> {
+        record
+    }
+}
+
+/// The [`Form`] of values that do not borrow any `tracing` data, such as
+/// deserialized ones. Their `Ser` variants cannot be constructed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Detached {}
+
+impl Form for Detached {
+    type Arguments<'a> = core::convert::Infallible;
+    type Event<'a> = core::convert::Infallible;
+    type Record<'a> = core::convert::Infallible;
+
+    fn arguments<'b, 'a: 'b>(args: &'b Self::Arguments<'a>) -> &'b Arguments<'a> {
+        match *args {}
+    }
+
+    fn event<'b, 'a: 'b>(event: &'b Self::Event<'a>) -> &'b Event<'a> {
+        match *event {}
+    }
+
+    fn record<'b, 'a: 'b>(record: &'b Self::Record<'a>) -> &'b Record<'a> {
+        match *record {}
+    }
+}
+
+#[cfg(feature = "postcard-schema")]
+impl postcard_schema::Schema for Live {
+    const SCHEMA: &'static postcard_schema::schema::NamedType =
+        &postcard_schema::schema::NamedType {
+            name: "Live",
+            ty: &postcard_schema::schema::DataModelType::Unit,
+        };
+}
+
+#[cfg(feature = "postcard-schema")]
+impl postcard_schema::Schema for Detached {
+    const SCHEMA: &'static postcard_schema::schema::NamedType =
+        &postcard_schema::schema::NamedType {
+            name: "Detached",
+            ty: &postcard_schema::schema::DataModelType::Unit,
+        };
+}
+
+// Detached values can be shared between threads without any `unsafe`. This
+// stops compiling if a change to one of them takes that away.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+
+    assert_send_sync::<CowString<'static>>();
+    assert_send_sync::<SerializeMetadata<'static>>();
+    assert_send_sync::<SerializeAttributes<'static>>();
+    assert_send_sync::<SerializeEvent<'static>>();
+    assert_send_sync::<SerializeRecord<'static>>();
+    assert_send_sync::<SerializeRecordFields<'static>>();
+    assert_send_sync::<SerializeValue<'static>>();
+    assert_send_sync::<DebugRecord<'static>>();
+    assert_send_sync::<wire::TracingWire<'static>>();
+    assert_send_sync::<wire::SerializeEventRef<'static>>();
+    assert_send_sync::<wire::SerializeEventInterned<'static>>();
+    assert_send_sync::<wire::SerializePanic<'static>>();
+    assert_send_sync::<wire::delta::DeltaFrame<'static>>();
+    assert_send_sync::<wire::delta::SourceFrame<'static>>();
+    #[cfg(feature = "host")]
+    assert_send_sync::<host::Message>();
+    #[cfg(feature = "host")]
+    assert_send_sync::<host::SourceMessage>();
+};
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Live {
+    fn format(&self, _: defmt::Formatter<'_>) {
+        match *self {}
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Detached {
+    fn format(&self, _: defmt::Formatter<'_>) {
+        match *self {}
+    }
+}
+
+/// Implements `serde::Serialize` to write `Record` data to a serializer.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(from = "RecordMap<'a>", bound(deserialize = ""))]
+pub enum SerializeRecord<'a, F: Form = Detached> {
+    #[serde(borrow)]
+    Ser(F::Record<'a>),
+    De(RecordMap<'a>),
+}
+
+impl<'a, F: Form> Serialize for SerializeRecord<'a, F> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            SerializeRecord::Ser(serf) => {
+                let serf = F::record(serf);
+                serialize_fields(serializer, serf.is_empty(), |v| serf.record(v))
+            }
+            SerializeRecord::De(derf) => derf.serialize(serializer),
+        }
+    }
+}
+
+/// Records are equal if they have the same values for the same fields.
+#[cfg(feature = "alloc")]
+impl<'a, 'b, F: Form, G: Form> PartialEq<SerializeRecord<'b, G>> for SerializeRecord<'a, F> {
+    fn eq(&self, other: &SerializeRecord<'b, G>) -> bool {
+        match (self, other) {
+            (SerializeRecord::De(a), SerializeRecord::De(b)) => maps_eq(a, b),
+            _ => self.to_owned() == other.to_owned(),
+        }
+    }
+}
+
+/// Compares maps with keys that may borrow for different lifetimes.
+#[cfg(feature = "alloc")]
+pub(crate) fn maps_eq<K1, K2, V1, V2>(a: &TracingMap<K1, V1>, b: &TracingMap<K2, V2>) -> bool
+where
+    K1: PartialEq<K2>,
+    V1: PartialEq<V2>,
+{
+    a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .all(|((ka, va), (kb, vb))| ka == kb && va == vb)
+}
+
+impl<'a, F: Form> From<RecordMap<'a>> for SerializeRecord<'a, F> {
+    fn from(other: RecordMap<'a>) -> Self {
+        Self::De(other)
+    }
+}
+
+impl<'a> From<SerializeRecord<'a>> for SerializeRecord<'a, Live> {
+    fn from(other: SerializeRecord<'a>) -> Self {
+        let SerializeRecord::De(map) = other;
+        Self::De(map)
+    }
+}
+
+/// Collects the values of a record, a later value for the same name replacing
+/// an earlier one.
+///
+/// # Panics
+///
+/// Without `alloc`, panics if there are more than [`MAX_FIELDS`] names, like
+/// collecting into a full `heapless` collection.
+impl<'a, F: Form> FromIterator<(CowString<'a>, SerializeValue<'a>)> for SerializeRecord<'a, F> {
+    fn from_iter<I: IntoIterator<Item = (CowString<'a>, SerializeValue<'a>)>>(iter: I) -> Self {
+        Self::De(collect_record_map(iter))
+    }
+}
+
+fn collect_record_map<'a>(
+    iter: impl IntoIterator<Item = (CowString<'a>, SerializeValue<'a>)>,
+) -> RecordMap<'a> {
+    let mut map = RecordMap::new();
+    for (name, value) in iter {
+        #[cfg(feature = "alloc")]
+        map.insert(name, value);
+        #[cfg(not(feature = "alloc"))]
+        if map.insert(name, value).is_err() {
+            panic!("more than MAX_FIELDS ({}) fields", MAX_FIELDS);
+        }
+    }
+    map
+}
+
+#[cfg(feature = "postcard-schema")]
+impl<'a, F: Form> postcard_schema::Schema for SerializeRecord<'a, F> {
+    const SCHEMA: &'static postcard_schema::schema::NamedType =
+        &postcard_schema::schema::NamedType {
+            name: "SerializeRecord",
+            ty: &postcard_schema::schema::DataModelType::Map {
+                key: CowString::SCHEMA,
+                val: SerializeValue::SCHEMA,
+            },
+        };
+}
+
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+#[cfg_attr(feature = "postcard-schema", derive(postcard_schema::Schema))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SerializeValue<'a> {
+    Debug(DebugRecord<'a>),
+    Str(CowString<'a>),
+    F64(f64),
+    I64(i64),
+    U64(u64),
+    Bool(bool),
+    /// A nested value, such as one recorded with `valuable`.
+    #[cfg(feature = "alloc")]
+    Tree(tree::SerializeTree<'a>),
+    /// A kind of value added in a later version of this crate.
+    ///
+    /// Human-readable formats such as JSON skip over the value, so the rest of
+    /// the event still decodes. Positional formats such as postcard cannot
+    /// tell where the value ends, and fail to decode it instead.
+    ///
+    /// Serializing it writes a value that reads back as `Unknown` in every
+    /// version.
+    Unknown,
+}
+
+const VALUE_VARIANTS: &[&str] = &["Debug", "Str", "F64", "I64", "U64", "Bool", "Tree"];
+
+impl<'a> Serialize for SerializeValue<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        const NAME: &str = "SerializeValue";
+        match self {
+            SerializeValue::Debug(dr) => serializer.serialize_newtype_variant(NAME, 0, "Debug", dr),
+            SerializeValue::Str(s) => serializer.serialize_newtype_variant(NAME, 1, "Str", s),
+            SerializeValue::F64(x) => serializer.serialize_newtype_variant(NAME, 2, "F64", x),
+            SerializeValue::I64(x) => serializer.serialize_newtype_variant(NAME, 3, "I64", x),
+            SerializeValue::U64(x) => serializer.serialize_newtype_variant(NAME, 4, "U64", x),
+            SerializeValue::Bool(x) => serializer.serialize_newtype_variant(NAME, 5, "Bool", x),
+            #[cfg(feature = "alloc")]
+            SerializeValue::Tree(t) => serializer.serialize_newtype_variant(NAME, 6, "Tree", t),
+            SerializeValue::Unknown => serialize_unknown(NAME, serializer),
+        }
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for SerializeValue<'a> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let visitor = ValueVisitor {
+            human_readable: deserializer.is_human_readable(),
+            marker: core::marker::PhantomData,
+        };
+        deserializer.deserialize_enum("SerializeValue", VALUE_VARIANTS, visitor)
+    }
+}
+
+struct ValueVisitor<'a> {
+    human_readable: bool,
+    marker: core::marker::PhantomData<SerializeValue<'a>>,
+}
+
+impl<'de: 'a, 'a> de::Visitor<'de> for ValueVisitor<'a> {
+    type Value = SerializeValue<'a>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("enum SerializeValue")
+    }
+
+    fn visit_enum<A>(self, data: A) -> Result<Self::Value, A::Error>
+    where
+        A: de::EnumAccess<'de>,
+    {
+        use de::VariantAccess;
+
+        let (tag, variant) = data.variant_seed(VariantTagSeed(VALUE_VARIANTS))?;
+        match tag {
+            VariantTag::Known(0) => variant.newtype_variant().map(SerializeValue::Debug),
+            VariantTag::Known(1) => variant.newtype_variant().map(SerializeValue::Str),
+            VariantTag::Known(2) => variant.newtype_variant().map(SerializeValue::F64),
+            VariantTag::Known(3) => variant.newtype_variant().map(SerializeValue::I64),
+            VariantTag::Known(4) => variant.newtype_variant().map(SerializeValue::U64),
+            VariantTag::Known(5) => variant.newtype_variant().map(SerializeValue::Bool),
+            #[cfg(feature = "alloc")]
+            VariantTag::Known(_) => variant.newtype_variant().map(SerializeValue::Tree),
+            #[cfg(not(feature = "alloc"))]
+            VariantTag::Known(_) => {
+                skip_variant(variant, self.human_readable, false).map(|()| SerializeValue::Unknown)
+            }
+            VariantTag::Unknown => variant.unit_variant().map(|()| SerializeValue::Unknown),
+            VariantTag::Other => {
+                skip_variant(variant, self.human_readable, false).map(|()| SerializeValue::Unknown)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, 'b> PartialEq<SerializeValue<'b>> for SerializeValue<'a> {
+    fn eq(&self, other: &SerializeValue<'b>) -> bool {
+        match (self, other) {
+            (SerializeValue::Debug(a), SerializeValue::Debug(b)) => a == b,
+            (SerializeValue::Str(a), SerializeValue::Str(b)) => a == b,
+            (SerializeValue::F64(a), SerializeValue::F64(b)) => a == b,
+            (SerializeValue::I64(a), SerializeValue::I64(b)) => a == b,
+            (SerializeValue::U64(a), SerializeValue::U64(b)) => a == b,
+            (SerializeValue::Bool(a), SerializeValue::Bool(b)) => a == b,
+            (SerializeValue::Tree(a), SerializeValue::Tree(b)) => a == b,
+            (SerializeValue::Unknown, SerializeValue::Unknown) => true,
+            _ => false,
+        }
+    }
+}
+
+macro_rules! value_from {
+    ($($ty:ty => $variant:ident,)*) => {
+        $(
+            impl<'a> From<$ty> for SerializeValue<'a> {
+                fn from(other: $ty) -> Self {
+                    SerializeValue::$variant(other.into())
+                }
+            }
+        )*
+    };
+}
 
-/// Implements `serde::Serialize` to write `Record` data to a serializer.
-#[derive(Debug, Deserialize)]
-#[serde(from = "RecordMap<'a>")]
-pub enum SerializeRecord<'a> {
-    #[serde(borrow)]
-    Ser(&'a Record<'a>),
-    De(RecordMap<'a>),
+value_from! {
+    bool => Bool,
+    i8 => I64,
+    i16 => I64,
+    i32 => I64,
+    i64 => I64,
+    u8 => U64,
+    u16 => U64,
+    u32 => U64,
+    u64 => U64,
+    f32 => F64,
+    f64 => F64,
+    &'a str => Str,
+    CowString<'a> => Str,
+}
+
+#[cfg(feature = "alloc")]
+value_from! {
+    String => Str,
 }
 
-impl<'a> Serialize for SerializeRecord<'a> {
+/// A `Debug` value being recorded, serialized as a [`SerializeValue::Debug`].
+///
+/// A `SerializeValue` only holds values that can be sent to another thread,
+/// which a value's formatting arguments cannot, so visitors write them with
+/// this instead.
+pub(crate) struct LiveDebug<'a>(pub(crate) &'a Arguments<'a>);
+
+impl<'a> Serialize for LiveDebug<'a> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        match self {
-            SerializeRecord::Ser(serf) => {
-                let items = serf.len();
+        serializer.serialize_newtype_variant(
+            "SerializeValue",
+            0,
+            "Debug",
+            &DebugRecord::<Live>::Ser(self.0),
+        )
+    }
+}
 
-                let serializer = serializer.serialize_map(Some(items))?;
-                let mut ssv = SerdeMapVisitor::new(serializer);
-                serf.record(&mut ssv);
-                ssv.finish()
-            }
-            SerializeRecord::De(derf) => derf.serialize(serializer),
+/// Formats the value without any quoting or type information.
+impl<'a> fmt::Display for SerializeValue<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SerializeValue::Debug(dr) => dr.fmt(f),
+            SerializeValue::Str(s) => f.write_str(s.as_str()),
+            SerializeValue::F64(x) => x.fmt(f),
+            SerializeValue::I64(x) => x.fmt(f),
+            SerializeValue::U64(x) => x.fmt(f),
+            SerializeValue::Bool(x) => x.fmt(f),
+            #[cfg(feature = "alloc")]
+            SerializeValue::Tree(t) => t.fmt(f),
+            SerializeValue::Unknown => f.write_str("<unknown>"),
         }
     }
 }
 
-impl<'a> From<RecordMap<'a>> for SerializeRecord<'a> {
-    fn from(other: RecordMap<'a>) -> Self {
+#[derive(Debug, Clone, Deserialize)]
+#[serde(from = "CowString<'a>", bound(deserialize = ""))]
+pub enum DebugRecord<'a, F: Form = Detached> {
+    #[serde(borrow)]
+    Ser(F::Arguments<'a>),
+    De(CowString<'a>),
+}
+
+impl<'a, F: Form> From<CowString<'a>> for DebugRecord<'a, F> {
+    fn from(other: CowString<'a>) -> Self {
         Self::De(other)
     }
 }
 
-#[cfg(feature = "postcard-schema")]
-impl<'a> postcard_schema::Schema for SerializeRecord<'a> {
-    const SCHEMA: &'static postcard_schema::schema::NamedType =
-        &postcard_schema::schema::NamedType {
-            name: "SerializeRecord",
-            ty: &postcard_schema::schema::DataModelType::Map {
-                key: CowString::SCHEMA,
-                val: SerializeValue::SCHEMA,
-            },
-        };
+impl<'a> From<DebugRecord<'a>> for DebugRecord<'a, Live> {
+    fn from(other: DebugRecord<'a>) -> Self {
+        let DebugRecord::De(msg) = other;
+        Self::De(msg)
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-#[non_exhaustive]
-#[cfg_attr(
-    feature = "postcard-schema",
-    derive(postcard_schema::Schema)
-)]
-pub enum SerializeValue<'a> {
-    #[serde(borrow)]
-    Debug(DebugRecord<'a>),
-    Str(CowString<'a>),
-    F64(f64),
-    I64(i64),
-    U64(u64),
-    Bool(bool),
+/// Recorded values are equal if they format the same.
+#[cfg(feature = "alloc")]
+impl<'a, 'b, F: Form, G: Form> PartialEq<DebugRecord<'b, G>> for DebugRecord<'a, F> {
+    fn eq(&self, other: &DebugRecord<'b, G>) -> bool {
+        match (self, other) {
+            (DebugRecord::De(a), DebugRecord::De(b)) => a == b,
+            _ => self.to_owned() == other.to_owned(),
+        }
+    }
 }
 
-#[derive(Debug, Deserialize)]
-#[serde(from = "CowString<'a>")]
-pub enum DebugRecord<'a> {
-    #[serde(borrow)]
-    Ser(&'a Arguments<'a>),
-    De(CowString<'a>),
+#[cfg(feature = "alloc")]
+impl<'a, F: Form> Eq for DebugRecord<'a, F> {}
+
+impl<'a, F: Form> fmt::Display for DebugRecord<'a, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DebugRecord::Ser(args) => f.write_fmt(*F::arguments(args)),
+            DebugRecord::De(msg) => f.write_str(msg.as_str()),
+        }
+    }
 }
 
-impl<'a> From<CowString<'a>> for DebugRecord<'a> {
-    fn from(other: CowString<'a>) -> Self {
-        Self::De(other)
+/// Formatting arguments can only be rendered with `core::fmt`, so recorded
+/// `Debug` values are written as `<debug>`, unless they are a plain string.
+#[cfg(feature = "defmt")]
+impl<'a, F: Form> defmt::Format for DebugRecord<'a, F> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        match self {
+            DebugRecord::Ser(args) => match F::arguments(args).as_str() {
+                Some(s) => defmt::write!(f, "{=str}", s),
+                None => defmt::write!(f, "<debug>"),
+            },
+            DebugRecord::De(msg) => msg.format(f),
+        }
     }
 }
 
-impl<'a> Serialize for DebugRecord<'a> {
+impl<'a, F: Form> Serialize for DebugRecord<'a, F> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
         match self {
-            DebugRecord::Ser(args) => args.serialize(serializer),
+            DebugRecord::Ser(args) => F::arguments(args).serialize(serializer),
             DebugRecord::De(msg) => msg.serialize(serializer),
         }
     }
 }
 
 #[cfg(feature = "postcard-schema")]
-impl<'a> postcard_schema::Schema for DebugRecord<'a> {
+impl<'a, F: Form> postcard_schema::Schema for DebugRecord<'a, F> {
     const SCHEMA: &'static postcard_schema::schema::NamedType =
         &postcard_schema::schema::NamedType {
             name: "DebugRecord",
@@ -534,33 +2168,64 @@ impl<'a> postcard_schema::Schema for DebugRecord<'a> {
         };
 }
 
-#[derive(Debug, Deserialize)]
-#[serde(from = "RecordMap<'a>")]
-pub enum SerializeRecordFields<'a> {
+#[derive(Debug, Clone, Deserialize)]
+#[serde(from = "RecordMap<'a>", bound(deserialize = ""))]
+pub enum SerializeRecordFields<'a, F: Form = Detached> {
     #[serde(borrow)]
-    Ser(&'a Event<'a>),
+    Ser(F::Event<'a>),
     De(RecordMap<'a>),
 }
 
-impl<'a> From<RecordMap<'a>> for SerializeRecordFields<'a> {
+/// Fields are equal if they have the same values for the same names.
+#[cfg(feature = "alloc")]
+impl<'a, 'b, F: Form, G: Form> PartialEq<SerializeRecordFields<'b, G>>
+    for SerializeRecordFields<'a, F>
+{
+    fn eq(&self, other: &SerializeRecordFields<'b, G>) -> bool {
+        match (self, other) {
+            (SerializeRecordFields::De(a), SerializeRecordFields::De(b)) => maps_eq(a, b),
+            _ => self.to_owned() == other.to_owned(),
+        }
+    }
+}
+
+impl<'a, F: Form> From<RecordMap<'a>> for SerializeRecordFields<'a, F> {
     fn from(other: RecordMap<'a>) -> Self {
         Self::De(other)
     }
 }
 
-impl<'a> Serialize for SerializeRecordFields<'a> {
+impl<'a> From<SerializeRecordFields<'a>> for SerializeRecordFields<'a, Live> {
+    fn from(other: SerializeRecordFields<'a>) -> Self {
+        let SerializeRecordFields::De(map) = other;
+        Self::De(map)
+    }
+}
+
+/// Collects the fields of an event, a later value for the same name replacing
+/// an earlier one.
+///
+/// # Panics
+///
+/// Without `alloc`, panics if there are more than [`MAX_FIELDS`] names.
+impl<'a, F: Form> FromIterator<(CowString<'a>, SerializeValue<'a>)>
+    for SerializeRecordFields<'a, F>
+{
+    fn from_iter<I: IntoIterator<Item = (CowString<'a>, SerializeValue<'a>)>>(iter: I) -> Self {
+        Self::De(collect_record_map(iter))
+    }
+}
+
+impl<'a, F: Form> Serialize for SerializeRecordFields<'a, F> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
         match self {
             SerializeRecordFields::Ser(serf) => {
-                let items = serf.fields().count();
-
-                let serializer = serializer.serialize_map(Some(items))?;
-                let mut ssv = SerdeMapVisitor::new(serializer);
-                serf.record(&mut ssv);
-                ssv.finish()
+                let serf = F::event(serf);
+                let empty = serf.fields().next().is_none();
+                serialize_fields(serializer, empty, |v| serf.record(v))
             }
             SerializeRecordFields::De(derf) => derf.serialize(serializer),
         }
@@ -568,7 +2233,7 @@ impl<'a> Serialize for SerializeRecordFields<'a> {
 }
 
 #[cfg(feature = "postcard-schema")]
-impl<'a> postcard_schema::Schema for SerializeRecordFields<'a> {
+impl<'a, F: Form> postcard_schema::Schema for SerializeRecordFields<'a, F> {
     const SCHEMA: &'static postcard_schema::schema::NamedType =
         &postcard_schema::schema::NamedType {
             name: "SerializeRecordFields",
@@ -579,11 +2244,86 @@ impl<'a> postcard_schema::Schema for SerializeRecordFields<'a> {
         };
 }
 
+/// Serializes the fields passed to the visitor given to `record` as a map.
+///
+/// Formats that need the length of a map up front, like postcard, get it
+/// from a first pass over the fields that only counts them. Human-readable
+/// formats all accept maps of unknown length, so they skip that pass.
+fn serialize_fields<S>(
+    serializer: S,
+    empty: bool,
+    record: impl Fn(&mut dyn Visit),
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let len = if empty {
+        Some(0)
+    } else if serializer.is_human_readable() {
+        None
+    } else {
+        let mut count = FieldCount(0);
+        record(&mut count);
+        Some(count.0)
+    };
+
+    let mut ssv = SerdeMapVisitor::new(serializer.serialize_map(len)?);
+    if !empty {
+        record(&mut ssv);
+    }
+    ssv.finish()
+}
+
+/// Counts the fields that a visitor will actually be handed.
+///
+/// Neither `Event::fields()` nor `Record::len()` skip fields without a value
+/// (e.g. `field::Empty`), so using them as the map length would produce a
+/// corrupt length prefix in formats that are not self-describing.
+struct FieldCount(usize);
+
+impl Visit for FieldCount {
+    fn record_debug(&mut self, _field: &Field, _value: &dyn fmt::Debug) {
+        self.0 += 1;
+    }
+}
+
+/// The kind of value a field was recorded as, from the `Visit` method that
+/// received it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ValueKind {
+    Bool,
+    Debug,
+    U64,
+    I64,
+    F64,
+    Str,
+    /// A [`valuable`](https://docs.rs/valuable) value.
+    #[cfg(all(tracing_unstable, feature = "valuable"))]
+    #[cfg_attr(docsrs, doc(cfg(all(tracing_unstable, feature = "valuable"))))]
+    Valuable,
+}
+
+impl fmt::Display for ValueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ValueKind::Bool => "bool",
+            ValueKind::Debug => "debug",
+            ValueKind::U64 => "u64",
+            ValueKind::I64 => "i64",
+            ValueKind::F64 => "f64",
+            ValueKind::Str => "str",
+            #[cfg(all(tracing_unstable, feature = "valuable"))]
+            ValueKind::Valuable => "valuable",
+        })
+    }
+}
+
 /// Implements `tracing_core::field::Visit` for some `serde::ser::SerializeMap`.
 #[derive(Debug)]
 pub struct SerdeMapVisitor<S: SerializeMap> {
     serializer: S,
     state: Result<(), S::Error>,
+    failed: Option<(&'static str, ValueKind)>,
 }
 
 impl<S> SerdeMapVisitor<S>
@@ -595,9 +2335,20 @@ where
         Self {
             serializer,
             state: Ok(()),
+            failed: None,
         }
     }
 
+    /// The name and kind of the field that could not be serialized, if any.
+    ///
+    /// Once a field fails, the remaining fields are skipped, so this is the
+    /// field the error returned by [`Self::finish`] or
+    /// [`Self::take_serializer`] came from. It is `None` if every field was
+    /// serialized, even if ending the map fails afterwards.
+    pub fn failed_field(&self) -> Option<(&'static str, ValueKind)> {
+        self.failed
+    }
+
     /// Completes serializing the visited object, returning `Ok(())` if all
     /// fields were serialized correctly, or `Error(S::Error)` if a field could
     /// not be serialized.
@@ -613,6 +2364,20 @@ where
         self.state?;
         Ok(self.serializer)
     }
+
+    fn entry<V>(&mut self, field: &Field, kind: ValueKind, value: &V)
+    where
+        V: Serialize + ?Sized,
+    {
+        // If previous fields serialized successfully, continue serializing,
+        // otherwise, short-circuit and do nothing.
+        if self.state.is_ok() {
+            self.state = self.serializer.serialize_entry(field.name(), value);
+            if self.state.is_err() {
+                self.failed = Some((field.name(), kind));
+            }
+        }
+    }
 }
 
 impl<S> Visit for SerdeMapVisitor<S>
@@ -622,62 +2387,46 @@ where
     #[cfg(all(tracing_unstable, feature = "valuable"))]
     #[cfg_attr(docsrs, doc(cfg(all(tracing_unstable, feature = "valuable"))))]
     fn record_value(&mut self, field: &Field, value: valuable_crate::Value<'_>) {
-        if self.state.is_ok() {
-            self.state = self
-                .serializer
-                .serialize_entry(field.name(), &valuable_serde::Serializable::new(value));
-        }
+        #[cfg(feature = "alloc")]
+        self.entry(
+            field,
+            ValueKind::Valuable,
+            &SerializeValue::from_valuable(value),
+        );
+        #[cfg(not(feature = "alloc"))]
+        self.entry(
+            field,
+            ValueKind::Valuable,
+            &LiveDebug(&format_args!("{:?}", value)),
+        );
     }
 
     fn record_bool(&mut self, field: &Field, value: bool) {
-        // If previous fields serialized successfully, continue serializing,
-        // otherwise, short-circuit and do nothing.
-        if self.state.is_ok() {
-            self.state = self
-                .serializer
-                .serialize_entry(field.name(), &SerializeValue::Bool(value))
-        }
+        self.entry(field, ValueKind::Bool, &SerializeValue::Bool(value))
     }
 
     fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
-        if self.state.is_ok() {
-            self.state = self.serializer.serialize_entry(
-                field.name(),
-                &SerializeValue::Debug(DebugRecord::Ser(&format_args!("{:?}", value))),
-            )
-        }
+        self.entry(
+            field,
+            ValueKind::Debug,
+            &LiveDebug(&format_args!("{:?}", value)),
+        )
     }
 
     fn record_u64(&mut self, field: &Field, value: u64) {
-        if self.state.is_ok() {
-            self.state = self
-                .serializer
-                .serialize_entry(field.name(), &SerializeValue::U64(value))
-        }
+        self.entry(field, ValueKind::U64, &SerializeValue::U64(value))
     }
 
     fn record_i64(&mut self, field: &Field, value: i64) {
-        if self.state.is_ok() {
-            self.state = self
-                .serializer
-                .serialize_entry(field.name(), &SerializeValue::I64(value))
-        }
+        self.entry(field, ValueKind::I64, &SerializeValue::I64(value))
     }
 
     fn record_f64(&mut self, field: &Field, value: f64) {
-        if self.state.is_ok() {
-            self.state = self
-                .serializer
-                .serialize_entry(field.name(), &SerializeValue::F64(value))
-        }
+        self.entry(field, ValueKind::F64, &SerializeValue::F64(value))
     }
 
     fn record_str(&mut self, field: &Field, value: &str) {
-        if self.state.is_ok() {
-            self.state = self
-                .serializer
-                .serialize_entry(field.name(), &SerializeValue::Str(value.into()))
-        }
+        self.entry(field, ValueKind::Str, &SerializeValue::Str(value.into()))
     }
 }
 
@@ -693,7 +2442,7 @@ impl<'a> AsSerde<'a> for tracing_core::Metadata<'a> {
 
     fn as_serde(&'a self) -> Self::Serializable {
         SerializeMetadata {
-            name: self.name().into(),
+            name: CowString::Static(self.name()),
             target: self.target().into(),
             level: self.level().as_serde(),
             module_path: self.module_path().map(Into::into),
@@ -706,39 +2455,107 @@ impl<'a> AsSerde<'a> for tracing_core::Metadata<'a> {
     }
 }
 
-/// SAFETY: If all data is 'static and/or owned, it is safe
-/// to send between threads.
-unsafe impl Send for SerializeFieldSet<'static> {}
+impl<'a> SerializeFieldSet<'a> {
+    /// Whether this refers to a live [`FieldSet`] or holds any borrowed
+    /// names.
+    pub fn is_borrowed(&self) -> bool {
+        match self {
+            SerializeFieldSet::Ser(_) => true,
+            SerializeFieldSet::De(dfs) => dfs.iter().any(CowString::is_borrowed),
+        }
+    }
+
+    pub fn is_owned(&self) -> bool {
+        !self.is_borrowed()
+    }
+}
 
-#[cfg(feature = "std")]
+#[cfg(feature = "alloc")]
 impl<'a> SerializeFieldSet<'a> {
+    pub fn ensure_owned(&mut self) {
+        match self {
+            SerializeFieldSet::Ser(sfs) => {
+                *self =
+                    SerializeFieldSet::De(sfs.iter().map(|i| CowString::Static(i.name())).collect())
+            }
+            SerializeFieldSet::De(dfs) => dfs.iter_mut().for_each(CowString::ensure_owned_name),
+        }
+    }
+
     pub fn to_owned(&self) -> SerializeFieldSet<'static> {
         match self {
-            SerializeFieldSet::Ser(sfs) => SerializeFieldSet::De(
-                sfs.iter()
-                    .map(|i| CowString::from(i.name()).to_owned())
-                    .collect(),
-            ),
+            SerializeFieldSet::Ser(sfs) => {
+                SerializeFieldSet::De(sfs.iter().map(|i| CowString::Static(i.name())).collect())
+            }
             SerializeFieldSet::De(dfs) => {
-                SerializeFieldSet::De(dfs.iter().map(CowString::to_owned).collect())
+                SerializeFieldSet::De(dfs.iter().map(CowString::to_owned_name).collect())
             }
         }
     }
 }
 
-/// SAFETY: If all data is 'static and/or owned, it is safe
-/// to send between threads.
-unsafe impl Send for SerializeMetadata<'static> {}
+impl SerializeMetadata<'static> {
+    /// Like `as_serde`, but for the `'static` metadata of a callsite, whose
+    /// strings are all kept as [`CowString::Static`], so that `to_owned`
+    /// copies them without allocating.
+    ///
+    /// Events and span attributes always refer to `'static` metadata, and
+    /// use this when converted with `as_serde`.
+    pub fn from_static(metadata: &'static Metadata<'static>) -> Self {
+        SerializeMetadata {
+            name: CowString::Static(metadata.name()),
+            target: CowString::Static(metadata.target()),
+            level: metadata.level().as_serde(),
+            module_path: metadata.module_path().map(CowString::Static),
+            file: metadata.file().map(CowString::Static),
+            line: metadata.line(),
+            fields: SerializeFieldSet::Ser(metadata.fields()),
+            is_span: metadata.is_span(),
+            is_event: metadata.is_event(),
+        }
+    }
+}
 
-#[cfg(feature = "std")]
 impl<'a> SerializeMetadata<'a> {
+    /// Whether any of the strings or field names are borrowed.
+    pub fn is_borrowed(&self) -> bool {
+        self.name.is_borrowed()
+            || self.target.is_borrowed()
+            || self
+                .module_path
+                .as_ref()
+                .is_some_and(CowString::is_borrowed)
+            || self.file.as_ref().is_some_and(CowString::is_borrowed)
+            || self.fields.is_borrowed()
+    }
+
+    pub fn is_owned(&self) -> bool {
+        !self.is_borrowed()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> SerializeMetadata<'a> {
+    /// Like [`Self::to_owned`], but copies only what is borrowed, in place.
+    pub fn ensure_owned(&mut self) {
+        self.name.ensure_owned_name();
+        self.target.ensure_owned_name();
+        if let Some(module_path) = &mut self.module_path {
+            module_path.ensure_owned_name();
+        }
+        if let Some(file) = &mut self.file {
+            file.ensure_owned_name();
+        }
+        self.fields.ensure_owned();
+    }
+
     pub fn to_owned(&self) -> SerializeMetadata<'static> {
         SerializeMetadata {
-            name: self.name.to_owned(),
-            target: self.target.to_owned(),
+            name: self.name.to_owned_name(),
+            target: self.target.to_owned_name(),
             level: self.level,
-            module_path: self.module_path.as_ref().map(CowString::to_owned),
-            file: self.file.as_ref().map(CowString::to_owned),
+            module_path: self.module_path.as_ref().map(CowString::to_owned_name),
+            file: self.file.as_ref().map(CowString::to_owned_name),
             line: self.line,
             fields: self.fields.to_owned(),
             is_span: self.is_span,
@@ -748,37 +2565,98 @@ impl<'a> SerializeMetadata<'a> {
 }
 
 impl<'a> AsSerde<'a> for tracing_core::Event<'a> {
-    type Serializable = SerializeEvent<'a>;
+    type Serializable = SerializeEvent<'a, Live>;
 
     fn as_serde(&'a self) -> Self::Serializable {
         SerializeEvent {
             fields: SerializeRecordFields::Ser(self),
-            metadata: self.metadata().as_serde(),
+            metadata: SerializeMetadata::from_static(self.metadata()),
             parent: self.parent().map(|p| p.as_serde()),
         }
     }
 }
 
-/// SAFETY: If all data is 'static and/or owned, it is safe
-/// to send between threads.
-unsafe impl Send for DebugRecord<'static> {}
+impl<'a, F: Form> DebugRecord<'a, F> {
+    /// Whether this holds live format arguments or a borrowed string.
+    pub fn is_borrowed(&self) -> bool {
+        match self {
+            DebugRecord::Ser(_) => true,
+            DebugRecord::De(d) => d.is_borrowed(),
+        }
+    }
+
+    pub fn is_owned(&self) -> bool {
+        !self.is_borrowed()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, F: Form> DebugRecord<'a, F> {
+    pub fn ensure_owned(&mut self) {
+        match self {
+            DebugRecord::Ser(args) => {
+                *self = DebugRecord::De(CowString::formatted(*F::arguments(args)))
+            }
+            DebugRecord::De(d) => d.ensure_owned(),
+        }
+    }
 
-#[cfg(feature = "std")]
-impl<'a> DebugRecord<'a> {
     pub fn to_owned(&self) -> DebugRecord<'static> {
         match self {
-            DebugRecord::Ser(args) => DebugRecord::De(CowString::Owned(args.to_string())),
+            DebugRecord::Ser(args) => DebugRecord::De(CowString::formatted(*F::arguments(args))),
             DebugRecord::De(d) => DebugRecord::De(d.to_owned()),
         }
     }
 }
 
-/// SAFETY: If all data is 'static and/or owned, it is safe
-/// to send between threads.
-unsafe impl Send for SerializeValue<'static> {}
+impl<'a> SerializeValue<'a> {
+    /// Whether this is a string or debug value that borrows its data.
+    pub fn is_borrowed(&self) -> bool {
+        match self {
+            SerializeValue::Debug(dr) => dr.is_borrowed(),
+            SerializeValue::Str(s) => s.is_borrowed(),
+            #[cfg(feature = "alloc")]
+            SerializeValue::Tree(t) => t.is_borrowed(),
+            _ => false,
+        }
+    }
+
+    pub fn is_owned(&self) -> bool {
+        !self.is_borrowed()
+    }
+}
+
+fn map_is_borrowed(map: &RecordMap<'_>) -> bool {
+    map.iter().any(|(k, v)| k.is_borrowed() || v.is_borrowed())
+}
 
-#[cfg(feature = "std")]
+#[cfg(feature = "alloc")]
+fn ensure_map_owned(map: &mut RecordMap<'_>) {
+    if map.keys().any(CowString::is_borrowed) {
+        *map = core::mem::take(map)
+            .into_iter()
+            .map(|(mut k, mut v)| {
+                k.ensure_owned_name();
+                v.ensure_owned();
+                (k, v)
+            })
+            .collect();
+    } else {
+        map.values_mut().for_each(SerializeValue::ensure_owned);
+    }
+}
+
+#[cfg(feature = "alloc")]
 impl<'a> SerializeValue<'a> {
+    pub fn ensure_owned(&mut self) {
+        match self {
+            SerializeValue::Debug(dr) => dr.ensure_owned(),
+            SerializeValue::Str(s) => s.ensure_owned(),
+            SerializeValue::Tree(t) => t.ensure_owned(),
+            _ => {}
+        }
+    }
+
     pub fn to_owned(&self) -> SerializeValue<'static> {
         match self {
             SerializeValue::Debug(dr) => SerializeValue::Debug(dr.to_owned()),
@@ -787,86 +2665,128 @@ impl<'a> SerializeValue<'a> {
             SerializeValue::I64(x) => SerializeValue::I64(*x),
             SerializeValue::U64(x) => SerializeValue::U64(*x),
             SerializeValue::Bool(x) => SerializeValue::Bool(*x),
+            SerializeValue::Tree(t) => SerializeValue::Tree(t.to_owned()),
+            SerializeValue::Unknown => SerializeValue::Unknown,
         }
     }
 }
 
-#[cfg(feature = "std")]
-struct HashVisit(std::collections::BTreeMap<CowString<'static>, SerializeValue<'static>>);
+#[cfg(feature = "alloc")]
+struct HashVisit<'a>(alloc::collections::BTreeMap<CowString<'a>, SerializeValue<'a>>);
 
-#[cfg(feature = "std")]
-impl Visit for HashVisit {
-    fn record_bool(&mut self, field: &Field, value: bool) {
+#[cfg(feature = "alloc")]
+impl Visit for HashVisit<'_> {
+    #[cfg(all(tracing_unstable, feature = "valuable"))]
+    fn record_value(&mut self, field: &Field, value: valuable_crate::Value<'_>) {
         self.0.insert(
-            CowString::Owned(field.name().to_string()),
-            SerializeValue::Bool(value),
+            CowString::Static(field.name()),
+            SerializeValue::from_valuable(value),
         );
     }
 
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0
+            .insert(CowString::Static(field.name()), SerializeValue::Bool(value));
+    }
+
     fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
         self.0.insert(
-            CowString::Owned(field.name().to_string()),
-            SerializeValue::Debug(DebugRecord::De(CowString::Owned(format!("{:?}", value)))),
+            CowString::Static(field.name()),
+            SerializeValue::Debug(DebugRecord::De(CowString::formatted(format_args!(
+                "{:?}",
+                value
+            )))),
         );
     }
 
     fn record_u64(&mut self, field: &Field, value: u64) {
-        self.0.insert(
-            CowString::Owned(field.name().to_string()),
-            SerializeValue::U64(value),
-        );
+        self.0
+            .insert(CowString::Static(field.name()), SerializeValue::U64(value));
     }
 
     fn record_i64(&mut self, field: &Field, value: i64) {
-        self.0.insert(
-            CowString::Owned(field.name().to_string()),
-            SerializeValue::I64(value),
-        );
+        self.0
+            .insert(CowString::Static(field.name()), SerializeValue::I64(value));
     }
 
     fn record_f64(&mut self, field: &Field, value: f64) {
-        self.0.insert(
-            CowString::Owned(field.name().to_string()),
-            SerializeValue::F64(value),
-        );
+        self.0
+            .insert(CowString::Static(field.name()), SerializeValue::F64(value));
     }
 
     fn record_str(&mut self, field: &Field, value: &str) {
         self.0.insert(
-            CowString::Owned(field.name().to_string()),
-            SerializeValue::Str(CowString::Owned(value.to_string())),
+            CowString::Static(field.name()),
+            SerializeValue::Str(CowString::copied(value)),
         );
     }
 }
 
-/// SAFETY: If all data is 'static and/or owned, it is safe
-/// to send between threads.
-unsafe impl Send for SerializeRecordFields<'static> {}
+impl<'a, F: Form> SerializeRecordFields<'a, F> {
+    /// Whether this refers to a live event or holds any borrowed names or
+    /// values.
+    pub fn is_borrowed(&self) -> bool {
+        match self {
+            SerializeRecordFields::Ser(_) => true,
+            SerializeRecordFields::De(dsrf) => map_is_borrowed(dsrf),
+        }
+    }
+
+    pub fn is_owned(&self) -> bool {
+        !self.is_borrowed()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, F: Form> SerializeRecordFields<'a, F> {
+    pub fn ensure_owned(&mut self) {
+        match self {
+            SerializeRecordFields::Ser(e) => {
+                let mut hv = HashVisit(alloc::collections::BTreeMap::new());
+                F::event(e).record(&mut hv);
+                *self = SerializeRecordFields::De(hv.0);
+            }
+            SerializeRecordFields::De(dsrf) => ensure_map_owned(dsrf),
+        }
+    }
 
-#[cfg(feature = "std")]
-impl<'a> SerializeRecordFields<'a> {
     pub fn to_owned(&self) -> SerializeRecordFields<'static> {
         match self {
             SerializeRecordFields::Ser(e) => {
-                let mut hv = HashVisit(std::collections::BTreeMap::new());
-                e.record(&mut hv);
+                let mut hv = HashVisit(alloc::collections::BTreeMap::new());
+                F::event(e).record(&mut hv);
                 SerializeRecordFields::De(hv.0)
             }
             SerializeRecordFields::De(dsrf) => SerializeRecordFields::De(
                 dsrf.iter()
-                    .map(|(k, v)| (k.to_owned(), v.to_owned()))
+                    .map(|(k, v)| (k.to_owned_name(), v.to_owned()))
                     .collect(),
             ),
         }
     }
 }
 
-/// SAFETY: If all data is 'static and/or owned, it is safe
-/// to send between threads.
-unsafe impl Send for SerializeEvent<'static> {}
+impl<'a, F: Form> SerializeEvent<'a, F> {
+    /// Whether the fields or metadata borrow from the live event or from
+    /// the buffer the event was decoded from.
+    pub fn is_borrowed(&self) -> bool {
+        self.fields.is_borrowed() || self.metadata.is_borrowed()
+    }
+
+    pub fn is_owned(&self) -> bool {
+        !self.is_borrowed()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, F: Form> SerializeEvent<'a, F> {
+    /// Like [`Self::to_owned`], but copies only what is borrowed, in place,
+    /// so that an event that is already owned is left untouched.
+    pub fn ensure_owned(&mut self) {
+        self.fields.ensure_owned();
+        self.metadata.ensure_owned();
+    }
 
-#[cfg(feature = "std")]
-impl<'a> SerializeEvent<'a> {
     pub fn to_owned(&self) -> SerializeEvent<'static> {
         SerializeEvent {
             fields: self.fields.to_owned(),
@@ -881,19 +2801,29 @@ impl<'a> AsSerde<'a> for tracing_core::span::Attributes<'a> {
 
     fn as_serde(&'a self) -> Self::Serializable {
         SerializeAttributes {
-            metadata: self.metadata().as_serde(),
+            metadata: SerializeMetadata::from_static(self.metadata()),
             parent: self.parent().map(|p| p.as_serde()),
             is_root: self.is_root(),
         }
     }
 }
 
-/// SAFETY: If all data is 'static and/or owned, it is safe
-/// to send between threads.
-unsafe impl Send for SerializeAttributes<'static> {}
+impl<'a> SerializeAttributes<'a> {
+    pub fn is_borrowed(&self) -> bool {
+        self.metadata.is_borrowed()
+    }
+
+    pub fn is_owned(&self) -> bool {
+        !self.is_borrowed()
+    }
+}
 
-#[cfg(feature = "std")]
+#[cfg(feature = "alloc")]
 impl<'a> SerializeAttributes<'a> {
+    pub fn ensure_owned(&mut self) {
+        self.metadata.ensure_owned();
+    }
+
     pub fn to_owned(&self) -> SerializeAttributes<'static> {
         SerializeAttributes {
             metadata: self.metadata.to_owned(),
@@ -913,7 +2843,7 @@ impl<'a> AsSerde<'a> for tracing_core::span::Id {
     }
 }
 
-#[cfg(feature = "std")]
+#[cfg(feature = "alloc")]
 impl SerializeId {
     pub fn to_owned(&self) -> Self {
         self.clone()
@@ -921,29 +2851,51 @@ impl SerializeId {
 }
 
 impl<'a> AsSerde<'a> for tracing_core::span::Record<'a> {
-    type Serializable = SerializeRecord<'a>;
+    type Serializable = SerializeRecord<'a, Live>;
 
     fn as_serde(&'a self) -> Self::Serializable {
         SerializeRecord::Ser(self)
     }
 }
 
-/// SAFETY: If all data is 'static and/or owned, it is safe
-/// to send between threads.
-unsafe impl Send for SerializeRecord<'static> {}
+impl<'a, F: Form> SerializeRecord<'a, F> {
+    /// Whether this refers to a live record or holds any borrowed names or
+    /// values.
+    pub fn is_borrowed(&self) -> bool {
+        match self {
+            SerializeRecord::Ser(_) => true,
+            SerializeRecord::De(d) => map_is_borrowed(d),
+        }
+    }
+
+    pub fn is_owned(&self) -> bool {
+        !self.is_borrowed()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, F: Form> SerializeRecord<'a, F> {
+    pub fn ensure_owned(&mut self) {
+        match self {
+            SerializeRecord::Ser(s) => {
+                let mut hv = HashVisit(alloc::collections::BTreeMap::new());
+                F::record(s).record(&mut hv);
+                *self = SerializeRecord::De(hv.0);
+            }
+            SerializeRecord::De(d) => ensure_map_owned(d),
+        }
+    }
 
-#[cfg(feature = "std")]
-impl<'a> SerializeRecord<'a> {
     pub fn to_owned(&self) -> SerializeRecord<'static> {
         match self {
             SerializeRecord::Ser(s) => {
-                let mut hv = HashVisit(std::collections::BTreeMap::new());
-                s.record(&mut hv);
+                let mut hv = HashVisit(alloc::collections::BTreeMap::new());
+                F::record(s).record(&mut hv);
                 SerializeRecord::De(hv.0)
             }
             SerializeRecord::De(d) => SerializeRecord::De(
                 d.iter()
-                    .map(|(k, v)| (k.to_owned(), v.to_owned()))
+                    .map(|(k, v)| (k.to_owned_name(), v.to_owned()))
                     .collect(),
             ),
         }
@@ -964,7 +2916,47 @@ impl<'a> AsSerde<'a> for Level {
     }
 }
 
-#[cfg(feature = "std")]
+impl From<Level> for SerializeLevel {
+    fn from(level: Level) -> Self {
+        level.as_serde()
+    }
+}
+
+impl From<SerializeLevel> for Level {
+    fn from(level: SerializeLevel) -> Self {
+        match level {
+            SerializeLevel::Error => Level::ERROR,
+            SerializeLevel::Warn => Level::WARN,
+            SerializeLevel::Info => Level::INFO,
+            SerializeLevel::Debug => Level::DEBUG,
+            SerializeLevel::Trace => Level::TRACE,
+        }
+    }
+}
+
+/// A filter enabling `level` and everything less verbose.
+impl From<SerializeLevel> for LevelFilter {
+    fn from(level: SerializeLevel) -> Self {
+        LevelFilter::from_level(level.into())
+    }
+}
+
+impl SerializeLevel {
+    /// The most verbose level `filter` enables, or `None` if it is
+    /// [`LevelFilter::OFF`], the same way a maximum level is sent as an
+    /// `Option<SerializeLevel>`.
+    pub fn from_filter(filter: LevelFilter) -> Option<Self> {
+        filter.into_level().map(Self::from)
+    }
+
+    /// The filter enabling `max` and everything less verbose, or nothing if
+    /// `max` is `None`.
+    pub fn to_filter(max: Option<Self>) -> LevelFilter {
+        max.map_or(LevelFilter::OFF, LevelFilter::from)
+    }
+}
+
+#[cfg(feature = "alloc")]
 impl SerializeLevel {
     pub fn to_owned(&self) -> Self {
         *self
@@ -983,6 +2975,10 @@ impl<'a> self::sealed::Sealed for Record<'a> {}
 
 impl<'a> self::sealed::Sealed for Metadata<'a> {}
 
+impl self::sealed::Sealed for Live {}
+
+impl self::sealed::Sealed for Detached {}
+
 mod sealed {
     pub trait Sealed {}
 }
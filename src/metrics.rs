@@ -0,0 +1,175 @@
+//! Extracting metrics from events, by the names of their fields.
+//!
+//! Following the convention of `tracing-opentelemetry`'s `MetricsLayer`, an
+//! event field whose name starts with one of these prefixes records a metric,
+//! named by the rest of the field name:
+//!
+//! * `monotonic_counter.`: a counter that only goes up, by the value.
+//! * `counter.`: a counter that goes up or down, by the value.
+//! * `gauge.`: a measurement that replaces the previous one.
+//! * `histogram.`: a measurement to be aggregated into a distribution.
+//!
+//! The event's other fields, except `message`, are the metric's labels:
+//!
+//! ```rust
+//! use tracing_serde_structured::{
+//!     builder::SerializeEventBuilder,
+//!     metrics::{self, MetricKind, MetricValue},
+//!     SerializeValue,
+//! };
+//!
+//! // As recorded by `info!(histogram.request_ms = 12.5, route = "/", "done")`.
+//! let event = SerializeEventBuilder::new()
+//!     .field("histogram.request_ms", 12.5)
+//!     .field("route", "/")
+//!     .field("message", "done")
+//!     .build();
+//!
+//! let metrics = metrics::extract(&event);
+//! assert_eq!(metrics[0].kind, MetricKind::Histogram);
+//! assert_eq!(metrics[0].name, "request_ms");
+//! assert_eq!(metrics[0].value, MetricValue::F64(12.5));
+//! assert_eq!(metrics[0].labels["route"], SerializeValue::from("/"));
+//! ```
+//!
+//! So one `tracing` instrumentation can feed both a trace and a metrics
+//! backend, with the metrics extracted from the decoded stream.
+
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{SerializeEvent, SerializeRecordFields, SerializeValue};
+
+/// The kinds of metrics, by the prefix of the field they are recorded in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetricKind {
+    /// `monotonic_counter.`: a counter that only goes up.
+    MonotonicCounter,
+    /// `counter.`: a counter that goes up or down.
+    Counter,
+    /// `gauge.`: a measurement that replaces the previous one.
+    Gauge,
+    /// `histogram.`: a measurement aggregated into a distribution.
+    Histogram,
+}
+
+const PREFIXES: [(&str, MetricKind); 4] = [
+    ("monotonic_counter.", MetricKind::MonotonicCounter),
+    ("counter.", MetricKind::Counter),
+    ("gauge.", MetricKind::Gauge),
+    ("histogram.", MetricKind::Histogram),
+];
+
+impl MetricKind {
+    /// The kind of metric a field records, and the metric's name, or `None`
+    /// if the field is not named after a metric.
+    pub fn of_field(field: &str) -> Option<(MetricKind, &str)> {
+        PREFIXES.iter().find_map(|(prefix, kind)| {
+            let name = field.strip_prefix(prefix)?;
+            (!name.is_empty()).then_some((*kind, name))
+        })
+    }
+
+    /// The prefix of the fields that record this kind of metric, e.g.
+    /// `"counter."`.
+    pub fn prefix(&self) -> &'static str {
+        PREFIXES[*self as usize].0
+    }
+}
+
+impl fmt::Display for MetricKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.prefix().trim_end_matches('.'))
+    }
+}
+
+/// The value a metric was recorded with.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MetricValue {
+    U64(u64),
+    I64(i64),
+    F64(f64),
+}
+
+impl MetricValue {
+    pub fn as_f64(&self) -> f64 {
+        match *self {
+            MetricValue::U64(x) => x as f64,
+            MetricValue::I64(x) => x as f64,
+            MetricValue::F64(x) => x,
+        }
+    }
+}
+
+impl fmt::Display for MetricValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetricValue::U64(x) => x.fmt(f),
+            MetricValue::I64(x) => x.fmt(f),
+            MetricValue::F64(x) => x.fmt(f),
+        }
+    }
+}
+
+/// A metric recorded by an event.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Metric {
+    pub kind: MetricKind,
+    /// The name of the metric, without the prefix of its kind.
+    pub name: String,
+    pub value: MetricValue,
+    /// The event's other fields, except `message` and those recording
+    /// metrics.
+    pub labels: BTreeMap<String, SerializeValue<'static>>,
+}
+
+/// The metrics `event` records, in the order of their fields' names.
+///
+/// A field named after a metric is skipped if its value is not a number,
+/// or if it is negative for a monotonic counter.
+pub fn extract(event: &SerializeEvent<'_>) -> Vec<Metric> {
+    let fields = match &event.fields {
+        SerializeRecordFields::De(map) => map,
+        SerializeRecordFields::Ser(never) => match *never {},
+    };
+
+    let mut metrics = Vec::new();
+    let mut labels = BTreeMap::new();
+    for (field, value) in fields {
+        let field = field.as_str();
+        let Some((kind, name)) = MetricKind::of_field(field) else {
+            if field != "message" {
+                labels.insert(field.into(), value.to_owned());
+            }
+            continue;
+        };
+        let value = match (kind, value) {
+            (_, SerializeValue::U64(x)) => MetricValue::U64(*x),
+            (MetricKind::MonotonicCounter, SerializeValue::I64(x)) => match u64::try_from(*x) {
+                Ok(x) => MetricValue::U64(x),
+                Err(_) => continue,
+            },
+            (_, SerializeValue::I64(x)) => MetricValue::I64(*x),
+            (MetricKind::MonotonicCounter, SerializeValue::F64(x)) if *x < 0.0 => continue,
+            (_, SerializeValue::F64(x)) => MetricValue::F64(*x),
+            _ => continue,
+        };
+        metrics.push(Metric {
+            kind,
+            name: name.into(),
+            value,
+            labels: BTreeMap::new(),
+        });
+    }
+    if let Some((last, rest)) = metrics.split_last_mut() {
+        for metric in rest {
+            metric.labels.clone_from(&labels);
+        }
+        last.labels = labels;
+    }
+    metrics
+}
@@ -0,0 +1,394 @@
+//! A sink publishing serialized messages to MQTT topics.
+//!
+//! An [`MqttSink`] connects to a broker with [`rumqttc`], configured by the
+//! application (broker address, client id, credentials, keep alive), and
+//! publishes every event to a topic made from its metadata:
+//!
+//! ```rust,no_run
+//! use rumqttc::{MqttOptions, QoS};
+//! use tracing_serde_structured::mqtt::{Encoding, MqttSink, Topics};
+//! # use tracing_serde_structured::SerializeEvent;
+//!
+//! let options = MqttOptions::new("gateway-17", "broker.local", 1883);
+//! let mut sink = MqttSink::new(options, Topics::new("gateway-17"))
+//!     .with_encoding(Encoding::Json)
+//!     .with_qos(QoS::AtLeastOnce);
+//! # fn event() -> SerializeEvent<'static> { todo!() }
+//! sink.send_event(&event()).unwrap();
+//! ```
+//!
+//! The first message starts a thread driving the connection, which
+//! reconnects whenever the connection is lost, waiting longer after every
+//! failed attempt. While there is no connection, messages are kept in a
+//! bounded queue: once it is full, the oldest message is dropped for each
+//! new one, and counted in the [`stats`](MqttSink::stats). The queue is sent
+//! as soon as the connection is back, with the next message or
+//! [flush](MqttSink::flush).
+//!
+//! Messages are handed to `rumqttc`, which sends them, and with
+//! [`QoS::AtLeastOnce`] or [`QoS::ExactlyOnce`] sends them again after
+//! reconnecting until the broker acknowledges them. A message counts as sent
+//! once `rumqttc` has taken it.
+//!
+//! `mqtts` brokers need one of `rumqttc`'s TLS features enabled.
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use rumqttc::{Client, Connection, Event, MqttOptions, Outgoing, Packet, QoS};
+use serde::Serialize;
+
+use crate::{wire::SerializePipelineStats, SerializeEvent, SerializeMetadata};
+
+/// Errors returned by an [`MqttSink`].
+#[derive(Debug)]
+pub enum Error {
+    /// The message could not be encoded as postcard.
+    Postcard(::postcard::Error),
+    /// The message could not be encoded as JSON.
+    Json(serde_json::Error),
+    /// The topic is empty, or contains a wildcard (`+` or `#`).
+    InvalidTopic(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Postcard(e) => write!(f, "failed to encode message as postcard: {e}"),
+            Error::Json(e) => write!(f, "failed to encode message as JSON: {e}"),
+            Error::InvalidTopic(topic) => write!(f, "cannot publish to topic {topic:?}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Postcard(e) => Some(e),
+            Error::Json(e) => Some(e),
+            Error::InvalidTopic(_) => None,
+        }
+    }
+}
+
+/// The encoding used for message payloads.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    /// Compact binary encoding using `postcard`.
+    #[default]
+    Postcard,
+    /// JSON, for consumers that cannot decode postcard.
+    Json,
+}
+
+impl Encoding {
+    /// Encode `msg` using this encoding.
+    pub fn encode<T: Serialize + ?Sized>(&self, msg: &T) -> Result<Vec<u8>, Error> {
+        match self {
+            Encoding::Postcard => ::postcard::to_allocvec(msg).map_err(Error::Postcard),
+            Encoding::Json => serde_json::to_vec(msg).map_err(Error::Json),
+        }
+    }
+}
+
+/// How the topic of an event is made from its metadata.
+///
+/// The template is a topic with placeholders, replaced for every event:
+///
+/// * `{service}`: the service name the topics were created with, such as a
+///   device or gateway id.
+/// * `{level}`: the event's level, in lower case, such as `warn`.
+/// * `{target}`: the event's target, with every `::` turned into a `/`, so
+///   that `app::net` becomes the two levels `app/net`.
+///
+/// Characters a topic level cannot hold (`/`, `+` and `#`) are replaced with
+/// `_` in the values, so each value takes the levels it is meant to.
+///
+/// ```rust
+/// use tracing_serde_structured::{builder::SerializeEventBuilder, mqtt::Topics, SerializeLevel};
+///
+/// let event = SerializeEventBuilder::new()
+///     .target("app::net")
+///     .level(SerializeLevel::Warn)
+///     .build();
+///
+/// let topics = Topics::new("gateway-17");
+/// assert_eq!(topics.topic(&event.metadata), "tracing/gateway-17/warn");
+///
+/// let topics = topics.with_template("site/3/{service}/{target}");
+/// assert_eq!(topics.topic(&event.metadata), "site/3/gateway-17/app/net");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Topics {
+    template: String,
+    service: String,
+}
+
+impl Topics {
+    /// The template used by default.
+    pub const DEFAULT_TEMPLATE: &'static str = "tracing/{service}/{level}";
+
+    /// Topics for the service `service`, made from the
+    /// [`DEFAULT_TEMPLATE`](Self::DEFAULT_TEMPLATE).
+    pub fn new(service: impl Into<String>) -> Self {
+        Self {
+            template: Self::DEFAULT_TEMPLATE.to_owned(),
+            service: service.into(),
+        }
+    }
+
+    /// Make topics from `template` instead.
+    pub fn with_template(mut self, template: impl Into<String>) -> Self {
+        self.template = template.into();
+        self
+    }
+
+    /// Returns the topic for an event with `metadata`.
+    pub fn topic(&self, metadata: &SerializeMetadata<'_>) -> String {
+        let mut topic = String::with_capacity(self.template.len() + 32);
+        let mut rest = self.template.as_str();
+        while let Some(start) = rest.find('{') {
+            topic.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let Some(end) = rest.find('}') else {
+                break;
+            };
+            match &rest[1..end] {
+                "service" => push_level(&mut topic, &self.service),
+                "level" => push_level(&mut topic, &metadata.level.as_str().to_lowercase()),
+                "target" => {
+                    for (i, level) in metadata.target.as_str().split("::").enumerate() {
+                        if i > 0 {
+                            topic.push('/');
+                        }
+                        push_level(&mut topic, level);
+                    }
+                }
+                _ => topic.push_str(&rest[..=end]),
+            }
+            rest = &rest[end + 1..];
+        }
+        topic.push_str(rest);
+        topic
+    }
+}
+
+/// Appends `value` to `topic` as one level.
+fn push_level(topic: &mut String, value: &str) {
+    if value.is_empty() {
+        topic.push('_');
+    }
+    topic.extend(value.chars().map(|c| match c {
+        '/' | '+' | '#' => '_',
+        c => c,
+    }));
+}
+
+/// Publishes serialized messages to MQTT topics, queueing them while there is
+/// no connection.
+pub struct MqttSink {
+    client: Client,
+    /// The connection, until the first message starts the thread driving it.
+    connection: Option<Connection>,
+    online: Arc<AtomicBool>,
+    topics: Topics,
+    encoding: Encoding,
+    qos: QoS,
+    queue: VecDeque<(String, Vec<u8>)>,
+    capacity: usize,
+    backoff: (Duration, Duration),
+    stats: SerializePipelineStats,
+}
+
+impl fmt::Debug for MqttSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MqttSink")
+            .field("online", &self.is_online())
+            .field("topics", &self.topics)
+            .field("encoding", &self.encoding)
+            .field("qos", &self.qos)
+            .field("queued", &self.queue.len())
+            .field("capacity", &self.capacity)
+            .field("stats", &self.stats)
+            .finish_non_exhaustive()
+    }
+}
+
+impl MqttSink {
+    /// The number of messages queued while there is no connection, by
+    /// default.
+    pub const DEFAULT_CAPACITY: usize = 1024;
+
+    /// The number of messages `rumqttc` holds before sending them.
+    const CLIENT_CAPACITY: usize = 64;
+
+    /// A sink connecting with `options` once the first message is sent, and
+    /// publishing postcard encoded messages with [`QoS::AtLeastOnce`].
+    ///
+    /// It waits 500 ms before connecting again after a failed attempt, and
+    /// twice as long after every further one, up to 30 s.
+    pub fn new(options: MqttOptions, topics: Topics) -> Self {
+        let (client, connection) = Client::new(options, Self::CLIENT_CAPACITY);
+        Self {
+            client,
+            connection: Some(connection),
+            online: Arc::new(AtomicBool::new(false)),
+            topics,
+            encoding: Encoding::default(),
+            qos: QoS::AtLeastOnce,
+            queue: VecDeque::new(),
+            capacity: Self::DEFAULT_CAPACITY,
+            backoff: (Duration::from_millis(500), Duration::from_secs(30)),
+            stats: SerializePipelineStats::default(),
+        }
+    }
+
+    /// Set the encoding of message payloads.
+    pub fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Set the quality of service messages are published with.
+    pub fn with_qos(mut self, qos: QoS) -> Self {
+        self.qos = qos;
+        self
+    }
+
+    /// Queue at most `capacity` messages while there is no connection,
+    /// rather than [`DEFAULT_CAPACITY`](Self::DEFAULT_CAPACITY).
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// Wait `min` before connecting again after a failed attempt, and twice
+    /// as long after every further one, up to `max`.
+    pub fn with_backoff(mut self, min: Duration, max: Duration) -> Self {
+        self.backoff = (min, max.max(min));
+        self
+    }
+
+    /// Queue an event, to the topic made from its metadata, and send the
+    /// queue if there is a connection.
+    pub fn send_event(&mut self, event: &SerializeEvent<'_>) -> Result<(), Error> {
+        let topic = self.topics.topic(&event.metadata);
+        self.send_to(topic, event)
+    }
+
+    /// Queue any serializable message, to an explicit topic, and send the
+    /// queue if there is a connection.
+    pub fn send_to<T: Serialize + ?Sized>(
+        &mut self,
+        topic: impl Into<String>,
+        msg: &T,
+    ) -> Result<(), Error> {
+        let topic = topic.into();
+        if topic.is_empty() || topic.contains(['+', '#']) {
+            count(&mut self.stats.errors);
+            return Err(Error::InvalidTopic(topic));
+        }
+        let payload = match self.encoding.encode(msg) {
+            Ok(payload) => payload,
+            Err(e) => {
+                count(&mut self.stats.errors);
+                return Err(e);
+            }
+        };
+        if self.queue.len() >= self.capacity {
+            self.queue.pop_front();
+            count(&mut self.stats.dropped);
+        }
+        self.queue.push_back((topic, payload));
+        self.flush();
+        Ok(())
+    }
+
+    /// Hands as much of the queue to `rumqttc` as it takes, if there is a
+    /// connection.
+    pub fn flush(&mut self) {
+        if let Some(connection) = self.connection.take() {
+            let online = self.online.clone();
+            let (min, max) = self.backoff;
+            thread::spawn(move || drive(connection, &online, min, max));
+        }
+        if !self.is_online() {
+            return;
+        }
+        while let Some((topic, payload)) = self.queue.front() {
+            // The client only fails to take a message when it holds too many
+            // already.
+            if self
+                .client
+                .try_publish(topic.clone(), self.qos, false, payload.clone())
+                .is_err()
+            {
+                break;
+            }
+            self.queue.pop_front();
+            count(&mut self.stats.sent);
+        }
+    }
+
+    /// Returns `true` if the sink is connected to the broker.
+    pub fn is_online(&self) -> bool {
+        self.online.load(Ordering::Acquire)
+    }
+
+    /// The number of messages waiting for a connection.
+    pub fn queued(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// The counts of messages sent and lost so far.
+    pub fn stats(&self) -> SerializePipelineStats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = SerializePipelineStats::default();
+    }
+
+    /// Sends the queue, if there is a connection, and disconnects from the
+    /// broker once `rumqttc` has sent what it holds. Messages still queued
+    /// are dropped with the sink.
+    pub fn disconnect(mut self) {
+        self.flush();
+        // The connection is gone already if this fails.
+        let _ = self.client.disconnect();
+    }
+}
+
+/// Drives `connection` until the sink is dropped or disconnects,
+/// reconnecting after a backoff whenever it fails.
+fn drive(mut connection: Connection, online: &AtomicBool, min: Duration, max: Duration) {
+    let mut delay = min;
+    for event in connection.iter() {
+        match event {
+            Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                online.store(true, Ordering::Release);
+                delay = min;
+            }
+            Ok(Event::Outgoing(Outgoing::Disconnect)) => break,
+            Ok(_) => {}
+            Err(_) => {
+                online.store(false, Ordering::Release);
+                thread::sleep(delay);
+                delay = (delay * 2).min(max);
+            }
+        }
+    }
+    online.store(false, Ordering::Release);
+}
+
+fn count(count: &mut u32) {
+    *count = count.saturating_add(1);
+}
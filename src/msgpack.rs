@@ -0,0 +1,43 @@
+//! MessagePack encoding helpers.
+//!
+//! Structs can be written either as maps keyed by field name
+//! ([`Representation::Named`]), which is self-describing and easy to inspect
+//! with generic MessagePack tooling, or as positional arrays
+//! ([`Representation::Compact`]), which omits the field names entirely.
+//! [`from_msgpack_slice`] accepts both, so producers can pick a
+//! representation per deployment without any change on the consumer side.
+
+use serde::{Deserialize, Serialize};
+
+pub use rmp_serde::{decode::Error as DecodeError, encode::Error as EncodeError};
+
+/// How structs are laid out in the encoded output.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Representation {
+    /// Structs are encoded as maps from field name to value.
+    #[default]
+    Named,
+    /// Structs are encoded as arrays of values, in declaration order.
+    Compact,
+}
+
+/// Serializes `value` as MessagePack, using the given struct representation.
+pub fn to_msgpack<T>(value: &T, repr: Representation) -> Result<Vec<u8>, EncodeError>
+where
+    T: Serialize + ?Sized,
+{
+    match repr {
+        Representation::Named => rmp_serde::to_vec_named(value),
+        Representation::Compact => rmp_serde::to_vec(value),
+    }
+}
+
+/// Deserializes a value from MessagePack, borrowing strings from `bytes`.
+///
+/// Structs may be encoded with either [`Representation`].
+pub fn from_msgpack_slice<'a, T>(bytes: &'a [u8]) -> Result<T, DecodeError>
+where
+    T: Deserialize<'a>,
+{
+    rmp_serde::from_slice(bytes)
+}
@@ -0,0 +1,363 @@
+//! A sink publishing serialized messages to NATS subjects.
+//!
+//! The sink wraps an [`async_nats::Client`], which the application connects
+//! (servers, credentials, TLS) and hands over, along with the name of the
+//! service the messages come from:
+//!
+//! ```rust,no_run
+//! # async fn run(event: tracing_serde_structured::SerializeEvent<'_>) -> Result<(), Box<dyn std::error::Error>> {
+//! use tracing_serde_structured::nats::{NatsSink, Subjects};
+//!
+//! let client = async_nats::connect("nats://localhost:4222").await?;
+//! let mut sink = NatsSink::new(client, Subjects::new("billing")).with_jetstream();
+//! sink.send_event(&event).await?;
+//! // Before exiting:
+//! sink.flush().await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Each event is published to a subject made from the [`Subjects`] template,
+//! such as `tracing.billing.warn` with the default one, so that consumers
+//! can subscribe to what they need with wildcards (`tracing.*.error`,
+//! `tracing.billing.>`). Other messages, such as span attributes or wire
+//! messages, are published to the subject they are sent with.
+//!
+//! Messages are buffered and published in batches of up to
+//! [`NatsSink::with_batch_size`] messages. Without JetStream, flushing a
+//! batch waits until it has been written to the connection, and nothing
+//! tells whether the server received it. With
+//! [`with_jetstream`](NatsSink::with_jetstream), it waits until the streams
+//! capturing the subjects have acknowledged every message, so a stream
+//! covering them, such as `tracing.>`, has to exist. Messages that fail to
+//! publish are not retried, and are counted as dropped in the
+//! [`stats`](NatsSink::stats).
+
+use core::fmt;
+
+use async_nats::{jetstream, Client};
+use serde::Serialize;
+
+use crate::{wire::SerializePipelineStats, SerializeEvent, SerializeMetadata};
+
+/// Errors returned by a [`NatsSink`].
+#[derive(Debug)]
+pub enum Error {
+    /// The message could not be encoded as postcard.
+    Postcard(::postcard::Error),
+    /// The message could not be encoded as JSON.
+    Json(serde_json::Error),
+    /// The message could not be published.
+    Publish(async_nats::PublishError),
+    /// The batch could not be written to the connection.
+    Flush(async_nats::client::FlushError),
+    /// The message could not be published, or was not acknowledged, by
+    /// JetStream.
+    JetStream(jetstream::context::PublishError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Postcard(e) => write!(f, "failed to encode message as postcard: {e}"),
+            Error::Json(e) => write!(f, "failed to encode message as JSON: {e}"),
+            Error::Publish(e) => write!(f, "failed to publish message: {e}"),
+            Error::Flush(e) => write!(f, "failed to flush batch: {e}"),
+            Error::JetStream(e) => write!(f, "failed to publish message to JetStream: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Postcard(e) => Some(e),
+            Error::Json(e) => Some(e),
+            Error::Publish(e) => Some(e),
+            Error::Flush(e) => Some(e),
+            Error::JetStream(e) => Some(e),
+        }
+    }
+}
+
+/// The encoding used for message payloads.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    /// Compact binary encoding using `postcard`.
+    #[default]
+    Postcard,
+    /// JSON, for consumers that cannot decode postcard.
+    Json,
+}
+
+impl Encoding {
+    /// Encode `msg` using this encoding.
+    pub fn encode<T: Serialize + ?Sized>(&self, msg: &T) -> Result<Vec<u8>, Error> {
+        match self {
+            Encoding::Postcard => ::postcard::to_allocvec(msg).map_err(Error::Postcard),
+            Encoding::Json => serde_json::to_vec(msg).map_err(Error::Json),
+        }
+    }
+}
+
+/// How the subject of an event is made from its metadata.
+///
+/// The template is a subject with placeholders, replaced for every event:
+///
+/// * `{service}`: the service name the subjects were created with.
+/// * `{level}`: the event's level, in lower case, such as `warn`.
+/// * `{target}`: the event's target, with every `::` turned into a `.`, so
+///   that `app::net` becomes the two tokens `app.net`.
+///
+/// Characters a subject token cannot hold (`.`, `*`, `>` and whitespace) are
+/// replaced with `_` in the values, so each value takes the tokens it is
+/// meant to.
+///
+/// ```rust
+/// use tracing_serde_structured::{builder::SerializeEventBuilder, nats::Subjects, SerializeLevel};
+///
+/// let event = SerializeEventBuilder::new()
+///     .target("app::net")
+///     .level(SerializeLevel::Warn)
+///     .build();
+///
+/// let subjects = Subjects::new("billing");
+/// assert_eq!(subjects.subject(&event.metadata), "tracing.billing.warn");
+///
+/// let subjects = subjects.with_template("logs.{level}.{target}");
+/// assert_eq!(subjects.subject(&event.metadata), "logs.warn.app.net");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Subjects {
+    template: String,
+    service: String,
+}
+
+impl Subjects {
+    /// The template used by default.
+    pub const DEFAULT_TEMPLATE: &'static str = "tracing.{service}.{level}";
+
+    /// Subjects for the service `service`, made from the
+    /// [`DEFAULT_TEMPLATE`](Self::DEFAULT_TEMPLATE).
+    pub fn new(service: impl Into<String>) -> Self {
+        Self {
+            template: Self::DEFAULT_TEMPLATE.to_owned(),
+            service: service.into(),
+        }
+    }
+
+    /// Make subjects from `template` instead.
+    pub fn with_template(mut self, template: impl Into<String>) -> Self {
+        self.template = template.into();
+        self
+    }
+
+    /// Returns the subject for an event with `metadata`.
+    pub fn subject(&self, metadata: &SerializeMetadata<'_>) -> String {
+        let mut subject = String::with_capacity(self.template.len() + 32);
+        let mut rest = self.template.as_str();
+        while let Some(start) = rest.find('{') {
+            subject.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let Some(end) = rest.find('}') else {
+                break;
+            };
+            match &rest[1..end] {
+                "service" => push_token(&mut subject, &self.service),
+                "level" => push_token(&mut subject, &metadata.level.as_str().to_lowercase()),
+                "target" => {
+                    for (i, token) in metadata.target.as_str().split("::").enumerate() {
+                        if i > 0 {
+                            subject.push('.');
+                        }
+                        push_token(&mut subject, token);
+                    }
+                }
+                _ => subject.push_str(&rest[..=end]),
+            }
+            rest = &rest[end + 1..];
+        }
+        subject.push_str(rest);
+        subject
+    }
+}
+
+/// Appends `value` to `subject` as one token.
+fn push_token(subject: &mut String, value: &str) {
+    if value.is_empty() {
+        subject.push('_');
+    }
+    subject.extend(value.chars().map(|c| match c {
+        '.' | '*' | '>' => '_',
+        c if c.is_whitespace() => '_',
+        c => c,
+    }));
+}
+
+/// Publishes serialized messages to NATS subjects.
+pub struct NatsSink {
+    client: Client,
+    jetstream: Option<jetstream::Context>,
+    subjects: Subjects,
+    encoding: Encoding,
+    batch_size: usize,
+    pending: Vec<(String, Vec<u8>)>,
+    stats: SerializePipelineStats,
+}
+
+impl fmt::Debug for NatsSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NatsSink")
+            .field("jetstream", &self.jetstream.is_some())
+            .field("subjects", &self.subjects)
+            .field("encoding", &self.encoding)
+            .field("batch_size", &self.batch_size)
+            .field("pending", &self.pending.len())
+            .field("stats", &self.stats)
+            .finish_non_exhaustive()
+    }
+}
+
+impl NatsSink {
+    /// The default number of messages published per batch.
+    pub const DEFAULT_BATCH_SIZE: usize = 128;
+
+    /// Create a sink publishing postcard-encoded messages with `client`,
+    /// without JetStream.
+    pub fn new(client: Client, subjects: Subjects) -> Self {
+        Self {
+            client,
+            jetstream: None,
+            subjects,
+            encoding: Encoding::default(),
+            batch_size: Self::DEFAULT_BATCH_SIZE,
+            pending: Vec::new(),
+            stats: SerializePipelineStats::default(),
+        }
+    }
+
+    /// Publish to JetStream, waiting for every message to be acknowledged
+    /// when a batch is flushed.
+    pub fn with_jetstream(mut self) -> Self {
+        self.jetstream = Some(jetstream::new(self.client.clone()));
+        self
+    }
+
+    /// Set the encoding of message payloads.
+    pub fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Set the maximum number of messages buffered before a batch is
+    /// published.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Queue an event, to the subject made from its metadata.
+    pub async fn send_event(&mut self, event: &SerializeEvent<'_>) -> Result<(), Error> {
+        let subject = self.subjects.subject(&event.metadata);
+        self.send_to(subject, event).await
+    }
+
+    /// Queue any serializable message, to an explicit subject.
+    pub async fn send_to<T: Serialize + ?Sized>(
+        &mut self,
+        subject: impl Into<String>,
+        msg: &T,
+    ) -> Result<(), Error> {
+        let payload = match self.encoding.encode(msg) {
+            Ok(payload) => payload,
+            Err(e) => {
+                count(&mut self.stats.errors, 1);
+                return Err(e);
+            }
+        };
+        self.pending.push((subject.into(), payload));
+        if self.pending.len() >= self.batch_size {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// The number of messages waiting to be published.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Publish all queued messages, and wait until they have been written to
+    /// the connection or, with JetStream, until they have all been
+    /// acknowledged.
+    ///
+    /// Every message is tried, even after one fails, and the first error is
+    /// returned. Messages that failed are dropped.
+    pub async fn flush(&mut self) -> Result<(), Error> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let batch = core::mem::take(&mut self.pending);
+        let len = batch.len();
+        let mut result = Ok(());
+        match &self.jetstream {
+            None => {
+                let mut published = 0;
+                for (subject, payload) in batch {
+                    match self.client.publish(subject, payload.into()).await {
+                        Ok(()) => published += 1,
+                        Err(e) => {
+                            result = result.and(Err(Error::Publish(e)));
+                        }
+                    }
+                }
+                // Publishing only queues the message in the client, a flush
+                // waits until it is written out.
+                if let Err(e) = self.client.flush().await {
+                    count(&mut self.stats.dropped, len);
+                    return result.and(Err(Error::Flush(e)));
+                }
+                count(&mut self.stats.sent, published);
+                count(&mut self.stats.dropped, len - published);
+            }
+            Some(jetstream) => {
+                let mut acks = Vec::with_capacity(len);
+                for (subject, payload) in batch {
+                    match jetstream.publish(subject, payload.into()).await {
+                        Ok(ack) => acks.push(ack),
+                        Err(e) => result = result.and(Err(Error::JetStream(e))),
+                    }
+                }
+                count(&mut self.stats.dropped, len - acks.len());
+                for ack in acks {
+                    match ack.await {
+                        Ok(_) => count(&mut self.stats.sent, 1),
+                        Err(e) => {
+                            count(&mut self.stats.dropped, 1);
+                            result = result.and(Err(Error::JetStream(e)));
+                        }
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// The counts of messages published and lost so far.
+    pub fn stats(&self) -> SerializePipelineStats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = SerializePipelineStats::default();
+    }
+
+    /// Returns the underlying client.
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+}
+
+fn count(count: &mut u32, n: usize) {
+    *count = count.saturating_add(u32::try_from(n).unwrap_or(u32::MAX));
+}
@@ -0,0 +1,278 @@
+//! Writing framed postcard messages to non-blocking [`io::Write`] sinks.
+//!
+//! A socket in non-blocking mode takes only as many bytes as fit into its
+//! send buffer, and returns [`io::ErrorKind::WouldBlock`] rather than waiting
+//! for room. A [`NonBlockingWriter`] keeps the rest of a frame the sink would
+//! block on, so that no frame is cut short, and writes it once the sink is
+//! ready:
+//!
+//! ```rust
+//! use std::{io, task::Poll};
+//!
+//! use tracing_serde_structured::{
+//!     nonblocking::{Error, NonBlockingWriter},
+//!     SerializeLevel,
+//! };
+//!
+//! /// A sink with room for `room` more bytes.
+//! struct Socket {
+//!     out: Vec<u8>,
+//!     room: usize,
+//! }
+//!
+//! impl io::Write for Socket {
+//!     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+//!         if self.room == 0 {
+//!             return Err(io::ErrorKind::WouldBlock.into());
+//!         }
+//!         let len = buf.len().min(self.room);
+//!         self.out.extend_from_slice(&buf[..len]);
+//!         self.room -= len;
+//!         Ok(len)
+//!     }
+//!
+//!     fn flush(&mut self) -> io::Result<()> {
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let socket = Socket { out: Vec::new(), room: 1 };
+//! let mut writer = NonBlockingWriter::<_, 64>::new(socket);
+//! assert!(matches!(writer.write_frame(&SerializeLevel::Warn), Err(Error::WouldBlock)));
+//! // The rest of the first frame is in the way of the next one.
+//! assert!(matches!(writer.write_frame(&SerializeLevel::Info), Err(Error::Full)));
+//! assert!(writer.poll_flush().is_pending());
+//!
+//! writer.get_mut().room = 16;
+//! assert!(matches!(writer.poll_flush(), Poll::Ready(Ok(()))));
+//! writer.write_frame(&SerializeLevel::Info).unwrap();
+//! assert_eq!(writer.get_ref().out, [0x02, 0x03, 0x00, 0x02, 0x02, 0x00]);
+//! ```
+//!
+//! This lets an async runtime drive the sink without a thread of its own:
+//! whenever [`poll_flush`](NonBlockingWriter::poll_flush) returns
+//! `Poll::Pending`, wait until the socket is writable (e.g. with tokio's
+//! `TcpStream::writable`, or a `mio` event), and call it again. Messages
+//! written in the meantime are dropped and counted, as a producer that must
+//! not block would have to drop them anyway.
+//!
+//! Frames are COBS framed, unless the writer is created
+//! [`with_framing`](NonBlockingWriter::with_framing) some other
+//! [`Framing`], such as [`LengthPrefixed`](crate::framing::LengthPrefixed).
+
+use core::{fmt, ops::Range, task::Poll};
+use std::io::{self, ErrorKind};
+
+use serde::Serialize;
+
+use crate::{
+    framing::{Cobs, Framing},
+    wire::SerializePipelineStats,
+};
+
+/// Errors returned when writing a frame.
+#[derive(Debug)]
+pub enum Error {
+    /// The message could not be encoded, e.g. because its frame is longer
+    /// than the writer's buffer.
+    Encode(::postcard::Error),
+    /// The sink would block part way through the frame. The rest of it is
+    /// kept, and written by [`poll_flush`](NonBlockingWriter::poll_flush) or
+    /// before the next message.
+    WouldBlock,
+    /// The sink would still block on the rest of an earlier frame, so the
+    /// message was dropped without being encoded.
+    Full,
+    /// Writing to the sink failed, and the frame was dropped.
+    Io(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Encode(e) => write!(f, "failed to encode message: {e}"),
+            Error::WouldBlock => f.write_str("sink would block, frame is pending"),
+            Error::Full => f.write_str("sink would block on a pending frame"),
+            Error::Io(e) => write!(f, "failed to write frame: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Encode(e) => Some(e),
+            Error::Io(e) => Some(e),
+            Error::WouldBlock | Error::Full => None,
+        }
+    }
+}
+
+/// Writes messages as framed postcard to the non-blocking sink `W`, encoding
+/// each frame in a buffer of `N` bytes, and keeping what the sink would block
+/// on until it is ready.
+///
+/// A frame is counted as sent once all of it has been written.
+pub struct NonBlockingWriter<W, const N: usize, F = Cobs> {
+    writer: W,
+    buf: [u8; N],
+    /// The part of `buf` left to write.
+    pending: Range<usize>,
+    framing: F,
+    stats: SerializePipelineStats,
+}
+
+impl<W, const N: usize, F> fmt::Debug for NonBlockingWriter<W, N, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NonBlockingWriter")
+            .field("buf_len", &N)
+            .field("pending", &self.pending.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<W: io::Write, const N: usize> NonBlockingWriter<W, N> {
+    pub fn new(writer: W) -> Self {
+        Self::with_framing(writer, Cobs)
+    }
+}
+
+impl<W: io::Write, const N: usize, F: Framing> NonBlockingWriter<W, N, F> {
+    /// A writer framing messages with `framing`, such as
+    /// [`LengthPrefixed`](crate::framing::LengthPrefixed).
+    pub fn with_framing(writer: W, framing: F) -> Self {
+        Self {
+            writer,
+            buf: [0; N],
+            pending: 0..0,
+            framing,
+            stats: SerializePipelineStats::default(),
+        }
+    }
+
+    /// Writes the rest of a pending frame, if any, then encodes `value` and
+    /// writes it to the sink as one frame.
+    ///
+    /// Returns [`Error::WouldBlock`] if the sink would block on the new
+    /// frame, which is then pending, and [`Error::Full`] if it would still
+    /// block on the old one, in which case the message is dropped.
+    pub fn write_frame<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.encode_with(|framing, buf| framing.to_slice(value, buf))
+    }
+
+    /// Encodes a frame with `encode`, which is given the writer's buffer and
+    /// returns the frame in it, framed as the writer frames messages
+    /// (including a COBS frame's terminating zero byte), then writes it to the
+    /// sink, as [`write_frame`](Self::write_frame) does.
+    ///
+    /// This is for frames with trailers, such as those encoded by
+    /// [`Checksum::to_slice_cobs`](crate::checksum::Checksum::to_slice_cobs).
+    pub fn write_with<E>(&mut self, encode: E) -> Result<(), Error>
+    where
+        E: FnOnce(&mut [u8]) -> Result<&mut [u8], ::postcard::Error>,
+    {
+        self.encode_with(|_, buf| encode(buf))
+    }
+
+    fn encode_with<E>(&mut self, encode: E) -> Result<(), Error>
+    where
+        E: for<'b> FnOnce(&F, &'b mut [u8]) -> Result<&'b mut [u8], ::postcard::Error>,
+    {
+        if let Err(e) = self.write_pending() {
+            count(&mut self.stats.dropped);
+            return Err(match e.kind() {
+                ErrorKind::WouldBlock => Error::Full,
+                _ => Error::Io(e),
+            });
+        }
+        let base = self.buf.as_ptr() as usize;
+        match encode(&self.framing, &mut self.buf) {
+            Ok(frame) => {
+                let start = frame.as_ptr() as usize - base;
+                self.pending = start..start + frame.len();
+            }
+            Err(e) => {
+                count(match e {
+                    ::postcard::Error::SerializeBufferFull => &mut self.stats.overflows,
+                    _ => &mut self.stats.errors,
+                });
+                return Err(Error::Encode(e));
+            }
+        }
+        self.write_pending().map_err(|e| match e.kind() {
+            ErrorKind::WouldBlock => Error::WouldBlock,
+            _ => Error::Io(e),
+        })
+    }
+
+    /// Writes as much of the pending frame as the sink takes. The frame is
+    /// counted as sent once it is complete, and as dropped if the sink fails.
+    fn write_pending(&mut self) -> io::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        while !self.pending.is_empty() {
+            let result = match self.writer.write(&self.buf[self.pending.clone()]) {
+                Ok(0) => Err(ErrorKind::WriteZero.into()),
+                result => result,
+            };
+            match result {
+                Ok(len) => self.pending.start += len,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Err(e),
+                Err(e) => {
+                    self.pending = 0..0;
+                    count(&mut self.stats.dropped);
+                    return Err(e);
+                }
+            }
+        }
+        count(&mut self.stats.sent);
+        Ok(())
+    }
+
+    /// Writes the rest of a pending frame, if any, and flushes the sink.
+    ///
+    /// Returns `Poll::Pending` if the sink would block, in which case call it
+    /// again once the sink is writable.
+    pub fn poll_flush(&mut self) -> Poll<io::Result<()>> {
+        match self.write_pending().and_then(|()| self.writer.flush()) {
+            Err(e) if e.kind() == ErrorKind::WouldBlock => Poll::Pending,
+            result => Poll::Ready(result),
+        }
+    }
+
+    /// Whether part of a frame is waiting for the sink.
+    pub fn is_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// The counts of messages sent and lost so far.
+    pub fn stats(&self) -> SerializePipelineStats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = SerializePipelineStats::default();
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Returns the sink, dropping the rest of a pending frame, if any.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+fn count(count: &mut u32) {
+    *count = count.saturating_add(1);
+}
@@ -0,0 +1,665 @@
+//! Converting OpenTelemetry log records and spans, as exported over OTLP,
+//! into this crate's types.
+//!
+//! So the tools built on this crate also work for applications instrumented
+//! with OpenTelemetry instead of `tracing`: their events and spans can be
+//! printed, queried or stored in SQLite like any others. The body of an
+//! OTLP/HTTP request, or the message of an OTLP/gRPC call, is decoded with
+//! [`decode_logs`] or [`decode_spans`]:
+//!
+//! ```rust
+//! use tracing_serde_structured::otlp;
+//!
+//! # fn run(body: &[u8]) -> Result<(), prost::DecodeError> {
+//! for log in otlp::decode_logs(body)? {
+//!     let meta = &log.event.metadata;
+//!     println!("{:?} {} {}", log.timestamp, meta.level.as_str(), meta.target.as_str());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! A log record becomes a [`LogEvent`], whose event
+//!
+//! * is at the level of the record's severity number, or failing that its
+//!   severity text, with `FATAL` becoming `ERROR`;
+//! * has the record's event name as its name, or else `event file:line`,
+//!   like the events of `tracing`, if it has a code location;
+//! * has the record's body as its `message` field, and its attributes as its
+//!   other fields;
+//! * has the span the record was emitted in as its parent.
+//!
+//! A span becomes a [`SpanRecord`], holding its attributes, the values of its
+//! fields, and its span events, as events inside of it. Spans are at the
+//! `INFO` level, and their kind and status are recorded as the `otel.kind`
+//! and `otel.status_code` fields, as `tracing-opentelemetry` reads them.
+//!
+//! For both, the attributes `tracing-opentelemetry` adds are turned back into
+//! metadata: `target` and `level`, and the `code.namespace`,
+//! `code.filepath` and `code.lineno` of the location. Without a `target`,
+//! the name of the instrumentation scope is the target. Attribute values
+//! that have no equivalent, such as arrays and bytes, become debug-formatted
+//! values, and ids of the wrong length are ignored.
+
+use core::{fmt::Write, num::NonZeroU64, time::Duration};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use prost::Message;
+
+use crate::{
+    builder::{OwnedAttributesBuilder, OwnedEventBuilder},
+    CowString, DebugRecord, SerializeAttributes, SerializeEvent, SerializeId, SerializeLevel,
+    SerializeRecord, SerializeValue,
+};
+
+/// The messages of the OTLP logs and trace services, as `prost-build`
+/// generates them from `opentelemetry-proto`, without the span links and
+/// dropped counts, which are not converted.
+pub mod proto {
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct ExportLogsServiceRequest {
+        #[prost(message, repeated, tag = "1")]
+        pub resource_logs: Vec<ResourceLogs>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct ResourceLogs {
+        #[prost(message, optional, tag = "1")]
+        pub resource: Option<Resource>,
+        #[prost(message, repeated, tag = "2")]
+        pub scope_logs: Vec<ScopeLogs>,
+        #[prost(string, tag = "3")]
+        pub schema_url: String,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct ScopeLogs {
+        #[prost(message, optional, tag = "1")]
+        pub scope: Option<InstrumentationScope>,
+        #[prost(message, repeated, tag = "2")]
+        pub log_records: Vec<LogRecord>,
+        #[prost(string, tag = "3")]
+        pub schema_url: String,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct LogRecord {
+        #[prost(fixed64, tag = "1")]
+        pub time_unix_nano: u64,
+        #[prost(fixed64, tag = "11")]
+        pub observed_time_unix_nano: u64,
+        /// A `SeverityNumber`, from 1 for `TRACE` to 24 for `FATAL4`, or 0
+        /// if unspecified.
+        #[prost(int32, tag = "2")]
+        pub severity_number: i32,
+        #[prost(string, tag = "3")]
+        pub severity_text: String,
+        #[prost(message, optional, tag = "5")]
+        pub body: Option<AnyValue>,
+        #[prost(message, repeated, tag = "6")]
+        pub attributes: Vec<KeyValue>,
+        #[prost(fixed32, tag = "8")]
+        pub flags: u32,
+        #[prost(bytes = "vec", tag = "9")]
+        pub trace_id: Vec<u8>,
+        #[prost(bytes = "vec", tag = "10")]
+        pub span_id: Vec<u8>,
+        #[prost(string, tag = "12")]
+        pub event_name: String,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct ExportTraceServiceRequest {
+        #[prost(message, repeated, tag = "1")]
+        pub resource_spans: Vec<ResourceSpans>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct ResourceSpans {
+        #[prost(message, optional, tag = "1")]
+        pub resource: Option<Resource>,
+        #[prost(message, repeated, tag = "2")]
+        pub scope_spans: Vec<ScopeSpans>,
+        #[prost(string, tag = "3")]
+        pub schema_url: String,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct ScopeSpans {
+        #[prost(message, optional, tag = "1")]
+        pub scope: Option<InstrumentationScope>,
+        #[prost(message, repeated, tag = "2")]
+        pub spans: Vec<Span>,
+        #[prost(string, tag = "3")]
+        pub schema_url: String,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Span {
+        #[prost(bytes = "vec", tag = "1")]
+        pub trace_id: Vec<u8>,
+        #[prost(bytes = "vec", tag = "2")]
+        pub span_id: Vec<u8>,
+        #[prost(string, tag = "3")]
+        pub trace_state: String,
+        #[prost(bytes = "vec", tag = "4")]
+        pub parent_span_id: Vec<u8>,
+        #[prost(fixed32, tag = "16")]
+        pub flags: u32,
+        #[prost(string, tag = "5")]
+        pub name: String,
+        #[prost(enumeration = "span::SpanKind", tag = "6")]
+        pub kind: i32,
+        #[prost(fixed64, tag = "7")]
+        pub start_time_unix_nano: u64,
+        #[prost(fixed64, tag = "8")]
+        pub end_time_unix_nano: u64,
+        #[prost(message, repeated, tag = "9")]
+        pub attributes: Vec<KeyValue>,
+        #[prost(message, repeated, tag = "11")]
+        pub events: Vec<span::Event>,
+        #[prost(message, optional, tag = "15")]
+        pub status: Option<Status>,
+    }
+
+    pub mod span {
+        #[derive(Clone, PartialEq, ::prost::Message)]
+        pub struct Event {
+            #[prost(fixed64, tag = "1")]
+            pub time_unix_nano: u64,
+            #[prost(string, tag = "2")]
+            pub name: String,
+            #[prost(message, repeated, tag = "3")]
+            pub attributes: Vec<super::KeyValue>,
+        }
+
+        #[derive(
+            Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration,
+        )]
+        #[repr(i32)]
+        pub enum SpanKind {
+            Unspecified = 0,
+            Internal = 1,
+            Server = 2,
+            Client = 3,
+            Producer = 4,
+            Consumer = 5,
+        }
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Status {
+        #[prost(string, tag = "2")]
+        pub message: String,
+        #[prost(enumeration = "status::StatusCode", tag = "3")]
+        pub code: i32,
+    }
+
+    pub mod status {
+        #[derive(
+            Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration,
+        )]
+        #[repr(i32)]
+        pub enum StatusCode {
+            Unset = 0,
+            Ok = 1,
+            Error = 2,
+        }
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Resource {
+        #[prost(message, repeated, tag = "1")]
+        pub attributes: Vec<KeyValue>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct InstrumentationScope {
+        #[prost(string, tag = "1")]
+        pub name: String,
+        #[prost(string, tag = "2")]
+        pub version: String,
+        #[prost(message, repeated, tag = "3")]
+        pub attributes: Vec<KeyValue>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct KeyValue {
+        #[prost(string, tag = "1")]
+        pub key: String,
+        #[prost(message, optional, tag = "2")]
+        pub value: Option<AnyValue>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct AnyValue {
+        #[prost(oneof = "any_value::Value", tags = "1, 2, 3, 4, 5, 6, 7")]
+        pub value: Option<any_value::Value>,
+    }
+
+    pub mod any_value {
+        #[derive(Clone, PartialEq, ::prost::Oneof)]
+        pub enum Value {
+            #[prost(string, tag = "1")]
+            StringValue(String),
+            #[prost(bool, tag = "2")]
+            BoolValue(bool),
+            #[prost(int64, tag = "3")]
+            IntValue(i64),
+            #[prost(double, tag = "4")]
+            DoubleValue(f64),
+            #[prost(message, tag = "5")]
+            ArrayValue(super::ArrayValue),
+            #[prost(message, tag = "6")]
+            KvlistValue(super::KeyValueList),
+            #[prost(bytes = "vec", tag = "7")]
+            BytesValue(Vec<u8>),
+        }
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct ArrayValue {
+        #[prost(message, repeated, tag = "1")]
+        pub values: Vec<AnyValue>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct KeyValueList {
+        #[prost(message, repeated, tag = "1")]
+        pub values: Vec<KeyValue>,
+    }
+}
+
+use proto::{
+    any_value::Value, span::SpanKind, status::StatusCode, AnyValue, ExportLogsServiceRequest,
+    ExportTraceServiceRequest, KeyValue,
+};
+
+/// A log record, converted to an event.
+#[derive(Debug, Clone)]
+pub struct LogEvent {
+    /// When the event occurred or, if the record does not say, when it was
+    /// observed.
+    pub timestamp: Option<SystemTime>,
+    /// The trace the event occurred in.
+    pub trace_id: Option<[u8; 16]>,
+    /// The attributes of the resource that emitted the event, such as its
+    /// `service.name`.
+    pub resource: SerializeRecord<'static>,
+    pub event: SerializeEvent<'static>,
+}
+
+/// A span, converted to its attributes and the values of its fields.
+#[derive(Debug, Clone)]
+pub struct SpanRecord {
+    pub id: SerializeId,
+    pub trace_id: Option<[u8; 16]>,
+    pub start: Option<SystemTime>,
+    pub end: Option<SystemTime>,
+    /// The attributes of the resource that emitted the span, such as its
+    /// `service.name`.
+    pub resource: SerializeRecord<'static>,
+    pub attributes: SerializeAttributes<'static>,
+    /// The values of the fields declared by the attributes.
+    pub values: SerializeRecord<'static>,
+    /// The events of the span, whose parent is the span.
+    pub events: Vec<LogEvent>,
+}
+
+/// Decodes the body of an OTLP logs export request.
+pub fn decode_logs(body: &[u8]) -> Result<Vec<LogEvent>, prost::DecodeError> {
+    ExportLogsServiceRequest::decode(body).map(logs)
+}
+
+/// Decodes the body of an OTLP trace export request.
+pub fn decode_spans(body: &[u8]) -> Result<Vec<SpanRecord>, prost::DecodeError> {
+    ExportTraceServiceRequest::decode(body).map(spans)
+}
+
+/// Converts every log record of `request`, in order.
+pub fn logs(request: ExportLogsServiceRequest) -> Vec<LogEvent> {
+    let mut events = Vec::new();
+    for resource_logs in request.resource_logs {
+        let resource = resource(resource_logs.resource);
+        for scope_logs in resource_logs.scope_logs {
+            let scope = scope_logs.scope.map(|scope| scope.name).unwrap_or_default();
+            for record in scope_logs.log_records {
+                let time = match record.time_unix_nano {
+                    0 => record.observed_time_unix_nano,
+                    time => time,
+                };
+                let event = event(
+                    &scope,
+                    record.event_name,
+                    level(record.severity_number, &record.severity_text),
+                    record.body.map(SerializeValue::from),
+                    record.attributes,
+                    span_id(&record.span_id),
+                );
+                events.push(LogEvent {
+                    timestamp: timestamp(time),
+                    trace_id: trace_id(&record.trace_id),
+                    resource: resource.clone(),
+                    event,
+                });
+            }
+        }
+    }
+    events
+}
+
+/// Converts every span of `request`, in order, skipping those without a
+/// valid id.
+pub fn spans(request: ExportTraceServiceRequest) -> Vec<SpanRecord> {
+    let mut spans = Vec::new();
+    for resource_spans in request.resource_spans {
+        let resource = resource(resource_spans.resource);
+        for scope_spans in resource_spans.scope_spans {
+            let scope = scope_spans
+                .scope
+                .map(|scope| scope.name)
+                .unwrap_or_default();
+            spans.extend(
+                scope_spans
+                    .spans
+                    .into_iter()
+                    .filter_map(|span| self::span(&scope, &resource, span)),
+            );
+        }
+    }
+    spans
+}
+
+fn span(scope: &str, resource: &SerializeRecord<'static>, span: proto::Span) -> Option<SpanRecord> {
+    let id = span_id(&span.span_id)?;
+    let trace_id = trace_id(&span.trace_id);
+    let kind = span.kind();
+    let status = span.status.as_ref().map(|status| status.code());
+    let mut attributes = Attributes::new(span.attributes);
+
+    let mut builder = OwnedAttributesBuilder::new(span.name)
+        .target(attributes.target.take().unwrap_or_else(|| scope.to_owned()))
+        .level(attributes.level.unwrap_or(SerializeLevel::Info));
+    if let Some(module_path) = attributes.module_path.take() {
+        builder = builder.module_path(module_path);
+    }
+    if let Some((file, line)) = attributes.location.take() {
+        builder = builder.location(file, line);
+    }
+    builder = match span_id(&span.parent_span_id) {
+        Some(parent) => builder.parent(parent),
+        None => builder.root(),
+    };
+
+    let mut values = attributes.fields;
+    let kind = match kind {
+        SpanKind::Unspecified => None,
+        SpanKind::Internal => Some("internal"),
+        SpanKind::Server => Some("server"),
+        SpanKind::Client => Some("client"),
+        SpanKind::Producer => Some("producer"),
+        SpanKind::Consumer => Some("consumer"),
+    };
+    if let Some(kind) = kind {
+        values.push(("otel.kind".into(), SerializeValue::Str(kind.into())));
+    }
+    let status = match status {
+        Some(StatusCode::Ok) => Some("OK"),
+        Some(StatusCode::Error) => Some("ERROR"),
+        Some(StatusCode::Unset) | None => None,
+    };
+    if let Some(status) = status {
+        values.push((
+            "otel.status_code".into(),
+            SerializeValue::Str(status.into()),
+        ));
+    }
+    for (name, _) in &values {
+        builder = builder.field(name.clone());
+    }
+
+    let events = span
+        .events
+        .into_iter()
+        .map(|event| LogEvent {
+            timestamp: timestamp(event.time_unix_nano),
+            trace_id,
+            resource: resource.clone(),
+            event: self::event(
+                scope,
+                String::new(),
+                None,
+                Some(SerializeValue::Str(event.name.into())),
+                event.attributes,
+                Some(id.clone()),
+            ),
+        })
+        .collect();
+
+    Some(SpanRecord {
+        id,
+        trace_id,
+        start: timestamp(span.start_time_unix_nano),
+        end: timestamp(span.end_time_unix_nano),
+        resource: resource.clone(),
+        attributes: builder.build(),
+        values: values.into_iter().collect(),
+        events,
+    })
+}
+
+fn event(
+    scope: &str,
+    name: String,
+    level: Option<SerializeLevel>,
+    message: Option<SerializeValue<'static>>,
+    attributes: Vec<KeyValue>,
+    parent: Option<SerializeId>,
+) -> SerializeEvent<'static> {
+    let attributes = Attributes::new(attributes);
+    let mut builder = OwnedEventBuilder::new()
+        .target(attributes.target.unwrap_or_else(|| scope.to_owned()))
+        .level(level.or(attributes.level).unwrap_or(SerializeLevel::Info));
+    if let Some(module_path) = attributes.module_path {
+        builder = builder.module_path(module_path);
+    }
+    if !name.is_empty() {
+        builder = builder.name(name);
+    } else if let Some((file, line)) = &attributes.location {
+        builder = builder.name(format!("event {file}:{line}"));
+    }
+    if let Some((file, line)) = attributes.location {
+        builder = builder.location(file, line);
+    }
+    if let Some(message) = message {
+        builder = builder.field("message", message);
+    }
+    for (name, value) in attributes.fields {
+        builder = builder.field(name, value);
+    }
+    if let Some(parent) = parent {
+        builder = builder.parent(parent);
+    }
+    builder.build()
+}
+
+/// Attributes, with those `tracing-opentelemetry` adds for the metadata
+/// taken out of the fields.
+struct Attributes {
+    target: Option<String>,
+    level: Option<SerializeLevel>,
+    module_path: Option<String>,
+    location: Option<(String, u32)>,
+    fields: Vec<(CowString<'static>, SerializeValue<'static>)>,
+}
+
+impl Attributes {
+    fn new(mut attributes: Vec<KeyValue>) -> Self {
+        let target = take(&mut attributes, "target", string);
+        let level = take(&mut attributes, "level", |value| {
+            string(value)?.parse().ok()
+        });
+        let module_path = take(&mut attributes, "code.namespace", string);
+        let file = find(&attributes, "code.filepath", string);
+        let line = find(&attributes, "code.lineno", |value| match value {
+            Value::IntValue(line) => u32::try_from(*line).ok(),
+            _ => None,
+        });
+        let location = match (file, line) {
+            (Some((i, file)), Some((j, line))) => {
+                attributes.remove(i.max(j));
+                attributes.remove(i.min(j));
+                Some((file, line))
+            }
+            _ => None,
+        };
+        let fields = attributes
+            .into_iter()
+            .map(|kv| {
+                let value = kv
+                    .value
+                    .map_or(SerializeValue::Unknown, SerializeValue::from);
+                (kv.key.into(), value)
+            })
+            .collect();
+        Attributes {
+            target,
+            level,
+            module_path,
+            location,
+            fields,
+        }
+    }
+}
+
+/// Finds the attribute `key`, if `convert` accepts its value.
+fn find<T>(
+    attributes: &[KeyValue],
+    key: &str,
+    convert: impl Fn(&Value) -> Option<T>,
+) -> Option<(usize, T)> {
+    attributes.iter().enumerate().find_map(|(i, kv)| {
+        let value = kv.value.as_ref()?.value.as_ref()?;
+        (kv.key == key)
+            .then(|| convert(value))
+            .flatten()
+            .map(|value| (i, value))
+    })
+}
+
+/// Removes the attribute `key`, if `convert` accepts its value.
+fn take<T>(
+    attributes: &mut Vec<KeyValue>,
+    key: &str,
+    convert: impl Fn(&Value) -> Option<T>,
+) -> Option<T> {
+    let (i, value) = find(attributes, key, convert)?;
+    attributes.remove(i);
+    Some(value)
+}
+
+fn string(value: &Value) -> Option<String> {
+    match value {
+        Value::StringValue(s) => Some(s.clone()),
+        _ => None,
+    }
+}
+
+fn resource(resource: Option<proto::Resource>) -> SerializeRecord<'static> {
+    resource
+        .into_iter()
+        .flat_map(|resource| resource.attributes)
+        .map(|kv| {
+            let value = kv
+                .value
+                .map_or(SerializeValue::Unknown, SerializeValue::from);
+            (CowString::from(kv.key), value)
+        })
+        .collect()
+}
+
+/// Converts a value, debug-formatting those with no equivalent, and leaving
+/// one without a value [`Unknown`](SerializeValue::Unknown).
+impl From<AnyValue> for SerializeValue<'static> {
+    fn from(value: AnyValue) -> Self {
+        match value.value {
+            Some(Value::StringValue(s)) => SerializeValue::Str(s.into()),
+            Some(Value::BoolValue(x)) => SerializeValue::Bool(x),
+            Some(Value::IntValue(x)) => SerializeValue::I64(x),
+            Some(Value::DoubleValue(x)) => SerializeValue::F64(x),
+            Some(value) => {
+                let mut debug = String::new();
+                write_debug(&mut debug, Some(&value));
+                SerializeValue::Debug(DebugRecord::De(debug.into()))
+            }
+            None => SerializeValue::Unknown,
+        }
+    }
+}
+
+/// Writes `value` the way `{:?}` would write the Rust value closest to it.
+fn write_debug(out: &mut String, value: Option<&Value>) {
+    // Writing to a `String` cannot fail.
+    let _ = match value {
+        Some(Value::StringValue(s)) => write!(out, "{s:?}"),
+        Some(Value::BoolValue(x)) => write!(out, "{x:?}"),
+        Some(Value::IntValue(x)) => write!(out, "{x:?}"),
+        Some(Value::DoubleValue(x)) => write!(out, "{x:?}"),
+        Some(Value::BytesValue(bytes)) => write!(out, "{bytes:?}"),
+        Some(Value::ArrayValue(array)) => {
+            out.push('[');
+            for (i, value) in array.values.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_debug(out, value.value.as_ref());
+            }
+            out.push(']');
+            Ok(())
+        }
+        Some(Value::KvlistValue(list)) => {
+            out.push('{');
+            for (i, kv) in list.values.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                let _ = write!(out, "{:?}: ", kv.key);
+                write_debug(
+                    out,
+                    kv.value.as_ref().and_then(|value| value.value.as_ref()),
+                );
+            }
+            out.push('}');
+            Ok(())
+        }
+        None => write!(out, "None"),
+    };
+}
+
+/// Returns the level of a severity number or, if it is unspecified, text.
+fn level(number: i32, text: &str) -> Option<SerializeLevel> {
+    match number {
+        1..=4 => Some(SerializeLevel::Trace),
+        5..=8 => Some(SerializeLevel::Debug),
+        9..=12 => Some(SerializeLevel::Info),
+        13..=16 => Some(SerializeLevel::Warn),
+        17..=24 => Some(SerializeLevel::Error),
+        _ if text.eq_ignore_ascii_case("fatal") => Some(SerializeLevel::Error),
+        _ => text.parse().ok(),
+    }
+}
+
+fn timestamp(nanos: u64) -> Option<SystemTime> {
+    (nanos != 0).then(|| UNIX_EPOCH + Duration::from_nanos(nanos))
+}
+
+fn trace_id(bytes: &[u8]) -> Option<[u8; 16]> {
+    <[u8; 16]>::try_from(bytes).ok().filter(|id| *id != [0; 16])
+}
+
+fn span_id(bytes: &[u8]) -> Option<SerializeId> {
+    let id = u64::from_be_bytes(bytes.try_into().ok()?);
+    Some(SerializeId {
+        id: NonZeroU64::new(id)?,
+    })
+}
@@ -0,0 +1,99 @@
+//! Sending what is left in a sink when the firmware panics or faults.
+//!
+//! A device that buffers its trace data, in a ring buffer or a
+//! [`FramedWriter`](crate::embedded_io::FramedWriter)'s sink, loses the
+//! messages that matter most when it crashes: the ones leading up to the
+//! crash. Registering the sink here lets the panic handler (or a HardFault
+//! handler) push them out, followed by a [`TracingWire::Panic`] saying what
+//! went wrong, before the device resets:
+//!
+//! ```rust,ignore
+//! use tracing_serde_structured::panic_flush;
+//!
+//! // At startup, once the sink exists.
+//! panic_flush::register(SINK.init(sink));
+//!
+//! #[panic_handler]
+//! fn panic(info: &core::panic::PanicInfo<'_>) -> ! {
+//!     panic_flush::on_panic(info);
+//!     cortex_m::peripheral::SCB::sys_reset()
+//! }
+//!
+//! #[cortex_m_rt::exception]
+//! unsafe fn HardFault(_: &cortex_m_rt::ExceptionFrame) -> ! {
+//!     panic_flush::on_fault("HardFault");
+//!     cortex_m::peripheral::SCB::sys_reset()
+//! }
+//! ```
+//!
+//! The sink is taken out of the registry before it is flushed, so a panic
+//! while flushing does not flush again. The registry is guarded with
+//! [`critical_section`], so the target (or the HAL) must provide an
+//! implementation of it.
+
+use core::{cell::Cell, panic::PanicInfo};
+
+use critical_section::Mutex;
+
+use crate::{
+    wire::{SerializePanic, TracingWire},
+    CowString, DebugRecord, Live,
+};
+
+/// A sink that can send everything it still holds, in a hurry.
+pub trait FinalFlush {
+    /// Send anything buffered, then `msg`.
+    ///
+    /// This is called from a panic or fault handler, inside a critical
+    /// section, so it must not wait for interrupts or for other tasks. There
+    /// is nowhere left to report errors to, so they are ignored.
+    fn final_flush(&mut self, msg: &TracingWire<'_, Live>);
+}
+
+type Sink = &'static mut (dyn FinalFlush + Send);
+
+static SINK: Mutex<Cell<Option<Sink>>> = Mutex::new(Cell::new(None));
+
+/// Make `sink` the one flushed by [`on_panic`] and [`on_fault`].
+///
+/// Returns the sink registered before, if any.
+pub fn register(sink: Sink) -> Option<Sink> {
+    critical_section::with(|cs| SINK.borrow(cs).replace(Some(sink)))
+}
+
+/// Remove the registered sink, e.g. to use it for something else.
+pub fn unregister() -> Option<Sink> {
+    critical_section::with(|cs| SINK.borrow(cs).take())
+}
+
+/// Flush the registered sink, ending with `panic`.
+///
+/// Returns `false` if no sink was registered, or it was flushed already.
+pub fn flush(panic: SerializePanic<'_, Live>) -> bool {
+    critical_section::with(|cs| match SINK.borrow(cs).take() {
+        Some(sink) => {
+            sink.final_flush(&TracingWire::Panic(panic));
+            true
+        }
+        None => false,
+    })
+}
+
+/// Flush the registered sink with the message and location of a panic.
+pub fn on_panic(info: &PanicInfo<'_>) -> bool {
+    let location = info.location();
+    flush(SerializePanic {
+        message: DebugRecord::Ser(&format_args!("{}", info.message())),
+        file: location.map(|l| CowString::Borrowed(l.file())),
+        line: location.map(|l| l.line()),
+    })
+}
+
+/// Flush the registered sink after a fault, such as `"HardFault"`.
+pub fn on_fault(reason: &str) -> bool {
+    flush(SerializePanic {
+        message: DebugRecord::De(CowString::Borrowed(reason)),
+        file: None,
+        line: None,
+    })
+}
@@ -0,0 +1,135 @@
+//! Convenience functions for encoding to and decoding from [postcard].
+//!
+//! These work without `std`, and make it easy to serialize any of the
+//! `Serialize*` types (or a [`TracingWire`](crate::wire::TracingWire)
+//! message) into a fixed buffer:
+//!
+//! ```rust
+//! use tracing_serde_structured::{postcard, SerializeLevel};
+//!
+//! let mut buf = [0u8; 8];
+//! let used = postcard::serialize_into(&SerializeLevel::Warn, &mut buf).unwrap();
+//! assert_eq!(postcard::from_bytes::<SerializeLevel>(used).unwrap(), SerializeLevel::Warn);
+//!
+//! let vec: heapless::Vec<u8, 8> = postcard::to_heapless_vec(&SerializeLevel::Warn).unwrap();
+//! assert_eq!(vec.as_slice(), [3]);
+//! ```
+//!
+//! [`with_scratch`] encodes into a reusable [`SerializeScratch`] buffer
+//! instead, for hot paths that write each message out before encoding the
+//! next.
+//!
+//! The message types also have a `wire_size` method, which computes the
+//! exact encoded size without encoding anything, so a producer can pick a
+//! buffer, or drop a message that would not fit, up front.
+//!
+//! [postcard]: https://docs.rs/postcard
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    wire::{SerializeAttributesRef, SerializeEventRef, TracingWire},
+    Form, SerializeAttributes, SerializeEvent, SerializeMetadata, SerializeRecord,
+};
+
+#[cfg(any(feature = "std", target_has_atomic = "8"))]
+use crate::scratch::SerializeScratch;
+
+pub use ::postcard::Error;
+
+/// Serializes `value` into `buf`, returning the used part of the buffer.
+///
+/// Fails with [`Error::SerializeBufferFull`] if `buf` is too small.
+pub fn serialize_into<'b, T>(value: &T, buf: &'b mut [u8]) -> Result<&'b mut [u8], Error>
+where
+    T: Serialize + ?Sized,
+{
+    ::postcard::to_slice(value, buf)
+}
+
+/// Serializes `value` into a `heapless::Vec` with capacity `N`.
+///
+/// Fails with [`Error::SerializeBufferFull`] if the message is longer
+/// than `N` bytes.
+pub fn to_heapless_vec<T, const N: usize>(value: &T) -> Result<heapless::Vec<u8, N>, Error>
+where
+    T: Serialize + ?Sized,
+{
+    ::postcard::to_vec(value)
+}
+
+/// Deserializes a value from `bytes`, borrowing strings from it.
+///
+/// Trailing bytes after the value are ignored.
+pub fn from_bytes<'a, T>(bytes: &'a [u8]) -> Result<T, Error>
+where
+    T: Deserialize<'a>,
+{
+    ::postcard::from_bytes(bytes)
+}
+
+/// Serializes `value` into a [`SerializeScratch`] buffer, and passes the used
+/// part of the buffer to `f`.
+///
+/// Fails with [`Error::SerializeBufferFull`] if the message is longer than
+/// [`SCRATCH_SIZE`](crate::scratch::SCRATCH_SIZE), or if no scratch buffer is
+/// available (see [`SerializeScratch::take`]).
+#[cfg(any(feature = "std", target_has_atomic = "8"))]
+pub fn with_scratch<T, R>(value: &T, f: impl FnOnce(&[u8]) -> R) -> Result<R, Error>
+where
+    T: Serialize + ?Sized,
+{
+    let mut scratch = SerializeScratch::take().ok_or(Error::SerializeBufferFull)?;
+    let used = ::postcard::to_slice(value, &mut scratch)?;
+    Ok(f(used))
+}
+
+/// Computes the exact number of bytes [`serialize_into`] would use for `value`.
+pub fn serialized_size<T>(value: &T) -> Result<usize, Error>
+where
+    T: Serialize + ?Sized,
+{
+    ::postcard::experimental::serialized_size(value)
+}
+
+macro_rules! impl_wire_size {
+    (forms: $($ty:ty),* $(,)?) => {
+        $(
+            impl<F: Form> $ty {
+                /// The exact size of this message encoded with postcard.
+                ///
+                /// Returns `usize::MAX` if it cannot be encoded with postcard,
+                /// so that it never fits in a buffer.
+                pub fn wire_size(&self) -> usize {
+                    serialized_size(self).unwrap_or(usize::MAX)
+                }
+            }
+        )*
+    };
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl $ty {
+                /// The exact size of this message encoded with postcard.
+                ///
+                /// Returns `usize::MAX` if it cannot be encoded with postcard,
+                /// so that it never fits in a buffer.
+                pub fn wire_size(&self) -> usize {
+                    serialized_size(self).unwrap_or(usize::MAX)
+                }
+            }
+        )*
+    };
+}
+
+impl_wire_size!(
+    forms: TracingWire<'_, F>,
+    SerializeEventRef<'_, F>,
+    SerializeEvent<'_, F>,
+    SerializeRecord<'_, F>,
+);
+
+impl_wire_size!(
+    SerializeAttributesRef,
+    SerializeAttributes<'_>,
+    SerializeMetadata<'_>,
+);
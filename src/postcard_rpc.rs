@@ -0,0 +1,77 @@
+//! Topics and endpoints for sending trace data with [postcard-rpc].
+//!
+//! Devices that already talk to a host through postcard-rpc can publish
+//! their trace data on [`WireTopic`], using the same transport as everything
+//! else, and let the host change how much they report with
+//! [`SetMaxLevelEndpoint`]:
+//!
+//! ```rust
+//! use postcard_rpc::{Endpoint, Topic};
+//! use tracing_serde_structured::postcard_rpc::{SetMaxLevelEndpoint, WireTopic};
+//!
+//! assert_eq!(<WireTopic>::PATH, "tracing/wire");
+//! assert_eq!(SetMaxLevelEndpoint::PATH, "tracing/max_level/set");
+//! ```
+//!
+//! [`WireTopic`] carries borrowed messages, so a device can publish events
+//! as they happen without copying them, e.g. with
+//! `sender.publish::<WireTopic<'_, Live>>(seq, &msg)`. The host subscribes to
+//! the same topic in the default [`Detached`] form.
+//!
+//! [`Detached`]: crate::Detached
+//!
+//! A maximum level of `None` turns reporting off entirely. These marker types
+//! are not part of any `topics!` or `endpoints!` list; add their paths and
+//! keys to a device's own lists to have them show up in its schema report.
+//!
+//! [postcard-rpc]: https://docs.rs/postcard-rpc
+
+use core::marker::PhantomData;
+
+use ::postcard_rpc::{Endpoint, Key, Topic};
+
+use crate::{wire::TracingWire, Detached, Form, SerializeLevel};
+
+/// Trace data published by a device, on `"tracing/wire"`.
+///
+/// Both forms of the message have the same key.
+#[derive(Debug)]
+pub struct WireTopic<'a, F: Form = Detached> {
+    _msg: PhantomData<TracingWire<'a, F>>,
+}
+
+impl<'a, F: Form + postcard_schema::Schema> Topic for WireTopic<'a, F> {
+    type Message = TracingWire<'a, F>;
+    const PATH: &'static str = "tracing/wire";
+    const TOPIC_KEY: Key = Key::for_path::<TracingWire<'a, F>>(Self::PATH);
+}
+
+/// Sets the most verbose level a device reports, on `"tracing/max_level/set"`.
+///
+/// Responds with the previous maximum level.
+#[derive(Debug)]
+pub struct SetMaxLevelEndpoint {
+    _priv: (),
+}
+
+impl Endpoint for SetMaxLevelEndpoint {
+    type Request = Option<SerializeLevel>;
+    type Response = Option<SerializeLevel>;
+    const PATH: &'static str = "tracing/max_level/set";
+    const REQ_KEY: Key = Key::for_path::<Self::Request>(Self::PATH);
+    const RESP_KEY: Key = Key::for_path::<Self::Response>(Self::PATH);
+}
+
+/// Gets the most verbose level a device reports, on `"tracing/max_level/get"`.
+#[derive(Debug)]
+pub struct GetMaxLevelEndpoint {
+    _priv: (),
+}
+
+impl Endpoint for GetMaxLevelEndpoint {
+    type Request = ();
+    type Response = Option<SerializeLevel>;
+    const PATH: &'static str = "tracing/max_level/get";
+    const REQ_KEY: Key = Key::for_path::<Self::Request>(Self::PATH);
+    const RESP_KEY: Key = Key::for_path::<Self::Response>(Self::PATH);
+}
@@ -0,0 +1,237 @@
+//! A sink that stores events in a PostgreSQL table, with their fields as
+//! JSONB.
+//!
+//! The table uses the following schema, created on demand:
+//!
+//! ```sql
+//! CREATE TABLE tracing_events (
+//!     id        BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+//!     timestamp TIMESTAMPTZ NOT NULL,
+//!     level     TEXT NOT NULL,
+//!     target    TEXT NOT NULL,
+//!     name      TEXT NOT NULL,
+//!     parent    BIGINT,         -- the explicit parent span id, if any
+//!     fields    JSONB NOT NULL  -- {"name": value, ...}
+//! );
+//! CREATE INDEX ON tracing_events (timestamp);
+//! CREATE INDEX ON tracing_events USING GIN (fields);
+//! ```
+//!
+//! Field values are stored as the closest JSON value: numbers and booleans
+//! as such, strings and debug-formatted values as strings, and values of an
+//! unknown kind, as well as floats that are not finite, as `null`. So events can be queried with the JSONB
+//! operators:
+//!
+//! ```sql
+//! SELECT timestamp, target, fields->>'message'
+//! FROM tracing_events
+//! WHERE level = 'ERROR' AND fields @> '{"user_id": 42}';
+//! ```
+//!
+//! PostgreSQL cannot store zero bytes in `TEXT` or `JSONB` values, so they
+//! are replaced with `U+FFFD`.
+//!
+//! Events are buffered and written in batches of up to
+//! [`PostgresSink::with_batch_size`] rows, each with a single binary `COPY`,
+//! which is much faster than inserting rows one by one. Call
+//! [`PostgresSink::flush`] to write a partial batch; it is also written when
+//! the sink is dropped.
+
+use std::time::SystemTime;
+
+use postgres::{binary_copy::BinaryCopyInWriter, types::Type, Client, Error};
+use serde_json::{Map, Value as Json};
+
+use crate::{RecordMap, SerializeEvent, SerializeId, SerializeRecordFields, SerializeValue};
+
+/// An event, as it is written to the table.
+#[derive(Debug)]
+struct Row {
+    timestamp: SystemTime,
+    level: &'static str,
+    target: String,
+    name: String,
+    parent: Option<i64>,
+    fields: Json,
+}
+
+/// Stores events in a PostgreSQL table.
+pub struct PostgresSink {
+    client: Client,
+    copy: String,
+    batch_size: usize,
+    pending: Vec<Row>,
+}
+
+impl core::fmt::Debug for PostgresSink {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("PostgresSink")
+            .field("copy", &self.copy)
+            .field("batch_size", &self.batch_size)
+            .field("pending", &self.pending.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl PostgresSink {
+    /// The default number of rows written per batch.
+    pub const DEFAULT_BATCH_SIZE: usize = 1024;
+
+    /// The table used by default.
+    pub const DEFAULT_TABLE: &'static str = "tracing_events";
+
+    /// Use an existing client, creating the
+    /// [`DEFAULT_TABLE`](Self::DEFAULT_TABLE) if it does not exist.
+    pub fn new(client: Client) -> Result<Self, Error> {
+        Self::with_table(client, Self::DEFAULT_TABLE)
+    }
+
+    /// Use an existing client, storing events in `table`, which is created
+    /// if it does not exist.
+    ///
+    /// The name is quoted, so it is used as it is, case and all. It is
+    /// looked up in the client's `search_path`.
+    pub fn with_table(mut client: Client, table: &str) -> Result<Self, Error> {
+        let table = table.replace('"', "\"\"");
+        client.batch_execute(&format!(
+            "CREATE TABLE IF NOT EXISTS \"{table}\" (
+                id        BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+                timestamp TIMESTAMPTZ NOT NULL,
+                level     TEXT NOT NULL,
+                target    TEXT NOT NULL,
+                name      TEXT NOT NULL,
+                parent    BIGINT,
+                fields    JSONB NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS \"{table}_by_time\" ON \"{table}\" (timestamp);
+            CREATE INDEX IF NOT EXISTS \"{table}_by_fields\" ON \"{table}\" USING GIN (fields);"
+        ))?;
+        Ok(Self {
+            client,
+            copy: format!(
+                "COPY \"{table}\" (timestamp, level, target, name, parent, fields) \
+                 FROM STDIN (FORMAT binary)"
+            ),
+            batch_size: Self::DEFAULT_BATCH_SIZE,
+            pending: Vec::new(),
+        })
+    }
+
+    /// Set the maximum number of rows buffered before a batch is written.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Returns the underlying client, e.g. for running queries.
+    ///
+    /// Rows that have not been written yet are not visible to queries.
+    pub fn client(&mut self) -> &mut Client {
+        &mut self.client
+    }
+
+    /// Queue an event that occurred at `timestamp`, writing the batch if it
+    /// is full.
+    pub fn record_event(
+        &mut self,
+        timestamp: SystemTime,
+        event: &SerializeEvent<'_>,
+    ) -> Result<(), Error> {
+        let meta = &event.metadata;
+        let fields = match &event.fields {
+            SerializeRecordFields::De(map) => json_fields(map),
+            SerializeRecordFields::Ser(_) => {
+                let SerializeRecordFields::De(map) = event.fields.to_owned();
+                json_fields(&map)
+            }
+        };
+        self.pending.push(Row {
+            timestamp,
+            level: meta.level.as_str(),
+            target: text(meta.target.as_str()),
+            name: text(meta.name.as_str()),
+            parent: event.parent.as_ref().map(span_id),
+            fields,
+        });
+        if self.pending.len() >= self.batch_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// The number of rows waiting to be written.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Write all queued rows with a single `COPY`.
+    ///
+    /// The `COPY` writes all of the rows or none of them, so if it fails,
+    /// they all stay queued, and are written by the next flush.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let writer = self.client.copy_in(&self.copy)?;
+        let mut writer = BinaryCopyInWriter::new(
+            writer,
+            &[
+                Type::TIMESTAMPTZ,
+                Type::TEXT,
+                Type::TEXT,
+                Type::TEXT,
+                Type::INT8,
+                Type::JSONB,
+            ],
+        );
+        for row in &self.pending {
+            writer.write(&[
+                &row.timestamp,
+                &row.level,
+                &row.target,
+                &row.name,
+                &row.parent,
+                &row.fields,
+            ])?;
+        }
+        writer.finish()?;
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+impl Drop for PostgresSink {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+fn json_fields(fields: &RecordMap<'_>) -> Json {
+    let map: Map<String, Json> = fields
+        .iter()
+        .map(|(name, value)| (text(name.as_str()), json_value(value)))
+        .collect();
+    Json::Object(map)
+}
+
+fn json_value(value: &SerializeValue<'_>) -> Json {
+    match value {
+        SerializeValue::I64(x) => Json::from(*x),
+        SerializeValue::U64(x) => Json::from(*x),
+        // Not a number, or infinite, becomes `null`.
+        SerializeValue::F64(x) => Json::from(*x),
+        SerializeValue::Bool(x) => Json::Bool(*x),
+        SerializeValue::Unknown => Json::Null,
+        other => Json::String(text(&other.to_string())),
+    }
+}
+
+/// Returns `s` with zero bytes, which PostgreSQL refuses in text, replaced.
+fn text(s: &str) -> String {
+    s.replace('\0', "\u{fffd}")
+}
+
+fn span_id(id: &SerializeId) -> i64 {
+    // `BIGINT` is signed; keep the bit pattern of ids above `i64::MAX`.
+    id.id.get() as i64
+}
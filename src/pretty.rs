@@ -0,0 +1,373 @@
+//! Human readable, colored output for decoded streams.
+//!
+//! A [`Writer`] renders events and the lifecycle of spans one line each, in
+//! the style of `tracing-subscriber`'s `fmt` layer: levels are colored,
+//! events show the scope of spans they happened in, indented by how deeply
+//! nested that scope is, and fields start in a column of their own so that
+//! consecutive lines are easy to scan.
+//!
+//! ```rust
+//! use std::time::Duration;
+//! use tracing_serde_structured::{
+//!     builder::SerializeEventBuilder, pretty::Writer, wire::TracingWire, SerializeLevel,
+//! };
+//!
+//! let event = SerializeEventBuilder::new()
+//!     .target("app::net")
+//!     .level(SerializeLevel::Warn)
+//!     .field("message", "retrying")
+//!     .field("attempt", 2u64)
+//!     .build();
+//!
+//! let mut writer = Writer::new(Vec::new());
+//! writer.set_ansi(false);
+//! writer.set_message_width(12);
+//! writer
+//!     .write(Duration::from_millis(1500), &TracingWire::Event(event))
+//!     .unwrap();
+//!
+//! let out = String::from_utf8(writer.into_inner()).unwrap();
+//! assert_eq!(out, "    1.500000  WARN app::net: retrying     attempt=2\n");
+//! ```
+//!
+//! The writer keeps track of which spans exist and which are entered from
+//! the messages it is given, so it should see every message of a stream, in
+//! order, such as those of a [`host::Decoder`](crate::host::Decoder).
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{self, Write as _},
+    io,
+    time::Duration,
+};
+
+use crate::{
+    wire::TracingWire, CowString, SerializeEvent, SerializeLevel, SerializeRecord,
+    SerializeRecordFields, SerializeValue,
+};
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const ITALIC: &str = "\x1b[3m";
+
+fn level_color(level: SerializeLevel) -> &'static str {
+    match level {
+        SerializeLevel::Trace => "\x1b[35m",
+        SerializeLevel::Debug => "\x1b[34m",
+        SerializeLevel::Info => "\x1b[32m",
+        SerializeLevel::Warn => "\x1b[33m",
+        SerializeLevel::Error => "\x1b[31m",
+    }
+}
+
+/// A span the writer has seen created, and the values recorded for it.
+#[derive(Debug)]
+struct Span {
+    name: String,
+    level: SerializeLevel,
+    fields: BTreeMap<String, String>,
+}
+
+/// Writes messages to an [`io::Write`], one line each, for a person to read.
+#[derive(Debug)]
+pub struct Writer<W> {
+    inner: W,
+    ansi: bool,
+    message_width: usize,
+    spans: HashMap<u64, Span>,
+    /// The entered spans, innermost last.
+    stack: Vec<u64>,
+    line: String,
+}
+
+impl<W: io::Write> Writer<W> {
+    /// Create a writer with ANSI colors, and fields starting 32 columns
+    /// after the message.
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            ansi: true,
+            message_width: 32,
+            spans: HashMap::new(),
+            stack: Vec::new(),
+            line: String::new(),
+        }
+    }
+
+    /// Whether to color the output with ANSI escape codes, e.g. only if it
+    /// goes to a terminal.
+    pub fn set_ansi(&mut self, ansi: bool) {
+        self.ansi = ansi;
+    }
+
+    /// Pad messages to `width` characters, so that the fields after them
+    /// line up. Longer messages push their fields further right.
+    pub fn set_message_width(&mut self, width: usize) {
+        self.message_width = width;
+    }
+
+    /// Writes `msg`, which happened `since_start` after the stream began.
+    ///
+    /// Entering and exiting spans and recording their values only updates
+    /// the scope shown with later events, and messages that are neither
+    /// about events nor spans are skipped.
+    pub fn write(&mut self, since_start: Duration, msg: &TracingWire<'_>) -> io::Result<()> {
+        self.line.clear();
+        // Writing to a `String` cannot fail.
+        let _: fmt::Result = match msg {
+            TracingWire::Event(event) => self.event(since_start, event),
+            TracingWire::NewSpan { id, attributes } => {
+                let meta = &attributes.metadata;
+                self.spans.insert(
+                    id.id.get(),
+                    Span {
+                        name: meta.name.as_str().into(),
+                        level: meta.level,
+                        fields: BTreeMap::new(),
+                    },
+                );
+                self.lifecycle(since_start, meta.level, "new", id.id.get())
+            }
+            TracingWire::CloseSpan(id) => {
+                let level = self
+                    .spans
+                    .get(&id.id.get())
+                    .map_or(SerializeLevel::Trace, |s| s.level);
+                let written = self.lifecycle(since_start, level, "close", id.id.get());
+                self.spans.remove(&id.id.get());
+                self.stack.retain(|s| *s != id.id.get());
+                written
+            }
+            TracingWire::Record { span, values } => {
+                if let Some(span) = self.spans.get_mut(&span.id.get()) {
+                    for (k, v) in record_fields(values) {
+                        span.fields.insert(k.as_str().into(), v.to_string());
+                    }
+                }
+                return Ok(());
+            }
+            TracingWire::Enter(id) => {
+                self.stack.push(id.id.get());
+                return Ok(());
+            }
+            TracingWire::Exit(id) => {
+                if let Some(i) = self.stack.iter().rposition(|s| *s == id.id.get()) {
+                    self.stack.remove(i);
+                }
+                return Ok(());
+            }
+            TracingWire::Panic(panic) => self.panic(since_start, panic),
+            TracingWire::Dropped(dropped) => self.dropped(since_start, dropped),
+            _ => return Ok(()),
+        };
+        self.line.push('\n');
+        self.inner.write_all(self.line.as_bytes())
+    }
+
+    /// Writes an event that happened `since_start` after the stream began.
+    pub fn write_event(
+        &mut self,
+        since_start: Duration,
+        event: &SerializeEvent<'_>,
+    ) -> io::Result<()> {
+        self.line.clear();
+        let _ = self.event(since_start, event);
+        self.line.push('\n');
+        self.inner.write_all(self.line.as_bytes())
+    }
+
+    /// Writes a message decoded by a [`host::Decoder`](crate::host::Decoder).
+    #[cfg(feature = "host")]
+    pub fn write_message(&mut self, msg: &crate::host::Message) -> io::Result<()> {
+        self.write(msg.since_start, &msg.msg)
+    }
+
+    /// Flush the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    /// Returns a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Returns the underlying writer.
+    ///
+    /// The writer is not flushed first.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    fn style(&mut self, style: &str) {
+        if self.ansi {
+            self.line.push_str(style);
+        }
+    }
+
+    /// Writes the timestamp, level and indentation every line starts with.
+    fn prefix(&mut self, since_start: Duration, level: SerializeLevel) -> fmt::Result {
+        self.style(DIM);
+        write!(self.line, "{:>12.6}", since_start.as_secs_f64())?;
+        self.style(RESET);
+        self.line.push(' ');
+        self.style(level_color(level));
+        write!(self.line, "{level:>5}")?;
+        self.style(RESET);
+        write!(self.line, " {:indent$}", "", indent = 2 * self.stack.len())
+    }
+
+    fn event(&mut self, since_start: Duration, event: &SerializeEvent<'_>) -> fmt::Result {
+        let meta = &event.metadata;
+        self.prefix(since_start, meta.level)?;
+
+        for i in 0..self.stack.len() {
+            let Some(span) = self.spans.get(&self.stack[i]) else {
+                continue;
+            };
+            let scope = scope(span);
+            self.style(BOLD);
+            self.line.push_str(&scope);
+            self.style(RESET);
+            self.line.push(':');
+        }
+        if !self.stack.is_empty() {
+            self.line.push(' ');
+        }
+        self.style(DIM);
+        write!(self.line, "{}:", meta.target.as_str())?;
+        self.style(RESET);
+
+        let (message, fields) = split_message(&event.fields);
+        let message = message.map(|m| m.to_string()).unwrap_or_default();
+        if !fields.is_empty() {
+            write!(self.line, " {message:<width$}", width = self.message_width)?;
+        } else if !message.is_empty() {
+            write!(self.line, " {message}")?;
+        }
+        self.fields(fields.into_iter().map(|(k, v)| (k.as_str(), v)))
+    }
+
+    fn fields<'f, 'a: 'f>(
+        &mut self,
+        fields: impl Iterator<Item = (&'f str, &'f SerializeValue<'a>)>,
+    ) -> fmt::Result {
+        for (k, v) in fields {
+            self.line.push(' ');
+            self.style(ITALIC);
+            self.line.push_str(k);
+            self.style(RESET);
+            self.style(DIM);
+            self.line.push('=');
+            self.style(RESET);
+            match v {
+                SerializeValue::Str(s) => write!(self.line, "{:?}", s.as_str())?,
+                v => write!(self.line, "{v}")?,
+            }
+        }
+        Ok(())
+    }
+
+    fn lifecycle(
+        &mut self,
+        since_start: Duration,
+        level: SerializeLevel,
+        what: &str,
+        id: u64,
+    ) -> fmt::Result {
+        self.prefix(since_start, level)?;
+        self.style(DIM);
+        write!(self.line, "{what} span {id}")?;
+        self.style(RESET);
+        if let Some(span) = self.spans.get(&id) {
+            let scope = scope(span);
+            self.line.push(' ');
+            self.style(BOLD);
+            self.line.push_str(&scope);
+            self.style(RESET);
+        }
+        Ok(())
+    }
+
+    fn panic(
+        &mut self,
+        since_start: Duration,
+        panic: &crate::wire::SerializePanic<'_>,
+    ) -> fmt::Result {
+        self.prefix(since_start, SerializeLevel::Error)?;
+        self.style(BOLD);
+        self.style(level_color(SerializeLevel::Error));
+        self.line.push_str("panicked");
+        self.style(RESET);
+        if let (Some(file), Some(line)) = (&panic.file, panic.line) {
+            write!(self.line, " at {}:{line}", file.as_str())?;
+        }
+        write!(self.line, ": {}", panic.message)
+    }
+
+    fn dropped(
+        &mut self,
+        since_start: Duration,
+        dropped: &crate::wire::SerializeDropped,
+    ) -> fmt::Result {
+        self.prefix(since_start, SerializeLevel::Warn)?;
+        self.style(BOLD);
+        write!(self.line, "{} messages lost here", dropped.count)?;
+        self.style(RESET);
+        write!(
+            self.line,
+            " ({}, #{} to #{})",
+            dropped.reason, dropped.first_seq, dropped.last_seq
+        )
+    }
+}
+
+/// A span's name, with the values recorded for it, e.g. `connect{peer=1}`.
+fn scope(span: &Span) -> String {
+    let mut scope = span.name.clone();
+    if !span.fields.is_empty() {
+        scope.push('{');
+        for (i, (k, v)) in span.fields.iter().enumerate() {
+            if i > 0 {
+                scope.push(' ');
+            }
+            let _ = write!(scope, "{k}={v}");
+        }
+        scope.push('}');
+    }
+    scope
+}
+
+type Fields<'f, 'a> = Vec<(&'f CowString<'a>, &'f SerializeValue<'a>)>;
+
+/// Splits the `message` field off the others.
+fn split_message<'f, 'a>(
+    fields: &'f SerializeRecordFields<'a>,
+) -> (Option<&'f SerializeValue<'a>>, Fields<'f, 'a>) {
+    let map = match fields {
+        SerializeRecordFields::De(map) => map,
+        SerializeRecordFields::Ser(never) => match *never {},
+    };
+    let mut message = None;
+    let mut rest = Vec::new();
+    for (k, v) in map {
+        match k.as_str() {
+            "message" => message = Some(v),
+            _ => rest.push((k, v)),
+        }
+    }
+    (message, rest)
+}
+
+fn record_fields<'f, 'a>(values: &'f SerializeRecord<'a>) -> Fields<'f, 'a> {
+    match values {
+        SerializeRecord::De(map) => map.iter().collect(),
+        SerializeRecord::Ser(never) => match *never {},
+    }
+}
@@ -0,0 +1,150 @@
+//! [proptest] strategies generating owned events, metadata and values.
+//!
+//! The strategies stay within what `tracing` produces: names and targets
+//! look like Rust identifiers and module paths, text is printable UTF-8 of
+//! up to 32 characters, floats have up to three decimal places, events have
+//! up to [`MAX_FIELDS`] fields, all listed in their metadata, and spans and
+//! events are flagged as such. That makes the generated values round trip
+//! through every format exactly, so they can be compared after decoding:
+//!
+//! ```rust
+//! use proptest::prelude::*;
+//! use tracing_serde_structured::{proptest::event, SerializeEvent};
+//!
+//! proptest! {
+//!     fn events_round_trip(event in event()) {
+//!         let json = serde_json::to_string(&event).unwrap();
+//!         let decoded: SerializeEvent<'_> = serde_json::from_str(&json).unwrap();
+//!         prop_assert_eq!(serde_json::to_string(&decoded).unwrap(), json);
+//!     }
+//! }
+//! # events_round_trip();
+//! ```
+//!
+//! [proptest]: https://docs.rs/proptest
+
+use std::{collections::BTreeMap, num::NonZeroU64};
+
+use ::proptest::{collection, option, prelude::*};
+
+use crate::{
+    CowString, DebugRecord, SerializeAttributes, SerializeEvent, SerializeFieldSet, SerializeId,
+    SerializeLevel, SerializeMetadata, SerializeRecordFields, SerializeValue,
+};
+
+/// The most fields generated for an event or a span.
+pub const MAX_FIELDS: usize = 8;
+
+/// Any of the five levels.
+pub fn level() -> impl Strategy<Value = SerializeLevel> {
+    prop_oneof![
+        Just(SerializeLevel::Trace),
+        Just(SerializeLevel::Debug),
+        Just(SerializeLevel::Info),
+        Just(SerializeLevel::Warn),
+        Just(SerializeLevel::Error),
+    ]
+}
+
+/// A span id.
+pub fn id() -> impl Strategy<Value = SerializeId> {
+    any::<NonZeroU64>().prop_map(|id| SerializeId { id })
+}
+
+/// A name for a field, span or event, such as `request_id`.
+pub fn name() -> impl Strategy<Value = CowString<'static>> {
+    "[a-z][a-z0-9_]{0,15}".prop_map(CowString::from)
+}
+
+/// A target or module path, such as `my_app::net::tcp`.
+pub fn target() -> impl Strategy<Value = CowString<'static>> {
+    "[a-z][a-z0-9_]{0,11}(::[a-z][a-z0-9_]{0,11}){0,3}".prop_map(CowString::from)
+}
+
+/// Printable text, such as a message or a string value.
+pub fn text() -> impl Strategy<Value = CowString<'static>> {
+    "\\PC{0,32}".prop_map(CowString::from)
+}
+
+/// Floats are at most a billion, so that their decimal form is short enough
+/// for every parser to read back exactly.
+const MAX_MILLIS: i64 = 1_000_000_000_000;
+
+/// A value of any kind.
+pub fn value() -> impl Strategy<Value = SerializeValue<'static>> {
+    prop_oneof![
+        text().prop_map(|s| SerializeValue::Debug(DebugRecord::De(s))),
+        text().prop_map(SerializeValue::Str),
+        (-MAX_MILLIS..=MAX_MILLIS).prop_map(|millis| SerializeValue::F64(millis as f64 / 1000.0)),
+        any::<i64>().prop_map(SerializeValue::I64),
+        any::<u64>().prop_map(SerializeValue::U64),
+        any::<bool>().prop_map(SerializeValue::Bool),
+    ]
+}
+
+/// The fields of an event or a record, keyed by name.
+pub fn fields() -> impl Strategy<Value = BTreeMap<CowString<'static>, SerializeValue<'static>>> {
+    collection::btree_map(name(), value(), 0..=MAX_FIELDS)
+}
+
+/// The metadata of a span or an event.
+pub fn metadata() -> impl Strategy<Value = SerializeMetadata<'static>> {
+    (any::<bool>(), field_names()).prop_flat_map(|(is_span, names)| metadata_of(is_span, names))
+}
+
+/// The attributes of a new span.
+pub fn attributes() -> impl Strategy<Value = SerializeAttributes<'static>> {
+    (
+        field_names().prop_flat_map(|names| metadata_of(true, names)),
+        option::of(id()),
+        any::<bool>(),
+    )
+        .prop_map(|(metadata, parent, is_root)| SerializeAttributes {
+            metadata,
+            parent: parent.filter(|_| !is_root),
+            is_root,
+        })
+}
+
+/// An event, with metadata listing its fields.
+pub fn event() -> impl Strategy<Value = SerializeEvent<'static>> {
+    (fields(), option::of(id())).prop_flat_map(|(fields, parent)| {
+        let names = fields.keys().cloned().collect();
+        metadata_of(false, names).prop_map(move |metadata| SerializeEvent {
+            fields: SerializeRecordFields::De(fields.clone()),
+            metadata,
+            parent: parent.clone(),
+        })
+    })
+}
+
+fn field_names() -> impl Strategy<Value = Vec<CowString<'static>>> {
+    collection::btree_set(name(), 0..=MAX_FIELDS).prop_map(|names| names.into_iter().collect())
+}
+
+fn metadata_of(
+    is_span: bool,
+    names: Vec<CowString<'static>>,
+) -> impl Strategy<Value = SerializeMetadata<'static>> {
+    (
+        name(),
+        target(),
+        level(),
+        option::of(target()),
+        option::of("src/[a-z_]{1,12}\\.rs".prop_map(CowString::from)),
+        option::of(1..10_000u32),
+    )
+        .prop_map(
+            move |(name, target, level, module_path, file, line)| SerializeMetadata {
+                name,
+                target,
+                level,
+                module_path,
+                file,
+                line,
+                fields: SerializeFieldSet::De(names.clone()),
+                is_span,
+                is_event: !is_span,
+            },
+        )
+}
@@ -0,0 +1,328 @@
+//! Filtering decoded events.
+//!
+//! A [`Query`] is a predicate over events, built from simple ones, such as a
+//! range of levels or a pattern for targets, and combined with
+//! [`and`](Query::and), [`or`](Query::or) and `!`:
+//!
+//! ```rust
+//! use regex::Regex;
+//! use tracing_serde_structured::{
+//!     builder::SerializeEventBuilder, query::Query, SerializeLevel,
+//! };
+//!
+//! let query = Query::level(..=SerializeLevel::Warn)
+//!     .and(Query::target("app::net::*"))
+//!     .and(!Query::field("peer", "127.0.0.1"))
+//!     .or(Query::field_matches("message", Regex::new("^panic").unwrap()));
+//!
+//! let event = SerializeEventBuilder::new()
+//!     .target("app::net::tcp")
+//!     .level(SerializeLevel::Error)
+//!     .field("peer", "10.0.0.2")
+//!     .build();
+//! assert!(query.matches(&event));
+//! ```
+//!
+//! Whether an event happened within a span can only be told from the rest of
+//! the stream, so [`Query::in_span`] needs a [`Matcher`], which follows the
+//! spans of a stream of messages:
+//!
+//! ```rust,no_run
+//! use std::{fs::File, io::BufReader};
+//! use tracing_serde_structured::{host::Decoder, query::Query, time::TickRate};
+//!
+//! let file = BufReader::new(File::open("trace.bin").unwrap());
+//! let messages = Decoder::new(TickRate::MICROS).read(file).map(Result::unwrap);
+//! for msg in Query::in_span("request").filter(messages) {
+//!     println!("{:?}", msg.msg);
+//! }
+//! ```
+
+use std::{
+    collections::HashMap,
+    ops::{Bound, Not, RangeBounds},
+};
+
+use regex::Regex;
+
+use crate::{
+    glob::Glob, wire::TracingWire, SerializeEvent, SerializeLevel, SerializeRecordFields,
+    SerializeValue,
+};
+
+/// A predicate over events. See the [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct Query(Kind);
+
+#[derive(Debug, Clone)]
+enum Kind {
+    All,
+    Level(Bound<SerializeLevel>, Bound<SerializeLevel>),
+    Target(Glob),
+    Name(Glob),
+    Field(String, SerializeValue<'static>),
+    FieldMatches(String, Regex),
+    HasField(String),
+    InSpan(Glob),
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Not(Box<Query>),
+}
+
+impl Query {
+    /// Matches every event.
+    pub fn all() -> Self {
+        Query(Kind::All)
+    }
+
+    /// Matches events at levels within `range`.
+    ///
+    /// Levels compare as in `tracing`, with more verbose levels greater, so
+    /// `..=SerializeLevel::Warn` is `WARN` and `ERROR`.
+    pub fn level(range: impl RangeBounds<SerializeLevel>) -> Self {
+        Query(Kind::Level(
+            range.start_bound().cloned(),
+            range.end_bound().cloned(),
+        ))
+    }
+
+    /// Matches events whose target matches `pattern`, in which `*` stands for
+    /// any number of characters, and `?` for any one.
+    pub fn target(pattern: &str) -> Self {
+        Query(Kind::Target(Glob::new(pattern)))
+    }
+
+    /// Matches events whose callsite's name matches `pattern`, as with
+    /// [`Query::target`].
+    pub fn name(pattern: &str) -> Self {
+        Query(Kind::Name(Glob::new(pattern)))
+    }
+
+    /// Matches events with a field `name` equal to `value`.
+    ///
+    /// Integers are equal whatever their type, and strings equal values
+    /// recorded as `Debug` or `Display` that format as them.
+    pub fn field(name: &str, value: impl Into<SerializeValue<'static>>) -> Self {
+        Query(Kind::Field(name.into(), value.into()))
+    }
+
+    /// Matches events with a field `name` whose value, formatted as by its
+    /// `Display` implementation, matches `regex`.
+    pub fn field_matches(name: &str, regex: Regex) -> Self {
+        Query(Kind::FieldMatches(name.into(), regex))
+    }
+
+    /// Matches events with a field `name`, whatever its value.
+    pub fn has_field(name: &str) -> Self {
+        Query(Kind::HasField(name.into()))
+    }
+
+    /// Matches events within a span whose name matches `pattern`, as with
+    /// [`Query::target`], or within one of that span's children.
+    ///
+    /// Only a [`Matcher`] knows which spans an event is within; for
+    /// [`Query::matches`], events are never within any span.
+    pub fn in_span(pattern: &str) -> Self {
+        Query(Kind::InSpan(Glob::new(pattern)))
+    }
+
+    /// Matches events that match both `self` and `other`.
+    pub fn and(self, other: Query) -> Self {
+        Query(Kind::And(Box::new(self), Box::new(other)))
+    }
+
+    /// Matches events that match `self`, `other`, or both.
+    pub fn or(self, other: Query) -> Self {
+        Query(Kind::Or(Box::new(self), Box::new(other)))
+    }
+
+    /// Whether `event` matches, without knowing the spans it is within.
+    pub fn matches(&self, event: &SerializeEvent<'_>) -> bool {
+        self.eval(event, &[])
+    }
+
+    /// The events of `messages` that match, such as wire messages or those
+    /// of a [`host::Decoder`](crate::host::Decoder). See [`Matcher`].
+    pub fn filter<'a, I>(self, messages: I) -> impl Iterator<Item = I::Item>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<TracingWire<'a>>,
+    {
+        let mut matcher = Matcher::new(self);
+        messages
+            .into_iter()
+            .filter(move |msg| matcher.matches(msg.as_ref()))
+    }
+
+    /// `scope` holds the names of the spans the event is within.
+    fn eval(&self, event: &SerializeEvent<'_>, scope: &[&str]) -> bool {
+        let meta = &event.metadata;
+        match &self.0 {
+            Kind::All => true,
+            Kind::Level(start, end) => (*start, *end).contains(&meta.level),
+            Kind::Target(glob) => glob.matches(meta.target.as_str()),
+            Kind::Name(glob) => glob.matches(meta.name.as_str()),
+            Kind::Field(name, expected) => {
+                field(event, name).is_some_and(|value| values_match(value, expected))
+            }
+            Kind::FieldMatches(name, regex) => {
+                field(event, name).is_some_and(|value| regex.is_match(&value.to_string()))
+            }
+            Kind::HasField(name) => field(event, name).is_some(),
+            Kind::InSpan(glob) => scope.iter().any(|name| glob.matches(name)),
+            Kind::And(a, b) => a.eval(event, scope) && b.eval(event, scope),
+            Kind::Or(a, b) => a.eval(event, scope) || b.eval(event, scope),
+            Kind::Not(q) => !q.eval(event, scope),
+        }
+    }
+
+    fn needs_scope(&self) -> bool {
+        match &self.0 {
+            Kind::InSpan(_) => true,
+            Kind::And(a, b) | Kind::Or(a, b) => a.needs_scope() || b.needs_scope(),
+            Kind::Not(q) => q.needs_scope(),
+            _ => false,
+        }
+    }
+}
+
+/// Matches events that do not match the query.
+impl Not for Query {
+    type Output = Query;
+
+    fn not(self) -> Query {
+        Query(Kind::Not(Box::new(self)))
+    }
+}
+
+impl Default for Query {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+fn field<'e, 'a>(event: &'e SerializeEvent<'a>, name: &str) -> Option<&'e SerializeValue<'a>> {
+    match &event.fields {
+        SerializeRecordFields::De(map) => {
+            map.iter().find(|(k, _)| k.as_str() == name).map(|(_, v)| v)
+        }
+        SerializeRecordFields::Ser(never) => match *never {},
+    }
+}
+
+fn values_match(actual: &SerializeValue<'_>, expected: &SerializeValue<'_>) -> bool {
+    use SerializeValue::*;
+    match (actual, expected) {
+        (U64(a), I64(b)) | (I64(b), U64(a)) => i64::try_from(*a).is_ok_and(|a| a == *b),
+        (Debug(a), Str(b)) => a.to_string() == b.as_str(),
+        (a, b) => a == b,
+    }
+}
+
+/// Applies a [`Query`] to a stream of messages, following the spans they
+/// create, enter and close, to tell which spans each event is within.
+///
+/// An event is within the span given as its parent, or else the span most
+/// recently entered and not yet exited, and within all of that span's
+/// ancestors. Messages from several threads may be interleaved, so which
+/// spans are entered is only a guess for streams from multi-threaded
+/// programs.
+#[derive(Debug)]
+pub struct Matcher {
+    query: Query,
+    needs_scope: bool,
+    spans: HashMap<u64, Span>,
+    /// The entered spans, innermost last.
+    stack: Vec<u64>,
+    scope: Vec<String>,
+}
+
+#[derive(Debug)]
+struct Span {
+    name: String,
+    parent: Option<u64>,
+}
+
+impl Matcher {
+    pub fn new(query: Query) -> Self {
+        Self {
+            needs_scope: query.needs_scope(),
+            query,
+            spans: HashMap::new(),
+            stack: Vec::new(),
+            scope: Vec::new(),
+        }
+    }
+
+    pub fn query(&self) -> &Query {
+        &self.query
+    }
+
+    /// Whether `msg` is an event that matches. Other messages never match,
+    /// but update the spans that later events are within.
+    pub fn matches(&mut self, msg: &TracingWire<'_>) -> bool {
+        match msg {
+            TracingWire::Event(event) => return self.matches_event(event),
+            TracingWire::NewSpan { id, attributes } => {
+                let parent = match &attributes.parent {
+                    Some(parent) => Some(parent.id.get()),
+                    None if attributes.is_root => None,
+                    None => self.stack.last().copied(),
+                };
+                let span = Span {
+                    name: attributes.metadata.name.as_str().into(),
+                    parent,
+                };
+                self.spans.insert(id.id.get(), span);
+            }
+            TracingWire::Enter(id) => self.stack.push(id.id.get()),
+            TracingWire::Exit(id) => {
+                if let Some(i) = self.stack.iter().rposition(|s| *s == id.id.get()) {
+                    self.stack.remove(i);
+                }
+            }
+            TracingWire::CloseSpan(id) => {
+                self.spans.remove(&id.id.get());
+            }
+            _ => {}
+        }
+        false
+    }
+
+    /// Whether `event` matches, within the spans entered so far.
+    pub fn matches_event(&mut self, event: &SerializeEvent<'_>) -> bool {
+        if !self.needs_scope {
+            return self.query.eval(event, &[]);
+        }
+        self.scope.clear();
+        let mut next = event
+            .parent
+            .as_ref()
+            .map(|parent| parent.id.get())
+            .or_else(|| self.stack.last().copied());
+        // A span's ancestors are closed after it, so the chain ends, unless
+        // the stream reuses ids while they are still open.
+        while let Some(span) = next.and_then(|id| self.spans.get(&id)) {
+            if self.scope.len() > self.spans.len() {
+                break;
+            }
+            self.scope.push(span.name.clone());
+            next = span.parent;
+        }
+        let scope: Vec<&str> = self.scope.iter().map(String::as_str).collect();
+        self.query.eval(event, &scope)
+    }
+}
+
+impl<'a> AsRef<TracingWire<'a>> for TracingWire<'a> {
+    fn as_ref(&self) -> &TracingWire<'a> {
+        self
+    }
+}
+
+#[cfg(feature = "host")]
+impl AsRef<TracingWire<'static>> for crate::host::Message {
+    fn as_ref(&self) -> &TracingWire<'static> {
+        &self.msg
+    }
+}
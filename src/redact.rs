@@ -0,0 +1,394 @@
+//! Redacting personal data from field values.
+//!
+//! A [`Redactor`] replaces the values of fields with [`REDACTED`] when the
+//! field's name matches one of its patterns, or when the value looks like
+//! personal data to one of its detectors, whatever the field is called:
+//!
+//! ```rust
+//! use regex::Regex;
+//! use tracing_serde_structured::{
+//!     builder::SerializeEventBuilder,
+//!     redact::{Detector, Redactions, Redactor, REDACTED},
+//!     SerializeRecordFields, SerializeValue,
+//! };
+//!
+//! let redactor = Redactor::new()
+//!     .field("*.password")
+//!     .field_matches(Regex::new("(?i)token").unwrap())
+//!     .detect(Detector::Email)
+//!     .detect(Detector::CardNumber);
+//!
+//! let mut event = SerializeEventBuilder::new()
+//!     .field("db.password", "hunter2")
+//!     .field("message", "mail ferris@example.com about 4111 1111 1111 1111")
+//!     .field("status", 200u64)
+//!     .build();
+//! assert_eq!(redactor.redact_fields(&mut event.fields), 2);
+//!
+//! let SerializeRecordFields::De(map) = &event.fields else { unreachable!() };
+//! let value = |name: &str| map.iter().find(|(k, _)| k.as_str() == name).unwrap().1;
+//! assert_eq!(*value("db.password"), SerializeValue::from(REDACTED));
+//! assert_eq!(*value("message"), SerializeValue::from(REDACTED));
+//! assert_eq!(*value("status"), SerializeValue::U64(200));
+//! assert_eq!(redactor.redactions(), Redactions { by_name: 1, by_value: 1 });
+//! ```
+//!
+//! Values are redacted whole, rather than just the parts that were detected,
+//! so nothing is left to piece the data back together from.
+//!
+//! On the producer side, [`Redactor::visitor`] wraps the visitor that
+//! serializes an event's or span's fields, such as a
+//! [`SerdeMapVisitor`](crate::SerdeMapVisitor), so values are redacted as
+//! they are recorded, before they are copied anywhere:
+//!
+//! ```rust
+//! # use serde::Serializer;
+//! # use tracing_core::Event;
+//! use tracing_serde_structured::{redact::Redactor, SerdeMapVisitor};
+//!
+//! fn serialize_fields<S: Serializer>(
+//!     redactor: &Redactor,
+//!     event: &Event<'_>,
+//!     serializer: S,
+//! ) -> Result<S::Ok, S::Error> {
+//!     let mut visitor = redactor.visitor(SerdeMapVisitor::new(serializer.serialize_map(None)?));
+//!     event.record(&mut visitor);
+//!     visitor.into_inner().finish()
+//! }
+//! ```
+//!
+//! The redactor counts the values it redacts in either way, so that
+//! [`Redactor::redactions`] can be reported alongside the stream, e.g. to
+//! show that a policy is in effect.
+
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use regex::Regex;
+use tracing_core::field::{Field, Visit};
+
+use crate::{
+    glob::Glob, wire::TracingWire, DebugRecord, Form, RecordMap, SerializeRecord,
+    SerializeRecordFields, SerializeValue,
+};
+
+/// The value redacted values are replaced with.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Kinds of personal data a [`Redactor`] can find in values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Detector {
+    /// Email addresses, such as `ferris@example.com`.
+    Email,
+    /// Payment card numbers: 13 to 19 digits, optionally grouped with spaces
+    /// or dashes, that pass the Luhn check.
+    CardNumber,
+}
+
+impl Detector {
+    fn regex(self) -> Regex {
+        let pattern = match self {
+            Detector::Email => r"(?i)[a-z0-9._%+-]+@[a-z0-9-]+(\.[a-z0-9-]+)*\.[a-z]{2,}",
+            Detector::CardNumber => r"\b\d(?:[ -]?\d){12,18}\b",
+        };
+        Regex::new(pattern).expect("detector patterns are valid")
+    }
+}
+
+/// What a value must match to be redacted.
+#[derive(Debug, Clone)]
+struct ValuePattern {
+    regex: Regex,
+    /// Whether a match must also pass the Luhn check.
+    luhn: bool,
+}
+
+impl ValuePattern {
+    fn matches(&self, value: &str) -> bool {
+        if !self.luhn {
+            return self.regex.is_match(value);
+        }
+        self.regex.find_iter(value).any(|m| luhn(m.as_str()))
+    }
+}
+
+/// Whether the digits of `s`, ignoring anything else, pass the Luhn check.
+fn luhn(s: &str) -> bool {
+    let sum: u32 = s
+        .chars()
+        .rev()
+        .filter_map(|c| c.to_digit(10))
+        .enumerate()
+        .map(|(i, d)| match (i % 2, d * 2) {
+            (0, _) => d,
+            (_, d) if d > 9 => d - 9,
+            (_, d) => d,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+/// How many values a [`Redactor`] has redacted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Redactions {
+    /// Values redacted because of the name of their field.
+    pub by_name: u64,
+    /// Values redacted because of what they contain.
+    pub by_value: u64,
+}
+
+impl Redactions {
+    /// Every value redacted.
+    pub fn total(&self) -> u64 {
+        self.by_name + self.by_value
+    }
+}
+
+/// Redacts values by the names of their fields, or by what they contain. See
+/// the [module documentation](self).
+#[derive(Debug, Default)]
+pub struct Redactor {
+    names: Vec<NamePattern>,
+    values: Vec<ValuePattern>,
+    by_name: AtomicU64,
+    by_value: AtomicU64,
+}
+
+#[derive(Debug, Clone)]
+enum NamePattern {
+    Glob(Glob),
+    Regex(Regex),
+}
+
+impl Redactor {
+    /// A redactor that redacts nothing yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Redacts the values of fields whose names match `pattern`, in which
+    /// `*` stands for any number of characters, and `?` for any one.
+    pub fn field(mut self, pattern: &str) -> Self {
+        self.names.push(NamePattern::Glob(Glob::new(pattern)));
+        self
+    }
+
+    /// Redacts the values of fields whose names match `regex` anywhere.
+    pub fn field_matches(mut self, regex: Regex) -> Self {
+        self.names.push(NamePattern::Regex(regex));
+        self
+    }
+
+    /// Redacts values in which `detector` finds personal data.
+    pub fn detect(mut self, detector: Detector) -> Self {
+        self.values.push(ValuePattern {
+            regex: detector.regex(),
+            luhn: detector == Detector::CardNumber,
+        });
+        self
+    }
+
+    /// Redacts values that match `regex` anywhere.
+    pub fn value_matches(mut self, regex: Regex) -> Self {
+        self.values.push(ValuePattern { regex, luhn: false });
+        self
+    }
+
+    /// Whether the values of fields named `name` are redacted, whatever
+    /// they are.
+    pub fn redacts_field(&self, name: &str) -> bool {
+        self.names.iter().any(|pattern| match pattern {
+            NamePattern::Glob(glob) => glob.matches(name),
+            NamePattern::Regex(regex) => regex.is_match(name),
+        })
+    }
+
+    /// Whether `value`, formatted as a string, is redacted, whatever its
+    /// field is called.
+    pub fn redacts_value(&self, value: &str) -> bool {
+        self.values.iter().any(|pattern| pattern.matches(value))
+    }
+
+    /// How many values have been redacted so far.
+    pub fn redactions(&self) -> Redactions {
+        Redactions {
+            by_name: self.by_name.load(Ordering::Relaxed),
+            by_value: self.by_value.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Wraps `inner`, so that it records [`REDACTED`] as a string in place of
+    /// the values this redactor redacts.
+    pub fn visitor<V: Visit>(&self, inner: V) -> Redacting<'_, V> {
+        Redacting {
+            redactor: self,
+            inner,
+        }
+    }
+
+    /// Redacts the values of `fields`, copying the fields of a live event
+    /// first, and returns how many were redacted.
+    pub fn redact_fields<F: Form>(&self, fields: &mut SerializeRecordFields<'_, F>) -> usize {
+        fields.ensure_owned();
+        match fields {
+            SerializeRecordFields::De(map) => self.redact_map(map),
+            SerializeRecordFields::Ser(_) => unreachable!("fields were just copied"),
+        }
+    }
+
+    /// Redacts the values recorded on a span, as with
+    /// [`redact_fields`](Self::redact_fields).
+    pub fn redact_record<F: Form>(&self, values: &mut SerializeRecord<'_, F>) -> usize {
+        values.ensure_owned();
+        match values {
+            SerializeRecord::De(map) => self.redact_map(map),
+            SerializeRecord::Ser(_) => unreachable!("values were just copied"),
+        }
+    }
+
+    /// Redacts the values of an event, or of values recorded on a span, as
+    /// with [`redact_fields`](Self::redact_fields).
+    ///
+    /// Other messages carry no values, except for
+    /// [`TracingWire::EventInterned`], whose field names are not known
+    /// without the stream's definitions, and which is left alone.
+    pub fn redact_message<F: Form>(&self, msg: &mut TracingWire<'_, F>) -> usize {
+        match msg {
+            TracingWire::Event(event) => self.redact_fields(&mut event.fields),
+            TracingWire::EventRef(event) => self.redact_fields(&mut event.fields),
+            TracingWire::Record { values, .. } => self.redact_record(values),
+            _ => 0,
+        }
+    }
+
+    fn redact_map(&self, map: &mut RecordMap<'_>) -> usize {
+        let mut count = 0;
+        for (field, value) in map.iter_mut() {
+            if matches!(value, SerializeValue::Str(s) if s.as_str() == REDACTED) {
+                continue;
+            }
+            let redact = self.redact_name(field.as_str())
+                || match value {
+                    SerializeValue::Str(s) => self.redact_value(s.as_str()),
+                    SerializeValue::Debug(DebugRecord::De(s)) => self.redact_value(s.as_str()),
+                    SerializeValue::U64(x) => self.redact_value(&x.to_string()),
+                    SerializeValue::I64(x) => self.redact_value(&x.to_string()),
+                    _ => false,
+                };
+            if redact {
+                *value = SerializeValue::Str(REDACTED.into());
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Whether to redact the value of a field named `name`, counting it if
+    /// so.
+    fn redact_name(&self, name: &str) -> bool {
+        let redact = self.redacts_field(name);
+        if redact {
+            self.by_name.fetch_add(1, Ordering::Relaxed);
+        }
+        redact
+    }
+
+    /// Whether to redact `value`, counting it if so.
+    fn redact_value(&self, value: &str) -> bool {
+        let redact = self.redacts_value(value);
+        if redact {
+            self.by_value.fetch_add(1, Ordering::Relaxed);
+        }
+        redact
+    }
+}
+
+/// A visitor recording [`REDACTED`] to the one it wraps in place of the
+/// values a [`Redactor`] redacts. Returned by [`Redactor::visitor`].
+///
+/// Values recorded as `Debug`, and integers, are only formatted if the
+/// redactor looks for anything in values.
+#[derive(Debug)]
+pub struct Redacting<'r, V> {
+    redactor: &'r Redactor,
+    inner: V,
+}
+
+impl<V> Redacting<'_, V> {
+    /// The wrapped visitor.
+    pub fn get_ref(&self) -> &V {
+        &self.inner
+    }
+
+    /// Returns the wrapped visitor, e.g. to finish serializing.
+    pub fn into_inner(self) -> V {
+        self.inner
+    }
+}
+
+impl<V: Visit> Redacting<'_, V> {
+    /// Whether to redact a value of a field, formatting it with `value` only
+    /// if it has to be looked at.
+    fn redact(&self, field: &Field, value: impl FnOnce() -> String) -> bool {
+        self.redactor.redact_name(field.name())
+            || (!self.redactor.values.is_empty() && self.redactor.redact_value(&value()))
+    }
+}
+
+impl<V: Visit> Visit for Redacting<'_, V> {
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        if self.redactor.redact_name(field.name()) {
+            self.inner.record_str(field, REDACTED);
+        } else {
+            self.inner.record_bool(field, value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if self.redactor.redact_name(field.name()) {
+            self.inner.record_str(field, REDACTED);
+        } else if self.redactor.values.is_empty() {
+            self.inner.record_debug(field, value);
+        } else {
+            let formatted = format!("{value:?}");
+            if self.redactor.redact_value(&formatted) {
+                self.inner.record_str(field, REDACTED);
+            } else {
+                self.inner.record_debug(field, &format_args!("{formatted}"));
+            }
+        }
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if self.redact(field, || value.to_string()) {
+            self.inner.record_str(field, REDACTED);
+        } else {
+            self.inner.record_u64(field, value);
+        }
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        if self.redact(field, || value.to_string()) {
+            self.inner.record_str(field, REDACTED);
+        } else {
+            self.inner.record_i64(field, value);
+        }
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        if self.redactor.redact_name(field.name()) {
+            self.inner.record_str(field, REDACTED);
+        } else {
+            self.inner.record_f64(field, value);
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        let redact = self.redactor.redact_name(field.name()) || self.redactor.redact_value(value);
+        self.inner
+            .record_str(field, if redact { REDACTED } else { value });
+    }
+}
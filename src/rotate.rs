@@ -0,0 +1,337 @@
+//! Writing a stream to a series of files, each of which can be decoded on
+//! its own.
+//!
+//! A [`RotatingFile`] delta encodes messages and writes them to a file,
+//! framed like the streams of the `embedded_io` module, and starts a new file
+//! once the current one is large or old enough:
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//!
+//! use tracing_serde_structured::{rotate::RotatingFile, wire::TracingWire, SerializeId};
+//!
+//! let mut files = RotatingFile::new("/var/log/app", "trace")
+//!     .with_max_bytes(64 << 20)
+//!     .with_max_age(Duration::from_secs(3600));
+//! # let now = 0;
+//! # let msg = TracingWire::Enter(SerializeId { id: 1.try_into().unwrap() });
+//! files.write(now, msg).unwrap();
+//! files.flush().unwrap();
+//! ```
+//!
+//! Files are named after the prefix and a counter, such as
+//! `trace-000042.tss`, carrying on from the highest counter already in the
+//! directory.
+//!
+//! Every file starts afresh: with a [`TracingWire::Hello`] saying what the
+//! stream uses, then every [`TracingWire::DefineMetadata`] and
+//! [`TracingWire::DefineString`] written to earlier files, then messages
+//! delta encoded from scratch. So a [`Decoder`](crate::host::Decoder) can read
+//! any one of them, such as the last file of a service that has been running
+//! for weeks, without the ones before it. With a sync interval set, files
+//! also carry a [`TracingWire::Sync`] every so many messages, from which a
+//! [`Decoder::mid_stream`](crate::host::Decoder::mid_stream) can pick up the
+//! stream if the start of a file is damaged.
+
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+use crate::{
+    framing::{Cobs, Framing},
+    wire::{
+        delta::DeltaEncoder,
+        handshake::{Capabilities, Hello},
+        MetadataDictionary, TracingWire,
+    },
+    Live,
+};
+
+/// Errors returned by a [`RotatingFile`].
+#[derive(Debug)]
+pub enum Error {
+    /// The message could not be encoded, e.g. because its frame is longer
+    /// than [`RotatingFile::with_max_frame`].
+    Encode(::postcard::Error),
+    /// Creating or writing a file failed.
+    Io(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Encode(e) => write!(f, "failed to encode message: {e}"),
+            Error::Io(e) => write!(f, "failed to write file: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Encode(e) => Some(e),
+            Error::Io(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/// The file being written, and how much has gone into it.
+#[derive(Debug)]
+struct Current {
+    file: BufWriter<File>,
+    path: PathBuf,
+    opened: Instant,
+    bytes: u64,
+    messages: u64,
+}
+
+impl Current {
+    /// Encodes `frame` into `buf` with `framing`, and writes it.
+    fn write_frame<F, T>(&mut self, framing: &F, buf: &mut [u8], frame: &T) -> Result<(), Error>
+    where
+        F: Framing,
+        T: Serialize + ?Sized,
+    {
+        let frame = framing.to_slice(frame, buf).map_err(Error::Encode)?;
+        self.file.write_all(frame)?;
+        self.bytes += frame.len() as u64;
+        Ok(())
+    }
+}
+
+/// Writes delta encoded messages to a series of files in a directory,
+/// starting a new one when the current one is too large or too old.
+#[derive(Debug)]
+pub struct RotatingFile<F = Cobs> {
+    dir: PathBuf,
+    prefix: String,
+    framing: F,
+    hello: Hello<'static>,
+    max_bytes: Option<u64>,
+    max_age: Option<Duration>,
+    sync_interval: Option<u32>,
+    encoder: DeltaEncoder,
+    definitions: MetadataDictionary,
+    buf: Vec<u8>,
+    next_index: Option<u32>,
+    current: Option<Current>,
+}
+
+impl RotatingFile {
+    /// The longest frame written, by default: 16 KiB.
+    pub const DEFAULT_MAX_FRAME: usize = 16 << 10;
+
+    /// Writes files named after `prefix` into `dir`, which is created if it
+    /// does not exist yet.
+    ///
+    /// No file is created until the first message is written. Files are
+    /// never rotated until a limit is set.
+    pub fn new(dir: impl Into<PathBuf>, prefix: impl Into<String>) -> Self {
+        Self {
+            dir: dir.into(),
+            prefix: prefix.into(),
+            framing: Cobs,
+            hello: Hello::new(Capabilities::DELTA | Capabilities::DEFINITIONS),
+            max_bytes: None,
+            max_age: None,
+            sync_interval: None,
+            encoder: DeltaEncoder::new(),
+            definitions: MetadataDictionary::new(),
+            buf: vec![0; Self::DEFAULT_MAX_FRAME],
+            next_index: None,
+            current: None,
+        }
+    }
+}
+
+impl<F: Framing> RotatingFile<F> {
+    /// Frame messages with `framing` rather than COBS, e.g. with
+    /// [`LengthPrefixed`](crate::framing::LengthPrefixed), which suits files
+    /// better.
+    pub fn with_framing<G: Framing>(self, framing: G) -> RotatingFile<G> {
+        RotatingFile {
+            dir: self.dir,
+            prefix: self.prefix,
+            framing,
+            hello: self.hello,
+            max_bytes: self.max_bytes,
+            max_age: self.max_age,
+            sync_interval: self.sync_interval,
+            encoder: self.encoder,
+            definitions: self.definitions,
+            buf: self.buf,
+            next_index: self.next_index,
+            current: self.current,
+        }
+    }
+
+    /// Start every file with `hello`, rather than one saying the stream is
+    /// delta encoded with definitions, e.g. to give the tick rate of the
+    /// timestamps, or say that it uses interned field names.
+    pub fn with_hello(mut self, hello: Hello<'static>) -> Self {
+        self.hello = hello;
+        self
+    }
+
+    /// Start a new file once the current one holds `max_bytes`. Files may
+    /// end up larger by the last frame written to them.
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Start a new file once the current one was opened `max_age` ago.
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Write a [`TracingWire::Sync`] before every `interval` messages of a
+    /// file (see [`DeltaEncoder::set_sync_interval`]).
+    pub fn with_sync_interval(mut self, interval: u32) -> Self {
+        self.sync_interval = Some(interval);
+        self.encoder.set_sync_interval(self.sync_interval);
+        self
+    }
+
+    /// Encode frames in a buffer of `max_frame` bytes, rather than
+    /// [`RotatingFile::DEFAULT_MAX_FRAME`].
+    pub fn with_max_frame(mut self, max_frame: usize) -> Self {
+        self.buf = vec![0; max_frame];
+        self
+    }
+
+    /// Writes `msg`, produced at `timestamp`, to the current file, first
+    /// starting a new one if the current one is due to be rotated.
+    ///
+    /// Messages are taken in the [`Live`] form, as with
+    /// [`DeltaEncoder::encode`]; others can be converted with `into`.
+    pub fn write(&mut self, timestamp: u64, msg: TracingWire<'_, Live>) -> Result<(), Error> {
+        let due = match &self.current {
+            Some(current) => self.is_due(current),
+            None => true,
+        };
+        if due {
+            self.rotate(timestamp)?;
+        }
+        self.definitions.observe(&msg);
+
+        let Self {
+            framing,
+            encoder,
+            buf,
+            current,
+            ..
+        } = self;
+        let current = current.as_mut().expect("a file was opened");
+        let mut synced = Ok(());
+        let frame = encoder.encode_with_sync(timestamp, msg, |sync| {
+            synced = current.write_frame(framing, buf, &sync);
+        });
+        synced?;
+        current.write_frame(framing, buf, &frame)?;
+        current.messages += 1;
+        Ok(())
+    }
+
+    /// Closes the current file, and starts a new one, beginning with the
+    /// hello and the definitions written so far, at `timestamp`.
+    pub fn rotate(&mut self, timestamp: u64) -> Result<(), Error> {
+        if let Some(mut current) = self.current.take() {
+            current.file.flush()?;
+        }
+        let index = match self.next_index {
+            Some(index) => index,
+            None => {
+                fs::create_dir_all(&self.dir)?;
+                self.last_index()?.map_or(0, |index| index + 1)
+            }
+        };
+        self.next_index = Some(index + 1);
+        let path = self.dir.join(format!("{}-{index:06}.tss", self.prefix));
+        let mut current = Current {
+            file: BufWriter::new(File::create(&path)?),
+            path,
+            opened: Instant::now(),
+            bytes: 0,
+            messages: 0,
+        };
+
+        self.encoder = DeltaEncoder::new();
+        self.encoder.set_sync_interval(self.sync_interval);
+        let mut header = vec![TracingWire::Hello(self.hello.clone())];
+        header.extend(
+            self.definitions
+                .iter()
+                .map(|(id, metadata)| TracingWire::DefineMetadata {
+                    id,
+                    metadata: metadata.clone(),
+                }),
+        );
+        header.extend(
+            self.definitions
+                .strings()
+                .map(|(id, value)| TracingWire::DefineString {
+                    id,
+                    value: value.clone(),
+                }),
+        );
+        for msg in header {
+            let frame = self.encoder.encode(timestamp, msg);
+            current.write_frame(&self.framing, &mut self.buf, &frame)?;
+        }
+        self.current = Some(current);
+        Ok(())
+    }
+
+    /// Flushes the current file.
+    pub fn flush(&mut self) -> io::Result<()> {
+        match &mut self.current {
+            Some(current) => current.file.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// The path of the current file, if one has been opened.
+    pub fn path(&self) -> Option<&Path> {
+        self.current.as_ref().map(|current| current.path.as_path())
+    }
+
+    /// Whether the current file has messages in it, and is too large or too
+    /// old to take any more. A file with only the header in it is never
+    /// rotated, or one with a large dictionary would never hold a message.
+    fn is_due(&self, current: &Current) -> bool {
+        current.messages > 0
+            && (self.max_bytes.is_some_and(|max| current.bytes >= max)
+                || self
+                    .max_age
+                    .is_some_and(|max| current.opened.elapsed() >= max))
+    }
+
+    /// The highest counter of the files already in the directory.
+    fn last_index(&self) -> io::Result<Option<u32>> {
+        let prefix = format!("{}-", self.prefix);
+        let mut last = None;
+        for entry in fs::read_dir(&self.dir)? {
+            let name = entry?.file_name();
+            let index = name
+                .to_str()
+                .and_then(|name| name.strip_prefix(&prefix)?.strip_suffix(".tss"))
+                .and_then(|index| index.parse::<u32>().ok());
+            last = last.max(index);
+        }
+        Ok(last)
+    }
+}
@@ -0,0 +1,166 @@
+//! Reusable buffers for serializing messages.
+//!
+//! Hot paths that encode one message at a time can borrow a
+//! [`SerializeScratch`] buffer instead of allocating a `Vec<u8>` for each
+//! message:
+//!
+//! ```rust
+//! use tracing_serde_structured::{scratch::SerializeScratch, SerializeLevel};
+//!
+//! let mut scratch = SerializeScratch::take().expect("scratch buffer in use");
+//! let used = postcard::to_slice(&SerializeLevel::Info, &mut scratch).unwrap();
+//! assert_eq!(used, [2]);
+//! ```
+//!
+//! With `std`, each thread keeps a small pool of buffers, so a buffer is only
+//! allocated the first time a thread needs one (or when it needs several at
+//! once). Without `std` there is a single static buffer, and [`SerializeScratch::take`]
+//! returns `None` while it is in use, e.g. when an interrupt handler emits an
+//! event while the main loop is serializing one. The static buffer needs
+//! atomic compare-and-swap, so it is not available on targets such as
+//! `thumbv6m-none-eabi`.
+//!
+//! Every buffer is [`SCRATCH_SIZE`] bytes long. To size them for a project, set
+//! the `TRACING_SERDE_STRUCTURED_SCRATCH_SIZE` environment variable when building,
+//! e.g. in `.cargo/config.toml`:
+//!
+//! ```toml
+//! [env]
+//! TRACING_SERDE_STRUCTURED_SCRATCH_SIZE = "128"
+//! ```
+
+use core::{
+    fmt,
+    ops::{Deref, DerefMut},
+};
+
+/// The size of each scratch buffer, in bytes.
+///
+/// This is 512 unless the `TRACING_SERDE_STRUCTURED_SCRATCH_SIZE` environment
+/// variable was set when building this crate.
+pub const SCRATCH_SIZE: usize = include!(concat!(env!("OUT_DIR"), "/scratch_size"));
+
+/// A scratch buffer of [`SCRATCH_SIZE`] bytes, returned for reuse when dropped.
+///
+/// The contents of a newly taken buffer are unspecified.
+pub struct SerializeScratch {
+    #[cfg(feature = "std")]
+    buf: Option<Box<[u8]>>,
+    #[cfg(not(feature = "std"))]
+    buf: &'static mut [u8; SCRATCH_SIZE],
+}
+
+impl fmt::Debug for SerializeScratch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SerializeScratch")
+            .field("len", &self.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Deref for SerializeScratch {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        #[cfg(feature = "std")]
+        {
+            self.buf.as_deref().expect("only taken when dropped")
+        }
+        #[cfg(not(feature = "std"))]
+        {
+            &self.buf[..]
+        }
+    }
+}
+
+impl DerefMut for SerializeScratch {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        #[cfg(feature = "std")]
+        {
+            self.buf.as_deref_mut().expect("only taken when dropped")
+        }
+        #[cfg(not(feature = "std"))]
+        {
+            &mut self.buf[..]
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+mod pool {
+    use std::cell::RefCell;
+
+    use super::{SerializeScratch, SCRATCH_SIZE};
+
+    /// The number of idle buffers each thread keeps.
+    const POOL_SIZE: usize = 4;
+
+    thread_local! {
+        static POOL: RefCell<Vec<Box<[u8]>>> = const { RefCell::new(Vec::new()) };
+    }
+
+    impl SerializeScratch {
+        /// Borrow a buffer from this thread's pool, allocating one if the pool is empty.
+        ///
+        /// This always returns `Some` with `std`.
+        pub fn take() -> Option<Self> {
+            let buf = POOL
+                .try_with(|pool| pool.borrow_mut().pop())
+                .ok()
+                .flatten()
+                .unwrap_or_else(|| vec![0; SCRATCH_SIZE].into_boxed_slice());
+            Some(Self { buf: Some(buf) })
+        }
+    }
+
+    impl Drop for SerializeScratch {
+        fn drop(&mut self) {
+            if let Some(buf) = self.buf.take() {
+                let _ = POOL.try_with(|pool| {
+                    let mut pool = pool.borrow_mut();
+                    if pool.len() < POOL_SIZE {
+                        pool.push(buf);
+                    }
+                });
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "std"))]
+mod single {
+    use core::{
+        cell::UnsafeCell,
+        sync::atomic::{AtomicBool, Ordering},
+    };
+
+    use super::{SerializeScratch, SCRATCH_SIZE};
+
+    struct Buffer(UnsafeCell<[u8; SCRATCH_SIZE]>);
+
+    // SAFETY: the buffer is only accessed through the `SerializeScratch`
+    // holding `IN_USE`, of which there is at most one at a time.
+    unsafe impl Sync for Buffer {}
+
+    static BUFFER: Buffer = Buffer(UnsafeCell::new([0; SCRATCH_SIZE]));
+    static IN_USE: AtomicBool = AtomicBool::new(false);
+
+    impl SerializeScratch {
+        /// Borrow the static buffer, or return `None` if it is already borrowed.
+        pub fn take() -> Option<Self> {
+            IN_USE
+                .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .ok()?;
+            // SAFETY: `IN_USE` was false, so no other reference to the buffer
+            // exists until this one is dropped.
+            let buf = unsafe { &mut *BUFFER.0.get() };
+            Some(Self { buf })
+        }
+    }
+
+    impl Drop for SerializeScratch {
+        fn drop(&mut self) {
+            IN_USE.store(false, Ordering::Release);
+        }
+    }
+}
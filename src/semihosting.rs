@@ -0,0 +1,90 @@
+//! Sending trace data to the debugger's console with semihosting.
+//!
+//! Semihosting needs no pins or peripherals at all, only an attached
+//! debugger, which makes it the last resort for getting traces off a board.
+//! A [`SemihostingWriter`] is an [`embedded_io::Write`](::embedded_io::Write)
+//! sink, so a [`FramedWriter`](crate::embedded_io::FramedWriter) can frame
+//! messages onto it:
+//!
+//! ```rust,ignore
+//! use tracing_serde_structured::{embedded_io::FramedWriter, semihosting::SemihostingWriter};
+//!
+//! let mut writer = FramedWriter::<_, 256>::new(SemihostingWriter::stdout()?);
+//! writer.write(&msg)?;
+//! ```
+//!
+//! Every write stops the core until the debugger has handled it, which takes
+//! milliseconds, and without a debugger attached the core faults instead. So
+//! this module only exists in builds with debug assertions enabled, and
+//! firmware using it fails to build in release mode rather than crash in the
+//! field.
+
+use core::fmt;
+
+use ::cortex_m_semihosting::hio::{self, HostStream};
+
+/// The error returned when the debugger rejects a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Error {
+    _priv: (),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("semihosting request failed")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+impl ::embedded_io::Error for Error {
+    fn kind(&self) -> ::embedded_io::ErrorKind {
+        ::embedded_io::ErrorKind::Other
+    }
+}
+
+/// Writes to the debugger's standard output or standard error.
+#[derive(Clone, Copy)]
+pub struct SemihostingWriter {
+    stream: HostStream,
+}
+
+impl fmt::Debug for SemihostingWriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SemihostingWriter").finish_non_exhaustive()
+    }
+}
+
+impl SemihostingWriter {
+    /// Opens the debugger's standard output.
+    pub fn stdout() -> Result<Self, Error> {
+        hio::hstdout()
+            .map(|stream| Self { stream })
+            .map_err(|()| Error { _priv: () })
+    }
+
+    /// Opens the debugger's standard error.
+    pub fn stderr() -> Result<Self, Error> {
+        hio::hstderr()
+            .map(|stream| Self { stream })
+            .map_err(|()| Error { _priv: () })
+    }
+}
+
+impl ::embedded_io::ErrorType for SemihostingWriter {
+    type Error = Error;
+}
+
+impl ::embedded_io::Write for SemihostingWriter {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        self.stream
+            .write_all(buf)
+            .map_err(|()| Error { _priv: () })?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
@@ -0,0 +1,239 @@
+//! A fixed-capacity map backed by a sorted `heapless::Vec`.
+//!
+//! [`SortedMap`] stores its entries in key order, and finds them with a
+//! binary search. Compared to `heapless::FnvIndexMap`, it does not store a
+//! hash and an index for each entry, and its capacity does not have to be a
+//! power of two, so it can be sized to exactly the number of fields needed.
+//! Inserting is `O(n)`, which is cheap for the handful of fields an event
+//! usually has.
+//!
+//! With the `sorted-map` feature and without `alloc`, this is the map used
+//! for deserialized field values. It has the same methods as
+//! `FnvIndexMap` for the common operations, so code reading field maps does
+//! not need to change. Code that names the map type does, since enabling the
+//! feature changes the type held by
+//! [`SerializeRecordFields::De`](crate::SerializeRecordFields::De) and
+//! [`SerializeRecord::De`](crate::SerializeRecord::De).
+
+use core::{borrow::Borrow, fmt, marker::PhantomData};
+
+use serde::{
+    de::{self, Deserializer, MapAccess},
+    ser::{SerializeMap, Serializer},
+    Deserialize, Serialize,
+};
+
+/// A fixed-capacity map with entries sorted by key.
+#[derive(Clone)]
+pub struct SortedMap<K, V, const N: usize> {
+    entries: heapless::Vec<(K, V), N>,
+}
+
+impl<K, V, const N: usize> SortedMap<K, V, N> {
+    /// Create an empty map.
+    pub const fn new() -> Self {
+        SortedMap {
+            entries: heapless::Vec::new(),
+        }
+    }
+
+    /// The number of entries the map can hold.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear()
+    }
+
+    /// Iterate over the entries, in key order.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            inner: self.entries.iter(),
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.entries.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.entries.iter().map(|(_, v)| v)
+    }
+}
+
+impl<K: Ord, V, const N: usize> SortedMap<K, V, N> {
+    fn search<Q>(&self, key: &Q) -> Result<usize, usize>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.entries.binary_search_by(|(k, _)| k.borrow().cmp(key))
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.search(key).ok().map(|i| &self.entries[i].1)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.search(key).ok().map(|i| &mut self.entries[i].1)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.search(key).is_ok()
+    }
+
+    /// Insert an entry, returning the previous value for `key`, if any.
+    ///
+    /// If the map is full and does not contain `key`, the entry is handed
+    /// back as an error.
+    pub fn insert(&mut self, key: K, value: V) -> Result<Option<V>, (K, V)> {
+        match self.search(&key) {
+            Ok(i) => Ok(Some(core::mem::replace(&mut self.entries[i].1, value))),
+            Err(i) => {
+                if self.entries.is_full() {
+                    return Err((key, value));
+                }
+                // Cannot fail, the map is not full.
+                let _ = self.entries.insert(i, (key, value));
+                Ok(None)
+            }
+        }
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.search(key).ok().map(|i| self.entries.remove(i).1)
+    }
+}
+
+impl<K, V, const N: usize> Default for SortedMap<K, V, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, const N: usize> fmt::Debug for SortedMap<K, V, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: PartialEq, V: PartialEq, const N: usize> PartialEq for SortedMap<K, V, N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.entries == other.entries
+    }
+}
+
+impl<K: Eq, V: Eq, const N: usize> Eq for SortedMap<K, V, N> {}
+
+impl<'a, K, V, const N: usize> IntoIterator for &'a SortedMap<K, V, N> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the entries of a [`SortedMap`], in key order.
+#[derive(Debug, Clone)]
+pub struct Iter<'a, K, V> {
+    inner: core::slice::Iter<'a, (K, V)>,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(k, v)| (k, v))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Iter<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(k, v)| (k, v))
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {}
+
+impl<K: Serialize, V: Serialize, const N: usize> Serialize for SortedMap<K, V, N> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(self.len()))?;
+        for (k, v) in self.iter() {
+            map.serialize_entry(k, v)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de, K, V, const N: usize> Deserialize<'de> for SortedMap<K, V, N>
+where
+    K: Ord + Deserialize<'de>,
+    V: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(SortedMapVisitor(PhantomData))
+    }
+}
+
+struct SortedMapVisitor<K, V, const N: usize>(PhantomData<(K, V)>);
+
+impl<'de, K, V, const N: usize> de::Visitor<'de> for SortedMapVisitor<K, V, N>
+where
+    K: Ord + Deserialize<'de>,
+    V: Deserialize<'de>,
+{
+    type Value = SortedMap<K, V, N>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "a map with at most {} entries", N)
+    }
+
+    fn visit_map<A>(self, mut access: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut map = SortedMap::new();
+        while let Some((k, v)) = access.next_entry()? {
+            if map.insert(k, v).is_err() {
+                return Err(de::Error::invalid_length(N + 1, &self));
+            }
+        }
+        Ok(map)
+    }
+}
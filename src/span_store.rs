@@ -0,0 +1,452 @@
+//! Keeping the spans of a decoded stream around, to give events their
+//! context.
+//!
+//! An event on the wire only carries its own fields, while the request id or
+//! user it is about was usually recorded once, on a span it is inside of. A
+//! [`SpanStore`] is handed every message of a stream, remembers the
+//! attributes and values of the spans, and enriches events with the fields
+//! of the spans they are within:
+//!
+//! ```rust
+//! use std::time::Duration;
+//! use tracing_serde_structured::{
+//!     builder::{SerializeAttributesBuilder, SerializeEventBuilder},
+//!     span_store::SpanStore,
+//!     wire::TracingWire,
+//!     SerializeId, SerializeRecord, SerializeRecordFields, SerializeValue,
+//! };
+//!
+//! let id = SerializeId { id: 1.try_into().unwrap() };
+//! let mut store = SpanStore::new();
+//! store.push(
+//!     Duration::ZERO,
+//!     &TracingWire::NewSpan {
+//!         id: id.clone(),
+//!         attributes: SerializeAttributesBuilder::new("request").field("user").build(),
+//!     },
+//! );
+//! let values: SerializeRecord<'_> = [("user".into(), SerializeValue::U64(42))]
+//!     .into_iter()
+//!     .collect();
+//! store.push(Duration::ZERO, &TracingWire::Record { span: id.clone(), values });
+//!
+//! let event = SerializeEventBuilder::new()
+//!     .field("message", "logged in")
+//!     .parent(id)
+//!     .build();
+//! let event = store.enrich(&event);
+//! let SerializeRecordFields::De(fields) = &event.fields else { unreachable!() };
+//! assert_eq!(fields[&"user".into()], SerializeValue::U64(42));
+//! ```
+//!
+//! An event is within the span given as its parent, or else the span most
+//! recently entered and not yet exited, and within all of that span's
+//! ancestors. Its own fields take precedence over those of its spans, and
+//! the fields of inner spans over those of outer ones.
+//!
+//! Spans are kept after they close, since events from other threads or cores
+//! may arrive after the span they happened in has closed. To bound the
+//! memory used, the store holds at most
+//! [`with_capacity`](SpanStore::with_capacity) spans: when a new span would
+//! exceed it, the span that closed first is evicted or, if every span is
+//! open, the one that was used least recently. Closed spans are also evicted
+//! once they have been closed for [`with_max_age`](SpanStore::with_max_age).
+//! The [`stats`](SpanStore::stats) count the spans evicted, and the events
+//! and messages about spans that were no longer, or never, in the store.
+//!
+//! The store also keeps the [`baggage`](crate::baggage) sent for each span,
+//! which [`baggage`](SpanStore::baggage) looks up through its ancestors.
+
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::time::Duration;
+
+use crate::{
+    baggage::SerializeBaggage, wire::TracingWire, CowString, SerializeAttributes, SerializeEvent,
+    SerializeFieldSet, SerializeId, SerializeRecord, SerializeRecordFields, SerializeValue,
+};
+
+/// A span kept in a [`SpanStore`].
+#[derive(Debug, Clone)]
+pub struct StoredSpan {
+    id: SerializeId,
+    attributes: SerializeAttributes<'static>,
+    values: BTreeMap<CowString<'static>, SerializeValue<'static>>,
+    baggage: Option<SerializeBaggage<'static>>,
+    parent: Option<u64>,
+    created: Duration,
+    closed: Option<Duration>,
+    /// The span's key in the order of eviction.
+    seq: u64,
+}
+
+impl StoredSpan {
+    pub fn id(&self) -> &SerializeId {
+        &self.id
+    }
+
+    pub fn name(&self) -> &str {
+        self.attributes.metadata.name.as_str()
+    }
+
+    pub fn attributes(&self) -> &SerializeAttributes<'static> {
+        &self.attributes
+    }
+
+    /// The values recorded for the span's fields, by name.
+    pub fn values(&self) -> impl Iterator<Item = (&str, &SerializeValue<'static>)> + '_ {
+        self.values
+            .iter()
+            .map(|(name, value)| (name.as_str(), value))
+    }
+
+    pub fn value(&self, name: &str) -> Option<&SerializeValue<'static>> {
+        self.values
+            .iter()
+            .find(|(key, _)| key.as_str() == name)
+            .map(|(_, value)| value)
+    }
+
+    /// The baggage sent for the span itself, if any. See
+    /// [`SpanStore::baggage`] for the baggage it inherits.
+    pub fn baggage(&self) -> Option<&SerializeBaggage<'static>> {
+        self.baggage.as_ref()
+    }
+
+    /// The id of the span's parent, which may no longer be in the store.
+    pub fn parent(&self) -> Option<SerializeId> {
+        Some(SerializeId {
+            id: self.parent?.try_into().ok()?,
+        })
+    }
+
+    /// When the span was created, since the stream began.
+    pub fn created(&self) -> Duration {
+        self.created
+    }
+
+    /// When the span was closed, since the stream began, if it is.
+    pub fn closed(&self) -> Option<Duration> {
+        self.closed
+    }
+}
+
+/// The number of spans a [`SpanStore`] evicted, and of lookups that failed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct SpanStoreStats {
+    /// Spans evicted to make room for new ones.
+    pub evicted: u64,
+    /// Closed spans evicted for being older than the maximum age.
+    pub expired: u64,
+    /// Spans that were still open when they were evicted.
+    pub evicted_open: u64,
+    /// Events and messages about a span that was not in the store.
+    pub misses: u64,
+}
+
+/// Keeps the spans of a stream, with bounded memory, to enrich its events.
+/// See the [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct SpanStore {
+    spans: BTreeMap<u64, StoredSpan>,
+    /// Open spans by `seq`, which is bumped when they are used.
+    open: BTreeMap<u64, u64>,
+    /// Closed spans by `seq`, which is set when they close.
+    closed: BTreeMap<u64, u64>,
+    /// The entered spans, innermost last.
+    stack: Vec<u64>,
+    seq: u64,
+    now: Duration,
+    capacity: usize,
+    max_age: Option<Duration>,
+    stats: SpanStoreStats,
+}
+
+impl Default for SpanStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SpanStore {
+    /// The number of spans kept by default.
+    pub const DEFAULT_CAPACITY: usize = 4096;
+
+    /// A store keeping up to [`DEFAULT_CAPACITY`](Self::DEFAULT_CAPACITY)
+    /// spans, however long ago they closed.
+    pub fn new() -> Self {
+        Self {
+            spans: BTreeMap::new(),
+            open: BTreeMap::new(),
+            closed: BTreeMap::new(),
+            stack: Vec::new(),
+            seq: 0,
+            now: Duration::ZERO,
+            capacity: Self::DEFAULT_CAPACITY,
+            max_age: None,
+            stats: SpanStoreStats::default(),
+        }
+    }
+
+    /// Set the maximum number of spans kept, open or closed.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// Evict spans once they have been closed for `max_age`, according to
+    /// the times messages are pushed with.
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Adds `msg`, which happened `since_start` after the stream began.
+    ///
+    /// Events leave the store as it is; [`enrich`](Self::enrich) them
+    /// instead. Other messages that are not about spans are ignored.
+    pub fn push(&mut self, since_start: Duration, msg: &TracingWire<'_>) {
+        self.now = self.now.max(since_start);
+        self.expire();
+        match msg {
+            TracingWire::NewSpan { id, attributes } => self.new_span(id, attributes),
+            TracingWire::Record { span, values } => {
+                let Some(stored) = self.get_mut(span.id.get()) else {
+                    return;
+                };
+                match values {
+                    SerializeRecord::De(values) => stored.values.extend(
+                        values
+                            .iter()
+                            .map(|(name, value)| (name.to_owned_name(), value.to_owned())),
+                    ),
+                    SerializeRecord::Ser(never) => match *never {},
+                }
+            }
+            TracingWire::Baggage { span, baggage } => {
+                if let Some(stored) = self.get_mut(span.id.get()) {
+                    stored.baggage = Some(baggage.to_owned());
+                }
+            }
+            TracingWire::Enter(span) if self.get_mut(span.id.get()).is_some() => {
+                self.stack.push(span.id.get());
+            }
+            TracingWire::Exit(span) => {
+                if let Some(i) = self.stack.iter().rposition(|s| *s == span.id.get()) {
+                    self.stack.remove(i);
+                }
+            }
+            TracingWire::CloseSpan(span) => {
+                let id = span.id.get();
+                self.stack.retain(|s| *s != id);
+                let Some(stored) = self.spans.get_mut(&id) else {
+                    self.stats.misses += 1;
+                    return;
+                };
+                if stored.closed.is_none() {
+                    self.open.remove(&stored.seq);
+                    self.seq += 1;
+                    stored.seq = self.seq;
+                    stored.closed = Some(since_start);
+                    self.closed.insert(self.seq, id);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Adds a message decoded by a [`host::Decoder`](crate::host::Decoder).
+    #[cfg(feature = "host")]
+    pub fn push_message(&mut self, msg: &crate::host::Message) {
+        self.push(msg.since_start, &msg.msg);
+    }
+
+    /// Returns the span `id`, if it is in the store.
+    pub fn get(&self, id: &SerializeId) -> Option<&StoredSpan> {
+        self.spans.get(&id.id.get())
+    }
+
+    /// Returns the spans `event` is within, innermost first.
+    pub fn scope(&self, event: &SerializeEvent<'_>) -> impl Iterator<Item = &StoredSpan> + '_ {
+        let mut next = self.parent_of(event);
+        let mut left = self.spans.len();
+        core::iter::from_fn(move || {
+            // The chain ends, unless ids are reused while their spans are
+            // still in the store.
+            left = left.checked_sub(1)?;
+            let span = self.spans.get(&next?)?;
+            next = span.parent;
+            Some(span)
+        })
+    }
+
+    /// The baggage of the span `id`: that sent for it, or else that of its
+    /// nearest ancestor in the store with any.
+    pub fn baggage(&self, id: &SerializeId) -> Option<&SerializeBaggage<'static>> {
+        let mut next = Some(id.id.get());
+        for _ in 0..self.spans.len() {
+            let span = self.spans.get(&next?)?;
+            if let Some(baggage) = &span.baggage {
+                return Some(baggage);
+            }
+            next = span.parent;
+        }
+        None
+    }
+
+    /// Returns `event`, owned, with the fields of the spans it is within
+    /// added to its own.
+    ///
+    /// Using the spans counts as using them, for choosing the open span to
+    /// evict.
+    pub fn enrich(&mut self, event: &SerializeEvent<'_>) -> SerializeEvent<'static> {
+        let mut event = event.to_owned();
+        let mut scope = Vec::new();
+        let mut next = self.parent_of(&event);
+        while let Some(id) = next {
+            let Some(span) = self.get_mut(id) else {
+                break;
+            };
+            next = span.parent;
+            scope.push(id);
+            if scope.len() >= self.spans.len() {
+                break;
+            }
+        }
+
+        let fields = match &mut event.fields {
+            SerializeRecordFields::De(fields) => fields,
+            SerializeRecordFields::Ser(never) => match *never {},
+        };
+        let mut added = Vec::new();
+        for id in scope {
+            for (name, value) in &self.spans[&id].values {
+                if !fields.contains_key(name) {
+                    fields.insert(name.clone(), value.clone());
+                    added.push(name.clone());
+                }
+            }
+        }
+        if let SerializeFieldSet::De(names) = &mut event.metadata.fields {
+            names.extend(added);
+        }
+        event
+    }
+
+    /// The number of spans in the store, open or closed.
+    pub fn len(&self) -> usize {
+        self.spans.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+
+    /// The number of spans in the store that are open.
+    pub fn open(&self) -> usize {
+        self.open.len()
+    }
+
+    pub fn stats(&self) -> SpanStoreStats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = SpanStoreStats::default();
+    }
+
+    /// The span that an event, or a new span, is directly within.
+    fn parent_of(&self, event: &SerializeEvent<'_>) -> Option<u64> {
+        match &event.parent {
+            Some(parent) => Some(parent.id.get()),
+            None => self.stack.last().copied(),
+        }
+    }
+
+    /// Returns the span `id` as it is used, counting a miss if it is not in
+    /// the store.
+    fn get_mut(&mut self, id: u64) -> Option<&mut StoredSpan> {
+        let Some(span) = self.spans.get_mut(&id) else {
+            self.stats.misses += 1;
+            return None;
+        };
+        if span.closed.is_none() {
+            self.open.remove(&span.seq);
+            self.seq += 1;
+            span.seq = self.seq;
+            self.open.insert(self.seq, id);
+        }
+        Some(span)
+    }
+
+    fn new_span(&mut self, id: &SerializeId, attributes: &SerializeAttributes<'_>) {
+        let parent = match &attributes.parent {
+            Some(parent) => Some(parent.id.get()),
+            None if attributes.is_root => None,
+            None => self.stack.last().copied(),
+        };
+        // A span whose close was lost is replaced when its id is reused.
+        self.remove(id.id.get());
+        while self.spans.len() >= self.capacity {
+            let (open, seq) = match self.closed.first_key_value() {
+                Some((&seq, _)) => (false, seq),
+                None => match self.open.first_key_value() {
+                    Some((&seq, _)) => (true, seq),
+                    None => break,
+                },
+            };
+            let index = if open {
+                &mut self.open
+            } else {
+                &mut self.closed
+            };
+            let evicted = index.remove(&seq).unwrap_or_default();
+            self.spans.remove(&evicted);
+            self.stats.evicted += 1;
+            if open {
+                self.stats.evicted_open += 1;
+            }
+        }
+
+        self.seq += 1;
+        self.open.insert(self.seq, id.id.get());
+        self.spans.insert(
+            id.id.get(),
+            StoredSpan {
+                id: id.clone(),
+                attributes: attributes.to_owned(),
+                values: BTreeMap::new(),
+                baggage: None,
+                parent,
+                created: self.now,
+                closed: None,
+                seq: self.seq,
+            },
+        );
+    }
+
+    fn remove(&mut self, id: u64) {
+        if let Some(span) = self.spans.remove(&id) {
+            match span.closed {
+                Some(_) => self.closed.remove(&span.seq),
+                None => self.open.remove(&span.seq),
+            };
+        }
+    }
+
+    /// Evicts the closed spans older than the maximum age.
+    fn expire(&mut self) {
+        let Some(max_age) = self.max_age else {
+            return;
+        };
+        // Spans are in the order they closed, so the oldest come first.
+        while let Some((&seq, &id)) = self.closed.first_key_value() {
+            let closed = self.spans[&id].closed.unwrap_or_default();
+            if self.now.saturating_sub(closed) <= max_age {
+                break;
+            }
+            self.closed.remove(&seq);
+            self.spans.remove(&id);
+            self.stats.expired += 1;
+        }
+    }
+}
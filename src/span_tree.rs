@@ -0,0 +1,418 @@
+//! Reconstructing the tree of spans of a decoded stream.
+//!
+//! A [`SpanTreeBuilder`] is handed every message of a stream, and keeps the
+//! books on which span is whose parent, which span is entered, the values
+//! recorded for each span and which events happened in it. The
+//! [`SpanTree`] it builds is a forest of spans, navigated with [`SpanRef`]s:
+//!
+//! ```rust
+//! use std::time::Duration;
+//! use tracing_serde_structured::{
+//!     builder::{SerializeAttributesBuilder, SerializeEventBuilder},
+//!     span_tree::SpanTreeBuilder,
+//!     wire::TracingWire,
+//!     SerializeId,
+//! };
+//!
+//! let id = |id: u64| SerializeId { id: id.try_into().unwrap() };
+//! let mut builder = SpanTreeBuilder::new();
+//! let messages = [
+//!     TracingWire::NewSpan {
+//!         id: id(1),
+//!         attributes: SerializeAttributesBuilder::new("request").build(),
+//!     },
+//!     TracingWire::Enter(id(1)),
+//!     TracingWire::NewSpan {
+//!         id: id(2),
+//!         attributes: SerializeAttributesBuilder::new("query").build(),
+//!     },
+//!     TracingWire::Enter(id(2)),
+//!     TracingWire::Event(SerializeEventBuilder::new().name("rows").build()),
+//!     TracingWire::Exit(id(2)),
+//!     TracingWire::CloseSpan(id(2)),
+//!     TracingWire::Exit(id(1)),
+//!     TracingWire::CloseSpan(id(1)),
+//! ];
+//! for (ms, msg) in messages.iter().enumerate() {
+//!     builder.push(Duration::from_millis(ms as u64), msg);
+//! }
+//!
+//! let tree = builder.finish();
+//! let request = tree.roots().next().unwrap();
+//! let query = request.children().next().unwrap();
+//! assert_eq!(query.name(), "query");
+//! assert_eq!(query.events()[0].event.metadata.name.as_str(), "rows");
+//! assert_eq!(request.duration(), Some(Duration::from_millis(8)));
+//! ```
+//!
+//! Span ids are only unique among the spans that are open at the same time,
+//! so once a span is closed, its id may be reused by a new span, which gets a
+//! node of its own. Messages about a span that arrive before the span is
+//! created, such as from another thread or core whose messages are sent
+//! later, are held back until it is; [`SpanTreeBuilder::pending`] counts
+//! those still waiting.
+//!
+//! Which span is entered is tracked for the stream as a whole, so for
+//! streams from several threads, spans and events without an explicit
+//! parent may be attributed to a span entered on another thread.
+
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::time::Duration;
+
+use crate::{
+    wire::TracingWire, CowString, SerializeAttributes, SerializeEvent, SerializeId,
+    SerializeMetadata, SerializeRecord, SerializeValue,
+};
+
+/// An event, and when it happened.
+#[derive(Debug, Clone)]
+pub struct TimedEvent {
+    pub since_start: Duration,
+    pub event: SerializeEvent<'static>,
+}
+
+/// A forest of spans, built by a [`SpanTreeBuilder`].
+#[derive(Debug, Clone, Default)]
+pub struct SpanTree {
+    /// Every span, in the order they were created.
+    nodes: Vec<Node>,
+    roots: Vec<usize>,
+    /// Events that did not happen within any span.
+    events: Vec<TimedEvent>,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    id: SerializeId,
+    attributes: SerializeAttributes<'static>,
+    values: BTreeMap<CowString<'static>, SerializeValue<'static>>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    follows_from: Vec<usize>,
+    events: Vec<TimedEvent>,
+    created: Duration,
+    closed: Option<Duration>,
+    busy: Duration,
+    entries: u64,
+    /// When the span was entered, while it is.
+    entered: Option<Duration>,
+}
+
+impl SpanTree {
+    /// The spans without a parent, in the order they were created.
+    pub fn roots(&self) -> impl Iterator<Item = SpanRef<'_>> + '_ {
+        self.roots
+            .iter()
+            .map(|&index| SpanRef { tree: self, index })
+    }
+
+    /// Every span, in the order they were created.
+    pub fn spans(&self) -> impl Iterator<Item = SpanRef<'_>> + '_ {
+        (0..self.nodes.len()).map(|index| SpanRef { tree: self, index })
+    }
+
+    /// The events that did not happen within any span.
+    pub fn events(&self) -> &[TimedEvent] {
+        &self.events
+    }
+
+    /// The number of spans.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+/// A span in a [`SpanTree`].
+#[derive(Debug, Clone, Copy)]
+pub struct SpanRef<'t> {
+    tree: &'t SpanTree,
+    index: usize,
+}
+
+impl<'t> SpanRef<'t> {
+    fn node(&self) -> &'t Node {
+        &self.tree.nodes[self.index]
+    }
+
+    fn at(&self, index: usize) -> SpanRef<'t> {
+        SpanRef {
+            tree: self.tree,
+            index,
+        }
+    }
+
+    /// The id the stream referred to the span by.
+    pub fn id(&self) -> &'t SerializeId {
+        &self.node().id
+    }
+
+    pub fn name(&self) -> &'t str {
+        self.node().attributes.metadata.name.as_str()
+    }
+
+    pub fn metadata(&self) -> &'t SerializeMetadata<'static> {
+        &self.node().attributes.metadata
+    }
+
+    pub fn attributes(&self) -> &'t SerializeAttributes<'static> {
+        &self.node().attributes
+    }
+
+    /// The values recorded for the span, the latest for each field.
+    pub fn values(&self) -> impl Iterator<Item = (&'t str, &'t SerializeValue<'static>)> + 't {
+        self.node().values.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    /// The latest value recorded for the field `name`.
+    pub fn value(&self, name: &str) -> Option<&'t SerializeValue<'static>> {
+        self.values().find(|(k, _)| *k == name).map(|(_, v)| v)
+    }
+
+    pub fn parent(&self) -> Option<SpanRef<'t>> {
+        self.node().parent.map(|index| self.at(index))
+    }
+
+    /// The span's parent, its parent's parent, and so on.
+    pub fn ancestors(&self) -> impl Iterator<Item = SpanRef<'t>> + 't {
+        core::iter::successors(self.parent(), SpanRef::parent)
+    }
+
+    /// The spans created within this one, in the order they were created.
+    pub fn children(&self) -> impl Iterator<Item = SpanRef<'t>> + 't {
+        let this = *self;
+        self.node()
+            .children
+            .iter()
+            .map(move |&index| this.at(index))
+    }
+
+    /// The spans this one follows from.
+    pub fn follows_from(&self) -> impl Iterator<Item = SpanRef<'t>> + 't {
+        let this = *self;
+        self.node()
+            .follows_from
+            .iter()
+            .map(move |&index| this.at(index))
+    }
+
+    /// The events that happened within this span, but not within any of its
+    /// children.
+    pub fn events(&self) -> &'t [TimedEvent] {
+        &self.node().events
+    }
+
+    /// When the span was created.
+    pub fn created(&self) -> Duration {
+        self.node().created
+    }
+
+    /// When the span was closed, if it was.
+    pub fn closed(&self) -> Option<Duration> {
+        self.node().closed
+    }
+
+    /// The time from the span's creation to its closing, if it was closed.
+    pub fn duration(&self) -> Option<Duration> {
+        let node = self.node();
+        node.closed
+            .map(|closed| closed.saturating_sub(node.created))
+    }
+
+    /// The total time the span was entered for, not counting an entry that
+    /// was never exited.
+    pub fn busy(&self) -> Duration {
+        self.node().busy
+    }
+
+    /// The number of times the span was entered.
+    pub fn entries(&self) -> u64 {
+        self.node().entries
+    }
+}
+
+/// Builds a [`SpanTree`] from the messages of a stream. See the
+/// [module documentation](self).
+#[derive(Debug, Default)]
+pub struct SpanTreeBuilder {
+    tree: SpanTree,
+    /// The nodes of the spans that are open, by id.
+    open: BTreeMap<u64, usize>,
+    /// The entered spans, innermost last.
+    stack: Vec<usize>,
+    /// Messages about spans that have not been created yet, by id.
+    pending: BTreeMap<u64, Vec<(Duration, TracingWire<'static>)>>,
+}
+
+impl SpanTreeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `msg`, which happened `since_start` after the stream began.
+    ///
+    /// Messages that are not about spans or events are ignored.
+    pub fn push(&mut self, since_start: Duration, msg: &TracingWire<'_>) {
+        match msg {
+            TracingWire::NewSpan { id, attributes } => {
+                self.new_span(since_start, id, attributes.to_owned())
+            }
+            TracingWire::Event(event) => {
+                let parent = match &event.parent {
+                    Some(parent) => match self.open.get(&parent.id.get()) {
+                        Some(&index) => Some(index),
+                        None => return self.hold(since_start, parent.id.get(), msg),
+                    },
+                    None => self.stack.last().copied(),
+                };
+                let event = TimedEvent {
+                    since_start,
+                    event: event.to_owned(),
+                };
+                match parent {
+                    Some(index) => self.tree.nodes[index].events.push(event),
+                    None => self.tree.events.push(event),
+                }
+            }
+            TracingWire::Record { span, .. }
+            | TracingWire::Enter(span)
+            | TracingWire::Exit(span)
+            | TracingWire::CloseSpan(span)
+            | TracingWire::FollowsFrom { span, .. } => {
+                let Some(&index) = self.open.get(&span.id.get()) else {
+                    return self.hold(since_start, span.id.get(), msg);
+                };
+                self.update(since_start, index, msg);
+            }
+            _ => {}
+        }
+    }
+
+    /// Adds a message decoded by a [`host::Decoder`](crate::host::Decoder).
+    #[cfg(feature = "host")]
+    pub fn push_message(&mut self, msg: &crate::host::Message) {
+        self.push(msg.since_start, &msg.msg);
+    }
+
+    /// The spans added so far.
+    ///
+    /// Spans that are still open have no [`SpanRef::closed`] time yet, and
+    /// messages held back for spans that have not been created are missing.
+    pub fn tree(&self) -> &SpanTree {
+        &self.tree
+    }
+
+    /// The number of messages held back for spans that have not been created
+    /// yet.
+    pub fn pending(&self) -> usize {
+        self.pending.values().map(Vec::len).sum()
+    }
+
+    /// Returns the tree, dropping the messages still held back.
+    pub fn finish(self) -> SpanTree {
+        self.tree
+    }
+
+    /// Holds `msg` back until the span `id` is created.
+    fn hold(&mut self, since_start: Duration, id: u64, msg: &TracingWire<'_>) {
+        self.pending
+            .entry(id)
+            .or_default()
+            .push((since_start, msg.to_owned()));
+    }
+
+    fn new_span(
+        &mut self,
+        since_start: Duration,
+        id: &SerializeId,
+        attributes: SerializeAttributes<'static>,
+    ) {
+        let parent = match attributes.parent.as_ref().map(|p| p.id.get()) {
+            Some(parent) => match self.open.get(&parent) {
+                Some(&index) => Some(index),
+                None => {
+                    let msg = TracingWire::NewSpan {
+                        id: id.clone(),
+                        attributes,
+                    };
+                    return self.hold(since_start, parent, &msg);
+                }
+            },
+            None if attributes.is_root => None,
+            None => self.stack.last().copied(),
+        };
+
+        let index = self.tree.nodes.len();
+        self.tree.nodes.push(Node {
+            id: id.clone(),
+            attributes,
+            values: BTreeMap::new(),
+            parent,
+            children: Vec::new(),
+            follows_from: Vec::new(),
+            events: Vec::new(),
+            created: since_start,
+            closed: None,
+            busy: Duration::ZERO,
+            entries: 0,
+            entered: None,
+        });
+        match parent {
+            Some(parent) => self.tree.nodes[parent].children.push(index),
+            None => self.tree.roots.push(index),
+        }
+        // A span whose close was lost keeps its node, but not its id.
+        self.open.insert(id.id.get(), index);
+
+        if let Some(held) = self.pending.remove(&id.id.get()) {
+            for (since_start, msg) in held {
+                self.push(since_start, &msg);
+            }
+        }
+    }
+
+    fn update(&mut self, since_start: Duration, index: usize, msg: &TracingWire<'_>) {
+        match msg {
+            TracingWire::Record { values, .. } => {
+                let values = match values.to_owned() {
+                    SerializeRecord::De(values) => values,
+                    SerializeRecord::Ser(never) => match never {},
+                };
+                self.tree.nodes[index].values.extend(values);
+            }
+            TracingWire::Enter(_) => {
+                let node = &mut self.tree.nodes[index];
+                node.entries += 1;
+                node.entered.get_or_insert(since_start);
+                self.stack.push(index);
+            }
+            TracingWire::Exit(_) => {
+                if let Some(i) = self.stack.iter().rposition(|s| *s == index) {
+                    self.stack.remove(i);
+                }
+                // A span entered again while it is entered, e.g. by another
+                // thread, is only busy until the last exit.
+                if !self.stack.contains(&index) {
+                    let node = &mut self.tree.nodes[index];
+                    if let Some(entered) = node.entered.take() {
+                        node.busy += since_start.saturating_sub(entered);
+                    }
+                }
+            }
+            TracingWire::CloseSpan(id) => {
+                self.tree.nodes[index].closed = Some(since_start);
+                self.stack.retain(|s| *s != index);
+                self.open.remove(&id.id.get());
+            }
+            TracingWire::FollowsFrom { follows, .. } => {
+                if let Some(&follows) = self.open.get(&follows.id.get()) {
+                    self.tree.nodes[index].follows_from.push(follows);
+                }
+            }
+            _ => {}
+        }
+    }
+}
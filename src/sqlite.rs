@@ -0,0 +1,285 @@
+//! A sink that stores events and span lifecycles in a SQLite database.
+//!
+//! The database uses the following schema, created on demand:
+//!
+//! ```sql
+//! CREATE TABLE events (
+//!     rowid     INTEGER PRIMARY KEY,
+//!     timestamp INTEGER NOT NULL, -- nanoseconds since the UNIX epoch
+//!     level     TEXT NOT NULL,
+//!     target    TEXT NOT NULL,
+//!     name      TEXT NOT NULL,
+//!     parent    INTEGER           -- the explicit parent span id, if any
+//! );
+//! CREATE TABLE spans (
+//!     rowid     INTEGER PRIMARY KEY,
+//!     span_id   INTEGER NOT NULL,
+//!     opened_at INTEGER NOT NULL,
+//!     closed_at INTEGER,
+//!     level     TEXT NOT NULL,
+//!     target    TEXT NOT NULL,
+//!     name      TEXT NOT NULL,
+//!     parent    INTEGER
+//! );
+//! CREATE TABLE fields (
+//!     owner     TEXT NOT NULL,    -- 'event' or 'span'
+//!     owner_row INTEGER NOT NULL, -- the rowid in `events` or `spans`
+//!     name      TEXT NOT NULL,
+//!     value
+//! );
+//! ```
+//!
+//! Span ids may be reused by a subscriber once a span is closed, so spans are
+//! keyed by `rowid`, and span ids are resolved to the most recent span with
+//! that id that has not been closed yet.
+//!
+//! Writes are grouped into transactions of up to [`SqliteSink::with_batch_size`]
+//! operations. Call [`SqliteSink::flush`] to commit a partial batch; it is
+//! also committed when the sink is dropped. An operation that fails part-way
+//! is rolled back on its own, leaving the rest of its batch in place.
+
+use std::{path::Path, time::SystemTime};
+
+use rusqlite::{params, types::Value, Connection, OptionalExtension, Result, Savepoint};
+
+use crate::{
+    time::unix_nanos, RecordMap, SerializeAttributes, SerializeEvent, SerializeId, SerializeRecord,
+    SerializeRecordFields, SerializeValue,
+};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS events (
+    rowid     INTEGER PRIMARY KEY,
+    timestamp INTEGER NOT NULL,
+    level     TEXT NOT NULL,
+    target    TEXT NOT NULL,
+    name      TEXT NOT NULL,
+    parent    INTEGER
+);
+CREATE TABLE IF NOT EXISTS spans (
+    rowid     INTEGER PRIMARY KEY,
+    span_id   INTEGER NOT NULL,
+    opened_at INTEGER NOT NULL,
+    closed_at INTEGER,
+    level     TEXT NOT NULL,
+    target    TEXT NOT NULL,
+    name      TEXT NOT NULL,
+    parent    INTEGER
+);
+CREATE INDEX IF NOT EXISTS spans_by_id ON spans (span_id, closed_at);
+CREATE TABLE IF NOT EXISTS fields (
+    owner     TEXT NOT NULL,
+    owner_row INTEGER NOT NULL,
+    name      TEXT NOT NULL,
+    value
+);
+CREATE INDEX IF NOT EXISTS fields_by_owner ON fields (owner, owner_row);
+";
+
+/// Stores events and span lifecycles in a SQLite database.
+#[derive(Debug)]
+pub struct SqliteSink {
+    conn: Connection,
+    batch_size: usize,
+    pending: usize,
+}
+
+impl SqliteSink {
+    /// The default number of operations grouped into one transaction.
+    pub const DEFAULT_BATCH_SIZE: usize = 256;
+
+    /// Open (or create) the database at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    /// Use an existing connection, creating the tables if they do not exist.
+    pub fn from_connection(conn: Connection) -> Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self {
+            conn,
+            batch_size: Self::DEFAULT_BATCH_SIZE,
+            pending: 0,
+        })
+    }
+
+    /// Set the number of operations grouped into one transaction.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Returns the underlying connection, e.g. for running queries.
+    ///
+    /// Operations that have not been committed yet are visible on this
+    /// connection, but not on any other.
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    /// Store an event that occurred at `timestamp`, returning its `rowid`.
+    pub fn record_event(
+        &mut self,
+        timestamp: SystemTime,
+        event: &SerializeEvent<'_>,
+    ) -> Result<i64> {
+        let tx = self.begin()?;
+        let meta = &event.metadata;
+        tx.prepare_cached(
+            "INSERT INTO events (timestamp, level, target, name, parent)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?
+        .execute(params![
+            unix_nanos(timestamp),
+            meta.level.as_str(),
+            meta.target.as_str(),
+            meta.name.as_str(),
+            event.parent.as_ref().map(span_id),
+        ])?;
+        let row = tx.last_insert_rowid();
+
+        match &event.fields {
+            SerializeRecordFields::De(map) => insert_fields(&tx, "event", row, map)?,
+            SerializeRecordFields::Ser(never) => match *never {},
+        }
+        tx.commit()?;
+        self.end()?;
+        Ok(row)
+    }
+
+    /// Store a newly created span, returning its `rowid`.
+    ///
+    /// The values of the span's fields are not part of its attributes; record
+    /// them with [`Self::record_span_values`].
+    pub fn record_new_span(
+        &mut self,
+        timestamp: SystemTime,
+        id: &SerializeId,
+        attrs: &SerializeAttributes<'_>,
+    ) -> Result<i64> {
+        let tx = self.begin()?;
+        let meta = &attrs.metadata;
+        tx.prepare_cached(
+            "INSERT INTO spans (span_id, opened_at, level, target, name, parent)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?
+        .execute(params![
+            span_id(id),
+            unix_nanos(timestamp),
+            meta.level.as_str(),
+            meta.target.as_str(),
+            meta.name.as_str(),
+            attrs.parent.as_ref().map(span_id),
+        ])?;
+        let row = tx.last_insert_rowid();
+        tx.commit()?;
+        self.end()?;
+        Ok(row)
+    }
+
+    /// Store values recorded on an open span.
+    ///
+    /// Values for a span that is not open are silently discarded.
+    pub fn record_span_values(
+        &mut self,
+        id: &SerializeId,
+        values: &SerializeRecord<'_>,
+    ) -> Result<()> {
+        let tx = self.begin()?;
+        if let Some(row) = open_span(&tx, id)? {
+            match values {
+                SerializeRecord::De(map) => insert_fields(&tx, "span", row, map)?,
+                SerializeRecord::Ser(_) => {
+                    let SerializeRecord::De(map) = values.to_owned();
+                    insert_fields(&tx, "span", row, &map)?;
+                }
+            }
+        }
+        tx.commit()?;
+        self.end()
+    }
+
+    /// Mark an open span as closed at `timestamp`.
+    pub fn record_close(&mut self, timestamp: SystemTime, id: &SerializeId) -> Result<()> {
+        let tx = self.begin()?;
+        if let Some(row) = open_span(&tx, id)? {
+            tx.execute(
+                "UPDATE spans SET closed_at = ?1 WHERE rowid = ?2",
+                params![unix_nanos(timestamp), row],
+            )?;
+        }
+        tx.commit()?;
+        self.end()
+    }
+
+    /// Commit any operations in the current batch.
+    pub fn flush(&mut self) -> Result<()> {
+        if !self.conn.is_autocommit() {
+            self.conn.execute_batch("COMMIT")?;
+        }
+        self.pending = 0;
+        Ok(())
+    }
+
+    /// Starts the batch's transaction if needed, and a savepoint within it
+    /// for one operation, which is rolled back if it is dropped before being
+    /// committed.
+    fn begin(&mut self) -> Result<Savepoint<'_>> {
+        if self.conn.is_autocommit() {
+            self.conn.execute_batch("BEGIN")?;
+        }
+        self.conn.savepoint()
+    }
+
+    fn end(&mut self) -> Result<()> {
+        self.pending += 1;
+        if self.pending >= self.batch_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for SqliteSink {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+fn open_span(conn: &Connection, id: &SerializeId) -> Result<Option<i64>> {
+    conn.query_row(
+        "SELECT rowid FROM spans WHERE span_id = ?1 AND closed_at IS NULL ORDER BY rowid DESC LIMIT 1",
+        params![span_id(id)],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+fn insert_fields(conn: &Connection, owner: &str, row: i64, fields: &RecordMap<'_>) -> Result<()> {
+    let mut stmt = conn.prepare_cached(
+        "INSERT INTO fields (owner, owner_row, name, value) VALUES (?1, ?2, ?3, ?4)",
+    )?;
+    for (name, value) in fields.iter() {
+        stmt.execute(params![owner, row, name.as_str(), sql_value(value)])?;
+    }
+    Ok(())
+}
+
+fn sql_value(value: &SerializeValue<'_>) -> Value {
+    match value {
+        SerializeValue::I64(x) => Value::Integer(*x),
+        SerializeValue::U64(x) => match i64::try_from(*x) {
+            Ok(x) => Value::Integer(x),
+            Err(_) => Value::Text(x.to_string()),
+        },
+        SerializeValue::F64(x) => Value::Real(*x),
+        SerializeValue::Bool(x) => Value::Integer(i64::from(*x)),
+        SerializeValue::Unknown => Value::Null,
+        other => Value::Text(other.to_string()),
+    }
+}
+
+fn span_id(id: &SerializeId) -> i64 {
+    // SQLite integers are signed; keep the bit pattern of ids above `i64::MAX`.
+    id.id.get() as i64
+}
@@ -0,0 +1,930 @@
+//! Recording values that implement `Serialize` as event fields.
+//!
+//! `tracing` only accepts primitives, `Debug` and `Display` values as fields,
+//! so a struct logged with `?config` arrives as a single debug string. The
+//! [`structured_event!`](crate::structured_event) macro takes any
+//! `Serialize` value instead, and records it so that the structure survives:
+//!
+//! ```rust
+//! use serde::Serialize;
+//! use tracing_serde_structured::structured_event;
+//!
+//! #[derive(Serialize)]
+//! struct User {
+//!     name: &'static str,
+//!     admin: bool,
+//! }
+//!
+//! let user = User { name: "ferris", admin: true };
+//! structured_event!(tracing::Level::INFO, user = &user, "logged in");
+//! structured_event!(tracing::Level::DEBUG, user = &user, attempts = &3);
+//! ```
+//!
+//! With the `valuable` feature and `--cfg tracing_unstable`, each value is
+//! recorded with [`Visit::record_value`](tracing_core::field::Visit), and
+//! arrives as a [`SerializeValue::Tree`](crate::SerializeValue::Tree) of
+//! nested maps, sequences and primitives. Otherwise the value is recorded as
+//! `Debug`, formatted as compact JSON, so that it arrives as a
+//! [`DebugRecord`](crate::DebugRecord) that can still be parsed back.
+//!
+//! With the plain `tracing` macros, wrap a value in [`AsStructured`] and
+//! record the field from [`AsStructured::as_field`]. With `valuable`, the
+//! wrapper walks the value's `Serialize` implementation as the subscriber
+//! visits it, so it arrives as a tree like above without the intermediate
+//! copy [`Structured`] makes. Otherwise it is recorded as compact JSON, as
+//! it also is when recorded with `?` or `%`:
+//!
+//! ```rust
+//! use tracing_serde_structured::structured::AsStructured;
+//!
+//! let ports = [80u16, 443];
+//! tracing::info_span!("listen", ports = AsStructured(&ports).as_field());
+//! tracing::info!(ports = %AsStructured(&ports), "listening");
+//! ```
+//!
+//! `tracing` does not let other crates implement its field value trait, so
+//! the wrapper itself cannot be given as a field; `as_field` returns the
+//! `valuable` value, or the `Debug` value, that `tracing` accepts.
+
+use core::fmt;
+
+use serde::Serialize;
+use serde_json::Value as Json;
+
+#[doc(hidden)]
+pub use tracing as __tracing;
+
+/// Records an event with fields that implement `Serialize`.
+///
+/// Takes a level, then one or more `name = &value` fields, then optionally
+/// a format string and its arguments, as with `tracing::event!`. Each value
+/// is captured with [`Structured::new`].
+///
+/// See the [module documentation](crate::structured) for an example.
+#[macro_export]
+#[cfg_attr(docsrs, doc(cfg(feature = "structured-event")))]
+macro_rules! structured_event {
+    ($lvl:expr, $($field:ident = $value:expr),+ $(,)?) => {
+        $crate::structured::__tracing::event!(
+            $lvl,
+            $($field = $crate::structured::Structured::new($value).as_field()),+
+        )
+    };
+    ($lvl:expr, $($field:ident = $value:expr),+ , $($arg:tt)+) => {
+        $crate::structured::__tracing::event!(
+            $lvl,
+            $($field = $crate::structured::Structured::new($value).as_field()),+ ,
+            $($arg)+
+        )
+    };
+}
+
+/// A value captured for [`structured_event!`](crate::structured_event).
+///
+/// The value is serialized into a `serde_json::Value` when captured, so it
+/// can be recorded in whichever way `tracing` accepts it.
+#[derive(Clone, PartialEq)]
+pub struct Structured(Json);
+
+impl Structured {
+    /// Captures `value`.
+    ///
+    /// If `value` fails to serialize, the error message is captured as a
+    /// string instead, so that logging never panics.
+    pub fn new<T: Serialize + ?Sized>(value: &T) -> Self {
+        Structured(
+            serde_json::to_value(value)
+                .unwrap_or_else(|e| Json::String(format!("<failed to serialize: {e}>"))),
+        )
+    }
+
+    /// The captured value, as serialized.
+    pub fn as_json(&self) -> &Json {
+        &self.0
+    }
+
+    /// The value to give `tracing` as a field.
+    #[cfg(all(tracing_unstable, feature = "valuable"))]
+    pub fn as_field(&self) -> valuable_crate::Value<'_> {
+        valuable_crate::Valuable::as_value(self)
+    }
+
+    /// The value to give `tracing` as a field.
+    #[cfg(not(all(tracing_unstable, feature = "valuable")))]
+    pub fn as_field(&self) -> tracing_core::field::DebugValue<&Self> {
+        tracing_core::field::debug(self)
+    }
+}
+
+/// Formats the value as compact JSON.
+impl fmt::Debug for Structured {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+/// Records a `Serialize` value as a field without copying it.
+///
+/// With the `valuable` feature and `--cfg tracing_unstable`, the wrapper is
+/// [`Valuable`](https://docs.rs/valuable): each map or sequence in the value
+/// is visited by serializing it as `tracing` asks for it, so nothing is
+/// copied, though nested values are serialized more than once.
+///
+/// Formatted with `{:?}` or `{}`, the value is written as compact JSON,
+/// straight to the formatter. If `T` fails to serialize, formatting fails
+/// with [`fmt::Error`].
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct AsStructured<T>(pub T);
+
+impl<T: Serialize> AsStructured<T> {
+    /// The value to give `tracing` as a field.
+    #[cfg(all(tracing_unstable, feature = "valuable"))]
+    pub fn as_field(&self) -> valuable_crate::Value<'_> {
+        valuable_crate::Valuable::as_value(self)
+    }
+
+    /// The value to give `tracing` as a field.
+    #[cfg(not(all(tracing_unstable, feature = "valuable")))]
+    pub fn as_field(&self) -> tracing_core::field::DebugValue<&Self> {
+        tracing_core::field::debug(self)
+    }
+}
+
+impl<T: Serialize> fmt::Debug for AsStructured<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        serde_json::to_writer(FmtWriter(f), &self.0).map_err(|_| fmt::Error)
+    }
+}
+
+impl<T: Serialize> fmt::Display for AsStructured<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// Adapts a formatter to the `io::Write` that `serde_json` writes to.
+struct FmtWriter<'a, 'b>(&'a mut fmt::Formatter<'b>);
+
+impl std::io::Write for FmtWriter<'_, '_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        // `serde_json` only splits its output between characters.
+        let s = core::str::from_utf8(buf)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        self.0.write_str(s).map_err(std::io::Error::other)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(all(tracing_unstable, feature = "valuable"))]
+mod valuable_impls {
+    use serde_json::Value as Json;
+    use valuable_crate::{Listable, Mappable, Valuable, Value, Visit};
+
+    use super::Structured;
+
+    /// A value nested inside a [`Structured`] value.
+    struct Node<'a>(&'a Json);
+
+    fn as_value<'a, T: Listable + Mappable>(json: &'a Json, this: &'a T) -> Value<'a> {
+        match json {
+            Json::Null => Value::Unit,
+            Json::Bool(b) => Value::Bool(*b),
+            Json::Number(n) => match (n.as_u64(), n.as_i64()) {
+                (Some(u), _) => Value::U64(u),
+                (None, Some(i)) => Value::I64(i),
+                _ => Value::F64(n.as_f64().unwrap_or(f64::NAN)),
+            },
+            Json::String(s) => Value::String(s),
+            Json::Array(_) => Value::Listable(this),
+            Json::Object(_) => Value::Mappable(this),
+        }
+    }
+
+    fn visit(json: &Json, visit: &mut dyn Visit) {
+        match json {
+            Json::Array(items) => {
+                for item in items {
+                    visit.visit_value(Node(item).as_value());
+                }
+            }
+            Json::Object(map) => {
+                for (k, v) in map {
+                    visit.visit_entry(Value::String(k), Node(v).as_value());
+                }
+            }
+            _ => visit.visit_value(Node(json).as_value()),
+        }
+    }
+
+    fn size_hint(json: &Json) -> (usize, Option<usize>) {
+        let len = match json {
+            Json::Array(items) => items.len(),
+            Json::Object(map) => map.len(),
+            _ => 0,
+        };
+        (len, Some(len))
+    }
+
+    macro_rules! impl_valuable {
+        ($ty:ty, $json:ident => $get:expr) => {
+            impl Valuable for $ty {
+                fn as_value(&self) -> Value<'_> {
+                    let $json = self;
+                    as_value($get, self)
+                }
+
+                fn visit(&self, v: &mut dyn Visit) {
+                    let $json = self;
+                    visit($get, v)
+                }
+            }
+
+            impl Listable for $ty {
+                fn size_hint(&self) -> (usize, Option<usize>) {
+                    let $json = self;
+                    size_hint($get)
+                }
+            }
+
+            impl Mappable for $ty {
+                fn size_hint(&self) -> (usize, Option<usize>) {
+                    let $json = self;
+                    size_hint($get)
+                }
+            }
+        };
+    }
+
+    impl_valuable!(Node<'_>, node => node.0);
+    impl_valuable!(Structured, s => &s.0);
+}
+
+/// Records an [`AsStructured`] value with `valuable`, by walking its
+/// `Serialize` implementation as `tracing` visits it.
+///
+/// A value only says what shape it has by serializing itself, so each map or
+/// sequence is serialized once to find its shape, stopping at the first call,
+/// and again to visit its contents. Primitives and strings are visited as
+/// they are serialized, which is how strings can be borrowed for the visit.
+#[cfg(all(tracing_unstable, feature = "valuable"))]
+mod as_structured_impls {
+    use core::fmt;
+
+    use serde::ser::{self, Impossible, Serialize, Serializer};
+    use valuable_crate::{
+        Fields, Listable, Mappable, StructDef, Structable, Valuable, Value, Visit,
+    };
+
+    use super::{AsStructured, Structured};
+
+    /// How a value serializes itself.
+    #[derive(Debug)]
+    enum Shape {
+        Primitive(Value<'static>),
+        /// A string, which is only borrowed while it is serialized.
+        Str,
+        Seq,
+        Map,
+        NewtypeVariant,
+        TupleVariant,
+        StructVariant,
+    }
+
+    impl Shape {
+        fn of<T: Serialize + ?Sized>(value: &T) -> Shape {
+            let mut shape = Shape::Primitive(Value::Unit);
+            let found = value.serialize(Leaf(|v: Value<'_>| shape = Shape::primitive(v)));
+            match found {
+                Err(Stop::Compound(compound)) => compound,
+                _ => shape,
+            }
+        }
+
+        fn primitive(value: Value<'_>) -> Shape {
+            Shape::Primitive(match value {
+                Value::Bool(x) => Value::Bool(x),
+                Value::Char(x) => Value::Char(x),
+                Value::F32(x) => Value::F32(x),
+                Value::F64(x) => Value::F64(x),
+                Value::I8(x) => Value::I8(x),
+                Value::I16(x) => Value::I16(x),
+                Value::I32(x) => Value::I32(x),
+                Value::I64(x) => Value::I64(x),
+                Value::I128(x) => Value::I128(x),
+                Value::U8(x) => Value::U8(x),
+                Value::U16(x) => Value::U16(x),
+                Value::U32(x) => Value::U32(x),
+                Value::U64(x) => Value::U64(x),
+                Value::U128(x) => Value::U128(x),
+                Value::String(_) => return Shape::Str,
+                _ => Value::Unit,
+            })
+        }
+    }
+
+    /// Why serializing a value for a visit stopped early.
+    #[derive(Debug)]
+    enum Stop {
+        /// The value is a map or sequence, which [`Leaf`] does not serialize.
+        Compound(Shape),
+        Failed,
+    }
+
+    impl fmt::Display for Stop {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("failed to serialize a structured value")
+        }
+    }
+
+    impl std::error::Error for Stop {}
+
+    impl ser::Error for Stop {
+        fn custom<T: fmt::Display>(_: T) -> Self {
+            Stop::Failed
+        }
+    }
+
+    /// Calls `f` with the value if it is a primitive or a string, or with an
+    /// [`AsStructured`] of it that `f` can visit.
+    fn with_value<T: Serialize + ?Sized>(value: &T, mut f: impl FnMut(Value<'_>)) {
+        if let Err(Stop::Compound(_)) = value.serialize(Leaf(&mut f)) {
+            f(AsStructured(value).as_value());
+        }
+    }
+
+    /// Passes a primitive or string to its function, and stops at anything
+    /// else.
+    struct Leaf<F>(F);
+
+    impl<F: FnMut(Value<'_>)> Leaf<F> {
+        fn call(mut self, value: Value<'_>) -> Result<(), Stop> {
+            (self.0)(value);
+            Ok(())
+        }
+    }
+
+    impl<F: FnMut(Value<'_>)> Serializer for Leaf<F> {
+        type Ok = ();
+        type Error = Stop;
+        type SerializeSeq = Impossible<(), Stop>;
+        type SerializeTuple = Impossible<(), Stop>;
+        type SerializeTupleStruct = Impossible<(), Stop>;
+        type SerializeTupleVariant = Impossible<(), Stop>;
+        type SerializeMap = Impossible<(), Stop>;
+        type SerializeStruct = Impossible<(), Stop>;
+        type SerializeStructVariant = Impossible<(), Stop>;
+
+        fn serialize_bool(self, v: bool) -> Result<(), Stop> {
+            self.call(Value::Bool(v))
+        }
+
+        fn serialize_i8(self, v: i8) -> Result<(), Stop> {
+            self.call(Value::I8(v))
+        }
+
+        fn serialize_i16(self, v: i16) -> Result<(), Stop> {
+            self.call(Value::I16(v))
+        }
+
+        fn serialize_i32(self, v: i32) -> Result<(), Stop> {
+            self.call(Value::I32(v))
+        }
+
+        fn serialize_i64(self, v: i64) -> Result<(), Stop> {
+            self.call(Value::I64(v))
+        }
+
+        fn serialize_i128(self, v: i128) -> Result<(), Stop> {
+            self.call(Value::I128(v))
+        }
+
+        fn serialize_u8(self, v: u8) -> Result<(), Stop> {
+            self.call(Value::U8(v))
+        }
+
+        fn serialize_u16(self, v: u16) -> Result<(), Stop> {
+            self.call(Value::U16(v))
+        }
+
+        fn serialize_u32(self, v: u32) -> Result<(), Stop> {
+            self.call(Value::U32(v))
+        }
+
+        fn serialize_u64(self, v: u64) -> Result<(), Stop> {
+            self.call(Value::U64(v))
+        }
+
+        fn serialize_u128(self, v: u128) -> Result<(), Stop> {
+            self.call(Value::U128(v))
+        }
+
+        fn serialize_f32(self, v: f32) -> Result<(), Stop> {
+            self.call(Value::F32(v))
+        }
+
+        fn serialize_f64(self, v: f64) -> Result<(), Stop> {
+            self.call(Value::F64(v))
+        }
+
+        fn serialize_char(self, v: char) -> Result<(), Stop> {
+            self.call(Value::Char(v))
+        }
+
+        fn serialize_str(self, v: &str) -> Result<(), Stop> {
+            self.call(Value::String(v))
+        }
+
+        fn serialize_bytes(self, _: &[u8]) -> Result<(), Stop> {
+            Err(Stop::Compound(Shape::Seq))
+        }
+
+        fn serialize_none(self) -> Result<(), Stop> {
+            self.call(Value::Unit)
+        }
+
+        fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Stop> {
+            value.serialize(self)
+        }
+
+        fn serialize_unit(self) -> Result<(), Stop> {
+            self.call(Value::Unit)
+        }
+
+        fn serialize_unit_struct(self, _: &'static str) -> Result<(), Stop> {
+            self.call(Value::Unit)
+        }
+
+        fn serialize_unit_variant(
+            self,
+            _: &'static str,
+            _: u32,
+            variant: &'static str,
+        ) -> Result<(), Stop> {
+            self.call(Value::String(variant))
+        }
+
+        fn serialize_newtype_struct<T: Serialize + ?Sized>(
+            self,
+            _: &'static str,
+            value: &T,
+        ) -> Result<(), Stop> {
+            value.serialize(self)
+        }
+
+        fn serialize_newtype_variant<T: Serialize + ?Sized>(
+            self,
+            _: &'static str,
+            _: u32,
+            _: &'static str,
+            _: &T,
+        ) -> Result<(), Stop> {
+            Err(Stop::Compound(Shape::NewtypeVariant))
+        }
+
+        fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, Stop> {
+            Err(Stop::Compound(Shape::Seq))
+        }
+
+        fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, Stop> {
+            Err(Stop::Compound(Shape::Seq))
+        }
+
+        fn serialize_tuple_struct(
+            self,
+            _: &'static str,
+            _: usize,
+        ) -> Result<Self::SerializeTupleStruct, Stop> {
+            Err(Stop::Compound(Shape::Seq))
+        }
+
+        fn serialize_tuple_variant(
+            self,
+            _: &'static str,
+            _: u32,
+            _: &'static str,
+            _: usize,
+        ) -> Result<Self::SerializeTupleVariant, Stop> {
+            Err(Stop::Compound(Shape::TupleVariant))
+        }
+
+        fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, Stop> {
+            Err(Stop::Compound(Shape::Map))
+        }
+
+        fn serialize_struct(
+            self,
+            _: &'static str,
+            _: usize,
+        ) -> Result<Self::SerializeStruct, Stop> {
+            Err(Stop::Compound(Shape::Map))
+        }
+
+        fn serialize_struct_variant(
+            self,
+            _: &'static str,
+            _: u32,
+            _: &'static str,
+            _: usize,
+        ) -> Result<Self::SerializeStructVariant, Stop> {
+            Err(Stop::Compound(Shape::StructVariant))
+        }
+    }
+
+    impl<T: Serialize> Valuable for AsStructured<T> {
+        fn as_value(&self) -> Value<'_> {
+            match Shape::of(&self.0) {
+                Shape::Primitive(value) => value,
+                // Visited as a newtype struct, which stands for its field.
+                Shape::Str => Value::Structable(self),
+                Shape::Seq => Value::Listable(self),
+                _ => Value::Mappable(self),
+            }
+        }
+
+        fn visit(&self, visit: &mut dyn Visit) {
+            let _ = self.0.serialize(Walker {
+                visit,
+                value: &self.0,
+                fields: false,
+            });
+        }
+    }
+
+    impl<T: Serialize> Listable for AsStructured<T> {
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            (0, None)
+        }
+    }
+
+    impl<T: Serialize> Mappable for AsStructured<T> {
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            (0, None)
+        }
+    }
+
+    impl<T: Serialize> Structable for AsStructured<T> {
+        fn definition(&self) -> StructDef<'_> {
+            StructDef::new_dynamic("AsStructured", Fields::Unnamed(1))
+        }
+    }
+
+    /// The fields of the enum variant `T` serializes as, visited by
+    /// serializing `T` again.
+    struct VariantFields<'t, T: ?Sized>(&'t T);
+
+    impl<T: Serialize + ?Sized> Valuable for VariantFields<'_, T> {
+        fn as_value(&self) -> Value<'_> {
+            match Shape::of(self.0) {
+                Shape::TupleVariant => Value::Listable(self),
+                _ => Value::Mappable(self),
+            }
+        }
+
+        fn visit(&self, visit: &mut dyn Visit) {
+            let _ = self.0.serialize(Walker {
+                visit,
+                value: self.0,
+                fields: true,
+            });
+        }
+    }
+
+    impl<T: Serialize + ?Sized> Listable for VariantFields<'_, T> {
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            (0, None)
+        }
+    }
+
+    impl<T: Serialize + ?Sized> Mappable for VariantFields<'_, T> {
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            (0, None)
+        }
+    }
+
+    /// Visits the contents of `value` as it serializes itself.
+    struct Walker<'v, 't, T: ?Sized> {
+        visit: &'v mut dyn Visit,
+        value: &'t T,
+        /// Whether to visit the fields of an enum variant, rather than the
+        /// variant as a map of one entry.
+        fields: bool,
+    }
+
+    impl<'v, T: Serialize + ?Sized> Walker<'v, '_, T> {
+        fn value(self, value: Value<'_>) -> Result<(), Stop> {
+            self.visit.visit_value(value);
+            Ok(())
+        }
+
+        fn compound(self) -> Result<Compound<'v>, Stop> {
+            Ok(Compound {
+                visit: Some(self.visit),
+                key: None,
+            })
+        }
+
+        fn variant(self, variant: &'static str) -> Result<Compound<'v>, Stop> {
+            if self.fields {
+                return self.compound();
+            }
+            let fields = VariantFields(self.value);
+            self.visit
+                .visit_entry(Value::String(variant), fields.as_value());
+            Ok(Compound {
+                visit: None,
+                key: None,
+            })
+        }
+    }
+
+    impl<'v, T: Serialize + ?Sized> Serializer for Walker<'v, '_, T> {
+        type Ok = ();
+        type Error = Stop;
+        type SerializeSeq = Compound<'v>;
+        type SerializeTuple = Compound<'v>;
+        type SerializeTupleStruct = Compound<'v>;
+        type SerializeTupleVariant = Compound<'v>;
+        type SerializeMap = Compound<'v>;
+        type SerializeStruct = Compound<'v>;
+        type SerializeStructVariant = Compound<'v>;
+
+        fn serialize_bool(self, v: bool) -> Result<(), Stop> {
+            self.value(Value::Bool(v))
+        }
+
+        fn serialize_i8(self, v: i8) -> Result<(), Stop> {
+            self.value(Value::I8(v))
+        }
+
+        fn serialize_i16(self, v: i16) -> Result<(), Stop> {
+            self.value(Value::I16(v))
+        }
+
+        fn serialize_i32(self, v: i32) -> Result<(), Stop> {
+            self.value(Value::I32(v))
+        }
+
+        fn serialize_i64(self, v: i64) -> Result<(), Stop> {
+            self.value(Value::I64(v))
+        }
+
+        fn serialize_i128(self, v: i128) -> Result<(), Stop> {
+            self.value(Value::I128(v))
+        }
+
+        fn serialize_u8(self, v: u8) -> Result<(), Stop> {
+            self.value(Value::U8(v))
+        }
+
+        fn serialize_u16(self, v: u16) -> Result<(), Stop> {
+            self.value(Value::U16(v))
+        }
+
+        fn serialize_u32(self, v: u32) -> Result<(), Stop> {
+            self.value(Value::U32(v))
+        }
+
+        fn serialize_u64(self, v: u64) -> Result<(), Stop> {
+            self.value(Value::U64(v))
+        }
+
+        fn serialize_u128(self, v: u128) -> Result<(), Stop> {
+            self.value(Value::U128(v))
+        }
+
+        fn serialize_f32(self, v: f32) -> Result<(), Stop> {
+            self.value(Value::F32(v))
+        }
+
+        fn serialize_f64(self, v: f64) -> Result<(), Stop> {
+            self.value(Value::F64(v))
+        }
+
+        fn serialize_char(self, v: char) -> Result<(), Stop> {
+            self.value(Value::Char(v))
+        }
+
+        /// A string is visited as the field of a newtype struct.
+        fn serialize_str(self, v: &str) -> Result<(), Stop> {
+            self.visit.visit_unnamed_fields(&[Value::String(v)]);
+            Ok(())
+        }
+
+        fn serialize_bytes(self, v: &[u8]) -> Result<(), Stop> {
+            for byte in v {
+                self.visit.visit_value(Value::U8(*byte));
+            }
+            Ok(())
+        }
+
+        fn serialize_none(self) -> Result<(), Stop> {
+            self.value(Value::Unit)
+        }
+
+        fn serialize_some<U: Serialize + ?Sized>(self, value: &U) -> Result<(), Stop> {
+            value.serialize(self)
+        }
+
+        fn serialize_unit(self) -> Result<(), Stop> {
+            self.value(Value::Unit)
+        }
+
+        fn serialize_unit_struct(self, _: &'static str) -> Result<(), Stop> {
+            self.value(Value::Unit)
+        }
+
+        fn serialize_unit_variant(
+            self,
+            _: &'static str,
+            _: u32,
+            variant: &'static str,
+        ) -> Result<(), Stop> {
+            self.serialize_str(variant)
+        }
+
+        fn serialize_newtype_struct<U: Serialize + ?Sized>(
+            self,
+            _: &'static str,
+            value: &U,
+        ) -> Result<(), Stop> {
+            value.serialize(self)
+        }
+
+        fn serialize_newtype_variant<U: Serialize + ?Sized>(
+            self,
+            _: &'static str,
+            _: u32,
+            variant: &'static str,
+            value: &U,
+        ) -> Result<(), Stop> {
+            with_value(value, |v| self.visit.visit_entry(Value::String(variant), v));
+            Ok(())
+        }
+
+        fn serialize_seq(self, _: Option<usize>) -> Result<Compound<'v>, Stop> {
+            self.compound()
+        }
+
+        fn serialize_tuple(self, _: usize) -> Result<Compound<'v>, Stop> {
+            self.compound()
+        }
+
+        fn serialize_tuple_struct(self, _: &'static str, _: usize) -> Result<Compound<'v>, Stop> {
+            self.compound()
+        }
+
+        fn serialize_tuple_variant(
+            self,
+            _: &'static str,
+            _: u32,
+            variant: &'static str,
+            _: usize,
+        ) -> Result<Compound<'v>, Stop> {
+            self.variant(variant)
+        }
+
+        fn serialize_map(self, _: Option<usize>) -> Result<Compound<'v>, Stop> {
+            self.compound()
+        }
+
+        fn serialize_struct(self, _: &'static str, _: usize) -> Result<Compound<'v>, Stop> {
+            self.compound()
+        }
+
+        fn serialize_struct_variant(
+            self,
+            _: &'static str,
+            _: u32,
+            variant: &'static str,
+            _: usize,
+        ) -> Result<Compound<'v>, Stop> {
+            self.variant(variant)
+        }
+    }
+
+    /// Visits the elements or entries of a map or sequence, or skips them if
+    /// there is nothing to visit.
+    struct Compound<'v> {
+        visit: Option<&'v mut dyn Visit>,
+        /// A key serialized on its own, copied until its value comes.
+        key: Option<Structured>,
+    }
+
+    impl Compound<'_> {
+        fn element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Stop> {
+            if let Some(visit) = &mut self.visit {
+                with_value(value, |v| visit.visit_value(v));
+            }
+            Ok(())
+        }
+
+        fn field<T: Serialize + ?Sized>(
+            &mut self,
+            key: &'static str,
+            value: &T,
+        ) -> Result<(), Stop> {
+            if let Some(visit) = &mut self.visit {
+                with_value(value, |v| visit.visit_entry(Value::String(key), v));
+            }
+            Ok(())
+        }
+    }
+
+    macro_rules! impl_compound {
+        ($($trait:ident::$method:ident),*) => {
+            $(
+                impl ser::$trait for Compound<'_> {
+                    type Ok = ();
+                    type Error = Stop;
+
+                    fn $method<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Stop> {
+                        self.element(value)
+                    }
+
+                    fn end(self) -> Result<(), Stop> {
+                        Ok(())
+                    }
+                }
+            )*
+        };
+    }
+
+    impl_compound!(
+        SerializeSeq::serialize_element,
+        SerializeTuple::serialize_element,
+        SerializeTupleStruct::serialize_field,
+        SerializeTupleVariant::serialize_field
+    );
+
+    impl ser::SerializeStruct for Compound<'_> {
+        type Ok = ();
+        type Error = Stop;
+
+        fn serialize_field<T: Serialize + ?Sized>(
+            &mut self,
+            key: &'static str,
+            value: &T,
+        ) -> Result<(), Stop> {
+            self.field(key, value)
+        }
+
+        fn end(self) -> Result<(), Stop> {
+            Ok(())
+        }
+    }
+
+    impl ser::SerializeStructVariant for Compound<'_> {
+        type Ok = ();
+        type Error = Stop;
+
+        fn serialize_field<T: Serialize + ?Sized>(
+            &mut self,
+            key: &'static str,
+            value: &T,
+        ) -> Result<(), Stop> {
+            self.field(key, value)
+        }
+
+        fn end(self) -> Result<(), Stop> {
+            Ok(())
+        }
+    }
+
+    impl ser::SerializeMap for Compound<'_> {
+        type Ok = ();
+        type Error = Stop;
+
+        fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Stop> {
+            self.key = Some(Structured::new(key));
+            Ok(())
+        }
+
+        fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Stop> {
+            let key = self.key.take().ok_or(Stop::Failed)?;
+            if let Some(visit) = &mut self.visit {
+                with_value(value, |v| visit.visit_entry(key.as_value(), v));
+            }
+            Ok(())
+        }
+
+        fn serialize_entry<K, V>(&mut self, key: &K, value: &V) -> Result<(), Stop>
+        where
+            K: Serialize + ?Sized,
+            V: Serialize + ?Sized,
+        {
+            if let Some(visit) = &mut self.visit {
+                with_value(key, |k| with_value(value, |v| visit.visit_entry(k, v)));
+            }
+            Ok(())
+        }
+
+        fn end(self) -> Result<(), Stop> {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,187 @@
+//! Writing each event to several outputs, in different formats, while
+//! visiting its fields only once.
+//!
+//! A deployment that sends postcard frames to a collector and also keeps
+//! JSON lines on disk would serialize every event twice, and so visit its
+//! fields twice: formatting its `Debug` values twice, for one. A [`Tee`]
+//! visits them once, into a map of values, and serializes the map in each
+//! format instead:
+//!
+//! ```rust
+//! # #[cfg(feature = "serde-json")] {
+//! # use tracing_core::{Event, Metadata, Subscriber};
+//! # use tracing_core::span::{Attributes, Id, Record};
+//! use std::sync::Mutex;
+//! use tracing_serde_structured::{json_lines, tee::Tee};
+//!
+//! struct Forwarder {
+//!     out: Mutex<Tee<json_lines::Writer<Vec<u8>>, json_lines::Writer<std::io::Stderr>>>,
+//! }
+//!
+//! impl Subscriber for Forwarder {
+//!     fn event(&self, event: &Event<'_>) {
+//!         let _ = self.out.lock().unwrap().write_event(event);
+//!     }
+//!
+//!     // ...
+//!     # fn enabled(&self, _: &Metadata<'_>) -> bool { true }
+//!     # fn new_span(&self, _: &Attributes<'_>) -> Id { Id::from_u64(1) }
+//!     # fn record(&self, _: &Id, _: &Record<'_>) {}
+//!     # fn record_follows_from(&self, _: &Id, _: &Id) {}
+//!     # fn enter(&self, _: &Id) {}
+//!     # fn exit(&self, _: &Id) {}
+//! }
+//!
+//! let forwarder = Forwarder {
+//!     out: Mutex::new(Tee::new(
+//!         json_lines::Writer::new(Vec::new()),
+//!         json_lines::Writer::new(std::io::stderr()),
+//!     )),
+//! };
+//! # }
+//! ```
+//!
+//! Outputs are [`Sink`]s, which [`json_lines::Writer`](crate::json_lines::Writer),
+//! [`cbor::SequenceWriter`](crate::cbor::SequenceWriter) and
+//! [`FramedWriter`](crate::embedded_io::FramedWriter) are, and so is a
+//! `Tee`: nest them for more than two outputs.
+//!
+//! The map orders the fields by name, where a [`SerializeEvent`] serialized
+//! from the event itself has them in the order of the callsite.
+
+use alloc::collections::BTreeMap;
+use core::fmt;
+
+use serde::Serialize;
+use tracing_core::Event;
+
+use crate::{AsSerde, HashVisit, SerializeEvent, SerializeRecordFields};
+
+/// An output that serializes values in some format.
+pub trait Sink {
+    type Error;
+
+    /// Serializes `value` and writes it as one message.
+    fn write<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: Serialize + ?Sized;
+}
+
+/// Writes to two [`Sink`]s. See the [module documentation](self).
+#[derive(Debug, Default, Clone)]
+pub struct Tee<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A: Sink, B: Sink> Tee<A, B> {
+    pub fn new(first: A, second: B) -> Self {
+        Self { first, second }
+    }
+
+    /// Writes `event` to both sinks, visiting its fields once.
+    pub fn write_event(&mut self, event: &Event<'_>) -> Result<(), Error<A::Error, B::Error>> {
+        let live = event.as_serde();
+        let mut visit = HashVisit(BTreeMap::new());
+        event.record(&mut visit);
+        let event: SerializeEvent<'_> = SerializeEvent {
+            fields: SerializeRecordFields::De(visit.0),
+            metadata: live.metadata,
+            parent: live.parent,
+        };
+        self.write(&event)
+    }
+
+    pub fn get_ref(&self) -> (&A, &B) {
+        (&self.first, &self.second)
+    }
+
+    pub fn get_mut(&mut self) -> (&mut A, &mut B) {
+        (&mut self.first, &mut self.second)
+    }
+
+    pub fn into_inner(self) -> (A, B) {
+        (self.first, self.second)
+    }
+}
+
+/// Writes to the second sink even if writing to the first fails, so that
+/// one broken output does not starve the other.
+impl<A: Sink, B: Sink> Sink for Tee<A, B> {
+    type Error = Error<A::Error, B::Error>;
+
+    fn write<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let first = self.first.write(value);
+        let second = self.second.write(value);
+        first.map_err(Error::First)?;
+        second.map_err(Error::Second)
+    }
+}
+
+/// Errors returned when writing to a [`Tee`], from the first sink that
+/// failed.
+#[derive(Debug)]
+pub enum Error<A, B> {
+    First(A),
+    Second(B),
+}
+
+impl<A: fmt::Display, B: fmt::Display> fmt::Display for Error<A, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::First(e) => write!(f, "first sink: {e}"),
+            Error::Second(e) => write!(f, "second sink: {e}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<A, B> std::error::Error for Error<A, B>
+where
+    A: fmt::Debug + fmt::Display,
+    B: fmt::Debug + fmt::Display,
+{
+}
+
+#[cfg(feature = "serde-json")]
+impl<W: std::io::Write> Sink for crate::json_lines::Writer<W> {
+    type Error = std::io::Error;
+
+    fn write<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        crate::json_lines::Writer::write(self, value)
+    }
+}
+
+#[cfg(feature = "cbor")]
+impl<W: std::io::Write> Sink for crate::cbor::SequenceWriter<W> {
+    type Error = crate::cbor::Error;
+
+    fn write<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        crate::cbor::SequenceWriter::write(self, value)
+    }
+}
+
+#[cfg(feature = "embedded-io")]
+impl<W, const N: usize, F> Sink for crate::embedded_io::FramedWriter<W, N, F>
+where
+    W: ::embedded_io::Write,
+    F: crate::framing::Framing,
+{
+    type Error = crate::embedded_io::Error<W::Error>;
+
+    fn write<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        crate::embedded_io::FramedWriter::write(self, value)
+    }
+}
@@ -0,0 +1,143 @@
+//! Assertions that a message survives being encoded and decoded.
+//!
+//! Each helper serializes the message, deserializes it again, and panics
+//! with both the original and the decoded value if they are not equal, so a
+//! test suite can check that its messages stay readable in a format with a
+//! single line:
+//!
+//! ```rust
+//! use tracing_serde_structured::{builder::SerializeEventBuilder, test_util};
+//!
+//! let event = SerializeEventBuilder::new()
+//!     .name("connected")
+//!     .field("port", 8080u64)
+//!     .build();
+//!
+//! test_util::assert_roundtrip_postcard(&event);
+//! test_util::assert_roundtrip_json(&event);
+//! ```
+//!
+//! Values captured from `tracing` with `as_serde` work too, and are compared
+//! against the detached values they decode to. With the `strip-locations`
+//! feature, metadata that has a file or line no longer matches what it decodes
+//! to, since those are left out of the encoding.
+
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    wire::{
+        delta::{DeltaFrame, SourceFrame},
+        SerializeAttributesRef, SerializeEventInterned, SerializeEventRef, SerializePanic,
+        TracingWire,
+    },
+    CowString, Form, SerializeAttributes, SerializeEvent, SerializeId, SerializeLevel,
+    SerializeMetadata, SerializeRecord, SerializeValue,
+};
+
+/// A message that can be compared with what it decodes to.
+pub trait RoundTrip: Serialize + fmt::Debug {
+    /// The type this message deserializes as, borrowing from the encoded
+    /// bytes.
+    type Decoded<'de>: Deserialize<'de> + fmt::Debug;
+
+    /// Whether `decoded` holds the same data as `self`.
+    fn eq_decoded(&self, decoded: &Self::Decoded<'_>) -> bool;
+}
+
+macro_rules! impl_round_trip {
+    ($($ty:ident<$a:lifetime>),* $(,)?) => {$(
+        impl<$a> RoundTrip for $ty<$a> {
+            type Decoded<'de> = $ty<'de>;
+
+            fn eq_decoded(&self, decoded: &Self::Decoded<'_>) -> bool {
+                self == decoded
+            }
+        }
+    )*};
+    ($($ty:ident<$a:lifetime, F>),* $(,)?) => {$(
+        impl<$a, F: Form> RoundTrip for $ty<$a, F> {
+            type Decoded<'de> = $ty<'de>;
+
+            fn eq_decoded(&self, decoded: &Self::Decoded<'_>) -> bool {
+                self == decoded
+            }
+        }
+    )*};
+    ($($ty:ident),* $(,)?) => {$(
+        impl RoundTrip for $ty {
+            type Decoded<'de> = $ty;
+
+            fn eq_decoded(&self, decoded: &Self::Decoded<'_>) -> bool {
+                self == decoded
+            }
+        }
+    )*};
+}
+
+impl_round_trip!(SerializeLevel, SerializeId, SerializeAttributesRef);
+impl_round_trip!(
+    CowString<'a>,
+    SerializeValue<'a>,
+    SerializeMetadata<'a>,
+    SerializeAttributes<'a>,
+);
+impl_round_trip!(
+    SerializeEvent<'a, F>,
+    SerializeRecord<'a, F>,
+    SerializeEventRef<'a, F>,
+    SerializeEventInterned<'a, F>,
+    SerializePanic<'a, F>,
+    TracingWire<'a, F>,
+    DeltaFrame<'a, F>,
+    SourceFrame<'a, F>,
+);
+
+/// Asserts that `msg` decodes to an equal value after being encoded with
+/// [postcard](https://docs.rs/postcard).
+///
+/// # Panics
+///
+/// If `msg` fails to encode, its encoding fails to decode, or the decoded
+/// value is not equal to `msg`.
+#[track_caller]
+pub fn assert_roundtrip_postcard<T: RoundTrip + ?Sized>(msg: &T) {
+    let bytes = match ::postcard::to_allocvec(msg) {
+        Ok(bytes) => bytes,
+        Err(e) => panic!("failed to encode {msg:?} with postcard: {e}"),
+    };
+    check(msg, &bytes, ::postcard::from_bytes(&bytes), "postcard");
+}
+
+/// Asserts that `msg` decodes to an equal value after being encoded as
+/// JSON.
+///
+/// # Panics
+///
+/// If `msg` fails to encode, its encoding fails to decode, or the decoded
+/// value is not equal to `msg`.
+#[track_caller]
+pub fn assert_roundtrip_json<T: RoundTrip + ?Sized>(msg: &T) {
+    let json = match serde_json::to_string(msg) {
+        Ok(json) => json,
+        Err(e) => panic!("failed to encode {msg:?} as JSON: {e}"),
+    };
+    check(msg, &json, serde_json::from_str(&json), "JSON");
+}
+
+#[track_caller]
+fn check<T, E, D>(msg: &T, encoded: &E, decoded: Result<T::Decoded<'_>, D>, format: &str)
+where
+    T: RoundTrip + ?Sized,
+    E: fmt::Debug + ?Sized,
+    D: fmt::Display,
+{
+    match decoded {
+        Ok(decoded) => assert!(
+            msg.eq_decoded(&decoded),
+            "{format} round trip changed the message\n  original: {msg:?}\n   decoded: {decoded:?}\n   encoded: {encoded:?}",
+        ),
+        Err(e) => panic!("failed to decode {msg:?} from {format} {encoded:?}: {e}"),
+    }
+}
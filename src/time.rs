@@ -0,0 +1,251 @@
+//! Timestamps from monotonic timers, for targets without `std`'s clocks.
+//!
+//! A [`MonotonicSource`] reads a hardware timer (or any other counter that
+//! never goes backwards) as a number of ticks, and says how fast it ticks, so
+//! the timestamps it produces can be turned into real time on the other end
+//! of a stream:
+//!
+//! ```rust
+//! use core::sync::atomic::{AtomicU64, Ordering};
+//! use tracing_serde_structured::time::{FnSource, MonotonicSource, TickRate};
+//!
+//! static TICKS: AtomicU64 = AtomicU64::new(0);
+//!
+//! // A 32.768 kHz RTC counter, for example.
+//! let clock = FnSource::new(TickRate::hz(32_768), || TICKS.load(Ordering::Relaxed));
+//!
+//! TICKS.store(65_536, Ordering::Relaxed);
+//! assert_eq!(clock.rate().to_nanos(clock.now()), 2_000_000_000);
+//! ```
+//!
+//! The timestamps can be passed straight to
+//! [`DeltaEncoder::encode`](crate::wire::delta::DeltaEncoder::encode), which
+//! keeps them small on the wire regardless of the tick rate.
+//!
+//! With the `fugit` feature, a [`FugitSource`] reads the `fugit` instants
+//! returned by most HAL and RTIC monotonic timers, taking the tick rate from
+//! their type.
+//!
+//! Ticks only say how long ago the clock started. A device that learns the
+//! time of day, from an RTC, a GNSS receiver or the host it is connected to,
+//! can send a [`TimeSync`] now and then, pairing a tick count with that time,
+//! and the host turns every timestamp into a time of day from there, also
+//! correcting for the device's clock running fast or slow.
+
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// A clock that counts up at a fixed rate.
+pub trait MonotonicSource {
+    /// The current time, in ticks since an arbitrary starting point.
+    ///
+    /// This must never decrease.
+    fn now(&self) -> u64;
+
+    /// How fast [`now`](MonotonicSource::now) counts.
+    fn rate(&self) -> TickRate;
+}
+
+impl<T: MonotonicSource + ?Sized> MonotonicSource for &T {
+    fn now(&self) -> u64 {
+        (**self).now()
+    }
+
+    fn rate(&self) -> TickRate {
+        (**self).rate()
+    }
+}
+
+/// The rate of a clock: `ticks` ticks every `seconds` seconds.
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "postcard-schema", derive(postcard_schema::Schema))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct TickRate {
+    pub ticks: u32,
+    pub seconds: u32,
+}
+
+impl TickRate {
+    /// One tick per nanosecond.
+    pub const NANOS: TickRate = TickRate::hz(1_000_000_000);
+
+    /// One tick per microsecond.
+    pub const MICROS: TickRate = TickRate::hz(1_000_000);
+
+    /// `hz` ticks per second.
+    pub const fn hz(hz: u32) -> Self {
+        Self {
+            ticks: hz,
+            seconds: 1,
+        }
+    }
+
+    /// The rate of `fugit` instants and durations with a tick period of
+    /// `NOM / DENOM` seconds.
+    #[cfg(feature = "fugit")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "fugit")))]
+    pub const fn fugit<const NOM: u32, const DENOM: u32>() -> Self {
+        Self {
+            ticks: DENOM,
+            seconds: NOM,
+        }
+    }
+
+    /// Converts a number of ticks at this rate into nanoseconds, saturating at
+    /// `u64::MAX`.
+    ///
+    /// Returns `u64::MAX` if the rate is zero ticks per second.
+    pub fn to_nanos(&self, ticks: u64) -> u64 {
+        if self.ticks == 0 {
+            return u64::MAX;
+        }
+        let nanos =
+            u128::from(ticks) * u128::from(self.seconds) * 1_000_000_000 / u128::from(self.ticks);
+        u64::try_from(nanos).unwrap_or(u64::MAX)
+    }
+}
+
+/// The time of day at a tick count, sent as a [`TracingWire::TimeSync`].
+///
+/// [`TracingWire::TimeSync`]: crate::wire::TracingWire::TimeSync
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "postcard-schema", derive(postcard_schema::Schema))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct TimeSync {
+    /// The device's clock, in ticks.
+    #[cfg_attr(
+        feature = "camel-case",
+        serde(rename = "deviceTicks", alias = "device_ticks")
+    )]
+    #[cfg_attr(not(feature = "camel-case"), serde(alias = "deviceTicks"))]
+    pub device_ticks: u64,
+    /// The time of day at `device_ticks`, in nanoseconds since the Unix epoch.
+    #[cfg_attr(
+        feature = "camel-case",
+        serde(rename = "wallClock", alias = "wall_clock")
+    )]
+    #[cfg_attr(not(feature = "camel-case"), serde(alias = "wallClock"))]
+    pub wall_clock: u64,
+}
+
+/// A clock read by calling a function, such as a HAL's timer counter.
+#[derive(Clone)]
+pub struct FnSource<F> {
+    rate: TickRate,
+    now: F,
+}
+
+impl<F> fmt::Debug for FnSource<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FnSource")
+            .field("rate", &self.rate)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F: Fn() -> u64> FnSource<F> {
+    /// A clock running at `rate`, read with `now`.
+    pub fn new(rate: TickRate, now: F) -> Self {
+        Self { rate, now }
+    }
+}
+
+impl<F: Fn() -> u64> MonotonicSource for FnSource<F> {
+    fn now(&self) -> u64 {
+        (self.now)()
+    }
+
+    fn rate(&self) -> TickRate {
+        self.rate
+    }
+}
+
+/// A clock read from a function returning `fugit` instants, such as the
+/// `now` of an RTIC monotonic.
+#[cfg(feature = "fugit")]
+#[cfg_attr(docsrs, doc(cfg(feature = "fugit")))]
+#[derive(Clone)]
+pub struct FugitSource<F> {
+    now: F,
+}
+
+#[cfg(feature = "fugit")]
+impl<F> fmt::Debug for FugitSource<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FugitSource").finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "fugit")]
+impl<F, const NOM: u32, const DENOM: u32> FugitSource<F>
+where
+    F: Fn() -> fugit::Instant<u64, NOM, DENOM>,
+{
+    pub fn new(now: F) -> Self {
+        Self { now }
+    }
+}
+
+#[cfg(feature = "fugit")]
+impl<F, const NOM: u32, const DENOM: u32> MonotonicSource for FugitSource<F>
+where
+    F: Fn() -> fugit::Instant<u64, NOM, DENOM>,
+{
+    fn now(&self) -> u64 {
+        (self.now)().ticks()
+    }
+
+    fn rate(&self) -> TickRate {
+        TickRate::fugit::<NOM, DENOM>()
+    }
+}
+
+/// Nanoseconds since the clock was created, read from [`std::time::Instant`].
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+#[derive(Debug, Clone)]
+pub struct InstantSource {
+    start: std::time::Instant,
+}
+
+#[cfg(feature = "std")]
+impl Default for InstantSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl InstantSource {
+    pub fn new() -> Self {
+        Self {
+            start: std::time::Instant::now(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl MonotonicSource for InstantSource {
+    fn now(&self) -> u64 {
+        u64::try_from(self.start.elapsed().as_nanos()).unwrap_or(u64::MAX)
+    }
+
+    fn rate(&self) -> TickRate {
+        TickRate::NANOS
+    }
+}
+
+/// Nanoseconds between the UNIX epoch and `timestamp`, negative before the
+/// epoch and saturating at the ends of `i64`.
+#[cfg(any(feature = "arrow", feature = "sqlite"))]
+pub(crate) fn unix_nanos(timestamp: std::time::SystemTime) -> i64 {
+    match timestamp.duration_since(std::time::UNIX_EPOCH) {
+        Ok(after) => i64::try_from(after.as_nanos()).unwrap_or(i64::MAX),
+        Err(before) => i64::try_from(before.duration().as_nanos())
+            .map(|n| -n)
+            .unwrap_or(i64::MIN),
+    }
+}
@@ -0,0 +1,658 @@
+//! Nested values, such as those recorded with `valuable`.
+//!
+//! With the `valuable` feature and `--cfg tracing_unstable`, a struct, enum,
+//! list or map recorded with [`Visit::record_value`] keeps its structure as
+//! a [`SerializeValue::Tree`](crate::SerializeValue::Tree), rather than arriving as a debug string:
+//!
+//! ```rust
+//! use tracing_serde_structured::tree::{SerializeTree, TreeNode};
+//! use tracing_serde_structured::SerializeValue;
+//!
+//! // `{"name": "edge", "ports": [80, 443]}`
+//! let tree = SerializeTree::new(vec![
+//!     TreeNode::Map(2),
+//!     TreeNode::Str("name".into()),
+//!     TreeNode::Str("edge".into()),
+//!     TreeNode::Str("ports".into()),
+//!     TreeNode::Seq(2),
+//!     TreeNode::U64(80),
+//!     TreeNode::U64(443),
+//! ])
+//! .unwrap();
+//!
+//! let value = SerializeValue::Tree(tree);
+//! let json = serde_json::to_string(&value).unwrap();
+//! assert_eq!(json, r#"{"Tree":{"name":"edge","ports":[80,443]}}"#);
+//! assert_eq!(serde_json::from_str::<SerializeValue<'_>>(&json).unwrap(), value);
+//! ```
+//!
+//! A tree is held as a flat list of [`TreeNode`]s in depth-first order, so
+//! that it has a fixed schema. Human-readable formats write it as the nested
+//! value it describes, as above; binary formats write the list itself.
+//!
+//! Writing and formatting a tree recurse into its maps and sequences, so a
+//! tree nests no deeper than [`MAX_DEPTH`], and decoding a deeper one fails.
+//!
+//! [`Visit::record_value`]: tracing_core::field::Visit::record_value
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use serde::{
+    de::{self, DeserializeSeed},
+    ser::{SerializeMap, SerializeSeq},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::CowString;
+
+/// The most maps and sequences a tree nests within one another.
+pub const MAX_DEPTH: usize = 64;
+
+/// One node of a [`SerializeTree`], followed in the tree by its children.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "postcard-schema", derive(postcard_schema::Schema))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TreeNode<'a> {
+    /// A map of this many entries, each a key subtree then a value subtree.
+    Map(u32),
+    /// A sequence of this many subtrees.
+    Seq(u32),
+    #[serde(borrow)]
+    Str(CowString<'a>),
+    F64(f64),
+    I64(i64),
+    U64(u64),
+    Bool(bool),
+    /// A unit, or a missing value.
+    Unit,
+}
+
+impl TreeNode<'_> {
+    /// How many subtrees follow this node as its children.
+    fn children(&self) -> u64 {
+        match self {
+            TreeNode::Map(n) => u64::from(*n) * 2,
+            TreeNode::Seq(n) => u64::from(*n),
+            _ => 0,
+        }
+    }
+
+    pub fn is_borrowed(&self) -> bool {
+        matches!(self, TreeNode::Str(s) if s.is_borrowed())
+    }
+
+    pub fn to_owned(&self) -> TreeNode<'static> {
+        match self {
+            TreeNode::Map(n) => TreeNode::Map(*n),
+            TreeNode::Seq(n) => TreeNode::Seq(*n),
+            TreeNode::Str(s) => TreeNode::Str(s.to_owned()),
+            TreeNode::F64(x) => TreeNode::F64(*x),
+            TreeNode::I64(x) => TreeNode::I64(*x),
+            TreeNode::U64(x) => TreeNode::U64(*x),
+            TreeNode::Bool(x) => TreeNode::Bool(*x),
+            TreeNode::Unit => TreeNode::Unit,
+        }
+    }
+}
+
+/// Integers compare by value, whether they are held as an `I64` or a `U64`,
+/// since human-readable formats do not keep the difference.
+impl<'a, 'b> PartialEq<TreeNode<'b>> for TreeNode<'a> {
+    fn eq(&self, other: &TreeNode<'b>) -> bool {
+        match (self, other) {
+            (TreeNode::Map(a), TreeNode::Map(b)) => a == b,
+            (TreeNode::Seq(a), TreeNode::Seq(b)) => a == b,
+            (TreeNode::Str(a), TreeNode::Str(b)) => a == b,
+            (TreeNode::F64(a), TreeNode::F64(b)) => a == b,
+            (TreeNode::I64(a), TreeNode::I64(b)) => a == b,
+            (TreeNode::U64(a), TreeNode::U64(b)) => a == b,
+            (TreeNode::I64(a), TreeNode::U64(b)) | (TreeNode::U64(b), TreeNode::I64(a)) => {
+                u64::try_from(*a) == Ok(*b)
+            }
+            (TreeNode::Bool(a), TreeNode::Bool(b)) => a == b,
+            (TreeNode::Unit, TreeNode::Unit) => true,
+            _ => false,
+        }
+    }
+}
+
+/// The length of the subtree at the start of `nodes`, if it is complete.
+fn subtree_len(nodes: &[TreeNode<'_>]) -> Option<usize> {
+    let mut pending = 1;
+    for (i, node) in nodes.iter().enumerate() {
+        pending = pending - 1 + node.children();
+        if pending == 0 {
+            return Some(i + 1);
+        }
+    }
+    None
+}
+
+/// Whether `nodes` form exactly one complete tree, no deeper than
+/// [`MAX_DEPTH`].
+fn is_tree(nodes: &[TreeNode<'_>]) -> bool {
+    // The children still to come of each map or sequence being read.
+    let mut open: Vec<u64> = Vec::new();
+    for (i, node) in nodes.iter().enumerate() {
+        let children = node.children();
+        if children > 0 {
+            if open.len() == MAX_DEPTH {
+                return false;
+            }
+            open.push(children);
+            continue;
+        }
+        // The node completes its parent if it is the last child, and so on
+        // up the tree.
+        loop {
+            match open.last_mut() {
+                None => return i + 1 == nodes.len(),
+                Some(1) => {
+                    open.pop();
+                }
+                Some(n) => {
+                    *n -= 1;
+                    break;
+                }
+            }
+        }
+    }
+    false
+}
+
+/// A nested value of maps, sequences and primitives, held as its nodes in
+/// depth-first order.
+///
+/// See the [module documentation](crate::tree) for an example.
+#[derive(Debug, Clone)]
+pub struct SerializeTree<'a> {
+    nodes: Vec<TreeNode<'a>>,
+}
+
+impl<'a> SerializeTree<'a> {
+    /// Creates a tree from its nodes, or returns `None` if they do not form
+    /// exactly one complete tree, or nest deeper than [`MAX_DEPTH`].
+    pub fn new(nodes: Vec<TreeNode<'a>>) -> Option<Self> {
+        is_tree(&nodes).then_some(SerializeTree { nodes })
+    }
+
+    /// The nodes of the tree, in depth-first order.
+    pub fn nodes(&self) -> &[TreeNode<'a>] {
+        &self.nodes
+    }
+
+    /// Whether any string in the tree borrows its data.
+    pub fn is_borrowed(&self) -> bool {
+        self.nodes.iter().any(TreeNode::is_borrowed)
+    }
+
+    pub fn is_owned(&self) -> bool {
+        !self.is_borrowed()
+    }
+
+    pub fn ensure_owned(&mut self) {
+        for node in &mut self.nodes {
+            if let TreeNode::Str(s) = node {
+                s.ensure_owned();
+            }
+        }
+    }
+
+    pub fn to_owned(&self) -> SerializeTree<'static> {
+        SerializeTree {
+            nodes: self.nodes.iter().map(TreeNode::to_owned).collect(),
+        }
+    }
+
+    fn root(&self) -> Subtree<'_, 'a> {
+        Subtree(&self.nodes)
+    }
+}
+
+impl<'a, 'b> PartialEq<SerializeTree<'b>> for SerializeTree<'a> {
+    fn eq(&self, other: &SerializeTree<'b>) -> bool {
+        self.nodes.len() == other.nodes.len()
+            && self.nodes.iter().zip(&other.nodes).all(|(a, b)| a == b)
+    }
+}
+
+#[cfg(feature = "postcard-schema")]
+impl<'a> postcard_schema::Schema for SerializeTree<'a> {
+    const SCHEMA: &'static postcard_schema::schema::NamedType =
+        &postcard_schema::schema::NamedType {
+            name: "SerializeTree",
+            ty: &postcard_schema::schema::DataModelType::Seq(TreeNode::SCHEMA),
+        };
+}
+
+#[cfg(feature = "defmt")]
+impl<'a> defmt::Format for SerializeTree<'a> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "{}", defmt::Display2Format(self))
+    }
+}
+
+/// Formats the tree much like JSON.
+impl<'a> fmt::Display for SerializeTree<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.root().fmt(f)
+    }
+}
+
+impl<'a> Serialize for SerializeTree<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if serializer.is_human_readable() {
+            self.root().serialize(serializer)
+        } else {
+            self.nodes.serialize(serializer)
+        }
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for SerializeTree<'a> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            let mut nodes = Vec::new();
+            NodeSeed(&mut nodes, 0).deserialize(deserializer)?;
+            Ok(SerializeTree { nodes })
+        } else {
+            SerializeTree::new(Vec::deserialize(deserializer)?)
+                .ok_or_else(|| de::Error::custom("incomplete or too deeply nested value tree"))
+        }
+    }
+}
+
+/// A complete subtree, starting with its root node.
+struct Subtree<'t, 'a>(&'t [TreeNode<'a>]);
+
+impl<'t, 'a> Subtree<'t, 'a> {
+    /// The root node, and the subtrees of its children.
+    fn split(&self) -> Option<(&'t TreeNode<'a>, Subtrees<'t, 'a>)> {
+        let (node, rest) = self.0.split_first()?;
+        Some((node, Subtrees(rest)))
+    }
+}
+
+/// Consecutive subtrees, split off the front one at a time.
+struct Subtrees<'t, 'a>(&'t [TreeNode<'a>]);
+
+impl<'t, 'a> Iterator for Subtrees<'t, 'a> {
+    type Item = Subtree<'t, 'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (subtree, rest) = self.0.split_at(subtree_len(self.0)?);
+        self.0 = rest;
+        Some(Subtree(subtree))
+    }
+}
+
+impl Serialize for Subtree<'_, '_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let Some((node, mut children)) = self.split() else {
+            return serializer.serialize_unit();
+        };
+        match node {
+            TreeNode::Map(n) => {
+                let mut map = serializer.serialize_map(Some(*n as usize))?;
+                while let (Some(k), Some(v)) = (children.next(), children.next()) {
+                    map.serialize_entry(&k, &v)?;
+                }
+                map.end()
+            }
+            TreeNode::Seq(n) => {
+                let mut seq = serializer.serialize_seq(Some(*n as usize))?;
+                for item in children {
+                    seq.serialize_element(&item)?;
+                }
+                seq.end()
+            }
+            TreeNode::Str(s) => s.serialize(serializer),
+            TreeNode::F64(x) => serializer.serialize_f64(*x),
+            TreeNode::I64(x) => serializer.serialize_i64(*x),
+            TreeNode::U64(x) => serializer.serialize_u64(*x),
+            TreeNode::Bool(x) => serializer.serialize_bool(*x),
+            TreeNode::Unit => serializer.serialize_unit(),
+        }
+    }
+}
+
+impl fmt::Display for Subtree<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some((node, mut children)) = self.split() else {
+            return Ok(());
+        };
+        match node {
+            TreeNode::Map(_) => {
+                f.write_str("{")?;
+                let mut first = true;
+                while let (Some(k), Some(v)) = (children.next(), children.next()) {
+                    let sep = if first { "" } else { ", " };
+                    write!(f, "{sep}{k}: {v}")?;
+                    first = false;
+                }
+                f.write_str("}")
+            }
+            TreeNode::Seq(_) => {
+                f.write_str("[")?;
+                for (i, item) in children.enumerate() {
+                    let sep = if i == 0 { "" } else { ", " };
+                    write!(f, "{sep}{item}")?;
+                }
+                f.write_str("]")
+            }
+            TreeNode::Str(s) => write!(f, "{:?}", s.as_str()),
+            TreeNode::F64(x) => x.fmt(f),
+            TreeNode::I64(x) => x.fmt(f),
+            TreeNode::U64(x) => x.fmt(f),
+            TreeNode::Bool(x) => x.fmt(f),
+            TreeNode::Unit => f.write_str("null"),
+        }
+    }
+}
+
+/// Reads a nested value from a human-readable format, appending its nodes,
+/// within this many maps and sequences.
+struct NodeSeed<'v, 'a>(&'v mut Vec<TreeNode<'a>>, usize);
+
+impl<'v, 'a> NodeSeed<'v, 'a> {
+    /// The seed for the children of a map or sequence starting here.
+    fn nested<E: de::Error>(&mut self) -> Result<NodeSeed<'_, 'a>, E> {
+        if self.1 == MAX_DEPTH {
+            return Err(E::custom("value tree nested too deeply"));
+        }
+        Ok(NodeSeed(self.0, self.1 + 1))
+    }
+}
+
+impl<'v, 'de: 'a, 'a> DeserializeSeed<'de> for NodeSeed<'v, 'a> {
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> Result<(), D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'v, 'de: 'a, 'a> de::Visitor<'de> for NodeSeed<'v, 'a> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a value")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<(), E> {
+        self.0.push(TreeNode::Bool(v));
+        Ok(())
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<(), E> {
+        self.0.push(TreeNode::I64(v));
+        Ok(())
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<(), E> {
+        self.0.push(TreeNode::U64(v));
+        Ok(())
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<(), E> {
+        self.0.push(TreeNode::F64(v));
+        Ok(())
+    }
+
+    fn visit_borrowed_str<E: de::Error>(self, v: &'de str) -> Result<(), E> {
+        self.0.push(TreeNode::Str(CowString::Borrowed(v)));
+        Ok(())
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<(), E> {
+        self.0.push(TreeNode::Str(CowString::copied(v)));
+        Ok(())
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<(), E> {
+        self.0.push(TreeNode::Unit);
+        Ok(())
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<(), E> {
+        self.visit_unit()
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        self.deserialize(deserializer)
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(mut self, mut seq: A) -> Result<(), A::Error> {
+        let at = self.0.len();
+        self.0.push(TreeNode::Seq(0));
+        let mut len = 0u32;
+        while seq.next_element_seed(self.nested()?)?.is_some() {
+            len = len
+                .checked_add(1)
+                .ok_or_else(|| de::Error::custom("sequence too long"))?;
+        }
+        self.0[at] = TreeNode::Seq(len);
+        Ok(())
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(mut self, mut map: A) -> Result<(), A::Error> {
+        let at = self.0.len();
+        self.0.push(TreeNode::Map(0));
+        let mut len = 0u32;
+        while map.next_key_seed(self.nested()?)?.is_some() {
+            map.next_value_seed(self.nested()?)?;
+            len = len
+                .checked_add(1)
+                .ok_or_else(|| de::Error::custom("map too long"))?;
+        }
+        self.0[at] = TreeNode::Map(len);
+        Ok(())
+    }
+}
+
+#[cfg(all(tracing_unstable, feature = "valuable"))]
+mod valuable_impls {
+    use alloc::vec::Vec;
+
+    use valuable_crate::{Fields, NamedValues, Value, Visit};
+
+    use super::{SerializeTree, TreeNode, MAX_DEPTH};
+    use crate::{CowString, DebugRecord, SerializeValue};
+
+    impl<'a> SerializeValue<'a> {
+        /// Copies a [`valuable`](https://docs.rs/valuable) value.
+        ///
+        /// Primitives become the matching variant, and structs, enums,
+        /// tuples, lists and maps become a [`SerializeValue::Tree`]. Structs
+        /// and enum variants with named fields become maps keyed by field
+        /// name, and those with unnamed fields become sequences, except that
+        /// a single unnamed field stands for itself, as in `serde`. An enum
+        /// variant with fields is a map of one entry, from the variant's
+        /// name to its fields, and one without is just its name.
+        ///
+        /// A value nested deeper than [`MAX_DEPTH`] is kept as its `Debug`
+        /// output instead.
+        #[cfg_attr(docsrs, doc(cfg(all(tracing_unstable, feature = "valuable"))))]
+        pub fn from_valuable(value: Value<'_>) -> Self {
+            let mut builder = Builder {
+                nodes: Vec::new(),
+                entries: false,
+                count: 0,
+                depth: 0,
+                failed: false,
+            };
+            builder.visit_value(value);
+            let tree = match builder.count {
+                1 if !builder.failed => SerializeTree::new(builder.nodes),
+                _ => None,
+            };
+            // A value that visits itself inconsistently, or nests too deeply,
+            // is kept as debug output instead.
+            let Some(tree) = tree else {
+                let debug = CowString::formatted(format_args!("{value:?}"));
+                return SerializeValue::Debug(DebugRecord::De(debug));
+            };
+            match tree.nodes() {
+                [TreeNode::Str(s)] => SerializeValue::Str(s.clone()),
+                [TreeNode::F64(x)] => SerializeValue::F64(*x),
+                [TreeNode::I64(x)] => SerializeValue::I64(*x),
+                [TreeNode::U64(x)] => SerializeValue::U64(*x),
+                [TreeNode::Bool(x)] => SerializeValue::Bool(*x),
+                _ => SerializeValue::Tree(tree),
+            }
+        }
+    }
+
+    /// Appends the nodes of visited values, counting the children of the
+    /// map or sequence being visited.
+    struct Builder {
+        nodes: Vec<TreeNode<'static>>,
+        /// Whether the children being visited are map entries.
+        entries: bool,
+        count: u32,
+        /// How many maps and sequences are being visited.
+        depth: usize,
+        failed: bool,
+    }
+
+    impl Builder {
+        /// Counts a child of the map or sequence being visited.
+        fn child(&mut self, entry: bool) {
+            match self.count.checked_add(1) {
+                Some(count) if entry == self.entries => self.count = count,
+                _ => self.failed = true,
+            }
+        }
+
+        fn nested(&mut self, entries: bool, visit: impl FnOnce(&mut Self)) {
+            if self.depth == MAX_DEPTH {
+                self.failed = true;
+                return;
+            }
+            let at = self.nodes.len();
+            self.nodes.push(TreeNode::Unit);
+            let outer = (
+                core::mem::replace(&mut self.entries, entries),
+                core::mem::replace(&mut self.count, 0),
+            );
+            self.depth += 1;
+            visit(self);
+            self.depth -= 1;
+            let count = core::mem::replace(&mut self.count, outer.1);
+            self.entries = outer.0;
+            self.nodes[at] = if entries {
+                TreeNode::Map(count)
+            } else {
+                TreeNode::Seq(count)
+            };
+        }
+
+        /// Visits a struct or variant with one unnamed field as just that
+        /// field.
+        fn newtype(&mut self, visit: impl FnOnce(&mut Self)) {
+            let outer = (
+                core::mem::replace(&mut self.entries, false),
+                core::mem::replace(&mut self.count, 0),
+            );
+            visit(self);
+            self.failed |= self.count != 1;
+            (self.entries, self.count) = outer;
+        }
+
+        fn node(&mut self, value: Value<'_>) {
+            let node = match value {
+                Value::Listable(l) => return self.nested(false, |b| l.visit(b)),
+                Value::Tuplable(t) => return self.nested(false, |b| t.visit(b)),
+                Value::Mappable(m) => return self.nested(true, |b| m.visit(b)),
+                Value::Structable(s) => {
+                    return match s.definition().fields() {
+                        Fields::Unnamed(1) => self.newtype(|b| s.visit(b)),
+                        fields => self.nested(fields.is_named(), |b| s.visit(b)),
+                    };
+                }
+                Value::Enumerable(e) => {
+                    let variant = e.variant();
+                    let name = TreeNode::Str(CowString::copied(variant.name()));
+                    match variant.fields() {
+                        Fields::Unnamed(0) => name,
+                        fields => {
+                            self.nodes.extend([TreeNode::Map(1), name]);
+                            return match fields {
+                                Fields::Unnamed(1) => self.newtype(|b| e.visit(b)),
+                                fields => self.nested(fields.is_named(), |b| e.visit(b)),
+                            };
+                        }
+                    }
+                }
+                Value::Bool(x) => TreeNode::Bool(x),
+                Value::F32(x) => TreeNode::F64(x.into()),
+                Value::F64(x) => TreeNode::F64(x),
+                Value::I8(x) => TreeNode::I64(x.into()),
+                Value::I16(x) => TreeNode::I64(x.into()),
+                Value::I32(x) => TreeNode::I64(x.into()),
+                Value::I64(x) => TreeNode::I64(x),
+                Value::Isize(x) => TreeNode::I64(x as i64),
+                Value::U8(x) => TreeNode::U64(x.into()),
+                Value::U16(x) => TreeNode::U64(x.into()),
+                Value::U32(x) => TreeNode::U64(x.into()),
+                Value::U64(x) => TreeNode::U64(x),
+                Value::Usize(x) => TreeNode::U64(x as u64),
+                Value::I128(x) => i64::try_from(x).map_or_else(
+                    |_| TreeNode::Str(CowString::formatted(format_args!("{x}"))),
+                    TreeNode::I64,
+                ),
+                Value::U128(x) => u64::try_from(x).map_or_else(
+                    |_| TreeNode::Str(CowString::formatted(format_args!("{x}"))),
+                    TreeNode::U64,
+                ),
+                Value::Char(c) => TreeNode::Str(CowString::formatted(format_args!("{c}"))),
+                Value::String(s) => TreeNode::Str(CowString::copied(s)),
+                Value::Unit => TreeNode::Unit,
+                other => TreeNode::Str(CowString::formatted(format_args!("{other:?}"))),
+            };
+            self.nodes.push(node);
+        }
+    }
+
+    impl Visit for Builder {
+        fn visit_value(&mut self, value: Value<'_>) {
+            self.child(false);
+            self.node(value);
+        }
+
+        fn visit_named_fields(&mut self, named_values: &NamedValues<'_>) {
+            for (field, value) in named_values {
+                self.child(true);
+                self.nodes
+                    .push(TreeNode::Str(CowString::copied(field.name())));
+                self.node(*value);
+            }
+        }
+
+        fn visit_unnamed_fields(&mut self, values: &[Value<'_>]) {
+            for value in values {
+                self.child(false);
+                self.node(*value);
+            }
+        }
+
+        fn visit_entry(&mut self, key: Value<'_>, value: Value<'_>) {
+            self.child(true);
+            self.node(key);
+            self.node(value);
+        }
+    }
+}
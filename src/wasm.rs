@@ -0,0 +1,259 @@
+//! Structured tracing in the browser, with [`serde-wasm-bindgen`].
+//!
+//! Events, span attributes and wire messages convert into plain JavaScript
+//! objects, laid out as [`json_lines`](crate::json_lines) writes them, to
+//! hand to JavaScript code or send from a web worker:
+//!
+//! ```rust,no_run
+//! use tracing_serde_structured::{builder::SerializeEventBuilder, SerializeLevel};
+//!
+//! let event = SerializeEventBuilder::new()
+//!     .level(SerializeLevel::Warn)
+//!     .field("attempt", 3u64)
+//!     .build();
+//!
+//! // `{ metadata: { level: "WARN", .. }, fields: { attempt: { U64: 3 } }, .. }`
+//! let object = event.to_js_value().unwrap();
+//! ```
+//!
+//! A [`ConsoleSubscriber`] logs each event to the browser console as such an
+//! object, with the console method matching its level, so that the developer
+//! tools can expand and filter them:
+//!
+//! ```rust,no_run
+//! use tracing_serde_structured::wasm::ConsoleSubscriber;
+//!
+//! tracing::subscriber::set_global_default(ConsoleSubscriber::new()).unwrap();
+//! ```
+//!
+//! Maps become objects rather than `Map`s, and 64 bit integers become
+//! numbers, or fail to convert if they do not fit into one exactly, as with
+//! [`Serializer::json_compatible`].
+//!
+//! [`serde-wasm-bindgen`]: https://docs.rs/serde-wasm-bindgen
+
+use std::{collections::HashMap, fmt, sync::Mutex};
+
+use serde::{Deserialize, Serialize};
+use serde_wasm_bindgen::{Deserializer, Serializer};
+use tracing_core::{
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    Event, Level, LevelFilter, Metadata, Subscriber,
+};
+use wasm_bindgen::JsValue;
+use web_sys::console;
+
+use crate::{wire::TracingWire, AsSerde, Form, SerializeAttributes, SerializeEvent};
+
+pub use serde_wasm_bindgen::Error;
+
+/// Serializes `value` into a JavaScript value.
+pub fn to_js<T>(value: &T) -> Result<JsValue, Error>
+where
+    T: Serialize + ?Sized,
+{
+    value.serialize(&Serializer::json_compatible())
+}
+
+/// Deserializes a value from a JavaScript value, such as one made by
+/// [`to_js`], copying its strings.
+pub fn from_js<'de, T>(value: JsValue) -> Result<T, Error>
+where
+    T: Deserialize<'de>,
+{
+    T::deserialize(Deserializer::from(value))
+}
+
+impl<'a, F: Form> SerializeEvent<'a, F> {
+    /// Serialize the event into a JavaScript object.
+    pub fn to_js_value(&self) -> Result<JsValue, Error> {
+        to_js(self)
+    }
+}
+
+impl<'a> SerializeAttributes<'a> {
+    /// Serialize the attributes into a JavaScript object.
+    pub fn to_js_value(&self) -> Result<JsValue, Error> {
+        to_js(self)
+    }
+}
+
+impl<'a, F: Form> TracingWire<'a, F> {
+    /// Serialize the message into a JavaScript object.
+    pub fn to_js_value(&self) -> Result<JsValue, Error> {
+        to_js(self)
+    }
+}
+
+/// A span the subscriber has created, and the number of handles to it.
+#[derive(Debug)]
+struct Span {
+    name: &'static str,
+    refs: usize,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    next_id: u64,
+    spans: HashMap<u64, Span>,
+    /// The entered spans, innermost last.
+    stack: Vec<u64>,
+}
+
+/// A subscriber logging events to the browser console.
+///
+/// Each event is logged with `console.error`, `console.warn`, `console.info`
+/// or `console.debug`, by its level (`console.trace` would add a stack trace
+/// to every event), as a line in the style of `tracing-subscriber`'s `fmt`
+/// layer, such as `request:fetch: app::net: connected`, followed by the event
+/// as a JavaScript object. Spans are tracked to name the scope of events, but
+/// not logged themselves.
+#[derive(Debug)]
+pub struct ConsoleSubscriber {
+    max_level: LevelFilter,
+    state: Mutex<State>,
+}
+
+impl Default for ConsoleSubscriber {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConsoleSubscriber {
+    /// A subscriber logging events at every level.
+    pub fn new() -> Self {
+        Self::with_max_level(LevelFilter::TRACE)
+    }
+
+    /// A subscriber logging events at `max_level` and more severe ones.
+    pub fn with_max_level(max_level: LevelFilter) -> Self {
+        Self {
+            max_level,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The line logged ahead of an event's object.
+    fn label(&self, event: &Event<'_>) -> String {
+        let mut label = String::new();
+        {
+            let state = self.state();
+            for span in state.stack.iter().filter_map(|id| state.spans.get(id)) {
+                label.push_str(span.name);
+                label.push(':');
+            }
+        }
+        if !label.is_empty() {
+            label.push(' ');
+        }
+        label.push_str(event.metadata().target());
+        label.push(':');
+
+        let mut message = Message(None);
+        event.record(&mut message);
+        if let Some(message) = message.0 {
+            label.push(' ');
+            label.push_str(&message);
+        }
+        label
+    }
+}
+
+/// Finds the `message` field of an event.
+struct Message(Option<String>);
+
+impl Visit for Message {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.0 = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.0 = Some(format!("{value:?}"));
+        }
+    }
+}
+
+impl Subscriber for ConsoleSubscriber {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        *metadata.level() <= self.max_level
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        Some(self.max_level)
+    }
+
+    fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+        let mut state = self.state();
+        state.next_id += 1;
+        let id = state.next_id;
+        let name = attrs.metadata().name();
+        state.spans.insert(id, Span { name, refs: 1 });
+        Id::from_u64(id)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let label = JsValue::from(self.label(event));
+        let object = match to_js(&event.as_serde()) {
+            Ok(object) => object,
+            Err(e) => {
+                let msg = format!("failed to convert event: {e}");
+                console::error_2(&label, &JsValue::from(msg));
+                return;
+            }
+        };
+        match *event.metadata().level() {
+            Level::ERROR => console::error_2(&label, &object),
+            Level::WARN => console::warn_2(&label, &object),
+            Level::INFO => console::info_2(&label, &object),
+            Level::DEBUG | Level::TRACE => console::debug_2(&label, &object),
+        }
+    }
+
+    fn enter(&self, id: &Id) {
+        self.state().stack.push(id.into_u64());
+    }
+
+    fn exit(&self, id: &Id) {
+        let mut state = self.state();
+        if let Some(at) = state
+            .stack
+            .iter()
+            .rposition(|entered| *entered == id.into_u64())
+        {
+            state.stack.remove(at);
+        }
+    }
+
+    fn clone_span(&self, id: &Id) -> Id {
+        if let Some(span) = self.state().spans.get_mut(&id.into_u64()) {
+            span.refs += 1;
+        }
+        id.clone()
+    }
+
+    fn try_close(&self, id: Id) -> bool {
+        let mut state = self.state();
+        let Some(span) = state.spans.get_mut(&id.into_u64()) else {
+            return false;
+        };
+        span.refs -= 1;
+        if span.refs > 0 {
+            return false;
+        }
+        state.spans.remove(&id.into_u64());
+        true
+    }
+}
@@ -0,0 +1,363 @@
+//! A sink streaming messages over a WebSocket connection, to a live viewer.
+//!
+//! A [`WebSocketSink`] connects to a server, such as a trace viewer
+//! listening for the services and browser apps it shows, and sends every
+//! message as one binary WebSocket message, postcard encoded:
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//!
+//! use tracing_serde_structured::{websocket::WebSocketSink, SerializeLevel};
+//!
+//! let mut sink = WebSocketSink::new("ws://localhost:9000/traces")
+//!     .with_capacity(4096)
+//!     .with_backoff(Duration::from_millis(250), Duration::from_secs(10));
+//! # let msg = SerializeLevel::Info;
+//! if let Err(e) = sink.send(&msg) {
+//!     eprintln!("trace viewer unreachable: {e}");
+//! }
+//! ```
+//!
+//! Messages are queued first, and sent as soon as there is a connection, so
+//! they survive the viewer restarting. The queue is bounded: once it is full,
+//! the oldest message is dropped for each new one, and counted in the
+//! [`stats`](WebSocketSink::stats), as a live viewer cares more for what is
+//! happening now. The sink connects when the first message is sent, and after
+//! losing the connection, reconnects when a message is sent or the sink is
+//! [flushed](WebSocketSink::flush), waiting longer after every failed attempt.
+//!
+//! Messages are sent as they are given, so to send a stream a
+//! [`host::Decoder`](crate::host::Decoder) can read, delta encode its
+//! messages, and start every connection with a
+//! [`TracingWire::Hello`](crate::wire::TracingWire::Hello) and the
+//! definitions sent so far (see
+//! [`connection`](WebSocketSink::connection)).
+//!
+//! On native targets, the connection is a blocking [`tungstenite`] client:
+//! connecting and sending block until done, and failures are reported by the
+//! call that ran into them. `wss://` URLs need one of tungstenite's TLS
+//! features enabled. In the browser (on `wasm32`), it is a
+//! [`web_sys::WebSocket`], which connects in the background, so messages
+//! stay queued until it is open, and go out with the next call to
+//! [`send`](WebSocketSink::send) or [`flush`](WebSocketSink::flush). Messages
+//! are also held back while the browser's own buffer holds more than
+//! [`with_max_buffered`](WebSocketSink::with_max_buffered) bytes.
+
+use std::{collections::VecDeque, fmt, time::Duration};
+
+use serde::Serialize;
+
+use crate::wire::SerializePipelineStats;
+
+#[cfg(not(target_arch = "wasm32"))]
+type Socket = tungstenite::WebSocket<tungstenite::stream::MaybeTlsStream<std::net::TcpStream>>;
+#[cfg(target_arch = "wasm32")]
+type Socket = web_sys::WebSocket;
+
+/// Errors returned by a [`WebSocketSink`].
+#[derive(Debug)]
+pub enum Error {
+    /// The message could not be encoded.
+    Encode(::postcard::Error),
+    /// Connecting or sending failed. The connection is dropped, and the
+    /// messages not sent yet stay queued.
+    #[cfg(not(target_arch = "wasm32"))]
+    WebSocket(tungstenite::Error),
+    /// The browser refused to connect or to send, e.g. because of a malformed
+    /// URL. The connection is dropped, and the messages not sent yet stay
+    /// queued.
+    #[cfg(target_arch = "wasm32")]
+    WebSocket(wasm_bindgen::JsValue),
+    /// The browser closed the connection. Why is only reported to the
+    /// socket's `close` event.
+    Closed,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Encode(e) => write!(f, "failed to encode message: {e}"),
+            #[cfg(not(target_arch = "wasm32"))]
+            Error::WebSocket(e) => write!(f, "failed to send message: {e}"),
+            #[cfg(target_arch = "wasm32")]
+            Error::WebSocket(e) => write!(f, "failed to send message: {e:?}"),
+            Error::Closed => f.write_str("connection closed"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Encode(e) => Some(e),
+            #[cfg(not(target_arch = "wasm32"))]
+            Error::WebSocket(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// How long to wait before connecting again, doubling after every failed
+/// attempt.
+#[derive(Debug)]
+struct Backoff {
+    min: Duration,
+    max: Duration,
+    delay: Duration,
+    retry_at: Option<Duration>,
+}
+
+impl Backoff {
+    fn is_due(&self, now: Duration) -> bool {
+        self.retry_at.is_none_or(|at| now >= at)
+    }
+
+    fn failed(&mut self, now: Duration) {
+        self.retry_at = Some(now + self.delay);
+        self.delay = (self.delay * 2).min(self.max);
+    }
+
+    fn reset(&mut self) {
+        self.delay = self.min;
+        self.retry_at = None;
+    }
+}
+
+/// Streams postcard encoded messages over a WebSocket connection, queueing
+/// them while there is none.
+pub struct WebSocketSink {
+    url: String,
+    socket: Option<Socket>,
+    queue: VecDeque<Vec<u8>>,
+    capacity: usize,
+    backoff: Backoff,
+    #[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+    max_buffered: u32,
+    connections: u32,
+    stats: SerializePipelineStats,
+}
+
+impl fmt::Debug for WebSocketSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebSocketSink")
+            .field("url", &self.url)
+            .field("connected", &self.socket.is_some())
+            .field("queued", &self.queue.len())
+            .field("capacity", &self.capacity)
+            .field("stats", &self.stats)
+            .finish_non_exhaustive()
+    }
+}
+
+impl WebSocketSink {
+    /// The number of messages queued, by default.
+    pub const DEFAULT_CAPACITY: usize = 1024;
+
+    /// The bytes the browser may buffer before messages are held back, by
+    /// default: 1 MiB.
+    pub const DEFAULT_MAX_BUFFERED: u32 = 1 << 20;
+
+    /// A sink connecting to `url`, such as `ws://localhost:9000`, once the
+    /// first message is sent.
+    ///
+    /// It waits 500 ms before connecting again after a failed attempt, and
+    /// twice as long after every further one, up to 30 s.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            socket: None,
+            queue: VecDeque::new(),
+            capacity: Self::DEFAULT_CAPACITY,
+            backoff: Backoff {
+                min: Duration::from_millis(500),
+                max: Duration::from_secs(30),
+                delay: Duration::from_millis(500),
+                retry_at: None,
+            },
+            max_buffered: Self::DEFAULT_MAX_BUFFERED,
+            connections: 0,
+            stats: SerializePipelineStats::default(),
+        }
+    }
+
+    /// Queue at most `capacity` messages, rather than
+    /// [`DEFAULT_CAPACITY`](Self::DEFAULT_CAPACITY).
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// Wait `min` before connecting again after a failed attempt, and twice
+    /// as long after every further one, up to `max`.
+    pub fn with_backoff(mut self, min: Duration, max: Duration) -> Self {
+        self.backoff.min = min;
+        self.backoff.max = max.max(min);
+        self.backoff.delay = min;
+        self
+    }
+
+    /// In the browser, hold messages back while the socket has more than
+    /// `max_buffered` bytes waiting to go out, rather than
+    /// [`DEFAULT_MAX_BUFFERED`](Self::DEFAULT_MAX_BUFFERED). Native sockets
+    /// block instead.
+    pub fn with_max_buffered(mut self, max_buffered: u32) -> Self {
+        self.max_buffered = max_buffered;
+        self
+    }
+
+    /// Encodes `msg`, queues it, and sends as much of the queue as the
+    /// connection takes, connecting first if there is no connection and the
+    /// backoff has passed.
+    ///
+    /// An error connecting or sending leaves the message queued, to be sent
+    /// once there is a connection again, unless it is dropped for newer ones
+    /// in the meantime.
+    pub fn send<T>(&mut self, msg: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let frame = match ::postcard::to_allocvec(msg) {
+            Ok(frame) => frame,
+            Err(e) => {
+                count(&mut self.stats.errors);
+                return Err(Error::Encode(e));
+            }
+        };
+        if self.queue.len() >= self.capacity {
+            self.queue.pop_front();
+            count(&mut self.stats.dropped);
+        }
+        self.queue.push_back(frame);
+        self.flush()
+    }
+
+    /// Sends as much of the queue as the connection takes, connecting first
+    /// if there is no connection and the backoff has passed.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        let socket = match &mut self.socket {
+            Some(socket) => socket,
+            None if !self.backoff.is_due(now()) => return Ok(()),
+            None => match connect(&self.url) {
+                Ok(socket) => {
+                    self.connections = self.connections.wrapping_add(1);
+                    self.socket.insert(socket)
+                }
+                Err(e) => {
+                    self.backoff.failed(now());
+                    return Err(e);
+                }
+            },
+        };
+        while let Some(frame) = self.queue.front() {
+            match write(socket, frame, self.max_buffered) {
+                Ok(true) => {
+                    self.queue.pop_front();
+                    count(&mut self.stats.sent);
+                    self.backoff.reset();
+                }
+                Ok(false) => break,
+                Err(e) => {
+                    self.socket = None;
+                    self.backoff.failed(now());
+                    return Err(e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The number of the current connection, counting from 1, if there is
+    /// one. It changes whenever the sink connects again, so that a producer
+    /// can tell when to send its hello and definitions again.
+    pub fn connection(&self) -> Option<u32> {
+        self.socket.as_ref().map(|_| self.connections)
+    }
+
+    /// The number of messages waiting to be sent.
+    pub fn queued(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// The counts of messages sent and lost so far.
+    pub fn stats(&self) -> SerializePipelineStats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = SerializePipelineStats::default();
+    }
+
+    /// Closes the connection, if any, leaving the queue as it is. The next
+    /// message connects again.
+    pub fn close(&mut self) {
+        if let Some(socket) = self.socket.take() {
+            close(socket);
+        }
+        self.backoff.reset();
+    }
+}
+
+fn count(count: &mut u32) {
+    *count = count.saturating_add(1);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn now() -> Duration {
+    static START: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();
+    START.get_or_init(std::time::Instant::now).elapsed()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn connect(url: &str) -> Result<Socket, Error> {
+    tungstenite::connect(url)
+        .map(|(socket, _)| socket)
+        .map_err(Error::WebSocket)
+}
+
+/// Sends `frame`, returning whether it went out.
+#[cfg(not(target_arch = "wasm32"))]
+fn write(socket: &mut Socket, frame: &[u8], _: u32) -> Result<bool, Error> {
+    let msg = tungstenite::Message::binary(frame.to_vec());
+    socket.send(msg).map_err(Error::WebSocket)?;
+    Ok(true)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn close(mut socket: Socket) {
+    // The server is gone, or about to be. Either way there is nothing to do
+    // about an error.
+    let _ = socket.close(None);
+    let _ = socket.flush();
+}
+
+#[cfg(target_arch = "wasm32")]
+fn now() -> Duration {
+    Duration::from_secs_f64(js_sys::Date::now() / 1000.0)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn connect(url: &str) -> Result<Socket, Error> {
+    let socket = web_sys::WebSocket::new(url).map_err(Error::WebSocket)?;
+    socket.set_binary_type(web_sys::BinaryType::Arraybuffer);
+    Ok(socket)
+}
+
+/// Sends `frame` if the socket is open, and its buffer has room, returning
+/// whether it went out.
+#[cfg(target_arch = "wasm32")]
+fn write(socket: &mut Socket, frame: &[u8], max_buffered: u32) -> Result<bool, Error> {
+    match socket.ready_state() {
+        web_sys::WebSocket::CONNECTING => Ok(false),
+        web_sys::WebSocket::OPEN if socket.buffered_amount() > max_buffered => Ok(false),
+        web_sys::WebSocket::OPEN => {
+            socket.send_with_u8_array(frame).map_err(Error::WebSocket)?;
+            Ok(true)
+        }
+        _ => Err(Error::Closed),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn close(socket: Socket) {
+    let _ = socket.close();
+}
@@ -0,0 +1,1656 @@
+//! A single message type for streaming trace data, and the metadata dictionary.
+//!
+//! [`TracingWire`] has one variant per [`Subscriber`] notification, so a
+//! subscriber can forward everything it sees over one connection.
+//!
+//! Every [`SerializeEvent`] carries its full [`SerializeMetadata`], which is
+//! usually most of the message. Since metadata is `'static` and fixed per
+//! callsite, a producer can instead send it once, as a
+//! [`TracingWire::DefineMetadata`] (for example from
+//! [`Subscriber::register_callsite`]), and from then on send events and spans
+//! that only carry a [`SerializeMetadataId`]. On the consumer side, a
+//! [`MetadataDictionary`] collects the definitions, and looks the metadata up
+//! only when it is needed.
+//!
+//! Field names are repeated in every event as well. A [`StringTable`] on the
+//! producer side assigns each name an id, sent once as a
+//! [`TracingWire::DefineString`], and turns events into
+//! [`SerializeEventInterned`]s, which carry only the ids. The consumer's
+//! dictionary collects these definitions too, and resolves the ids back into
+//! names. Together, the two leave no strings in the per-event traffic other
+//! than the values themselves.
+//!
+//! A stream can start with a [`TracingWire::Hello`], with which the
+//! [`handshake`] module lets producers and consumers of different versions
+//! agree on what the stream uses, or fail with a clear error.
+//!
+//! A producer that has to discard messages, because its queue is full or
+//! they are too large, can send a [`TracingWire::Dropped`] in their place,
+//! collected by a [`DropTracker`], so that a consumer shows where they are
+//! missing rather than leaving a silent gap.
+//!
+//! So that capture files can still be decoded long after they were written,
+//! a producer can follow its hello with a [`TracingWire::Description`] of
+//! how its stream is encoded (see [`description`]).
+//!
+//! The [`baggage`](crate::baggage) set on a span, which the spans created
+//! within it inherit, is sent as a [`TracingWire::Baggage`].
+//!
+//! On slow links, the [`delta`] module shrinks the span ids and timestamps
+//! in each message down to a byte or two, and the [`table`] module lets a
+//! device skip the definitions altogether.
+//!
+//! [`Subscriber`]: tracing_core::Subscriber
+//! [`Subscriber::register_callsite`]: tracing_core::Subscriber::register_callsite
+
+pub mod delta;
+pub mod description;
+pub mod handshake;
+pub mod table;
+
+use core::{fmt, marker::PhantomData};
+
+use serde::ser::SerializeMap;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use tracing_core::field::{Field, Visit};
+use tracing_core::{span::Attributes, Event, Metadata};
+
+use crate::{
+    baggage::SerializeBaggage, serialize_unknown, skip_variant, time::TimeSync, AsSerde, CowString,
+    DebugRecord, Detached, Form, Live, LiveDebug, SerializeAttributes, SerializeEvent, SerializeId,
+    SerializeMetadata, SerializeRecord, SerializeRecordFields, SerializeValue, TracingMap,
+    VariantTag, VariantTagSeed,
+};
+
+use self::{delta::SyncMarker, description::Description, handshake::Hello, table::StaticStrings};
+
+/// Identifies the metadata of a callsite.
+///
+/// Ids are derived from the address of the callsite's `'static` metadata,
+/// so they are unique and stable for the lifetime of a process, but not
+/// across runs of a program; a consumer must receive the matching
+/// [`TracingWire::DefineMetadata`] from each run.
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+#[cfg_attr(feature = "postcard-schema", derive(postcard_schema::Schema))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct SerializeMetadataId {
+    pub id: u64,
+}
+
+impl SerializeMetadataId {
+    /// Returns the id of a callsite's metadata.
+    pub fn of(metadata: &'static Metadata<'static>) -> Self {
+        SerializeMetadataId {
+            id: metadata as *const Metadata<'static> as usize as u64,
+        }
+    }
+}
+
+/// An event that refers to its metadata by id.
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "postcard-schema", derive(postcard_schema::Schema))]
+pub struct SerializeEventRef<'a, F: Form = Detached> {
+    #[serde(borrow)]
+    pub fields: SerializeRecordFields<'a, F>,
+    pub metadata: SerializeMetadataId,
+    pub parent: Option<SerializeId>,
+}
+
+impl<'a> SerializeEventRef<'a, Live> {
+    pub fn new(event: &'a Event<'a>) -> Self {
+        SerializeEventRef {
+            fields: SerializeRecordFields::Ser(event),
+            metadata: SerializeMetadataId::of(event.metadata()),
+            parent: event.parent().map(|p| p.as_serde()),
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, 'b, F: Form, G: Form> PartialEq<SerializeEventRef<'b, G>> for SerializeEventRef<'a, F> {
+    fn eq(&self, other: &SerializeEventRef<'b, G>) -> bool {
+        self.fields == other.fields
+            && self.metadata == other.metadata
+            && self.parent == other.parent
+    }
+}
+
+impl<'a> From<SerializeEventRef<'a>> for SerializeEventRef<'a, Live> {
+    fn from(other: SerializeEventRef<'a>) -> Self {
+        SerializeEventRef {
+            fields: other.fields.into(),
+            metadata: other.metadata,
+            parent: other.parent,
+        }
+    }
+}
+
+/// Span attributes that refer to their metadata by id.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "postcard-schema", derive(postcard_schema::Schema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct SerializeAttributesRef {
+    pub metadata: SerializeMetadataId,
+    pub parent: Option<SerializeId>,
+    #[cfg_attr(feature = "camel-case", serde(rename = "isRoot", alias = "is_root"))]
+    #[cfg_attr(not(feature = "camel-case"), serde(alias = "isRoot"))]
+    pub is_root: bool,
+}
+
+impl SerializeAttributesRef {
+    pub fn new(attrs: &Attributes<'_>) -> Self {
+        SerializeAttributesRef {
+            metadata: SerializeMetadataId::of(attrs.metadata()),
+            parent: attrs.parent().map(|p| p.as_serde()),
+            is_root: attrs.is_root(),
+        }
+    }
+}
+
+/// One message in a stream of trace data.
+///
+/// Each kind of message is written with a fixed index, by which positional
+/// formats such as postcard tell them apart, and a fixed name. Kinds are only
+/// ever added at the end, with the next index, so that older consumers read
+/// them as [`Unknown`](TracingWire::Unknown).
+#[derive(Debug)]
+#[cfg_attr(feature = "postcard-schema", derive(postcard_schema::Schema))]
+pub enum TracingWire<'a, F: Form = Detached> {
+    /// Defines the metadata referred to by `id` in later messages.
+    DefineMetadata {
+        id: SerializeMetadataId,
+        metadata: SerializeMetadata<'a>,
+    },
+    /// A span was created.
+    NewSpan {
+        id: SerializeId,
+        attributes: SerializeAttributes<'a>,
+    },
+    /// A span was created, with metadata defined earlier in the stream.
+    NewSpanRef {
+        id: SerializeId,
+        attributes: SerializeAttributesRef,
+    },
+    /// Values were recorded on a span.
+    Record {
+        span: SerializeId,
+        values: SerializeRecord<'a, F>,
+    },
+    /// A span follows from another one.
+    FollowsFrom {
+        span: SerializeId,
+        follows: SerializeId,
+    },
+    /// An event occurred.
+    Event(SerializeEvent<'a, F>),
+    /// An event occurred, with metadata defined earlier in the stream.
+    EventRef(SerializeEventRef<'a, F>),
+    /// A span was entered.
+    Enter(SerializeId),
+    /// A span was exited.
+    Exit(SerializeId),
+    /// A span was closed.
+    CloseSpan(SerializeId),
+    /// Defines the string referred to by `id` in later messages.
+    DefineString { id: StringId, value: CowString<'a> },
+    /// An event occurred, with metadata and field names defined earlier in
+    /// the stream.
+    EventInterned(SerializeEventInterned<'a, F>),
+    /// The state of the stream, sent now and then so that a consumer that
+    /// starts reading mid-stream can decode what follows.
+    Sync(SyncMarker),
+    /// The producer panicked or faulted, and is about to stop.
+    Panic(SerializePanic<'a, F>),
+    /// The time of day at a tick count of the producer's clock.
+    TimeSync(TimeSync),
+    /// How many messages the producer's sink has sent and lost so far.
+    PipelineStats(SerializePipelineStats),
+    /// The first message of a stream, saying what it uses (see
+    /// [`handshake`]).
+    Hello(Hello<'a>),
+    /// Messages the producer discarded rather than sent, so that a consumer
+    /// can show where they are missing.
+    Dropped(SerializeDropped),
+    /// The baggage of a span, inherited entries included, which the spans
+    /// created within it inherit in turn.
+    Baggage {
+        span: SerializeId,
+        baggage: SerializeBaggage<'a>,
+    },
+    /// How the stream is encoded, sent after its hello so that it can be
+    /// decoded without the producer (see [`description`]).
+    Description(Description<'a>),
+    /// A kind of message added in a later version of this crate.
+    ///
+    /// Human-readable formats such as JSON skip over the message. Positional
+    /// formats such as postcard cannot tell where it ends, and leave the rest
+    /// of the input unread, which is only right when the message is the last
+    /// thing in it, as it is in a [`DeltaFrame`](delta::DeltaFrame) or a
+    /// [`SourceFrame`](delta::SourceFrame).
+    ///
+    /// There is nothing left to send of such a message, so it cannot be
+    /// serialized.
+    Unknown,
+}
+
+const WIRE_VARIANTS: &[&str] = &[
+    "DefineMetadata",
+    "NewSpan",
+    "NewSpanRef",
+    "Record",
+    "FollowsFrom",
+    "Event",
+    "EventRef",
+    "Enter",
+    "Exit",
+    "CloseSpan",
+    "DefineString",
+    "EventInterned",
+    "Sync",
+    "Panic",
+    "TimeSync",
+    "PipelineStats",
+    "Hello",
+    "Dropped",
+    "Baggage",
+    "Description",
+];
+
+impl<'a, F: Form> Serialize for TracingWire<'a, F> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        use TracingWire as W;
+
+        const NAME: &str = "TracingWire";
+        match self {
+            W::DefineMetadata { id, metadata } => struct_variant(
+                serializer,
+                0,
+                "DefineMetadata",
+                ("id", id),
+                ("metadata", metadata),
+            ),
+            W::NewSpan { id, attributes } => struct_variant(
+                serializer,
+                1,
+                "NewSpan",
+                ("id", id),
+                ("attributes", attributes),
+            ),
+            W::NewSpanRef { id, attributes } => struct_variant(
+                serializer,
+                2,
+                "NewSpanRef",
+                ("id", id),
+                ("attributes", attributes),
+            ),
+            W::Record { span, values } => {
+                struct_variant(serializer, 3, "Record", ("span", span), ("values", values))
+            }
+            W::FollowsFrom { span, follows } => struct_variant(
+                serializer,
+                4,
+                "FollowsFrom",
+                ("span", span),
+                ("follows", follows),
+            ),
+            W::Event(e) => serializer.serialize_newtype_variant(NAME, 5, "Event", e),
+            W::EventRef(e) => serializer.serialize_newtype_variant(NAME, 6, "EventRef", e),
+            W::Enter(id) => serializer.serialize_newtype_variant(NAME, 7, "Enter", id),
+            W::Exit(id) => serializer.serialize_newtype_variant(NAME, 8, "Exit", id),
+            W::CloseSpan(id) => serializer.serialize_newtype_variant(NAME, 9, "CloseSpan", id),
+            W::DefineString { id, value } => {
+                struct_variant(serializer, 10, "DefineString", ("id", id), ("value", value))
+            }
+            W::EventInterned(e) => {
+                serializer.serialize_newtype_variant(NAME, 11, "EventInterned", e)
+            }
+            W::Sync(sync) => serializer.serialize_newtype_variant(NAME, 12, "Sync", sync),
+            W::Panic(panic) => serializer.serialize_newtype_variant(NAME, 13, "Panic", panic),
+            W::TimeSync(sync) => serializer.serialize_newtype_variant(NAME, 14, "TimeSync", sync),
+            W::PipelineStats(stats) => {
+                serializer.serialize_newtype_variant(NAME, 15, "PipelineStats", stats)
+            }
+            W::Hello(hello) => serializer.serialize_newtype_variant(NAME, 16, "Hello", hello),
+            W::Dropped(dropped) => {
+                serializer.serialize_newtype_variant(NAME, 17, "Dropped", dropped)
+            }
+            W::Baggage { span, baggage } => struct_variant(
+                serializer,
+                18,
+                "Baggage",
+                ("span", span),
+                ("baggage", baggage),
+            ),
+            W::Description(description) => {
+                serializer.serialize_newtype_variant(NAME, 19, "Description", description)
+            }
+            // There is nothing left to send of it.
+            W::Unknown => Err(serde::ser::Error::custom(
+                "the enum variant TracingWire::Unknown cannot be serialized",
+            )),
+        }
+    }
+}
+
+/// Writes a struct variant of `TracingWire`, all of which have two fields.
+fn struct_variant<S, A, B>(
+    serializer: S,
+    index: u32,
+    variant: &'static str,
+    (a, a_value): (&'static str, &A),
+    (b, b_value): (&'static str, &B),
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    A: Serialize,
+    B: Serialize,
+{
+    use serde::ser::SerializeStructVariant;
+
+    let mut state = serializer.serialize_struct_variant("TracingWire", index, variant, 2)?;
+    state.serialize_field(a, a_value)?;
+    state.serialize_field(b, b_value)?;
+    state.end()
+}
+
+// The struct variants of `TracingWire`, which are encoded the same way as a
+// newtype variant holding a struct with the same fields.
+
+#[derive(Deserialize)]
+struct DefineMetadata<'a> {
+    id: SerializeMetadataId,
+    #[serde(borrow)]
+    metadata: SerializeMetadata<'a>,
+}
+
+#[derive(Deserialize)]
+struct NewSpan<'a> {
+    id: SerializeId,
+    #[serde(borrow)]
+    attributes: SerializeAttributes<'a>,
+}
+
+#[derive(Deserialize)]
+struct NewSpanRef {
+    id: SerializeId,
+    attributes: SerializeAttributesRef,
+}
+
+#[derive(Deserialize)]
+#[serde(bound(deserialize = ""))]
+struct Record<'a, F: Form> {
+    span: SerializeId,
+    #[serde(borrow)]
+    values: SerializeRecord<'a, F>,
+}
+
+#[derive(Deserialize)]
+struct FollowsFrom {
+    span: SerializeId,
+    follows: SerializeId,
+}
+
+#[derive(Deserialize)]
+struct DefineString<'a> {
+    id: StringId,
+    #[serde(borrow)]
+    value: CowString<'a>,
+}
+
+#[derive(Deserialize)]
+struct Baggage<'a> {
+    span: SerializeId,
+    #[serde(borrow)]
+    baggage: SerializeBaggage<'a>,
+}
+
+impl<'de: 'a, 'a, F: Form> Deserialize<'de> for TracingWire<'a, F>
+where
+    F: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let visitor = WireVisitor {
+            human_readable: deserializer.is_human_readable(),
+            marker: PhantomData,
+        };
+        deserializer.deserialize_enum("TracingWire", WIRE_VARIANTS, visitor)
+    }
+}
+
+struct WireVisitor<'a, F: Form> {
+    human_readable: bool,
+    marker: PhantomData<TracingWire<'a, F>>,
+}
+
+impl<'de: 'a, 'a, F: Form> de::Visitor<'de> for WireVisitor<'a, F>
+where
+    F: Deserialize<'de>,
+{
+    type Value = TracingWire<'a, F>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("enum TracingWire")
+    }
+
+    fn visit_enum<A>(self, data: A) -> Result<Self::Value, A::Error>
+    where
+        A: de::EnumAccess<'de>,
+    {
+        use de::VariantAccess;
+        use TracingWire as W;
+
+        let (tag, variant) = data.variant_seed(VariantTagSeed(WIRE_VARIANTS))?;
+        let index = match tag {
+            VariantTag::Known(index) => index,
+            VariantTag::Unknown => return variant.unit_variant().map(|()| W::Unknown),
+            VariantTag::Other => {
+                return skip_variant(variant, self.human_readable, true).map(|()| W::Unknown)
+            }
+        };
+        Ok(match index {
+            0 => {
+                let DefineMetadata { id, metadata } = variant.newtype_variant()?;
+                W::DefineMetadata { id, metadata }
+            }
+            1 => {
+                let NewSpan { id, attributes } = variant.newtype_variant()?;
+                W::NewSpan { id, attributes }
+            }
+            2 => {
+                let NewSpanRef { id, attributes } = variant.newtype_variant()?;
+                W::NewSpanRef { id, attributes }
+            }
+            3 => {
+                let Record { span, values } = variant.newtype_variant()?;
+                W::Record { span, values }
+            }
+            4 => {
+                let FollowsFrom { span, follows } = variant.newtype_variant()?;
+                W::FollowsFrom { span, follows }
+            }
+            5 => W::Event(variant.newtype_variant()?),
+            6 => W::EventRef(variant.newtype_variant()?),
+            7 => W::Enter(variant.newtype_variant()?),
+            8 => W::Exit(variant.newtype_variant()?),
+            9 => W::CloseSpan(variant.newtype_variant()?),
+            10 => {
+                let DefineString { id, value } = variant.newtype_variant()?;
+                W::DefineString { id, value }
+            }
+            11 => W::EventInterned(variant.newtype_variant()?),
+            12 => W::Sync(variant.newtype_variant()?),
+            13 => W::Panic(variant.newtype_variant()?),
+            14 => W::TimeSync(variant.newtype_variant()?),
+            15 => W::PipelineStats(variant.newtype_variant()?),
+            16 => W::Hello(variant.newtype_variant()?),
+            17 => W::Dropped(variant.newtype_variant()?),
+            18 => {
+                let Baggage { span, baggage } = variant.newtype_variant()?;
+                W::Baggage { span, baggage }
+            }
+            _ => W::Description(variant.newtype_variant()?),
+        })
+    }
+}
+
+/// Counts of what happened to the messages given to a sink, such as a
+/// [`FramedWriter`](crate::embedded_io::FramedWriter).
+///
+/// Every message is counted exactly once. The counts saturate rather than
+/// wrap, and are only reset when the producer resets them.
+#[derive(Debug, Copy, Clone, Default, Hash, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "postcard-schema", derive(postcard_schema::Schema))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct SerializePipelineStats {
+    /// Messages that were serialized and sent.
+    pub sent: u32,
+    /// Messages that were serialized, but the transport failed to send.
+    pub dropped: u32,
+    /// Messages too large for the sink's buffer.
+    pub overflows: u32,
+    /// Messages that failed to serialize for any other reason.
+    pub errors: u32,
+}
+
+impl SerializePipelineStats {
+    /// The number of messages lost, for any reason.
+    pub fn lost(&self) -> u32 {
+        self.dropped
+            .saturating_add(self.overflows)
+            .saturating_add(self.errors)
+    }
+}
+
+/// Messages a producer discarded rather than sent, sent in their place as a
+/// [`TracingWire::Dropped`].
+///
+/// Messages are numbered by the producer, e.g. by counting every message it
+/// was handed. Of those from `first_seq` to `last_seq`, `count` were
+/// discarded for `reason`; any others in between were sent, or discarded for
+/// another reason.
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "postcard-schema", derive(postcard_schema::Schema))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct SerializeDropped {
+    pub count: u32,
+    pub reason: DropReason,
+    pub first_seq: u32,
+    pub last_seq: u32,
+}
+
+/// Why a producer discarded messages.
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+#[non_exhaustive]
+#[cfg_attr(feature = "postcard-schema", derive(postcard_schema::Schema))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum DropReason {
+    /// The queue in front of the sink was full.
+    QueueFull,
+    /// The producer was sending more than it is allowed to.
+    RateLimited,
+    /// The message was too large for the sink's buffer.
+    Oversize,
+    /// A reason added in a later version of this crate.
+    ///
+    /// Reasons carry nothing else, so they read as `Unknown` in every format.
+    Unknown,
+}
+
+const DROP_REASONS: &[&str] = &["QueueFull", "RateLimited", "Oversize"];
+
+impl DropReason {
+    fn index(self) -> usize {
+        match self {
+            DropReason::QueueFull => 0,
+            DropReason::RateLimited => 1,
+            DropReason::Oversize => 2,
+            DropReason::Unknown => 3,
+        }
+    }
+}
+
+impl fmt::Display for DropReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DropReason::QueueFull => "queue full",
+            DropReason::RateLimited => "rate limited",
+            DropReason::Oversize => "oversize",
+            DropReason::Unknown => "unknown reason",
+        })
+    }
+}
+
+impl Serialize for DropReason {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        const NAME: &str = "DropReason";
+        match self {
+            DropReason::Unknown => serialize_unknown(NAME, serializer),
+            reason => {
+                let index = reason.index();
+                serializer.serialize_unit_variant(NAME, index as u32, DROP_REASONS[index])
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for DropReason {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_enum("DropReason", DROP_REASONS, DropReasonVisitor)
+    }
+}
+
+struct DropReasonVisitor;
+
+impl<'de> de::Visitor<'de> for DropReasonVisitor {
+    type Value = DropReason;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("enum DropReason")
+    }
+
+    fn visit_enum<A>(self, data: A) -> Result<Self::Value, A::Error>
+    where
+        A: de::EnumAccess<'de>,
+    {
+        use de::VariantAccess;
+
+        let (tag, variant) = data.variant_seed(VariantTagSeed(DROP_REASONS))?;
+        variant.unit_variant()?;
+        Ok(match tag {
+            VariantTag::Known(0) => DropReason::QueueFull,
+            VariantTag::Known(1) => DropReason::RateLimited,
+            VariantTag::Known(2) => DropReason::Oversize,
+            VariantTag::Known(_) | VariantTag::Unknown | VariantTag::Other => DropReason::Unknown,
+        })
+    }
+}
+
+/// Collects the messages a producer discards into [`SerializeDropped`]
+/// notices, one for each reason, to send once its sink has room again:
+///
+/// ```rust
+/// use tracing_serde_structured::wire::{DropReason, DropTracker};
+///
+/// let mut drops = DropTracker::new();
+/// drops.record(7, DropReason::QueueFull);
+/// drops.record(8, DropReason::QueueFull);
+/// drops.record(9, DropReason::Oversize);
+///
+/// // Later, before sending the next message:
+/// let dropped = drops.take().unwrap();
+/// assert_eq!((dropped.count, dropped.first_seq, dropped.last_seq), (2, 7, 8));
+/// assert_eq!(drops.take().unwrap().reason, DropReason::Oversize);
+/// assert!(drops.take().is_none());
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct DropTracker {
+    runs: [Option<SerializeDropped>; 4],
+}
+
+impl DropTracker {
+    pub const fn new() -> Self {
+        Self { runs: [None; 4] }
+    }
+
+    /// Records that the message numbered `seq` was discarded for `reason`.
+    pub fn record(&mut self, seq: u32, reason: DropReason) {
+        match &mut self.runs[reason.index()] {
+            Some(run) => {
+                run.count = run.count.saturating_add(1);
+                run.last_seq = seq;
+            }
+            run => {
+                *run = Some(SerializeDropped {
+                    count: 1,
+                    reason,
+                    first_seq: seq,
+                    last_seq: seq,
+                })
+            }
+        }
+    }
+
+    /// Whether there are no discarded messages left to report.
+    pub fn is_empty(&self) -> bool {
+        self.runs.iter().all(Option::is_none)
+    }
+
+    /// Takes the notice of the earliest discarded messages. Call it until it
+    /// returns `None`, sending each notice, before sending the next message.
+    pub fn take(&mut self) -> Option<SerializeDropped> {
+        self.runs
+            .iter_mut()
+            .filter(|run| run.is_some())
+            .min_by_key(|run| run.map(|run| run.first_seq))?
+            .take()
+    }
+}
+
+/// Why a producer stopped, sent as its last message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "postcard-schema", derive(postcard_schema::Schema))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SerializePanic<'a, F: Form = Detached> {
+    /// The panic message, or the name of the fault.
+    #[serde(borrow)]
+    pub message: DebugRecord<'a, F>,
+    #[serde(borrow)]
+    pub file: Option<CowString<'a>>,
+    pub line: Option<u32>,
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, 'b, F: Form, G: Form> PartialEq<SerializePanic<'b, G>> for SerializePanic<'a, F> {
+    fn eq(&self, other: &SerializePanic<'b, G>) -> bool {
+        self.message == other.message && self.file == other.file && self.line == other.line
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, F: Form> Eq for SerializePanic<'a, F> {}
+
+impl<'a> From<SerializePanic<'a>> for SerializePanic<'a, Live> {
+    fn from(other: SerializePanic<'a>) -> Self {
+        SerializePanic {
+            message: other.message.into(),
+            file: other.file,
+            line: other.line,
+        }
+    }
+}
+
+/// Messages are equal if they are the same kind of message, with equal
+/// contents.
+#[cfg(feature = "alloc")]
+impl<'a, 'b, F: Form, G: Form> PartialEq<TracingWire<'b, G>> for TracingWire<'a, F> {
+    fn eq(&self, other: &TracingWire<'b, G>) -> bool {
+        use TracingWire as W;
+        match (self, other) {
+            (
+                W::DefineMetadata { id, metadata },
+                W::DefineMetadata {
+                    id: id2,
+                    metadata: metadata2,
+                },
+            ) => id == id2 && metadata == metadata2,
+            (
+                W::NewSpan { id, attributes },
+                W::NewSpan {
+                    id: id2,
+                    attributes: attributes2,
+                },
+            ) => id == id2 && attributes == attributes2,
+            (
+                W::NewSpanRef { id, attributes },
+                W::NewSpanRef {
+                    id: id2,
+                    attributes: attributes2,
+                },
+            ) => id == id2 && attributes == attributes2,
+            (
+                W::Record { span, values },
+                W::Record {
+                    span: span2,
+                    values: values2,
+                },
+            ) => span == span2 && values == values2,
+            (
+                W::FollowsFrom { span, follows },
+                W::FollowsFrom {
+                    span: span2,
+                    follows: follows2,
+                },
+            ) => span == span2 && follows == follows2,
+            (W::Event(a), W::Event(b)) => a == b,
+            (W::EventRef(a), W::EventRef(b)) => a == b,
+            (W::Enter(a), W::Enter(b)) => a == b,
+            (W::Exit(a), W::Exit(b)) => a == b,
+            (W::CloseSpan(a), W::CloseSpan(b)) => a == b,
+            (
+                W::DefineString { id, value },
+                W::DefineString {
+                    id: id2,
+                    value: value2,
+                },
+            ) => id == id2 && value == value2,
+            (W::EventInterned(a), W::EventInterned(b)) => a == b,
+            (W::Sync(a), W::Sync(b)) => a == b,
+            (W::Panic(a), W::Panic(b)) => a == b,
+            (W::TimeSync(a), W::TimeSync(b)) => a == b,
+            (W::PipelineStats(a), W::PipelineStats(b)) => a == b,
+            (W::Hello(a), W::Hello(b)) => a == b,
+            (W::Dropped(a), W::Dropped(b)) => a == b,
+            (
+                W::Baggage { span, baggage },
+                W::Baggage {
+                    span: span2,
+                    baggage: baggage2,
+                },
+            ) => span == span2 && baggage == baggage2,
+            (W::Description(a), W::Description(b)) => a == b,
+            (W::Unknown, W::Unknown) => true,
+            _ => false,
+        }
+    }
+}
+
+/// A message that does not borrow any `tracing` data can be sent along with
+/// ones that do.
+impl<'a> From<TracingWire<'a>> for TracingWire<'a, Live> {
+    fn from(other: TracingWire<'a>) -> Self {
+        match other {
+            TracingWire::DefineMetadata { id, metadata } => {
+                TracingWire::DefineMetadata { id, metadata }
+            }
+            TracingWire::NewSpan { id, attributes } => TracingWire::NewSpan { id, attributes },
+            TracingWire::NewSpanRef { id, attributes } => {
+                TracingWire::NewSpanRef { id, attributes }
+            }
+            TracingWire::Record { span, values } => TracingWire::Record {
+                span,
+                values: values.into(),
+            },
+            TracingWire::FollowsFrom { span, follows } => {
+                TracingWire::FollowsFrom { span, follows }
+            }
+            TracingWire::Event(e) => TracingWire::Event(e.into()),
+            TracingWire::EventRef(e) => TracingWire::EventRef(e.into()),
+            TracingWire::Enter(id) => TracingWire::Enter(id),
+            TracingWire::Exit(id) => TracingWire::Exit(id),
+            TracingWire::CloseSpan(id) => TracingWire::CloseSpan(id),
+            TracingWire::DefineString { id, value } => TracingWire::DefineString { id, value },
+            TracingWire::EventInterned(e) => TracingWire::EventInterned(e.into()),
+            TracingWire::Sync(sync) => TracingWire::Sync(sync),
+            TracingWire::Panic(panic) => TracingWire::Panic(panic.into()),
+            TracingWire::TimeSync(sync) => TracingWire::TimeSync(sync),
+            TracingWire::PipelineStats(stats) => TracingWire::PipelineStats(stats),
+            TracingWire::Hello(hello) => TracingWire::Hello(hello),
+            TracingWire::Dropped(dropped) => TracingWire::Dropped(dropped),
+            TracingWire::Baggage { span, baggage } => TracingWire::Baggage { span, baggage },
+            TracingWire::Description(description) => TracingWire::Description(description),
+            TracingWire::Unknown => TracingWire::Unknown,
+        }
+    }
+}
+
+/// Writes the kind of message and the ids it carries, with the level, target
+/// and name of any metadata, but not field values.
+#[cfg(feature = "defmt")]
+impl<'a, F: Form> defmt::Format for TracingWire<'a, F> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        match self {
+            TracingWire::DefineMetadata { id, metadata } => defmt::write!(
+                f,
+                "DefineMetadata({} {} {} {})",
+                id,
+                metadata.level,
+                metadata.target,
+                metadata.name
+            ),
+            TracingWire::NewSpan { id, attributes } => defmt::write!(
+                f,
+                "NewSpan({} {} {} {})",
+                id,
+                attributes.metadata.level,
+                attributes.metadata.target,
+                attributes.metadata.name
+            ),
+            TracingWire::NewSpanRef { id, attributes } => {
+                defmt::write!(f, "NewSpanRef({} {})", id, attributes.metadata)
+            }
+            TracingWire::Record { span, .. } => defmt::write!(f, "Record({})", span),
+            TracingWire::FollowsFrom { span, follows } => {
+                defmt::write!(f, "FollowsFrom({} {})", span, follows)
+            }
+            TracingWire::Event(event) => defmt::write!(
+                f,
+                "Event({} {} {})",
+                event.metadata.level,
+                event.metadata.target,
+                event.metadata.name
+            ),
+            TracingWire::EventRef(event) => defmt::write!(f, "EventRef({})", event.metadata),
+            TracingWire::Enter(id) => defmt::write!(f, "Enter({})", id),
+            TracingWire::Exit(id) => defmt::write!(f, "Exit({})", id),
+            TracingWire::CloseSpan(id) => defmt::write!(f, "CloseSpan({})", id),
+            TracingWire::DefineString { id, value } => {
+                defmt::write!(f, "DefineString({} {})", id, value)
+            }
+            TracingWire::EventInterned(event) => {
+                defmt::write!(f, "EventInterned({})", event.metadata)
+            }
+            TracingWire::Sync(sync) => defmt::write!(f, "Sync({})", sync),
+            TracingWire::Panic(panic) => defmt::write!(f, "Panic({})", panic),
+            TracingWire::TimeSync(sync) => defmt::write!(f, "TimeSync({})", sync),
+            TracingWire::PipelineStats(stats) => defmt::write!(f, "PipelineStats({})", stats),
+            TracingWire::Hello(hello) => defmt::write!(f, "Hello({})", hello),
+            TracingWire::Dropped(dropped) => defmt::write!(f, "Dropped({})", dropped),
+            TracingWire::Baggage { span, baggage } => {
+                defmt::write!(f, "Baggage({} {})", span, baggage)
+            }
+            TracingWire::Description(description) => {
+                defmt::write!(f, "Description({})", description)
+            }
+            TracingWire::Unknown => defmt::write!(f, "Unknown"),
+        }
+    }
+}
+
+/// Identifies a string defined with [`TracingWire::DefineString`].
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+#[serde(transparent)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct StringId {
+    pub id: u32,
+}
+
+impl hash32::Hash for StringId {
+    fn hash<H>(&self, state: &mut H)
+    where
+        H: hash32::Hasher,
+    {
+        self.id.hash(state)
+    }
+}
+
+#[cfg(feature = "postcard-schema")]
+impl postcard_schema::Schema for StringId {
+    const SCHEMA: &'static postcard_schema::schema::NamedType =
+        &postcard_schema::schema::NamedType {
+            name: "StringId",
+            ty: u32::SCHEMA.ty,
+        };
+}
+
+/// An event that refers to its metadata and field names by id.
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "postcard-schema", derive(postcard_schema::Schema))]
+pub struct SerializeEventInterned<'a, F: Form = Detached> {
+    #[serde(borrow)]
+    pub fields: InternedFields<'a, F>,
+    pub metadata: SerializeMetadataId,
+    pub parent: Option<SerializeId>,
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, 'b, F: Form, G: Form> PartialEq<SerializeEventInterned<'b, G>>
+    for SerializeEventInterned<'a, F>
+{
+    fn eq(&self, other: &SerializeEventInterned<'b, G>) -> bool {
+        self.fields == other.fields
+            && self.metadata == other.metadata
+            && self.parent == other.parent
+    }
+}
+
+impl<'a> From<SerializeEventInterned<'a>> for SerializeEventInterned<'a, Live> {
+    fn from(other: SerializeEventInterned<'a>) -> Self {
+        let InternedFields::De(map) = other.fields;
+        SerializeEventInterned {
+            fields: InternedFields::De(map),
+            metadata: other.metadata,
+            parent: other.parent,
+        }
+    }
+}
+
+/// The values of an event's fields, keyed by the ids of their names.
+#[derive(Debug)]
+pub enum InternedFields<'a, F: Form = Detached> {
+    /// Fields of an event, with names looked up in a producer's table.
+    #[cfg(feature = "alloc")]
+    Ser {
+        event: F::Event<'a>,
+        table: &'a StringTable,
+    },
+    /// Fields of an event, with names looked up in a build-time table.
+    Static {
+        event: F::Event<'a>,
+        strings: &'a StaticStrings,
+    },
+    De(TracingMap<StringId, SerializeValue<'a>>),
+}
+
+/// Fields are equal if they have the same values for the same ids.
+#[cfg(feature = "alloc")]
+impl<'a, 'b, F: Form, G: Form> PartialEq<InternedFields<'b, G>> for InternedFields<'a, F> {
+    fn eq(&self, other: &InternedFields<'b, G>) -> bool {
+        match (self, other) {
+            (InternedFields::De(a), InternedFields::De(b)) => crate::maps_eq(a, b),
+            _ => self.to_owned() == other.to_owned(),
+        }
+    }
+}
+
+impl<'a, F: Form> Serialize for InternedFields<'a, F> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            #[cfg(feature = "alloc")]
+            InternedFields::Ser { event, table } => {
+                serialize_interned(F::event(event), Names::Table(table), serializer)
+            }
+            InternedFields::Static { event, strings } => {
+                serialize_interned(F::event(event), Names::Static(strings), serializer)
+            }
+            InternedFields::De(map) => map.serialize(serializer),
+        }
+    }
+}
+
+fn serialize_interned<S>(
+    event: &Event<'_>,
+    names: Names<'_>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let mut count = InternedCount { names, count: 0 };
+    event.record(&mut count);
+
+    let mut visitor = InternedVisitor {
+        names,
+        serializer: serializer.serialize_map(Some(count.count))?,
+        state: Ok(()),
+    };
+    event.record(&mut visitor);
+    visitor.state?;
+    visitor.serializer.end()
+}
+
+impl<'de: 'a, 'a, F: Form> Deserialize<'de> for InternedFields<'a, F> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        TracingMap::deserialize(deserializer).map(InternedFields::De)
+    }
+}
+
+#[cfg(feature = "postcard-schema")]
+impl<'a, F: Form> postcard_schema::Schema for InternedFields<'a, F> {
+    const SCHEMA: &'static postcard_schema::schema::NamedType =
+        &postcard_schema::schema::NamedType {
+            name: "InternedFields",
+            ty: &postcard_schema::schema::DataModelType::Map {
+                key: StringId::SCHEMA,
+                val: SerializeValue::SCHEMA,
+            },
+        };
+}
+
+/// Assigns ids to field names on the producer side of a stream.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Default)]
+pub struct StringTable {
+    ids: alloc::collections::BTreeMap<&'static str, StringId>,
+}
+
+#[cfg(feature = "alloc")]
+impl StringTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the id of `value`, and the message defining it if it is new.
+    pub fn intern(&mut self, value: &'static str) -> (StringId, Option<TracingWire<'static>>) {
+        if let Some(id) = self.ids.get(value) {
+            return (*id, None);
+        }
+        let id = StringId {
+            id: self.ids.len() as u32,
+        };
+        self.ids.insert(value, id);
+        let define = TracingWire::DefineString {
+            id,
+            value: CowString::Static(value),
+        };
+        (id, Some(define))
+    }
+
+    /// Returns the id of `value`, if it has been interned.
+    pub fn get(&self, value: &str) -> Option<StringId> {
+        self.ids.get(value).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Convert an event, first interning any of its field names that are
+    /// new and passing their definitions to `define`, which must send them
+    /// before the event.
+    pub fn event<'a>(
+        &'a mut self,
+        event: &'a Event<'a>,
+        mut define: impl FnMut(TracingWire<'static>),
+    ) -> SerializeEventInterned<'a, Live> {
+        for field in event.metadata().fields() {
+            if let (_, Some(msg)) = self.intern(field.name()) {
+                define(msg);
+            }
+        }
+        SerializeEventInterned {
+            fields: InternedFields::Ser { event, table: self },
+            metadata: SerializeMetadataId::of(event.metadata()),
+            parent: event.parent().map(|p| p.as_serde()),
+        }
+    }
+}
+
+/// Where the ids of an event's field names come from.
+#[derive(Clone, Copy)]
+enum Names<'t> {
+    #[cfg(feature = "alloc")]
+    Table(&'t StringTable),
+    Static(&'t StaticStrings),
+}
+
+impl<'t> Names<'t> {
+    fn get(&self, name: &str) -> Option<StringId> {
+        match self {
+            #[cfg(feature = "alloc")]
+            Names::Table(table) => table.get(name),
+            Names::Static(strings) => strings.id_of(name),
+        }
+    }
+}
+
+struct InternedCount<'t> {
+    names: Names<'t>,
+    count: usize,
+}
+
+impl<'t> Visit for InternedCount<'t> {
+    fn record_debug(&mut self, field: &Field, _value: &dyn fmt::Debug) {
+        if self.names.get(field.name()).is_some() {
+            self.count += 1;
+        }
+    }
+}
+
+/// Serializes the fields that have an id, keyed by that id.
+struct InternedVisitor<'t, S: SerializeMap> {
+    names: Names<'t>,
+    serializer: S,
+    state: Result<(), S::Error>,
+}
+
+impl<'t, S: SerializeMap> InternedVisitor<'t, S> {
+    fn entry(&mut self, field: &Field, value: &impl Serialize) {
+        if self.state.is_ok() {
+            if let Some(id) = self.names.get(field.name()) {
+                self.state = self.serializer.serialize_entry(&id, value);
+            }
+        }
+    }
+}
+
+impl<'t, S: SerializeMap> Visit for InternedVisitor<'t, S> {
+    #[cfg(all(tracing_unstable, feature = "valuable"))]
+    fn record_value(&mut self, field: &Field, value: valuable_crate::Value<'_>) {
+        #[cfg(feature = "alloc")]
+        self.entry(field, &SerializeValue::from_valuable(value));
+        #[cfg(not(feature = "alloc"))]
+        self.entry(field, &LiveDebug(&format_args!("{:?}", value)));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.entry(field, &SerializeValue::Bool(value))
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.entry(field, &LiveDebug(&format_args!("{:?}", value)))
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.entry(field, &SerializeValue::U64(value))
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.entry(field, &SerializeValue::I64(value))
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.entry(field, &SerializeValue::F64(value))
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.entry(field, &SerializeValue::Str(value.into()))
+    }
+}
+
+impl<'a, F: Form> SerializeEventRef<'a, F> {
+    pub fn is_borrowed(&self) -> bool {
+        self.fields.is_borrowed()
+    }
+
+    pub fn is_owned(&self) -> bool {
+        !self.is_borrowed()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, F: Form> SerializeEventRef<'a, F> {
+    pub fn ensure_owned(&mut self) {
+        self.fields.ensure_owned();
+    }
+
+    pub fn to_owned(&self) -> SerializeEventRef<'static> {
+        SerializeEventRef {
+            fields: self.fields.to_owned(),
+            metadata: self.metadata,
+            parent: self.parent.clone(),
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl SerializeAttributesRef {
+    pub fn to_owned(&self) -> Self {
+        self.clone()
+    }
+}
+
+impl<'a, F: Form> TracingWire<'a, F> {
+    /// Whether this message refers to live `tracing` data or borrows from
+    /// the buffer it was decoded from, and so cannot be kept past either.
+    pub fn is_borrowed(&self) -> bool {
+        match self {
+            TracingWire::DefineMetadata { metadata, .. } => metadata.is_borrowed(),
+            TracingWire::NewSpan { attributes, .. } => attributes.is_borrowed(),
+            TracingWire::Record { values, .. } => values.is_borrowed(),
+            TracingWire::Event(e) => e.is_borrowed(),
+            TracingWire::EventRef(e) => e.is_borrowed(),
+            TracingWire::DefineString { value, .. } => value.is_borrowed(),
+            TracingWire::EventInterned(e) => e.is_borrowed(),
+            TracingWire::Panic(panic) => panic.is_borrowed(),
+            TracingWire::Hello(hello) => hello.is_borrowed(),
+            TracingWire::Baggage { baggage, .. } => baggage.is_borrowed(),
+            TracingWire::Description(description) => description.is_borrowed(),
+            TracingWire::NewSpanRef { .. }
+            | TracingWire::FollowsFrom { .. }
+            | TracingWire::Enter(_)
+            | TracingWire::Exit(_)
+            | TracingWire::CloseSpan(_)
+            | TracingWire::Sync(_)
+            | TracingWire::TimeSync(_)
+            | TracingWire::PipelineStats(_)
+            | TracingWire::Dropped(_)
+            | TracingWire::Unknown => false,
+        }
+    }
+
+    pub fn is_owned(&self) -> bool {
+        !self.is_borrowed()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, F: Form> TracingWire<'a, F> {
+    /// Copies whatever this message borrows in place, so that it can be
+    /// kept without the `'static` copy of [`Self::to_owned`] when most of
+    /// it is owned already.
+    pub fn ensure_owned(&mut self) {
+        match self {
+            TracingWire::DefineMetadata { metadata, .. } => metadata.ensure_owned(),
+            TracingWire::NewSpan { attributes, .. } => attributes.ensure_owned(),
+            TracingWire::Record { values, .. } => values.ensure_owned(),
+            TracingWire::Event(e) => e.ensure_owned(),
+            TracingWire::EventRef(e) => e.ensure_owned(),
+            TracingWire::DefineString { value, .. } => value.ensure_owned(),
+            TracingWire::EventInterned(e) => e.ensure_owned(),
+            TracingWire::Panic(panic) => panic.ensure_owned(),
+            TracingWire::Hello(hello) => hello.ensure_owned(),
+            TracingWire::Baggage { baggage, .. } => baggage.ensure_owned(),
+            TracingWire::Description(description) => description.ensure_owned(),
+            TracingWire::NewSpanRef { .. }
+            | TracingWire::FollowsFrom { .. }
+            | TracingWire::Enter(_)
+            | TracingWire::Exit(_)
+            | TracingWire::CloseSpan(_)
+            | TracingWire::Sync(_)
+            | TracingWire::TimeSync(_)
+            | TracingWire::PipelineStats(_)
+            | TracingWire::Dropped(_)
+            | TracingWire::Unknown => {}
+        }
+    }
+
+    pub fn to_owned(&self) -> TracingWire<'static> {
+        match self {
+            TracingWire::DefineMetadata { id, metadata } => TracingWire::DefineMetadata {
+                id: *id,
+                metadata: metadata.to_owned(),
+            },
+            TracingWire::NewSpan { id, attributes } => TracingWire::NewSpan {
+                id: id.clone(),
+                attributes: attributes.to_owned(),
+            },
+            TracingWire::NewSpanRef { id, attributes } => TracingWire::NewSpanRef {
+                id: id.clone(),
+                attributes: attributes.clone(),
+            },
+            TracingWire::Record { span, values } => TracingWire::Record {
+                span: span.clone(),
+                values: values.to_owned(),
+            },
+            TracingWire::FollowsFrom { span, follows } => TracingWire::FollowsFrom {
+                span: span.clone(),
+                follows: follows.clone(),
+            },
+            TracingWire::Event(e) => TracingWire::Event(e.to_owned()),
+            TracingWire::EventRef(e) => TracingWire::EventRef(e.to_owned()),
+            TracingWire::Enter(id) => TracingWire::Enter(id.clone()),
+            TracingWire::Exit(id) => TracingWire::Exit(id.clone()),
+            TracingWire::CloseSpan(id) => TracingWire::CloseSpan(id.clone()),
+            TracingWire::DefineString { id, value } => TracingWire::DefineString {
+                id: *id,
+                value: value.to_owned_name(),
+            },
+            TracingWire::EventInterned(e) => TracingWire::EventInterned(e.to_owned()),
+            TracingWire::Sync(sync) => TracingWire::Sync(sync.clone()),
+            TracingWire::Panic(panic) => TracingWire::Panic(panic.to_owned()),
+            TracingWire::TimeSync(sync) => TracingWire::TimeSync(*sync),
+            TracingWire::PipelineStats(stats) => TracingWire::PipelineStats(*stats),
+            TracingWire::Hello(hello) => TracingWire::Hello(hello.to_owned()),
+            TracingWire::Dropped(dropped) => TracingWire::Dropped(*dropped),
+            TracingWire::Baggage { span, baggage } => TracingWire::Baggage {
+                span: span.clone(),
+                baggage: baggage.to_owned(),
+            },
+            TracingWire::Description(description) => {
+                TracingWire::Description(description.to_owned())
+            }
+            TracingWire::Unknown => TracingWire::Unknown,
+        }
+    }
+}
+
+impl<'a, F: Form> SerializePanic<'a, F> {
+    pub fn is_borrowed(&self) -> bool {
+        self.message.is_borrowed() || self.file.as_ref().is_some_and(CowString::is_borrowed)
+    }
+
+    pub fn is_owned(&self) -> bool {
+        !self.is_borrowed()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, F: Form> SerializePanic<'a, F> {
+    pub fn ensure_owned(&mut self) {
+        self.message.ensure_owned();
+        if let Some(file) = &mut self.file {
+            file.ensure_owned();
+        }
+    }
+
+    pub fn to_owned(&self) -> SerializePanic<'static> {
+        SerializePanic {
+            message: self.message.to_owned(),
+            file: self.file.as_ref().map(|f| f.to_owned()),
+            line: self.line,
+        }
+    }
+}
+
+impl<'a, F: Form> InternedFields<'a, F> {
+    /// Whether this refers to a live event or holds any borrowed values.
+    pub fn is_borrowed(&self) -> bool {
+        match self {
+            #[cfg(feature = "alloc")]
+            InternedFields::Ser { .. } => true,
+            InternedFields::Static { .. } => true,
+            InternedFields::De(map) => map.values().any(SerializeValue::is_borrowed),
+        }
+    }
+
+    pub fn is_owned(&self) -> bool {
+        !self.is_borrowed()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, F: Form> InternedFields<'a, F> {
+    pub fn ensure_owned(&mut self) {
+        match self {
+            InternedFields::Ser { event, table } => {
+                *self = owned_interned(F::event(event), Names::Table(table))
+            }
+            InternedFields::Static { event, strings } => {
+                *self = owned_interned(F::event(event), Names::Static(strings))
+            }
+            InternedFields::De(map) => map.values_mut().for_each(SerializeValue::ensure_owned),
+        }
+    }
+
+    pub fn to_owned(&self) -> InternedFields<'static> {
+        match self {
+            InternedFields::Ser { event, table } => {
+                owned_interned(F::event(event), Names::Table(table))
+            }
+            InternedFields::Static { event, strings } => {
+                owned_interned(F::event(event), Names::Static(strings))
+            }
+            InternedFields::De(map) => {
+                InternedFields::De(map.iter().map(|(k, v)| (*k, v.to_owned())).collect())
+            }
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+fn owned_interned<'a, F: Form>(event: &Event<'_>, names: Names<'_>) -> InternedFields<'a, F> {
+    let mut visit = OwnedInterned {
+        names,
+        map: TracingMap::new(),
+    };
+    event.record(&mut visit);
+    InternedFields::De(visit.map)
+}
+
+#[cfg(feature = "alloc")]
+struct OwnedInterned<'t, 'a> {
+    names: Names<'t>,
+    map: TracingMap<StringId, SerializeValue<'a>>,
+}
+
+#[cfg(feature = "alloc")]
+impl Visit for OwnedInterned<'_, '_> {
+    #[cfg(all(tracing_unstable, feature = "valuable"))]
+    fn record_value(&mut self, field: &Field, value: valuable_crate::Value<'_>) {
+        if let Some(id) = self.names.get(field.name()) {
+            self.map.insert(id, SerializeValue::from_valuable(value));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if let Some(id) = self.names.get(field.name()) {
+            let value = CowString::formatted(format_args!("{:?}", value));
+            self.map
+                .insert(id, SerializeValue::Debug(DebugRecord::De(value)));
+        }
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        if let Some(id) = self.names.get(field.name()) {
+            self.map.insert(id, SerializeValue::Bool(value));
+        }
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if let Some(id) = self.names.get(field.name()) {
+            self.map.insert(id, SerializeValue::U64(value));
+        }
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        if let Some(id) = self.names.get(field.name()) {
+            self.map.insert(id, SerializeValue::I64(value));
+        }
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        if let Some(id) = self.names.get(field.name()) {
+            self.map.insert(id, SerializeValue::F64(value));
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if let Some(id) = self.names.get(field.name()) {
+            self.map
+                .insert(id, SerializeValue::Str(CowString::copied(value)));
+        }
+    }
+}
+
+impl<'a, F: Form> SerializeEventInterned<'a, F> {
+    pub fn is_borrowed(&self) -> bool {
+        self.fields.is_borrowed()
+    }
+
+    pub fn is_owned(&self) -> bool {
+        !self.is_borrowed()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, F: Form> SerializeEventInterned<'a, F> {
+    pub fn ensure_owned(&mut self) {
+        self.fields.ensure_owned();
+    }
+
+    pub fn to_owned(&self) -> SerializeEventInterned<'static> {
+        SerializeEventInterned {
+            fields: self.fields.to_owned(),
+            metadata: self.metadata,
+            parent: self.parent.clone(),
+        }
+    }
+}
+
+/// Collects metadata definitions on the consumer side of a stream.
+#[cfg(feature = "std")]
+#[derive(Debug, Default)]
+pub struct MetadataDictionary {
+    entries: std::collections::HashMap<SerializeMetadataId, SerializeMetadata<'static>>,
+    strings: std::collections::HashMap<StringId, CowString<'static>>,
+}
+
+#[cfg(feature = "std")]
+impl MetadataDictionary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store a definition, replacing any previous definition for `id`.
+    pub fn insert(&mut self, id: SerializeMetadataId, metadata: &SerializeMetadata<'_>) {
+        self.entries.insert(id, metadata.to_owned());
+    }
+
+    /// Store a string definition, replacing any previous definition for `id`.
+    pub fn insert_string(&mut self, id: StringId, value: &CowString<'_>) {
+        self.strings.insert(id, value.to_owned());
+    }
+
+    /// Store the definition carried by `msg`, if it is a
+    /// [`TracingWire::DefineMetadata`] or a [`TracingWire::DefineString`].
+    /// Returns `true` if it was.
+    pub fn observe<F: Form>(&mut self, msg: &TracingWire<'_, F>) -> bool {
+        match msg {
+            TracingWire::DefineMetadata { id, metadata } => {
+                self.insert(*id, metadata);
+                true
+            }
+            TracingWire::DefineString { id, value } => {
+                self.insert_string(*id, value);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the metadata defined for `id`, if any.
+    pub fn get(&self, id: SerializeMetadataId) -> Option<&SerializeMetadata<'static>> {
+        self.entries.get(&id)
+    }
+
+    /// Returns the string defined for `id`, if any.
+    pub fn string(&self, id: StringId) -> Option<&str> {
+        self.strings.get(&id).map(|s| s.as_str())
+    }
+
+    /// Iterates over the metadata definitions, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (SerializeMetadataId, &SerializeMetadata<'static>)> {
+        self.entries.iter().map(|(id, metadata)| (*id, metadata))
+    }
+
+    /// Iterates over the string definitions, in no particular order.
+    pub fn strings(&self) -> impl Iterator<Item = (StringId, &CowString<'static>)> {
+        self.strings.iter().map(|(id, value)| (*id, value))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Remove all definitions, e.g. when the producer restarts.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.strings.clear();
+    }
+
+    /// Convert an event reference into a full event, copying its metadata.
+    ///
+    /// Returns the reference unchanged if its metadata has not been defined.
+    pub fn resolve_event<'a, F: Form>(
+        &self,
+        event: SerializeEventRef<'a, F>,
+    ) -> Result<SerializeEvent<'a, F>, SerializeEventRef<'a, F>> {
+        match self.get(event.metadata) {
+            Some(metadata) => Ok(SerializeEvent {
+                fields: event.fields,
+                metadata: metadata.to_owned(),
+                parent: event.parent,
+            }),
+            None => Err(event),
+        }
+    }
+
+    /// Convert span attributes that refer to their metadata into full
+    /// attributes, copying the metadata.
+    pub fn resolve_attributes(
+        &self,
+        attributes: &SerializeAttributesRef,
+    ) -> Option<SerializeAttributes<'static>> {
+        self.get(attributes.metadata)
+            .map(|metadata| SerializeAttributes {
+                metadata: metadata.to_owned(),
+                parent: attributes.parent.clone(),
+                is_root: attributes.is_root,
+            })
+    }
+
+    /// Convert an event with interned field names into a full event,
+    /// copying its metadata and field names.
+    ///
+    /// Returns the event unchanged if its metadata or any of its field names
+    /// have not been defined.
+    pub fn resolve_interned<'a, F: Form>(
+        &self,
+        event: SerializeEventInterned<'a, F>,
+    ) -> Result<SerializeEvent<'a, F>, SerializeEventInterned<'a, F>> {
+        let map = match &event.fields {
+            InternedFields::De(map) => map,
+            InternedFields::Ser { .. } | InternedFields::Static { .. } => return Err(event),
+        };
+        let Some(metadata) = self.get(event.metadata) else {
+            return Err(event);
+        };
+        if !map.iter().all(|(id, _)| self.strings.contains_key(id)) {
+            return Err(event);
+        }
+
+        let InternedFields::De(map) = event.fields else {
+            unreachable!()
+        };
+        let fields = map
+            .into_iter()
+            .map(|(id, value)| (self.strings[&id].to_owned(), value))
+            .collect();
+        Ok(SerializeEvent {
+            fields: SerializeRecordFields::De(fields),
+            metadata: metadata.to_owned(),
+            parent: event.parent,
+        })
+    }
+}
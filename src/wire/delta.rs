@@ -0,0 +1,355 @@
+//! Delta encoding of span ids and timestamps.
+//!
+//! Span ids are usually close to the previous id in the stream (the same
+//! span being entered and exited, or the next span created), and messages
+//! are close together in time. A [`DeltaEncoder`] replaces each id with its
+//! difference from the previous id, and each timestamp with its difference
+//! from the previous timestamp, so that with postcard's varints they take
+//! one or two bytes instead of up to ten. A [`DeltaDecoder`] on the other
+//! side of the connection reverses this.
+//!
+//! Both sides must see every message, in order, starting from the same
+//! state: if a message is lost, [`DeltaEncoder::reset`] and
+//! [`DeltaDecoder::reset`] must be called before the next one. Alternatively,
+//! the encoder can send a [`SyncMarker`] every so often (see
+//! [`DeltaEncoder::set_sync_interval`]), from which a decoder that lost
+//! track, or that started reading mid-stream, picks the state back up.
+//!
+//! When several producers share one connection, such as the two cores of an
+//! RP2040, each keeps its own encoder and tags its frames with a
+//! [`SourceId`], sending [`SourceFrame`]s; the consumer keeps one decoder per
+//! source.
+
+use core::num::NonZeroU64;
+
+use serde::{Deserialize, Serialize};
+
+use super::TracingWire;
+use crate::{Detached, Form, Live, SerializeId};
+
+/// A message, and the time it was produced, with both delta encoded.
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "postcard-schema", derive(postcard_schema::Schema))]
+pub struct DeltaFrame<'a, F: Form = Detached> {
+    /// The difference from the previous frame's timestamp.
+    pub time: i64,
+    /// The message, with every span id replaced by its encoded difference
+    /// from the previous span id in the stream.
+    #[serde(borrow)]
+    pub msg: TracingWire<'a, F>,
+}
+
+/// Identifies one of several producers sharing a connection, such as a core,
+/// a DMA channel or a task.
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+#[serde(transparent)]
+#[cfg_attr(feature = "postcard-schema", derive(postcard_schema::Schema))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct SourceId {
+    pub id: u8,
+}
+
+/// A frame tagged with the producer it came from.
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "postcard-schema", derive(postcard_schema::Schema))]
+pub struct SourceFrame<'a, F: Form = Detached> {
+    pub source: SourceId,
+    /// The frame, delta encoded against the previous frame from the same
+    /// source.
+    #[serde(borrow)]
+    pub frame: DeltaFrame<'a, F>,
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, 'b, F: Form, G: Form> PartialEq<DeltaFrame<'b, G>> for DeltaFrame<'a, F> {
+    fn eq(&self, other: &DeltaFrame<'b, G>) -> bool {
+        self.time == other.time && self.msg == other.msg
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, 'b, F: Form, G: Form> PartialEq<SourceFrame<'b, G>> for SourceFrame<'a, F> {
+    fn eq(&self, other: &SourceFrame<'b, G>) -> bool {
+        self.source == other.source && self.frame == other.frame
+    }
+}
+
+/// The state of a delta encoded stream, sent as a [`TracingWire::Sync`].
+///
+/// A consumer that starts reading in the middle of a stream (a logic
+/// analyzer, or a serial port that was reconnected) cannot decode anything
+/// until it has seen one of these: see [`DeltaEncoder::set_sync_interval`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "postcard-schema", derive(postcard_schema::Schema))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SyncMarker {
+    /// Always [`SyncMarker::MAGIC`], so that a corrupt frame is unlikely to
+    /// be taken for a marker.
+    pub magic: u32,
+    /// The number of frames sent before this one.
+    pub seq: u32,
+    /// Changes whenever the producer forgets the definitions it has sent,
+    /// e.g. because it restarted; see [`DeltaEncoder::set_generation`].
+    pub generation: u32,
+    /// The timestamp the next frame is delta encoded against.
+    pub time: u64,
+    /// The span id the next frame is delta encoded against.
+    pub span: u64,
+}
+
+impl SyncMarker {
+    /// The value of [`SyncMarker::magic`]: `"TSSY"` in ASCII.
+    pub const MAGIC: u32 = u32::from_be_bytes(*b"TSSY");
+}
+
+/// Delta encodes messages on the producer side of a stream.
+#[derive(Debug, Default, Clone)]
+pub struct DeltaEncoder {
+    state: State,
+    seq: u32,
+    generation: u32,
+    sync_interval: Option<u32>,
+    since_sync: u32,
+}
+
+impl DeltaEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Encode `msg`, produced at `timestamp`.
+    ///
+    /// Timestamps can be in any unit (e.g. nanoseconds since startup, or
+    /// ticks of a hardware timer), as long as the decoder knows which.
+    ///
+    /// Messages are encoded in the [`Live`] form, which the messages a
+    /// producer sends are usually in; others can be converted with `into`.
+    pub fn encode<'a>(
+        &mut self,
+        timestamp: u64,
+        mut msg: TracingWire<'a, Live>,
+    ) -> DeltaFrame<'a, Live> {
+        let time = timestamp.wrapping_sub(self.state.time) as i64;
+        self.state.time = timestamp;
+        for_each_id(&mut msg, |id| {
+            let delta = id.id.get().wrapping_sub(self.state.span) as i64;
+            self.state.span = id.id.get();
+            // Zigzag, so small negative differences stay small, and offset
+            // by one, since ids cannot be zero.
+            let zigzag = ((delta << 1) ^ (delta >> 63)) as u64;
+            id.id = NonZeroU64::new(zigzag.wrapping_add(1)).unwrap_or(NonZeroU64::MAX);
+        });
+        self.seq = self.seq.wrapping_add(1);
+        self.since_sync = self.since_sync.saturating_add(1);
+        DeltaFrame { time, msg }
+    }
+
+    /// Like [`encode`](DeltaEncoder::encode), but first passes a
+    /// [`TracingWire::Sync`] frame to `sync` if one is due, which must be sent
+    /// before the returned frame.
+    pub fn encode_with_sync<'a>(
+        &mut self,
+        timestamp: u64,
+        msg: TracingWire<'a, Live>,
+        sync: impl FnOnce(DeltaFrame<'static>),
+    ) -> DeltaFrame<'a, Live> {
+        if let Some(interval) = self.sync_interval {
+            if self.since_sync >= interval {
+                sync(self.sync());
+            }
+        }
+        self.encode(timestamp, msg)
+    }
+
+    /// A [`TracingWire::Sync`] frame with the current state of the stream.
+    ///
+    /// Sending one does not change how later frames are encoded.
+    pub fn sync(&mut self) -> DeltaFrame<'static> {
+        let marker = SyncMarker {
+            magic: SyncMarker::MAGIC,
+            seq: self.seq,
+            generation: self.generation,
+            time: self.state.time,
+            span: self.state.span,
+        };
+        self.seq = self.seq.wrapping_add(1);
+        self.since_sync = 0;
+        DeltaFrame {
+            time: 0,
+            msg: TracingWire::Sync(marker),
+        }
+    }
+
+    /// Have [`encode_with_sync`](DeltaEncoder::encode_with_sync) send a sync
+    /// frame before every `interval` frames, or never with `None`.
+    ///
+    /// The first frame is preceded by one as well.
+    pub fn set_sync_interval(&mut self, interval: Option<u32>) {
+        self.sync_interval = interval;
+        self.since_sync = interval.unwrap_or(0);
+    }
+
+    /// Set the generation reported in sync frames. Change it whenever the
+    /// producer starts sending its definitions again from scratch, so that a
+    /// consumer that reconnects knows to drop the ones it has.
+    pub fn set_generation(&mut self, generation: u32) {
+        self.generation = generation;
+    }
+
+    /// Forget the previous message, e.g. after a message was lost.
+    ///
+    /// The sequence number and generation are kept.
+    pub fn reset(&mut self) {
+        self.state = State::default();
+    }
+}
+
+/// Reverses the encoding of a [`DeltaEncoder`] on the consumer side.
+#[derive(Debug, Clone)]
+pub struct DeltaDecoder {
+    state: State,
+    synced: bool,
+    seq: u32,
+    sync: Option<SyncMarker>,
+}
+
+impl Default for DeltaDecoder {
+    fn default() -> Self {
+        Self {
+            state: State::default(),
+            synced: true,
+            seq: 0,
+            sync: None,
+        }
+    }
+}
+
+impl DeltaDecoder {
+    /// A decoder for a stream read from its start.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A decoder for a stream read from some point in the middle, which
+    /// rejects every frame until it sees a [`TracingWire::Sync`].
+    pub fn mid_stream() -> Self {
+        Self {
+            synced: false,
+            ..Self::default()
+        }
+    }
+
+    /// Decode a frame, returning the message and its timestamp.
+    ///
+    /// Returns `None` if the frame decodes to a span id of zero, which means
+    /// the stream is corrupt, or the encoder was not in the same state. A
+    /// sync frame always decodes, and brings the decoder into the state of
+    /// the encoder.
+    pub fn decode<'a, F: Form>(
+        &mut self,
+        frame: DeltaFrame<'a, F>,
+    ) -> Option<(u64, TracingWire<'a, F>)> {
+        let DeltaFrame { time, mut msg } = frame;
+        if let TracingWire::Sync(marker) = &msg {
+            if marker.magic != SyncMarker::MAGIC {
+                return None;
+            }
+            self.state = State {
+                time: marker.time,
+                span: marker.span,
+            };
+            self.synced = true;
+            self.seq = marker.seq.wrapping_add(1);
+            self.sync = Some(marker.clone());
+            return Some((self.state.time, msg));
+        }
+        if !self.synced {
+            return None;
+        }
+
+        let mut state = self.state.clone();
+        state.time = state.time.wrapping_add(time as u64);
+
+        let mut ok = true;
+        for_each_id(&mut msg, |id| {
+            let zigzag = id.id.get().wrapping_sub(1);
+            let delta = ((zigzag >> 1) as i64) ^ -((zigzag & 1) as i64);
+            state.span = state.span.wrapping_add(delta as u64);
+            match NonZeroU64::new(state.span) {
+                Some(span) => id.id = span,
+                None => ok = false,
+            }
+        });
+
+        if !ok {
+            return None;
+        }
+        self.state = state;
+        self.seq = self.seq.wrapping_add(1);
+        Some((self.state.time, msg))
+    }
+
+    /// The sequence number the next frame should have.
+    ///
+    /// If a sync frame carries a different one, frames were lost since the
+    /// previous sync frame, and those decoded in between may have wrong span
+    /// ids and timestamps.
+    pub fn seq(&self) -> u32 {
+        self.seq
+    }
+
+    /// The last sync marker decoded, if any.
+    pub fn last_sync(&self) -> Option<&SyncMarker> {
+        self.sync.as_ref()
+    }
+
+    /// Forget the previous message, matching [`DeltaEncoder::reset`].
+    pub fn reset(&mut self) {
+        self.state = State::default();
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+struct State {
+    time: u64,
+    span: u64,
+}
+
+/// Calls `f` with every span id in `msg`, in a fixed order.
+fn for_each_id<F: Form>(msg: &mut TracingWire<'_, F>, mut f: impl FnMut(&mut SerializeId)) {
+    let mut parent = |p: &mut Option<SerializeId>| {
+        if let Some(p) = p {
+            f(p)
+        }
+    };
+    match msg {
+        TracingWire::DefineMetadata { .. }
+        | TracingWire::DefineString { .. }
+        | TracingWire::Sync(_)
+        | TracingWire::Panic(_)
+        | TracingWire::TimeSync(_)
+        | TracingWire::PipelineStats(_)
+        | TracingWire::Hello(_)
+        | TracingWire::Description(_)
+        | TracingWire::Dropped(_)
+        | TracingWire::Unknown => {}
+        TracingWire::NewSpan { id, attributes } => {
+            parent(&mut attributes.parent);
+            f(id)
+        }
+        TracingWire::NewSpanRef { id, attributes } => {
+            parent(&mut attributes.parent);
+            f(id)
+        }
+        TracingWire::Record { span, .. } | TracingWire::Baggage { span, .. } => f(span),
+        TracingWire::FollowsFrom { span, follows } => {
+            f(span);
+            f(follows)
+        }
+        TracingWire::Event(e) => parent(&mut e.parent),
+        TracingWire::EventRef(e) => parent(&mut e.parent),
+        TracingWire::EventInterned(e) => parent(&mut e.parent),
+        TracingWire::Enter(id) | TracingWire::Exit(id) | TracingWire::CloseSpan(id) => f(id),
+    }
+}
@@ -0,0 +1,423 @@
+//! Describing how a stream is encoded, in the stream itself.
+//!
+//! A capture file outlives the producer that wrote it. Years later, its
+//! [`Hello`](super::handshake::Hello) still says which version of the wire
+//! format it uses, but decoding it also takes the features of this crate
+//! that changed the encoding, and, for a positional format like postcard,
+//! the exact layout of every message, which only the producer's version of
+//! the crate knows.
+//!
+//! A producer can write both into the stream, right after its hello, as a
+//! [`TracingWire::Description`]: the version of the crate it was built with,
+//! its [`Encoding`] options and, with the `postcard-schema` feature, the
+//! schema of [`TracingWire`] itself, as a [`SchemaBlob`]:
+//!
+//! ```rust
+//! # #[cfg(all(feature = "postcard", feature = "postcard-schema"))] {
+//! use tracing_serde_structured::wire::{description::{Description, SchemaBlob}, TracingWire};
+//!
+//! let describe: TracingWire<'_> = Description::new().with_schema(SchemaBlob::wire()).into();
+//! let frame = postcard::to_allocvec(&describe).unwrap();
+//!
+//! // Years later:
+//! let msg: TracingWire<'_> = postcard::from_bytes(&frame).unwrap();
+//! let TracingWire::Description(description) = msg else {
+//!     unreachable!()
+//! };
+//! let schema = description.schema().unwrap().unwrap();
+//! println!("written by version {} as:\n{schema}", description.crate_version.as_str());
+//! # }
+//! ```
+//!
+//! Without `alloc`, [`SchemaBlob::wire_in`] writes the schema into a buffer
+//! instead. Either way, the schema takes a few kilobytes, once per stream.
+//!
+//! A consumer gets it back with [`Description::schema`], as a
+//! [`OwnedNamedType`](postcard_schema::schema::owned::OwnedNamedType) that
+//! it can print, compare with the schema of its own version of the crate, or
+//! decode the stream with dynamically. The schema blob is itself postcard,
+//! of the schema types of `postcard-schema`, which change far less often
+//! than this crate.
+//!
+//! With the `host` feature, the [`Decoder`](crate::host::Decoder) keeps the
+//! description it last read.
+
+use core::{fmt, ops};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use super::TracingWire;
+use crate::CowString;
+
+/// Options of this crate, chosen with features when building it, that
+/// change how messages are encoded.
+///
+/// Sets serialize as a `u32`. Bits this version of the crate does not know
+/// are kept.
+#[derive(Copy, Clone, Default, Hash, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+#[cfg_attr(feature = "postcard-schema", derive(postcard_schema::Schema))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct Encoding(u32);
+
+/// The names of the known options, for `Debug` and `Display`, which are
+/// those of the features.
+const NAMES: &[(Encoding, &str)] = &[
+    (Encoding::SKIP_NONE, "skip-none"),
+    (Encoding::CAMEL_CASE, "camel-case"),
+    (Encoding::STRIP_LOCATIONS, "strip-locations"),
+];
+
+impl Encoding {
+    /// `None` values are left out of human-readable formats.
+    pub const SKIP_NONE: Self = Self(1 << 0);
+    /// Struct fields are written in `camelCase` in human-readable formats.
+    pub const CAMEL_CASE: Self = Self(1 << 1);
+    /// Metadata has no `module_path`, `file` or `line`, in every format.
+    pub const STRIP_LOCATIONS: Self = Self(1 << 2);
+
+    /// No options.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// The options of this build of the crate.
+    pub const fn current() -> Self {
+        let mut bits = 0;
+        if cfg!(feature = "skip-none") {
+            bits |= Self::SKIP_NONE.0;
+        }
+        if cfg!(feature = "camel-case") {
+            bits |= Self::CAMEL_CASE.0;
+        }
+        if cfg!(feature = "strip-locations") {
+            bits |= Self::STRIP_LOCATIONS.0;
+        }
+        Self(bits)
+    }
+
+    /// The set with the given bits, including unknown ones.
+    pub const fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
+
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Whether every option in `other` is in `self`.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// The options in `self` that are not in `other`.
+    pub const fn difference(self, other: Self) -> Self {
+        Self(self.0 & !other.0)
+    }
+}
+
+impl ops::BitOr for Encoding {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+/// Lists the options by name, separated by `|`, with unknown bits in hex,
+/// such as `skip-none | camel-case`.
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut rest = *self;
+        let mut sep = "";
+        for (option, name) in NAMES {
+            if rest.contains(*option) {
+                write!(f, "{sep}{name}")?;
+                rest = rest.difference(*option);
+                sep = " | ";
+            }
+        }
+        if !rest.is_empty() || self.is_empty() {
+            write!(f, "{sep}{:#x}", rest.0)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Encoding({self})")
+    }
+}
+
+/// The schema of a stream's messages, as postcard-serialized
+/// `postcard-schema` types.
+///
+/// Blobs serialize as bytes. They are borrowed from the input when the
+/// format allows it, and copied otherwise (with the `alloc` feature).
+#[derive(Clone, Eq)]
+pub enum SchemaBlob<'a> {
+    Borrowed(&'a [u8]),
+    #[cfg(feature = "alloc")]
+    Owned(alloc::vec::Vec<u8>),
+}
+
+impl<'a> SchemaBlob<'a> {
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            SchemaBlob::Borrowed(bytes) => bytes,
+            #[cfg(feature = "alloc")]
+            SchemaBlob::Owned(bytes) => bytes,
+        }
+    }
+
+    pub fn is_borrowed(&self) -> bool {
+        matches!(self, SchemaBlob::Borrowed(_))
+    }
+
+    /// Writes the schema of [`TracingWire`] into `buf`, and borrows it from
+    /// there.
+    #[cfg(all(feature = "postcard", feature = "postcard-schema"))]
+    #[cfg_attr(
+        docsrs,
+        doc(cfg(all(feature = "postcard", feature = "postcard-schema")))
+    )]
+    pub fn wire_in(buf: &'a mut [u8]) -> Result<Self, ::postcard::Error> {
+        use postcard_schema::Schema;
+
+        let bytes = ::postcard::to_slice(<TracingWire<'_>>::SCHEMA, buf)?;
+        Ok(SchemaBlob::Borrowed(bytes))
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> SchemaBlob<'a> {
+    pub fn ensure_owned(&mut self) {
+        if let SchemaBlob::Borrowed(bytes) = self {
+            *self = SchemaBlob::Owned(bytes.to_vec());
+        }
+    }
+
+    pub fn to_owned(&self) -> SchemaBlob<'static> {
+        SchemaBlob::Owned(self.as_bytes().to_vec())
+    }
+
+    /// The schema of [`TracingWire`].
+    #[cfg(all(feature = "postcard", feature = "postcard-schema"))]
+    #[cfg_attr(
+        docsrs,
+        doc(cfg(all(feature = "postcard", feature = "postcard-schema")))
+    )]
+    pub fn wire() -> SchemaBlob<'static> {
+        use postcard_schema::Schema;
+
+        let bytes = ::postcard::to_extend(<TracingWire<'_>>::SCHEMA, alloc::vec::Vec::new())
+            .expect("serializing into a vector cannot fail");
+        SchemaBlob::Owned(bytes)
+    }
+
+    /// Deserializes the schema.
+    #[cfg(all(feature = "postcard", feature = "postcard-schema"))]
+    #[cfg_attr(
+        docsrs,
+        doc(cfg(all(feature = "postcard", feature = "postcard-schema")))
+    )]
+    pub fn decode(
+        &self,
+    ) -> Result<postcard_schema::schema::owned::OwnedNamedType, ::postcard::Error> {
+        ::postcard::from_bytes(self.as_bytes())
+    }
+}
+
+impl<'a, 'b> PartialEq<SchemaBlob<'b>> for SchemaBlob<'a> {
+    fn eq(&self, other: &SchemaBlob<'b>) -> bool {
+        self.as_bytes() == other.as_bytes()
+    }
+}
+
+impl<'a> fmt::Debug for SchemaBlob<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SchemaBlob({} bytes)", self.as_bytes().len())
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<'a> defmt::Format for SchemaBlob<'a> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "SchemaBlob({=usize} bytes)", self.as_bytes().len())
+    }
+}
+
+#[cfg(feature = "postcard-schema")]
+impl<'a> postcard_schema::Schema for SchemaBlob<'a> {
+    const SCHEMA: &'static postcard_schema::schema::NamedType =
+        &postcard_schema::schema::NamedType {
+            name: "SchemaBlob",
+            ty: &postcard_schema::schema::DataModelType::ByteArray,
+        };
+}
+
+impl<'a> Serialize for SchemaBlob<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_bytes(self.as_bytes())
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for SchemaBlob<'a> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_bytes(SchemaBlobVisitor)
+    }
+}
+
+struct SchemaBlobVisitor;
+
+impl<'de> de::Visitor<'de> for SchemaBlobVisitor {
+    type Value = SchemaBlob<'de>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("bytes")
+    }
+
+    fn visit_borrowed_bytes<E>(self, v: &'de [u8]) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(SchemaBlob::Borrowed(v))
+    }
+
+    #[cfg(feature = "alloc")]
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(SchemaBlob::Owned(v.to_vec()))
+    }
+
+    #[cfg(feature = "alloc")]
+    fn visit_byte_buf<E>(self, v: alloc::vec::Vec<u8>) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(SchemaBlob::Owned(v))
+    }
+
+    /// Human-readable formats such as JSON write bytes as a sequence of
+    /// numbers.
+    #[cfg(feature = "alloc")]
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        let mut bytes = alloc::vec::Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        Ok(SchemaBlob::Owned(bytes))
+    }
+}
+
+/// How a stream is encoded, sent as a [`TracingWire::Description`] after the
+/// stream's hello. See the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "postcard-schema", derive(postcard_schema::Schema))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Description<'a> {
+    /// The version of this crate the producer was built with, such as
+    /// `0.4.0`.
+    #[cfg_attr(
+        feature = "camel-case",
+        serde(rename = "crateVersion", alias = "crate_version")
+    )]
+    #[cfg_attr(not(feature = "camel-case"), serde(alias = "crateVersion"))]
+    #[serde(borrow)]
+    pub crate_version: CowString<'a>,
+    /// The options the producer was built with.
+    pub encoding: Encoding,
+    /// The schema of the producer's [`TracingWire`], if it sent it.
+    #[serde(borrow)]
+    pub schema: Option<SchemaBlob<'a>>,
+}
+
+impl Description<'static> {
+    /// A description of a stream written by this build of the crate, without
+    /// a schema.
+    pub const fn new() -> Self {
+        Self {
+            crate_version: CowString::Static(env!("CARGO_PKG_VERSION")),
+            encoding: Encoding::current(),
+            schema: None,
+        }
+    }
+}
+
+impl Default for Description<'static> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> Description<'a> {
+    /// Sends `schema` as well, such as [`SchemaBlob::wire`].
+    pub fn with_schema(self, schema: SchemaBlob<'a>) -> Description<'a> {
+        Description {
+            schema: Some(schema),
+            ..self
+        }
+    }
+
+    /// Deserializes the schema, if the producer sent one.
+    #[cfg(all(feature = "alloc", feature = "postcard", feature = "postcard-schema"))]
+    #[cfg_attr(
+        docsrs,
+        doc(cfg(all(feature = "alloc", feature = "postcard", feature = "postcard-schema")))
+    )]
+    pub fn schema(
+        &self,
+    ) -> Option<Result<postcard_schema::schema::owned::OwnedNamedType, ::postcard::Error>> {
+        self.schema.as_ref().map(SchemaBlob::decode)
+    }
+
+    pub fn is_borrowed(&self) -> bool {
+        self.crate_version.is_borrowed()
+            || self.schema.as_ref().is_some_and(SchemaBlob::is_borrowed)
+    }
+
+    pub fn is_owned(&self) -> bool {
+        !self.is_borrowed()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> Description<'a> {
+    pub fn ensure_owned(&mut self) {
+        self.crate_version.ensure_owned();
+        if let Some(schema) = &mut self.schema {
+            schema.ensure_owned();
+        }
+    }
+
+    pub fn to_owned(&self) -> Description<'static> {
+        Description {
+            crate_version: self.crate_version.to_owned(),
+            encoding: self.encoding,
+            schema: self.schema.as_ref().map(SchemaBlob::to_owned),
+        }
+    }
+}
+
+impl<'a> From<Description<'a>> for TracingWire<'a> {
+    fn from(description: Description<'a>) -> Self {
+        TracingWire::Description(description)
+    }
+}
@@ -0,0 +1,408 @@
+//! Agreeing on the version of the wire format, and the features a stream
+//! uses, before sending it.
+//!
+//! A producer starts its stream with a [`TracingWire::Hello`], saying which
+//! version of the wire format it writes, which [`Capabilities`] its stream
+//! uses, such as delta encoding or interned field names, and how fast its
+//! clock ticks. A consumer checks the hello against what it can decode with
+//! [`Hello::negotiate`], and either fails right away with an [`Error`] that
+//! says what is missing, rather than with a confusing decoding error later
+//! on, or carries on:
+//!
+//! ```rust
+//! use tracing_serde_structured::wire::handshake::{Capabilities, Error, Hello};
+//!
+//! let hello = Hello::new(Capabilities::DELTA | Capabilities::INTERNED)
+//!     .offering(Capabilities::LZ4 | Capabilities::ZSTD);
+//!
+//! // On a consumer that can read delta encoded, interned streams, and
+//! // decompress LZ4.
+//! let supported = Capabilities::DELTA | Capabilities::INTERNED | Capabilities::LZ4;
+//! let accept = hello.negotiate(supported).unwrap();
+//! assert_eq!(accept.capabilities, supported);
+//!
+//! // On one that cannot read interned field names.
+//! let err = hello.negotiate(Capabilities::DELTA).unwrap_err();
+//! assert_eq!(err, Error::Unsupported(Capabilities::INTERNED));
+//! ```
+//!
+//! On a connection with a way back, such as a USB or TCP link, the consumer
+//! replies with the [`Accept`], and the producer turns on what it offered and
+//! the consumer accepted, after checking it with [`Hello::accepted`]. On one
+//! without, such as a UART or a file, the producer only uses what it says it
+//! uses, and a consumer that cannot read it at least says why.
+//!
+//! With the `host` feature, the [`Decoder`](crate::host::Decoder) checks the
+//! hello against what it was set up to decode, and takes the tick rate from
+//! it.
+
+use core::{fmt, ops};
+
+use serde::{Deserialize, Serialize};
+
+use super::TracingWire;
+use crate::{time::TickRate, CowString};
+
+/// The version of the wire format this crate writes.
+///
+/// It goes up whenever messages are encoded differently, rather than when
+/// kinds of messages are added, which older consumers skip over.
+pub const VERSION: u16 = 1;
+
+/// The oldest version of the wire format this crate reads, and the oldest a
+/// consumer must read to decode what it writes.
+pub const MIN_VERSION: u16 = 1;
+
+/// A set of features of a stream, or of a consumer.
+///
+/// Sets serialize as a `u32`. Bits this version of the crate does not know
+/// are kept, and never supported.
+#[derive(Copy, Clone, Default, Hash, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+#[cfg_attr(feature = "postcard-schema", derive(postcard_schema::Schema))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct Capabilities(u32);
+
+/// The names of the known capabilities, for `Debug` and `Display`.
+const NAMES: &[(Capabilities, &str)] = &[
+    (Capabilities::DELTA, "delta"),
+    (Capabilities::DEFINITIONS, "definitions"),
+    (Capabilities::INTERNED, "interned"),
+    (Capabilities::METADATA_TABLE, "metadata-table"),
+    (Capabilities::SOURCES, "sources"),
+    (Capabilities::DEFLATE, "deflate"),
+    (Capabilities::LZ4, "lz4"),
+    (Capabilities::ZSTD, "zstd"),
+    (Capabilities::HEATSHRINK, "heatshrink"),
+    (Capabilities::AUDIT, "audit"),
+    (Capabilities::AUTHENTICATION, "authentication"),
+    (Capabilities::CRC16, "crc16"),
+    (Capabilities::CRC32, "crc32"),
+];
+
+impl Capabilities {
+    /// Frames are [`DeltaFrame`](super::delta::DeltaFrame)s.
+    pub const DELTA: Self = Self(1 << 0);
+    /// Metadata is defined once, and referred to by id.
+    pub const DEFINITIONS: Self = Self(1 << 1);
+    /// Field names are defined once, and referred to by id.
+    pub const INTERNED: Self = Self(1 << 2);
+    /// Callsites are referred to by their ids in a table built into both
+    /// ends (see [`table`](super::table)), without being defined.
+    pub const METADATA_TABLE: Self = Self(1 << 3);
+    /// Frames are [`SourceFrame`](super::delta::SourceFrame)s, from several
+    /// producers.
+    pub const SOURCES: Self = Self(1 << 4);
+    /// Batches are compressed with DEFLATE.
+    pub const DEFLATE: Self = Self(1 << 5);
+    /// Batches are compressed with LZ4.
+    pub const LZ4: Self = Self(1 << 6);
+    /// Batches are compressed with Zstandard.
+    pub const ZSTD: Self = Self(1 << 7);
+    /// Frames are compressed with heatshrink.
+    pub const HEATSHRINK: Self = Self(1 << 8);
+    /// Frames are chained to the ones before them (see
+    /// [`audit`](crate::audit)).
+    pub const AUDIT: Self = Self(1 << 9);
+    /// Frames end with an authentication tag (see [`crypto`](crate::crypto)).
+    pub const AUTHENTICATION: Self = Self(1 << 10);
+    /// Frames end with a CRC-16 (see [`checksum`](crate::checksum)).
+    pub const CRC16: Self = Self(1 << 11);
+    /// Frames end with a CRC-32 (see [`checksum`](crate::checksum)).
+    pub const CRC32: Self = Self(1 << 12);
+
+    /// No capabilities.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// The capabilities this build of the crate can decode, depending on the
+    /// features it was built with.
+    pub const fn supported() -> Self {
+        let mut bits = Self::DELTA.0
+            | Self::DEFINITIONS.0
+            | Self::INTERNED.0
+            | Self::METADATA_TABLE.0
+            | Self::SOURCES.0;
+        if cfg!(feature = "deflate") {
+            bits |= Self::DEFLATE.0;
+        }
+        if cfg!(feature = "lz4") {
+            bits |= Self::LZ4.0;
+        }
+        if cfg!(feature = "zstd") {
+            bits |= Self::ZSTD.0;
+        }
+        if cfg!(feature = "heatshrink") {
+            bits |= Self::HEATSHRINK.0;
+        }
+        if cfg!(feature = "audit") {
+            bits |= Self::AUDIT.0;
+        }
+        if cfg!(feature = "crypto") {
+            bits |= Self::AUTHENTICATION.0;
+        }
+        if cfg!(feature = "checksum") {
+            bits |= Self::CRC16.0 | Self::CRC32.0;
+        }
+        Self(bits)
+    }
+
+    /// The set with the given bits, including unknown ones.
+    pub const fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
+
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Whether every capability in `other` is in `self`.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// The capabilities in `self` that are not in `other`.
+    pub const fn difference(self, other: Self) -> Self {
+        Self(self.0 & !other.0)
+    }
+}
+
+impl ops::BitOr for Capabilities {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+impl ops::BitOrAssign for Capabilities {
+    fn bitor_assign(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+}
+
+impl ops::BitAnd for Capabilities {
+    type Output = Self;
+
+    fn bitand(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+}
+
+/// Lists the capabilities by name, separated by `|`, with unknown bits in
+/// hex, such as `delta | interned | 0x80000000`.
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut rest = *self;
+        let mut sep = "";
+        for (capability, name) in NAMES {
+            if rest.contains(*capability) {
+                write!(f, "{sep}{name}")?;
+                rest = rest.difference(*capability);
+                sep = " | ";
+            }
+        }
+        if !rest.is_empty() || self.is_empty() {
+            write!(f, "{sep}{:#x}", rest.0)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Capabilities({self})")
+    }
+}
+
+/// The first message of a stream, sent as a [`TracingWire::Hello`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "postcard-schema", derive(postcard_schema::Schema))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Hello<'a> {
+    /// The version of the wire format the producer writes.
+    pub version: u16,
+    /// The oldest version of the wire format a consumer must read to decode
+    /// the stream.
+    #[cfg_attr(
+        feature = "camel-case",
+        serde(rename = "minVersion", alias = "min_version")
+    )]
+    #[cfg_attr(not(feature = "camel-case"), serde(alias = "minVersion"))]
+    pub min_version: u16,
+    /// What the stream uses, which a consumer must support to decode it.
+    pub uses: Capabilities,
+    /// What the producer could also use, if the consumer accepts it.
+    pub offers: Capabilities,
+    /// The rate of the producer's clock, which timestamps count ticks of.
+    #[cfg_attr(
+        feature = "camel-case",
+        serde(rename = "tickRate", alias = "tick_rate")
+    )]
+    #[cfg_attr(not(feature = "camel-case"), serde(alias = "tickRate"))]
+    pub tick_rate: Option<TickRate>,
+    /// The name and version of the producer, such as its firmware's.
+    #[serde(borrow)]
+    pub producer: Option<CowString<'a>>,
+}
+
+impl Hello<'static> {
+    /// A hello for a stream written by this version of the crate, using
+    /// `uses`, and offering nothing else.
+    pub const fn new(uses: Capabilities) -> Self {
+        Self {
+            version: VERSION,
+            min_version: MIN_VERSION,
+            uses,
+            offers: Capabilities::empty(),
+            tick_rate: None,
+            producer: None,
+        }
+    }
+}
+
+impl<'a> Hello<'a> {
+    /// Offers `offers` as well.
+    pub fn offering(mut self, offers: Capabilities) -> Self {
+        self.offers |= offers;
+        self
+    }
+
+    /// Says that timestamps count ticks at `rate`.
+    pub fn with_tick_rate(mut self, rate: TickRate) -> Self {
+        self.tick_rate = Some(rate);
+        self
+    }
+
+    /// Names the producer.
+    pub fn with_producer(mut self, producer: impl Into<CowString<'a>>) -> Self {
+        self.producer = Some(producer.into());
+        self
+    }
+
+    /// Checks, on the consumer's side, that a consumer of this version of
+    /// the crate supporting `supported` can decode the stream, and accepts
+    /// what it supports of what the producer offers.
+    pub fn negotiate(&self, supported: Capabilities) -> Result<Accept, Error> {
+        if self.min_version > VERSION || self.version < MIN_VERSION {
+            return Err(Error::Version {
+                version: self.version,
+                min_version: self.min_version,
+            });
+        }
+        let missing = self.uses.difference(supported);
+        if !missing.is_empty() {
+            return Err(Error::Unsupported(missing));
+        }
+        Ok(Accept {
+            version: self.version.min(VERSION),
+            capabilities: self.uses | (self.offers & supported),
+        })
+    }
+
+    /// Checks, on the producer's side, the consumer's reply to this hello,
+    /// and returns the capabilities to use from then on.
+    ///
+    /// The consumer must have accepted a version between
+    /// [`min_version`](Self::min_version) and [`version`](Self::version),
+    /// everything the stream uses, and nothing it does not offer.
+    pub fn accepted(&self, accept: &Accept) -> Result<Capabilities, Error> {
+        let offered = self.uses | self.offers;
+        if !(self.min_version..=self.version).contains(&accept.version)
+            || !accept.capabilities.contains(self.uses)
+            || !offered.contains(accept.capabilities)
+        {
+            return Err(Error::Rejected);
+        }
+        Ok(accept.capabilities)
+    }
+
+    pub fn is_borrowed(&self) -> bool {
+        self.producer.as_ref().is_some_and(CowString::is_borrowed)
+    }
+
+    pub fn is_owned(&self) -> bool {
+        !self.is_borrowed()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> Hello<'a> {
+    pub fn ensure_owned(&mut self) {
+        if let Some(producer) = &mut self.producer {
+            producer.ensure_owned();
+        }
+    }
+
+    pub fn to_owned(&self) -> Hello<'static> {
+        Hello {
+            version: self.version,
+            min_version: self.min_version,
+            uses: self.uses,
+            offers: self.offers,
+            tick_rate: self.tick_rate,
+            producer: self.producer.as_ref().map(CowString::to_owned),
+        }
+    }
+}
+
+impl<'a> From<Hello<'a>> for TracingWire<'a> {
+    fn from(hello: Hello<'a>) -> Self {
+        TracingWire::Hello(hello)
+    }
+}
+
+/// A consumer's reply to a [`Hello`], on connections with a way back.
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "postcard-schema", derive(postcard_schema::Schema))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct Accept {
+    /// The version of the wire format to write: the newest both ends know.
+    pub version: u16,
+    /// What the stream uses, and what the consumer accepted of what the
+    /// producer offered.
+    pub capabilities: Capabilities,
+}
+
+/// Errors returned when a producer and a consumer cannot agree.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The producer writes a version of the wire format this version of the
+    /// crate cannot read.
+    Version { version: u16, min_version: u16 },
+    /// The stream uses these capabilities, which the consumer lacks.
+    Unsupported(Capabilities),
+    /// The consumer accepted a version or capabilities the producer did not
+    /// offer, or did not accept everything the stream uses.
+    Rejected,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Version {
+                version,
+                min_version,
+            } => write!(
+                f,
+                "producer writes version {version} of the wire format, readable from \
+                 version {min_version}, but this consumer reads versions {MIN_VERSION} \
+                 to {VERSION}"
+            ),
+            Error::Unsupported(missing) => {
+                write!(f, "stream uses capabilities this consumer lacks: {missing}")
+            }
+            Error::Rejected => f.write_str("consumer accepted what the producer did not offer"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
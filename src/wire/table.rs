@@ -0,0 +1,303 @@
+//! Metadata ids assigned ahead of time, so a device never sends definitions.
+//!
+//! A [`SerializeMetadataId`] is normally the address of a callsite's
+//! metadata, which changes between builds, so the producer has to define each
+//! callsite with a [`DefineMetadata`](super::TracingWire::DefineMetadata)
+//! before using it. For devices where even that is too much, a
+//! [`MetadataTable`] assigns every known callsite a dense id instead, which
+//! postcard encodes in one byte for the first 128 callsites, and two for the
+//! next 16 thousand.
+//!
+//! The table is an artifact, saved next to the firmware, for example as JSON:
+//!
+//! 1. Record the metadata of the firmware's callsites once, e.g. from a run
+//!    that sends definitions, with [`MetadataTable::from_dictionary`], and
+//!    save the table.
+//! 2. In the firmware's build script, turn the table into a lookup with
+//!    [`MetadataTable::write_lookup`]:
+//!
+//!    ```rust,no_run
+//!    # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!    use std::{env, fs, path::Path};
+//!    use tracing_serde_structured::wire::table::MetadataTable;
+//!
+//!    let table: MetadataTable = serde_json::from_str(&fs::read_to_string("tracing.json")?)?;
+//!    let out = Path::new(&env::var("OUT_DIR")?).join("tracing_table.rs");
+//!    table.write_lookup(fs::File::create(out)?)?;
+//!    println!("cargo:rerun-if-changed=tracing.json");
+//!    # Ok(())
+//!    # }
+//!    ```
+//!
+//! 3. In the firmware, include the lookup as a [`StaticTable`], and send
+//!    messages with the ids it returns, falling back to definitions for
+//!    callsites that are not in the table yet:
+//!
+//!    ```rust,ignore
+//!    static TABLE: StaticTable = StaticTable::new(include!(concat!(env!("OUT_DIR"), "/tracing_table.rs")));
+//!
+//!    let metadata = TABLE
+//!        .index_of(event.metadata())
+//!        .unwrap_or_else(|| SerializeMetadataId::of(event.metadata()));
+//!    let msg = TracingWire::EventRef(SerializeEventRef { metadata, ..SerializeEventRef::new(event) });
+//!    ```
+//!
+//! 4. On the host, load the same table, and decode with
+//!    [`MetadataTable::to_dictionary`] as the starting dictionary.
+//!
+//! Callsites are identified by their file, line and name. Two callsites that
+//! share all three, such as two events in one expansion of a `macro_rules!`
+//! macro, get the same id, the first one's.
+//!
+//! The field names of an event are the only strings left in it, apart from
+//! the values. [`MetadataTable::write_strings`] generates a lookup for those
+//! as well, a [`StaticStrings`], which gives every field name in the table a
+//! [`StringId`] and sends events as
+//! [`SerializeEventInterned`](super::SerializeEventInterned)s without any
+//! [`DefineString`](super::TracingWire::DefineString)s:
+//!
+//! ```rust,ignore
+//! static STRINGS: StaticStrings = StaticStrings::new(include!(concat!(env!("OUT_DIR"), "/tracing_strings.rs")));
+//!
+//! let msg = TracingWire::EventInterned(STRINGS.event(event, metadata));
+//! ```
+//!
+//! Its ids overlap with those of a [`StringTable`](super::StringTable), so a
+//! stream uses one or the other. Fields whose names are not in the lookup are
+//! left out of the event.
+
+#[cfg(feature = "std")]
+use std::io;
+
+use tracing_core::Metadata;
+
+#[cfg(feature = "std")]
+use super::MetadataDictionary;
+use tracing_core::Event;
+
+use super::{InternedFields, SerializeEventInterned, SerializeMetadataId, StringId};
+use crate::{AsSerde, Live};
+#[cfg(feature = "std")]
+use crate::{CowString, SerializeFieldSet, SerializeMetadata};
+
+/// How a [`StaticTable`] identifies a callsite.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StaticEntry {
+    pub file: Option<&'static str>,
+    pub line: Option<u32>,
+    pub name: &'static str,
+}
+
+/// The ids of a [`MetadataTable`], compiled into a device.
+#[derive(Debug, Clone, Copy)]
+pub struct StaticTable {
+    entries: &'static [StaticEntry],
+}
+
+impl StaticTable {
+    /// A table of `entries`, which must be sorted by file, line and name, as
+    /// [`MetadataTable::write_lookup`] writes them.
+    pub const fn new(entries: &'static [StaticEntry]) -> Self {
+        Self { entries }
+    }
+
+    /// Returns the id of the callsite `metadata` belongs to, if it is in the
+    /// table.
+    pub fn index_of(&self, metadata: &Metadata<'_>) -> Option<SerializeMetadataId> {
+        let key = (metadata.file(), metadata.line(), metadata.name());
+        let index = self
+            .entries
+            .binary_search_by(|entry| (entry.file, entry.line, entry.name).cmp(&key))
+            .ok()?;
+        Some(SerializeMetadataId { id: index as u64 })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// The ids of the field names in a [`MetadataTable`], compiled into a device.
+#[derive(Debug, Clone, Copy)]
+pub struct StaticStrings {
+    strings: &'static [&'static str],
+}
+
+impl StaticStrings {
+    /// A lookup of `strings`, which must be sorted, as
+    /// [`MetadataTable::write_strings`] writes them.
+    pub const fn new(strings: &'static [&'static str]) -> Self {
+        Self { strings }
+    }
+
+    /// Returns the id of `value`, if it is in the lookup.
+    pub fn id_of(&self, value: &str) -> Option<StringId> {
+        let index = self.strings.binary_search(&value).ok()?;
+        Some(StringId { id: index as u32 })
+    }
+
+    /// Convert an event whose metadata has the id `metadata`, with its field
+    /// names looked up here.
+    pub fn event<'a>(
+        &'a self,
+        event: &'a Event<'a>,
+        metadata: SerializeMetadataId,
+    ) -> SerializeEventInterned<'a, Live> {
+        SerializeEventInterned {
+            fields: InternedFields::Static {
+                event,
+                strings: self,
+            },
+            metadata,
+            parent: event.parent().map(|p| p.as_serde()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
+
+/// Metadata for every known callsite, each with a dense id.
+///
+/// Serializes as a list of [`SerializeMetadata`], in id order.
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(transparent)]
+pub struct MetadataTable {
+    entries: Vec<SerializeMetadata<'static>>,
+}
+
+#[cfg(feature = "std")]
+impl MetadataTable {
+    /// A table of `metadata`, sorted to assign the ids, and without
+    /// duplicate callsites.
+    pub fn new<'a>(metadata: impl IntoIterator<Item = &'a SerializeMetadata<'a>>) -> Self {
+        let mut entries: Vec<_> = metadata.into_iter().map(|m| m.to_owned()).collect();
+        entries.sort_by(|a, b| key(a).cmp(&key(b)));
+        entries.dedup_by(|a, b| key(a) == key(b));
+        Self { entries }
+    }
+
+    /// A table of all the metadata defined in `dictionary`.
+    pub fn from_dictionary(dictionary: &MetadataDictionary) -> Self {
+        Self::new(dictionary.iter().map(|(_, metadata)| metadata))
+    }
+
+    /// Returns the metadata with id `id`, if any.
+    pub fn get(&self, id: SerializeMetadataId) -> Option<&SerializeMetadata<'static>> {
+        usize::try_from(id.id)
+            .ok()
+            .and_then(|index| self.entries.get(index))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (SerializeMetadataId, &SerializeMetadata<'static>)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .map(|(index, metadata)| (SerializeMetadataId { id: index as u64 }, metadata))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The field names of all the callsites in the table, sorted and without
+    /// duplicates. A name's id is its position in the list.
+    pub fn strings(&self) -> Vec<&str> {
+        let mut strings = Vec::new();
+        for metadata in &self.entries {
+            match &metadata.fields {
+                SerializeFieldSet::Ser(fields) => strings.extend(fields.iter().map(|f| f.name())),
+                SerializeFieldSet::De(fields) => strings.extend(fields.iter().map(|f| f.as_str())),
+            }
+        }
+        strings.sort_unstable();
+        strings.dedup();
+        strings
+    }
+
+    /// A dictionary with every entry of the table defined, and every field
+    /// name too.
+    pub fn to_dictionary(&self) -> MetadataDictionary {
+        let mut dictionary = MetadataDictionary::new();
+        for (id, metadata) in self.iter() {
+            dictionary.insert(id, metadata);
+        }
+        for (id, value) in self.strings().into_iter().enumerate() {
+            dictionary.insert_string(StringId { id: id as u32 }, &CowString::Borrowed(value));
+        }
+        dictionary
+    }
+
+    /// Writes the entries of the table as a Rust expression of type
+    /// `&'static [StaticEntry]`, to be `include!`d and passed to
+    /// [`StaticTable::new`].
+    pub fn write_lookup(&self, mut out: impl io::Write) -> io::Result<()> {
+        writeln!(
+            out,
+            "// Generated by tracing-serde-structured; do not edit."
+        )?;
+        writeln!(out, "&[")?;
+        for metadata in &self.entries {
+            writeln!(
+                out,
+                "    ::tracing_serde_structured::wire::table::StaticEntry {{ file: {:?}, line: {:?}, name: {:?} }},",
+                metadata.file.as_deref(),
+                metadata.line,
+                metadata.name.as_str(),
+            )?;
+        }
+        writeln!(out, "]")
+    }
+
+    /// Writes the field names of the table as a Rust expression of type
+    /// `&'static [&'static str]`, to be `include!`d and passed to
+    /// [`StaticStrings::new`].
+    pub fn write_strings(&self, mut out: impl io::Write) -> io::Result<()> {
+        writeln!(
+            out,
+            "// Generated by tracing-serde-structured; do not edit."
+        )?;
+        writeln!(out, "&[")?;
+        for value in self.strings() {
+            writeln!(out, "    {:?},", value)?;
+        }
+        writeln!(out, "]")
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'de> serde::Deserialize<'de> for MetadataTable {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let entries = Vec::<SerializeMetadata<'de>>::deserialize(deserializer)?;
+        Ok(Self {
+            entries: entries.iter().map(|m| m.to_owned()).collect(),
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+fn key<'a>(metadata: &'a SerializeMetadata<'_>) -> (Option<&'a str>, Option<u32>, &'a str) {
+    (
+        metadata.file.as_deref(),
+        metadata.line,
+        metadata.name.as_str(),
+    )
+}
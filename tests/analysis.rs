@@ -0,0 +1,238 @@
+#![cfg(feature = "analysis")]
+
+use std::time::Duration;
+
+use tracing_serde_structured::{
+    analysis::{Callsite, Histogram, Latencies, LatencyReport, Stats},
+    builder::{SerializeAttributesBuilder, SerializeEventBuilder},
+    span_tree::SpanTreeBuilder,
+    wire::TracingWire,
+    SerializeId, SerializeLevel,
+};
+
+fn id(id: u64) -> SerializeId {
+    SerializeId {
+        id: id.try_into().unwrap(),
+    }
+}
+
+fn event(target: &'static str, name: &'static str, level: SerializeLevel) -> TracingWire<'static> {
+    TracingWire::Event(
+        SerializeEventBuilder::new()
+            .target(target)
+            .name(name)
+            .level(level)
+            .build(),
+    )
+}
+
+#[test]
+fn counts_by_level_target_and_callsite() {
+    let mut stats = Stats::new();
+    let ms = Duration::from_millis;
+    stats.record(ms(0), &event("net", "recv", SerializeLevel::Debug), 10);
+    stats.record(ms(1), &event("net", "recv", SerializeLevel::Debug), 10);
+    stats.record(ms(2), &event("net", "send", SerializeLevel::Info), 30);
+    stats.record(ms(3), &event("db", "query", SerializeLevel::Warn), 5);
+    stats.record(ms(4), &TracingWire::Enter(id(1)), 3);
+
+    let summary = stats.summary();
+    assert_eq!(summary.messages, 5);
+    assert_eq!(summary.bytes, 58);
+    assert_eq!(summary.duration, ms(4));
+    assert_eq!(summary.levels[&SerializeLevel::Debug], 2);
+    assert_eq!(summary.levels[&SerializeLevel::Info], 1);
+    assert!(!summary.levels.contains_key(&SerializeLevel::Error));
+
+    let targets: Vec<_> = summary
+        .targets
+        .iter()
+        .map(|t| (t.target.as_str(), t.counts.messages, t.counts.bytes))
+        .collect();
+    assert_eq!(targets, [("net", 3, 50), ("db", 1, 5)]);
+
+    let callsites: Vec<_> = summary
+        .callsites
+        .iter()
+        .map(|c| (c.callsite.name.as_str(), c.level, c.counts.bytes))
+        .collect();
+    assert_eq!(
+        callsites,
+        [
+            ("send", SerializeLevel::Info, 30),
+            ("recv", SerializeLevel::Debug, 20),
+            ("query", SerializeLevel::Warn, 5),
+        ]
+    );
+}
+
+#[test]
+fn spans_are_counted_by_callsite() {
+    let mut stats = Stats::new();
+    let attributes = SerializeAttributesBuilder::new("connect")
+        .target("net")
+        .build();
+    let msg = TracingWire::NewSpan {
+        id: id(1),
+        attributes,
+    };
+    stats.record(Duration::ZERO, &msg, 8);
+
+    let summary = stats.summary();
+    assert_eq!(
+        summary.callsites[0].callsite,
+        Callsite {
+            target: "net".into(),
+            name: "connect".into(),
+            file: None,
+            line: None,
+        }
+    );
+    // Spans are not events.
+    assert_eq!(summary.events_per_window, [0]);
+}
+
+#[test]
+fn events_are_counted_per_window() {
+    let mut stats = Stats::new();
+    stats.set_window(Duration::from_millis(100));
+    for ms in [1000, 1010, 1020, 1250, 1260] {
+        stats.record(
+            Duration::from_millis(ms),
+            &event("t", "e", SerializeLevel::Info),
+            1,
+        );
+    }
+
+    let summary = stats.summary();
+    assert_eq!(summary.window, Duration::from_millis(100));
+    assert_eq!(summary.events_per_window, [3, 0, 2]);
+    assert_eq!(summary.peak_events_per_window, 3);
+
+    stats.clear();
+    let summary = stats.summary();
+    assert_eq!(summary.messages, 0);
+    assert!(summary.events_per_window.is_empty());
+    assert_eq!(summary.window, Duration::from_millis(100));
+}
+
+#[test]
+fn summary_round_trips_through_json() {
+    let mut stats = Stats::new();
+    stats.record(
+        Duration::from_secs(2),
+        &event("t", "e", SerializeLevel::Error),
+        7,
+    );
+    let summary = stats.summary();
+
+    let json = serde_json::to_value(&summary).unwrap();
+    assert_eq!(json["levels"]["ERROR"], 1);
+    assert_eq!(json["callsites"][0]["bytes"], 7);
+    assert_eq!(json["callsites"][0]["callsite"]["target"], "t");
+
+    let back = serde_json::from_value(json).unwrap();
+    assert_eq!(summary, back);
+}
+
+#[test]
+fn histograms_are_exact_for_short_durations() {
+    let mut histogram = Histogram::new();
+    for ns in [1, 2, 3, 4, 50] {
+        histogram.record(Duration::from_nanos(ns));
+    }
+    assert_eq!(histogram.count(), 5);
+    assert_eq!(histogram.min(), Duration::from_nanos(1));
+    assert_eq!(histogram.max(), Duration::from_nanos(50));
+    assert_eq!(histogram.mean(), Duration::from_nanos(12));
+    assert_eq!(histogram.quantile(0.5), Duration::from_nanos(3));
+    assert_eq!(histogram.quantile(0.0), Duration::from_nanos(1));
+    assert_eq!(histogram.quantile(1.0), Duration::from_nanos(50));
+    assert_eq!(histogram.buckets().count(), 5);
+}
+
+#[test]
+fn histograms_are_accurate_for_long_durations() {
+    let mut histogram = Histogram::new();
+    for ms in 1..=1000 {
+        histogram.record(Duration::from_millis(ms));
+    }
+    for (quantile, expected) in [(0.5, 500.0), (0.95, 950.0), (0.99, 990.0)] {
+        let actual = histogram.quantile(quantile).as_secs_f64() * 1000.0;
+        assert!(
+            (actual - expected).abs() / expected < 0.035,
+            "p{quantile}: {actual}ms"
+        );
+    }
+    assert_eq!(histogram.max(), Duration::from_secs(1));
+
+    // Buckets cover every duration once, in order.
+    let buckets: Vec<_> = histogram.buckets().collect();
+    assert_eq!(buckets.iter().map(|b| b.count).sum::<u64>(), 1000);
+    for pair in buckets.windows(2) {
+        assert!(pair[0].high < pair[1].low);
+    }
+    for bucket in &buckets {
+        assert!(bucket.low <= bucket.high);
+    }
+
+    let mut merged = Histogram::new();
+    merged.merge(&histogram);
+    merged.merge(&histogram);
+    assert_eq!(merged.count(), 2000);
+    assert_eq!(merged.quantile(0.5), histogram.quantile(0.5));
+    assert_eq!(merged.min(), Duration::from_millis(1));
+
+    let mut extreme = Histogram::new();
+    extreme.record(Duration::MAX);
+    assert_eq!(extreme.quantile(0.5), Duration::from_nanos(u64::MAX));
+    assert!(Histogram::new().is_empty());
+    assert_eq!(Histogram::new().quantile(0.5), Duration::ZERO);
+}
+
+#[test]
+fn latencies_are_collected_per_callsite_from_span_trees() {
+    let mut builder = SpanTreeBuilder::new();
+    let ms = Duration::from_millis;
+    for (i, (name, took)) in [("fast", 1), ("slow", 100), ("fast", 3), ("open", 0)]
+        .into_iter()
+        .enumerate()
+    {
+        let attributes = SerializeAttributesBuilder::new(name).target("db").build();
+        let span = id(i as u64 + 1);
+        builder.push(
+            ms(10),
+            &TracingWire::NewSpan {
+                id: span.clone(),
+                attributes,
+            },
+        );
+        if name != "open" {
+            builder.push(ms(10 + took), &TracingWire::CloseSpan(span));
+        }
+    }
+    let mut latencies = Latencies::new();
+    latencies.add_tree(&builder.finish());
+
+    let report = latencies.report();
+    let names: Vec<_> = report
+        .callsites
+        .iter()
+        .map(|c| (c.callsite.name.as_str(), c.count))
+        .collect();
+    assert_eq!(names, [("slow", 1), ("fast", 2)]);
+
+    let fast = &report.callsites[1];
+    assert_eq!(fast.min, ms(1));
+    assert_eq!(fast.max, ms(3));
+    assert_eq!(fast.mean, ms(2));
+    assert_eq!(fast.buckets.len(), 2);
+    let histogram = latencies.histogram(&fast.callsite).unwrap();
+    assert_eq!(histogram.count(), 2);
+
+    let json = serde_json::to_string(&report).unwrap();
+    assert_eq!(
+        serde_json::from_str::<LatencyReport>(&json).unwrap(),
+        report
+    );
+}
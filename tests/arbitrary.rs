@@ -0,0 +1,74 @@
+#![cfg(feature = "arbitrary")]
+
+use arbitrary::{Arbitrary, Unstructured};
+use tracing_serde_structured::{
+    wire::{delta::DeltaFrame, TracingWire},
+    SerializeEvent, SerializeFieldSet, SerializeRecordFields,
+};
+
+/// Deterministic noise to generate values from.
+fn noise(seed: u64, len: usize) -> Vec<u8> {
+    let mut state = seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+fn generate<T: for<'a> Arbitrary<'a>>(seed: u64) -> T {
+    let bytes = noise(seed, 512);
+    T::arbitrary(&mut Unstructured::new(&bytes)).unwrap()
+}
+
+#[test]
+fn messages_round_trip_through_postcard() {
+    for seed in 0..500 {
+        let msg: TracingWire<'static> = generate(seed);
+        let bytes = postcard::to_allocvec(&msg).unwrap();
+        let decoded: TracingWire<'_> = postcard::from_bytes(&bytes).unwrap();
+        assert_eq!(postcard::to_allocvec(&decoded).unwrap(), bytes, "{msg:?}");
+    }
+}
+
+#[test]
+fn frames_round_trip_through_postcard() {
+    for seed in 0..100 {
+        let frame: DeltaFrame<'static> = generate(seed);
+        let bytes = postcard::to_allocvec(&frame).unwrap();
+        let decoded: DeltaFrame<'_> = postcard::from_bytes(&bytes).unwrap();
+        assert_eq!(postcard::to_allocvec(&decoded).unwrap(), bytes);
+    }
+}
+
+#[test]
+fn events_declare_their_fields() {
+    for seed in 0..100 {
+        let event: SerializeEvent<'static> = generate(seed);
+        assert!(event.metadata.is_event && !event.metadata.is_span);
+
+        let (SerializeRecordFields::De(fields), SerializeFieldSet::De(names)) =
+            (&event.fields, &event.metadata.fields)
+        else {
+            panic!("generated a borrowed event: {event:?}");
+        };
+        assert!(fields.keys().eq(names.iter()));
+    }
+}
+
+#[test]
+fn every_kind_of_message_is_generated() {
+    let mut kinds = std::collections::BTreeSet::new();
+    for seed in 0..500 {
+        let msg: TracingWire<'static> = generate(seed);
+        let json = serde_json::to_value(&msg).unwrap();
+        kinds.insert(match json {
+            serde_json::Value::Object(map) => map.keys().next().unwrap().clone(),
+            other => other.as_str().unwrap().to_string(),
+        });
+    }
+    assert_eq!(kinds.len(), 20, "{kinds:?}");
+}
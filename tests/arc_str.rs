@@ -0,0 +1,85 @@
+#![cfg(feature = "arc-str")]
+
+use std::sync::Mutex;
+
+use tracing::info;
+use tracing_core::{
+    span::{Attributes, Id, Record},
+    Event, Metadata, Subscriber,
+};
+use tracing_serde_structured::{
+    AsSerde, CowString, DebugRecord, SerializeEvent, SerializeRecordFields, SerializeValue,
+};
+
+#[derive(Default)]
+struct OwnedSubscriber {
+    events: Mutex<Vec<SerializeEvent<'static>>>,
+}
+
+impl Subscriber for OwnedSubscriber {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, _: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        self.events
+            .lock()
+            .unwrap()
+            .push(event.as_serde().to_owned());
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+fn shared(value: &SerializeValue<'_>) -> *const u8 {
+    match value {
+        SerializeValue::Str(s @ CowString::Copied(_))
+        | SerializeValue::Debug(DebugRecord::De(s @ CowString::Copied(_))) => s.as_ptr(),
+        other => panic!("not copied: {other:?}"),
+    }
+}
+
+#[test]
+fn clones_share_strings() {
+    let subscriber = std::sync::Arc::new(OwnedSubscriber::default());
+    tracing::subscriber::with_default(subscriber.clone(), || {
+        let user = String::from("ferris");
+        info!(user = user.as_str(), id = ?(1, 2), "logged in");
+    });
+    let event = subscriber.events.lock().unwrap().pop().unwrap();
+    let copy = event.clone();
+
+    let (SerializeRecordFields::De(a), SerializeRecordFields::De(b)) =
+        (&event.fields, &copy.fields)
+    else {
+        panic!("fields were not converted");
+    };
+    assert_eq!(a.len(), 3);
+    for ((ka, va), (kb, vb)) in a.iter().zip(b.iter()) {
+        assert_eq!(ka, kb);
+        assert_eq!(shared(va), shared(vb));
+    }
+
+    // Names from `'static` metadata are not copied in the first place.
+    assert!(matches!(event.metadata.target, CowString::Static(_)));
+}
+
+#[test]
+fn deserialized_strings_are_shared() {
+    let json = r#""needs \"escaping\"""#;
+    let value: CowString<'static> = serde_json::from_str(json).unwrap();
+    assert!(matches!(&value, CowString::Copied(s) if s.as_str() == "needs \"escaping\""));
+
+    let clone = value.clone();
+    assert_eq!(clone.as_str().as_ptr(), value.as_str().as_ptr());
+}
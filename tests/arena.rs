@@ -0,0 +1,85 @@
+#![cfg(feature = "bumpalo")]
+
+use std::sync::{Arc, Mutex};
+
+use bumpalo::Bump;
+use tracing::info;
+use tracing_core::{
+    span::{Attributes, Id, Record},
+    Event, Metadata, Subscriber,
+};
+use tracing_serde_structured::{AsSerde, CowString, SerializeRecordFields, SerializeValue};
+
+/// Converts each event into the arena, and keeps its JSON and the arena size.
+#[derive(Default)]
+struct ArenaSubscriber {
+    converted: Mutex<Vec<(serde_json::Value, serde_json::Value, usize)>>,
+}
+
+impl Subscriber for ArenaSubscriber {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, _: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let bump = Bump::new();
+        let owned = event.as_serde().to_owned_in(&bump);
+
+        match &owned.fields {
+            SerializeRecordFields::De(map) => {
+                assert!(map.keys().all(|k| matches!(k, CowString::Static(_))));
+                assert!(map.values().all(|v| match v {
+                    SerializeValue::Str(s) => matches!(s, CowString::Borrowed(_)),
+                    _ => true,
+                }));
+            }
+            SerializeRecordFields::Ser(_) => panic!("fields were not converted"),
+        }
+        assert!(matches!(owned.metadata.target, CowString::Static(_)));
+
+        let expected = serde_json::to_value(event.as_serde().to_owned()).unwrap();
+        let json = serde_json::to_value(&owned).unwrap();
+        let used = bump.allocated_bytes();
+        self.converted.lock().unwrap().push((expected, json, used));
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+#[test]
+fn events_convert_into_the_arena() {
+    let subscriber = Arc::new(ArenaSubscriber::default());
+    tracing::subscriber::with_default(subscriber.clone(), || {
+        info!(user = "ferris", attempt = 3, "logged in as {}", "ferris");
+    });
+
+    let converted = std::mem::take(&mut *subscriber.converted.lock().unwrap());
+    assert_eq!(converted.len(), 1);
+    let (expected, json, used) = &converted[0];
+    assert_eq!(expected, json);
+    assert!(*used > 0);
+}
+
+#[test]
+fn converting_deserialized_values_copies_into_the_arena() {
+    let json = r#"{"Str":"escaped \"value\""}"#;
+    let value: SerializeValue<'_> = serde_json::from_str(json).unwrap();
+
+    let bump = Bump::new();
+    let copied = value.to_owned_in(&bump);
+    drop(value);
+    match copied {
+        SerializeValue::Str(CowString::Borrowed(s)) => assert_eq!(s, "escaped \"value\""),
+        other => panic!("unexpected value {other:?}"),
+    }
+}
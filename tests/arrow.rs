@@ -0,0 +1,87 @@
+#![cfg(feature = "arrow")]
+
+use std::{
+    collections::BTreeMap,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use arrow_array::{cast::AsArray, types::TimestampNanosecondType, Array};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use tracing_serde_structured::{
+    arrow::{EventBatchBuilder, ParquetEventWriter},
+    CowString, DebugRecord, SerializeEvent, SerializeFieldSet, SerializeLevel, SerializeMetadata,
+    SerializeRecordFields, SerializeValue,
+};
+
+fn event(message: Option<&'static str>, answer: u64) -> SerializeEvent<'static> {
+    let mut fields = BTreeMap::new();
+    if let Some(message) = message {
+        fields.insert(
+            CowString::Borrowed("message"),
+            SerializeValue::Debug(DebugRecord::De(CowString::Borrowed(message))),
+        );
+    }
+    fields.insert(CowString::Borrowed("answer"), SerializeValue::U64(answer));
+    SerializeEvent {
+        fields: SerializeRecordFields::De(fields),
+        metadata: SerializeMetadata {
+            name: CowString::Borrowed("event src/main.rs:7"),
+            target: CowString::Borrowed("my_crate"),
+            level: SerializeLevel::Warn,
+            module_path: None,
+            file: None,
+            line: None,
+            fields: SerializeFieldSet::De(vec![]),
+            is_span: false,
+            is_event: true,
+        },
+        parent: None,
+    }
+}
+
+#[test]
+fn builds_record_batches() {
+    let mut builder = EventBatchBuilder::new();
+    builder.push(
+        UNIX_EPOCH + Duration::from_nanos(5),
+        &event(Some("hello"), 42),
+    );
+    builder.push(UNIX_EPOCH + Duration::from_nanos(6), &event(None, 7));
+    let batch = builder.finish();
+    assert!(builder.is_empty());
+
+    assert_eq!(batch.num_rows(), 2);
+    let ts = batch.column(0).as_primitive::<TimestampNanosecondType>();
+    assert_eq!(ts.values(), &[5, 6]);
+    assert_eq!(batch.column(1).as_string::<i32>().value(0), "WARN");
+    assert_eq!(batch.column(2).as_string::<i32>().value(1), "my_crate");
+
+    let message = batch.column(3).as_string::<i32>();
+    assert_eq!(message.value(0), "hello");
+    assert!(message.is_null(1));
+
+    let fields = batch.column(4).as_map();
+    let first = fields.value(0);
+    assert_eq!(first.column(0).as_string::<i32>().value(0), "answer");
+    assert_eq!(first.column(1).as_string::<i32>().value(0), "42");
+}
+
+#[test]
+fn writes_parquet() {
+    let path = std::env::temp_dir().join(format!("tss-arrow-{}.parquet", std::process::id()));
+    let file = std::fs::File::create(&path).unwrap();
+
+    let mut writer = ParquetEventWriter::with_options(file, 2, None).unwrap();
+    for i in 0..5 {
+        writer.push(UNIX_EPOCH, &event(Some("hi"), i)).unwrap();
+    }
+    writer.into_inner().unwrap();
+
+    let reader = ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(&path).unwrap())
+        .unwrap()
+        .build()
+        .unwrap();
+    let rows: usize = reader.map(|batch| batch.unwrap().num_rows()).sum();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(rows, 5);
+}
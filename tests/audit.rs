@@ -0,0 +1,217 @@
+#![cfg(feature = "audit")]
+
+use sha2::{Digest, Sha256};
+use tracing_serde_structured::{
+    audit::{Chain, Error, Verifier},
+    SerializeLevel,
+};
+
+const SEED: &[u8] = b"seed";
+
+fn frames(messages: &[&str]) -> (Vec<Vec<u8>>, Chain<Sha256>) {
+    let mut chain = Chain::new(SEED);
+    let frames = messages
+        .iter()
+        .map(|msg| {
+            let mut buf = [0u8; 128];
+            chain.to_slice_cobs(msg, &mut buf).unwrap().to_vec()
+        })
+        .collect();
+    (frames, chain)
+}
+
+/// Verifies each frame, without its terminating zero byte.
+fn verify(verifier: &mut Verifier<Sha256>, frames: &[Vec<u8>]) -> Vec<Result<String, String>> {
+    frames
+        .iter()
+        .map(|frame| {
+            let mut frame = frame[..frame.len() - 1].to_vec();
+            verifier
+                .from_bytes_cobs::<&str>(&mut frame)
+                .map(str::to_owned)
+                .map_err(|e| e.to_string())
+        })
+        .collect()
+}
+
+#[test]
+fn intact_chains_verify() {
+    let (frames, chain) = frames(&["a", "b", "c"]);
+    for frame in &frames {
+        // Message, hash, and COBS overhead.
+        assert_eq!(frame.len(), 2 + 32 + 2);
+        assert_eq!(frame.iter().position(|b| *b == 0), Some(frame.len() - 1));
+    }
+
+    let mut verifier = Verifier::<Sha256>::new(SEED);
+    let messages = verify(&mut verifier, &frames);
+    assert_eq!(messages, [Ok("a".into()), Ok("b".into()), Ok("c".into())]);
+    assert_eq!(verifier.head(), chain.head());
+}
+
+#[test]
+fn the_first_frame_embeds_the_seed() {
+    let (frames, _) = frames(&["a"]);
+    let mut frame = frames[0][..frames[0].len() - 1].to_vec();
+    let len = cobs::decode_in_place(&mut frame).unwrap();
+    assert_eq!(frame[len - 32..len], Sha256::digest(SEED)[..]);
+
+    let mut verifier = Verifier::<Sha256>::new(b"other seed");
+    assert!(verify(&mut verifier, &frames)[0].is_err());
+}
+
+#[test]
+fn gaps_are_reported_once() {
+    let (mut frames, _) = frames(&["a", "b", "c", "d"]);
+    frames.remove(1);
+
+    let mut verifier = Verifier::<Sha256>::new(SEED);
+    let messages = verify(&mut verifier, &frames);
+    assert_eq!(messages[0], Ok("a".into()));
+    assert_eq!(
+        messages[1],
+        Err("frame does not follow from the previous frame's hash".into())
+    );
+    assert_eq!(messages[2], Ok("d".into()));
+}
+
+#[test]
+fn alterations_break_the_next_frame() {
+    let (mut frames, _) = frames(&["a", "b", "c"]);
+    // "b" becomes "x", which COBS leaves as is.
+    let at = frames[1].iter().position(|b| *b == b'b').unwrap();
+    frames[1][at] = b'x';
+
+    let mut verifier = Verifier::<Sha256>::new(SEED);
+    let messages = verify(&mut verifier, &frames);
+    assert_eq!(messages[1], Ok("x".into()));
+    assert!(messages[2].is_err());
+}
+
+#[test]
+fn truncation_is_found_against_the_head() {
+    let (frames, chain) = frames(&["a", "b", "c"]);
+
+    let mut verifier = Verifier::<Sha256>::new(SEED);
+    let messages = verify(&mut verifier, &frames[..2]);
+    assert!(messages.iter().all(Result::is_ok));
+    assert_ne!(verifier.head(), chain.head());
+}
+
+#[test]
+fn short_frames_are_malformed() {
+    let mut verifier = Verifier::<Sha256>::new(SEED);
+    assert!(matches!(verifier.verify(&[1, 2, 3]), Err(Error::Frame)));
+    let mut frame = [0x05, 1, 2];
+    assert!(matches!(verifier.open(&mut frame), Err(Error::Frame)));
+}
+
+#[test]
+fn frames_that_do_not_fit_leave_the_chain() {
+    let mut chain = Chain::<Sha256>::new(SEED);
+    let head = *chain.head();
+    let mut buf = [0u8; 16];
+    let err = chain
+        .to_slice_cobs(&SerializeLevel::Warn, &mut buf)
+        .unwrap_err();
+    assert_eq!(err, postcard::Error::SerializeBufferFull);
+    assert_eq!(*chain.head(), head);
+}
+
+#[cfg(feature = "embedded-io")]
+#[test]
+fn framed_writers_write_chained_frames() {
+    use tracing_serde_structured::embedded_io::FramedWriter;
+
+    let mut chain = Chain::<Sha256>::new(SEED);
+    let mut writer = FramedWriter::<_, 64>::new(Vec::new());
+    writer.write_chained("a", &mut chain).unwrap();
+    writer.write_chained("b", &mut chain).unwrap();
+    assert_eq!(writer.stats().sent, 2);
+
+    let (expected, _) = frames(&["a", "b"]);
+    assert_eq!(*writer.get_ref(), expected.concat());
+}
+
+#[cfg(feature = "host")]
+mod host {
+    use std::io::Cursor;
+
+    use tracing_serde_structured::{
+        host::{Decoder, Error as HostError},
+        time::TickRate,
+        wire::{delta::DeltaEncoder, TracingWire},
+        Live, SerializeId,
+    };
+
+    use super::*;
+
+    fn id(id: u64) -> SerializeId {
+        SerializeId {
+            id: id.try_into().unwrap(),
+        }
+    }
+
+    fn enter(id_: u64) -> TracingWire<'static> {
+        TracingWire::Enter(id(id_))
+    }
+
+    fn stream(count: u64) -> (Vec<Vec<u8>>, Chain<Sha256>) {
+        let mut encoder = DeltaEncoder::new();
+        let mut chain = Chain::new(SEED);
+        let frames = (1..=count)
+            .map(|i| {
+                let mut buf = [0u8; 128];
+                let frame = encoder.encode(i, TracingWire::<Live>::Enter(id(i)));
+                chain.to_slice_cobs(&frame, &mut buf).unwrap().to_vec()
+            })
+            .collect();
+        (frames, chain)
+    }
+
+    #[test]
+    fn decoders_verify_audit_streams() {
+        let (frames, chain) = stream(3);
+        let mut decoder = Decoder::new(TickRate::MICROS);
+        decoder.set_audit(Verifier::<Sha256>::new(SEED));
+
+        let mut messages = decoder.read(Cursor::new(frames.concat()));
+        let decoded: Vec<_> = messages.by_ref().map(|msg| msg.unwrap().msg).collect();
+        assert_eq!(decoded, [enter(1), enter(2), enter(3)]);
+        assert_eq!(messages.decoder().audit_head(), Some(&chain.head()[..]));
+
+        assert_eq!(Decoder::new(TickRate::MICROS).audit_head(), None);
+    }
+
+    #[test]
+    fn decoders_report_broken_chains() {
+        let (mut frames, chain) = stream(4);
+        // Alter the hash the second frame embeds.
+        let mut raw = frames[1][..frames[1].len() - 1].to_vec();
+        let len = cobs::decode_in_place(&mut raw).unwrap();
+        raw.truncate(len);
+        *raw.last_mut().unwrap() ^= 1;
+        let mut frame = vec![0; cobs::max_encoding_length(len) + 1];
+        let len = cobs::encode(&raw, &mut frame);
+        frame.truncate(len + 1);
+        frames[1] = frame;
+
+        let mut decoder = Decoder::new(TickRate::MICROS);
+        decoder.set_audit(Verifier::<Sha256>::new(SEED));
+        let mut messages = decoder.read(Cursor::new(frames.concat()));
+
+        assert_eq!(messages.next().unwrap().unwrap().msg, enter(1));
+        // The altered frame, and the one after it, which embeds the hash of
+        // the original.
+        for _ in 0..2 {
+            assert!(matches!(
+                messages.next(),
+                Some(Err(HostError::Audit(Error::Broken)))
+            ));
+        }
+        // The broken frames were still decoded, so the delta encoding carries
+        // on.
+        assert_eq!(messages.next().unwrap().unwrap().msg, enter(4));
+        assert_eq!(messages.decoder().audit_head(), Some(&chain.head()[..]));
+    }
+}
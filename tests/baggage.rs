@@ -0,0 +1,239 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use tracing::info_span;
+use tracing_core::{
+    span::{Attributes, Id, Record},
+    Event, Metadata, Subscriber,
+};
+use tracing_serde_structured::{
+    baggage::{BaggageEntry, SerializeBaggage, SpanBaggage, MAX_ENTRIES, MAX_HEADER_LEN},
+    builder::SerializeAttributesBuilder,
+    span_store::SpanStore,
+    wire::{
+        delta::{DeltaDecoder, DeltaEncoder, DeltaFrame},
+        TracingWire,
+    },
+    Detached, SerializeId,
+};
+
+#[test]
+fn headers_parse_and_format() {
+    let baggage = SerializeBaggage::parse(
+        " tenant = acme , region=eu%20west;ttl=60, bad key=1,=x,broken,note=%E2%9C%93, tenant=beta",
+    );
+    let entries: Vec<(&str, &str, Option<&str>)> = baggage
+        .iter()
+        .map(|e| {
+            (
+                e.key.as_str(),
+                e.value.as_str(),
+                e.metadata.as_ref().map(|m| m.as_str()),
+            )
+        })
+        .collect();
+    assert_eq!(
+        entries,
+        [
+            ("tenant", "beta", None),
+            ("region", "eu west", Some("ttl=60")),
+            ("note", "\u{2713}", None),
+        ]
+    );
+    assert!(baggage.entry("tenant").unwrap().value.is_borrowed());
+    assert_eq!(
+        baggage.to_string(),
+        "tenant=beta,region=eu%20west;ttl=60,note=%E2%9C%93"
+    );
+    assert_eq!(SerializeBaggage::parse(&baggage.to_string()), baggage);
+
+    // Percent signs are escaped, and invalid escapes refused.
+    let mut baggage = SerializeBaggage::new();
+    baggage.insert(BaggageEntry::new("ratio", "50%")).unwrap();
+    baggage.insert(BaggageEntry::new("not a key", "x")).unwrap();
+    assert_eq!(baggage.to_string(), "ratio=50%25");
+    assert!(SerializeBaggage::parse("a=%zz,b=%4").is_empty());
+    assert_eq!(baggage.remove("ratio").unwrap().value.as_str(), "50%");
+    assert_eq!(baggage.len(), 1);
+}
+
+#[test]
+fn limits_are_kept() {
+    let mut baggage: SerializeBaggage<'_> = (0..MAX_ENTRIES)
+        .map(|i| BaggageEntry::new(format!("k{i}"), "v"))
+        .collect();
+    assert_eq!(baggage.len(), MAX_ENTRIES);
+    let extra = BaggageEntry::new("extra", "v");
+    assert_eq!(baggage.insert(extra.clone()), Err(extra));
+    baggage.insert(BaggageEntry::new("k0", "w")).unwrap();
+    assert_eq!(baggage.get("k0"), Some("w"));
+
+    let header: Vec<String> = (0..=MAX_ENTRIES).map(|i| format!("k{i}=v")).collect();
+    assert_eq!(
+        SerializeBaggage::parse(&header.join(",")).len(),
+        MAX_ENTRIES
+    );
+
+    // Entries that would make the header too long are left out.
+    let long = "x".repeat(MAX_HEADER_LEN - 10);
+    let baggage: SerializeBaggage<'_> = [
+        BaggageEntry::new("a", long.as_str()),
+        BaggageEntry::new("b", "too long"),
+        BaggageEntry::new("c", "1"),
+    ]
+    .into_iter()
+    .collect();
+    let header = baggage.to_string();
+    assert_eq!(header.len(), MAX_HEADER_LEN - 4);
+    assert!(header.ends_with(",c=1"));
+}
+
+struct BaggageSubscriber {
+    baggage: SpanBaggage,
+    next_id: AtomicU64,
+    stack: Mutex<Vec<Id>>,
+    sent: Mutex<Vec<TracingWire<'static>>>,
+}
+
+impl Subscriber for BaggageSubscriber {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+        let id = Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed));
+        let current = self.stack.lock().unwrap().last().cloned();
+        self.baggage.new_span(&id, attrs, current.as_ref());
+        if attrs.is_root() {
+            self.baggage
+                .extend(&id, &SerializeBaggage::parse("tenant=acme,plan=free"));
+            self.sent
+                .lock()
+                .unwrap()
+                .push(self.baggage.message(&id).unwrap());
+        }
+        id
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, _: &Event<'_>) {}
+
+    fn enter(&self, id: &Id) {
+        self.stack.lock().unwrap().push(id.clone());
+    }
+
+    fn exit(&self, _: &Id) {
+        self.stack.lock().unwrap().pop();
+    }
+
+    fn try_close(&self, id: Id) -> bool {
+        self.baggage.close(&id);
+        true
+    }
+}
+
+#[test]
+fn children_inherit_the_baggage_of_their_parents() {
+    let subscriber = Arc::new(BaggageSubscriber {
+        baggage: SpanBaggage::new(),
+        next_id: AtomicU64::new(1),
+        stack: Mutex::default(),
+        sent: Mutex::default(),
+    });
+    tracing::subscriber::with_default(subscriber.clone(), || {
+        let root = info_span!(parent: None, "request");
+        let _root = root.enter();
+        let child = info_span!("query");
+        let child_id = child.id().unwrap();
+        let baggage = &subscriber.baggage;
+        assert_eq!(baggage.get(&child_id).unwrap().get("tenant"), Some("acme"));
+
+        // Changes apply to the span's subtree from then on, and leave the
+        // spans created before alone.
+        baggage
+            .insert(&child_id, BaggageEntry::new("plan", "paid"))
+            .unwrap();
+        let _child = child.enter();
+        let grandchild = info_span!("fetch");
+        let grandchild_id = grandchild.id().unwrap();
+        assert_eq!(
+            baggage.get(&grandchild_id).unwrap().get("plan"),
+            Some("paid")
+        );
+        baggage.remove(&grandchild_id, "tenant");
+        assert_eq!(baggage.get(&grandchild_id).unwrap().get("tenant"), None);
+        assert_eq!(baggage.get(&child_id).unwrap().get("tenant"), Some("acme"));
+        let root_id = root.id().unwrap();
+        assert_eq!(baggage.get(&root_id).unwrap().get("plan"), Some("free"));
+        assert_eq!(baggage.len(), 3);
+    });
+    assert!(subscriber.baggage.is_empty());
+
+    let sent = subscriber.sent.lock().unwrap();
+    assert_eq!(sent.len(), 1);
+    let json = serde_json::to_string(&sent[0]).unwrap();
+    assert_eq!(
+        json,
+        r#"{"Baggage":{"span":{"id":1},"baggage":[{"key":"tenant","value":"acme","metadata":null},{"key":"plan","value":"free","metadata":null}]}}"#
+    );
+}
+
+fn id(id: u64) -> SerializeId {
+    SerializeId {
+        id: id.try_into().unwrap(),
+    }
+}
+
+#[test]
+fn consumers_find_the_baggage_of_a_span() {
+    let baggage = SerializeBaggage::parse("tenant=acme");
+    let msgs: [TracingWire<'_>; 3] = [
+        TracingWire::NewSpan {
+            id: id(1),
+            attributes: SerializeAttributesBuilder::new("request").build(),
+        },
+        TracingWire::Baggage {
+            span: id(1),
+            baggage: baggage.clone(),
+        },
+        TracingWire::NewSpan {
+            id: id(2),
+            attributes: SerializeAttributesBuilder::new("query")
+                .parent(id(1))
+                .build(),
+        },
+    ];
+
+    // Baggage refers to its span by id, which delta encoding shrinks.
+    let mut encoder = DeltaEncoder::new();
+    let mut decoder = DeltaDecoder::new();
+    let mut store = SpanStore::new();
+    for msg in msgs {
+        let bytes = postcard::to_allocvec(&encoder.encode(0, msg.to_owned().into())).unwrap();
+        let frame: DeltaFrame<'_> = postcard::from_bytes(&bytes).unwrap();
+        let (_, decoded) = decoder.decode(frame).unwrap();
+        assert_eq!(decoded, msg);
+        store.push(Duration::ZERO, &decoded.to_owned());
+    }
+    assert_eq!(
+        store.get(&id(1)).unwrap().baggage(),
+        Some(&baggage.to_owned())
+    );
+    assert!(store.get(&id(2)).unwrap().baggage().is_none());
+    assert_eq!(store.baggage(&id(2)).unwrap().get("tenant"), Some("acme"));
+    assert!(store.baggage(&id(3)).is_none());
+
+    let owned: TracingWire<'static, Detached> = TracingWire::Baggage {
+        span: id(1),
+        baggage: baggage.to_owned(),
+    };
+    assert!(owned.is_owned());
+}
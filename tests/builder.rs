@@ -0,0 +1,119 @@
+use std::num::NonZeroU64;
+
+use tracing_serde_structured::{
+    builder::{OwnedEventBuilder, SerializeAttributesBuilder, SerializeEventBuilder},
+    CowString, SerializeEvent, SerializeFieldSet, SerializeId, SerializeLevel, SerializeMetadata,
+    SerializeRecordFields, SerializeValue,
+};
+
+#[test]
+fn event_defaults() {
+    let event = SerializeEventBuilder::new().build();
+    assert_eq!(
+        event.metadata,
+        SerializeMetadata {
+            name: CowString::Static("event"),
+            target: CowString::Static(""),
+            level: SerializeLevel::Info,
+            module_path: None,
+            file: None,
+            line: None,
+            fields: SerializeFieldSet::De(vec![]),
+            is_span: false,
+            is_event: true,
+        }
+    );
+    assert!(event.parent.is_none());
+}
+
+#[test]
+fn event_fields() {
+    let parent = SerializeId {
+        id: NonZeroU64::new(4).unwrap(),
+    };
+    let event = SerializeEventBuilder::new()
+        .name("retry")
+        .target("my_app::net")
+        .level(SerializeLevel::Warn)
+        .module_path("my_app::net")
+        .location("src/net.rs", 12)
+        .field("message", "retrying")
+        .field("attempt", 3u32)
+        .field("delay", -1.5)
+        .field("attempt", 4u32)
+        .parent(parent.clone())
+        .build();
+
+    assert_eq!(event.metadata.level, SerializeLevel::Warn);
+    assert_eq!(event.metadata.line, Some(12));
+    assert_eq!(event.parent, Some(parent));
+    // Fields keep the order they were first added in, once each.
+    assert_eq!(
+        event.metadata.fields,
+        SerializeFieldSet::De(vec!["message".into(), "attempt".into(), "delay".into()])
+    );
+    let SerializeRecordFields::De(fields) = &event.fields else {
+        panic!("built events hold their values");
+    };
+    assert_eq!(fields.len(), 3);
+    assert_eq!(
+        fields[&CowString::Static("attempt")],
+        SerializeValue::U64(4)
+    );
+    assert_eq!(
+        fields[&CowString::Static("delay")],
+        SerializeValue::F64(-1.5)
+    );
+}
+
+#[test]
+fn owned_events_outlive_their_strings() {
+    fn build(target: String) -> SerializeEvent<'static> {
+        OwnedEventBuilder::new()
+            .target(target)
+            .field(String::from("message"), String::from("hello"))
+            .build()
+    }
+
+    let event = build(String::from("my_app"));
+    assert_eq!(event.metadata.target.as_str(), "my_app");
+    let json = serde_json::to_value(&event).unwrap();
+    assert_eq!(
+        json["fields"]["message"],
+        serde_json::json!({ "Str": "hello" })
+    );
+}
+
+#[test]
+fn attributes() {
+    let span = SerializeAttributesBuilder::new("request")
+        .target("my_app::http")
+        .field("method")
+        .field("path")
+        .build();
+    assert_eq!(span.metadata.name.as_str(), "request");
+    assert!(span.metadata.is_span);
+    assert!(!span.metadata.is_event);
+    assert_eq!(
+        span.metadata.fields,
+        SerializeFieldSet::De(vec!["method".into(), "path".into()])
+    );
+    assert!(!span.is_root);
+    assert!(span.parent.is_none());
+
+    let parent = SerializeId {
+        id: NonZeroU64::new(1).unwrap(),
+    };
+    let child = SerializeAttributesBuilder::new("query")
+        .parent(parent.clone())
+        .build();
+    assert_eq!(child.parent, Some(parent.clone()));
+    assert!(!child.is_root);
+
+    let root = SerializeAttributesBuilder::new("job")
+        .parent(parent)
+        .root()
+        .build();
+    assert!(root.parent.is_none());
+    assert!(root.is_root);
+}
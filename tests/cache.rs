@@ -0,0 +1,64 @@
+use std::sync::{Arc, Mutex};
+
+use tracing::{info, warn};
+use tracing_core::{
+    span::{Attributes, Id, Record},
+    Event, Metadata, Subscriber,
+};
+use tracing_serde_structured::{cache::MetadataCache, SerializeMetadata};
+
+#[derive(Default)]
+struct CachingSubscriber {
+    cache: MetadataCache,
+    seen: Mutex<Vec<Arc<SerializeMetadata<'static>>>>,
+}
+
+impl Subscriber for CachingSubscriber {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, _: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let metadata = self.cache.get(event.metadata());
+        self.seen.lock().unwrap().push(metadata);
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+#[test]
+fn metadata_is_converted_once_per_callsite() {
+    let subscriber = Arc::new(CachingSubscriber::default());
+    tracing::subscriber::with_default(subscriber.clone(), || {
+        for i in 0..3 {
+            info!(i, "looping");
+        }
+        warn!("done");
+    });
+
+    let seen = subscriber.seen.lock().unwrap();
+    assert_eq!(seen.len(), 4);
+    assert!(Arc::ptr_eq(&seen[0], &seen[1]));
+    assert!(Arc::ptr_eq(&seen[0], &seen[2]));
+    assert!(!Arc::ptr_eq(&seen[0], &seen[3]));
+    assert_eq!(subscriber.cache.len(), 2);
+
+    assert_eq!(seen[0].target.as_str(), "cache");
+    assert_eq!(
+        seen[3].level,
+        tracing_serde_structured::SerializeLevel::Warn
+    );
+
+    subscriber.cache.clear();
+    assert!(subscriber.cache.is_empty());
+}
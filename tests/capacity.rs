@@ -0,0 +1,41 @@
+#![cfg(not(feature = "alloc"))]
+//! Without `alloc`, messages with more than `MAX_FIELDS` entries fail to
+//! deserialize instead of losing entries.
+//!
+//! Run with `cargo test --no-default-features --test capacity`.
+
+use tracing_serde_structured::{SerializeFieldSet, SerializeRecord, SerializeValue, MAX_FIELDS};
+
+#[test]
+fn too_many_fields_is_an_error() {
+    let names: Vec<String> = (0..=MAX_FIELDS).map(|i| format!("f{i}")).collect();
+    let json = serde_json::to_string(&names).unwrap();
+    let err = serde_json::from_str::<SerializeFieldSet<'_>>(&json).unwrap_err();
+    assert!(err.to_string().contains("invalid length"), "{err}");
+
+    let fits = serde_json::to_string(&names[..MAX_FIELDS]).unwrap();
+    assert!(serde_json::from_str::<SerializeFieldSet<'_>>(&fits).is_ok());
+}
+
+#[test]
+fn too_many_values_is_an_error() {
+    let entries: Vec<String> = (0..=MAX_FIELDS)
+        .map(|i| format!("\"f{i}\":{{\"U64\":{i}}}"))
+        .collect();
+    let json = format!("{{{}}}", entries.join(","));
+    let err = serde_json::from_str::<SerializeRecord<'_>>(&json).unwrap_err();
+    assert!(err.to_string().contains("invalid length"), "{err}");
+
+    let fits = format!("{{{}}}", entries[..MAX_FIELDS].join(","));
+    assert!(serde_json::from_str::<SerializeRecord<'_>>(&fits).is_ok());
+}
+
+#[test]
+#[should_panic(expected = "more than MAX_FIELDS")]
+fn collecting_too_many_values_panics() {
+    let names: Vec<String> = (0..=MAX_FIELDS).map(|i| format!("f{i}")).collect();
+    let _: SerializeRecord<'_> = names
+        .iter()
+        .map(|name| (name.as_str().into(), SerializeValue::Bool(true)))
+        .collect();
+}
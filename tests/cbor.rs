@@ -0,0 +1,192 @@
+#![cfg(feature = "cbor")]
+
+use std::collections::BTreeMap;
+
+use tracing_serde_structured::{
+    cbor::{from_cbor_slice, to_cbor, Error, Representation, SequenceReader, SequenceWriter},
+    CowString, SerializeFieldSet, SerializeLevel, SerializeMetadata, SerializeRecord,
+    SerializeValue,
+};
+
+fn metadata() -> SerializeMetadata<'static> {
+    SerializeMetadata {
+        name: CowString::Borrowed("event src/main.rs:7"),
+        target: CowString::Borrowed("my_crate"),
+        level: SerializeLevel::Info,
+        module_path: Some(CowString::Borrowed("my_crate")),
+        file: Some(CowString::Borrowed("src/main.rs")),
+        line: Some(7),
+        fields: SerializeFieldSet::De(vec![CowString::Borrowed("message")]),
+        is_span: false,
+        is_event: true,
+    }
+}
+
+fn map_keys(bytes: &[u8]) -> Vec<String> {
+    let value: ciborium::Value = ciborium::from_reader(bytes).unwrap();
+    value
+        .as_map()
+        .unwrap()
+        .iter()
+        .map(|(k, _)| k.as_text().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+#[cfg(not(any(feature = "camel-case", feature = "strip-locations")))]
+fn struct_keys_are_sorted_canonically() {
+    let bytes = to_cbor(&metadata(), Representation::Named).unwrap();
+
+    // Shorter keys first, then bytewise order, regardless of declaration order.
+    assert_eq!(
+        map_keys(&bytes),
+        [
+            "file",
+            "line",
+            "name",
+            "level",
+            "fields",
+            "target",
+            "is_span",
+            "is_event",
+            "module_path",
+        ]
+    );
+}
+
+#[test]
+fn field_map_keys_are_sorted_canonically() {
+    let mut fields = BTreeMap::new();
+    fields.insert(CowString::Borrowed("aa"), SerializeValue::Bool(true));
+    fields.insert(CowString::Borrowed("b"), SerializeValue::Bool(false));
+    let record: SerializeRecord<'_> = SerializeRecord::De(fields);
+
+    // `BTreeMap` iterates "aa" before "b", canonical CBOR wants the reverse.
+    assert_eq!(
+        map_keys(&to_cbor(&record, Representation::Named).unwrap()),
+        ["b", "aa"]
+    );
+}
+
+#[test]
+fn integers_use_shortest_form() {
+    // {"U64": 1}
+    assert_eq!(
+        to_cbor(&SerializeValue::U64(1), Representation::Named).unwrap(),
+        [0xa1, 0x63, b'U', b'6', b'4', 0x01]
+    );
+    // {"I64": -500}
+    assert_eq!(
+        to_cbor(&SerializeValue::I64(-500), Representation::Named).unwrap(),
+        [0xa1, 0x63, b'I', b'6', b'4', 0x39, 0x01, 0xf3]
+    );
+}
+
+#[test]
+fn encoding_is_deterministic_and_round_trips() {
+    let first = to_cbor(&metadata(), Representation::Named).unwrap();
+    let decoded: SerializeMetadata<'_> = from_cbor_slice(&first).unwrap();
+    assert_eq!(to_cbor(&decoded, Representation::Named).unwrap(), first);
+}
+
+#[test]
+fn non_canonical_input_is_accepted() {
+    let mut bytes = Vec::new();
+    ciborium::into_writer(&metadata(), &mut bytes).unwrap();
+    let decoded: SerializeMetadata<'_> = from_cbor_slice(&bytes).unwrap();
+    assert_eq!(decoded.target.as_str(), "my_crate");
+    #[cfg(not(feature = "strip-locations"))]
+    assert_eq!(decoded.line, Some(7));
+}
+
+#[test]
+fn floats_use_shortest_lossless_form() {
+    // {"F64": 1.5} fits in a half-precision float.
+    assert_eq!(
+        to_cbor(&SerializeValue::F64(1.5), Representation::Named).unwrap(),
+        [0xa1, 0x63, b'F', b'6', b'4', 0xf9, 0x3e, 0x00]
+    );
+}
+
+#[test]
+fn sequences_are_items_back_to_back() {
+    let mut writer = SequenceWriter::new(Vec::new());
+    writer.write(&SerializeValue::U64(1)).unwrap();
+    writer.write(&metadata()).unwrap();
+    writer.write(&SerializeValue::U64(2)).unwrap();
+    let bytes = writer.into_inner();
+
+    // No array around the items: the first byte starts the first item.
+    let first = to_cbor(&SerializeValue::U64(1), Representation::Named).unwrap();
+    assert_eq!(bytes[..first.len()], first);
+
+    let values: Vec<Result<SerializeValue<'static>, Error>> =
+        SequenceReader::new(&bytes[..]).collect();
+    assert_eq!(values.len(), 3);
+    assert_eq!(values[0].as_ref().unwrap(), &SerializeValue::U64(1));
+    // An item of another shape fails on its own.
+    assert!(matches!(values[1], Err(Error::Deserialize(_))));
+    assert_eq!(values[2].as_ref().unwrap(), &SerializeValue::U64(2));
+
+    let decoded: Vec<SerializeMetadata<'static>> = SequenceReader::new(&bytes[first.len()..])
+        .take(1)
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        to_cbor(&decoded[0], Representation::Named).unwrap(),
+        to_cbor(&metadata(), Representation::Named).unwrap()
+    );
+}
+
+#[test]
+fn truncated_sequences_end_with_an_error() {
+    let mut writer = SequenceWriter::new(Vec::new());
+    writer.write(&metadata()).unwrap();
+    writer.write(&metadata()).unwrap();
+    let mut bytes = writer.into_inner();
+    bytes.pop();
+
+    let mut reader = SequenceReader::<_, SerializeMetadata<'static>>::new(&bytes[..]);
+    assert!(reader.next().unwrap().is_ok());
+    assert!(matches!(reader.next(), Some(Err(Error::Decode(_)))));
+    assert!(reader.next().is_none());
+    assert!(SequenceReader::<_, SerializeValue<'static>>::new(&[][..])
+        .next()
+        .is_none());
+}
+
+#[test]
+fn compact_structs_are_arrays() {
+    let named = to_cbor(&metadata(), Representation::Named).unwrap();
+    let compact = to_cbor(&metadata(), Representation::Compact).unwrap();
+    let value: ciborium::Value = ciborium::from_reader(&compact[..]).unwrap();
+    assert!(value.is_array());
+    assert!(compact.len() < named.len());
+
+    // Real maps keep their keys, in canonical order.
+    let mut fields = BTreeMap::new();
+    fields.insert(CowString::Borrowed("aa"), SerializeValue::Bool(true));
+    fields.insert(CowString::Borrowed("b"), SerializeValue::Bool(false));
+    let record: SerializeRecord<'_> = SerializeRecord::De(fields);
+    assert_eq!(
+        map_keys(&to_cbor(&record, Representation::Compact).unwrap()),
+        ["b", "aa"]
+    );
+}
+
+#[test]
+fn both_representations_decode() {
+    for repr in [Representation::Named, Representation::Compact] {
+        let mut writer = SequenceWriter::with_representation(Vec::new(), repr);
+        writer.write(&metadata()).unwrap();
+        let bytes = writer.into_inner();
+        assert_eq!(bytes, to_cbor(&metadata(), repr).unwrap());
+
+        let decoded: SerializeMetadata<'static> = from_cbor_slice(&bytes).unwrap();
+        assert_eq!(
+            to_cbor(&decoded, Representation::Named).unwrap(),
+            to_cbor(&metadata(), Representation::Named).unwrap(),
+            "{repr:?}"
+        );
+    }
+}
@@ -0,0 +1,192 @@
+#![cfg(feature = "checksum")]
+
+use tracing_serde_structured::checksum::{Checksum, Error};
+
+/// Encodes `msg` into a frame ending with `checksum`, including its
+/// terminating zero byte.
+fn frame(checksum: Checksum, msg: &str) -> Vec<u8> {
+    let mut buf = [0u8; 64];
+    checksum.to_slice_cobs(msg, &mut buf).unwrap().to_vec()
+}
+
+/// Decodes a frame, without its terminating zero byte.
+fn open(checksum: Checksum, frame: &[u8]) -> Result<String, Error> {
+    let mut frame = frame[..frame.len() - 1].to_vec();
+    checksum
+        .from_bytes_cobs::<&str>(&mut frame)
+        .map(str::to_owned)
+}
+
+/// Flips `mask` in byte `at` of the COBS decoded frame, and encodes it again.
+fn corrupt(frame: &[u8], at: usize, mask: u8) -> Vec<u8> {
+    let mut decoded = frame[..frame.len() - 1].to_vec();
+    let len = cobs::decode_in_place(&mut decoded).unwrap();
+    decoded.truncate(len);
+    decoded[at] ^= mask;
+    let mut encoded = [0u8; 64];
+    let len = cobs::encode(&decoded, &mut encoded);
+    [&encoded[..len], &[0]].concat()
+}
+
+#[test]
+fn checksums_match_their_catalog() {
+    assert_eq!(Checksum::Crc16.checksum(b"123456789"), 0x906e);
+    assert_eq!(Checksum::Crc32.checksum(b"123456789"), 0xcbf4_3926);
+}
+
+#[test]
+fn checked_frames_round_trip() {
+    for checksum in [Checksum::Crc16, Checksum::Crc32] {
+        let frame = frame(checksum, "abc");
+        // Message, CRC, and COBS overhead.
+        assert_eq!(frame.len(), 4 + checksum.size() + 2);
+        assert_eq!(frame.iter().position(|b| *b == 0), Some(frame.len() - 1));
+        assert_eq!(open(checksum, &frame).unwrap(), "abc");
+
+        let mut decoded = frame[..frame.len() - 1].to_vec();
+        let len = cobs::decode_in_place(&mut decoded).unwrap();
+        let (msg, crc) = decoded[..len].split_at(4);
+        assert_eq!(
+            crc,
+            &checksum.checksum(msg).to_le_bytes()[..checksum.size()]
+        );
+    }
+}
+
+#[test]
+fn flipped_bits_are_found() {
+    for checksum in [Checksum::Crc16, Checksum::Crc32] {
+        let frame = frame(checksum, "abc");
+        for at in 0..4 + checksum.size() {
+            for bit in 0..8 {
+                let corrupted = corrupt(&frame, at, 1 << bit);
+                assert!(
+                    matches!(open(checksum, &corrupted), Err(Error::Corrupt)),
+                    "{checksum:?}, byte {at}, bit {bit}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn checksums_must_agree() {
+    let frame = frame(Checksum::Crc16, "abcd");
+    assert!(matches!(open(Checksum::Crc32, &frame), Err(Error::Corrupt)));
+}
+
+#[test]
+fn short_frames_are_malformed() {
+    let mut buf = [0u8; 8];
+    let frame = postcard::to_slice_cobs(&1u8, &mut buf).unwrap();
+    assert!(matches!(open(Checksum::Crc16, frame), Err(Error::Frame)));
+}
+
+#[cfg(feature = "embedded-io")]
+#[test]
+fn framed_writers_write_checked_frames() {
+    use tracing_serde_structured::embedded_io::FramedWriter;
+
+    let mut writer = FramedWriter::<_, 64>::new(Vec::new());
+    writer.write_checked("a", Checksum::Crc32).unwrap();
+    writer.write_checked("b", Checksum::Crc16).unwrap();
+    assert_eq!(writer.stats().sent, 2);
+    assert_eq!(
+        *writer.get_ref(),
+        [frame(Checksum::Crc32, "a"), frame(Checksum::Crc16, "b")].concat()
+    );
+}
+
+#[cfg(feature = "host")]
+mod host {
+    use std::io::Cursor;
+
+    use tracing_serde_structured::{
+        host::{Decoder, Error as HostError},
+        time::TickRate,
+        wire::{
+            delta::DeltaEncoder,
+            handshake::{Capabilities, Hello},
+            TracingWire,
+        },
+        SerializeId,
+    };
+
+    use super::*;
+
+    fn enter(id: u64) -> TracingWire<'static> {
+        TracingWire::Enter(SerializeId {
+            id: id.try_into().unwrap(),
+        })
+    }
+
+    /// Delta encoded frames of `msgs`, ending with a CRC-32.
+    fn stream(msgs: Vec<TracingWire<'static>>) -> Vec<Vec<u8>> {
+        let mut encoder = DeltaEncoder::new();
+        msgs.into_iter()
+            .enumerate()
+            .map(|(i, msg)| {
+                let frame = encoder.encode(i as u64, msg.into());
+                let mut buf = [0u8; 128];
+                Checksum::Crc32
+                    .to_slice_cobs(&frame, &mut buf)
+                    .unwrap()
+                    .to_vec()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn decoders_count_and_skip_corrupt_frames() {
+        let mut frames = stream(vec![enter(1), enter(1), enter(1)]);
+        frames[1] = corrupt(&frames[1], 1, 0x10);
+
+        let mut decoder = Decoder::new(TickRate::MICROS);
+        decoder.set_checksum(Checksum::Crc32);
+        let mut messages = decoder.read(Cursor::new(frames.concat()));
+        assert_eq!(messages.next().unwrap().unwrap().msg, enter(1));
+        // The corrupt frame was not decoded, so the next one's timestamp is
+        // relative to the first one's.
+        assert_eq!(messages.next().unwrap().unwrap().ticks, 1);
+        assert!(messages.next().is_none());
+        assert_eq!(messages.decoder().corrupt_frames(), 1);
+    }
+
+    #[test]
+    fn decoders_report_corrupt_frames() {
+        let frames = stream(vec![enter(1)]);
+        let mut frame = corrupt(&frames[0], 0, 0x01);
+        frame.pop();
+
+        let mut decoder = Decoder::new(TickRate::MICROS);
+        decoder.set_checksum(Checksum::Crc32);
+        let err = decoder.decode_frame(&mut frame).unwrap_err();
+        assert!(matches!(err, HostError::Checksum(Error::Corrupt)));
+        assert_eq!(
+            err.to_string(),
+            "failed to check frame: frame's CRC is wrong"
+        );
+        assert_eq!(decoder.corrupt_frames(), 1);
+    }
+
+    #[test]
+    fn hellos_say_which_checksum_is_used() {
+        let hello = Hello::new(Capabilities::DELTA | Capabilities::CRC32);
+        let frames = stream(vec![TracingWire::Hello(hello), enter(1)]);
+
+        let mut decoder = Decoder::new(TickRate::MICROS);
+        decoder.set_checksum(Checksum::Crc32);
+        assert!(decoder.capabilities().contains(Capabilities::CRC32));
+        assert!(!decoder.capabilities().contains(Capabilities::CRC16));
+        let mut messages = decoder.read(Cursor::new(frames.concat()));
+        assert_eq!(messages.next().unwrap().unwrap().msg, enter(1));
+
+        // Without a checksum set, the decoder reads the hello, ignoring the
+        // CRC after it, and finds it cannot check the rest.
+        let mut messages = Decoder::new(TickRate::MICROS).read(Cursor::new(frames.concat()));
+        assert!(matches!(
+            messages.next(),
+            Some(Err(HostError::Handshake(_)))
+        ));
+    }
+}
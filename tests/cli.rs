@@ -0,0 +1,123 @@
+#![cfg(feature = "cli")]
+
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use tracing_serde_structured::{
+    builder::{SerializeAttributesBuilder, SerializeEventBuilder},
+    framing::{Cobs, Framing, LengthPrefixed},
+    wire::{delta::DeltaEncoder, TracingWire},
+    SerializeId, SerializeLevel,
+};
+
+fn id(id: u64) -> SerializeId {
+    SerializeId {
+        id: id.try_into().unwrap(),
+    }
+}
+
+/// A stream of a span with one event in it, one millisecond apart.
+fn stream(framing: impl Framing) -> Vec<u8> {
+    let msgs: Vec<TracingWire<'static>> = vec![
+        TracingWire::NewSpan {
+            id: id(1),
+            attributes: SerializeAttributesBuilder::new("connect")
+                .target("app::net")
+                .build(),
+        },
+        TracingWire::Enter(id(1)),
+        TracingWire::Event(
+            SerializeEventBuilder::new()
+                .target("app::net")
+                .level(SerializeLevel::Warn)
+                .field("message", "retrying")
+                .field("peer", "10.0.0.1 port 80")
+                .field("attempt", 2u64)
+                .parent(id(1))
+                .build(),
+        ),
+        TracingWire::Exit(id(1)),
+    ];
+    let mut encoder = DeltaEncoder::new();
+    let mut stream = Vec::new();
+    for (i, msg) in msgs.into_iter().enumerate() {
+        let frame = encoder.encode(1000 * (i as u64 + 1), msg.into());
+        let mut buf = [0u8; 256];
+        stream.extend_from_slice(framing.to_slice(&frame, &mut buf).unwrap());
+    }
+    stream
+}
+
+fn decode(args: &[&str]) -> String {
+    decode_stream(args, stream(Cobs))
+}
+
+fn decode_stream(args: &[&str], stream: Vec<u8>) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_tss-decode"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(&stream).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn decodes_as_json_lines() {
+    let out = decode(&["--format", "json"]);
+    let lines: Vec<serde_json::Value> = out
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[2]["since_start_ns"], 3_000_000);
+    assert_eq!(lines[2]["msg"]["Event"]["metadata"]["level"], "WARN");
+    assert_eq!(lines[3]["msg"], serde_json::json!({ "Exit": { "id": 1 } }));
+}
+
+#[test]
+fn decodes_as_logfmt() {
+    let out = decode(&["-f", "logfmt", "-"]);
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(
+        lines[0],
+        "ts=0.001000 kind=new_span span=1 name=connect level=INFO target=app::net"
+    );
+    assert_eq!(
+        lines[2],
+        "ts=0.003000 kind=event level=WARN target=app::net msg=retrying span=1 attempt=2 peer=\"10.0.0.1 port 80\""
+    );
+    assert_eq!(lines[3], "ts=0.004000 kind=exit span=1");
+}
+
+#[test]
+fn decodes_for_people() {
+    let out = decode(&["--rate", "1000", "--color", "never"]);
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0], "    1.000000  INFO new span 1 connect");
+    assert!(lines[1].starts_with("    3.000000  WARN   connect: app::net: retrying "));
+    assert!(lines[1].ends_with(" attempt=2 peer=\"10.0.0.1 port 80\""));
+}
+
+#[test]
+fn decodes_length_prefixed_frames() {
+    let out = decode_stream(&["-l", "-f", "logfmt"], stream(LengthPrefixed::new()));
+    assert_eq!(out.lines().count(), 4);
+    assert_eq!(out, decode(&["-f", "logfmt"]));
+}
+
+#[test]
+fn rejects_unknown_options() {
+    let output = Command::new(env!("CARGO_BIN_EXE_tss-decode"))
+        .arg("--colour")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unknown option `--colour`"));
+}
@@ -0,0 +1,111 @@
+#![cfg(all(feature = "compact_str", not(feature = "arc-str")))]
+//! Counts the heap allocations made when converting an event to owned.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    sync::{Arc, Mutex},
+};
+
+use tracing::info;
+use tracing_core::{
+    span::{Attributes, Id, Record},
+    Event, Metadata, Subscriber,
+};
+use tracing_serde_structured::{
+    AsSerde, CowString, DebugRecord, SerializeEvent, SerializeRecordFields, SerializeValue,
+};
+
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(Cell::get)
+}
+
+/// Whether a copied string is stored within the `CowString` itself.
+fn inline(s: &CowString<'_>) -> bool {
+    let start = s as *const CowString<'_> as usize;
+    matches!(s, CowString::Copied(_))
+        && (start..start + std::mem::size_of_val(s)).contains(&(s.as_ptr() as usize))
+}
+
+#[derive(Default)]
+struct OwnedSubscriber {
+    events: Mutex<Vec<(SerializeEvent<'static>, usize)>>,
+}
+
+impl Subscriber for OwnedSubscriber {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, _: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let before = allocations();
+        let owned = event.as_serde().to_owned();
+        let made = allocations() - before;
+        self.events.lock().unwrap().push((owned, made));
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+#[test]
+fn short_strings_are_not_allocated() {
+    let subscriber = Arc::new(OwnedSubscriber::default());
+    tracing::subscriber::with_default(subscriber.clone(), || {
+        info!(user = "ferris", id = ?(1, 2), path = "/index.html", "logged in");
+    });
+    let (event, made) = std::mem::take(&mut *subscriber.events.lock().unwrap())
+        .pop()
+        .unwrap();
+    let SerializeRecordFields::De(fields) = &event.fields else {
+        panic!("fields were not converted");
+    };
+    assert_eq!(fields.len(), 4);
+    assert!(fields.values().all(|v| match v {
+        SerializeValue::Str(s) | SerializeValue::Debug(DebugRecord::De(s)) => inline(s),
+        _ => false,
+    }));
+
+    // The field names and metadata are `'static`, and all four values fit
+    // inline, so the only allocations are the field map and the field set.
+    // Without `compact_str`, each value would be allocated as well.
+    assert_eq!(made, 2);
+}
+
+#[test]
+fn long_strings_are_still_copied() {
+    let long = "a string that is too long to be stored inline";
+    let owned = CowString::Borrowed(long).to_owned();
+    assert!(matches!(owned, CowString::Copied(_)));
+    assert!(!inline(&owned));
+    assert_eq!(owned.as_str(), long);
+}
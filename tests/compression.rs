@@ -0,0 +1,103 @@
+#![cfg(any(feature = "deflate", feature = "lz4", feature = "zstd"))]
+
+use std::num::NonZeroU64;
+
+use tracing_serde_structured::{
+    compression::{CompressedBatch, Compression, Error},
+    wire::TracingWire,
+    SerializeId,
+};
+
+fn messages() -> Vec<TracingWire<'static>> {
+    (0..64)
+        .flat_map(|id| {
+            let id = SerializeId {
+                id: NonZeroU64::new(1 + id % 4).unwrap(),
+            };
+            [TracingWire::Enter(id.clone()), TracingWire::Exit(id)]
+        })
+        .collect()
+}
+
+#[test]
+fn round_trip_every_supported_algorithm() {
+    let messages = messages();
+    let plain = postcard::to_allocvec(&messages).unwrap();
+
+    for &compression in Compression::supported() {
+        let batch = CompressedBatch::new(compression, &messages).unwrap();
+        assert_eq!(batch.compression, compression);
+        assert_eq!(batch.len as usize, plain.len());
+        if compression != Compression::None {
+            assert!(
+                batch.data.len() < plain.len(),
+                "{compression:?} did not compress"
+            );
+        }
+
+        let frame = postcard::to_allocvec(&batch).unwrap();
+        let batch: CompressedBatch = postcard::from_bytes(&frame).unwrap();
+        let raw = batch.decompress().unwrap();
+        assert_eq!(raw, plain);
+
+        let decoded = CompressedBatch::messages(&raw).unwrap();
+        assert_eq!(format!("{decoded:?}"), format!("{messages:?}"));
+    }
+}
+
+#[test]
+fn negotiate_prefers_this_build_and_falls_back_to_none() {
+    let best = Compression::supported()[0];
+    assert_eq!(Compression::negotiate(Compression::supported()), best);
+    assert_eq!(Compression::negotiate(&[Compression::None, best]), best);
+    assert_eq!(Compression::negotiate(&[]), Compression::None);
+    assert!(Compression::None.is_supported());
+}
+
+#[test]
+fn declared_length_is_checked() {
+    let compression = Compression::supported()[0];
+    let mut batch = CompressedBatch::new(compression, &messages()).unwrap();
+
+    assert!(matches!(
+        batch.decompress_with_limit(batch.len as usize - 1),
+        Err(Error::TooLarge(_))
+    ));
+
+    batch.len += 1;
+    assert!(batch.decompress().is_err());
+}
+
+#[test]
+fn unsupported_algorithms_are_reported() {
+    let missing = [Compression::Deflate, Compression::Lz4, Compression::Zstd]
+        .into_iter()
+        .find(|c| !c.is_supported());
+    if let Some(missing) = missing {
+        let batch = CompressedBatch {
+            compression: missing,
+            len: 0,
+            data: Vec::new(),
+        };
+        assert!(matches!(batch.decompress(), Err(Error::Unsupported(c)) if c == missing));
+    }
+}
+
+#[test]
+fn algorithms_keep_their_discriminants() {
+    let algorithms = [
+        (Compression::None, "None"),
+        (Compression::Deflate, "Deflate"),
+        (Compression::Lz4, "Lz4"),
+        (Compression::Zstd, "Zstd"),
+    ];
+    for (index, (compression, name)) in algorithms.into_iter().enumerate() {
+        assert_eq!(compression as usize, index);
+        assert_eq!(postcard::to_allocvec(&compression).unwrap(), [index as u8]);
+        assert_eq!(
+            postcard::from_bytes::<Compression>(&[index as u8]).unwrap(),
+            compression
+        );
+        assert_eq!(serde_json::to_value(compression).unwrap(), name);
+    }
+}
@@ -0,0 +1,347 @@
+#![cfg(feature = "crypto")]
+
+use sha2::Sha256;
+use tracing_serde_structured::{
+    crypto::{Error, Hmac, Signer, Verifier},
+    SerializeLevel,
+};
+
+type Mac = Hmac<Sha256>;
+
+const KEY: &[u8] = b"key";
+
+fn sign(signer: &Signer<Mac>, msg: &str) -> Vec<u8> {
+    let mut buf = [0u8; 128];
+    signer.to_slice_cobs(msg, &mut buf).unwrap().to_vec()
+}
+
+/// Verifies a frame, without its terminating zero byte.
+fn verify(verifier: &Verifier<Mac>, frame: &[u8]) -> Result<String, Error> {
+    let mut frame = frame[..frame.len() - 1].to_vec();
+    verifier
+        .from_bytes_cobs::<&str>(&mut frame)
+        .map(str::to_owned)
+}
+
+#[test]
+fn signed_frames_verify() {
+    let signer = Signer::<Mac>::new(KEY).unwrap();
+    let frame = sign(&signer, "hello");
+    // Message, tag, and COBS overhead.
+    assert_eq!(frame.len(), 6 + 32 + 2);
+
+    let verifier = Verifier::<Mac>::new(KEY).unwrap();
+    assert_eq!(verify(&verifier, &frame).unwrap(), "hello");
+    // Verifying does not change the verifier.
+    assert_eq!(verify(&verifier, &frame).unwrap(), "hello");
+}
+
+#[test]
+fn other_keys_are_rejected() {
+    let frame = sign(&Signer::new(b"other key").unwrap(), "hello");
+    let verifier = Verifier::<Mac>::new(KEY).unwrap();
+    assert!(matches!(verify(&verifier, &frame), Err(Error::Forged)));
+}
+
+#[test]
+fn altered_frames_are_rejected() {
+    let mut frame = sign(&Signer::new(KEY).unwrap(), "hello");
+    let at = frame.iter().position(|b| *b == b'h').unwrap();
+    frame[at] = b'j';
+
+    let verifier = Verifier::<Mac>::new(KEY).unwrap();
+    let err = verify(&verifier, &frame).unwrap_err();
+    assert!(matches!(err, Error::Forged));
+    assert_eq!(err.to_string(), "frame's authentication tag is wrong");
+}
+
+#[test]
+fn short_frames_are_malformed() {
+    let verifier = Verifier::<Mac>::new(KEY).unwrap();
+    assert!(matches!(verifier.verify(&[1; 31]), Err(Error::Frame)));
+    assert!(matches!(verifier.open(&mut [0x05, 1]), Err(Error::Frame)));
+}
+
+#[test]
+fn frames_that_do_not_fit_are_not_encoded() {
+    let signer = Signer::<Mac>::new(KEY).unwrap();
+    let mut buf = [0u8; 32];
+    assert_eq!(
+        signer.to_slice_cobs(&SerializeLevel::Warn, &mut buf),
+        Err(postcard::Error::SerializeBufferFull)
+    );
+}
+
+#[cfg(feature = "embedded-io")]
+#[test]
+fn framed_writers_write_signed_frames() {
+    use tracing_serde_structured::embedded_io::FramedWriter;
+
+    let signer = Signer::<Mac>::new(KEY).unwrap();
+    let mut writer = FramedWriter::<_, 64>::new(Vec::new());
+    writer.write_signed("a", &signer).unwrap();
+    writer
+        .write_with(|buf| signer.to_slice_cobs("b", buf))
+        .unwrap();
+    assert_eq!(writer.stats().sent, 2);
+    assert_eq!(
+        *writer.get_ref(),
+        [sign(&signer, "a"), sign(&signer, "b")].concat()
+    );
+}
+
+#[cfg(feature = "host")]
+mod host {
+    use std::io::Cursor;
+
+    use tracing_serde_structured::{
+        host::{Decoder, Error as HostError},
+        time::TickRate,
+        wire::{
+            delta::{DeltaEncoder, DeltaFrame},
+            TracingWire,
+        },
+        Live, SerializeId,
+    };
+
+    use super::*;
+
+    fn id(id: u64) -> SerializeId {
+        SerializeId {
+            id: id.try_into().unwrap(),
+        }
+    }
+
+    fn enter(id_: u64) -> TracingWire<'static> {
+        TracingWire::Enter(id(id_))
+    }
+
+    /// Delta encoded frames entering spans 1 to `count`.
+    fn frames(count: u64) -> Vec<DeltaFrame<'static, Live>> {
+        let mut encoder = DeltaEncoder::new();
+        (1..=count)
+            .map(|i| encoder.encode(i, TracingWire::<Live>::Enter(id(i))))
+            .collect()
+    }
+
+    #[test]
+    fn decoders_reject_forged_frames() {
+        let signer = Signer::<Mac>::new(KEY).unwrap();
+        let forger = Signer::<Mac>::new(b"guess").unwrap();
+        let mut stream = Vec::new();
+        for (i, frame) in frames(3).iter().enumerate() {
+            let mut buf = [0u8; 128];
+            let signer = if i == 1 { &forger } else { &signer };
+            stream.extend_from_slice(signer.to_slice_cobs(frame, &mut buf).unwrap());
+        }
+
+        let mut decoder = Decoder::new(TickRate::MICROS);
+        decoder.set_authentication(Verifier::<Mac>::new(KEY).unwrap());
+        let mut messages = decoder.read(Cursor::new(stream));
+
+        assert_eq!(messages.next().unwrap().unwrap().msg, enter(1));
+        let err = messages.next().unwrap().unwrap_err();
+        assert!(matches!(err, HostError::Crypto(Error::Forged)));
+        // The forged frame was not decoded, so the next one's id is relative
+        // to the first one's.
+        assert_eq!(messages.next().unwrap().unwrap().msg, enter(2));
+        assert!(messages.next().is_none());
+    }
+
+    #[cfg(feature = "audit")]
+    #[test]
+    fn decoders_find_replayed_frames() {
+        use tracing_serde_structured::audit::{self, Chain};
+
+        let signer = Signer::<Mac>::new(KEY).unwrap();
+        let mut chain = Chain::<Sha256>::new(b"seed");
+        let mut encoded = Vec::new();
+        for frame in frames(2) {
+            let mut buf = [0u8; 128];
+            let frame = signer.to_slice_cobs_chained(&frame, &mut chain, &mut buf);
+            encoded.push(frame.unwrap().to_vec());
+        }
+        // Genuine frames, sent again.
+        let stream = [&encoded[..], &encoded[..]].concat().concat();
+
+        let mut decoder = Decoder::new(TickRate::MICROS);
+        decoder.set_audit(audit::Verifier::<Sha256>::new(b"seed"));
+        decoder.set_authentication(Verifier::<Mac>::new(KEY).unwrap());
+        let results: Vec<_> = decoder.read(Cursor::new(stream)).collect();
+
+        assert_eq!(results[0].as_ref().unwrap().msg, enter(1));
+        assert_eq!(results[1].as_ref().unwrap().msg, enter(2));
+        assert!(matches!(
+            results[2],
+            Err(HostError::Audit(audit::Error::Broken))
+        ));
+    }
+}
+
+#[cfg(feature = "alloc")]
+mod fields {
+    use std::collections::BTreeMap;
+
+    use chacha20poly1305::{
+        aead::{KeyInit, OsRng},
+        ChaCha20Poly1305, Key,
+    };
+    use tracing_serde_structured::{
+        builder::SerializeEventBuilder,
+        crypto::fields::{is_encrypted, Error, FieldDecryptor, FieldEncryptor},
+        wire::TracingWire,
+        CowString, DebugRecord, SerializeEvent, SerializeId, SerializeRecord,
+        SerializeRecordFields, SerializeValue,
+    };
+
+    fn key(byte: u8) -> Key {
+        [byte; 32].into()
+    }
+
+    fn encryptor(patterns: &[&str]) -> FieldEncryptor<ChaCha20Poly1305, OsRng> {
+        let mut encryptor = FieldEncryptor::new(ChaCha20Poly1305::new(&key(1)), OsRng);
+        for pattern in patterns {
+            encryptor.add(pattern);
+        }
+        encryptor
+    }
+
+    fn decryptor() -> FieldDecryptor<ChaCha20Poly1305> {
+        FieldDecryptor::new(ChaCha20Poly1305::new(&key(1)))
+    }
+
+    fn event() -> SerializeEvent<'static> {
+        SerializeEventBuilder::new()
+            .field("user.email", "ferris@example.com")
+            .field("user.id", 42u64)
+            .field("status", 200u64)
+            .build()
+    }
+
+    fn get<'e>(
+        fields: &'e SerializeRecordFields<'static>,
+        name: &str,
+    ) -> &'e SerializeValue<'static> {
+        let SerializeRecordFields::De(map) = fields else {
+            panic!("fields are deserialized fields");
+        };
+        map.iter().find(|(k, _)| k.as_str() == name).unwrap().1
+    }
+
+    #[test]
+    fn values_round_trip() {
+        let mut encryptor = encryptor(&[]);
+        let decryptor = decryptor();
+        let values = [
+            SerializeValue::from("secret"),
+            SerializeValue::U64(u64::MAX),
+            SerializeValue::I64(-7),
+            SerializeValue::F64(1.5),
+            SerializeValue::Bool(true),
+            SerializeValue::Debug(DebugRecord::De("Some(1)".into())),
+            SerializeValue::Unknown,
+        ];
+        for value in values {
+            let encrypted = encryptor.encrypt_value("field", &value).unwrap();
+            assert!(is_encrypted(&encrypted));
+            let decrypted = decryptor.decrypt_value("field", &encrypted).unwrap();
+            assert_eq!(decrypted, Some(value));
+        }
+    }
+
+    #[test]
+    fn only_matching_fields_are_encrypted() {
+        let mut encryptor = encryptor(&["user.*"]);
+        assert!(encryptor.matches("user.email"));
+        assert!(!encryptor.matches("status"));
+
+        let mut event = event();
+        assert_eq!(encryptor.encrypt_fields(&mut event.fields).unwrap(), 2);
+        assert!(is_encrypted(get(&event.fields, "user.email")));
+        assert!(is_encrypted(get(&event.fields, "user.id")));
+        assert_eq!(*get(&event.fields, "status"), SerializeValue::U64(200));
+
+        // Encrypted values are not encrypted again.
+        assert_eq!(encryptor.encrypt_fields(&mut event.fields).unwrap(), 0);
+
+        assert_eq!(decryptor().decrypt_fields(&mut event.fields).unwrap(), 2);
+        assert_eq!(event, self::event());
+    }
+
+    #[test]
+    fn nonces_are_not_reused() {
+        let mut encryptor = encryptor(&[]);
+        let value = SerializeValue::from("secret");
+        let a = encryptor.encrypt_value("field", &value).unwrap();
+        let b = encryptor.encrypt_value("field", &value).unwrap();
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn ciphertexts_are_bound_to_their_field() {
+        let mut encryptor = encryptor(&[]);
+        let encrypted = encryptor
+            .encrypt_value("user.email", &SerializeValue::from("secret"))
+            .unwrap();
+        assert_eq!(
+            decryptor().decrypt_value("status", &encrypted),
+            Err(Error::Decrypt)
+        );
+
+        let other = FieldDecryptor::new(ChaCha20Poly1305::new(&key(2)));
+        assert_eq!(
+            other.decrypt_value("user.email", &encrypted),
+            Err(Error::Decrypt)
+        );
+    }
+
+    #[test]
+    fn malformed_values_are_rejected() {
+        let decryptor = decryptor();
+        for value in ["enc:???", "enc:", "enc:AAAA"] {
+            let err = decryptor
+                .decrypt_value("field", &SerializeValue::from(value))
+                .unwrap_err();
+            assert_eq!(err, Error::Malformed, "{value}");
+        }
+        assert_eq!(
+            decryptor.decrypt_value("field", &SerializeValue::from("plain")),
+            Ok(None)
+        );
+        assert_eq!(
+            decryptor.decrypt_value("field", &SerializeValue::U64(1)),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn messages_are_encrypted() {
+        let mut encryptor = encryptor(&["user.*"]);
+        let decryptor = decryptor();
+
+        let mut event = TracingWire::Event(event());
+        assert_eq!(encryptor.encrypt_message(&mut event).unwrap(), 2);
+
+        let mut values = BTreeMap::new();
+        values.insert(CowString::Borrowed("user.name"), "ferris".into());
+        let mut record: TracingWire<'_> = TracingWire::Record {
+            span: SerializeId {
+                id: 1.try_into().unwrap(),
+            },
+            values: SerializeRecord::De(values),
+        };
+        assert_eq!(encryptor.encrypt_message(&mut record).unwrap(), 1);
+
+        let mut exit: TracingWire<'_> = TracingWire::Exit(SerializeId {
+            id: 1.try_into().unwrap(),
+        });
+        assert_eq!(encryptor.encrypt_message(&mut exit).unwrap(), 0);
+
+        // Encrypted messages pass through serialization unchanged.
+        let bytes = postcard::to_allocvec(&event).unwrap();
+        let mut event: TracingWire<'_> = postcard::from_bytes(&bytes).unwrap();
+        assert_eq!(decryptor.decrypt_message(&mut event).unwrap(), 2);
+        assert_eq!(event, TracingWire::Event(self::event()));
+        assert_eq!(decryptor.decrypt_message(&mut record).unwrap(), 1);
+    }
+}
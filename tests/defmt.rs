@@ -0,0 +1,19 @@
+#![cfg(feature = "defmt")]
+
+use tracing_serde_structured::{
+    wire::{SerializeMetadataId, StringId, TracingWire},
+    CowString, SerializeId, SerializeLevel, SerializeValue,
+};
+
+fn assert_format<T: defmt::Format + ?Sized>() {}
+
+#[test]
+fn wire_types_implement_format() {
+    assert_format::<SerializeLevel>();
+    assert_format::<SerializeId>();
+    assert_format::<SerializeValue<'static>>();
+    assert_format::<CowString<'static>>();
+    assert_format::<SerializeMetadataId>();
+    assert_format::<StringId>();
+    assert_format::<TracingWire<'static>>();
+}
@@ -0,0 +1,80 @@
+#![cfg(all(feature = "host", feature = "postcard-schema"))]
+
+use postcard_schema::{schema::owned::OwnedNamedType, Schema};
+use tracing_serde_structured::{
+    host::Decoder,
+    time::TickRate,
+    wire::{
+        delta::{DeltaEncoder, DeltaFrame},
+        description::{Description, Encoding, SchemaBlob},
+        handshake::{Capabilities, Hello},
+        TracingWire,
+    },
+};
+
+#[test]
+fn the_schema_comes_back_out() {
+    let description = Description::new().with_schema(SchemaBlob::wire());
+    assert_eq!(
+        description.crate_version.as_str(),
+        env!("CARGO_PKG_VERSION")
+    );
+    assert_eq!(description.encoding, Encoding::current());
+
+    let msg: TracingWire<'_> = description.into();
+    let bytes = postcard::to_allocvec(&msg).unwrap();
+    let decoded: TracingWire<'_> = postcard::from_bytes(&bytes).unwrap();
+    assert_eq!(decoded, msg);
+    assert!(decoded.is_borrowed());
+    let TracingWire::Description(description) = decoded else {
+        panic!("decoded a description");
+    };
+    let schema = description.schema().unwrap().unwrap();
+    assert_eq!(schema, OwnedNamedType::from(<TracingWire<'_>>::SCHEMA));
+    assert!(schema.to_pseudocode().contains("Description"));
+
+    // Human-readable formats copy the schema.
+    let json = serde_json::to_string(&msg).unwrap();
+    let from_json: TracingWire<'_> = serde_json::from_str(&json).unwrap();
+    assert_eq!(from_json, msg);
+
+    // Without `alloc`, the schema is written into a buffer.
+    let mut buf = [0; 8192];
+    let blob = SchemaBlob::wire_in(&mut buf).unwrap();
+    assert_eq!(blob, SchemaBlob::wire());
+    assert!(SchemaBlob::wire_in(&mut [0; 16]).is_err());
+}
+
+#[test]
+fn options_are_named() {
+    let encoding = Encoding::CAMEL_CASE | Encoding::STRIP_LOCATIONS | Encoding::from_bits(1 << 31);
+    assert_eq!(
+        encoding.to_string(),
+        "camel-case | strip-locations | 0x80000000"
+    );
+    assert_eq!(format!("{:?}", Encoding::empty()), "Encoding(0x0)");
+    assert!(encoding.contains(Encoding::CAMEL_CASE));
+    assert_eq!(
+        encoding.difference(Encoding::from_bits(1 << 31)).bits(),
+        0b110
+    );
+}
+
+#[test]
+fn decoders_keep_the_description() {
+    let mut encoder = DeltaEncoder::new();
+    let mut decoder = Decoder::new(TickRate::hz(1_000));
+    assert!(decoder.description().is_none());
+    let description = Description::new().with_schema(SchemaBlob::wire());
+    let msgs: [TracingWire<'_>; 2] = [
+        Hello::new(Capabilities::DELTA).into(),
+        description.clone().into(),
+    ];
+    for msg in msgs {
+        let bytes = postcard::to_allocvec(&encoder.encode(0, msg.into())).unwrap();
+        let frame: DeltaFrame<'_> = postcard::from_bytes(&bytes).unwrap();
+        assert!(decoder.decode(frame).unwrap().is_none());
+    }
+    assert_eq!(decoder.description(), Some(&description));
+    assert!(decoder.description().unwrap().is_owned());
+}
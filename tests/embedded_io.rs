@@ -0,0 +1,102 @@
+#![cfg(feature = "embedded-io")]
+
+use tracing_serde_structured::{
+    embedded_io::{Error, FramedWriter},
+    wire::SerializePipelineStats,
+    SerializeLevel,
+};
+
+#[test]
+fn frames_decode_back() {
+    let mut writer = FramedWriter::<_, 16>::new(Vec::new());
+    for level in [SerializeLevel::Trace, SerializeLevel::Error] {
+        writer.write(&level).unwrap();
+    }
+    writer.write("hi").unwrap();
+    let mut out = writer.into_inner();
+
+    let mut frames = out.split_mut(|b| *b == 0);
+    let mut levels = Vec::new();
+    for frame in frames.by_ref().take(2) {
+        levels.push(postcard::from_bytes_cobs::<SerializeLevel>(frame).unwrap());
+    }
+    assert_eq!(levels, [SerializeLevel::Trace, SerializeLevel::Error]);
+    let text: &str = postcard::from_bytes_cobs(frames.next().unwrap()).unwrap();
+    assert_eq!(text, "hi");
+    assert_eq!(frames.next(), Some(&mut [][..]));
+}
+
+#[test]
+fn oversized_messages_write_nothing() {
+    let mut writer = FramedWriter::<_, 4>::new(Vec::new());
+    let err = writer.write("too long for four bytes").unwrap_err();
+    assert!(matches!(
+        err,
+        Error::Encode(postcard::Error::SerializeBufferFull)
+    ));
+    assert!(writer.get_ref().is_empty());
+}
+
+#[test]
+fn counts_sent_and_lost_messages() {
+    let mut out = [0u8; 6];
+    let mut writer = FramedWriter::<_, 8>::new(&mut out[..]);
+    writer.write(&SerializeLevel::Warn).unwrap();
+    writer.write("too long for eight bytes").unwrap_err();
+    writer.write("fits").unwrap_err(); // but not in what is left of `out`
+    assert_eq!(
+        writer.stats(),
+        SerializePipelineStats {
+            sent: 1,
+            dropped: 1,
+            overflows: 1,
+            errors: 0,
+        }
+    );
+    assert_eq!(writer.stats().lost(), 2);
+
+    writer.reset_stats();
+    assert_eq!(writer.stats(), SerializePipelineStats::default());
+}
+
+#[test]
+fn stats_are_due_every_interval() {
+    let mut writer = FramedWriter::<_, 16>::new(Vec::new());
+    writer.write(&SerializeLevel::Info).unwrap();
+    assert_eq!(writer.stats_due(), None);
+
+    writer.set_stats_interval(Some(2));
+    writer.write(&SerializeLevel::Info).unwrap();
+    assert_eq!(writer.stats_due().unwrap().sent, 2);
+    assert_eq!(writer.stats_due(), None);
+    writer.write(&SerializeLevel::Info).unwrap();
+    assert_eq!(writer.stats_due(), None);
+    writer.write(&SerializeLevel::Info).unwrap();
+    assert_eq!(writer.stats_due().unwrap().sent, 4);
+}
+
+#[cfg(feature = "embedded-io-async")]
+#[test]
+fn async_writer_matches_blocking() {
+    use std::{
+        future::Future,
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+    use tracing_serde_structured::embedded_io::AsyncFramedWriter;
+
+    let mut blocking = FramedWriter::<_, 16>::new(Vec::new());
+    blocking.write(&SerializeLevel::Warn).unwrap();
+
+    let mut writer = AsyncFramedWriter::<_, 16>::new(Vec::new());
+    {
+        let mut fut = pin!(writer.write(&SerializeLevel::Warn));
+        // Writing to a `Vec` never waits.
+        let Poll::Ready(res) = fut.as_mut().poll(&mut Context::from_waker(Waker::noop())) else {
+            panic!("write did not complete");
+        };
+        res.unwrap();
+    }
+
+    assert_eq!(writer.get_ref(), blocking.get_ref());
+}
@@ -0,0 +1,118 @@
+//! Borrowed and deserialized values compare equal when they hold the same
+//! data, however they were captured.
+#![cfg(not(feature = "strip-locations"))]
+
+use std::sync::{Arc, Mutex};
+
+use tracing::{info, info_span};
+use tracing_core::{
+    span::{Attributes, Id, Record},
+    Event, Metadata, Subscriber,
+};
+use tracing_serde_structured::{
+    wire::{SerializeEventRef, StringTable, TracingWire},
+    AsSerde, CowString, SerializeEvent, SerializeFieldSet, SerializeRecord,
+};
+
+/// Checks every event and record against its own round trip through JSON.
+#[derive(Default)]
+struct EqSubscriber {
+    checked: Mutex<usize>,
+}
+
+impl Subscriber for EqSubscriber {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+        let attrs = attrs.as_serde();
+        let json = serde_json::to_string(&attrs).unwrap();
+        assert_eq!(attrs, serde_json::from_str(&json).unwrap());
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _: &Id, values: &Record<'_>) {
+        let record = values.as_serde();
+        let json = serde_json::to_string(&record).unwrap();
+        let decoded: SerializeRecord<'_> = serde_json::from_str(&json).unwrap();
+        assert_eq!(record, decoded);
+        assert_eq!(decoded, record.to_owned());
+        *self.checked.lock().unwrap() += 1;
+    }
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let serde = event.as_serde();
+        let json = serde_json::to_string(&serde).unwrap();
+        let decoded: SerializeEvent<'_> = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde, decoded);
+        assert_eq!(serde, event.as_serde());
+
+        let wire = TracingWire::EventRef(SerializeEventRef::new(event));
+        assert_eq!(wire, wire.to_owned());
+
+        let mut table = StringTable::new();
+        let interned = table.event(event, |_| {});
+        assert_eq!(interned, interned.to_owned());
+        *self.checked.lock().unwrap() += 1;
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+#[test]
+fn captured_values_equal_their_round_trips() {
+    let subscriber = Arc::new(EqSubscriber::default());
+    tracing::subscriber::with_default(subscriber.clone(), || {
+        let span = info_span!("request", path = "/", status = tracing::field::Empty);
+        span.record("status", 200u64);
+        info!(user = "ferris", ratio = 0.5, ok = true, "logged {}", "in");
+    });
+    assert_eq!(*subscriber.checked.lock().unwrap(), 2);
+}
+
+#[test]
+fn different_values_are_not_equal() {
+    let a = SerializeFieldSet::De(vec![CowString::Static("a"), CowString::Static("b")]);
+    let b = SerializeFieldSet::De(vec![CowString::Borrowed("a"), CowString::Borrowed("b")]);
+    let reversed = SerializeFieldSet::De(vec![CowString::Static("b"), CowString::Static("a")]);
+    assert_eq!(a, b);
+    assert_ne!(a, reversed);
+
+    let one: SerializeEvent<'_> = serde_json::from_str(
+        r#"{"fields":{"n":{"U64":1}},"metadata":{"name":"e","target":"t","level":"INFO","fields":["n"],"is_span":false,"is_event":true}}"#,
+    )
+    .unwrap();
+    let two: SerializeEvent<'_> = serde_json::from_str(
+        r#"{"fields":{"n":{"U64":2}},"metadata":{"name":"e","target":"t","level":"INFO","fields":["n"],"is_span":false,"is_event":true}}"#,
+    )
+    .unwrap();
+    assert_ne!(one, two);
+    assert_eq!(one.metadata, two.metadata);
+}
+
+#[test]
+fn strings_order_by_contents() {
+    let strings = [
+        CowString::Static("b"),
+        CowString::Borrowed("a"),
+        CowString::Borrowed("c").to_owned(),
+        CowString::Owned("b".into()),
+    ];
+    for x in &strings {
+        for y in &strings {
+            assert_eq!(x.cmp(y), x.as_str().cmp(y.as_str()));
+            assert_eq!(x == y, x.cmp(y).is_eq());
+        }
+    }
+
+    let mut map = std::collections::BTreeMap::new();
+    map.insert(CowString::Borrowed("b"), 1);
+    map.insert(CowString::Static("a"), 2);
+    assert_eq!(map.get(&CowString::Static("b")), Some(&1));
+    assert_eq!(map.get(&CowString::Borrowed("a").to_owned()), Some(&2));
+}
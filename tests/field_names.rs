@@ -0,0 +1,113 @@
+use tracing_serde_structured::{
+    time::TimeSync, wire::SerializeAttributesRef, CowString, SerializeAttributes,
+    SerializeFieldSet, SerializeLevel, SerializeMetadata,
+};
+
+fn metadata() -> SerializeMetadata<'static> {
+    SerializeMetadata {
+        name: CowString::Static("span"),
+        target: CowString::Static("my_crate"),
+        level: SerializeLevel::Info,
+        module_path: Some(CowString::Static("my_crate::io")),
+        file: None,
+        line: None,
+        fields: SerializeFieldSet::De(vec![]),
+        is_span: true,
+        is_event: false,
+    }
+}
+
+fn keys(value: &serde_json::Value) -> Vec<&str> {
+    let mut keys: Vec<_> = value
+        .as_object()
+        .unwrap()
+        .keys()
+        .map(|k| k.as_str())
+        .collect();
+    keys.sort_unstable();
+    keys
+}
+
+#[test]
+#[cfg(not(feature = "camel-case"))]
+fn fields_are_snake_case() {
+    let attrs = serde_json::to_value(SerializeAttributes {
+        metadata: metadata(),
+        parent: None,
+        is_root: true,
+    })
+    .unwrap();
+    assert!(keys(&attrs).contains(&"is_root"));
+    #[cfg(not(feature = "strip-locations"))]
+    assert_eq!(attrs["metadata"]["module_path"], "my_crate::io");
+    assert_eq!(attrs["metadata"]["is_span"], true);
+    assert_eq!(attrs["metadata"]["is_event"], false);
+
+    let sync = serde_json::to_value(TimeSync {
+        device_ticks: 1,
+        wall_clock: 2,
+    })
+    .unwrap();
+    assert_eq!(keys(&sync), ["device_ticks", "wall_clock"]);
+}
+
+#[test]
+#[cfg(feature = "camel-case")]
+fn fields_are_camel_case() {
+    let attrs = serde_json::to_value(SerializeAttributes {
+        metadata: metadata(),
+        parent: None,
+        is_root: true,
+    })
+    .unwrap();
+    assert!(keys(&attrs).contains(&"isRoot"));
+    #[cfg(not(feature = "strip-locations"))]
+    assert_eq!(attrs["metadata"]["modulePath"], "my_crate::io");
+    assert_eq!(attrs["metadata"]["isSpan"], true);
+    assert_eq!(attrs["metadata"]["isEvent"], false);
+
+    let sync = serde_json::to_value(TimeSync {
+        device_ticks: 1,
+        wall_clock: 2,
+    })
+    .unwrap();
+    assert_eq!(keys(&sync), ["deviceTicks", "wallClock"]);
+}
+
+#[test]
+fn both_spellings_deserialize() {
+    for json in [
+        r#"{"name":"n","target":"t","level":"WARN","module_path":"m","file":null,"line":null,"fields":[],"is_span":true,"is_event":false}"#,
+        r#"{"name":"n","target":"t","level":"WARN","modulePath":"m","file":null,"line":null,"fields":[],"isSpan":true,"isEvent":false}"#,
+    ] {
+        let decoded: SerializeMetadata<'_> = serde_json::from_str(json).unwrap();
+        assert!(decoded.is_span);
+        assert!(!decoded.is_event);
+        #[cfg(not(feature = "strip-locations"))]
+        assert_eq!(decoded.module_path.as_deref(), Some("m"));
+    }
+
+    for json in [
+        r#"{"metadata":{"id":3},"parent":null,"is_root":true}"#,
+        r#"{"metadata":{"id":3},"parent":null,"isRoot":true}"#,
+    ] {
+        let decoded: SerializeAttributesRef = serde_json::from_str(json).unwrap();
+        assert!(decoded.is_root);
+    }
+
+    for json in [
+        r#"{"device_ticks":1,"wall_clock":2}"#,
+        r#"{"deviceTicks":1,"wallClock":2}"#,
+    ] {
+        let decoded: TimeSync = serde_json::from_str(json).unwrap();
+        assert_eq!(decoded.wall_clock, 2);
+    }
+}
+
+#[test]
+#[cfg(not(feature = "strip-locations"))]
+fn names_round_trip() {
+    let json = serde_json::to_string(&metadata()).unwrap();
+    let decoded: SerializeMetadata<'_> = serde_json::from_str(&json).unwrap();
+    assert_eq!(decoded, metadata());
+}
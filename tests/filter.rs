@@ -0,0 +1,113 @@
+use tracing_core::{callsite::Identifier, field::FieldSet, Kind, Level, LevelFilter, Metadata};
+use tracing_serde_structured::{
+    builder::SerializeEventBuilder, filter::Directives, SerializeLevel,
+};
+
+#[test]
+fn most_specific_target_decides() {
+    let filter: Directives = "warn,app=info,app::db=trace,app::net=off".parse().unwrap();
+    assert_eq!(filter.level_for("app"), LevelFilter::INFO);
+    assert_eq!(filter.level_for("app::db::pool"), LevelFilter::TRACE);
+    assert_eq!(filter.level_for("app::net"), LevelFilter::OFF);
+    assert_eq!(filter.level_for("other"), LevelFilter::WARN);
+    assert_eq!(filter.max_level(), LevelFilter::TRACE);
+
+    assert!(filter.enabled_at("app::ui", SerializeLevel::Info));
+    assert!(!filter.enabled_at("app::ui", SerializeLevel::Debug));
+    assert!(filter.enabled_at("other", SerializeLevel::Error));
+    assert!(!filter.enabled_at("other", SerializeLevel::Info));
+}
+
+#[test]
+fn parses_like_env_filter() {
+    let parse = |s: &str| s.parse::<Directives>();
+
+    // Nothing is enabled unless a directive says so.
+    assert_eq!(parse("").unwrap().max_level(), LevelFilter::OFF);
+    assert_eq!(parse("app").unwrap().level_for("app"), LevelFilter::TRACE);
+    assert_eq!(parse("app").unwrap().level_for("lib"), LevelFilter::OFF);
+
+    // Levels in any case, or as numbers, with spaces and empty directives.
+    let filter = parse(" DEBUG , app=2 ,, lib=Off,").unwrap();
+    assert_eq!(filter.level_for("x"), LevelFilter::DEBUG);
+    assert_eq!(filter.level_for("app"), LevelFilter::WARN);
+    assert_eq!(filter.level_for("lib"), LevelFilter::OFF);
+
+    // Later directives for the same target win.
+    let filter = parse("app=info,app=error,warn,trace").unwrap();
+    assert_eq!(filter.level_for("app"), LevelFilter::ERROR);
+    assert_eq!(filter.level_for("x"), LevelFilter::TRACE);
+
+    for invalid in [
+        "app=",
+        "app=loud",
+        "=info",
+        "[span]=debug",
+        "app[span]=info",
+        "app{x=1}",
+    ] {
+        let err = parse(invalid).unwrap_err();
+        assert!(err.to_string().contains("invalid directive"), "{invalid}");
+    }
+}
+
+#[test]
+fn displays_as_parseable_directives() {
+    let filter: Directives = "warn,app=info,app::db=trace".parse().unwrap();
+    let shown = filter.to_string();
+    assert_eq!(shown, "app::db=trace,app=info,warn");
+    assert_eq!(shown.parse::<Directives>().unwrap(), filter);
+}
+
+#[test]
+fn filters_decoded_metadata() {
+    let filter: Directives = "app=debug".parse().unwrap();
+    let event = |target, level| {
+        SerializeEventBuilder::new()
+            .target(target)
+            .level(level)
+            .build()
+    };
+    assert!(filter.enabled(&event("app::net", SerializeLevel::Debug).metadata));
+    assert!(!filter.enabled(&event("app::net", SerializeLevel::Trace).metadata));
+    assert!(!filter.enabled(&event("lib", SerializeLevel::Error).metadata));
+}
+
+struct Callsite;
+
+impl tracing_core::Callsite for Callsite {
+    fn set_interest(&self, _: tracing_core::subscriber::Interest) {}
+
+    fn metadata(&self) -> &Metadata<'_> {
+        &DEBUG_META
+    }
+}
+
+static CALLSITE: Callsite = Callsite;
+static DEBUG_META: Metadata<'static> = Metadata::new(
+    "poll",
+    "app::net",
+    Level::DEBUG,
+    None,
+    None,
+    None,
+    FieldSet::new(&[], Identifier(&CALLSITE)),
+    Kind::EVENT,
+);
+
+#[test]
+fn gives_subscribers_interest() {
+    let filter: Directives = "app=debug".parse().unwrap();
+    assert!(filter.interest(&DEBUG_META).is_always());
+    let filter: Directives = "app=info".parse().unwrap();
+    assert!(filter.interest(&DEBUG_META).is_never());
+}
+
+#[test]
+fn directives_can_be_built() {
+    let mut filter = Directives::new(LevelFilter::ERROR);
+    filter.add("app", LevelFilter::INFO);
+    filter.add("app::db", LevelFilter::TRACE);
+    assert_eq!(filter, "error,app=info,app::db=trace".parse().unwrap());
+    assert_eq!(Directives::default().max_level(), LevelFilter::OFF);
+}
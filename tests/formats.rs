@@ -0,0 +1,160 @@
+//! Round-trips captured `tracing` data through a matrix of serde formats.
+//!
+//! postcard and bincode are not self-describing, and ciborium never hands out
+//! borrowed strings, which makes these a good check that none of the
+//! `Deserialize` impls depend on `deserialize_any` or on borrowing from the
+//! input.
+
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Mutex,
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::{field::Empty, info_span, warn};
+use tracing_core::{
+    span::{Attributes, Id, Record},
+    Event, Metadata, Subscriber,
+};
+use tracing_serde_structured::{AsSerde, SerializeAttributes, SerializeEvent, SerializeRecord};
+
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    Event,
+    Attributes,
+    Record,
+}
+
+#[derive(Debug)]
+struct Captured {
+    kind: Kind,
+    json: serde_json::Value,
+    json_bytes: Vec<u8>,
+    postcard: Vec<u8>,
+    bincode: Vec<u8>,
+    cbor: Vec<u8>,
+}
+
+impl Captured {
+    fn new<T: Serialize>(kind: Kind, value: &T) -> Self {
+        let mut cbor = Vec::new();
+        ciborium::into_writer(value, &mut cbor).unwrap();
+        Captured {
+            kind,
+            json: serde_json::to_value(value).unwrap(),
+            json_bytes: serde_json::to_vec(value).unwrap(),
+            postcard: postcard::to_allocvec(value).unwrap(),
+            bincode: bincode::serialize(value).unwrap(),
+            cbor,
+        }
+    }
+}
+
+#[derive(Default)]
+struct CaptureSubscriber {
+    next_id: AtomicU64,
+    captured: Mutex<Vec<Captured>>,
+}
+
+impl Subscriber for CaptureSubscriber {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+        let id = Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed) + 1);
+        let captured = Captured::new(Kind::Attributes, &attrs.as_serde());
+        self.captured.lock().unwrap().push(captured);
+        id
+    }
+
+    fn record(&self, _: &Id, values: &Record<'_>) {
+        let captured = Captured::new(Kind::Record, &values.as_serde());
+        self.captured.lock().unwrap().push(captured);
+    }
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let captured = Captured::new(Kind::Event, &event.as_serde());
+        self.captured.lock().unwrap().push(captured);
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+fn capture() -> Vec<Captured> {
+    let subscriber = std::sync::Arc::new(CaptureSubscriber::default());
+    tracing::subscriber::with_default(subscriber.clone(), || {
+        let span = info_span!("outer", answer = 42u64, later = Empty);
+        span.record("later", "recorded \"later\"");
+        let _guard = span.enter();
+
+        warn!(
+            missing = Empty,
+            flag = true,
+            neg = -5i64,
+            float = 1.5f64,
+            text = "needs\nescaping",
+            debug = ?vec![1, 2, 3],
+            "a message with \"quotes\""
+        );
+    });
+    let captured = std::mem::take(&mut *subscriber.captured.lock().unwrap());
+    captured
+}
+
+fn check<'a, T>(captured: &'a Captured)
+where
+    T: Serialize + Deserialize<'a>,
+{
+    let json: T = serde_json::from_slice(&captured.json_bytes).unwrap();
+    assert_eq!(serde_json::to_value(&json).unwrap(), captured.json, "json");
+
+    let postcard: T = postcard::from_bytes(&captured.postcard).unwrap();
+    assert_eq!(
+        serde_json::to_value(&postcard).unwrap(),
+        captured.json,
+        "postcard"
+    );
+
+    let bincode: T = bincode::deserialize(&captured.bincode).unwrap();
+    assert_eq!(
+        serde_json::to_value(&bincode).unwrap(),
+        captured.json,
+        "bincode"
+    );
+
+    let cbor: ciborium::Value = ciborium::from_reader(captured.cbor.as_slice()).unwrap();
+    let cbor: T = cbor.deserialized().unwrap();
+    assert_eq!(serde_json::to_value(&cbor).unwrap(), captured.json, "cbor");
+}
+
+#[test]
+fn format_matrix() {
+    let captured = capture();
+    assert_eq!(captured.len(), 3);
+
+    for item in &captured {
+        match item.kind {
+            Kind::Event => check::<SerializeEvent<'_>>(item),
+            Kind::Attributes => check::<SerializeAttributes<'_>>(item),
+            Kind::Record => check::<SerializeRecord<'_>>(item),
+        }
+    }
+}
+
+#[test]
+fn empty_fields_are_not_counted() {
+    let captured = capture();
+    let event = captured
+        .iter()
+        .find(|c| matches!(c.kind, Kind::Event))
+        .unwrap();
+
+    let fields = event.json["fields"].as_object().unwrap();
+    assert!(!fields.contains_key("missing"));
+    assert_eq!(fields.len(), 6);
+}
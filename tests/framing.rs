@@ -0,0 +1,228 @@
+#![cfg(feature = "postcard")]
+
+use std::io::{Cursor, ErrorKind};
+
+use postcard::Error;
+use tracing_serde_structured::{
+    framing::{Cobs, Framing, LengthPrefixed},
+    SerializeLevel,
+};
+
+/// Frames a string of `len` bytes, which postcard encodes with a varint
+/// length of its own.
+fn frame(framing: impl Framing, len: usize) -> Vec<u8> {
+    let msg = "x".repeat(len);
+    let mut buf = vec![0u8; len + 16];
+    framing.to_slice(msg.as_str(), &mut buf).unwrap().to_vec()
+}
+
+#[test]
+fn cobs_frames_match_postcard() {
+    let mut buf = [0u8; 16];
+    let expected = postcard::to_slice_cobs(&SerializeLevel::Warn, &mut buf)
+        .unwrap()
+        .to_vec();
+    let mut buf = [0u8; 16];
+    let frame = Cobs.to_slice(&SerializeLevel::Warn, &mut buf).unwrap();
+    assert_eq!(*frame, expected);
+
+    let mut frame = frame[..frame.len() - 1].to_vec();
+    let level: SerializeLevel = Cobs.decode(&mut frame).unwrap();
+    assert_eq!(level, SerializeLevel::Warn);
+}
+
+#[test]
+fn prefixes_grow_with_the_message() {
+    for (len, prefix) in [(0, [1]), (126, [127])] {
+        let frame = frame(LengthPrefixed::new(), len);
+        assert_eq!(frame[..1], prefix);
+        assert_eq!(frame.len(), 1 + 1 + len);
+    }
+    // 127 bytes, and their own one byte length, are 128 bytes of message.
+    let frame = frame(LengthPrefixed::new(), 127);
+    assert_eq!(frame[..2], [0x80, 0x01]);
+    assert_eq!(frame.len(), 2 + 1 + 127);
+
+    let mut frame = frame.clone();
+    let msg: &str = LengthPrefixed::new().decode(&mut frame).unwrap();
+    assert_eq!(msg, "x".repeat(127));
+}
+
+#[test]
+fn messages_must_fit_after_the_longest_prefix() {
+    let mut buf = [0u8; 6];
+    let frame = LengthPrefixed::new().to_slice(&SerializeLevel::Warn, &mut buf);
+    assert_eq!(frame.unwrap(), [1, 3]);
+
+    let mut buf = [0u8; 6];
+    let frame = LengthPrefixed::new().to_slice("a", &mut buf);
+    assert_eq!(frame.unwrap_err(), Error::SerializeBufferFull);
+
+    let mut buf = [0u8; 4];
+    let frame = LengthPrefixed::new().to_slice(&(), &mut buf);
+    assert_eq!(frame.unwrap_err(), Error::SerializeBufferFull);
+}
+
+#[test]
+fn frames_must_be_as_long_as_their_prefix() {
+    let framing = LengthPrefixed::new();
+    assert_eq!(framing.unframe(&mut [2, 1, 2]).unwrap(), [1, 2]);
+    assert_eq!(
+        framing.unframe(&mut [2, 1]).unwrap_err(),
+        Error::DeserializeBadEncoding
+    );
+    assert_eq!(
+        framing.unframe(&mut [2, 1, 2, 3]).unwrap_err(),
+        Error::DeserializeBadEncoding
+    );
+    assert_eq!(
+        framing.unframe(&mut [0x80]).unwrap_err(),
+        Error::DeserializeBadVarint
+    );
+    assert_eq!(
+        framing.unframe(&mut [0xff; 5]).unwrap_err(),
+        Error::DeserializeBadVarint
+    );
+}
+
+#[test]
+fn streams_are_split_into_frames() {
+    let stream = [
+        frame(LengthPrefixed::new(), 3),
+        frame(LengthPrefixed::new(), 200),
+    ]
+    .concat();
+    let framing = LengthPrefixed::new();
+    let mut reader = Cursor::new(&stream);
+    let mut buf = Vec::new();
+
+    assert!(framing.read_frame(&mut reader, &mut buf).unwrap());
+    assert_eq!(buf, frame(LengthPrefixed::new(), 3));
+    assert!(framing.read_frame(&mut reader, &mut buf).unwrap());
+    let msg: &str = framing.decode(&mut buf).unwrap();
+    assert_eq!(msg.len(), 200);
+    assert!(!framing.read_frame(&mut reader, &mut buf).unwrap());
+
+    // An incomplete last frame is dropped.
+    let mut reader = Cursor::new(&stream[..stream.len() - 1]);
+    assert!(framing.read_frame(&mut reader, &mut buf).unwrap());
+    assert!(!framing.read_frame(&mut reader, &mut buf).unwrap());
+}
+
+#[test]
+fn long_frames_are_rejected_before_they_are_read() {
+    let stream = frame(LengthPrefixed::new(), 200);
+    let framing = LengthPrefixed::with_max_len(64);
+    let err = framing
+        .read_frame(&mut Cursor::new(&stream), &mut Vec::new())
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert_eq!(err.to_string(), "frame of 202 bytes is longer than 64");
+}
+
+#[test]
+fn cobs_streams_skip_empty_frames() {
+    let stream = [&[0, 0][..], &frame(Cobs, 3), &[0]].concat();
+    let mut reader = Cursor::new(&stream);
+    let mut buf = Vec::new();
+    assert!(Cobs.read_frame(&mut reader, &mut buf).unwrap());
+    let msg: &str = Cobs.decode(&mut buf).unwrap();
+    assert_eq!(msg, "xxx");
+    assert!(!Cobs.read_frame(&mut reader, &mut buf).unwrap());
+}
+
+#[cfg(feature = "embedded-io")]
+#[test]
+fn framed_writers_use_their_framing() {
+    use tracing_serde_structured::embedded_io::FramedWriter;
+
+    let mut writer = FramedWriter::<_, 64, _>::with_framing(Vec::new(), LengthPrefixed::new());
+    writer.write("xxx").unwrap();
+    writer
+        .write_with(|buf| postcard::to_slice(&7u8, buf))
+        .unwrap();
+    assert_eq!(writer.stats().sent, 2);
+    assert_eq!(
+        *writer.get_ref(),
+        [frame(LengthPrefixed::new(), 3), vec![7]].concat()
+    );
+}
+
+#[cfg(all(feature = "embedded-io", feature = "checksum"))]
+#[test]
+fn trailers_go_inside_the_framing() {
+    use tracing_serde_structured::{checksum::Checksum, embedded_io::FramedWriter};
+
+    let mut writer = FramedWriter::<_, 64, _>::with_framing(Vec::new(), LengthPrefixed::new());
+    writer.write_checked("xxx", Checksum::Crc16).unwrap();
+    let mut frame = writer.into_inner();
+    assert_eq!(frame[0], 4 + 2);
+
+    let msg = Checksum::Crc16
+        .check(LengthPrefixed::new().unframe(&mut frame).unwrap())
+        .unwrap();
+    assert_eq!(postcard::from_bytes::<&str>(msg).unwrap(), "xxx");
+}
+
+#[cfg(feature = "host")]
+mod host {
+    use tracing_serde_structured::{
+        host::{Decoder, Merger},
+        time::TickRate,
+        wire::{
+            delta::{DeltaEncoder, SourceFrame, SourceId},
+            TracingWire,
+        },
+        SerializeId,
+    };
+
+    use super::*;
+
+    fn enter(id: u64) -> TracingWire<'static> {
+        TracingWire::Enter(SerializeId {
+            id: id.try_into().unwrap(),
+        })
+    }
+
+    #[test]
+    fn decoders_read_length_prefixed_streams() {
+        let mut encoder = DeltaEncoder::new();
+        let mut stream = Vec::new();
+        for i in 1..=3 {
+            let mut buf = [0u8; 64];
+            let frame = encoder.encode(i, enter(i).into());
+            stream.extend_from_slice(LengthPrefixed::new().to_slice(&frame, &mut buf).unwrap());
+        }
+
+        let messages: Vec<_> = Decoder::new(TickRate::MICROS)
+            .read_framed(Cursor::new(&stream), LengthPrefixed::new())
+            .map(|msg| msg.unwrap().msg)
+            .collect();
+        assert_eq!(messages, [enter(1), enter(2), enter(3)]);
+
+        // Read as COBS, the stream has no zero bytes, so it is one
+        // incomplete frame.
+        let mut messages = Decoder::new(TickRate::MICROS).read(Cursor::new(&stream));
+        assert!(messages.next().is_none());
+    }
+
+    #[test]
+    fn mergers_read_length_prefixed_streams() {
+        let mut encoder = DeltaEncoder::new();
+        let mut stream = Vec::new();
+        for i in 1..=2 {
+            let mut buf = [0u8; 64];
+            let frame = SourceFrame {
+                source: SourceId { id: 0 },
+                frame: encoder.encode(i, enter(i).into()),
+            };
+            stream.extend_from_slice(LengthPrefixed::new().to_slice(&frame, &mut buf).unwrap());
+        }
+
+        let messages: Vec<_> = Merger::new(TickRate::MICROS)
+            .read_framed(Cursor::new(&stream), LengthPrefixed::new())
+            .map(|msg| msg.unwrap().message.msg)
+            .collect();
+        assert_eq!(messages, [enter(1), enter(2)]);
+    }
+}
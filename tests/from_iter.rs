@@ -0,0 +1,36 @@
+use tracing_serde_structured::{CowString, SerializeRecord, SerializeRecordFields, SerializeValue};
+
+fn values<'a>() -> Vec<(CowString<'a>, SerializeValue<'a>)> {
+    vec![
+        ("b".into(), SerializeValue::U64(1)),
+        ("a".into(), SerializeValue::Bool(true)),
+        ("b".into(), SerializeValue::U64(2)),
+    ]
+}
+
+#[test]
+fn record_fields() {
+    let fields: SerializeRecordFields<'_> = values().into_iter().collect();
+    let SerializeRecordFields::De(map) = &fields else {
+        panic!("collected fields are deserialized fields");
+    };
+    assert_eq!(map.len(), 2);
+    assert_eq!(
+        serde_json::to_value(&fields).unwrap(),
+        serde_json::json!({ "a": { "Bool": true }, "b": { "U64": 2 } })
+    );
+}
+
+#[test]
+fn record() {
+    let record: SerializeRecord<'_> = values().into_iter().collect();
+    let SerializeRecord::De(map) = &record else {
+        panic!("collected records are deserialized records");
+    };
+    assert_eq!(map.len(), 2);
+    assert_eq!(map[&CowString::Static("b")], SerializeValue::U64(2));
+
+    let json = serde_json::to_string(&record).unwrap();
+    let decoded: SerializeRecord<'_> = serde_json::from_str(&json).unwrap();
+    assert_eq!(decoded, record);
+}
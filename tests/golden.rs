@@ -0,0 +1,386 @@
+//! The encoding of a representative set of messages, checked against the
+//! files in `tests/golden`.
+//!
+//! Deployed consumers decode what producers built from older versions of this
+//! crate send them, so any change to these bytes (reordering an enum, renaming
+//! a field) is a breaking change, and the tests fail until the fixtures are
+//! deliberately regenerated with:
+//!
+//! ```text
+//! GOLDEN_UPDATE=1 cargo test --test golden
+//! ```
+#![cfg(not(feature = "strip-locations"))]
+
+use std::{fs, num::NonZeroU64, path::PathBuf};
+
+use tracing_serde_structured::{
+    baggage::{BaggageEntry, SerializeBaggage},
+    builder::{SerializeAttributesBuilder, SerializeEventBuilder},
+    time::{TickRate, TimeSync},
+    tree::{SerializeTree, TreeNode},
+    wire::{
+        delta::{DeltaFrame, SourceFrame, SourceId, SyncMarker},
+        description::{Description, Encoding, SchemaBlob},
+        handshake::{Capabilities, Hello},
+        DropReason, InternedFields, SerializeAttributesRef, SerializeDropped,
+        SerializeEventInterned, SerializeEventRef, SerializeMetadataId, SerializePanic,
+        SerializePipelineStats, StringId, TracingWire,
+    },
+    CowString, DebugRecord, Detached, SerializeId, SerializeLevel, SerializeRecord, SerializeValue,
+};
+
+fn id(id: u64) -> SerializeId {
+    SerializeId {
+        id: NonZeroU64::new(id).unwrap(),
+    }
+}
+
+fn debug(s: &'static str) -> DebugRecord<'static> {
+    DebugRecord::De(CowString::Static(s))
+}
+
+/// One of each message, with values of every kind and every level.
+fn messages() -> Vec<(&'static str, TracingWire<'static>)> {
+    let metadata = SerializeMetadataId { id: 0x1000 };
+    let event = SerializeEventBuilder::new()
+        .name("event src/main.rs:12")
+        .target("app::net")
+        .level(SerializeLevel::Warn)
+        .module_path("app::net")
+        .location("src/main.rs", 12)
+        .field("message", SerializeValue::Debug(debug("retrying")))
+        .field("peer", "10.0.0.1")
+        .field("ratio", 0.25)
+        .field("offset", -3i64)
+        .field("attempt", 2u64)
+        .field("tls", true)
+        .parent(id(1))
+        .build();
+    let attributes = SerializeAttributesBuilder::new("connect")
+        .target("app::net")
+        .level(SerializeLevel::Debug)
+        .location("src/net.rs", 40)
+        .field("peer")
+        .root()
+        .build();
+
+    vec![
+        (
+            "define_metadata",
+            TracingWire::DefineMetadata {
+                id: metadata,
+                metadata: event.metadata.clone(),
+            },
+        ),
+        (
+            "new_span",
+            TracingWire::NewSpan {
+                id: id(1),
+                attributes,
+            },
+        ),
+        (
+            "new_span_ref",
+            TracingWire::NewSpanRef {
+                id: id(2),
+                attributes: SerializeAttributesRef {
+                    metadata,
+                    parent: Some(id(1)),
+                    is_root: false,
+                },
+            },
+        ),
+        (
+            "record",
+            TracingWire::Record {
+                span: id(1),
+                values: [(CowString::Static("status"), SerializeValue::U64(200))]
+                    .into_iter()
+                    .collect::<SerializeRecord<'_>>(),
+            },
+        ),
+        (
+            "follows_from",
+            TracingWire::FollowsFrom {
+                span: id(2),
+                follows: id(1),
+            },
+        ),
+        ("event", TracingWire::Event(event)),
+        (
+            "event_ref",
+            TracingWire::EventRef(SerializeEventRef {
+                fields: [(CowString::Static("done"), SerializeValue::Bool(false))]
+                    .into_iter()
+                    .collect(),
+                metadata,
+                parent: None,
+            }),
+        ),
+        ("enter", TracingWire::Enter(id(1))),
+        ("exit", TracingWire::Exit(id(1))),
+        ("close_span", TracingWire::CloseSpan(id(u64::MAX))),
+        (
+            "define_string",
+            TracingWire::DefineString {
+                id: StringId { id: 7 },
+                value: CowString::Static("peer"),
+            },
+        ),
+        (
+            "event_interned",
+            TracingWire::EventInterned(SerializeEventInterned {
+                fields: InternedFields::De(
+                    [(StringId { id: 7 }, SerializeValue::Str("10.0.0.2".into()))]
+                        .into_iter()
+                        .collect(),
+                ),
+                metadata,
+                parent: Some(id(2)),
+            }),
+        ),
+        (
+            "sync",
+            TracingWire::Sync(SyncMarker {
+                magic: SyncMarker::MAGIC,
+                seq: 100,
+                generation: 3,
+                time: 1_000_000,
+                span: 2,
+            }),
+        ),
+        (
+            "panic",
+            TracingWire::Panic(SerializePanic {
+                message: debug("index out of bounds"),
+                file: Some(CowString::Static("src/main.rs")),
+                line: Some(99),
+            }),
+        ),
+        (
+            "time_sync",
+            TracingWire::TimeSync(TimeSync {
+                device_ticks: 32_768,
+                wall_clock: 1_700_000_000_000_000_000,
+            }),
+        ),
+        (
+            "pipeline_stats",
+            TracingWire::PipelineStats(SerializePipelineStats {
+                sent: 1000,
+                dropped: 1,
+                overflows: 2,
+                errors: 3,
+            }),
+        ),
+        (
+            "hello",
+            TracingWire::Hello(Hello {
+                version: 1,
+                min_version: 1,
+                uses: Capabilities::DELTA | Capabilities::INTERNED,
+                offers: Capabilities::LZ4,
+                tick_rate: Some(TickRate::hz(32_768)),
+                producer: Some(CowString::Static("sensor 1.2.0")),
+            }),
+        ),
+        (
+            "dropped",
+            TracingWire::Dropped(SerializeDropped {
+                count: 12,
+                reason: DropReason::RateLimited,
+                first_seq: 300,
+                last_seq: 320,
+            }),
+        ),
+        (
+            "baggage",
+            TracingWire::Baggage {
+                span: id(1),
+                baggage: [
+                    BaggageEntry::new("tenant", "acme"),
+                    BaggageEntry::new("region", "eu west").with_metadata("ttl=60"),
+                ]
+                .into_iter()
+                .collect::<SerializeBaggage<'_>>(),
+            },
+        ),
+        (
+            "description",
+            TracingWire::Description(Description {
+                crate_version: CowString::Static("0.4.0"),
+                encoding: Encoding::SKIP_NONE | Encoding::CAMEL_CASE,
+                schema: Some(SchemaBlob::Borrowed(&[0x02, b'u', b'8', 0x02])),
+            }),
+        ),
+    ]
+}
+
+fn levels() -> [SerializeLevel; 5] {
+    [
+        SerializeLevel::Trace,
+        SerializeLevel::Debug,
+        SerializeLevel::Info,
+        SerializeLevel::Warn,
+        SerializeLevel::Error,
+    ]
+}
+
+fn frame() -> SourceFrame<'static> {
+    SourceFrame {
+        source: SourceId { id: 1 },
+        frame: DeltaFrame {
+            time: -5,
+            msg: TracingWire::Enter(id(3)),
+        },
+    }
+}
+
+/// Compares `actual` with the fixture `name`, or overwrites the fixture with
+/// it if `GOLDEN_UPDATE` is set.
+fn check(name: &str, actual: &[u8]) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(name);
+    if std::env::var_os("GOLDEN_UPDATE").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = fs::read(&path).unwrap_or_else(|e| panic!("reading {}: {e}", path.display()));
+    assert!(
+        expected == actual,
+        "the encoding of {name} changed\nexpected: {expected:02x?}\n  actual: {actual:02x?}\n\
+         rerun with GOLDEN_UPDATE=1 if this is intended, and release it as a breaking change",
+    );
+}
+
+#[test]
+fn postcard_is_stable() {
+    for (name, msg) in messages() {
+        let bytes = postcard::to_allocvec(&msg).unwrap();
+        check(&format!("{name}.postcard"), &bytes);
+        let decoded: TracingWire<'_> = postcard::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded, msg);
+    }
+    check(
+        "levels.postcard",
+        &postcard::to_allocvec(&levels()).unwrap(),
+    );
+    check(
+        "source_frame.postcard",
+        &postcard::to_allocvec(&frame()).unwrap(),
+    );
+}
+
+#[test]
+#[cfg(not(any(feature = "camel-case", feature = "skip-none")))]
+fn json_is_stable() {
+    for (name, msg) in messages() {
+        let mut json = serde_json::to_string_pretty(&msg).unwrap();
+        json.push('\n');
+        check(&format!("{name}.json"), json.as_bytes());
+        let decoded: TracingWire<'_> = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, msg);
+    }
+    let mut json = serde_json::to_string(&levels()).unwrap();
+    json.push('\n');
+    check("levels.json", json.as_bytes());
+}
+
+/// The index and name of each kind of message, in the order of `messages`.
+/// They never change: new kinds get the next index.
+const WIRE_VARIANTS: &[(u8, &str)] = &[
+    (0, "DefineMetadata"),
+    (1, "NewSpan"),
+    (2, "NewSpanRef"),
+    (3, "Record"),
+    (4, "FollowsFrom"),
+    (5, "Event"),
+    (6, "EventRef"),
+    (7, "Enter"),
+    (8, "Exit"),
+    (9, "CloseSpan"),
+    (10, "DefineString"),
+    (11, "EventInterned"),
+    (12, "Sync"),
+    (13, "Panic"),
+    (14, "TimeSync"),
+    (15, "PipelineStats"),
+    (16, "Hello"),
+    (17, "Dropped"),
+    (18, "Baggage"),
+    (19, "Description"),
+];
+
+/// `Unknown` variants are written with an index no variant will ever have,
+/// `u32::MAX` as a postcard varint.
+const UNKNOWN_INDEX: &[u8] = &[0xff, 0xff, 0xff, 0xff, 0x0f];
+
+#[test]
+fn discriminants_are_locked() {
+    let messages = messages();
+    assert_eq!(messages.len(), WIRE_VARIANTS.len());
+    for ((_, msg), (index, name)) in messages.iter().zip(WIRE_VARIANTS) {
+        assert_eq!(postcard::to_allocvec(msg).unwrap()[0], *index, "{name}");
+        let json = serde_json::to_value(msg).unwrap();
+        assert!(json.get(name).is_some(), "{name}: {json}");
+    }
+    assert!(postcard::to_allocvec(&TracingWire::<'_, Detached>::Unknown).is_err());
+
+    let values = [
+        (SerializeValue::Debug(debug("x")), "Debug"),
+        (SerializeValue::Str("x".into()), "Str"),
+        (SerializeValue::F64(0.5), "F64"),
+        (SerializeValue::I64(-1), "I64"),
+        (SerializeValue::U64(1), "U64"),
+        (SerializeValue::Bool(true), "Bool"),
+        (
+            SerializeValue::Tree(SerializeTree::new(vec![TreeNode::Unit]).unwrap()),
+            "Tree",
+        ),
+    ];
+    for (index, (value, name)) in values.iter().enumerate() {
+        assert_eq!(
+            postcard::to_allocvec(value).unwrap()[0],
+            index as u8,
+            "{name}"
+        );
+        assert!(
+            serde_json::to_value(value).unwrap().get(name).is_some(),
+            "{name}"
+        );
+    }
+    assert_eq!(
+        postcard::to_allocvec(&SerializeValue::Unknown).unwrap(),
+        UNKNOWN_INDEX
+    );
+
+    let names = ["TRACE", "DEBUG", "INFO", "WARN", "ERROR"];
+    for (index, (level, name)) in levels().into_iter().zip(names).enumerate() {
+        assert_eq!(postcard::to_allocvec(&level).unwrap(), [index as u8]);
+        assert_eq!(
+            postcard::from_bytes::<SerializeLevel>(&[index as u8]),
+            Ok(level)
+        );
+        assert_eq!(serde_json::to_value(level).unwrap(), name);
+    }
+
+    let reasons = [
+        (DropReason::QueueFull, "QueueFull"),
+        (DropReason::RateLimited, "RateLimited"),
+        (DropReason::Oversize, "Oversize"),
+    ];
+    for (index, (reason, name)) in reasons.into_iter().enumerate() {
+        assert_eq!(postcard::to_allocvec(&reason).unwrap(), [index as u8]);
+        assert_eq!(
+            postcard::from_bytes::<DropReason>(&[index as u8]),
+            Ok(reason)
+        );
+        assert_eq!(serde_json::to_value(reason).unwrap(), name);
+    }
+    assert_eq!(
+        postcard::to_allocvec(&DropReason::Unknown).unwrap(),
+        UNKNOWN_INDEX
+    );
+}
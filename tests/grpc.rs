@@ -0,0 +1,204 @@
+#![cfg(feature = "grpc")]
+
+use std::{
+    convert::Infallible,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use tokio::net::TcpListener;
+use tonic::{
+    body::Body,
+    codegen::{http, Service},
+    server::{Grpc, NamedService},
+    transport::{server::TcpIncoming, Server},
+    Request, Response, Status, Streaming,
+};
+use tracing_serde_structured::{
+    builder::SerializeEventBuilder,
+    grpc::{
+        proto::{envelope::Payload, value::Kind, Envelope, Event, Level, Summary, Value},
+        CollectorClient, CollectorSink, Error,
+    },
+    wire::{
+        delta::{DeltaEncoder, DeltaFrame, SourceId},
+        TracingWire,
+    },
+    CowString, SerializeId, SerializeLevel, SerializeValue,
+};
+
+/// A collector keeping every envelope it receives.
+#[derive(Clone, Default)]
+struct Collector {
+    received: Arc<Mutex<Vec<Envelope>>>,
+}
+
+impl NamedService for Collector {
+    const NAME: &'static str = "tracing_serde_structured.collector.v1.Collector";
+}
+
+impl Service<http::Request<Body>> for Collector {
+    type Response = http::Response<Body>;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Infallible>> + Send>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<Body>) -> Self::Future {
+        let collect = Collect(self.received.clone());
+        Box::pin(async move {
+            if req.uri().path() != "/tracing_serde_structured.collector.v1.Collector/Collect" {
+                return Ok(Status::unimplemented("no such method").into_http());
+            }
+            let mut grpc = Grpc::new(tonic_prost::ProstCodec::default());
+            Ok(grpc.client_streaming(collect, req).await)
+        })
+    }
+}
+
+struct Collect(Arc<Mutex<Vec<Envelope>>>);
+
+impl Service<Request<Streaming<Envelope>>> for Collect {
+    type Response = Response<Summary>;
+    type Error = Status;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Status>> + Send>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Status>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<Streaming<Envelope>>) -> Self::Future {
+        let received = self.0.clone();
+        Box::pin(async move {
+            let mut stream = req.into_inner();
+            let mut count = 0;
+            while let Some(envelope) = stream.message().await? {
+                received.lock().unwrap().push(envelope);
+                count += 1;
+            }
+            Ok(Response::new(Summary { received: count }))
+        })
+    }
+}
+
+/// Serves a collector on a port of its own, returning it and its URL.
+async fn serve() -> (Collector, String) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let collector = Collector::default();
+    let service = collector.clone();
+    tokio::spawn(async move {
+        Server::builder()
+            .add_service(service)
+            .serve_with_incoming(TcpIncoming::from(listener))
+            .await
+            .unwrap();
+    });
+    (collector, url)
+}
+
+fn enter(id: u64) -> TracingWire<'static> {
+    TracingWire::Enter(SerializeId {
+        id: id.try_into().unwrap(),
+    })
+}
+
+#[tokio::test]
+async fn sinks_stream_frames_and_events() {
+    let (collector, url) = serve().await;
+    let client = CollectorClient::connect(url).await.unwrap();
+    let (sink, call) = CollectorSink::new(client, 16);
+    let mut sink = sink.with_source(SourceId { id: 3 });
+    let call = tokio::spawn(call);
+
+    let mut encoder = DeltaEncoder::new();
+    let frame = encoder.encode(5, enter(1).into());
+    sink.send_frame(&frame).unwrap();
+    let event = SerializeEventBuilder::new()
+        .target("app::net")
+        .level(SerializeLevel::Warn)
+        .field("attempt", 3u64)
+        .build();
+    sink.send_event(Duration::from_millis(7), Some(UNIX_EPOCH), &event)
+        .unwrap();
+    assert_eq!(sink.stats().sent, 2);
+    drop(sink);
+
+    assert_eq!(call.await.unwrap().unwrap(), Summary { received: 2 });
+    let received = collector.received.lock().unwrap().clone();
+    assert_eq!(received.len(), 2);
+    assert!(received.iter().all(|envelope| envelope.source == 3));
+
+    let Some(Payload::Frame(bytes)) = &received[0].payload else {
+        panic!("expected a frame, got {:?}", received[0]);
+    };
+    let decoded: DeltaFrame<'_> = postcard::from_bytes(bytes).unwrap();
+    assert_eq!(decoded, frame);
+
+    let Some(Payload::Event(event)) = &received[1].payload else {
+        panic!("expected an event, got {:?}", received[1]);
+    };
+    assert_eq!(event.since_start_nanos, 7_000_000);
+    assert_eq!(event.wall_clock_nanos, Some(0));
+    assert_eq!(event.level(), Level::Warn);
+    assert_eq!(event.target, "app::net");
+    assert_eq!(
+        event.fields["attempt"],
+        Value {
+            kind: Some(Kind::U64(3))
+        }
+    );
+}
+
+#[test]
+fn values_convert_by_kind() {
+    let kind = |value: SerializeValue<'_>| Value::from(&value).kind;
+    assert_eq!(
+        kind(SerializeValue::Str(CowString::Borrowed("on"))),
+        Some(Kind::Str("on".into()))
+    );
+    assert_eq!(kind(SerializeValue::I64(-2)), Some(Kind::I64(-2)));
+    assert_eq!(kind(SerializeValue::Bool(true)), Some(Kind::Bool(true)));
+    assert_eq!(kind(SerializeValue::Unknown), None);
+    assert_eq!(Level::from(SerializeLevel::Trace), Level::Trace);
+}
+
+#[tokio::test]
+async fn full_sinks_drop_envelopes() {
+    let (collector, url) = serve().await;
+    let client = CollectorClient::connect(url).await.unwrap();
+    let (mut sink, call) = CollectorSink::new(client, 2);
+
+    // Nothing is sent until the call runs.
+    let event = Event::default();
+    for _ in 0..2 {
+        sink.send(Envelope::event(SourceId { id: 0 }, event.clone()))
+            .unwrap();
+    }
+    let err = sink
+        .send(Envelope::event(SourceId { id: 0 }, event.clone()))
+        .unwrap_err();
+    assert!(matches!(err, Error::Full));
+    assert_eq!(sink.stats().dropped, 1);
+    drop(sink);
+
+    assert_eq!(call.await.unwrap(), Summary { received: 2 });
+    assert_eq!(collector.received.lock().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn ended_calls_close_the_sink() {
+    let (_, url) = serve().await;
+    let client = CollectorClient::connect(url).await.unwrap();
+    let (mut sink, call) = CollectorSink::new(client, 2);
+    drop(call);
+
+    let err = sink.send(Envelope::default()).unwrap_err();
+    assert!(matches!(err, Error::Closed));
+    assert_eq!(err.to_string(), "call to the collector has ended");
+}
@@ -0,0 +1,195 @@
+use tracing_serde_structured::{
+    time::TickRate,
+    wire::{
+        handshake::{Accept, Capabilities, Error, Hello, MIN_VERSION, VERSION},
+        TracingWire,
+    },
+};
+
+#[test]
+fn consumers_accept_what_they_support() {
+    let hello = Hello::new(Capabilities::DELTA)
+        .offering(Capabilities::INTERNED | Capabilities::ZSTD)
+        .with_tick_rate(TickRate::hz(32_768));
+    let accept = hello
+        .negotiate(Capabilities::DELTA | Capabilities::INTERNED | Capabilities::LZ4)
+        .unwrap();
+    assert_eq!(
+        accept,
+        Accept {
+            version: VERSION,
+            capabilities: Capabilities::DELTA | Capabilities::INTERNED,
+        }
+    );
+    assert_eq!(hello.accepted(&accept), Ok(accept.capabilities));
+}
+
+#[test]
+fn missing_capabilities_are_named() {
+    let hello = Hello::new(Capabilities::DELTA | Capabilities::AUDIT | Capabilities::HEATSHRINK);
+    let err = hello.negotiate(Capabilities::DELTA).unwrap_err();
+    assert_eq!(
+        err,
+        Error::Unsupported(Capabilities::HEATSHRINK | Capabilities::AUDIT)
+    );
+    assert_eq!(
+        err.to_string(),
+        "stream uses capabilities this consumer lacks: heatshrink | audit"
+    );
+}
+
+#[test]
+fn newer_versions_are_read_while_compatible() {
+    let mut hello = Hello::new(Capabilities::empty());
+    hello.version = VERSION + 1;
+    let accept = hello.negotiate(Capabilities::empty()).unwrap();
+    assert_eq!(accept.version, VERSION);
+
+    hello.min_version = VERSION + 1;
+    let err = hello.negotiate(Capabilities::supported()).unwrap_err();
+    assert_eq!(
+        err,
+        Error::Version {
+            version: VERSION + 1,
+            min_version: VERSION + 1,
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "producer writes version {0} of the wire format, readable from version {0}, \
+             but this consumer reads versions {MIN_VERSION} to {VERSION}",
+            VERSION + 1
+        )
+    );
+}
+
+#[test]
+fn producers_reject_what_they_did_not_offer() {
+    let hello = Hello::new(Capabilities::DELTA).offering(Capabilities::LZ4);
+    let accept = |version, capabilities| Accept {
+        version,
+        capabilities,
+    };
+    assert_eq!(
+        hello.accepted(&accept(VERSION, Capabilities::DELTA)),
+        Ok(Capabilities::DELTA)
+    );
+    for rejected in [
+        accept(VERSION, Capabilities::LZ4),
+        accept(VERSION, Capabilities::DELTA | Capabilities::ZSTD),
+        accept(VERSION + 1, Capabilities::DELTA),
+        accept(0, Capabilities::DELTA),
+    ] {
+        assert_eq!(
+            hello.accepted(&rejected),
+            Err(Error::Rejected),
+            "{rejected:?}"
+        );
+    }
+}
+
+#[test]
+fn unknown_capabilities_are_kept_and_unsupported() {
+    let future = Capabilities::from_bits(1 << 31);
+    let uses = Capabilities::DELTA | future;
+    assert_eq!(uses.to_string(), "delta | 0x80000000");
+    assert_eq!(format!("{:?}", Capabilities::empty()), "Capabilities(0x0)");
+
+    let hello = Hello::new(uses).with_producer("sensor 2.0.0");
+    let bytes = postcard::to_allocvec(&TracingWire::from(hello.clone())).unwrap();
+    let decoded: TracingWire<'_> = postcard::from_bytes(&bytes).unwrap();
+    let TracingWire::Hello(decoded) = decoded else {
+        panic!("decoded a hello");
+    };
+    assert_eq!(decoded, hello);
+    assert!(decoded.is_borrowed());
+    assert_eq!(
+        decoded.negotiate(Capabilities::supported()),
+        Err(Error::Unsupported(future))
+    );
+}
+
+#[test]
+fn supported_capabilities_follow_features() {
+    let supported = Capabilities::supported();
+    assert!(supported.contains(Capabilities::DELTA | Capabilities::INTERNED));
+    assert_eq!(supported.contains(Capabilities::LZ4), cfg!(feature = "lz4"));
+    assert_eq!(
+        supported.contains(Capabilities::AUDIT),
+        cfg!(feature = "audit")
+    );
+}
+
+#[cfg(feature = "host")]
+mod host {
+    use std::io::Cursor;
+
+    use tracing_serde_structured::{
+        builder::SerializeAttributesBuilder,
+        host::{Decoder, Error as HostError},
+        wire::{delta::DeltaEncoder, table::MetadataTable},
+        SerializeId,
+    };
+
+    use super::*;
+
+    fn stream(hello: Hello<'static>) -> Vec<u8> {
+        let mut encoder = DeltaEncoder::new();
+        let enter = TracingWire::Enter(SerializeId {
+            id: 1.try_into().unwrap(),
+        });
+        [(0, TracingWire::Hello(hello)), (32_768, enter)]
+            .into_iter()
+            .flat_map(|(now, msg)| postcard::to_allocvec_cobs(&encoder.encode(now, msg)).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn decoders_take_the_tick_rate_from_hellos() {
+        let hello = Hello::new(Capabilities::DELTA).with_tick_rate(TickRate::hz(32_768));
+        let mut messages = Decoder::new(TickRate::MICROS).read(Cursor::new(stream(hello.clone())));
+
+        let msg = messages.next().unwrap().unwrap();
+        assert_eq!(msg.since_start.as_secs(), 1);
+        assert!(messages.next().is_none());
+        assert_eq!(messages.decoder().hello(), Some(&hello));
+    }
+
+    #[test]
+    fn decoders_fail_fast_on_what_they_cannot_read() {
+        let hello = Hello::new(Capabilities::DELTA | Capabilities::AUTHENTICATION);
+        let mut messages = Decoder::new(TickRate::MICROS).read(Cursor::new(stream(hello)));
+
+        let err = messages.next().unwrap().unwrap_err();
+        assert!(matches!(
+            err,
+            HostError::Handshake(Error::Unsupported(Capabilities::AUTHENTICATION))
+        ));
+        assert_eq!(
+            err.to_string(),
+            "cannot read stream: stream uses capabilities this consumer lacks: authentication"
+        );
+        assert_eq!(messages.decoder().hello(), None);
+    }
+
+    #[test]
+    fn decoders_without_tables_reject_compact_streams() {
+        let hello = Hello::new(Capabilities::DELTA | Capabilities::METADATA_TABLE);
+        let mut messages = Decoder::new(TickRate::MICROS).read(Cursor::new(stream(hello)));
+        assert!(matches!(
+            messages.next(),
+            Some(Err(HostError::Handshake(Error::Unsupported(
+                Capabilities::METADATA_TABLE
+            ))))
+        ));
+
+        let metadata = SerializeAttributesBuilder::new("span").build().metadata;
+        let decoder = Decoder::with_table(TickRate::MICROS, MetadataTable::new([&metadata]));
+        assert!(decoder
+            .capabilities()
+            .contains(Capabilities::METADATA_TABLE));
+        // Compressed frames are decompressed before they reach a decoder.
+        assert!(!decoder.capabilities().contains(Capabilities::ZSTD));
+    }
+}
@@ -0,0 +1,82 @@
+#![cfg(feature = "heatshrink")]
+
+use std::num::NonZeroU64;
+
+use tracing_serde_structured::{
+    heatshrink::{self, Config, Error},
+    wire::TracingWire,
+    SerializeId,
+};
+
+fn batch() -> Vec<TracingWire<'static>> {
+    (0..32)
+        .flat_map(|id| {
+            let id = SerializeId {
+                id: NonZeroU64::new(1 + id % 4).unwrap(),
+            };
+            [TracingWire::Enter(id.clone()), TracingWire::Exit(id)]
+        })
+        .collect()
+}
+
+#[test]
+fn round_trip_with_fixed_buffers() {
+    let messages = batch();
+    let plain = postcard::to_allocvec(&messages).unwrap();
+
+    let mut scratch = [0u8; 512];
+    let mut out = [0u8; 512];
+    let frame =
+        heatshrink::serialize_into(&messages, &mut scratch, &mut out, Config::DEFAULT).unwrap();
+    assert!(frame.len() < plain.len());
+
+    let mut buf = [0u8; 512];
+    let raw = heatshrink::decompress_into(frame, &mut buf).unwrap();
+    assert_eq!(raw, plain);
+}
+
+#[test]
+fn frames_record_their_config() {
+    let plain = postcard::to_allocvec(&batch()).unwrap();
+
+    for (window, lookahead) in [(4, 3), (8, 4), (11, 4), (15, 8)] {
+        let config = Config::new(window, lookahead).unwrap();
+        let mut out = vec![0u8; plain.len() * 2];
+        let frame = heatshrink::compress_into(&plain, &mut out, config).unwrap();
+        assert_eq!(
+            heatshrink::decompress(frame).unwrap(),
+            plain,
+            "{window} {lookahead}"
+        );
+    }
+
+    assert!(Config::new(3, 2).is_none());
+    assert!(Config::new(16, 4).is_none());
+    assert!(Config::new(8, 8).is_none());
+    assert!(Config::new(15, 9).is_none());
+}
+
+#[test]
+fn small_buffers_are_reported() {
+    let plain = postcard::to_allocvec(&batch()).unwrap();
+    let mut out = [0u8; 512];
+    let frame = heatshrink::compress_into(&plain, &mut out, Config::DEFAULT).unwrap();
+
+    let mut short = [0u8; 4];
+    assert!(matches!(
+        heatshrink::compress_into(&plain, &mut short, Config::DEFAULT),
+        Err(Error::OutputFull)
+    ));
+    assert!(matches!(
+        heatshrink::decompress_into(frame, &mut short),
+        Err(Error::OutputFull)
+    ));
+    assert!(matches!(
+        heatshrink::decompress(&[]),
+        Err(Error::InvalidHeader)
+    ));
+    assert!(matches!(
+        heatshrink::decompress(&[0x00, 0x12]),
+        Err(Error::InvalidHeader)
+    ));
+}
@@ -0,0 +1,514 @@
+#![cfg(feature = "host")]
+
+use std::{
+    io::Cursor,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use tracing::{info, info_span};
+use tracing_core::{
+    span::{Attributes, Id, Record},
+    subscriber::Interest,
+    Event, Metadata, Subscriber,
+};
+use tracing_serde_structured::{
+    host::{self, Decoder, Error, Merger, Message, WallClock},
+    time::{TickRate, TimeSync},
+    wire::{
+        delta::{DeltaEncoder, SourceFrame, SourceId},
+        SerializeAttributesRef, SerializeEventRef, SerializeMetadataId, StringId, StringTable,
+        TracingWire,
+    },
+    AsSerde, CowString, Live, SerializeMetadata, SerializeRecordFields,
+};
+
+/// Sends everything as compactly as possible, the way a device would, with
+/// one tick per message as the timestamp.
+#[derive(Default)]
+struct DeviceSubscriber {
+    next_id: AtomicU64,
+    state: Mutex<(DeltaEncoder, u64, StringTable)>,
+    stream: Mutex<Vec<u8>>,
+    expected: Mutex<Vec<serde_json::Value>>,
+}
+
+impl DeviceSubscriber {
+    fn send(&self, encoder: &mut DeltaEncoder, ticks: &mut u64, msg: TracingWire<'_, Live>) {
+        *ticks += 1;
+        let frame = encoder.encode(*ticks, msg);
+        let mut buf = [0u8; 512];
+        let bytes = postcard::to_slice_cobs(&frame, &mut buf).unwrap();
+        self.stream.lock().unwrap().extend_from_slice(bytes);
+    }
+}
+
+impl Subscriber for DeviceSubscriber {
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        let (encoder, ticks, _) = &mut *self.state.lock().unwrap();
+        self.send(
+            encoder,
+            ticks,
+            TracingWire::DefineMetadata {
+                id: SerializeMetadataId::of(metadata),
+                metadata: SerializeMetadata::from_static(metadata),
+            },
+        );
+        Interest::always()
+    }
+
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+        let id = Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed) + 1);
+        let (encoder, ticks, _) = &mut *self.state.lock().unwrap();
+        self.send(
+            encoder,
+            ticks,
+            TracingWire::NewSpanRef {
+                id: id.as_serde(),
+                attributes: SerializeAttributesRef::new(attrs),
+            },
+        );
+        id
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let (encoder, ticks, table) = &mut *self.state.lock().unwrap();
+        if event.fields().any(|f| f.name() == "interned") {
+            let mut defines = Vec::new();
+            let interned = table.event(event, |define| defines.push(define));
+            for define in defines {
+                self.send(encoder, ticks, define.into());
+            }
+            self.send(encoder, ticks, TracingWire::EventInterned(interned));
+        } else {
+            self.send(
+                encoder,
+                ticks,
+                TracingWire::EventRef(SerializeEventRef::new(event)),
+            );
+        }
+        let json = serde_json::to_value(event.as_serde()).unwrap();
+        self.expected.lock().unwrap().push(json);
+    }
+
+    fn enter(&self, id: &Id) {
+        let (encoder, ticks, _) = &mut *self.state.lock().unwrap();
+        self.send(encoder, ticks, TracingWire::Enter(id.as_serde()));
+    }
+
+    fn exit(&self, _: &Id) {}
+}
+
+#[test]
+fn decodes_compact_device_streams() {
+    let subscriber = Arc::new(DeviceSubscriber::default());
+    tracing::subscriber::with_default(subscriber.clone(), || {
+        let span = info_span!("request", path = "/index.html");
+        let _guard = span.enter();
+        info!(user = "ferris", "logging in");
+        info!(interned = true, attempt = 2u64, "logging in");
+    });
+    let stream = std::mem::take(&mut *subscriber.stream.lock().unwrap());
+    let expected = std::mem::take(&mut *subscriber.expected.lock().unwrap());
+
+    let mut messages = Decoder::new(TickRate::hz(1_000)).read(Cursor::new(stream));
+    let decoded: Vec<_> = messages.by_ref().map(Result::unwrap).collect();
+
+    let mut events = Vec::new();
+    let mut last = 0;
+    for msg in &decoded {
+        assert!(msg.ticks > last);
+        assert_eq!(msg.since_start, Duration::from_millis(msg.ticks));
+        last = msg.ticks;
+        match &msg.msg {
+            TracingWire::NewSpan { id, attributes } => {
+                assert_eq!(id.id.get(), 1);
+                assert_eq!(attributes.metadata.name.as_str(), "request");
+            }
+            TracingWire::Enter(id) => assert_eq!(id.id.get(), 1),
+            TracingWire::Event(event) => events.push(serde_json::to_value(event).unwrap()),
+            msg => panic!("unexpected message {msg:?}"),
+        }
+    }
+    assert_eq!(events, expected);
+    assert!(!messages.decoder().dictionary().is_empty());
+}
+
+#[test]
+fn skips_bad_frames() {
+    let mut encoder = DeltaEncoder::new();
+    let mut stream = Vec::new();
+    let mut buf = [0u8; 64];
+    let id = |id: u64| Id::from_u64(id).as_serde();
+
+    stream.extend_from_slice(
+        postcard::to_slice_cobs(&encoder.encode(10, TracingWire::Enter(id(3))), &mut buf).unwrap(),
+    );
+    stream.extend_from_slice(&[0xFF, 0xFF, 0x00]);
+    stream.extend_from_slice(
+        postcard::to_slice_cobs(&encoder.encode(20, TracingWire::Exit(id(3))), &mut buf).unwrap(),
+    );
+    stream.extend_from_slice(
+        postcard::to_slice_cobs(
+            &encoder.encode(
+                30,
+                TracingWire::EventRef(SerializeEventRef {
+                    fields: SerializeRecordFields::De(Default::default()),
+                    metadata: SerializeMetadataId { id: 1 },
+                    parent: None,
+                }),
+            ),
+            &mut buf,
+        )
+        .unwrap(),
+    );
+    // An incomplete last frame is dropped.
+    stream.extend_from_slice(&[0x05, 0x01]);
+
+    let results: Vec<_> = Decoder::new(TickRate::hz(10))
+        .read(Cursor::new(stream))
+        .collect();
+    assert_eq!(results.len(), 4);
+    assert!(matches!(results[0], Ok(ref m) if m.ticks == 10));
+    assert!(matches!(results[1], Err(Error::Postcard(_))));
+    let exit = results[2].as_ref().unwrap();
+    assert_eq!(exit.since_start, Duration::from_secs(2));
+    assert!(matches!(exit.msg, TracingWire::Exit(_)));
+    assert!(matches!(results[3], Err(Error::Undefined)));
+}
+
+#[test]
+fn skips_unknown_messages() {
+    let mut encoder = DeltaEncoder::new();
+    let mut stream = Vec::new();
+    let mut buf = [0u8; 64];
+
+    // A message from a later version, 5 ticks in.
+    stream.extend_from_slice(&postcard::to_allocvec_cobs(&[10u8, 40, 1, 2, 3]).unwrap());
+    encoder.encode(5, TracingWire::Unknown);
+    stream.extend_from_slice(
+        postcard::to_slice_cobs(
+            &encoder.encode(20, TracingWire::Enter(Id::from_u64(3).as_serde())),
+            &mut buf,
+        )
+        .unwrap(),
+    );
+
+    let results: Vec<_> = Decoder::new(TickRate::hz(10))
+        .read(Cursor::new(stream))
+        .collect();
+    assert_eq!(results.len(), 1);
+    let enter = results[0].as_ref().unwrap();
+    assert_eq!(enter.ticks, 20);
+    assert!(matches!(enter.msg, TracingWire::Enter(_)));
+}
+
+#[test]
+fn merges_sources_by_timestamp() {
+    let mut encoders = [DeltaEncoder::new(), DeltaEncoder::new()];
+    let mut buf = [0u8; 64];
+    let mut frame = |source: u8, ticks: u64| {
+        let msg = TracingWire::Enter(Id::from_u64(u64::from(source) + 1).as_serde());
+        let frame = SourceFrame {
+            source: SourceId { id: source },
+            frame: encoders[usize::from(source)].encode(ticks, msg),
+        };
+        postcard::to_slice_cobs(&frame, &mut buf).unwrap().to_vec()
+    };
+
+    // Core 1 buffers its frames, and sends them after core 0's.
+    let mut stream = Vec::new();
+    for ticks in [1, 3, 4, 8] {
+        stream.extend(frame(0, ticks));
+    }
+    for ticks in [2, 5, 6] {
+        stream.extend(frame(1, ticks));
+    }
+
+    let mut merger = Merger::new(TickRate::hz(1));
+    merger.add(SourceId { id: 1 });
+    let merged: Vec<_> = merger
+        .read(Cursor::new(stream))
+        .map(|msg| {
+            let msg = msg.unwrap();
+            (msg.source.id, msg.message.ticks)
+        })
+        .collect();
+    assert_eq!(
+        merged,
+        [(0, 1), (1, 2), (0, 3), (0, 4), (1, 5), (1, 6), (0, 8)]
+    );
+}
+
+#[test]
+fn merger_waits_for_every_source() {
+    let mut encoders = [DeltaEncoder::new(), DeltaEncoder::new()];
+    let mut frame = |source: u8, ticks: u64| SourceFrame {
+        source: SourceId { id: source },
+        frame: encoders[usize::from(source)]
+            .encode(ticks, TracingWire::Exit(Id::from_u64(1).as_serde())),
+    };
+
+    let mut merger = Merger::new(TickRate::hz(1));
+    merger.decode(frame(0, 5)).unwrap();
+    merger.decode(frame(1, 3)).unwrap();
+    assert_eq!(merger.pop().unwrap().message.ticks, 3);
+    // Source 1 could still send something before tick 5.
+    assert!(merger.pop().is_none());
+
+    merger.remove(SourceId { id: 1 });
+    assert_eq!(merger.pop().unwrap().message.ticks, 5);
+    assert!(merger.drain().next().is_none());
+}
+
+#[test]
+fn mid_stream_decoders_wait_for_sync() {
+    let name = StringId { id: 0 };
+    let define = |value| TracingWire::DefineString {
+        id: name,
+        value: CowString::Borrowed(value),
+    };
+    let enter = TracingWire::Enter(Id::from_u64(1).as_serde());
+    let exit = TracingWire::Exit(Id::from_u64(1).as_serde());
+
+    let mut encoder = DeltaEncoder::new();
+    encoder.set_sync_interval(Some(2));
+    let mut stream = Vec::new();
+    let mut send = |encoder: &mut DeltaEncoder, ticks, msg| {
+        let mut buf = [0u8; 64];
+        let frame = encoder.encode_with_sync(ticks, msg, |sync| {
+            stream.extend_from_slice(postcard::to_slice_cobs(&sync, &mut buf).unwrap());
+        });
+        stream.extend_from_slice(postcard::to_slice_cobs(&frame, &mut buf).unwrap());
+    };
+    send(&mut encoder, 1, define("before"));
+    send(&mut encoder, 2, enter);
+    // The device starts its definitions over.
+    encoder.set_generation(1);
+    send(&mut encoder, 3, exit);
+    send(&mut encoder, 4, define("after"));
+
+    // Skip the first sync frame, as if the port was opened late.
+    let start = stream.iter().position(|b| *b == 0).unwrap() + 1;
+    let mut messages = Decoder::mid_stream(TickRate::hz(1)).read(Cursor::new(&stream[start..]));
+    assert!(matches!(messages.next(), Some(Err(Error::Delta))));
+    assert!(matches!(messages.next(), Some(Err(Error::Delta))));
+    assert_eq!(messages.next().unwrap().unwrap().ticks, 3);
+    assert!(messages.next().is_none());
+    assert_eq!(messages.decoder().dictionary().string(name), Some("after"));
+
+    // A decoder that saw the whole stream drops the definitions of the first
+    // generation when the second one starts.
+    let mut messages = Decoder::new(TickRate::hz(1)).read(Cursor::new(&stream[..]));
+    assert_eq!(messages.next().unwrap().unwrap().ticks, 2);
+    assert_eq!(messages.decoder().dictionary().string(name), Some("before"));
+    assert_eq!(messages.next().unwrap().unwrap().ticks, 3);
+    assert_eq!(messages.decoder().dictionary().string(name), None);
+}
+
+fn at(nanos: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_nanos(nanos)
+}
+
+#[test]
+fn wall_clock_corrects_drift() {
+    const NOON: u64 = 1_700_000_000_000_000_000;
+    // A 1 kHz clock that runs 100 ppm fast.
+    let sync = |ticks: u64| TimeSync {
+        device_ticks: ticks,
+        wall_clock: NOON + ticks * 999_900,
+    };
+
+    let mut clock = WallClock::new(TickRate::hz(1_000));
+    assert_eq!(clock.to_wall(0), None);
+
+    clock.observe(sync(1_000));
+    assert_eq!(clock.drift_ppm(), None);
+    // One sync: the nominal rate.
+    assert_eq!(
+        clock.to_wall(2_000),
+        Some(at(NOON + 999_900_000 + 1_000_000_000))
+    );
+
+    for ticks in [2_000, 10_000, 100_000] {
+        clock.observe(sync(ticks));
+    }
+    let drift = clock.drift_ppm().unwrap();
+    assert!((drift - 100.0).abs() < 0.1, "{drift}");
+    let wall = clock.to_wall(1_000_000).unwrap();
+    let expected = at(NOON + 1_000_000 * 999_900);
+    let error = wall
+        .duration_since(expected)
+        .unwrap_or_else(|e| e.duration());
+    assert!(error < Duration::from_micros(10), "{error:?}");
+
+    // The device restarted.
+    clock.observe(TimeSync {
+        device_ticks: 5,
+        wall_clock: NOON,
+    });
+    assert_eq!(clock.drift_ppm(), None);
+    assert_eq!(clock.to_wall(5), Some(at(NOON)));
+}
+
+#[test]
+fn wall_clock_ignores_times_out_of_range() {
+    let mut clock = WallClock::new(TickRate::hz(1));
+    clock.observe(TimeSync {
+        device_ticks: 0,
+        wall_clock: 0,
+    });
+    clock.observe(TimeSync {
+        device_ticks: 1,
+        wall_clock: u64::MAX,
+    });
+    assert_eq!(clock.to_wall(u64::MAX), None);
+}
+
+#[test]
+fn decoders_timestamp_messages_with_time_syncs() {
+    let mut encoder = DeltaEncoder::new();
+    let mut decoder = Decoder::new(TickRate::hz(1));
+    let enter = || TracingWire::Enter(Id::from_u64(1).as_serde());
+
+    let msg = decoder.decode(encoder.encode(1, enter())).unwrap().unwrap();
+    assert_eq!(msg.wall_clock, None);
+
+    let sync = TracingWire::TimeSync(TimeSync {
+        device_ticks: 2,
+        wall_clock: 1_000_000_000_000,
+    });
+    assert!(decoder.decode(encoder.encode(2, sync)).unwrap().is_none());
+    let msg = decoder.decode(encoder.encode(5, enter())).unwrap().unwrap();
+    assert_eq!(msg.wall_clock, Some(at(1_003_000_000_000)));
+}
+
+#[test]
+fn merger_shares_time_syncs_between_sources() {
+    let mut encoders = [DeltaEncoder::new(), DeltaEncoder::new()];
+    let mut frame = |source: u8, ticks: u64, msg| SourceFrame {
+        source: SourceId { id: source },
+        frame: encoders[usize::from(source)].encode(ticks, msg),
+    };
+    let exit = || TracingWire::Exit(Id::from_u64(1).as_serde());
+
+    let mut merger = Merger::new(TickRate::hz(1));
+    merger.decode(frame(1, 1, exit())).unwrap();
+    merger
+        .decode(frame(
+            0,
+            2,
+            TracingWire::TimeSync(TimeSync {
+                device_ticks: 2,
+                wall_clock: 10_000_000_000,
+            }),
+        ))
+        .unwrap();
+    merger.decode(frame(0, 3, exit())).unwrap();
+
+    let merged: Vec<_> = merger
+        .drain()
+        .map(|msg| (msg.source.id, msg.message.wall_clock))
+        .collect();
+    assert_eq!(
+        merged,
+        [(1, Some(at(9_000_000_000))), (0, Some(at(11_000_000_000)))]
+    );
+}
+
+fn message(since_start: u64, wall_clock: Option<u64>) -> Result<Message, Error> {
+    Ok(Message {
+        ticks: since_start,
+        since_start: Duration::from_secs(since_start),
+        wall_clock: wall_clock.map(|s| at(s * 1_000_000_000)),
+        msg: TracingWire::Exit(Id::from_u64(1).as_serde()),
+    })
+}
+
+#[test]
+fn merges_streams_by_timestamp() {
+    let a = vec![message(1, None), message(4, None), message(5, None)];
+    let b = vec![message(2, None), message(3, None), message(9, None)];
+    let merged: Vec<_> = host::merge([("a", a), ("b", b)])
+        .map(|msg| {
+            let msg = msg.unwrap();
+            (msg.stream, msg.message.ticks)
+        })
+        .collect();
+    assert_eq!(
+        merged,
+        [("a", 1), ("b", 2), ("b", 3), ("a", 4), ("a", 5), ("b", 9)]
+    );
+}
+
+#[test]
+fn merges_streams_by_wall_clock() {
+    // The second device started later, so its clock is behind.
+    let a = vec![message(10, Some(100)), message(20, Some(110))];
+    let b = vec![
+        message(1, None),
+        message(2, Some(105)),
+        message(3, Some(115)),
+    ];
+    let merged: Vec<_> = host::merge([("a", a), ("b", b)])
+        .by_wall_clock()
+        .map(|msg| {
+            let msg = msg.unwrap();
+            (msg.stream, msg.message.ticks)
+        })
+        .collect();
+    assert_eq!(merged, [("b", 1), ("a", 10), ("b", 2), ("a", 20), ("b", 3)]);
+}
+
+#[test]
+fn merge_reorders_within_window() {
+    let a = || Vec::from([1, 3, 2, 6].map(|s| message(s, None)));
+    let b = || Vec::from([4, 8].map(|s| message(s, None)));
+
+    let mut merged = host::merge([("a", a()), ("b", b())]);
+    merged.set_window(Duration::from_secs(2));
+    let ticks: Vec<_> = merged.map(|msg| msg.unwrap().message.ticks).collect();
+    assert_eq!(ticks, [1, 2, 3, 4, 6, 8]);
+
+    // Without a window, the late message is returned when it is read.
+    let ticks: Vec<_> = host::merge([("a", a()), ("b", b())])
+        .map(|msg| msg.unwrap().message.ticks)
+        .collect();
+    assert_eq!(ticks, [1, 3, 2, 4, 6, 8]);
+}
+
+#[test]
+fn merge_buffers_at_most_its_capacity() {
+    let a = vec![message(1, None), message(2, None), message(3, None)];
+    let b = vec![message(10, None)];
+    let mut merged = host::merge([("a", a), ("b", b)]);
+    merged.set_capacity(1);
+    merged.set_window(Duration::from_secs(100));
+    assert_eq!(merged.next().unwrap().unwrap().message.ticks, 1);
+    assert_eq!(merged.pending(), 0);
+}
+
+#[test]
+fn merge_tags_errors_with_their_stream() {
+    let a = vec![message(1, None), Err(Error::Delta), message(2, None)];
+    let merged: Vec<_> = host::merge([("a", a)]).collect();
+    assert_eq!(merged.len(), 3);
+    let error = merged[1].as_ref().unwrap_err();
+    assert_eq!(error.stream, "a");
+    assert!(matches!(error.error, Error::Delta));
+    assert_eq!(
+        error.to_string(),
+        "a: frame does not follow from the previous frame"
+    );
+}
@@ -0,0 +1,198 @@
+#![cfg(feature = "http")]
+
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use flate2::read::GzDecoder;
+use tracing_serde_structured::{
+    http::{ContentEncoding, Encoding, Error, HttpExporter},
+    wire::SerializePipelineStats,
+};
+
+/// A request as the server received it.
+#[derive(Debug)]
+struct Request {
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The lines of a gzip compressed NDJSON body.
+    fn lines(&self) -> Vec<String> {
+        let mut body = String::new();
+        GzDecoder::new(&self.body[..])
+            .read_to_string(&mut body)
+            .unwrap();
+        body.lines().map(str::to_owned).collect()
+    }
+}
+
+/// A server answering one request after the other with `statuses`, and
+/// returning the requests it received.
+fn server(statuses: &'static [u16]) -> (String, JoinHandle<Vec<Request>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/intake", listener.local_addr().unwrap());
+    let server = thread::spawn(move || {
+        statuses
+            .iter()
+            .map(|status| {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                assert!(line.starts_with("POST /intake "), "{line}");
+                let mut headers = Vec::new();
+                loop {
+                    line.clear();
+                    reader.read_line(&mut line).unwrap();
+                    let Some((name, value)) = line.trim_end().split_once(": ") else {
+                        break;
+                    };
+                    headers.push((name.to_owned(), value.to_owned()));
+                }
+                let request = Request {
+                    headers,
+                    body: Vec::new(),
+                };
+                let len = request.header("content-length").unwrap().parse().unwrap();
+                let mut body = vec![0; len];
+                reader.read_exact(&mut body).unwrap();
+                write!(
+                    reader.get_mut(),
+                    "HTTP/1.1 {status} Whatever\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                )
+                .unwrap();
+                Request { body, ..request }
+            })
+            .collect()
+    });
+    (url, server)
+}
+
+/// An address nothing listens on.
+fn unreachable() -> SocketAddr {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+}
+
+#[test]
+fn full_batches_are_posted() {
+    let (url, server) = server(&[200, 204]);
+    let mut exporter = HttpExporter::new(url)
+        .with_header("Authorization", "Bearer secret")
+        .with_batch_size(2);
+    for msg in ["one", "two", "three"] {
+        exporter.send(msg).unwrap();
+    }
+    assert_eq!(exporter.pending(), 1);
+    assert_eq!(exporter.stats().sent, 2);
+    exporter.flush().unwrap();
+    assert_eq!(exporter.pending(), 0);
+    assert_eq!(exporter.stats().sent, 3);
+
+    let requests = server.join().unwrap();
+    assert_eq!(
+        requests[0].header("content-type"),
+        Some("application/x-ndjson")
+    );
+    assert_eq!(requests[0].header("content-encoding"), Some("gzip"));
+    assert_eq!(requests[0].header("authorization"), Some("Bearer secret"));
+    assert_eq!(requests[0].lines(), ["\"one\"", "\"two\""]);
+    assert_eq!(requests[1].lines(), ["\"three\""]);
+}
+
+#[test]
+fn postcard_batches_are_cobs_framed() {
+    let (url, server) = server(&[200]);
+    let mut exporter = HttpExporter::new(url)
+        .with_encoding(Encoding::Postcard)
+        .with_content_encoding(ContentEncoding::Identity);
+    exporter.send("one").unwrap();
+    exporter.send("two").unwrap();
+    exporter.flush().unwrap();
+
+    let requests = server.join().unwrap();
+    assert_eq!(requests[0].header("content-encoding"), None);
+    let mut body = requests[0].body.clone();
+    let frames: Vec<String> = body
+        .split_mut(|b| *b == 0)
+        .filter(|frame| !frame.is_empty())
+        .map(|frame| postcard::from_bytes_cobs(frame).unwrap())
+        .collect();
+    assert_eq!(frames, ["one", "two"]);
+}
+
+#[test]
+fn failed_batches_are_sent_again() {
+    let (url, server) = server(&[503, 200, 200]);
+    let mut exporter = HttpExporter::new(url)
+        .with_batch_size(1)
+        .with_backoff(Duration::ZERO, Duration::ZERO);
+    let err = exporter.send("first").unwrap_err();
+    assert!(matches!(err, Error::Status(503)));
+    assert!(err.is_retried());
+    assert_eq!(exporter.queued(), 1);
+
+    exporter.send("second").unwrap();
+    assert_eq!(exporter.queued(), 0);
+    assert_eq!(exporter.stats().sent, 2);
+
+    let requests = server.join().unwrap();
+    assert_eq!(requests[0].lines(), ["\"first\""]);
+    assert_eq!(requests[1].lines(), ["\"first\""]);
+    assert_eq!(requests[2].lines(), ["\"second\""]);
+}
+
+#[test]
+fn rejected_batches_are_dropped() {
+    let (url, server) = server(&[400]);
+    let mut exporter = HttpExporter::new(url);
+    exporter.send("bad").unwrap();
+    let err = exporter.flush().unwrap_err();
+    assert!(matches!(err, Error::Status(400)));
+    assert!(!err.is_retried());
+    assert_eq!(exporter.queued(), 0);
+    assert_eq!(exporter.stats().dropped, 1);
+    server.join().unwrap();
+}
+
+#[test]
+fn queued_batches_are_bounded() {
+    let mut exporter = HttpExporter::new(format!("http://{}", unreachable()))
+        .with_content_encoding(ContentEncoding::Identity)
+        .with_batch_size(1)
+        .with_max_buffered(32)
+        .with_backoff(Duration::from_secs(3600), Duration::from_secs(3600));
+    assert!(matches!(
+        exporter.send("0123456789").unwrap_err(),
+        Error::Http(_)
+    ));
+    // No attempt is made to send again until the backoff has passed.
+    for _ in 0..3 {
+        exporter.send("0123456789").unwrap();
+    }
+    // Each batch takes 13 bytes, so only two fit.
+    assert_eq!(exporter.queued(), 2);
+    assert_eq!(
+        exporter.stats(),
+        SerializePipelineStats {
+            sent: 0,
+            dropped: 2,
+            overflows: 0,
+            errors: 0,
+        }
+    );
+}
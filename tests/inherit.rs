@@ -0,0 +1,191 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+
+use serde_json::{json, Value};
+use tracing::{info, info_span, warn};
+use tracing_core::{
+    span::{Attributes, Id, Record},
+    Event, Metadata, Subscriber,
+};
+use tracing_serde_structured::{
+    inherit::FieldInheritance, SerializeEvent, SerializeFieldSet, SerializeRecordFields,
+    SerializeValue,
+};
+
+struct InheritingSubscriber {
+    inheritance: FieldInheritance,
+    next_id: AtomicU64,
+    stack: Mutex<Vec<Id>>,
+    json: Mutex<Vec<Value>>,
+    postcard: Mutex<Vec<Vec<u8>>>,
+    owned: Mutex<Vec<SerializeEvent<'static>>>,
+}
+
+impl InheritingSubscriber {
+    fn new(inheritance: FieldInheritance) -> Self {
+        Self {
+            inheritance,
+            next_id: AtomicU64::new(1),
+            stack: Mutex::default(),
+            json: Mutex::default(),
+            postcard: Mutex::default(),
+            owned: Mutex::default(),
+        }
+    }
+
+    fn current(&self) -> Option<Id> {
+        self.stack.lock().unwrap().last().cloned()
+    }
+}
+
+impl Subscriber for InheritingSubscriber {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+        let id = Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed));
+        self.inheritance
+            .new_span(&id, attrs, self.current().as_ref());
+        id
+    }
+
+    fn record(&self, id: &Id, values: &Record<'_>) {
+        self.inheritance.record(id, values);
+    }
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let event = self.inheritance.event(event, self.current().as_ref());
+        self.json
+            .lock()
+            .unwrap()
+            .push(serde_json::to_value(&event).unwrap());
+        self.postcard
+            .lock()
+            .unwrap()
+            .push(postcard::to_allocvec(&event).unwrap());
+        self.owned.lock().unwrap().push(event.to_owned());
+    }
+
+    fn enter(&self, id: &Id) {
+        self.stack.lock().unwrap().push(id.clone());
+    }
+
+    fn exit(&self, id: &Id) {
+        let mut stack = self.stack.lock().unwrap();
+        if let Some(i) = stack.iter().rposition(|s| s == id) {
+            stack.remove(i);
+        }
+    }
+
+    fn try_close(&self, id: Id) -> bool {
+        self.inheritance.close(&id);
+        true
+    }
+}
+
+#[test]
+fn events_inherit_selected_fields_of_their_spans() {
+    let subscriber = Arc::new(InheritingSubscriber::new(FieldInheritance::new([
+        "request_id",
+        "tenant",
+    ])));
+    tracing::subscriber::with_default(subscriber.clone(), || {
+        let request = info_span!(
+            "request",
+            request_id = 7u64,
+            tenant = "acme",
+            user = 1u64,
+            attempt = tracing::field::Empty
+        );
+        let _request = request.enter();
+        info!("received");
+
+        let query = info_span!("query", tenant = "beta", request_id = tracing::field::Empty);
+        query.in_scope(|| {
+            warn!(request_id = 8u64, "slow");
+        });
+        query.record("request_id", 9u64);
+        query.in_scope(|| info!("done"));
+        drop(query);
+
+        info!(parent: None, "unrelated");
+    });
+
+    let json = subscriber.json.lock().unwrap();
+    let fields: Vec<&Value> = json.iter().map(|event| &event["fields"]).collect();
+    assert_eq!(
+        fields,
+        [
+            &json!({ "message": { "Debug": "received" }, "request_id": { "U64": 7 }, "tenant": { "Str": "acme" } }),
+            &json!({ "message": { "Debug": "slow" }, "request_id": { "U64": 8 }, "tenant": { "Str": "beta" } }),
+            &json!({ "message": { "Debug": "done" }, "request_id": { "U64": 9 }, "tenant": { "Str": "beta" } }),
+            &json!({ "message": { "Debug": "unrelated" } }),
+        ]
+    );
+    assert_eq!(json[0]["metadata"]["level"], "INFO");
+
+    // Formats that are not self-describing need the right number of fields.
+    let postcard = subscriber.postcard.lock().unwrap();
+    let decoded: SerializeEvent<'_> = postcard::from_bytes(&postcard[2]).unwrap();
+    let SerializeRecordFields::De(fields) = &decoded.fields else {
+        panic!("decoded fields are deserialized fields");
+    };
+    assert_eq!(fields.len(), 3);
+
+    let owned = subscriber.owned.lock().unwrap();
+    let SerializeRecordFields::De(fields) = &owned[0].fields else {
+        panic!("owned fields are deserialized fields");
+    };
+    assert_eq!(fields[&"request_id".into()], SerializeValue::U64(7));
+    let SerializeFieldSet::De(names) = &owned[0].metadata.fields else {
+        panic!("owned metadata has deserialized fields");
+    };
+    let names: Vec<&str> = names.iter().map(|name| name.as_str()).collect();
+    assert_eq!(names, ["message", "request_id", "tenant"]);
+
+    // Closed spans are forgotten.
+    assert!(subscriber.inheritance.is_empty());
+}
+
+#[test]
+fn correlation_ids_come_from_the_root() {
+    let subscriber = Arc::new(InheritingSubscriber::new(FieldInheritance::correlation_id(
+        "request_id",
+    )));
+    tracing::subscriber::with_default(subscriber.clone(), || {
+        let request = info_span!("request", request_id = tracing::field::Empty);
+        let _request = request.enter();
+        let query = info_span!("query", request_id = 8u64);
+        let _query = query.enter();
+
+        // Recorded on the root after its children were created, the id
+        // still reaches them, while ids recorded on them are ignored.
+        request.record("request_id", 7u64);
+        query.record("request_id", 9u64);
+        info!("nested");
+        warn!(request_id = 1u64, "own");
+
+        let other = info_span!(parent: None, "other", request_id = 2u64);
+        other.in_scope(|| info!("other"));
+    });
+
+    let json = subscriber.json.lock().unwrap();
+    let ids: Vec<&Value> = json
+        .iter()
+        .map(|event| &event["fields"]["request_id"])
+        .collect();
+    assert_eq!(
+        ids,
+        [
+            &json!({ "U64": 7 }),
+            &json!({ "U64": 1 }),
+            &json!({ "U64": 2 })
+        ]
+    );
+    assert!(subscriber.inheritance.is_empty());
+}
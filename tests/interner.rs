@@ -0,0 +1,75 @@
+#![cfg(feature = "interner")]
+
+use std::sync::Arc;
+
+use tracing_serde_structured::{
+    interner::Interner, CowString, SerializeEvent, SerializeRecordFields, SerializeValue,
+};
+
+type RecordMap = std::collections::BTreeMap<CowString<'static>, SerializeValue<'static>>;
+
+const JSON: &str = r#"{
+    "fields": {"message": {"Str": "hello"}, "request_id": {"Str": "abc"}},
+    "metadata": {
+        "name": "event src/main.rs:10",
+        "target": "my_app::handlers",
+        "level": "INFO",
+        "module_path": "my_app::handlers",
+        "file": "src/main.rs",
+        "line": 10,
+        "fields": ["message", "request_id"],
+        "is_span": false,
+        "is_event": true
+    },
+    "parent": null
+}"#;
+
+fn shared(s: &CowString<'_>) -> *const u8 {
+    match s {
+        CowString::Copied(_) => s.as_ptr(),
+        other => panic!("not copied: {other:?}"),
+    }
+}
+
+#[test]
+fn owned_events_share_names() {
+    let a: SerializeEvent<'_> = serde_json::from_str(JSON).unwrap();
+    let b: SerializeEvent<'_> = serde_json::from_str(JSON).unwrap();
+    let (a, b) = (a.to_owned(), b.to_owned());
+
+    assert_eq!(shared(&a.metadata.target), shared(&b.metadata.target));
+    #[cfg(not(feature = "strip-locations"))]
+    assert_eq!(
+        shared(a.metadata.module_path.as_ref().unwrap()),
+        shared(&a.metadata.target)
+    );
+
+    let (SerializeRecordFields::De(fa), SerializeRecordFields::De(fb)) = (&a.fields, &b.fields)
+    else {
+        panic!("fields were not deserialized");
+    };
+    for (ka, kb) in fa.keys().zip(fb.keys()) {
+        assert_eq!(shared(ka), shared(kb));
+    }
+
+    // Values are copied, not interned.
+    let values = |fields: &RecordMap| match fields.values().next() {
+        Some(SerializeValue::Str(s)) => shared(s),
+        other => panic!("unexpected {other:?}"),
+    };
+    assert_ne!(values(fa), values(fb));
+}
+
+#[test]
+fn purge_drops_unused_strings() {
+    let interner = Interner::new();
+    let kept = interner.intern("kept");
+    let again = interner.intern("kept");
+    assert!(Arc::ptr_eq(&kept, &again));
+    drop(interner.intern("dropped"));
+    assert_eq!(interner.len(), 2);
+
+    assert_eq!(interner.purge(), 1);
+    assert_eq!(interner.len(), 1);
+    assert!(Arc::ptr_eq(&interner.intern("kept"), &kept));
+}
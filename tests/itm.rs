@@ -0,0 +1,12 @@
+#![cfg(feature = "itm")]
+
+use tracing_serde_structured::{embedded_io::FramedWriter, itm::ItmWriter};
+
+fn assert_write<W: embedded_io::Write>() {}
+
+#[test]
+fn itm_writer_is_a_sink() {
+    assert_write::<ItmWriter<'static>>();
+    assert_write::<&mut ItmWriter<'static>>();
+    let _: Option<FramedWriter<ItmWriter<'static>, 64>> = None;
+}
@@ -0,0 +1,58 @@
+#![cfg(feature = "serde-json")]
+
+use serde_json::json;
+use tracing_serde_structured::{
+    builder::{SerializeAttributesBuilder, SerializeEventBuilder},
+    wire::TracingWire,
+    SerializeId, SerializeLevel,
+};
+
+#[test]
+fn event_to_json() {
+    let event = SerializeEventBuilder::new()
+        .target("my_app")
+        .field("message", "hello")
+        .build();
+
+    let value = event.to_json_value().unwrap();
+    assert_eq!(value, serde_json::to_value(&event).unwrap());
+    assert_eq!(value["metadata"]["target"], "my_app");
+    assert_eq!(value["fields"]["message"], json!({ "Str": "hello" }));
+
+    let pretty = event.to_json_string_pretty().unwrap();
+    assert!(pretty.contains('\n'));
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&pretty).unwrap(),
+        value
+    );
+}
+
+#[test]
+fn attributes_to_json() {
+    let attributes = SerializeAttributesBuilder::new("request")
+        .level(SerializeLevel::Debug)
+        .field("id")
+        .root()
+        .build();
+
+    let value = attributes.to_json_value().unwrap();
+    assert_eq!(value["metadata"]["name"], "request");
+    assert_eq!(value["metadata"]["fields"], json!(["id"]));
+    assert_eq!(value, serde_json::to_value(&attributes).unwrap());
+}
+
+#[test]
+fn wire_to_json() {
+    let msg: TracingWire<'_> = TracingWire::Enter(SerializeId {
+        id: 7.try_into().unwrap(),
+    });
+
+    assert_eq!(
+        msg.to_json_value().unwrap(),
+        json!({ "Enter": { "id": 7 } })
+    );
+    assert_eq!(
+        msg.to_json_string_pretty().unwrap(),
+        "{\n  \"Enter\": {\n    \"id\": 7\n  }\n}"
+    );
+}
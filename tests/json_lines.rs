@@ -0,0 +1,74 @@
+#![cfg(feature = "serde-json")]
+
+use std::io;
+
+use tracing_serde_structured::{
+    json_lines::{FlushPolicy, Writer},
+    SerializeLevel,
+};
+
+/// Collects what is written, and counts how often it is flushed.
+#[derive(Debug, Default)]
+struct CountingWriter {
+    out: Vec<u8>,
+    flushes: usize,
+}
+
+impl io::Write for CountingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.out.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flushes += 1;
+        Ok(())
+    }
+}
+
+/// A value that always fails to serialize.
+struct Unserializable;
+
+impl serde::Serialize for Unserializable {
+    fn serialize<S: serde::Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+        Err(serde::ser::Error::custom("nope"))
+    }
+}
+
+#[test]
+fn manual_only_flushes_when_asked() {
+    let mut writer = Writer::new(CountingWriter::default());
+    writer.write(&SerializeLevel::Info).unwrap();
+    writer.write(&SerializeLevel::Warn).unwrap();
+    assert_eq!(writer.get_ref().flushes, 0);
+
+    writer.flush().unwrap();
+    assert_eq!(writer.get_ref().flushes, 1);
+
+    let inner = writer.into_inner();
+    assert_eq!(inner.out, b"\"INFO\"\n\"WARN\"\n");
+    assert_eq!(inner.flushes, 1);
+}
+
+#[test]
+fn every_line_flushes_after_each_line() {
+    let mut writer = Writer::with_flush_policy(CountingWriter::default(), FlushPolicy::EveryLine);
+    writer.write(&SerializeLevel::Info).unwrap();
+    assert_eq!(writer.get_ref().flushes, 1);
+    writer.write(&SerializeLevel::Warn).unwrap();
+    assert_eq!(writer.get_ref().flushes, 2);
+
+    writer.flush().unwrap();
+    assert_eq!(writer.get_ref().flushes, 3);
+    assert_eq!(writer.get_ref().out, b"\"INFO\"\n\"WARN\"\n");
+}
+
+#[test]
+fn failed_lines_are_neither_written_nor_flushed() {
+    for policy in [FlushPolicy::Manual, FlushPolicy::EveryLine] {
+        let mut writer = Writer::with_flush_policy(CountingWriter::default(), policy);
+        assert!(writer.write(&Unserializable).is_err());
+        assert!(writer.get_ref().out.is_empty(), "{policy:?}");
+        assert_eq!(writer.get_ref().flushes, 0, "{policy:?}");
+    }
+}
@@ -0,0 +1,76 @@
+#![cfg(feature = "kafka")]
+
+use std::{collections::BTreeMap, num::NonZeroU64};
+
+use kafka::{client::KafkaClient, producer::Producer};
+use tracing_serde_structured::{
+    kafka::{Encoding, Error, KafkaSink, PartitionKey},
+    CowString, SerializeEvent, SerializeFieldSet, SerializeId, SerializeLevel, SerializeMetadata,
+    SerializeRecordFields, SerializeValue,
+};
+
+fn event() -> SerializeEvent<'static> {
+    let mut fields = BTreeMap::new();
+    fields.insert(CowString::Borrowed("trace_id"), SerializeValue::U64(1234));
+    SerializeEvent {
+        fields: SerializeRecordFields::De(fields),
+        metadata: SerializeMetadata {
+            name: CowString::Borrowed("event src/main.rs:7"),
+            target: CowString::Borrowed("my_crate::db"),
+            level: SerializeLevel::Info,
+            module_path: None,
+            file: None,
+            line: None,
+            fields: SerializeFieldSet::De(vec![CowString::Borrowed("trace_id")]),
+            is_span: false,
+            is_event: true,
+        },
+        parent: Some(SerializeId {
+            id: NonZeroU64::new(9).unwrap(),
+        }),
+    }
+}
+
+#[test]
+fn partition_keys() {
+    let event = event();
+    assert!(PartitionKey::None.key_for(&event).is_empty());
+    assert_eq!(PartitionKey::Target.key_for(&event), b"my_crate::db");
+    assert_eq!(PartitionKey::Parent.key_for(&event), 9u64.to_be_bytes());
+    assert_eq!(
+        PartitionKey::Field("trace_id".into()).key_for(&event),
+        b"1234"
+    );
+    assert!(PartitionKey::Field("missing".into())
+        .key_for(&event)
+        .is_empty());
+}
+
+#[test]
+fn encodings() {
+    let postcard = Encoding::Postcard.encode(&event()).unwrap();
+    let decoded: SerializeEvent<'_> = postcard::from_bytes(&postcard).unwrap();
+    assert_eq!(decoded.metadata.target.as_str(), "my_crate::db");
+
+    let json = Encoding::Json.encode(&event()).unwrap();
+    let decoded: SerializeEvent<'_> = serde_json::from_slice(&json).unwrap();
+    assert_eq!(decoded.metadata.target.as_str(), "my_crate::db");
+}
+
+#[test]
+fn pending_records_are_capped() {
+    // Without metadata for the topic, every batch fails before it is sent.
+    let producer = Producer::from_client(KafkaClient::new(vec![]))
+        .create()
+        .unwrap();
+    let mut sink = KafkaSink::new(producer, "traces").with_max_pending(3);
+    for _ in 0..5 {
+        sink.send_event(&event()).unwrap();
+    }
+    assert_eq!(sink.pending(), 3);
+    assert_eq!(sink.stats().dropped, 2);
+
+    assert!(matches!(sink.flush(), Err(Error::Kafka(_))));
+    assert_eq!(sink.pending(), 3);
+    assert_eq!(sink.stats().sent, 0);
+}
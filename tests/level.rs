@@ -0,0 +1,132 @@
+use tracing_serde_structured::{ParseLevelError, SerializeLevel};
+
+#[test]
+fn postcard_writes_one_byte() {
+    assert_eq!(postcard::to_allocvec(&SerializeLevel::Error).unwrap(), [4]);
+}
+
+#[test]
+fn either_form_deserializes_from_json() {
+    for json in ["\"INFO\"", "2"] {
+        let level: SerializeLevel = serde_json::from_str(json).unwrap();
+        assert_eq!(level, SerializeLevel::Info);
+    }
+
+    assert!(serde_json::from_str::<SerializeLevel>("5").is_err());
+    assert!(serde_json::from_str::<SerializeLevel>("\"LOUD\"").is_err());
+}
+
+#[test]
+fn binary_formats_round_trip() {
+    for level in [
+        SerializeLevel::Trace,
+        SerializeLevel::Debug,
+        SerializeLevel::Info,
+        SerializeLevel::Warn,
+        SerializeLevel::Error,
+    ] {
+        let bytes = postcard::to_allocvec(&level).unwrap();
+        assert_eq!(
+            postcard::from_bytes::<SerializeLevel>(&bytes).unwrap(),
+            level
+        );
+
+        let bytes = bincode::serialize(&level).unwrap();
+        assert_eq!(
+            bincode::deserialize::<SerializeLevel>(&bytes).unwrap(),
+            level
+        );
+    }
+}
+
+#[test]
+fn display_matches_tracing() {
+    for level in [
+        tracing::Level::TRACE,
+        tracing::Level::DEBUG,
+        tracing::Level::INFO,
+        tracing::Level::WARN,
+        tracing::Level::ERROR,
+    ] {
+        let ours = tracing_serde_structured::AsSerde::as_serde(&level);
+        assert_eq!(ours.to_string(), level.to_string());
+        assert_eq!(format!("{ours:>6}"), format!("{level:>6}"));
+    }
+}
+
+#[test]
+fn from_str_ignores_case() {
+    for s in ["warn", "WARN", "Warn"] {
+        assert_eq!(s.parse::<SerializeLevel>(), Ok(SerializeLevel::Warn));
+    }
+    assert_eq!("trace".parse::<SerializeLevel>(), Ok(SerializeLevel::Trace));
+    assert_eq!("error".parse::<SerializeLevel>(), Ok(SerializeLevel::Error));
+
+    let err: ParseLevelError = "loud".parse::<SerializeLevel>().unwrap_err();
+    assert!(err.to_string().contains("INFO"));
+    assert!("".parse::<SerializeLevel>().is_err());
+    assert!("2".parse::<SerializeLevel>().is_err());
+}
+
+#[test]
+fn converts_to_and_from_tracing() {
+    use tracing::level_filters::LevelFilter;
+    use tracing::Level;
+
+    for level in [
+        Level::TRACE,
+        Level::DEBUG,
+        Level::INFO,
+        Level::WARN,
+        Level::ERROR,
+    ] {
+        let ours = SerializeLevel::from(level);
+        assert_eq!(ours.as_str(), level.as_str());
+        assert_eq!(Level::from(ours), level);
+        assert_eq!(LevelFilter::from(ours), LevelFilter::from_level(level));
+        assert_eq!(SerializeLevel::from_filter(ours.into()), Some(ours));
+        assert_eq!(
+            SerializeLevel::to_filter(Some(ours)),
+            LevelFilter::from_level(level)
+        );
+    }
+
+    assert_eq!(SerializeLevel::from_filter(LevelFilter::OFF), None);
+    assert_eq!(SerializeLevel::to_filter(None), LevelFilter::OFF);
+}
+
+#[test]
+fn orders_like_tracing() {
+    use tracing::{level_filters::LevelFilter, Level};
+
+    let levels = [
+        Level::TRACE,
+        Level::DEBUG,
+        Level::INFO,
+        Level::WARN,
+        Level::ERROR,
+    ];
+    for a in levels {
+        for b in levels {
+            let (ours_a, ours_b) = (SerializeLevel::from(a), SerializeLevel::from(b));
+            assert_eq!(ours_a.cmp(&ours_b), a.cmp(&b), "{a} vs {b}");
+        }
+        for filter in [LevelFilter::OFF, LevelFilter::INFO, LevelFilter::TRACE] {
+            let ours = SerializeLevel::from(a);
+            assert_eq!(ours <= filter, a <= filter, "{a} vs {filter}");
+            assert_eq!(filter >= ours, filter >= a, "{filter} vs {a}");
+        }
+    }
+
+    assert!(SerializeLevel::Warn <= SerializeLevel::Info);
+    assert!(SerializeLevel::Trace > SerializeLevel::Debug);
+    assert!(SerializeLevel::Error <= LevelFilter::ERROR);
+    assert!(SerializeLevel::Debug > LevelFilter::INFO);
+
+    // A maximum level of `None` enables nothing.
+    let max: Option<SerializeLevel> = None;
+    assert!(Some(SerializeLevel::Error) > max);
+    let max = Some(SerializeLevel::Info);
+    assert!(Some(SerializeLevel::Warn) <= max);
+    assert!(Some(SerializeLevel::Debug) > max);
+}
@@ -0,0 +1,81 @@
+//! `SerdeMapVisitor` reports which field an error came from.
+
+use std::sync::{Arc, Mutex};
+
+use postcard::{ser_flavors::Slice, Serializer};
+use serde::Serializer as _;
+use tracing::info;
+use tracing_core::{
+    span::{Attributes, Id, Record},
+    Event, Metadata, Subscriber,
+};
+use tracing_serde_structured::{SerdeMapVisitor, ValueKind};
+
+/// Whether the fields were serialized, and the field that failed.
+type Outcome = (bool, Option<(&'static str, ValueKind)>);
+
+/// Serializes the fields of each event into a buffer of `len` bytes.
+struct MapSubscriber {
+    len: usize,
+    results: Mutex<Vec<Outcome>>,
+}
+
+impl Subscriber for MapSubscriber {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, _: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut buf = vec![0; self.len];
+        let mut serializer = Serializer {
+            output: Slice::new(&mut buf),
+        };
+        let map = (&mut serializer).serialize_map(Some(3)).unwrap();
+        let mut visitor = SerdeMapVisitor::new(map);
+        event.record(&mut visitor);
+        let failed = visitor.failed_field();
+        let ok = visitor.finish().is_ok();
+        self.results.lock().unwrap().push((ok, failed));
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+fn serialize_with(len: usize) -> Outcome {
+    let subscriber = Arc::new(MapSubscriber {
+        len,
+        results: Mutex::new(Vec::new()),
+    });
+    tracing::subscriber::with_default(subscriber.clone(), || {
+        info!(
+            port = 8080u64,
+            host = "a-rather-long-host-name.example.com",
+            up = true
+        );
+    });
+    let result = subscriber.results.lock().unwrap().pop().unwrap();
+    result
+}
+
+#[test]
+fn failed_field_names_the_field() {
+    let (ok, failed) = serialize_with(16);
+    assert!(!ok);
+    assert_eq!(failed, Some(("host", ValueKind::Str)));
+    assert_eq!(ValueKind::Str.to_string(), "str");
+}
+
+#[test]
+fn no_failed_field_on_success() {
+    assert_eq!(serialize_with(128), (true, None));
+}
@@ -0,0 +1,110 @@
+use tracing_serde_structured::{
+    builder::SerializeEventBuilder,
+    metrics::{extract, MetricKind, MetricValue},
+    SerializeValue,
+};
+
+#[test]
+fn recognizes_prefixes() {
+    assert_eq!(
+        MetricKind::of_field("monotonic_counter.hits"),
+        Some((MetricKind::MonotonicCounter, "hits"))
+    );
+    assert_eq!(
+        MetricKind::of_field("counter.queue.depth"),
+        Some((MetricKind::Counter, "queue.depth"))
+    );
+    assert_eq!(
+        MetricKind::of_field("gauge.temp"),
+        Some((MetricKind::Gauge, "temp"))
+    );
+    assert_eq!(
+        MetricKind::of_field("histogram.ms"),
+        Some((MetricKind::Histogram, "ms"))
+    );
+    assert_eq!(MetricKind::of_field("counter."), None);
+    assert_eq!(MetricKind::of_field("counters.x"), None);
+    assert_eq!(MetricKind::of_field("message"), None);
+
+    assert_eq!(MetricKind::Counter.prefix(), "counter.");
+    assert_eq!(
+        MetricKind::MonotonicCounter.to_string(),
+        "monotonic_counter"
+    );
+}
+
+#[test]
+fn extracts_every_metric_with_the_other_fields_as_labels() {
+    let event = SerializeEventBuilder::new()
+        .field("monotonic_counter.requests", 1u64)
+        .field("gauge.connections", 12i64)
+        .field("route", "/users")
+        .field("status", 200u64)
+        .field("message", "request done")
+        .build();
+
+    let metrics = extract(&event);
+    let found: Vec<_> = metrics
+        .iter()
+        .map(|m| (m.kind, m.name.as_str(), m.value))
+        .collect();
+    assert_eq!(
+        found,
+        [
+            (MetricKind::Gauge, "connections", MetricValue::I64(12)),
+            (
+                MetricKind::MonotonicCounter,
+                "requests",
+                MetricValue::U64(1)
+            ),
+        ]
+    );
+    for metric in &metrics {
+        let labels: Vec<_> = metric.labels.keys().map(String::as_str).collect();
+        assert_eq!(labels, ["route", "status"]);
+        assert_eq!(metric.labels["status"], SerializeValue::U64(200));
+    }
+
+    let json = serde_json::to_value(&metrics[1]).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "kind": "monotonic_counter",
+            "name": "requests",
+            "value": 1,
+            "labels": {"route": {"Str": "/users"}, "status": {"U64": 200}},
+        })
+    );
+}
+
+#[test]
+fn skips_values_that_are_not_metrics() {
+    let event = SerializeEventBuilder::new()
+        .field("counter.text", "three")
+        .field("counter.flag", true)
+        .field("monotonic_counter.down", -1i64)
+        .field("monotonic_counter.down_f", -0.5)
+        .field("monotonic_counter.up", 2i64)
+        .field("counter.down", -1i64)
+        .build();
+
+    let found: Vec<_> = extract(&event)
+        .into_iter()
+        .map(|m| (m.name, m.value))
+        .collect();
+    assert_eq!(
+        found,
+        [
+            ("down".to_owned(), MetricValue::I64(-1)),
+            ("up".to_owned(), MetricValue::U64(2)),
+        ]
+    );
+    assert_eq!(MetricValue::I64(-1).as_f64(), -1.0);
+    assert_eq!(MetricValue::F64(0.5).to_string(), "0.5");
+}
+
+#[test]
+fn events_without_metrics_have_none() {
+    let event = SerializeEventBuilder::new().field("x", 1u64).build();
+    assert!(extract(&event).is_empty());
+}
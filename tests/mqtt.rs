@@ -0,0 +1,177 @@
+#![cfg(feature = "mqtt")]
+
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use rumqttc::{MqttOptions, QoS};
+use tracing_serde_structured::{
+    builder::SerializeEventBuilder,
+    mqtt::{Encoding, Error, MqttSink, Topics},
+    SerializeEvent, SerializeLevel,
+};
+
+/// A message as the broker received it.
+#[derive(Debug)]
+struct Publish {
+    topic: String,
+    qos: u8,
+    payload: Vec<u8>,
+}
+
+/// Reads one packet, returning its first byte and the rest of it, or `None`
+/// once the client hangs up.
+fn packet(stream: &mut TcpStream) -> Option<(u8, Vec<u8>)> {
+    let mut byte = [0];
+    stream.read_exact(&mut byte).ok()?;
+    let header = byte[0];
+    let mut len = 0;
+    for shift in (0..).step_by(7) {
+        stream.read_exact(&mut byte).ok()?;
+        len |= usize::from(byte[0] & 0x7f) << shift;
+        if byte[0] & 0x80 == 0 {
+            break;
+        }
+    }
+    let mut body = vec![0; len];
+    stream.read_exact(&mut body).ok()?;
+    Some((header, body))
+}
+
+/// A broker speaking just enough MQTT 3.1.1 for a client to publish, taking
+/// one connection, and returning what was published on it.
+fn broker(listener: TcpListener) -> JoinHandle<Vec<Publish>> {
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut published = Vec::new();
+        while let Some((header, body)) = packet(&mut stream) {
+            match header >> 4 {
+                // CONNECT
+                1 => stream.write_all(&[0x20, 2, 0, 0]).unwrap(),
+                // PUBLISH
+                3 => {
+                    let qos = (header >> 1) & 3;
+                    let len = usize::from(u16::from_be_bytes([body[0], body[1]]));
+                    let topic = String::from_utf8(body[2..2 + len].to_vec()).unwrap();
+                    let mut payload = &body[2 + len..];
+                    if qos > 0 {
+                        stream
+                            .write_all(&[0x40, 2, payload[0], payload[1]])
+                            .unwrap();
+                        payload = &payload[2..];
+                    }
+                    published.push(Publish {
+                        topic,
+                        qos,
+                        payload: payload.to_vec(),
+                    });
+                }
+                // PINGREQ
+                12 => stream.write_all(&[0xd0, 0]).unwrap(),
+                // DISCONNECT
+                14 => break,
+                _ => {}
+            }
+        }
+        published
+    })
+}
+
+fn options(addr: SocketAddr) -> MqttOptions {
+    MqttOptions::new("test", addr.ip().to_string(), addr.port())
+}
+
+fn wait_online(sink: &MqttSink) {
+    let start = Instant::now();
+    while !sink.is_online() {
+        assert!(start.elapsed() < Duration::from_secs(10), "never connected");
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+fn event(target: &str, level: SerializeLevel) -> SerializeEvent<'_> {
+    SerializeEventBuilder::new()
+        .target(target)
+        .level(level)
+        .field("attempt", 3u64)
+        .build()
+}
+
+#[test]
+fn events_are_published_to_their_topics() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let broker = broker(listener);
+
+    let mut sink = MqttSink::new(options(addr), Topics::new("gw-1")).with_encoding(Encoding::Json);
+    sink.send_event(&event("app::net", SerializeLevel::Warn))
+        .unwrap();
+    wait_online(&sink);
+    sink.send_to("tracing/gw-1/spans", &SerializeLevel::Info)
+        .unwrap();
+    assert_eq!(sink.queued(), 0);
+    assert_eq!(sink.stats().sent, 2);
+    sink.disconnect();
+
+    let published = broker.join().unwrap();
+    assert_eq!(published.len(), 2);
+    assert_eq!(published[0].topic, "tracing/gw-1/warn");
+    assert_eq!(published[0].qos, 1);
+    let json: serde_json::Value = serde_json::from_slice(&published[0].payload).unwrap();
+    assert_eq!(json["metadata"]["target"], "app::net");
+    assert_eq!(published[1].topic, "tracing/gw-1/spans");
+    assert_eq!(published[1].payload, b"\"INFO\"");
+}
+
+#[test]
+fn messages_wait_for_the_broker() {
+    // Reserve an address nothing listens on yet.
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let mut sink = MqttSink::new(options(addr), Topics::new("gw-1"))
+        .with_qos(QoS::AtMostOnce)
+        .with_capacity(2)
+        .with_backoff(Duration::from_millis(10), Duration::from_millis(10));
+    for level in [
+        SerializeLevel::Trace,
+        SerializeLevel::Debug,
+        SerializeLevel::Info,
+    ] {
+        sink.send_event(&event("app", level)).unwrap();
+    }
+    assert!(!sink.is_online());
+    assert_eq!(sink.queued(), 2);
+    assert_eq!(sink.stats().dropped, 1);
+
+    let broker = broker(TcpListener::bind(addr).unwrap());
+    wait_online(&sink);
+    sink.flush();
+    assert_eq!(sink.queued(), 0);
+    sink.disconnect();
+
+    let published = broker.join().unwrap();
+    let topics: Vec<&str> = published.iter().map(|p| p.topic.as_str()).collect();
+    assert_eq!(topics, ["tracing/gw-1/debug", "tracing/gw-1/info"]);
+    assert!(published.iter().all(|p| p.qos == 0));
+    let decoded: SerializeEvent<'_> = postcard::from_bytes(&published[1].payload).unwrap();
+    assert_eq!(decoded.metadata.level, SerializeLevel::Info);
+}
+
+#[test]
+fn wildcard_topics_are_refused() {
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let mut sink = MqttSink::new(options(addr), Topics::new("gw-1"));
+    let err = sink.send_to("tracing/+/x", "msg").unwrap_err();
+    assert!(matches!(err, Error::InvalidTopic(_)));
+    assert_eq!(err.to_string(), "cannot publish to topic \"tracing/+/x\"");
+    assert_eq!(sink.stats().errors, 1);
+    assert_eq!(sink.queued(), 0);
+}
@@ -0,0 +1,58 @@
+#![cfg(feature = "msgpack")]
+
+use std::collections::BTreeMap;
+
+use tracing_serde_structured::{
+    msgpack::{from_msgpack_slice, to_msgpack, Representation},
+    CowString, SerializeEvent, SerializeFieldSet, SerializeLevel, SerializeMetadata,
+    SerializeRecordFields, SerializeValue,
+};
+
+fn event() -> SerializeEvent<'static> {
+    let mut fields = BTreeMap::new();
+    fields.insert(CowString::Borrowed("answer"), SerializeValue::U64(42));
+    SerializeEvent {
+        fields: SerializeRecordFields::De(fields),
+        metadata: SerializeMetadata {
+            name: CowString::Borrowed("event src/main.rs:7"),
+            target: CowString::Borrowed("my_crate"),
+            level: SerializeLevel::Warn,
+            module_path: None,
+            file: None,
+            line: Some(7),
+            fields: SerializeFieldSet::De(vec![CowString::Borrowed("answer")]),
+            is_span: false,
+            is_event: true,
+        },
+        parent: None,
+    }
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+#[test]
+fn named_includes_field_names() {
+    let named = to_msgpack(&event(), Representation::Named).unwrap();
+    assert!(contains(&named, b"metadata"));
+
+    let compact = to_msgpack(&event(), Representation::Compact).unwrap();
+    assert!(!contains(&compact, b"metadata"));
+    assert!(compact.len() < named.len());
+}
+
+#[test]
+fn both_representations_decode() {
+    let expected = serde_json::to_value(event()).unwrap();
+
+    for repr in [Representation::Named, Representation::Compact] {
+        let bytes = to_msgpack(&event(), repr).unwrap();
+        let decoded: SerializeEvent<'_> = from_msgpack_slice(&bytes).unwrap();
+        assert_eq!(
+            serde_json::to_value(&decoded).unwrap(),
+            expected,
+            "{repr:?}"
+        );
+    }
+}
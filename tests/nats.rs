@@ -0,0 +1,185 @@
+#![cfg(feature = "nats")]
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::TcpListener,
+};
+use tracing_serde_structured::{
+    builder::SerializeEventBuilder,
+    nats::{Encoding, Error, NatsSink, Subjects},
+    wire::SerializePipelineStats,
+    SerializeEvent, SerializeLevel,
+};
+
+type Published = Arc<Mutex<Vec<(String, Vec<u8>)>>>;
+
+/// A server speaking just enough of the NATS protocol for a client to
+/// publish, acknowledging messages sent with a reply subject like JetStream
+/// does, unless their payload contains `reject`.
+async fn server() -> (String, Published) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("nats://{}", listener.local_addr().unwrap());
+    let published = Published::default();
+    let received = published.clone();
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+        writer
+            .write_all(b"INFO {\"server_id\":\"test\",\"version\":\"2.10.0\",\"proto\":1,\"headers\":true,\"max_payload\":1048576}\r\n")
+            .await
+            .unwrap();
+        let mut subscriptions = Vec::new();
+        let mut seq = 0;
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line).await.unwrap() == 0 {
+                break;
+            }
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words[..] {
+                ["PING"] => writer.write_all(b"PONG\r\n").await.unwrap(),
+                ["SUB", subject, sid] => {
+                    let prefix = subject.trim_end_matches('*').to_owned();
+                    subscriptions.push((prefix, sid.to_owned()));
+                }
+                ["PUB", subject, ref rest @ ..] => {
+                    let len: usize = rest.last().unwrap().parse().unwrap();
+                    let mut payload = vec![0; len + 2];
+                    reader.read_exact(&mut payload).await.unwrap();
+                    payload.truncate(len);
+                    received
+                        .lock()
+                        .unwrap()
+                        .push((subject.to_owned(), payload.clone()));
+                    let [reply, _] = rest[..] else {
+                        continue;
+                    };
+                    let (_, sid) = subscriptions
+                        .iter()
+                        .find(|(prefix, _)| reply.starts_with(prefix.as_str()))
+                        .unwrap();
+                    let ack = if payload.windows(6).any(|w| w == b"reject") {
+                        r#"{"error":{"code":503,"err_code":10077,"description":"stream unavailable"}}"#.to_owned()
+                    } else {
+                        seq += 1;
+                        format!(r#"{{"stream":"TRACES","seq":{seq}}}"#)
+                    };
+                    let msg = format!("MSG {reply} {sid} {}\r\n{ack}\r\n", ack.len());
+                    writer.write_all(msg.as_bytes()).await.unwrap();
+                }
+                _ => {}
+            }
+        }
+    });
+    (url, published)
+}
+
+/// Waits until the server has received `n` messages.
+async fn received(published: &Published, n: usize) {
+    while published.lock().unwrap().len() < n {
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+}
+
+fn event(target: &str, level: SerializeLevel) -> SerializeEvent<'_> {
+    SerializeEventBuilder::new()
+        .target(target)
+        .level(level)
+        .field("attempt", 3u64)
+        .build()
+}
+
+#[test]
+fn subjects_are_made_from_metadata() {
+    let event = event("app::http server", SerializeLevel::Error);
+    let subjects = Subjects::new("billing.eu");
+    assert_eq!(
+        subjects.subject(&event.metadata),
+        "tracing.billing_eu.error"
+    );
+
+    let subjects = subjects.with_template("{service}.{target}.{level}.{unknown}");
+    assert_eq!(
+        subjects.subject(&event.metadata),
+        "billing_eu.app.http_server.error.{unknown}"
+    );
+}
+
+#[tokio::test]
+async fn batches_are_published() {
+    let (url, published) = server().await;
+    let client = async_nats::connect(url).await.unwrap();
+    let mut sink = NatsSink::new(client, Subjects::new("billing")).with_batch_size(2);
+
+    sink.send_event(&event("app", SerializeLevel::Info))
+        .await
+        .unwrap();
+    assert_eq!(sink.pending(), 1);
+    assert!(published.lock().unwrap().is_empty());
+    sink.send_to("tracing.billing.spans", &SerializeLevel::Warn)
+        .await
+        .unwrap();
+    assert_eq!(sink.pending(), 0);
+    assert_eq!(sink.stats().sent, 2);
+
+    received(&published, 2).await;
+    let published = published.lock().unwrap();
+    assert_eq!(published[0].0, "tracing.billing.info");
+    let decoded: SerializeEvent<'_> = postcard::from_bytes(&published[0].1).unwrap();
+    assert_eq!(decoded.metadata.target.as_str(), "app");
+    assert_eq!(published[1].0, "tracing.billing.spans");
+    assert_eq!(
+        published[1].1,
+        postcard::to_allocvec(&SerializeLevel::Warn).unwrap()
+    );
+}
+
+#[tokio::test]
+async fn jetstream_acknowledges_messages() {
+    let (url, published) = server().await;
+    let client = async_nats::connect(url).await.unwrap();
+    let mut sink = NatsSink::new(client, Subjects::new("billing"))
+        .with_jetstream()
+        .with_encoding(Encoding::Json);
+
+    sink.send_event(&event("app", SerializeLevel::Debug))
+        .await
+        .unwrap();
+    sink.send_event(&event("app::reject", SerializeLevel::Debug))
+        .await
+        .unwrap();
+    sink.send_event(&event("app", SerializeLevel::Warn))
+        .await
+        .unwrap();
+    let err = sink.flush().await.unwrap_err();
+    assert!(matches!(err, Error::JetStream(_)));
+    assert_eq!(
+        sink.stats(),
+        SerializePipelineStats {
+            sent: 2,
+            dropped: 1,
+            overflows: 0,
+            errors: 0,
+        }
+    );
+
+    let published = published.lock().unwrap();
+    let subjects: Vec<&str> = published.iter().map(|(s, _)| s.as_str()).collect();
+    assert_eq!(
+        subjects,
+        [
+            "tracing.billing.debug",
+            "tracing.billing.debug",
+            "tracing.billing.warn"
+        ]
+    );
+    let json: serde_json::Value = serde_json::from_slice(&published[2].1).unwrap();
+    assert_eq!(json["metadata"]["level"], "WARN");
+}
@@ -0,0 +1,137 @@
+#![cfg(feature = "nonblocking")]
+
+use std::{
+    io::{self, ErrorKind},
+    task::Poll,
+};
+
+use tracing_serde_structured::{
+    framing::{Framing, LengthPrefixed},
+    nonblocking::{Error, NonBlockingWriter},
+    wire::SerializePipelineStats,
+    SerializeLevel,
+};
+
+/// A sink with room for `room` more bytes, failing with `error` once it has
+/// none.
+struct Socket {
+    out: Vec<u8>,
+    room: usize,
+    error: ErrorKind,
+}
+
+impl Socket {
+    fn new(room: usize) -> Self {
+        Socket {
+            out: Vec::new(),
+            room,
+            error: ErrorKind::WouldBlock,
+        }
+    }
+}
+
+impl io::Write for Socket {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.room == 0 {
+            return Err(self.error.into());
+        }
+        let len = buf.len().min(self.room);
+        self.out.extend_from_slice(&buf[..len]);
+        self.room -= len;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn cobs(msg: &str) -> Vec<u8> {
+    postcard::to_allocvec_cobs(msg).unwrap()
+}
+
+#[test]
+fn pending_frames_are_finished_first() {
+    let mut writer = NonBlockingWriter::<_, 32>::new(Socket::new(3));
+    assert!(matches!(
+        writer.write_frame("first"),
+        Err(Error::WouldBlock)
+    ));
+    assert!(writer.is_pending());
+    assert!(writer.poll_flush().is_pending());
+
+    writer.get_mut().room = 2;
+    assert!(matches!(writer.write_frame("second"), Err(Error::Full)));
+    writer.get_mut().room = 64;
+    writer.write_frame("third").unwrap();
+    assert!(!writer.is_pending());
+    assert!(matches!(writer.poll_flush(), Poll::Ready(Ok(()))));
+
+    assert_eq!(
+        writer.get_ref().out,
+        [cobs("first"), cobs("third")].concat()
+    );
+    assert_eq!(
+        writer.stats(),
+        SerializePipelineStats {
+            sent: 2,
+            dropped: 1,
+            overflows: 0,
+            errors: 0,
+        }
+    );
+}
+
+#[test]
+fn failed_sinks_drop_the_pending_frame() {
+    let mut writer = NonBlockingWriter::<_, 32>::new(Socket::new(2));
+    assert!(matches!(
+        writer.write_frame("first"),
+        Err(Error::WouldBlock)
+    ));
+
+    writer.get_mut().error = ErrorKind::BrokenPipe;
+    let Poll::Ready(Err(e)) = writer.poll_flush() else {
+        panic!("the sink is broken");
+    };
+    assert_eq!(e.kind(), ErrorKind::BrokenPipe);
+    assert!(!writer.is_pending());
+
+    let err = writer.write_frame("second").unwrap_err();
+    assert!(matches!(&err, Error::Io(e) if e.kind() == ErrorKind::BrokenPipe));
+    assert_eq!(err.to_string(), "failed to write frame: broken pipe");
+    assert_eq!(writer.stats().dropped, 2);
+    assert_eq!(writer.stats().sent, 0);
+}
+
+#[test]
+fn oversized_messages_leave_nothing_pending() {
+    let mut writer = NonBlockingWriter::<_, 4>::new(Socket::new(0));
+    let err = writer.write_frame("too long for four bytes").unwrap_err();
+    assert!(matches!(
+        err,
+        Error::Encode(postcard::Error::SerializeBufferFull)
+    ));
+    assert!(!writer.is_pending());
+    assert!(matches!(writer.poll_flush(), Poll::Ready(Ok(()))));
+    assert_eq!(writer.stats().overflows, 1);
+}
+
+#[test]
+fn frames_take_the_writers_framing() {
+    let framing = LengthPrefixed::new();
+    let mut writer = NonBlockingWriter::<_, 32, _>::with_framing(Socket::new(1), framing);
+    assert!(matches!(
+        writer.write_frame(&SerializeLevel::Error),
+        Err(Error::WouldBlock)
+    ));
+    writer.get_mut().room = 64;
+    writer
+        .write_with(|buf| postcard::to_slice(&7u8, buf))
+        .unwrap();
+
+    let mut buf = [0u8; 8];
+    let frame = framing.to_slice(&SerializeLevel::Error, &mut buf).unwrap();
+    assert_eq!(writer.get_ref().out, [&frame[..], &[7]].concat());
+    assert_eq!(writer.stats().sent, 2);
+}
@@ -0,0 +1,296 @@
+#![cfg(feature = "otlp")]
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use prost::Message;
+use tracing_serde_structured::{
+    otlp::{
+        decode_logs, decode_spans,
+        proto::{
+            any_value::Value, span, status::StatusCode, AnyValue, ArrayValue,
+            ExportLogsServiceRequest, ExportTraceServiceRequest, InstrumentationScope, KeyValue,
+            KeyValueList, LogRecord, Resource, ResourceLogs, ResourceSpans, ScopeLogs, ScopeSpans,
+            Span, Status,
+        },
+    },
+    SerializeLevel, SerializeRecord, SerializeRecordFields, SerializeValue,
+};
+
+fn kv(key: &str, value: Value) -> KeyValue {
+    KeyValue {
+        key: key.to_owned(),
+        value: Some(AnyValue { value: Some(value) }),
+    }
+}
+
+fn string(s: &str) -> Value {
+    Value::StringValue(s.to_owned())
+}
+
+fn resource() -> Option<Resource> {
+    Some(Resource {
+        attributes: vec![kv("service.name", string("billing"))],
+    })
+}
+
+fn scope() -> Option<InstrumentationScope> {
+    Some(InstrumentationScope {
+        name: "billing::api".to_owned(),
+        ..Default::default()
+    })
+}
+
+fn field<'a>(
+    fields: &'a SerializeRecordFields<'static>,
+    name: &str,
+) -> Option<&'a SerializeValue<'static>> {
+    let SerializeRecordFields::De(map) = fields else {
+        panic!("converted fields are deserialized fields");
+    };
+    map.iter()
+        .find(|(key, _)| key.as_str() == name)
+        .map(|(_, value)| value)
+}
+
+fn value<'a>(
+    record: &'a SerializeRecord<'static>,
+    name: &str,
+) -> Option<&'a SerializeValue<'static>> {
+    let SerializeRecord::De(map) = record else {
+        panic!("converted records are deserialized records");
+    };
+    map.iter()
+        .find(|(key, _)| key.as_str() == name)
+        .map(|(_, value)| value)
+}
+
+#[test]
+fn log_records_become_events() {
+    let request = ExportLogsServiceRequest {
+        resource_logs: vec![ResourceLogs {
+            resource: resource(),
+            scope_logs: vec![ScopeLogs {
+                scope: scope(),
+                log_records: vec![
+                    LogRecord {
+                        time_unix_nano: 1_700_000_000_000_000_001,
+                        severity_number: 13,
+                        severity_text: "WARN".to_owned(),
+                        body: Some(AnyValue {
+                            value: Some(string("retrying")),
+                        }),
+                        attributes: vec![
+                            kv("attempt", Value::IntValue(3)),
+                            kv("code.filepath", string("src/api.rs")),
+                            kv("code.namespace", string("billing::api")),
+                            kv("code.lineno", Value::IntValue(42)),
+                        ],
+                        trace_id: vec![7; 16],
+                        span_id: 5u64.to_be_bytes().to_vec(),
+                        ..Default::default()
+                    },
+                    LogRecord {
+                        observed_time_unix_nano: 1_700_000_001_000_000_000,
+                        severity_text: "fatal".to_owned(),
+                        event_name: "payment.failed".to_owned(),
+                        attributes: vec![
+                            kv("target", string("billing::db")),
+                            kv(
+                                "ids",
+                                Value::ArrayValue(ArrayValue {
+                                    values: vec![
+                                        AnyValue {
+                                            value: Some(Value::IntValue(1)),
+                                        },
+                                        AnyValue {
+                                            value: Some(string("two")),
+                                        },
+                                    ],
+                                }),
+                            ),
+                            kv(
+                                "card",
+                                Value::KvlistValue(KeyValueList {
+                                    values: vec![kv("last4", string("4242"))],
+                                }),
+                            ),
+                            kv("raw", Value::BytesValue(vec![0xca, 0xfe])),
+                            KeyValue {
+                                key: "empty".to_owned(),
+                                value: None,
+                            },
+                        ],
+                        span_id: vec![1, 2, 3],
+                        ..Default::default()
+                    },
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }],
+    };
+    let logs = decode_logs(&request.encode_to_vec()).unwrap();
+    assert_eq!(logs.len(), 2);
+
+    let log = &logs[0];
+    assert_eq!(
+        log.timestamp,
+        Some(UNIX_EPOCH + Duration::from_nanos(1_700_000_000_000_000_001))
+    );
+    assert_eq!(log.trace_id, Some([7; 16]));
+    assert!(matches!(
+        value(&log.resource, "service.name"),
+        Some(SerializeValue::Str(s)) if s.as_str() == "billing"
+    ));
+    let event = &log.event;
+    let meta = &event.metadata;
+    assert_eq!(meta.level, SerializeLevel::Warn);
+    assert_eq!(meta.target.as_str(), "billing::api");
+    assert_eq!(meta.name.as_str(), "event src/api.rs:42");
+    assert_eq!(meta.module_path.as_ref().unwrap().as_str(), "billing::api");
+    assert_eq!(meta.file.as_ref().unwrap().as_str(), "src/api.rs");
+    assert_eq!(meta.line, Some(42));
+    assert!(meta.is_event);
+    assert_eq!(event.parent.as_ref().unwrap().id.get(), 5);
+    assert!(matches!(
+        field(&event.fields, "message"),
+        Some(SerializeValue::Str(s)) if s.as_str() == "retrying"
+    ));
+    assert!(matches!(
+        field(&event.fields, "attempt"),
+        Some(SerializeValue::I64(3))
+    ));
+    assert!(field(&event.fields, "code.lineno").is_none());
+
+    let log = &logs[1];
+    assert_eq!(
+        log.timestamp,
+        Some(UNIX_EPOCH + Duration::from_secs(1_700_000_001))
+    );
+    assert_eq!(log.trace_id, None);
+    let event = &log.event;
+    assert_eq!(event.metadata.level, SerializeLevel::Error);
+    assert_eq!(event.metadata.target.as_str(), "billing::db");
+    assert_eq!(event.metadata.name.as_str(), "payment.failed");
+    assert!(event.parent.is_none());
+    assert!(field(&event.fields, "message").is_none());
+    for (name, debug) in [
+        ("ids", r#"[1, "two"]"#),
+        ("card", r#"{"last4": "4242"}"#),
+        ("raw", "[202, 254]"),
+    ] {
+        let Some(SerializeValue::Debug(value)) = field(&event.fields, name) else {
+            panic!("{name} is not a debug value");
+        };
+        assert_eq!(value.to_string(), debug);
+    }
+    assert!(matches!(
+        field(&event.fields, "empty"),
+        Some(SerializeValue::Unknown)
+    ));
+}
+
+#[test]
+fn spans_become_attributes_values_and_events() {
+    let request = ExportTraceServiceRequest {
+        resource_spans: vec![ResourceSpans {
+            resource: resource(),
+            scope_spans: vec![ScopeSpans {
+                scope: scope(),
+                spans: vec![
+                    Span {
+                        trace_id: vec![7; 16],
+                        span_id: 6u64.to_be_bytes().to_vec(),
+                        parent_span_id: 5u64.to_be_bytes().to_vec(),
+                        name: "charge".to_owned(),
+                        kind: span::SpanKind::Server.into(),
+                        start_time_unix_nano: 1_000,
+                        end_time_unix_nano: 2_000,
+                        attributes: vec![
+                            kv("level", string("DEBUG")),
+                            kv("amount", Value::DoubleValue(9.5)),
+                        ],
+                        events: vec![span::Event {
+                            time_unix_nano: 1_500,
+                            name: "card declined".to_owned(),
+                            attributes: vec![kv("level", string("warn"))],
+                        }],
+                        status: Some(Status {
+                            code: StatusCode::Error.into(),
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    },
+                    Span {
+                        span_id: vec![0; 8],
+                        name: "invalid".to_owned(),
+                        ..Default::default()
+                    },
+                    Span {
+                        span_id: 5u64.to_be_bytes().to_vec(),
+                        name: "request".to_owned(),
+                        ..Default::default()
+                    },
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }],
+    };
+    let spans = decode_spans(&request.encode_to_vec()).unwrap();
+    let names: Vec<&str> = spans
+        .iter()
+        .map(|span| span.attributes.metadata.name.as_str())
+        .collect();
+    assert_eq!(names, ["charge", "request"]);
+
+    let span = &spans[0];
+    assert_eq!(span.id.id.get(), 6);
+    assert_eq!(span.start, Some(UNIX_EPOCH + Duration::from_nanos(1_000)));
+    assert_eq!(span.end, Some(UNIX_EPOCH + Duration::from_nanos(2_000)));
+    let attributes = &span.attributes;
+    assert_eq!(attributes.metadata.level, SerializeLevel::Debug);
+    assert_eq!(attributes.metadata.target.as_str(), "billing::api");
+    assert!(attributes.metadata.is_span);
+    assert_eq!(attributes.parent.as_ref().unwrap().id.get(), 5);
+    assert!(!attributes.is_root);
+    assert!(matches!(
+        value(&span.values, "amount"),
+        Some(SerializeValue::F64(x)) if *x == 9.5
+    ));
+    assert!(matches!(
+        value(&span.values, "otel.kind"),
+        Some(SerializeValue::Str(s)) if s.as_str() == "server"
+    ));
+    assert!(matches!(
+        value(&span.values, "otel.status_code"),
+        Some(SerializeValue::Str(s)) if s.as_str() == "ERROR"
+    ));
+    assert!(value(&span.values, "level").is_none());
+
+    assert_eq!(span.events.len(), 1);
+    let log = &span.events[0];
+    assert_eq!(
+        log.timestamp,
+        Some(UNIX_EPOCH + Duration::from_nanos(1_500))
+    );
+    assert_eq!(log.trace_id, Some([7; 16]));
+    assert_eq!(log.event.metadata.level, SerializeLevel::Warn);
+    assert_eq!(log.event.parent.as_ref().unwrap().id.get(), 6);
+    assert!(matches!(
+        field(&log.event.fields, "message"),
+        Some(SerializeValue::Str(s)) if s.as_str() == "card declined"
+    ));
+
+    let root = &spans[1];
+    assert!(root.attributes.is_root);
+    assert!(root.attributes.parent.is_none());
+    assert_eq!(root.attributes.metadata.level, SerializeLevel::Info);
+    assert!(value(&root.values, "otel.kind").is_none());
+}
+
+#[test]
+fn invalid_bodies_are_refused() {
+    assert!(decode_logs(&[0xff, 0xff]).is_err());
+    assert!(decode_spans(&[0x0a, 0x05]).is_err());
+}
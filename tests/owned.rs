@@ -0,0 +1,160 @@
+//! Converting captured data to owned keeps strings from `'static` metadata
+//! as pointer copies.
+
+use std::sync::{Arc, Mutex};
+
+use tracing::info;
+use tracing_core::{
+    span::{Attributes, Id, Record},
+    Event, Metadata, Subscriber,
+};
+use tracing_serde_structured::{
+    builder::SerializeEventBuilder, wire::TracingWire, AsSerde, CowString, SerializeEvent,
+    SerializeFieldSet, SerializeRecordFields, SerializeValue,
+};
+
+#[derive(Default)]
+struct OwnedSubscriber {
+    events: Mutex<Vec<SerializeEvent<'static>>>,
+}
+
+impl Subscriber for OwnedSubscriber {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, _: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let owned = event.as_serde().to_owned();
+        assert!(owned.is_owned());
+
+        // Converting in place gives the same event.
+        let mut ensured = event.as_serde();
+        assert!(ensured.is_borrowed());
+        ensured.ensure_owned();
+        assert!(ensured.is_owned());
+        assert_eq!(ensured, owned);
+
+        self.events.lock().unwrap().push(owned);
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+fn is_static(s: &CowString<'_>) -> bool {
+    matches!(s, CowString::Static(_))
+}
+
+#[test]
+fn owned_metadata_is_static() {
+    let subscriber = Arc::new(OwnedSubscriber::default());
+    tracing::subscriber::with_default(subscriber.clone(), || {
+        info!(answer = 42, "hello");
+    });
+    let events = std::mem::take(&mut *subscriber.events.lock().unwrap());
+    let event = &events[0];
+
+    let meta = &event.metadata;
+    assert!(is_static(&meta.name));
+    assert!(is_static(&meta.target));
+    assert!(meta.module_path.as_ref().is_some_and(is_static));
+    assert!(meta.file.as_ref().is_some_and(is_static));
+    match &meta.fields {
+        SerializeFieldSet::De(names) => assert!(names.iter().all(is_static)),
+        SerializeFieldSet::Ser(_) => panic!("fields were not converted"),
+    }
+
+    // Field names come from the metadata too; only the values are copied.
+    match &event.fields {
+        SerializeRecordFields::De(map) => {
+            assert_eq!(map.len(), 2);
+            assert!(map.keys().all(is_static));
+        }
+        SerializeRecordFields::Ser(_) => panic!("fields were not converted"),
+    }
+}
+
+#[test]
+fn borrowed_strings_are_copied() {
+    let owned = CowString::Borrowed("hello").to_owned();
+    assert!(!matches!(
+        owned,
+        CowString::Borrowed(_) | CowString::Static(_)
+    ));
+    assert_eq!(owned.as_str(), "hello");
+    assert_eq!(owned, CowString::Static("hello"));
+}
+
+#[test]
+fn owned_events_move_between_threads() {
+    let subscriber = Arc::new(OwnedSubscriber::default());
+    tracing::subscriber::with_default(subscriber.clone(), || {
+        info!(answer = 42, "hello");
+    });
+    let mut events = std::mem::take(&mut *subscriber.events.lock().unwrap());
+
+    let event = std::thread::spawn(move || events.pop().unwrap())
+        .join()
+        .unwrap();
+    let json = serde_json::to_value(&event).unwrap();
+    assert_eq!(json["fields"]["answer"], serde_json::json!({ "I64": 42 }));
+}
+
+#[test]
+fn owned_events_are_shared_between_threads() {
+    let subscriber = Arc::new(OwnedSubscriber::default());
+    tracing::subscriber::with_default(subscriber.clone(), || {
+        info!(answer = 42, "hello");
+    });
+    let event = Arc::new(subscriber.events.lock().unwrap().pop().unwrap());
+
+    let workers: Vec<_> = (0..2)
+        .map(|_| {
+            let event = event.clone();
+            std::thread::spawn(move || serde_json::to_value(&*event).unwrap())
+        })
+        .collect();
+    for worker in workers {
+        let json = worker.join().unwrap();
+        assert_eq!(json["fields"]["answer"], serde_json::json!({ "I64": 42 }));
+    }
+}
+
+#[test]
+fn decoded_messages_are_owned_in_place() {
+    let event = SerializeEventBuilder::new()
+        .name("connected")
+        .field("peer", "10.0.0.1")
+        .field("port", 8080u64)
+        .build();
+    let bytes = postcard::to_allocvec(&TracingWire::Event(event.clone())).unwrap();
+
+    let mut msg: TracingWire<'_> = postcard::from_bytes(&bytes).unwrap();
+    assert!(msg.is_borrowed());
+    msg.ensure_owned();
+    assert!(msg.is_owned());
+    assert_eq!(msg, TracingWire::Event(event));
+
+    // Values that are already owned are left as they are.
+    let mut value = SerializeValue::Str(CowString::Static("peer"));
+    assert!(value.is_owned());
+    value.ensure_owned();
+    assert!(matches!(
+        value,
+        SerializeValue::Str(CowString::Static("peer"))
+    ));
+
+    let mut value = SerializeValue::Str(CowString::Borrowed("peer"));
+    assert!(value.is_borrowed());
+    value.ensure_owned();
+    assert!(value.is_owned());
+}
@@ -0,0 +1,84 @@
+#![cfg(all(feature = "panic-flush", feature = "embedded-io"))]
+
+use std::{convert::Infallible, sync::Mutex};
+
+use tracing_serde_structured::{
+    embedded_io::FramedWriter,
+    panic_flush,
+    wire::{SerializePanic, TracingWire},
+    CowString, DebugRecord,
+};
+
+static OUT: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+
+/// Writes to `OUT`, so the test can still read what was flushed after the
+/// registry has taken the sink.
+struct Out;
+
+impl embedded_io::ErrorType for Out {
+    type Error = Infallible;
+}
+
+impl embedded_io::Write for Out {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Infallible> {
+        OUT.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+}
+
+fn flushed() -> Vec<serde_json::Value> {
+    let mut out = std::mem::take(&mut *OUT.lock().unwrap());
+    out.split_mut(|b| *b == 0)
+        .filter(|f| !f.is_empty())
+        .map(|frame| {
+            let msg: TracingWire<'_> = postcard::from_bytes_cobs(frame).unwrap();
+            serde_json::to_value(&msg).unwrap()
+        })
+        .collect()
+}
+
+fn register() {
+    let sink = Box::leak(Box::new(FramedWriter::<_, 128>::new(Out)));
+    assert!(panic_flush::register(sink).is_none());
+}
+
+// The registry is global, so everything runs in one test.
+#[test]
+fn flushes_once() {
+    assert!(!panic_flush::on_fault("HardFault"));
+    assert!(flushed().is_empty());
+
+    register();
+    assert!(panic_flush::on_fault("HardFault"));
+    assert_eq!(
+        flushed(),
+        [serde_json::json!({
+            "Panic": { "message": "HardFault", "file": null, "line": null }
+        })]
+    );
+
+    // The sink was taken, so a second fault while flushing does nothing.
+    assert!(!panic_flush::on_fault("HardFault"));
+    assert!(flushed().is_empty());
+
+    register();
+    assert!(panic_flush::flush(SerializePanic {
+        message: DebugRecord::Ser(&format_args!("index {} out of bounds", 7)),
+        file: Some(CowString::Borrowed("src/main.rs")),
+        line: Some(12),
+    }));
+    assert_eq!(
+        flushed(),
+        [serde_json::json!({
+            "Panic": { "message": "index 7 out of bounds", "file": "src/main.rs", "line": 12 }
+        })]
+    );
+
+    register();
+    assert!(panic_flush::unregister().is_some());
+    assert!(!panic_flush::on_fault("HardFault"));
+}
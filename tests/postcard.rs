@@ -0,0 +1,149 @@
+#![cfg(feature = "postcard")]
+
+use std::sync::Mutex;
+
+use tracing::info;
+use tracing_core::{
+    span::{Attributes, Id, Record},
+    Event, Metadata, Subscriber,
+};
+use tracing_serde_structured::{
+    postcard::{self, Error},
+    wire::{SerializeEventRef, TracingWire},
+    AsSerde, CowString, SerializeEvent, SerializeFieldSet, SerializeLevel, SerializeMetadata,
+    SerializeRecordFields, SerializeValue,
+};
+
+fn event() -> SerializeEvent<'static> {
+    let mut fields = std::collections::BTreeMap::new();
+    fields.insert(CowString::Static("answer"), SerializeValue::U64(42));
+    SerializeEvent {
+        fields: SerializeRecordFields::De(fields),
+        metadata: SerializeMetadata {
+            name: CowString::Static("event src/main.rs:7"),
+            target: CowString::Static("my_crate"),
+            level: SerializeLevel::Info,
+            module_path: None,
+            file: None,
+            line: Some(7),
+            fields: SerializeFieldSet::De(vec![CowString::Static("answer")]),
+            is_span: false,
+            is_event: true,
+        },
+        parent: None,
+    }
+}
+
+#[test]
+fn serialize_into_uses_a_prefix_of_the_buffer() {
+    let event = event();
+    let mut buf = [0u8; 128];
+    let used = postcard::serialize_into(&event, &mut buf).unwrap();
+    assert_eq!(&*used, ::postcard::to_allocvec(&event).unwrap().as_slice());
+
+    let decoded: SerializeEvent<'_> = postcard::from_bytes(used).unwrap();
+    assert_eq!(decoded.metadata.name.as_str(), "event src/main.rs:7");
+}
+
+#[test]
+fn small_buffers_are_rejected() {
+    let event = event();
+    let mut buf = [0u8; 8];
+    assert_eq!(
+        postcard::serialize_into(&event, &mut buf).unwrap_err(),
+        Error::SerializeBufferFull
+    );
+    assert_eq!(
+        postcard::to_heapless_vec::<_, 8>(&event).unwrap_err(),
+        Error::SerializeBufferFull
+    );
+
+    let vec = postcard::to_heapless_vec::<_, 128>(&event).unwrap();
+    assert_eq!(vec, ::postcard::to_allocvec(&event).unwrap().as_slice());
+}
+
+/// Checks `wire_size` against the encoded length of borrowed messages.
+#[derive(Default)]
+struct SizeSubscriber {
+    checked: Mutex<usize>,
+}
+
+impl SizeSubscriber {
+    fn check(&self, size: usize, encoded: Vec<u8>) {
+        assert_eq!(size, encoded.len());
+        *self.checked.lock().unwrap() += 1;
+    }
+}
+
+impl Subscriber for SizeSubscriber {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+        let attrs = attrs.as_serde();
+        self.check(attrs.wire_size(), ::postcard::to_allocvec(&attrs).unwrap());
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _: &Id, values: &Record<'_>) {
+        let values = values.as_serde();
+        self.check(
+            values.wire_size(),
+            ::postcard::to_allocvec(&values).unwrap(),
+        );
+    }
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let full = TracingWire::Event(event.as_serde());
+        self.check(full.wire_size(), ::postcard::to_allocvec(&full).unwrap());
+        let by_ref = SerializeEventRef::new(event);
+        self.check(
+            by_ref.wire_size(),
+            ::postcard::to_allocvec(&by_ref).unwrap(),
+        );
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+#[test]
+fn wire_size_is_exact() {
+    let event = event();
+    assert_eq!(
+        event.wire_size(),
+        ::postcard::to_allocvec(&event).unwrap().len()
+    );
+    assert_eq!(
+        event.metadata.wire_size(),
+        ::postcard::to_allocvec(&event.metadata).unwrap().len()
+    );
+
+    let subscriber = std::sync::Arc::new(SizeSubscriber::default());
+    tracing::subscriber::with_default(subscriber.clone(), || {
+        let span = tracing::info_span!("request", user = tracing::field::Empty);
+        span.record("user", "ferris");
+        info!(attempt = 3, ok = true, "logged in as {}", "ferris");
+    });
+    assert_eq!(*subscriber.checked.lock().unwrap(), 4);
+}
+
+#[test]
+fn with_scratch_passes_the_encoded_message() {
+    let len = postcard::with_scratch(&SerializeLevel::Warn, |bytes| {
+        assert_eq!(bytes, [3]);
+        bytes.len()
+    })
+    .unwrap();
+    assert_eq!(len, 1);
+
+    let too_long = "x".repeat(tracing_serde_structured::scratch::SCRATCH_SIZE);
+    assert!(matches!(
+        postcard::with_scratch(&too_long, |_| ()),
+        Err(Error::SerializeBufferFull)
+    ));
+}
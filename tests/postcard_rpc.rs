@@ -0,0 +1,47 @@
+#![cfg(feature = "postcard-rpc")]
+
+use postcard_rpc::{Endpoint, Key, Topic};
+use tracing_serde_structured::{
+    postcard_rpc::{GetMaxLevelEndpoint, SetMaxLevelEndpoint, WireTopic},
+    wire::TracingWire,
+    Live, SerializeId, SerializeLevel,
+};
+
+#[test]
+fn keys_are_distinct() {
+    let keys = [
+        <WireTopic>::TOPIC_KEY,
+        SetMaxLevelEndpoint::REQ_KEY,
+        GetMaxLevelEndpoint::REQ_KEY,
+        GetMaxLevelEndpoint::RESP_KEY,
+    ];
+    for (i, a) in keys.iter().enumerate() {
+        for b in &keys[i + 1..] {
+            assert_ne!(a, b);
+        }
+    }
+    assert_eq!(
+        <WireTopic>::TOPIC_KEY,
+        Key::for_path::<TracingWire<'_>>("tracing/wire")
+    );
+    assert_eq!(WireTopic::<Live>::TOPIC_KEY, <WireTopic>::TOPIC_KEY);
+}
+
+#[test]
+fn messages_round_trip() {
+    let msg: <WireTopic<'_> as Topic>::Message = TracingWire::Enter(SerializeId {
+        id: 7.try_into().unwrap(),
+    });
+    let bytes = postcard::to_allocvec(&msg).unwrap();
+    let TracingWire::Enter(id) = postcard::from_bytes::<TracingWire<'_>>(&bytes).unwrap() else {
+        panic!("wrong message");
+    };
+    assert_eq!(id.id.get(), 7);
+
+    let req: <SetMaxLevelEndpoint as Endpoint>::Request = Some(SerializeLevel::Debug);
+    let bytes = postcard::to_allocvec(&req).unwrap();
+    assert_eq!(
+        postcard::from_bytes::<Option<SerializeLevel>>(&bytes).unwrap(),
+        req
+    );
+}
@@ -0,0 +1,144 @@
+#![cfg(feature = "postgres")]
+
+// These tests need a server: set `TSS_POSTGRES_URL` to a connection string
+// such as `host=localhost user=postgres` to run them. They create tables
+// with unique names, and drop them when they pass.
+
+use std::{
+    num::NonZeroU64,
+    process,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use postgres::{Client, NoTls};
+use serde_json::{json, Value};
+use tracing_serde_structured::{
+    builder::SerializeEventBuilder, postgres::PostgresSink, SerializeId, SerializeLevel,
+    SerializeValue,
+};
+
+fn connect() -> Option<Client> {
+    let Ok(url) = std::env::var("TSS_POSTGRES_URL") else {
+        eprintln!("TSS_POSTGRES_URL is not set, skipping");
+        return None;
+    };
+    Some(Client::connect(&url, NoTls).unwrap())
+}
+
+fn table(test: &str) -> String {
+    format!("tss_{test}_{}", process::id())
+}
+
+#[test]
+fn events_are_copied_in_batches() {
+    let Some(client) = connect() else {
+        return;
+    };
+    let table = table("batches");
+    let mut sink = PostgresSink::with_table(client, &table)
+        .unwrap()
+        .with_batch_size(2);
+
+    let event = SerializeEventBuilder::new()
+        .target("app::net")
+        .name("event src/net.rs:12")
+        .level(SerializeLevel::Warn)
+        .field("message", "retrying")
+        .field("attempt", 3u64)
+        .field("delay", -1.5f64)
+        .field("path", "/tmp/\0")
+        .field("unknown", SerializeValue::Unknown)
+        .parent(SerializeId {
+            id: NonZeroU64::new(u64::MAX).unwrap(),
+        })
+        .build();
+    let timestamp = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    sink.record_event(timestamp, &event).unwrap();
+    assert_eq!(sink.pending(), 1);
+    let count: i64 = sink
+        .client()
+        .query_one(&format!("SELECT count(*) FROM \"{table}\""), &[])
+        .unwrap()
+        .get(0);
+    assert_eq!(count, 0);
+
+    let event = SerializeEventBuilder::new()
+        .target("app")
+        .level(SerializeLevel::Info)
+        .field("done", true)
+        .build();
+    sink.record_event(timestamp + Duration::from_secs(1), &event)
+        .unwrap();
+    assert_eq!(sink.pending(), 0);
+
+    let row = sink
+        .client()
+        .query_one(
+            &format!(
+                "SELECT timestamp, level, target, name, parent, fields FROM \"{table}\" \
+                 WHERE fields @> '{{\"attempt\": 3}}'"
+            ),
+            &[],
+        )
+        .unwrap();
+    assert_eq!(row.get::<_, SystemTime>(0), timestamp);
+    assert_eq!(row.get::<_, &str>(1), "WARN");
+    assert_eq!(row.get::<_, &str>(2), "app::net");
+    assert_eq!(row.get::<_, &str>(3), "event src/net.rs:12");
+    assert_eq!(row.get::<_, Option<i64>>(4), Some(-1));
+    assert_eq!(
+        row.get::<_, Value>(5),
+        json!({
+            "message": "retrying",
+            "attempt": 3,
+            "delay": -1.5,
+            "path": "/tmp/\u{fffd}",
+            "unknown": null,
+        })
+    );
+
+    let row = sink
+        .client()
+        .query_one(
+            &format!("SELECT level, parent, fields->'done' FROM \"{table}\" WHERE target = 'app'"),
+            &[],
+        )
+        .unwrap();
+    assert_eq!(row.get::<_, &str>(0), "INFO");
+    assert_eq!(row.get::<_, Option<i64>>(1), None);
+    assert_eq!(row.get::<_, Value>(2), json!(true));
+
+    sink.client()
+        .batch_execute(&format!("DROP TABLE \"{table}\""))
+        .unwrap();
+}
+
+#[test]
+fn partial_batches_are_written_on_flush_and_drop() {
+    let Some(client) = connect() else {
+        return;
+    };
+    let table = table("flush");
+    let mut sink = PostgresSink::with_table(client, &table).unwrap();
+    let event = SerializeEventBuilder::new().field("n", 1i64).build();
+    sink.record_event(UNIX_EPOCH, &event).unwrap();
+    sink.flush().unwrap();
+    assert_eq!(sink.pending(), 0);
+    sink.record_event(UNIX_EPOCH, &event).unwrap();
+    drop(sink);
+
+    let mut client = connect().unwrap();
+    let count: i64 = client
+        .query_one(&format!("SELECT count(*) FROM \"{table}\""), &[])
+        .unwrap()
+        .get(0);
+    assert_eq!(count, 2);
+    // The table already exists, and is reused.
+    let sink = PostgresSink::with_table(client, &table).unwrap();
+    drop(sink);
+
+    let mut client = connect().unwrap();
+    client
+        .batch_execute(&format!("DROP TABLE \"{table}\""))
+        .unwrap();
+}
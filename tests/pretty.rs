@@ -0,0 +1,108 @@
+#![cfg(feature = "pretty")]
+
+use std::time::Duration;
+
+use tracing_serde_structured::{
+    builder::{SerializeAttributesBuilder, SerializeEventBuilder},
+    pretty::Writer,
+    wire::{DropReason, SerializeDropped, TracingWire},
+    CowString, SerializeId, SerializeLevel, SerializeRecord, SerializeValue,
+};
+
+fn id(id: u64) -> SerializeId {
+    SerializeId {
+        id: id.try_into().unwrap(),
+    }
+}
+
+fn event(message: &'static str) -> TracingWire<'static> {
+    TracingWire::Event(
+        SerializeEventBuilder::new()
+            .target("app")
+            .field("message", message)
+            .field("n", 1u64)
+            .build(),
+    )
+}
+
+fn render(ansi: bool, msgs: Vec<TracingWire<'static>>) -> String {
+    let mut writer = Writer::new(Vec::new());
+    writer.set_ansi(ansi);
+    writer.set_message_width(8);
+    for (i, msg) in msgs.iter().enumerate() {
+        writer.write(Duration::from_secs(i as u64), msg).unwrap();
+    }
+    String::from_utf8(writer.into_inner()).unwrap()
+}
+
+fn new_span(span: u64, name: &'static str) -> TracingWire<'static> {
+    TracingWire::NewSpan {
+        id: id(span),
+        attributes: SerializeAttributesBuilder::new(name)
+            .level(SerializeLevel::Debug)
+            .build(),
+    }
+}
+
+#[test]
+fn scopes_are_indented() {
+    let out = render(
+        false,
+        vec![
+            new_span(1, "outer"),
+            TracingWire::Record {
+                span: id(1),
+                values: [(CowString::Static("user"), SerializeValue::U64(7))]
+                    .into_iter()
+                    .collect::<SerializeRecord<'_>>(),
+            },
+            TracingWire::Enter(id(1)),
+            new_span(2, "inner"),
+            TracingWire::Enter(id(2)),
+            event("deep"),
+            TracingWire::Exit(id(2)),
+            TracingWire::CloseSpan(id(2)),
+            event("shallow"),
+            TracingWire::Exit(id(1)),
+            event("top"),
+        ],
+    );
+    let expected = [
+        "    0.000000 DEBUG new span 1 outer",
+        "    3.000000 DEBUG   new span 2 inner",
+        "    5.000000  INFO     outer{user=7}:inner: app: deep     n=1",
+        "    7.000000 DEBUG   close span 2 inner",
+        "    8.000000  INFO   outer{user=7}: app: shallow  n=1",
+        "   10.000000  INFO app: top      n=1",
+    ];
+    assert_eq!(out.lines().collect::<Vec<_>>(), expected);
+}
+
+#[test]
+fn levels_are_colored() {
+    let out = render(true, vec![event("hi")]);
+    assert!(out.contains("\x1b[32m INFO\x1b[0m"));
+    assert!(out.contains("\x1b[3mn\x1b[0m\x1b[2m=\x1b[0m1"));
+    assert!(!render(false, vec![event("hi")]).contains('\x1b'));
+}
+
+#[test]
+fn other_messages_are_skipped() {
+    let out = render(false, vec![TracingWire::Enter(id(1)), TracingWire::Unknown]);
+    assert_eq!(out, "");
+}
+
+#[test]
+fn dropped_messages_are_marked() {
+    let dropped = TracingWire::Dropped(SerializeDropped {
+        count: 3,
+        reason: DropReason::QueueFull,
+        first_seq: 7,
+        last_seq: 9,
+    });
+    let out = render(false, vec![dropped]);
+    assert_eq!(
+        out,
+        "    0.000000  WARN 3 messages lost here (queue full, #7 to #9)\n"
+    );
+}
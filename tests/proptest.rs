@@ -0,0 +1,64 @@
+#![cfg(feature = "proptest")]
+
+use proptest::prelude::*;
+use tracing_serde_structured::{
+    proptest::{attributes, event, metadata, value, MAX_FIELDS},
+    SerializeAttributes, SerializeEvent, SerializeFieldSet, SerializeMetadata,
+    SerializeRecordFields, SerializeValue,
+};
+
+proptest! {
+    #[test]
+    fn events_list_their_fields(event in event()) {
+        let (SerializeRecordFields::De(fields), SerializeFieldSet::De(names)) =
+            (&event.fields, &event.metadata.fields)
+        else {
+            panic!("generated a borrowed event");
+        };
+        prop_assert!(fields.len() <= MAX_FIELDS);
+        prop_assert!(fields.keys().eq(names.iter()));
+        prop_assert!(event.metadata.is_event && !event.metadata.is_span);
+    }
+
+    #[test]
+    fn spans_are_spans(attributes in attributes()) {
+        prop_assert!(attributes.metadata.is_span && !attributes.metadata.is_event);
+        prop_assert!(!(attributes.is_root && attributes.parent.is_some()));
+    }
+
+    #[test]
+    fn events_round_trip_through_json(event in event()) {
+        let json = serde_json::to_string(&event).unwrap();
+        let decoded: SerializeEvent<'_> = serde_json::from_str(&json).unwrap();
+        if cfg!(feature = "strip-locations") {
+            prop_assert_eq!(serde_json::to_string(&decoded).unwrap(), json);
+        } else {
+            prop_assert_eq!(decoded, event);
+        }
+    }
+
+    #[test]
+    fn events_round_trip_through_postcard(event in event()) {
+        let bytes = postcard::to_allocvec(&event).unwrap();
+        let decoded: SerializeEvent<'_> = postcard::from_bytes(&bytes).unwrap();
+        prop_assert_eq!(postcard::to_allocvec(&decoded).unwrap(), bytes);
+    }
+
+    #[test]
+    fn metadata_and_attributes_round_trip(metadata in metadata(), attributes in attributes()) {
+        let json = serde_json::to_string(&metadata).unwrap();
+        let decoded: SerializeMetadata<'_> = serde_json::from_str(&json).unwrap();
+        prop_assert_eq!(serde_json::to_string(&decoded).unwrap(), json);
+
+        let json = serde_json::to_string(&attributes).unwrap();
+        let decoded: SerializeAttributes<'_> = serde_json::from_str(&json).unwrap();
+        prop_assert_eq!(serde_json::to_string(&decoded).unwrap(), json);
+    }
+
+    #[test]
+    fn values_round_trip_through_json(value in value()) {
+        let json = serde_json::to_string(&value).unwrap();
+        let decoded: SerializeValue<'_> = serde_json::from_str(&json).unwrap();
+        prop_assert_eq!(decoded, value);
+    }
+}
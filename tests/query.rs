@@ -0,0 +1,160 @@
+#![cfg(feature = "query")]
+
+use regex::Regex;
+use tracing_serde_structured::{
+    builder::{SerializeAttributesBuilder, SerializeEventBuilder},
+    query::{Matcher, Query},
+    wire::TracingWire,
+    DebugRecord, SerializeId, SerializeLevel, SerializeValue,
+};
+
+fn id(id: u64) -> SerializeId {
+    SerializeId {
+        id: id.try_into().unwrap(),
+    }
+}
+
+fn event(target: &'static str, level: SerializeLevel) -> SerializeEventBuilder<'static> {
+    SerializeEventBuilder::new().target(target).level(level)
+}
+
+#[test]
+fn levels_compare_like_tracing() {
+    let warn = event("t", SerializeLevel::Warn).build();
+    let debug = event("t", SerializeLevel::Debug).build();
+
+    let query = Query::level(..=SerializeLevel::Warn);
+    assert!(query.matches(&warn));
+    assert!(!query.matches(&debug));
+
+    let query = Query::level(SerializeLevel::Info..=SerializeLevel::Debug);
+    assert!(!query.matches(&warn));
+    assert!(query.matches(&debug));
+}
+
+#[test]
+fn targets_match_globs() {
+    let matches = |pattern, target| {
+        Query::target(pattern).matches(&event(target, SerializeLevel::Info).build())
+    };
+    assert!(matches("app::*", "app::net"));
+    assert!(matches("app::*", "app::"));
+    assert!(matches("*::net::*", "app::net::tcp"));
+    assert!(matches("app::n?t", "app::net"));
+    assert!(matches("*", ""));
+    assert!(!matches("app::*", "application"));
+    assert!(!matches("app", "app::net"));
+    assert!(!matches("*::net", "app::net::tcp"));
+}
+
+#[test]
+fn fields_match_values() {
+    let event = event("t", SerializeLevel::Info)
+        .field("port", 80u64)
+        .field("host", "example.com")
+        .field(
+            "addr",
+            SerializeValue::Debug(DebugRecord::De("10.0.0.1".into())),
+        )
+        .build();
+
+    assert!(Query::field("port", 80u64).matches(&event));
+    assert!(Query::field("port", 80i64).matches(&event));
+    assert!(!Query::field("port", 81u64).matches(&event));
+    assert!(!Query::field("port", "80").matches(&event));
+    assert!(Query::field("host", "example.com").matches(&event));
+    assert!(Query::field("addr", "10.0.0.1").matches(&event));
+    assert!(!Query::field("missing", 1u64).matches(&event));
+
+    assert!(Query::field_matches("host", Regex::new(r"\.com$").unwrap()).matches(&event));
+    assert!(Query::field_matches("port", Regex::new("^8").unwrap()).matches(&event));
+    assert!(!Query::field_matches("host", Regex::new("^com").unwrap()).matches(&event));
+
+    assert!(Query::has_field("addr").matches(&event));
+    assert!(!Query::has_field("user").matches(&event));
+}
+
+#[test]
+fn queries_combine() {
+    let event = event("app::db", SerializeLevel::Error)
+        .name("query failed")
+        .build();
+
+    assert!(Query::all().matches(&event));
+    assert!(Query::target("app::*")
+        .and(Query::name("query *"))
+        .matches(&event));
+    assert!(!Query::target("app::*")
+        .and(Query::name("connect"))
+        .matches(&event));
+    assert!(Query::target("lib")
+        .or(Query::level(..=SerializeLevel::Error))
+        .matches(&event));
+    assert!(!(!Query::all()).matches(&event));
+    assert!(!Query::in_span("*").matches(&event));
+    assert!((!Query::in_span("*")).matches(&event));
+}
+
+fn span(id_: u64, name: &'static str, parent: Option<u64>) -> TracingWire<'static> {
+    let mut attributes = SerializeAttributesBuilder::new(name);
+    if let Some(parent) = parent {
+        attributes = attributes.parent(id(parent));
+    }
+    TracingWire::NewSpan {
+        id: id(id_),
+        attributes: attributes.build(),
+    }
+}
+
+fn named(name: &'static str) -> TracingWire<'static> {
+    TracingWire::Event(event("t", SerializeLevel::Info).name(name).build())
+}
+
+#[test]
+fn matchers_follow_span_ancestry() {
+    let stream = vec![
+        span(1, "request", None),
+        TracingWire::Enter(id(1)),
+        // Contextual parent: the entered span.
+        span(2, "db", None),
+        TracingWire::Enter(id(2)),
+        named("query"),
+        TracingWire::Exit(id(2)),
+        named("respond"),
+        TracingWire::Exit(id(1)),
+        named("idle"),
+        // Explicit parent, without entering anything.
+        TracingWire::Event(
+            event("t", SerializeLevel::Info)
+                .name("late")
+                .parent(id(2))
+                .build(),
+        ),
+        TracingWire::CloseSpan(id(2)),
+        TracingWire::CloseSpan(id(1)),
+        named("after"),
+    ];
+
+    let names = |query: Query| -> Vec<String> {
+        query
+            .filter(&stream)
+            .map(|msg| match msg {
+                TracingWire::Event(event) => event.metadata.name.as_str().to_owned(),
+                _ => unreachable!("only events match"),
+            })
+            .collect()
+    };
+    assert_eq!(
+        names(Query::in_span("request")),
+        ["query", "respond", "late"]
+    );
+    assert_eq!(names(Query::in_span("db")), ["query", "late"]);
+    assert_eq!(names(!Query::in_span("*")), ["idle", "after"]);
+    assert_eq!(names(Query::all()).len(), 5);
+
+    let mut matcher = Matcher::new(Query::in_span("request"));
+    assert!(!matcher.matches(&span(3, "request", None)));
+    assert!(!matcher.matches(&span(4, "child", Some(3))));
+    let event = event("t", SerializeLevel::Info).parent(id(4)).build();
+    assert!(matcher.matches_event(&event));
+}
@@ -0,0 +1,220 @@
+#![cfg(feature = "redact")]
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use regex::Regex;
+use serde::Serializer as _;
+use tracing::info;
+use tracing_core::{
+    span::{Attributes, Id, Record},
+    Event, Metadata, Subscriber,
+};
+use tracing_serde_structured::{
+    builder::SerializeEventBuilder,
+    redact::{Detector, Redactions, Redactor, REDACTED},
+    wire::TracingWire,
+    CowString, DebugRecord, SerdeMapVisitor, SerializeId, SerializeRecord, SerializeRecordFields,
+    SerializeValue,
+};
+
+fn detectors() -> Redactor {
+    Redactor::new()
+        .detect(Detector::Email)
+        .detect(Detector::CardNumber)
+}
+
+#[test]
+fn field_names_match_globs_and_regexes() {
+    let redactor = Redactor::new()
+        .field("user.*")
+        .field("pass?ord")
+        .field_matches(Regex::new("(?i)secret").unwrap());
+    assert!(redactor.redacts_field("user.email"));
+    assert!(redactor.redacts_field("password"));
+    assert!(redactor.redacts_field("api_SECRET_key"));
+    assert!(!redactor.redacts_field("username"));
+    assert!(!redactor.redacts_field("passwords"));
+}
+
+#[test]
+fn emails_are_detected() {
+    let redactor = detectors();
+    assert!(redactor.redacts_value("ferris@example.com"));
+    assert!(redactor.redacts_value("from <First.Last+tag@mail.example.co.uk>"));
+    assert!(!redactor.redacts_value("user@localhost"));
+    assert!(!redactor.redacts_value("@example.com"));
+    assert!(!redactor.redacts_value("no address here"));
+}
+
+#[test]
+fn card_numbers_are_detected() {
+    let redactor = detectors();
+    assert!(redactor.redacts_value("4111111111111111"));
+    assert!(redactor.redacts_value("card 4111 1111 1111 1111, exp 12/30"));
+    assert!(redactor.redacts_value("5500-0000-0000-0004"));
+    assert!(redactor.redacts_value("378282246310005"));
+    // Fails the Luhn check.
+    assert!(!redactor.redacts_value("4111111111111112"));
+    // Too short, and too long.
+    assert!(!redactor.redacts_value("411111111111"));
+    assert!(!redactor.redacts_value("41111111111111111111"));
+}
+
+#[test]
+fn fields_are_redacted_and_counted() {
+    let redactor = detectors()
+        .field("*.password")
+        .value_matches(Regex::new("^sk_").unwrap());
+    let mut event = SerializeEventBuilder::new()
+        .field("db.password", 1234u64)
+        .field("note", "contact ferris@example.com")
+        .field("card", 4111111111111111u64)
+        .field("key", "sk_live_abc")
+        .field(
+            "debug",
+            SerializeValue::Debug(DebugRecord::De("Some(\"a@b.io\")".into())),
+        )
+        .field("count", 4111u64)
+        .field("ok", true)
+        .build();
+    assert_eq!(redactor.redact_fields(&mut event.fields), 5);
+    assert_eq!(
+        redactor.redactions(),
+        Redactions {
+            by_name: 1,
+            by_value: 4,
+        }
+    );
+    assert_eq!(redactor.redactions().total(), 5);
+
+    let SerializeRecordFields::De(map) = &event.fields else {
+        panic!("redacted fields are deserialized fields");
+    };
+    let redacted: Vec<_> = map
+        .iter()
+        .filter(|(_, v)| **v == SerializeValue::from(REDACTED))
+        .map(|(k, _)| k.as_str())
+        .collect();
+    assert_eq!(redacted, ["card", "db.password", "debug", "key", "note"]);
+
+    // Redacted values are not redacted, or counted, again.
+    assert_eq!(redactor.redact_fields(&mut event.fields), 0);
+    assert_eq!(redactor.redactions().total(), 5);
+}
+
+#[test]
+fn messages_are_redacted() {
+    let redactor = Redactor::new().field("user");
+
+    let mut event: TracingWire<'_> =
+        TracingWire::Event(SerializeEventBuilder::new().field("user", "ferris").build());
+    assert_eq!(redactor.redact_message(&mut event), 1);
+
+    let mut values = BTreeMap::new();
+    values.insert(CowString::Borrowed("user"), "ferris".into());
+    let mut record: TracingWire<'_> = TracingWire::Record {
+        span: SerializeId {
+            id: 1.try_into().unwrap(),
+        },
+        values: SerializeRecord::De(values),
+    };
+    assert_eq!(redactor.redact_message(&mut record), 1);
+    let TracingWire::Record {
+        values: SerializeRecord::De(values),
+        ..
+    } = &record
+    else {
+        unreachable!()
+    };
+    assert_eq!(values[&CowString::Borrowed("user")], REDACTED.into());
+
+    let mut exit: TracingWire<'_> = TracingWire::Exit(SerializeId {
+        id: 1.try_into().unwrap(),
+    });
+    assert_eq!(redactor.redact_message(&mut exit), 0);
+}
+
+/// Serializes the fields of each event to JSON through a redacting visitor.
+struct RedactingSubscriber {
+    redactor: Redactor,
+    events: Mutex<Vec<serde_json::Value>>,
+}
+
+impl Subscriber for RedactingSubscriber {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, _: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut buf = Vec::new();
+        let mut serializer = serde_json::Serializer::new(&mut buf);
+        let map = serializer.serialize_map(None).unwrap();
+        let mut visitor = self.redactor.visitor(SerdeMapVisitor::new(map));
+        event.record(&mut visitor);
+        visitor.into_inner().finish().unwrap();
+        let json = serde_json::from_slice(&buf).unwrap();
+        self.events.lock().unwrap().push(json);
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+#[test]
+fn visitors_redact_live_events() {
+    let subscriber = Arc::new(RedactingSubscriber {
+        redactor: detectors().field("token"),
+        events: Mutex::new(Vec::new()),
+    });
+    tracing::subscriber::with_default(subscriber.clone(), || {
+        info!(
+            token = 42u64,
+            card = 4111111111111111u64,
+            email = ?Some("ferris@example.com"),
+            port = 8080u64,
+            up = true,
+            "sent to {}",
+            "ferris@example.com"
+        );
+        info!(peer = ?("10.0.0.2", 80), "connected");
+    });
+
+    let events = subscriber.events.lock().unwrap();
+    assert_eq!(
+        events[0],
+        serde_json::json!({
+            "token": { "Str": REDACTED },
+            "card": { "Str": REDACTED },
+            "email": { "Str": REDACTED },
+            "port": { "U64": 8080 },
+            "up": { "Bool": true },
+            "message": { "Str": REDACTED },
+        })
+    );
+    assert_eq!(
+        events[1],
+        serde_json::json!({
+            "peer": { "Debug": "(\"10.0.0.2\", 80)" },
+            "message": { "Debug": "connected" },
+        })
+    );
+    assert_eq!(
+        subscriber.redactor.redactions(),
+        Redactions {
+            by_name: 1,
+            by_value: 3,
+        }
+    );
+}
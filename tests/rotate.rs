@@ -0,0 +1,241 @@
+#![cfg(feature = "rotate")]
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use tracing_serde_structured::{rotate::RotatingFile, wire::TracingWire, SerializeId};
+
+/// An empty directory of its own for each test.
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("tss-rotate-{}-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+/// The names of the files in `dir`, in order.
+fn files(dir: &Path) -> Vec<String> {
+    let mut names: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    names.sort();
+    names
+}
+
+fn enter(id: u64) -> TracingWire<'static> {
+    TracingWire::Enter(SerializeId {
+        id: id.try_into().unwrap(),
+    })
+}
+
+#[test]
+fn files_rotate_by_size() {
+    let dir = temp_dir("size");
+    let mut files = RotatingFile::new(&dir, "trace").with_max_bytes(8);
+    assert!(files.path().is_none());
+    for i in 1..=3 {
+        files.write(i, enter(i).into()).unwrap();
+    }
+    files.flush().unwrap();
+    assert_eq!(files.path(), Some(dir.join("trace-000002.tss").as_path()));
+    assert_eq!(
+        self::files(&dir),
+        ["trace-000000.tss", "trace-000001.tss", "trace-000002.tss"]
+    );
+
+    // A file holds at least one message, however large its header is.
+    let mut files = RotatingFile::new(&dir, "big").with_max_bytes(1);
+    files.write(1, enter(1).into()).unwrap();
+    assert_eq!(files.path(), Some(dir.join("big-000000.tss").as_path()));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn files_rotate_by_age() {
+    let dir = temp_dir("age");
+    let mut files = RotatingFile::new(&dir, "trace").with_max_age(Duration::ZERO);
+    for i in 1..=3 {
+        files.write(i, enter(i).into()).unwrap();
+    }
+    assert_eq!(self::files(&dir).len(), 3);
+
+    let mut files = RotatingFile::new(&dir, "trace").with_max_age(Duration::from_secs(3600));
+    for i in 1..=3 {
+        files.write(i, enter(i).into()).unwrap();
+    }
+    assert_eq!(self::files(&dir).len(), 4);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn numbering_carries_on_from_existing_files() {
+    let dir = temp_dir("numbering");
+    fs::create_dir_all(&dir).unwrap();
+    for name in ["trace-000007.tss", "trace-notes.tss", "other-000042.tss"] {
+        fs::write(dir.join(name), []).unwrap();
+    }
+
+    let mut files = RotatingFile::new(&dir, "trace");
+    files.write(1, enter(1).into()).unwrap();
+    assert_eq!(files.path(), Some(dir.join("trace-000008.tss").as_path()));
+    files.rotate(2).unwrap();
+    assert_eq!(files.path(), Some(dir.join("trace-000009.tss").as_path()));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "host")]
+mod host {
+    use std::{
+        fs::File,
+        io::{BufReader, Cursor},
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc, Mutex,
+        },
+    };
+
+    use tracing::{info, info_span};
+    use tracing_core::{
+        span::{Attributes, Id, Record},
+        subscriber::Interest,
+        Event, Metadata, Subscriber,
+    };
+    use tracing_serde_structured::{
+        framing::LengthPrefixed,
+        host::{Decoder, Error},
+        time::TickRate,
+        wire::{SerializeAttributesRef, SerializeEventRef, SerializeMetadataId},
+        AsSerde, Live, SerializeMetadata,
+    };
+
+    use super::*;
+
+    /// Writes everything to rotating files, with one tick per message as the
+    /// timestamp.
+    struct FileSubscriber {
+        next_id: AtomicU64,
+        files: Mutex<(RotatingFile<LengthPrefixed>, u64)>,
+    }
+
+    impl FileSubscriber {
+        fn write(&self, msg: TracingWire<'_, Live>) {
+            let (files, ticks) = &mut *self.files.lock().unwrap();
+            *ticks += 1;
+            files.write(*ticks, msg).unwrap();
+        }
+    }
+
+    impl Subscriber for FileSubscriber {
+        fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+            self.write(TracingWire::DefineMetadata {
+                id: SerializeMetadataId::of(metadata),
+                metadata: SerializeMetadata::from_static(metadata),
+            });
+            Interest::always()
+        }
+
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+            let id = Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed) + 1);
+            self.write(TracingWire::NewSpanRef {
+                id: id.as_serde(),
+                attributes: SerializeAttributesRef::new(attrs),
+            });
+            id
+        }
+
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            self.write(TracingWire::EventRef(SerializeEventRef::new(event)));
+        }
+
+        fn enter(&self, id: &Id) {
+            self.write(TracingWire::Enter(id.as_serde()));
+        }
+
+        fn exit(&self, _: &Id) {}
+    }
+
+    #[test]
+    fn every_file_decodes_on_its_own() {
+        let dir = temp_dir("decode");
+        let files = RotatingFile::new(&dir, "trace")
+            .with_framing(LengthPrefixed::new())
+            .with_max_bytes(256);
+        let subscriber = Arc::new(FileSubscriber {
+            next_id: AtomicU64::new(0),
+            files: Mutex::new((files, 0)),
+        });
+        tracing::subscriber::with_default(subscriber.clone(), || {
+            for i in 0..20u64 {
+                let span = info_span!("request", i);
+                let _guard = span.enter();
+                info!(i, "handled");
+            }
+        });
+        subscriber.files.lock().unwrap().0.flush().unwrap();
+
+        let names = super::files(&dir);
+        assert!(names.len() > 2, "{names:?}");
+        let mut events = Vec::new();
+        for name in &names {
+            let file = BufReader::new(File::open(dir.join(name)).unwrap());
+            let mut messages =
+                Decoder::new(TickRate::MICROS).read_framed(file, LengthPrefixed::new());
+            for msg in messages.by_ref() {
+                match msg.unwrap().msg {
+                    TracingWire::NewSpan { attributes, .. } => {
+                        assert_eq!(attributes.metadata.name.as_str(), "request");
+                    }
+                    TracingWire::Event(event) => events.push(event.metadata.name.to_string()),
+                    TracingWire::Enter(_) => {}
+                    msg => panic!("unexpected message {msg:?}"),
+                }
+            }
+            assert!(messages.decoder().hello().is_some());
+        }
+        assert_eq!(events.len(), 20);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn files_carry_sync_points() {
+        let dir = temp_dir("sync");
+        let mut files = RotatingFile::new(&dir, "trace").with_sync_interval(2);
+        for i in 1..=5 {
+            files.write(i, enter(i).into()).unwrap();
+        }
+        files.flush().unwrap();
+        let stream = fs::read(files.path().unwrap()).unwrap();
+
+        // Skip the hello, the first sync frame and the first message, as if
+        // the start of the file was damaged.
+        let start = stream
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| **b == 0)
+            .nth(2)
+            .unwrap()
+            .0
+            + 1;
+        let results: Vec<_> = Decoder::mid_stream(TickRate::MICROS)
+            .read(Cursor::new(&stream[start..]))
+            .collect();
+        assert!(matches!(results[0], Err(Error::Delta)));
+        let ticks: Vec<_> = results[1..]
+            .iter()
+            .map(|msg| msg.as_ref().unwrap().ticks)
+            .collect();
+        assert_eq!(ticks, [3, 4, 5]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
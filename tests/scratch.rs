@@ -0,0 +1,26 @@
+use tracing_serde_structured::{
+    scratch::{SerializeScratch, SCRATCH_SIZE},
+    SerializeLevel,
+};
+
+#[test]
+fn buffers_are_reused() {
+    let first = SerializeScratch::take().unwrap();
+    assert_eq!(first.len(), SCRATCH_SIZE);
+    let ptr = first.as_ptr();
+    drop(first);
+
+    let again = SerializeScratch::take().unwrap();
+    assert_eq!(again.as_ptr(), ptr);
+
+    // A second buffer can be taken while the first is still in use.
+    let other = SerializeScratch::take().unwrap();
+    assert_ne!(other.as_ptr(), ptr);
+}
+
+#[test]
+fn buffers_hold_encoded_messages() {
+    let mut scratch = SerializeScratch::take().unwrap();
+    let used = postcard::to_slice(&SerializeLevel::Error, &mut scratch).unwrap();
+    assert_eq!(used, [4]);
+}
@@ -0,0 +1,14 @@
+#![cfg(all(feature = "semihosting", debug_assertions))]
+
+use tracing_serde_structured::{
+    embedded_io::FramedWriter,
+    semihosting::{Error, SemihostingWriter},
+};
+
+fn assert_write<W: embedded_io::Write<Error = Error>>() {}
+
+#[test]
+fn semihosting_writer_is_a_sink() {
+    assert_write::<SemihostingWriter>();
+    let _: Option<FramedWriter<SemihostingWriter, 64>> = None;
+}
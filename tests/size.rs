@@ -0,0 +1,92 @@
+//! Guards the size of the types that collectors keep many owned copies of,
+//! and of the messages meant to save space on the wire.
+
+use std::mem::size_of;
+
+use tracing_serde_structured::{CowString, SerializeValue};
+
+const WORD: usize = size_of::<usize>();
+
+#[test]
+#[cfg(not(any(feature = "compact_str", feature = "arc-str")))]
+fn owned_strings_take_three_words() {
+    // `String` keeps a niche in its capacity, so the enum needs no separate
+    // tag. A `Box<str>` would not make it any smaller than this.
+    assert_eq!(size_of::<CowString<'static>>(), 3 * WORD);
+    assert_eq!(size_of::<SerializeValue<'static>>(), 4 * WORD);
+}
+
+#[test]
+#[cfg(all(feature = "compact_str", not(feature = "arc-str")))]
+fn compact_strings_take_four_words() {
+    assert_eq!(size_of::<CowString<'static>>(), 4 * WORD);
+    assert_eq!(size_of::<SerializeValue<'static>>(), 5 * WORD);
+}
+
+#[test]
+#[cfg(feature = "arc-str")]
+fn shared_strings_take_three_words() {
+    // `arc-str` takes precedence over `compact_str`, and an `Arc<str>` fits
+    // in the space `CowString::Owned` already needs.
+    assert_eq!(size_of::<CowString<'static>>(), 3 * WORD);
+    assert_eq!(size_of::<SerializeValue<'static>>(), 4 * WORD);
+}
+
+#[test]
+#[cfg(feature = "postcard")]
+fn dictionary_events_leave_out_their_metadata() {
+    use tracing_serde_structured::{
+        wire::{SerializeEventRef, SerializeMetadataId},
+        SerializeEvent, SerializeFieldSet, SerializeId, SerializeLevel, SerializeMetadata,
+        SerializeRecordFields,
+    };
+
+    let fields = || -> SerializeRecordFields<'static> {
+        let mut fields = std::collections::BTreeMap::new();
+        fields.insert(
+            CowString::Borrowed("user"),
+            SerializeValue::Str("ferris".into()),
+        );
+        fields.insert(CowString::Borrowed("attempt"), SerializeValue::U64(3));
+        SerializeRecordFields::De(fields)
+    };
+    let metadata = SerializeMetadata {
+        name: CowString::Borrowed("event src/server/auth.rs:42"),
+        target: CowString::Borrowed("my_server::auth"),
+        level: SerializeLevel::Info,
+        module_path: Some(CowString::Borrowed("my_server::auth")),
+        file: Some(CowString::Borrowed("src/server/auth.rs")),
+        line: Some(42),
+        fields: SerializeFieldSet::De(vec![
+            CowString::Borrowed("user"),
+            CowString::Borrowed("attempt"),
+        ]),
+        is_span: false,
+        is_event: true,
+    };
+    // Ids are addresses, which take a few bytes more than small numbers.
+    let id = SerializeMetadataId {
+        id: 0x5555_5555_5000,
+    };
+    let parent = SerializeId {
+        id: std::num::NonZeroU64::new(7).unwrap(),
+    };
+
+    let metadata_len = postcard::to_allocvec(&metadata).unwrap().len();
+    let id_len = postcard::to_allocvec(&id).unwrap().len();
+    let inline = postcard::to_allocvec(&SerializeEvent {
+        fields: fields(),
+        metadata,
+        parent: Some(parent.clone()),
+    })
+    .unwrap();
+    let by_ref = postcard::to_allocvec(&SerializeEventRef {
+        fields: fields(),
+        metadata: id,
+        parent: Some(parent),
+    })
+    .unwrap();
+
+    assert_eq!(inline.len() - by_ref.len(), metadata_len - id_len);
+    assert!(by_ref.len() * 2 < inline.len());
+}
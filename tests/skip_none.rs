@@ -0,0 +1,58 @@
+#![cfg(all(feature = "skip-none", not(feature = "camel-case")))]
+
+use tracing_serde_structured::{
+    CowString, SerializeAttributes, SerializeFieldSet, SerializeLevel, SerializeMetadata,
+};
+
+fn attributes() -> SerializeAttributes<'static> {
+    SerializeAttributes {
+        metadata: SerializeMetadata {
+            name: CowString::Static("span"),
+            target: CowString::Static("my_crate"),
+            level: SerializeLevel::Info,
+            module_path: None,
+            file: None,
+            line: Some(7),
+            fields: SerializeFieldSet::De(vec![]),
+            is_span: true,
+            is_event: false,
+        },
+        parent: None,
+        is_root: false,
+    }
+}
+
+#[test]
+fn none_is_skipped_in_json() {
+    let json = serde_json::to_value(attributes()).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "metadata": {
+                "name": "span",
+                "target": "my_crate",
+                "level": "INFO",
+                "line": 7,
+                "fields": [],
+                "is_span": true,
+                "is_event": false,
+            },
+            "is_root": false,
+        })
+    );
+
+    let json = json.to_string();
+    let decoded: SerializeAttributes<'_> = serde_json::from_str(&json).unwrap();
+    assert!(decoded.parent.is_none());
+    assert!(decoded.metadata.file.is_none());
+    assert_eq!(decoded.metadata.line, Some(7));
+}
+
+#[test]
+fn postcard_keeps_every_field() {
+    let bytes = postcard::to_allocvec(&attributes()).unwrap();
+    let decoded: SerializeAttributes<'_> = postcard::from_bytes(&bytes).unwrap();
+    assert!(decoded.metadata.module_path.is_none());
+    assert_eq!(decoded.metadata.line, Some(7));
+    assert!(!decoded.is_root);
+}
@@ -0,0 +1,55 @@
+#![cfg(feature = "sorted-map")]
+
+use tracing_serde_structured::{sorted_map::SortedMap, CowString, SerializeValue};
+
+#[test]
+fn entries_are_kept_in_key_order() {
+    let mut map = SortedMap::<&str, u32, 4>::new();
+    assert_eq!(map.insert("b", 2), Ok(None));
+    assert_eq!(map.insert("d", 4), Ok(None));
+    assert_eq!(map.insert("a", 1), Ok(None));
+    assert_eq!(map.insert("b", 20), Ok(Some(2)));
+    assert_eq!(map.insert("c", 3), Ok(None));
+    assert_eq!(map.insert("e", 5), Err(("e", 5)));
+
+    assert_eq!(
+        map.keys().copied().collect::<Vec<_>>(),
+        ["a", "b", "c", "d"]
+    );
+    assert_eq!(map.get("b"), Some(&20));
+    assert_eq!(map.remove("a"), Some(1));
+    assert!(!map.contains_key("a"));
+    assert_eq!(map.insert("e", 5), Ok(None));
+}
+
+#[test]
+fn keys_are_found_regardless_of_representation() {
+    let owned = String::from("answer");
+    let mut map = SortedMap::<CowString<'_>, SerializeValue<'_>, 4>::new();
+    map.insert(CowString::Static("zzz"), SerializeValue::Bool(true))
+        .unwrap();
+    map.insert(CowString::Borrowed(&owned), SerializeValue::U64(42))
+        .unwrap();
+    assert!(map.contains_key(&CowString::Static("answer")));
+}
+
+#[test]
+fn serde_round_trip() {
+    let json = r#"{"b":{"U64":2},"a":{"Bool":true}}"#;
+    let map: SortedMap<CowString<'_>, SerializeValue<'_>, 2> = serde_json::from_str(json).unwrap();
+    assert_eq!(
+        serde_json::to_string(&map).unwrap(),
+        r#"{"a":{"Bool":true},"b":{"U64":2}}"#
+    );
+
+    let bytes = postcard::to_allocvec(&map).unwrap();
+    let decoded: SortedMap<CowString<'_>, SerializeValue<'_>, 2> =
+        postcard::from_bytes(&bytes).unwrap();
+    assert_eq!(
+        serde_json::to_string(&decoded).unwrap(),
+        serde_json::to_string(&map).unwrap()
+    );
+
+    let err = serde_json::from_str::<SortedMap<CowString<'_>, SerializeValue<'_>, 1>>(json);
+    assert!(err.is_err());
+}
@@ -0,0 +1,193 @@
+use std::time::Duration;
+
+use tracing_serde_structured::{
+    builder::{SerializeAttributesBuilder, SerializeEventBuilder},
+    span_store::{SpanStore, SpanStoreStats},
+    wire::TracingWire,
+    SerializeEvent, SerializeFieldSet, SerializeId, SerializeRecord, SerializeRecordFields,
+    SerializeValue,
+};
+
+fn id(id: u64) -> SerializeId {
+    SerializeId {
+        id: id.try_into().unwrap(),
+    }
+}
+
+fn new_span(id_: u64, name: &'static str) -> TracingWire<'static> {
+    TracingWire::NewSpan {
+        id: id(id_),
+        attributes: SerializeAttributesBuilder::new(name).build(),
+    }
+}
+
+fn record(span: u64, name: &'static str, value: u64) -> TracingWire<'static> {
+    let values: SerializeRecord<'static> = [(name.into(), SerializeValue::U64(value))]
+        .into_iter()
+        .collect();
+    TracingWire::Record {
+        span: id(span),
+        values,
+    }
+}
+
+fn push(store: &mut SpanStore, ms: u64, msgs: &[TracingWire<'_>]) {
+    for msg in msgs {
+        store.push(Duration::from_millis(ms), msg);
+    }
+}
+
+fn field(event: &SerializeEvent<'_>, name: &str) -> Option<u64> {
+    let SerializeRecordFields::De(fields) = &event.fields else {
+        panic!("enriched events have deserialized fields");
+    };
+    match fields.iter().find(|(key, _)| key.as_str() == name)?.1 {
+        SerializeValue::U64(value) => Some(*value),
+        other => panic!("unexpected value {other:?}"),
+    }
+}
+
+#[test]
+fn events_get_the_fields_of_their_spans() {
+    let mut store = SpanStore::new();
+    push(
+        &mut store,
+        0,
+        &[
+            new_span(1, "request"),
+            record(1, "request_id", 7),
+            record(1, "user", 1),
+            TracingWire::Enter(id(1)),
+            new_span(2, "query"),
+            record(2, "user", 2),
+            TracingWire::Enter(id(2)),
+        ],
+    );
+    assert_eq!(store.get(&id(2)).unwrap().parent(), Some(id(1)));
+
+    let event = SerializeEventBuilder::new().field("rows", 3u64).build();
+    let names: Vec<&str> = store.scope(&event).map(|span| span.name()).collect();
+    assert_eq!(names, ["query", "request"]);
+    let enriched = store.enrich(&event);
+    assert_eq!(field(&enriched, "rows"), Some(3));
+    assert_eq!(field(&enriched, "user"), Some(2));
+    assert_eq!(field(&enriched, "request_id"), Some(7));
+    let SerializeFieldSet::De(names) = &enriched.metadata.fields else {
+        panic!("enriched events have deserialized metadata");
+    };
+    assert!(names.iter().any(|name| name.as_str() == "request_id"));
+
+    // The event's own fields win, and an explicit parent is followed even
+    // after the span has been exited and closed.
+    push(
+        &mut store,
+        1,
+        &[
+            TracingWire::Exit(id(2)),
+            TracingWire::CloseSpan(id(2)),
+            TracingWire::Exit(id(1)),
+        ],
+    );
+    let event = SerializeEventBuilder::new()
+        .field("user", 9u64)
+        .parent(id(2))
+        .build();
+    let enriched = store.enrich(&event);
+    assert_eq!(field(&enriched, "user"), Some(9));
+    assert_eq!(field(&enriched, "request_id"), Some(7));
+    assert_eq!(
+        store.get(&id(2)).unwrap().closed(),
+        Some(Duration::from_millis(1))
+    );
+
+    let event = SerializeEventBuilder::new().build();
+    assert_eq!(store.scope(&event).count(), 0);
+    assert_eq!(store.enrich(&event), event);
+    assert_eq!(store.stats(), SpanStoreStats::default());
+}
+
+#[test]
+fn closed_spans_are_evicted_first() {
+    let mut store = SpanStore::new().with_capacity(3);
+    push(
+        &mut store,
+        0,
+        &[
+            new_span(1, "a"),
+            new_span(2, "b"),
+            new_span(3, "c"),
+            TracingWire::CloseSpan(id(3)),
+            TracingWire::CloseSpan(id(2)),
+            new_span(4, "d"),
+        ],
+    );
+    assert!(store.get(&id(3)).is_none());
+    assert_eq!(store.len(), 3);
+    assert_eq!(store.open(), 2);
+
+    // With no closed span left, the open span used least recently goes,
+    // which is not `a`, since it was just recorded on.
+    push(
+        &mut store,
+        0,
+        &[
+            TracingWire::CloseSpan(id(2)),
+            record(1, "x", 1),
+            new_span(5, "e"),
+            new_span(6, "f"),
+        ],
+    );
+    let names: Vec<&str> = [1, 4, 5, 6]
+        .into_iter()
+        .filter_map(|n| store.get(&id(n)))
+        .map(|span| span.name())
+        .collect();
+    assert_eq!(names, ["a", "e", "f"]);
+    assert_eq!(
+        store.stats(),
+        SpanStoreStats {
+            evicted: 3,
+            expired: 0,
+            evicted_open: 1,
+            misses: 0,
+        }
+    );
+}
+
+#[test]
+fn old_closed_spans_expire() {
+    let mut store = SpanStore::new().with_max_age(Duration::from_millis(10));
+    push(
+        &mut store,
+        0,
+        &[
+            new_span(1, "a"),
+            new_span(2, "b"),
+            TracingWire::CloseSpan(id(1)),
+        ],
+    );
+    push(&mut store, 5, &[TracingWire::CloseSpan(id(2))]);
+    push(&mut store, 11, &[record(2, "x", 1)]);
+    assert!(store.get(&id(1)).is_none());
+    assert_eq!(
+        store.get(&id(2)).unwrap().value("x"),
+        Some(&SerializeValue::U64(1))
+    );
+    push(&mut store, 16, &[record(2, "x", 2)]);
+    assert!(store.is_empty());
+
+    // Events about spans that are gone are passed through, and counted.
+    let event = SerializeEventBuilder::new().parent(id(2)).build();
+    assert_eq!(store.enrich(&event), event);
+    assert_eq!(
+        store.stats(),
+        SpanStoreStats {
+            evicted: 0,
+            expired: 2,
+            evicted_open: 0,
+            misses: 2,
+        }
+    );
+    store.reset_stats();
+    assert_eq!(store.stats(), SpanStoreStats::default());
+}
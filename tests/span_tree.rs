@@ -0,0 +1,166 @@
+use std::time::Duration;
+
+use tracing_serde_structured::{
+    builder::{SerializeAttributesBuilder, SerializeEventBuilder},
+    span_tree::{SpanRef, SpanTree, SpanTreeBuilder},
+    wire::TracingWire,
+    SerializeId, SerializeRecord, SerializeValue,
+};
+
+fn id(id: u64) -> SerializeId {
+    SerializeId {
+        id: id.try_into().unwrap(),
+    }
+}
+
+fn new_span(id_: u64, name: &'static str) -> TracingWire<'static> {
+    TracingWire::NewSpan {
+        id: id(id_),
+        attributes: SerializeAttributesBuilder::new(name).build(),
+    }
+}
+
+fn child(id_: u64, name: &'static str, parent: u64) -> TracingWire<'static> {
+    TracingWire::NewSpan {
+        id: id(id_),
+        attributes: SerializeAttributesBuilder::new(name)
+            .parent(id(parent))
+            .build(),
+    }
+}
+
+fn event(name: &'static str) -> TracingWire<'static> {
+    TracingWire::Event(SerializeEventBuilder::new().name(name).build())
+}
+
+/// Builds a tree from `messages`, one millisecond apart.
+fn build(messages: &[TracingWire<'static>]) -> SpanTree {
+    let mut builder = SpanTreeBuilder::new();
+    for (ms, msg) in messages.iter().enumerate() {
+        builder.push(Duration::from_millis(ms as u64), msg);
+    }
+    assert_eq!(builder.pending(), 0);
+    builder.finish()
+}
+
+fn names<'t>(spans: impl Iterator<Item = SpanRef<'t>>) -> Vec<&'t str> {
+    spans.map(|span| span.name()).collect()
+}
+
+#[test]
+fn nests_spans_by_context_and_explicit_parents() {
+    let tree = build(&[
+        new_span(1, "a"),
+        TracingWire::Enter(id(1)),
+        new_span(2, "b"),
+        event("in a"),
+        TracingWire::Exit(id(1)),
+        child(3, "c", 2),
+        new_span(4, "d"),
+        event("outside"),
+    ]);
+
+    assert_eq!(tree.len(), 4);
+    assert_eq!(names(tree.roots()), ["a", "d"]);
+    let a = tree.roots().next().unwrap();
+    assert_eq!(names(a.children()), ["b"]);
+    let b = a.children().next().unwrap();
+    let c = b.children().next().unwrap();
+    assert_eq!(c.name(), "c");
+    assert_eq!(names(c.ancestors()), ["b", "a"]);
+    assert_eq!(c.parent().unwrap().id(), &id(2));
+
+    assert_eq!(a.events().len(), 1);
+    assert_eq!(a.events()[0].event.metadata.name.as_str(), "in a");
+    assert_eq!(a.events()[0].since_start, Duration::from_millis(3));
+    assert_eq!(tree.events()[0].event.metadata.name.as_str(), "outside");
+}
+
+#[test]
+fn tracks_timing_and_values() {
+    let mut values = std::collections::BTreeMap::new();
+    values.insert("rows".into(), SerializeValue::U64(3));
+    let tree = build(&[
+        new_span(1, "a"),
+        TracingWire::Enter(id(1)),
+        TracingWire::Exit(id(1)),
+        TracingWire::Record {
+            span: id(1),
+            values: SerializeRecord::De(values),
+        },
+        TracingWire::Enter(id(1)),
+        TracingWire::Enter(id(1)),
+        TracingWire::Exit(id(1)),
+        TracingWire::Exit(id(1)),
+        TracingWire::CloseSpan(id(1)),
+        new_span(2, "open"),
+    ]);
+
+    let a = tree.roots().next().unwrap();
+    assert_eq!(a.created(), Duration::ZERO);
+    assert_eq!(a.closed(), Some(Duration::from_millis(8)));
+    assert_eq!(a.duration(), Some(Duration::from_millis(8)));
+    assert_eq!(a.entries(), 3);
+    // 1..2, then 4..7 until the outer exit.
+    assert_eq!(a.busy(), Duration::from_millis(4));
+    assert!(matches!(a.value("rows"), Some(SerializeValue::U64(3))));
+    assert!(a.value("missing").is_none());
+
+    let open = tree.roots().nth(1).unwrap();
+    assert_eq!(open.closed(), None);
+    assert_eq!(open.duration(), None);
+}
+
+#[test]
+fn reused_ids_get_new_nodes() {
+    let tree = build(&[
+        new_span(1, "first"),
+        TracingWire::CloseSpan(id(1)),
+        new_span(1, "second"),
+        TracingWire::Enter(id(1)),
+        event("e"),
+    ]);
+    assert_eq!(names(tree.roots()), ["first", "second"]);
+    let mut roots = tree.roots();
+    assert!(roots.next().unwrap().events().is_empty());
+    assert_eq!(roots.next().unwrap().events().len(), 1);
+}
+
+#[test]
+fn holds_messages_until_their_span_is_created() {
+    let mut builder = SpanTreeBuilder::new();
+    let ms = Duration::from_millis;
+    builder.push(ms(0), &child(2, "child", 1));
+    builder.push(ms(1), &TracingWire::Enter(id(1)));
+    builder.push(
+        ms(2),
+        &TracingWire::Event(SerializeEventBuilder::new().parent(id(1)).build()),
+    );
+    builder.push(ms(3), &TracingWire::Enter(id(9)));
+    assert_eq!(builder.pending(), 4);
+    assert!(builder.tree().is_empty());
+
+    builder.push(ms(4), &new_span(1, "parent"));
+    assert_eq!(builder.pending(), 1);
+
+    let tree = builder.finish();
+    let parent = tree.roots().next().unwrap();
+    assert_eq!(names(parent.children()), ["child"]);
+    assert_eq!(parent.children().next().unwrap().created(), ms(0));
+    assert_eq!(parent.entries(), 1);
+    assert_eq!(parent.events().len(), 1);
+}
+
+#[test]
+fn records_follows_from() {
+    let tree = build(&[
+        new_span(1, "cause"),
+        new_span(2, "effect"),
+        TracingWire::FollowsFrom {
+            span: id(2),
+            follows: id(1),
+        },
+    ]);
+    let effect = tree.spans().nth(1).unwrap();
+    assert_eq!(names(effect.follows_from()), ["cause"]);
+}
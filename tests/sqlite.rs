@@ -0,0 +1,163 @@
+#![cfg(feature = "sqlite")]
+
+use std::{
+    collections::BTreeMap,
+    num::NonZeroU64,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use rusqlite::Connection;
+use tracing_serde_structured::{
+    sqlite::SqliteSink, CowString, SerializeAttributes, SerializeEvent, SerializeFieldSet,
+    SerializeId, SerializeLevel, SerializeMetadata, SerializeRecord, SerializeRecordFields,
+    SerializeValue,
+};
+
+fn metadata(name: &'static str, is_span: bool) -> SerializeMetadata<'static> {
+    SerializeMetadata {
+        name: CowString::Borrowed(name),
+        target: CowString::Borrowed("my_crate"),
+        level: SerializeLevel::Info,
+        module_path: None,
+        file: None,
+        line: None,
+        fields: SerializeFieldSet::De(vec![]),
+        is_span,
+        is_event: !is_span,
+    }
+}
+
+fn id(id: u64) -> SerializeId {
+    SerializeId {
+        id: NonZeroU64::new(id).unwrap(),
+    }
+}
+
+#[test]
+fn stores_events_and_spans() {
+    let mut sink = SqliteSink::from_connection(Connection::open_in_memory().unwrap())
+        .unwrap()
+        .with_batch_size(2);
+    let at = |n| UNIX_EPOCH + Duration::from_nanos(n);
+
+    let attrs = SerializeAttributes {
+        metadata: metadata("request", true),
+        parent: None,
+        is_root: true,
+    };
+    let span_row = sink.record_new_span(at(1), &id(1), &attrs).unwrap();
+
+    let mut values = BTreeMap::new();
+    values.insert(
+        CowString::Borrowed("user"),
+        SerializeValue::Str("alice".into()),
+    );
+    sink.record_span_values(&id(1), &SerializeRecord::De(values))
+        .unwrap();
+
+    let mut fields = BTreeMap::new();
+    fields.insert(CowString::Borrowed("big"), SerializeValue::U64(u64::MAX));
+    fields.insert(CowString::Borrowed("ok"), SerializeValue::Bool(true));
+    let event = SerializeEvent {
+        fields: SerializeRecordFields::De(fields),
+        metadata: metadata("event", false),
+        parent: Some(id(1)),
+    };
+    let event_row = sink.record_event(at(2), &event).unwrap();
+    sink.record_close(at(3), &id(1)).unwrap();
+    sink.flush().unwrap();
+
+    let conn = sink.connection();
+    let (opened, closed, name): (i64, i64, String) = conn
+        .query_row(
+            "SELECT opened_at, closed_at, name FROM spans WHERE rowid = ?1",
+            [span_row],
+            |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)),
+        )
+        .unwrap();
+    assert_eq!((opened, closed, name.as_str()), (1, 3, "request"));
+
+    let user: String = conn
+        .query_row(
+            "SELECT value FROM fields WHERE owner = 'span' AND owner_row = ?1 AND name = 'user'",
+            [span_row],
+            |r| r.get(0),
+        )
+        .unwrap();
+    assert_eq!(user, "alice");
+
+    let (parent, level): (i64, String) = conn
+        .query_row(
+            "SELECT parent, level FROM events WHERE rowid = ?1",
+            [event_row],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!((parent, level.as_str()), (1, "INFO"));
+
+    let big: String = conn
+        .query_row(
+            "SELECT value FROM fields WHERE owner = 'event' AND name = 'big'",
+            [],
+            |r| r.get(0),
+        )
+        .unwrap();
+    assert_eq!(big, u64::MAX.to_string());
+}
+
+#[test]
+fn values_for_closed_spans_are_discarded() {
+    let mut sink = SqliteSink::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+    let attrs = SerializeAttributes {
+        metadata: metadata("once", true),
+        parent: None,
+        is_root: true,
+    };
+    sink.record_new_span(UNIX_EPOCH, &id(7), &attrs).unwrap();
+    sink.record_close(UNIX_EPOCH, &id(7)).unwrap();
+
+    let mut values = BTreeMap::new();
+    values.insert(CowString::Borrowed("late"), SerializeValue::I64(-1));
+    sink.record_span_values(&id(7), &SerializeRecord::De(values))
+        .unwrap();
+
+    let count: i64 = sink
+        .connection()
+        .query_row("SELECT COUNT(*) FROM fields", [], |r| r.get(0))
+        .unwrap();
+    assert_eq!(count, 0);
+}
+
+#[test]
+fn failed_events_are_rolled_back() {
+    let mut sink = SqliteSink::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+    sink.connection()
+        .execute_batch(
+            "CREATE TRIGGER no_bad_fields BEFORE INSERT ON fields WHEN NEW.name = 'bad'
+             BEGIN SELECT RAISE(ABORT, 'bad field'); END;",
+        )
+        .unwrap();
+    let event = |name| {
+        let mut fields = BTreeMap::new();
+        fields.insert(CowString::Borrowed("a"), SerializeValue::I64(1));
+        fields.insert(CowString::Borrowed(name), SerializeValue::I64(2));
+        SerializeEvent {
+            fields: SerializeRecordFields::De(fields),
+            metadata: metadata("event", false),
+            parent: None,
+        }
+    };
+
+    assert!(sink.record_event(UNIX_EPOCH, &event("bad")).is_err());
+    sink.record_event(UNIX_EPOCH, &event("good")).unwrap();
+    sink.flush().unwrap();
+
+    let count = |table| -> i64 {
+        sink.connection()
+            .query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |r| r.get(0))
+            .unwrap()
+    };
+    assert_eq!(count("events"), 1);
+    assert_eq!(count("fields"), 2);
+    assert!(sink.connection().is_autocommit());
+}
@@ -0,0 +1,60 @@
+#![cfg(feature = "strip-locations")]
+
+use tracing_serde_structured::{CowString, SerializeFieldSet, SerializeLevel, SerializeMetadata};
+
+fn metadata() -> SerializeMetadata<'static> {
+    SerializeMetadata {
+        name: CowString::Static("event"),
+        target: CowString::Static("my_crate"),
+        level: SerializeLevel::Info,
+        module_path: Some(CowString::Static("my_crate::net")),
+        file: Some(CowString::Static("src/main.rs")),
+        line: Some(7),
+        fields: SerializeFieldSet::De(vec![CowString::Static("message")]),
+        is_span: false,
+        is_event: true,
+    }
+}
+
+#[test]
+#[cfg(not(feature = "camel-case"))]
+fn locations_are_left_out_of_json() {
+    let json = serde_json::to_value(metadata()).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "name": "event",
+            "target": "my_crate",
+            "level": "INFO",
+            "fields": ["message"],
+            "is_span": false,
+            "is_event": true,
+        })
+    );
+
+    // Locations sent by a build without the feature are ignored.
+    let json = r#"{"name":"n","target":"t","level":"WARN","module_path":"m","file":"f","line":1,"fields":[],"is_span":true,"is_event":false}"#;
+    let decoded: SerializeMetadata<'_> = serde_json::from_str(json).unwrap();
+    assert!(decoded.module_path.is_none());
+    assert!(decoded.file.is_none());
+    assert!(decoded.line.is_none());
+}
+
+#[test]
+fn locations_are_left_out_of_postcard() {
+    let stripped = postcard::to_allocvec(&metadata()).unwrap();
+    let unlocated = SerializeMetadata {
+        module_path: None,
+        file: None,
+        line: None,
+        ..metadata()
+    };
+    // Not even the `None` tags are sent.
+    assert_eq!(stripped, postcard::to_allocvec(&unlocated).unwrap());
+    assert!(!stripped.windows(4).any(|w| w == b"src/"));
+
+    let decoded: SerializeMetadata<'_> = postcard::from_bytes(&stripped).unwrap();
+    assert_eq!(decoded.name.as_str(), "event");
+    assert!(decoded.file.is_none());
+    assert!(decoded.line.is_none());
+}
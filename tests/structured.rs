@@ -0,0 +1,179 @@
+#![cfg(feature = "structured-event")]
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use serde::Serialize;
+use serde_json::json;
+use tracing::Level;
+use tracing_core::{
+    span::{Attributes, Id, Record},
+    Event, Metadata, Subscriber,
+};
+#[cfg(all(tracing_unstable, feature = "valuable"))]
+use tracing_serde_structured::SerializeValue;
+use tracing_serde_structured::{
+    structured::{AsStructured, Structured},
+    structured_event, AsSerde,
+};
+
+/// Keeps each event as JSON, serialized while the event is live.
+#[derive(Default)]
+struct JsonSubscriber {
+    events: Mutex<Vec<serde_json::Value>>,
+}
+
+impl Subscriber for JsonSubscriber {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, _: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let json = serde_json::to_value(event.as_serde()).unwrap();
+        self.events.lock().unwrap().push(json["fields"].clone());
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+#[derive(Serialize)]
+struct Config {
+    name: &'static str,
+    ports: Vec<u16>,
+    verbose: bool,
+}
+
+fn capture(f: impl FnOnce()) -> Vec<serde_json::Value> {
+    let subscriber = Arc::new(JsonSubscriber::default());
+    tracing::subscriber::with_default(subscriber.clone(), f);
+    let events = std::mem::take(&mut *subscriber.events.lock().unwrap());
+    events
+}
+
+fn config() -> Config {
+    Config {
+        name: "edge",
+        ports: vec![80, 443],
+        verbose: false,
+    }
+}
+
+#[test]
+fn fields_keep_their_structure() {
+    let config = config();
+    let fields = capture(|| {
+        structured_event!(Level::INFO, cfg = &config, "loaded {}", config.name);
+        structured_event!(Level::DEBUG, cfg = &config, retries = &3);
+    });
+    assert_eq!(fields.len(), 2);
+    assert_eq!(fields[0]["message"], json!({ "Debug": "loaded edge" }));
+
+    let expected = json!({ "name": "edge", "ports": [80, 443], "verbose": false });
+    for event in &fields {
+        #[cfg(all(tracing_unstable, feature = "valuable"))]
+        {
+            assert_eq!(event["cfg"], json!({ "Tree": expected }));
+            let json = event["cfg"].to_string();
+            let value: SerializeValue<'_> = serde_json::from_str(&json).unwrap();
+            assert!(matches!(value, SerializeValue::Tree(_)));
+        }
+        #[cfg(not(all(tracing_unstable, feature = "valuable")))]
+        {
+            let debug = event["cfg"]["Debug"].as_str().unwrap();
+            assert_eq!(
+                serde_json::from_str::<serde_json::Value>(debug).unwrap(),
+                expected
+            );
+        }
+    }
+}
+
+#[test]
+fn structured_values_format_as_json() {
+    let value = Structured::new(&config());
+    assert_eq!(
+        format!("{value:?}"),
+        r#"{"name":"edge","ports":[80,443],"verbose":false}"#
+    );
+    assert_eq!(value.as_json()["ports"][1], 443);
+}
+
+#[derive(Serialize)]
+enum Change {
+    Started,
+    Moved(u8),
+    Resized(u16, u16),
+    Renamed { from: &'static str },
+}
+
+#[test]
+fn wrapped_values_keep_their_structure() {
+    let config = config();
+    let changes = [
+        Change::Started,
+        Change::Moved(1),
+        Change::Resized(2, 3),
+        Change::Renamed { from: "core" },
+    ];
+    let names = BTreeMap::from([(80u16, "http"), (443, "https")]);
+    let fields = capture(|| {
+        tracing::info!(
+            cfg = AsStructured(&config).as_field(),
+            name = AsStructured("edge").as_field(),
+            changes = AsStructured(&changes).as_field(),
+            names = AsStructured(&names).as_field(),
+        );
+    });
+
+    let expected = json!({ "name": "edge", "ports": [80, 443], "verbose": false });
+    #[cfg(all(tracing_unstable, feature = "valuable"))]
+    {
+        assert_eq!(fields[0]["cfg"], json!({ "Tree": expected }));
+        assert_eq!(fields[0]["name"], json!({ "Str": "edge" }));
+        let changes = serde_json::to_value(changes).unwrap();
+        assert_eq!(fields[0]["changes"], json!({ "Tree": changes }));
+        assert_eq!(
+            fields[0]["names"],
+            json!({ "Tree": { "80": "http", "443": "https" } })
+        );
+    }
+    #[cfg(not(all(tracing_unstable, feature = "valuable")))]
+    {
+        let debug = fields[0]["cfg"]["Debug"].as_str().unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(debug).unwrap(),
+            expected
+        );
+        assert_eq!(fields[0]["name"], json!({ "Debug": "\"edge\"" }));
+    }
+}
+
+#[test]
+fn wrapped_values_are_recorded_as_json() {
+    let config = config();
+    let fields = capture(|| {
+        tracing::info!(cfg = ?AsStructured(&config), name = %AsStructured("edge"));
+    });
+    let debug = fields[0]["cfg"]["Debug"].as_str().unwrap();
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(debug).unwrap(),
+        json!({ "name": "edge", "ports": [80, 443], "verbose": false })
+    );
+    assert_eq!(fields[0]["name"], json!({ "Debug": "\"edge\"" }));
+    assert_eq!(
+        AsStructured("multi\nline ✓").to_string(),
+        r#""multi\nline ✓""#
+    );
+}
@@ -0,0 +1,273 @@
+#![cfg(all(feature = "host", not(feature = "strip-locations")))]
+
+use std::sync::{Arc, Mutex};
+
+use tracing::{info, warn};
+use tracing_core::{
+    span::{Attributes, Id, Record},
+    subscriber::Interest,
+    Event, Metadata, Subscriber,
+};
+use tracing_serde_structured::{
+    host::Decoder,
+    time::TickRate,
+    wire::{
+        delta::DeltaEncoder,
+        table::{MetadataTable, StaticEntry, StaticStrings, StaticTable},
+        MetadataDictionary, SerializeEventRef, SerializeMetadataId, TracingWire,
+    },
+    SerializeLevel, SerializeMetadata,
+};
+
+/// Records the callsites of the events it sees, and sends events with their ids in
+/// `table`, if it has one.
+#[derive(Default)]
+struct TableSubscriber {
+    table: Option<StaticTable>,
+    strings: Option<StaticStrings>,
+    callsites: Mutex<Vec<&'static Metadata<'static>>>,
+    stream: Mutex<Vec<u8>>,
+    encoder: Mutex<DeltaEncoder>,
+}
+
+impl Subscriber for TableSubscriber {
+    fn register_callsite(&self, _: &'static Metadata<'static>) -> Interest {
+        Interest::always()
+    }
+
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, _: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut callsites = self.callsites.lock().unwrap();
+        if !callsites.contains(&event.metadata()) {
+            callsites.push(event.metadata());
+        }
+        let Some(table) = self.table else { return };
+        let metadata = table
+            .index_of(event.metadata())
+            .unwrap_or_else(|| SerializeMetadataId::of(event.metadata()));
+        let msg = match &self.strings {
+            Some(strings) => TracingWire::EventInterned(strings.event(event, metadata)),
+            None => TracingWire::EventRef(SerializeEventRef {
+                metadata,
+                ..SerializeEventRef::new(event)
+            }),
+        };
+        let frame = self.encoder.lock().unwrap().encode(0, msg);
+        let mut buf = [0u8; 256];
+        let bytes = postcard::to_slice_cobs(&frame, &mut buf).unwrap();
+        self.stream.lock().unwrap().extend_from_slice(bytes);
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+fn emit() {
+    for i in 0..2 {
+        info!(i, "first");
+        warn!(i, ok = true, "second");
+    }
+}
+
+fn record() -> Vec<&'static Metadata<'static>> {
+    let subscriber = Arc::new(TableSubscriber::default());
+    tracing::subscriber::with_default(subscriber.clone(), emit);
+    let callsites = subscriber.callsites.lock().unwrap();
+    callsites.clone()
+}
+
+fn table_of(callsites: &[&'static Metadata<'static>]) -> MetadataTable {
+    let mut dictionary = MetadataDictionary::new();
+    for metadata in callsites {
+        dictionary.insert(
+            SerializeMetadataId::of(metadata),
+            &SerializeMetadata::from_static(metadata),
+        );
+    }
+    MetadataTable::from_dictionary(&dictionary)
+}
+
+/// What the generated lookup holds, without going through a build script.
+fn static_table_of(callsites: &[&'static Metadata<'static>]) -> StaticTable {
+    let mut entries: Vec<_> = callsites
+        .iter()
+        .map(|m| StaticEntry {
+            file: m.file(),
+            line: m.line(),
+            name: m.name(),
+        })
+        .collect();
+    entries.sort_by_key(|e| (e.file, e.line, e.name));
+    entries.dedup();
+    StaticTable::new(Vec::leak(entries))
+}
+
+#[test]
+fn ids_are_dense_and_sorted() {
+    let callsites = record();
+    let table = table_of(&callsites);
+    assert_eq!(table.len(), 2);
+
+    let lines: Vec<_> = table.iter().map(|(id, m)| (id.id, m.line)).collect();
+    assert_eq!(lines[0].0, 0);
+    assert_eq!(lines[1].0, 1);
+    assert!(lines[0].1 < lines[1].1);
+
+    // Recording the callsites in a different order assigns the same ids.
+    let reversed: Vec<_> = callsites.iter().rev().copied().collect();
+    let again = table_of(&reversed);
+    assert_eq!(
+        serde_json::to_value(&table).unwrap(),
+        serde_json::to_value(&again).unwrap()
+    );
+}
+
+#[test]
+fn static_table_matches_table() {
+    let callsites = record();
+    let table = table_of(&callsites);
+    let lookup = static_table_of(&callsites);
+    assert_eq!(lookup.len(), table.len());
+
+    for metadata in &callsites {
+        let id = lookup.index_of(metadata).unwrap();
+        let entry = table.get(id).unwrap();
+        assert_eq!(entry.name.as_str(), metadata.name());
+        assert_eq!(entry.line, metadata.line());
+    }
+    assert!(table.get(SerializeMetadataId { id: 2 }).is_none());
+}
+
+#[test]
+fn json_round_trip() {
+    let table = table_of(&record());
+    let json = serde_json::to_string(&table).unwrap();
+    let parsed: MetadataTable = serde_json::from_str(&json).unwrap();
+    assert_eq!(serde_json::to_string(&parsed).unwrap(), json);
+}
+
+#[test]
+fn write_lookup() {
+    let callsites = record();
+    let table = table_of(&callsites);
+    let mut out = Vec::new();
+    table.write_lookup(&mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+
+    let lines: Vec<_> = out.lines().collect();
+    assert_eq!(lines.len(), 5);
+    assert_eq!(lines[1], "&[");
+    assert_eq!(lines[4], "]");
+    let (_, first) = table.iter().next().unwrap();
+    assert_eq!(
+        lines[2],
+        format!(
+            "    ::tracing_serde_structured::wire::table::StaticEntry {{ file: Some({:?}), line: Some({}), name: {:?} }},",
+            first.file.as_deref().unwrap(),
+            first.line.unwrap(),
+            first.name.as_str(),
+        )
+    );
+}
+
+#[test]
+fn host_resolves_ids_from_table() {
+    let callsites = record();
+    let table = table_of(&callsites);
+
+    let subscriber = Arc::new(TableSubscriber {
+        table: Some(static_table_of(&callsites)),
+        ..Default::default()
+    });
+    tracing::subscriber::with_default(subscriber.clone(), emit);
+    let mut stream = subscriber.stream.lock().unwrap().clone();
+
+    // The stream carries no definitions.
+    let mut decoder = Decoder::with_table(TickRate::NANOS, table);
+    let mut levels = Vec::new();
+    for frame in stream.split_mut(|b| *b == 0).filter(|f| !f.is_empty()) {
+        let msg = decoder.decode_frame(frame).unwrap().unwrap();
+        let TracingWire::Event(event) = msg.msg else {
+            panic!("expected an event, got {:?}", msg.msg);
+        };
+        levels.push(event.metadata.level);
+    }
+    assert_eq!(
+        levels,
+        [
+            SerializeLevel::Info,
+            SerializeLevel::Warn,
+            SerializeLevel::Info,
+            SerializeLevel::Warn
+        ]
+    );
+
+    // The table survives the device starting its definitions over.
+    decoder.clear_dictionary();
+    assert_eq!(decoder.dictionary().len(), 2);
+}
+
+/// What the generated string lookup holds.
+fn static_strings_of(table: &MetadataTable) -> StaticStrings {
+    let strings: Vec<&'static str> = table
+        .strings()
+        .into_iter()
+        .map(|s| &*String::leak(s.to_string()))
+        .collect();
+    StaticStrings::new(Vec::leak(strings))
+}
+
+#[test]
+fn strings() {
+    let table = table_of(&record());
+    assert_eq!(table.strings(), ["i", "message", "ok"]);
+
+    let lookup = static_strings_of(&table);
+    assert_eq!(lookup.id_of("message").unwrap().id, 1);
+    assert!(lookup.id_of("missing").is_none());
+
+    let mut out = Vec::new();
+    table.write_strings(&mut out).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "// Generated by tracing-serde-structured; do not edit.\n&[\n    \"i\",\n    \"message\",\n    \"ok\",\n]\n"
+    );
+}
+
+#[test]
+fn host_resolves_interned_from_table() {
+    let callsites = record();
+    let table = table_of(&callsites);
+
+    let subscriber = Arc::new(TableSubscriber {
+        table: Some(static_table_of(&callsites)),
+        strings: Some(static_strings_of(&table)),
+        ..Default::default()
+    });
+    tracing::subscriber::with_default(subscriber.clone(), emit);
+    let mut stream = subscriber.stream.lock().unwrap().clone();
+
+    let mut decoder = Decoder::with_table(TickRate::NANOS, table);
+    let mut events = Vec::new();
+    for frame in stream.split_mut(|b| *b == 0).filter(|f| !f.is_empty()) {
+        let msg = decoder.decode_frame(frame).unwrap().unwrap();
+        events.push(serde_json::to_value(&msg.msg).unwrap());
+    }
+    assert_eq!(events.len(), 4);
+    assert_eq!(
+        events[3]["Event"]["fields"],
+        serde_json::json!({ "i": { "I64": 1 }, "ok": { "Bool": true }, "message": { "Debug": "second" } })
+    );
+}
@@ -0,0 +1,115 @@
+#![cfg(all(feature = "serde-json", feature = "embedded-io"))]
+
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use tracing::info;
+use tracing_core::{
+    span::{Attributes, Id, Record},
+    Event, Metadata, Subscriber,
+};
+use tracing_serde_structured::{
+    embedded_io::{Error as FrameError, FramedWriter},
+    json_lines,
+    tee::{Error, Tee},
+    SerializeEvent, SerializeLevel,
+};
+
+type Out<const N: usize> = Tee<FramedWriter<Vec<u8>, N>, json_lines::Writer<Vec<u8>>>;
+
+struct TeeSubscriber<const N: usize> {
+    out: Mutex<Out<N>>,
+    errors: Mutex<Vec<Error<FrameError<core::convert::Infallible>, std::io::Error>>>,
+}
+
+impl<const N: usize> TeeSubscriber<N> {
+    fn new() -> Self {
+        Self {
+            out: Mutex::new(Tee::new(
+                FramedWriter::new(Vec::new()),
+                json_lines::Writer::new(Vec::new()),
+            )),
+            errors: Mutex::default(),
+        }
+    }
+}
+
+impl<const N: usize> Subscriber for TeeSubscriber<N> {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, _: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        if let Err(e) = self.out.lock().unwrap().write_event(event) {
+            self.errors.lock().unwrap().push(e);
+        }
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+/// Counts how often it is formatted.
+struct Counted(Arc<AtomicUsize>);
+
+impl fmt::Debug for Counted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fetch_add(1, Ordering::Relaxed);
+        f.write_str("counted")
+    }
+}
+
+#[test]
+fn events_are_visited_once_for_both_formats() {
+    let subscriber = Arc::new(TeeSubscriber::<256>::new());
+    let count = Arc::new(AtomicUsize::new(0));
+    tracing::subscriber::with_default(subscriber.clone(), || {
+        info!(value = ?Counted(count.clone()), attempt = 3u64, "sent");
+    });
+    assert_eq!(count.load(Ordering::Relaxed), 1);
+    assert!(subscriber.errors.lock().unwrap().is_empty());
+
+    let out = subscriber.out.lock().unwrap();
+    let (frames, lines) = out.get_ref();
+    let mut frame = frames.get_ref().clone();
+    assert_eq!(frame.pop(), Some(0));
+    let decoded = postcard::from_bytes_cobs::<SerializeEvent<'_>>(&mut frame).unwrap();
+    let line = std::str::from_utf8(lines.get_ref()).unwrap();
+    let parsed: SerializeEvent<'_> = serde_json::from_str(line.trim_end()).unwrap();
+    assert_eq!(decoded, parsed);
+    assert_eq!(decoded.metadata.level, SerializeLevel::Info);
+
+    let fields = serde_json::to_value(&parsed.fields).unwrap();
+    assert_eq!(fields["value"], serde_json::json!({ "Debug": "counted" }));
+    assert_eq!(fields["attempt"], serde_json::json!({ "U64": 3 }));
+}
+
+#[test]
+fn a_failing_sink_does_not_starve_the_other() {
+    let subscriber = Arc::new(TeeSubscriber::<4>::new());
+    tracing::subscriber::with_default(subscriber.clone(), || {
+        info!("too long for a four byte frame");
+    });
+    let errors = subscriber.errors.lock().unwrap();
+    assert!(matches!(errors[..], [Error::First(FrameError::Encode(_))]));
+    assert!(errors[0].to_string().starts_with("first sink: "));
+
+    let out = subscriber.out.lock().unwrap();
+    let (frames, lines) = out.get_ref();
+    assert!(frames.get_ref().is_empty());
+    assert!(lines.get_ref().ends_with(b"}\n"));
+}
@@ -0,0 +1,102 @@
+#![cfg(feature = "test-util")]
+
+#[cfg(not(feature = "strip-locations"))]
+use std::sync::{Arc, Mutex};
+
+#[cfg(not(feature = "strip-locations"))]
+use tracing::{info, info_span};
+use tracing_core::span::Id;
+#[cfg(not(feature = "strip-locations"))]
+use tracing_core::{
+    span::{Attributes, Record},
+    Event, Metadata, Subscriber,
+};
+#[cfg(not(feature = "strip-locations"))]
+use tracing_serde_structured::wire::SerializeEventRef;
+use tracing_serde_structured::{
+    builder::{SerializeAttributesBuilder, SerializeEventBuilder},
+    test_util::{assert_roundtrip_json, assert_roundtrip_postcard},
+    wire::TracingWire,
+    AsSerde, SerializeLevel, SerializeValue,
+};
+
+/// Checks every span, record and event it sees, as captured.
+#[cfg(not(feature = "strip-locations"))]
+#[derive(Default)]
+struct RoundTripSubscriber {
+    checked: Mutex<usize>,
+}
+
+#[cfg(not(feature = "strip-locations"))]
+impl Subscriber for RoundTripSubscriber {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+        assert_roundtrip_postcard(&attrs.as_serde());
+        assert_roundtrip_json(&attrs.as_serde());
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _: &Id, values: &Record<'_>) {
+        assert_roundtrip_postcard(&values.as_serde());
+        assert_roundtrip_json(&values.as_serde());
+        *self.checked.lock().unwrap() += 1;
+    }
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        assert_roundtrip_postcard(&event.as_serde());
+        assert_roundtrip_json(&event.as_serde());
+
+        let wire = TracingWire::EventRef(SerializeEventRef::new(event));
+        assert_roundtrip_postcard(&wire);
+        assert_roundtrip_json(&wire);
+        *self.checked.lock().unwrap() += 1;
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+#[test]
+#[cfg(not(feature = "strip-locations"))]
+fn captured_values_round_trip() {
+    let subscriber = Arc::new(RoundTripSubscriber::default());
+    tracing::subscriber::with_default(subscriber.clone(), || {
+        let span = info_span!("request", path = "/", status = tracing::field::Empty);
+        span.record("status", 200u64);
+        info!(user = "ferris", ratio = 0.5, ok = true, "logged {}", "in");
+    });
+    assert_eq!(*subscriber.checked.lock().unwrap(), 2);
+}
+
+#[test]
+fn built_values_round_trip() {
+    let event = SerializeEventBuilder::new()
+        .level(SerializeLevel::Warn)
+        .field("attempt", 3u64)
+        .parent(Id::from_u64(7).as_serde())
+        .build();
+    let attributes = SerializeAttributesBuilder::new("connect")
+        .field("peer")
+        .root()
+        .build();
+
+    assert_roundtrip_postcard(&event);
+    assert_roundtrip_json(&event);
+    assert_roundtrip_postcard(&attributes);
+    assert_roundtrip_json(&attributes);
+    assert_roundtrip_postcard(&TracingWire::<'_>::Event(event));
+    assert_roundtrip_json(&SerializeLevel::Error);
+    assert_roundtrip_json(&SerializeValue::Str("multi\nline".into()));
+}
+
+#[test]
+#[should_panic(expected = "postcard round trip changed the message")]
+fn unequal_round_trips_panic() {
+    assert_roundtrip_postcard(&SerializeValue::F64(f64::NAN));
+}
@@ -0,0 +1,52 @@
+use std::cell::Cell;
+
+use tracing_serde_structured::time::{FnSource, InstantSource, MonotonicSource, TickRate};
+
+#[test]
+fn converts_ticks_to_nanos() {
+    assert_eq!(TickRate::NANOS.to_nanos(1234), 1234);
+    assert_eq!(TickRate::MICROS.to_nanos(3), 3_000);
+    assert_eq!(TickRate::hz(32_768).to_nanos(16_384), 500_000_000);
+
+    // One tick every two seconds.
+    let slow = TickRate {
+        ticks: 1,
+        seconds: 2,
+    };
+    assert_eq!(slow.to_nanos(3), 6_000_000_000);
+
+    assert_eq!(TickRate::hz(1).to_nanos(u64::MAX), u64::MAX);
+    assert_eq!(TickRate::hz(0).to_nanos(1), u64::MAX);
+}
+
+#[test]
+fn fn_source_reads_the_counter() {
+    let ticks = Cell::new(10);
+    let clock = FnSource::new(TickRate::hz(1_000), || ticks.get());
+    assert_eq!(clock.now(), 10);
+    ticks.set(250);
+    assert_eq!(clock.now(), 250);
+    assert_eq!(clock.rate(), TickRate::hz(1_000));
+}
+
+#[test]
+fn instant_source_counts_up() {
+    let clock = InstantSource::new();
+    let a = clock.now();
+    std::thread::sleep(std::time::Duration::from_millis(1));
+    assert!(clock.now() >= a + 1_000_000);
+    assert_eq!(clock.rate(), TickRate::NANOS);
+}
+
+#[cfg(feature = "fugit")]
+#[test]
+fn fugit_source_takes_rate_from_type() {
+    use tracing_serde_structured::time::FugitSource;
+
+    let clock = FugitSource::new(|| fugit::TimerInstantU64::<32_768>::from_ticks(32_768 * 3));
+    assert_eq!(clock.rate(), TickRate::hz(32_768));
+    assert_eq!(clock.rate().to_nanos(clock.now()), 3_000_000_000);
+
+    let millis = FugitSource::new(|| fugit::Instant::<u64, 1, 1_000>::from_ticks(5));
+    assert_eq!(millis.rate().to_nanos(millis.now()), 5_000_000);
+}
@@ -0,0 +1,118 @@
+#![cfg(feature = "alloc")]
+
+use tracing_serde_structured::{
+    tree::{SerializeTree, TreeNode, MAX_DEPTH},
+    SerializeValue,
+};
+
+/// `{"name": "edge", "ports": [80, 443], "up": true}`
+fn config() -> SerializeTree<'static> {
+    SerializeTree::new(vec![
+        TreeNode::Map(3),
+        TreeNode::Str("name".into()),
+        TreeNode::Str("edge".into()),
+        TreeNode::Str("ports".into()),
+        TreeNode::Seq(2),
+        TreeNode::U64(80),
+        TreeNode::U64(443),
+        TreeNode::Str("up".into()),
+        TreeNode::Bool(true),
+    ])
+    .unwrap()
+}
+
+#[test]
+fn trees_round_trip() {
+    let value = SerializeValue::Tree(config());
+
+    let bytes = postcard::to_allocvec(&value).unwrap();
+    let decoded: SerializeValue<'_> = postcard::from_bytes(&bytes).unwrap();
+    assert_eq!(decoded, value);
+    assert!(decoded.is_borrowed());
+    assert!(decoded.to_owned().is_owned());
+
+    let json = serde_json::to_string(&value).unwrap();
+    assert_eq!(
+        json,
+        r#"{"Tree":{"name":"edge","ports":[80,443],"up":true}}"#
+    );
+    let decoded: SerializeValue<'_> = serde_json::from_str(&json).unwrap();
+    assert_eq!(decoded, value);
+
+    assert_eq!(
+        value.to_string(),
+        r#"{"name": "edge", "ports": [80, 443], "up": true}"#
+    );
+}
+
+#[test]
+fn incomplete_trees_are_rejected() {
+    assert!(SerializeTree::new(vec![]).is_none());
+    assert!(SerializeTree::new(vec![TreeNode::Map(1), TreeNode::Unit]).is_none());
+    assert!(SerializeTree::new(vec![TreeNode::Unit, TreeNode::Unit]).is_none());
+
+    let nodes = [TreeNode::Seq(2), TreeNode::Bool(false)];
+    let bytes = postcard::to_allocvec(&nodes).unwrap();
+    assert!(postcard::from_bytes::<SerializeTree<'_>>(&bytes).is_err());
+}
+
+/// `depth` sequences, each holding the next, around a unit.
+fn nested(depth: usize) -> Vec<TreeNode<'static>> {
+    let mut nodes = vec![TreeNode::Seq(1); depth];
+    nodes.push(TreeNode::Unit);
+    nodes
+}
+
+#[test]
+fn deep_trees_are_rejected() {
+    let tree = SerializeTree::new(nested(MAX_DEPTH)).unwrap();
+    let json = serde_json::to_string(&tree).unwrap();
+    assert_eq!(
+        serde_json::from_str::<SerializeTree<'_>>(&json).unwrap(),
+        tree
+    );
+    assert!(SerializeTree::new(nested(MAX_DEPTH + 1)).is_none());
+
+    // Deep enough to overflow the stack if it were written out or formatted.
+    let bytes = postcard::to_allocvec(&nested(20_000)).unwrap();
+    assert!(postcard::from_bytes::<SerializeTree<'_>>(&bytes).is_err());
+
+    let json = format!(
+        "{}null{}",
+        "[".repeat(MAX_DEPTH + 1),
+        "]".repeat(MAX_DEPTH + 1)
+    );
+    assert!(serde_json::from_str::<SerializeTree<'_>>(&json).is_err());
+}
+
+#[cfg(all(tracing_unstable, feature = "valuable"))]
+#[test]
+fn valuable_values_keep_their_structure() {
+    use valuable_crate::Valuable;
+
+    let value = SerializeValue::from_valuable((7u8, "seven", [-1i32, 1]).as_value());
+    let SerializeValue::Tree(tree) = &value else {
+        panic!("{value:?} is not a tree");
+    };
+    assert_eq!(
+        tree.nodes(),
+        [
+            TreeNode::Seq(3),
+            TreeNode::U64(7),
+            TreeNode::Str("seven".into()),
+            TreeNode::Seq(2),
+            TreeNode::I64(-1),
+            TreeNode::I64(1),
+        ]
+    );
+    assert!(value.is_owned());
+
+    assert_eq!(
+        SerializeValue::from_valuable(3u8.as_value()),
+        SerializeValue::U64(3)
+    );
+    assert_eq!(
+        SerializeValue::from_valuable(u128::MAX.as_value()),
+        SerializeValue::Str(u128::MAX.to_string().into())
+    );
+}
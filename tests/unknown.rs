@@ -0,0 +1,90 @@
+//! Messages and values of kinds added in later versions decode as `Unknown`
+//! wherever the format allows it.
+
+use tracing_serde_structured::{
+    wire::{delta::DeltaFrame, DropReason, TracingWire},
+    CowString, Detached, SerializeEvent, SerializeRecordFields, SerializeValue,
+};
+
+#[test]
+fn unknown_values_are_skipped_in_json() {
+    let event: SerializeEvent<'_> = serde_json::from_str(
+        r#"{"fields":{"a":{"Uuid":[1,2,{"x":null}]},"b":{"U64":2}},"metadata":{"name":"e","target":"t","level":"INFO","fields":["a","b"],"is_span":false,"is_event":true}}"#,
+    )
+    .unwrap();
+    let SerializeRecordFields::De(fields) = &event.fields else {
+        panic!("deserialized as Ser");
+    };
+    assert!(matches!(
+        fields[&CowString::Borrowed("a")],
+        SerializeValue::Unknown
+    ));
+    assert!(matches!(
+        fields[&CowString::Borrowed("b")],
+        SerializeValue::U64(2)
+    ));
+}
+
+#[test]
+fn unknown_values_fail_in_postcard() {
+    // A value with variant index 9, and a payload postcard cannot skip.
+    assert!(postcard::from_bytes::<SerializeValue<'_>>(&[9, 1, 2, 3]).is_err());
+}
+
+#[test]
+fn unknown_messages_decode() {
+    let msg: TracingWire<'_> = serde_json::from_str(r#"{"Heartbeat":{"uptime":5}}"#).unwrap();
+    assert!(matches!(msg, TracingWire::Unknown));
+
+    let msg: TracingWire<'_> = postcard::from_bytes(&[40, 1, 2, 3]).unwrap();
+    assert!(matches!(msg, TracingWire::Unknown));
+
+    let frame: DeltaFrame<'_> = postcard::from_bytes(&[4, 40, 1, 2, 3]).unwrap();
+    assert_eq!(frame.time, 2);
+    assert!(matches!(frame.msg, TracingWire::Unknown));
+}
+
+#[test]
+fn unknown_values_round_trip_as_unknown() {
+    let json = serde_json::to_string(&SerializeValue::Unknown).unwrap();
+    assert_eq!(json, r#""Unknown""#);
+    let value: SerializeValue<'_> = serde_json::from_str(&json).unwrap();
+    assert!(matches!(value, SerializeValue::Unknown));
+
+    let bytes = postcard::to_allocvec(&SerializeValue::Unknown).unwrap();
+    assert_eq!(bytes, [0xFF, 0xFF, 0xFF, 0xFF, 0x0F]);
+    let value: SerializeValue<'_> = postcard::from_bytes(&bytes).unwrap();
+    assert!(matches!(value, SerializeValue::Unknown));
+}
+
+#[test]
+fn unknown_messages_cannot_be_sent() {
+    assert!(serde_json::to_string(&TracingWire::<'_, Detached>::Unknown).is_err());
+    assert!(postcard::to_allocvec(&TracingWire::<'_, Detached>::Unknown).is_err());
+}
+
+#[test]
+fn unknown_drop_reasons_decode() {
+    let msg: TracingWire<'_> = serde_json::from_str(
+        r#"{"Dropped":{"count":2,"reason":"Sampled","first_seq":1,"last_seq":2}}"#,
+    )
+    .unwrap();
+    let TracingWire::Dropped(dropped) = msg else {
+        panic!("decoded {msg:?}");
+    };
+    assert_eq!(dropped.reason, DropReason::Unknown);
+    assert_eq!(dropped.count, 2);
+
+    // Reasons carry nothing, so postcard reads past them too.
+    let msg: TracingWire<'_> = postcard::from_bytes(&[17, 2, 9, 1, 2]).unwrap();
+    let TracingWire::Dropped(dropped) = msg else {
+        panic!("decoded {msg:?}");
+    };
+    assert_eq!(dropped.reason, DropReason::Unknown);
+    assert_eq!(dropped.last_seq, 2);
+
+    let bytes = postcard::to_allocvec(&DropReason::Unknown).unwrap();
+    assert_eq!(bytes, [0xFF, 0xFF, 0xFF, 0xFF, 0x0F]);
+    let reason: DropReason = postcard::from_bytes(&bytes).unwrap();
+    assert_eq!(reason, DropReason::Unknown);
+}
@@ -0,0 +1,61 @@
+#![cfg(all(feature = "wasm", target_arch = "wasm32"))]
+
+use js_sys::Reflect;
+use tracing::{info, info_span, warn};
+use tracing_serde_structured::{
+    builder::SerializeEventBuilder,
+    wasm::{from_js, to_js, ConsoleSubscriber},
+    wire::TracingWire,
+    Detached, SerializeEvent, SerializeId, SerializeLevel,
+};
+use wasm_bindgen::JsValue;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+fn get(object: &JsValue, key: &str) -> JsValue {
+    Reflect::get(object, &JsValue::from_str(key)).unwrap()
+}
+
+#[wasm_bindgen_test]
+fn events_become_plain_objects() {
+    let event = SerializeEventBuilder::new()
+        .target("app::net")
+        .level(SerializeLevel::Warn)
+        .field("attempt", 3u64)
+        .build();
+
+    let object = event.to_js_value().unwrap();
+    let metadata = get(&object, "metadata");
+    assert_eq!(get(&metadata, "level").as_string().unwrap(), "WARN");
+    assert_eq!(get(&metadata, "target").as_string().unwrap(), "app::net");
+    let attempt = get(&get(&object, "fields"), "attempt");
+    assert_eq!(get(&attempt, "U64").as_f64(), Some(3.0));
+
+    let back: SerializeEvent<'static> = from_js(object).unwrap();
+    assert_eq!(back, event);
+}
+
+#[wasm_bindgen_test]
+fn messages_round_trip() {
+    let msg = TracingWire::<'static, Detached>::Enter(SerializeId {
+        id: 7.try_into().unwrap(),
+    });
+    let back: TracingWire<'static> = from_js(msg.to_js_value().unwrap()).unwrap();
+    assert_eq!(back, msg);
+}
+
+#[wasm_bindgen_test]
+fn integers_too_large_for_numbers_fail() {
+    assert!(to_js(&u64::MAX).is_err());
+    let max_safe = (1u64 << 53) - 1;
+    assert_eq!(to_js(&max_safe).unwrap().as_f64(), Some(max_safe as f64));
+}
+
+#[wasm_bindgen_test]
+fn console_subscriber_logs_events() {
+    tracing::subscriber::with_default(ConsoleSubscriber::new(), || {
+        let span = info_span!("request", path = "/index.html");
+        let _guard = span.enter();
+        info!(user = "ferris", "logging in");
+        warn!(attempt = 2u64, "retrying");
+    });
+}
@@ -0,0 +1,122 @@
+#![cfg(all(feature = "websocket", not(target_arch = "wasm32")))]
+
+use std::{
+    net::{SocketAddr, TcpListener},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use tracing_serde_structured::{
+    websocket::{Error, WebSocketSink},
+    wire::SerializePipelineStats,
+};
+use tungstenite::Message;
+
+/// A server taking `connections` connections one after the other, and
+/// returning the messages received on each.
+fn server(listener: TcpListener, connections: usize) -> JoinHandle<Vec<Vec<String>>> {
+    thread::spawn(move || {
+        (0..connections)
+            .map(|_| {
+                let (stream, _) = listener.accept().unwrap();
+                let mut socket = tungstenite::accept(stream).unwrap();
+                let mut messages = Vec::new();
+                loop {
+                    match socket.read() {
+                        Ok(Message::Binary(bytes)) => {
+                            messages.push(postcard::from_bytes::<String>(&bytes).unwrap());
+                        }
+                        Ok(Message::Close(_)) | Err(_) => break,
+                        Ok(_) => {}
+                    }
+                }
+                messages
+            })
+            .collect()
+    })
+}
+
+fn listen() -> (TcpListener, String) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    (listener, url)
+}
+
+/// An address nothing listens on.
+fn unreachable() -> SocketAddr {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+}
+
+#[test]
+fn messages_reach_the_server() {
+    let (listener, url) = listen();
+    let server = server(listener, 1);
+
+    let mut sink = WebSocketSink::new(url);
+    assert_eq!(sink.connection(), None);
+    for msg in ["one", "two", "three"] {
+        sink.send(msg).unwrap();
+    }
+    assert_eq!(sink.connection(), Some(1));
+    assert_eq!(sink.queued(), 0);
+    sink.close();
+
+    assert_eq!(server.join().unwrap(), [["one", "two", "three"]]);
+    assert_eq!(sink.stats().sent, 3);
+}
+
+#[test]
+fn messages_wait_for_the_server() {
+    let addr = unreachable();
+    let mut sink =
+        WebSocketSink::new(format!("ws://{addr}")).with_backoff(Duration::ZERO, Duration::ZERO);
+    let err = sink.send("early").unwrap_err();
+    assert!(matches!(err, Error::WebSocket(_)));
+    assert!(err.to_string().starts_with("failed to send message: "));
+    assert_eq!(sink.queued(), 1);
+
+    let server = server(TcpListener::bind(addr).unwrap(), 1);
+    sink.send("late").unwrap();
+    sink.close();
+    assert_eq!(server.join().unwrap(), [["early", "late"]]);
+}
+
+#[test]
+fn full_queues_drop_the_oldest_messages() {
+    let mut sink = WebSocketSink::new(format!("ws://{}", unreachable()))
+        .with_capacity(2)
+        .with_backoff(Duration::from_secs(3600), Duration::from_secs(3600));
+    assert!(sink.send("one").is_err());
+    // No attempt is made to connect again until the backoff has passed.
+    sink.send("two").unwrap();
+    sink.send("three").unwrap();
+    assert_eq!(sink.queued(), 2);
+    assert_eq!(
+        sink.stats(),
+        SerializePipelineStats {
+            sent: 0,
+            dropped: 1,
+            overflows: 0,
+            errors: 0,
+        }
+    );
+}
+
+#[test]
+fn closed_sinks_connect_again() {
+    let (listener, url) = listen();
+    let server = server(listener, 2);
+
+    let mut sink = WebSocketSink::new(url);
+    sink.send("first").unwrap();
+    sink.close();
+    assert_eq!(sink.connection(), None);
+    sink.send("second").unwrap();
+    assert_eq!(sink.connection(), Some(2));
+    sink.close();
+
+    assert_eq!(server.join().unwrap(), [["first"], ["second"]]);
+}
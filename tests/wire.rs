@@ -0,0 +1,505 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+
+use tracing::{info, info_span};
+use tracing_core::{
+    span::{Attributes, Id, Record},
+    subscriber::Interest,
+    Event, Metadata, Subscriber,
+};
+use tracing_serde_structured::{
+    wire::{
+        delta::{DeltaDecoder, DeltaEncoder, DeltaFrame, SyncMarker},
+        DropReason, DropTracker, InternedFields, MetadataDictionary, SerializeAttributesRef,
+        SerializeDropped, SerializeEventInterned, SerializeEventRef, SerializeMetadataId, StringId,
+        StringTable, TracingWire,
+    },
+    AsSerde, CowString, Detached, Live, SerializeFieldSet, SerializeId, SerializeLevel,
+    SerializeMetadata, SerializeValue,
+};
+
+/// Sends metadata once per callsite, and everything else by reference.
+#[derive(Default)]
+struct RefSubscriber {
+    next_id: AtomicU64,
+    frames: Mutex<Vec<Vec<u8>>>,
+    full_event_sizes: Mutex<Vec<usize>>,
+}
+
+impl RefSubscriber {
+    fn send(&self, msg: &TracingWire<'_, Live>) -> usize {
+        let bytes = postcard::to_allocvec(msg).unwrap();
+        let len = bytes.len();
+        self.frames.lock().unwrap().push(bytes);
+        len
+    }
+}
+
+impl Subscriber for RefSubscriber {
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        self.send(&TracingWire::DefineMetadata {
+            id: SerializeMetadataId::of(metadata),
+            metadata: SerializeMetadata::from_static(metadata),
+        });
+        Interest::always()
+    }
+
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+        let id = Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed) + 1);
+        self.send(&TracingWire::NewSpanRef {
+            id: id.as_serde(),
+            attributes: SerializeAttributesRef::new(attrs),
+        });
+        id
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        self.send(&TracingWire::EventRef(SerializeEventRef::new(event)));
+
+        let full = postcard::to_allocvec(&TracingWire::Event(event.as_serde())).unwrap();
+        self.full_event_sizes.lock().unwrap().push(full.len());
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+#[test]
+fn events_by_reference_resolve_and_are_smaller() {
+    let subscriber = Arc::new(RefSubscriber::default());
+    tracing::subscriber::with_default(subscriber.clone(), || {
+        let span = info_span!("request");
+        let _guard = span.enter();
+        for i in 0..10u64 {
+            info!(i, "handled");
+        }
+    });
+
+    let frames = std::mem::take(&mut *subscriber.frames.lock().unwrap());
+    let full_sizes = std::mem::take(&mut *subscriber.full_event_sizes.lock().unwrap());
+
+    let mut dictionary = MetadataDictionary::new();
+    let mut ref_sizes = Vec::new();
+    let mut definitions = 0;
+    let mut spans = 0;
+    for frame in &frames {
+        let msg: TracingWire<'_> = postcard::from_bytes(frame).unwrap();
+        if dictionary.observe(&msg) {
+            definitions += frame.len();
+            continue;
+        }
+        match msg {
+            TracingWire::NewSpanRef { attributes, .. } => {
+                let attributes = dictionary.resolve_attributes(&attributes).unwrap();
+                assert_eq!(attributes.metadata.name.as_str(), "request");
+                spans += 1;
+            }
+            TracingWire::EventRef(event) => {
+                ref_sizes.push(frame.len());
+                let event = dictionary.resolve_event(event).unwrap();
+                assert_eq!(event.metadata.target.as_str(), "wire");
+            }
+            other => panic!("unexpected message {other:?}"),
+        }
+    }
+
+    assert_eq!(spans, 1);
+    assert_eq!(dictionary.len(), 2);
+    assert_eq!(ref_sizes.len(), 10);
+
+    // Each event by reference is a fraction of the full event, and sending
+    // the definitions once quickly pays for itself.
+    for (by_ref, full) in ref_sizes.iter().zip(&full_sizes) {
+        assert!(
+            by_ref * 2 < *full,
+            "{by_ref} bytes by reference, {full} in full"
+        );
+    }
+    let full: usize = full_sizes.iter().sum();
+    let by_ref: usize = ref_sizes.iter().sum::<usize>() + definitions;
+    // Without locations, the full events are small enough that ten of them
+    // only just pay for the definitions.
+    let ratio = if cfg!(feature = "strip-locations") {
+        1
+    } else {
+        2
+    };
+    assert!(
+        by_ref * ratio < full,
+        "{by_ref} bytes by reference, {full} in full"
+    );
+}
+
+#[test]
+fn unknown_metadata_is_returned() {
+    let dictionary = MetadataDictionary::new();
+    let event: SerializeEventRef<'_> = SerializeEventRef {
+        fields: tracing_serde_structured::SerializeRecordFields::De(Default::default()),
+        metadata: SerializeMetadataId { id: 1 },
+        parent: None,
+    };
+    let event = dictionary.resolve_event(event).unwrap_err();
+    assert_eq!(event.metadata, SerializeMetadataId { id: 1 });
+}
+
+/// Sends metadata and field names once, and events with interned names.
+#[derive(Default)]
+struct InternSubscriber {
+    table: Mutex<StringTable>,
+    frames: Mutex<Vec<Vec<u8>>>,
+    expected: Mutex<Vec<(serde_json::Value, usize)>>,
+}
+
+impl InternSubscriber {
+    fn send(&self, msg: &TracingWire<'_, Live>) -> usize {
+        let bytes = postcard::to_allocvec(msg).unwrap();
+        let len = bytes.len();
+        self.frames.lock().unwrap().push(bytes);
+        len
+    }
+}
+
+impl Subscriber for InternSubscriber {
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        self.send(&TracingWire::DefineMetadata {
+            id: SerializeMetadataId::of(metadata),
+            metadata: SerializeMetadata::from_static(metadata),
+        });
+        Interest::always()
+    }
+
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, _: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut table = self.table.lock().unwrap();
+        let interned = table.event(event, |define| {
+            self.send(&define.into());
+        });
+        self.send(&TracingWire::EventInterned(interned));
+
+        let by_ref = postcard::to_allocvec(&SerializeEventRef::new(event)).unwrap();
+        let json = serde_json::to_value(event.as_serde()).unwrap();
+        self.expected.lock().unwrap().push((json, by_ref.len()));
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+#[test]
+fn interned_field_names_resolve() {
+    let subscriber = Arc::new(InternSubscriber::default());
+    tracing::subscriber::with_default(subscriber.clone(), || {
+        for attempt in 0..3u64 {
+            info!(attempt, user = "ferris", "logging in");
+        }
+        info!(attempt = 4u64, "giving up");
+    });
+
+    let frames = std::mem::take(&mut *subscriber.frames.lock().unwrap());
+    let expected = std::mem::take(&mut *subscriber.expected.lock().unwrap());
+
+    let mut dictionary = MetadataDictionary::new();
+    let mut strings = 0;
+    let mut events = Vec::new();
+    for frame in &frames {
+        let msg: TracingWire<'_> = postcard::from_bytes(frame).unwrap();
+        if let TracingWire::DefineString { .. } = msg {
+            strings += 1;
+        }
+        if dictionary.observe(&msg) {
+            continue;
+        }
+        let TracingWire::EventInterned(event) = msg else {
+            panic!("unexpected message {msg:?}");
+        };
+        let event = dictionary.resolve_interned(event).unwrap();
+        events.push((serde_json::to_value(&event).unwrap(), frame.len()));
+    }
+
+    // "message", "attempt" and "user" are each defined once.
+    assert_eq!(strings, 3);
+    let mut names: Vec<_> = (0..3)
+        .map(|id| dictionary.string(StringId { id }).unwrap())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["attempt", "message", "user"]);
+    assert_eq!(events.len(), expected.len());
+    for ((json, interned), (expected, by_ref)) in events.iter().zip(&expected) {
+        assert_eq!(json, expected);
+        assert!(
+            interned < by_ref,
+            "{interned} bytes interned, {by_ref} by reference"
+        );
+    }
+}
+
+#[test]
+fn undefined_strings_are_returned() {
+    let mut table = StringTable::new();
+    let (id, define) = table.intern("answer");
+    assert!(matches!(define, Some(TracingWire::DefineString { .. })));
+    let (again, define) = table.intern("answer");
+    assert_eq!(again, id);
+    assert!(define.is_none());
+
+    let metadata_id = SerializeMetadataId { id: 1 };
+    let mut dictionary = MetadataDictionary::new();
+    dictionary.insert(
+        metadata_id,
+        &SerializeMetadata {
+            name: CowString::Static("event"),
+            target: CowString::Static("wire"),
+            level: SerializeLevel::Info,
+            module_path: None,
+            file: None,
+            line: None,
+            fields: SerializeFieldSet::De(vec![CowString::Static("answer")]),
+            is_span: false,
+            is_event: true,
+        },
+    );
+
+    let event = || -> SerializeEventInterned<'_> {
+        let mut fields = std::collections::BTreeMap::new();
+        fields.insert(id, SerializeValue::U64(42));
+        SerializeEventInterned {
+            fields: InternedFields::De(fields),
+            metadata: metadata_id,
+            parent: None,
+        }
+    };
+    assert!(dictionary.resolve_interned(event()).is_err());
+
+    dictionary.insert_string(id, &CowString::Static("answer"));
+    let resolved = dictionary.resolve_interned(event()).unwrap();
+    assert_eq!(
+        serde_json::to_value(&resolved.fields).unwrap(),
+        serde_json::json!({ "answer": { "U64": 42 } })
+    );
+}
+
+fn span(id: u64) -> SerializeId {
+    Id::from_u64(id).as_serde()
+}
+
+#[test]
+fn delta_frames_round_trip() {
+    let base = 1 << 40;
+    let messages = || {
+        vec![
+            TracingWire::NewSpanRef {
+                id: span(base),
+                attributes: SerializeAttributesRef {
+                    metadata: SerializeMetadataId { id: 1 },
+                    parent: None,
+                    is_root: true,
+                },
+            },
+            TracingWire::Enter(span(base)),
+            TracingWire::NewSpanRef {
+                id: span(base + 1),
+                attributes: SerializeAttributesRef {
+                    metadata: SerializeMetadataId { id: 2 },
+                    parent: Some(span(base)),
+                    is_root: false,
+                },
+            },
+            TracingWire::FollowsFrom {
+                span: span(base + 1),
+                follows: span(base - 7),
+            },
+            TracingWire::Exit(span(base)),
+            TracingWire::CloseSpan(span(base)),
+        ]
+    };
+    let timestamps = [
+        1_000_000_000u64,
+        1_000_000_250,
+        1_000_001_000,
+        1_000_001_000,
+        999_999_000,
+        1_000_002_000,
+    ];
+
+    let mut encoder = DeltaEncoder::new();
+    let mut decoder = DeltaDecoder::new();
+    let mut sizes = Vec::new();
+    for (msg, timestamp) in messages().into_iter().zip(timestamps) {
+        let frame = encoder.encode(timestamp, msg);
+        let bytes = postcard::to_allocvec(&frame).unwrap();
+        sizes.push(bytes.len());
+
+        let frame: DeltaFrame<'_> = postcard::from_bytes(&bytes).unwrap();
+        let (decoded_time, decoded) = decoder.decode(frame).unwrap();
+        assert_eq!(decoded_time, timestamp);
+        let expected = &messages()[sizes.len() - 1];
+        assert_eq!(format!("{decoded:?}"), format!("{expected:?}"));
+    }
+
+    // After the first message, ids and timestamps each fit in two bytes.
+    let plain: Vec<_> = messages()
+        .iter()
+        .map(|msg| postcard::to_allocvec(msg).unwrap().len())
+        .collect();
+    for (delta, plain) in sizes.iter().zip(&plain).skip(1) {
+        assert!(delta < plain, "{delta} bytes delta encoded, {plain} plain");
+    }
+}
+
+#[test]
+fn delta_decoder_rejects_zero_ids() {
+    let mut decoder = DeltaDecoder::new();
+    let frame = |id| DeltaFrame {
+        time: 0,
+        msg: TracingWire::<Detached>::Enter(span(id)),
+    };
+    // +5, encoded as zigzag(5) + 1.
+    let (_, msg) = decoder.decode(frame(11)).unwrap();
+    assert!(matches!(msg, TracingWire::Enter(id) if id.id.get() == 5));
+    // -5, which would make the id zero.
+    assert!(decoder.decode(frame(10)).is_none());
+    // The decoder is unchanged by the rejected frame.
+    let (_, msg) = decoder.decode(frame(3)).unwrap();
+    assert!(matches!(msg, TracingWire::Enter(id) if id.id.get() == 6));
+}
+
+#[test]
+fn sync_frames_let_decoders_join_mid_stream() {
+    let mut encoder = DeltaEncoder::new();
+    encoder.set_sync_interval(Some(3));
+    encoder.set_generation(7);
+
+    let mut frames = Vec::new();
+    for i in 0..8u64 {
+        let frame =
+            encoder.encode_with_sync(100 + i * 10, TracingWire::Enter(span(50 + i)), |sync| {
+                frames.push(postcard::to_allocvec(&sync).unwrap());
+            });
+        frames.push(postcard::to_allocvec(&frame).unwrap());
+    }
+    let is_sync = |bytes: &Vec<u8>| {
+        let frame: DeltaFrame<'_> = postcard::from_bytes(bytes).unwrap();
+        matches!(frame.msg, TracingWire::Sync(_))
+    };
+    let syncs: Vec<_> = frames.iter().map(is_sync).collect();
+    assert_eq!(
+        syncs,
+        [true, false, false, false, true, false, false, false, true, false, false]
+    );
+
+    // Join after the first sync frame: everything up to the next one is
+    // rejected, and everything after it decodes.
+    let mut decoder = DeltaDecoder::mid_stream();
+    let mut decoded = Vec::new();
+    for bytes in &frames[2..] {
+        let frame: DeltaFrame<'_> = postcard::from_bytes(bytes).unwrap();
+        match decoder.decode(frame) {
+            Some((time, TracingWire::Enter(id))) => decoded.push((time, id.id.get())),
+            Some((_, TracingWire::Sync(marker))) => {
+                assert_eq!(marker.magic, SyncMarker::MAGIC);
+                assert_eq!(marker.generation, 7);
+            }
+            Some((_, msg)) => panic!("unexpected message {msg:?}"),
+            None => assert!(decoded.is_empty()),
+        }
+    }
+    assert_eq!(
+        decoded,
+        [(130, 53), (140, 54), (150, 55), (160, 56), (170, 57)]
+    );
+    assert_eq!(decoder.seq(), frames.len() as u32);
+}
+
+#[test]
+fn sync_frames_need_the_magic() {
+    let mut decoder = DeltaDecoder::mid_stream();
+    let mut marker = SyncMarker {
+        magic: 0,
+        seq: 0,
+        generation: 0,
+        time: 10,
+        span: 10,
+    };
+    let frame = |marker: &SyncMarker| DeltaFrame {
+        time: 0,
+        msg: TracingWire::<Detached>::Sync(marker.clone()),
+    };
+    assert!(decoder.decode(frame(&marker)).is_none());
+    marker.magic = SyncMarker::MAGIC;
+    assert_eq!(decoder.decode(frame(&marker)).unwrap().0, 10);
+    assert_eq!(decoder.last_sync(), Some(&marker));
+}
+
+#[test]
+fn drop_trackers_report_each_reason_in_order() {
+    let mut drops = DropTracker::new();
+    assert!(drops.is_empty());
+    drops.record(10, DropReason::Oversize);
+    drops.record(4, DropReason::RateLimited);
+    drops.record(11, DropReason::RateLimited);
+    drops.record(12, DropReason::Oversize);
+    assert!(!drops.is_empty());
+
+    let notices: Vec<_> = std::iter::from_fn(|| drops.take()).collect();
+    assert_eq!(
+        notices,
+        [
+            SerializeDropped {
+                count: 2,
+                reason: DropReason::RateLimited,
+                first_seq: 4,
+                last_seq: 11,
+            },
+            SerializeDropped {
+                count: 2,
+                reason: DropReason::Oversize,
+                first_seq: 10,
+                last_seq: 12,
+            },
+        ]
+    );
+    assert!(drops.is_empty());
+
+    // Taken notices are forgotten, and counting starts again.
+    drops.record(13, DropReason::Oversize);
+    assert_eq!(drops.take().unwrap().count, 1);
+}
+
+#[test]
+fn drop_notices_survive_delta_encoding() {
+    let dropped = SerializeDropped {
+        count: 3,
+        reason: DropReason::QueueFull,
+        first_seq: 100,
+        last_seq: 102,
+    };
+    let frame = DeltaEncoder::new().encode(5, TracingWire::Dropped(dropped));
+    let bytes = postcard::to_allocvec(&frame).unwrap();
+    let frame: DeltaFrame<'_> = postcard::from_bytes(&bytes).unwrap();
+    let (time, msg) = DeltaDecoder::new().decode(frame).unwrap();
+    assert_eq!(time, 5);
+    assert_eq!(msg, TracingWire::<Detached>::Dropped(dropped));
+}
@@ -0,0 +1,152 @@
+//! Checks that serializing the borrowed forms of events, attributes and
+//! records never touches the heap.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    sync::{Arc, Mutex},
+};
+
+use tracing::{info, info_span};
+use tracing_core::{
+    span::{Attributes, Id, Record},
+    Event, Metadata, Subscriber,
+};
+use tracing_serde_structured::{
+    wire::{SerializeAttributesRef, SerializeEventRef, TracingWire},
+    AsSerde, Live,
+};
+
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(Cell::get)
+}
+
+/// Serializes `value` as postcard and as JSON into fixed buffers, returning
+/// the number of allocations made.
+fn count<T: serde::Serialize>(value: impl FnOnce() -> T) -> usize {
+    let mut postcard = [0u8; 1024];
+    let mut json = [0u8; 4096];
+
+    let before = allocations();
+    let value = value();
+    postcard::to_slice(&value, &mut postcard).unwrap();
+    serde_json::to_writer(&mut json[..], &value).unwrap();
+    allocations() - before
+}
+
+#[derive(Default)]
+struct CountingSubscriber {
+    counts: Mutex<Vec<(&'static str, usize)>>,
+}
+
+impl CountingSubscriber {
+    fn push(&self, kind: &'static str, made: usize) {
+        self.counts.lock().unwrap().push((kind, made));
+    }
+}
+
+impl Subscriber for CountingSubscriber {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+        self.push("attributes", count(|| attrs.as_serde()));
+        self.push(
+            "attributes ref",
+            count::<TracingWire<'_, Live>>(|| TracingWire::NewSpanRef {
+                id: Id::from_u64(1).as_serde(),
+                attributes: SerializeAttributesRef::new(attrs),
+            }),
+        );
+        self.push("metadata", count(|| attrs.metadata().as_serde()));
+        Id::from_u64(1)
+    }
+
+    fn record(&self, id: &Id, values: &Record<'_>) {
+        self.push(
+            "record",
+            count(|| TracingWire::Record {
+                span: id.as_serde(),
+                values: values.as_serde(),
+            }),
+        );
+    }
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        self.push("event", count(|| event.as_serde()));
+        self.push(
+            "event ref",
+            count(|| TracingWire::EventRef(SerializeEventRef::new(event))),
+        );
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+#[derive(Debug)]
+#[allow(dead_code)]
+struct Request {
+    method: &'static str,
+    status: u16,
+}
+
+#[test]
+fn serializing_borrowed_forms_does_not_allocate() {
+    let subscriber = Arc::new(CountingSubscriber::default());
+    tracing::subscriber::with_default(subscriber.clone(), || {
+        let span = info_span!("handle", path = "/index.html", attempt = 1u64, done = false);
+        span.record("done", true);
+        info!(
+            parent: &span,
+            user = "ferris \"the crab\"",
+            latency = 1.5,
+            offset = -3i64,
+            request = ?Request { method: "GET", status: 200 },
+            "served {} in {}ms",
+            "/index.html",
+            2,
+        );
+    });
+
+    let counts = subscriber.counts.lock().unwrap();
+    let kinds: Vec<_> = counts.iter().map(|(kind, _)| *kind).collect();
+    assert_eq!(
+        kinds,
+        [
+            "attributes",
+            "attributes ref",
+            "metadata",
+            "record",
+            "event",
+            "event ref"
+        ]
+    );
+    for (kind, made) in counts.iter() {
+        assert_eq!(*made, 0, "serializing {kind} allocated");
+    }
+}